@@ -18,6 +18,25 @@ pub enum ApiBind {
 pub trait ApiStore: Send + Sync {
     async fn query_json(&self, sql: &str, binds: Vec<ApiBind>) -> Result<Vec<Value>>;
     async fn execute(&self, sql: &str, binds: Vec<ApiBind>) -> Result<usize>;
+
+    /// Runs `statements` in one transaction, returning the affected rows of
+    /// each; nothing is applied when one fails. Used to write a state
+    /// change and its outbox events together.
+    async fn execute_batch(&self, statements: Vec<(String, Vec<ApiBind>)>) -> Result<Vec<usize>> {
+        let _ = statements;
+        Err(anyhow!("transactions not supported by this api store"))
+    }
+
+    /// Round-trip to the database, used by `/healthz/deep`.
+    async fn ping(&self) -> Result<()> {
+        self.query_json("SELECT to_jsonb(1) as value", vec![]).await?;
+        Ok(())
+    }
+
+    /// Connection pool gauges for `/metrics`, when the store has a pool.
+    fn pool_stats(&self) -> Option<Value> {
+        None
+    }
 }
 
 pub struct NoopApiStore;
@@ -31,6 +50,11 @@ impl ApiStore for NoopApiStore {
     async fn execute(&self, _sql: &str, _binds: Vec<ApiBind>) -> Result<usize> {
         Err(anyhow!("api store not available (postgres-storage feature disabled)"))
     }
+
+    /// Nothing remote to reach: the WhatsApp store is a local SQLite file.
+    async fn ping(&self) -> Result<()> {
+        Ok(())
+    }
 }
 
 #[cfg(feature = "postgres-storage")]
@@ -40,7 +64,7 @@ mod postgres_impl {
     use async_trait::async_trait;
     use chatwarp_api_postgres_storage::BindValue as PgBind;
     use chatwarp_api_postgres_storage::PostgresStore;
-    use serde_json::Value;
+    use serde_json::{Value, json};
 
     fn to_pg_bind(bind: ApiBind) -> PgBind {
         match bind {
@@ -65,5 +89,30 @@ mod postgres_impl {
             let pg_binds = binds.into_iter().map(to_pg_bind).collect();
             Ok(self.api_execute(sql, pg_binds).await?)
         }
+
+        async fn execute_batch(
+            &self,
+            statements: Vec<(String, Vec<ApiBind>)>,
+        ) -> Result<Vec<usize>> {
+            let statements = statements
+                .into_iter()
+                .map(|(sql, binds)| (sql, binds.into_iter().map(to_pg_bind).collect()))
+                .collect();
+            Ok(self.api_execute_batch(statements).await?)
+        }
+
+        fn pool_stats(&self) -> Option<Value> {
+            let stats = PostgresStore::pool_stats(self);
+            Some(json!({
+                "max": stats.max_size,
+                "connections": stats.connections,
+                "idle": stats.idle,
+                "in_use": stats.in_use,
+                "checkouts_total": stats.checkouts,
+                "timeouts_total": stats.timeouts,
+                "wait_ms_avg": stats.wait_ms_avg,
+                "wait_ms_max": stats.wait_ms_max,
+            }))
+        }
     }
 }
@@ -5,11 +5,13 @@ use anyhow::{Result, anyhow};
 use async_trait::async_trait;
 use prost::Message;
 use tokio::sync::Mutex;
+use warp_core::appstate::encode::index_mac;
 use warp_core::appstate::hash::HashState;
 use warp_core::appstate::keys::ExpandedAppStateKeys;
 use warp_core::appstate::patch_decode::{PatchList, WAPatchName, parse_patch_list};
 use warp_core::appstate::{
-    collect_key_ids_from_patch_list, expand_app_state_keys, process_patch, process_snapshot,
+    PatchInfo, collect_key_ids_from_patch_list, encode_patch, expand_app_state_keys,
+    process_patch, process_snapshot,
 };
 use warp_core::store::traits::Backend;
 use warp_core_binary::node::Node;
@@ -210,6 +212,40 @@ impl AppStateProcessor {
         Ok((new_mutations, state, pl))
     }
 
+    /// Encodes `info` on top of the stored state of its collection with the
+    /// newest sync key. Returns the serialized patch and the version it was
+    /// built against.
+    pub async fn encode_patch(&self, info: &PatchInfo) -> Result<(Vec<u8>, u64)> {
+        let key_id = self
+            .backend
+            .get_latest_sync_key_id()
+            .await?
+            .ok_or_else(|| anyhow!("app state key not found"))?;
+        let keys = self.get_app_state_key(&key_id).await?;
+        let name = info.name.as_str();
+        let state = self.backend.get_version(name).await?;
+
+        let mut stored_macs: HashMap<Vec<u8>, Vec<u8>> = HashMap::new();
+        for mutation in &info.mutations {
+            let index_mac = index_mac(&mutation.index, &keys).map_err(|e| anyhow!("{}", e))?;
+            if let Some(value_mac) = self.backend.get_mutation_mac(name, &index_mac).await? {
+                stored_macs.insert(index_mac, value_mac);
+            }
+        }
+
+        let encoded = encode_patch(
+            info,
+            &state,
+            &keys,
+            &key_id,
+            chrono::Utc::now().timestamp_millis(),
+            rand::random::<[u8; 16]>,
+            |index_mac| Ok(stored_macs.get(index_mac).cloned()),
+        )
+        .map_err(|e| anyhow!("{}", e))?;
+        Ok((encoded.patch.encode_to_vec(), state.version))
+    }
+
     pub async fn get_missing_key_ids(&self, pl: &PatchList) -> Result<Vec<Vec<u8>>> {
         let key_ids = collect_key_ids_from_patch_list(pl.snapshot.as_ref(), &pl.patches);
         let mut missing = Vec::new();
@@ -1,3 +1,4 @@
+use crate::client::HandshakePhase;
 use crate::socket::NoiseSocket;
 use crate::transport::{Transport, TransportEvent};
 use log::{debug, info, warn};
@@ -23,14 +24,19 @@ pub enum HandshakeError {
     UnexpectedEvent(String),
     #[error("Edge routing error: {0}")]
     EdgeRouting(#[from] EdgeRoutingError),
+    #[error("Handshake aborted at {0:?}")]
+    Aborted(HandshakePhase),
 }
 
 type Result<T> = std::result::Result<T, HandshakeError>;
 
+/// Runs the Noise XX handshake over `transport`, reporting each phase
+/// reached through `on_phase`, which may abort the handshake there.
 pub async fn do_handshake(
     device: &crate::store::Device,
     transport: Arc<dyn Transport>,
     transport_events: &mut async_channel::Receiver<TransportEvent>,
+    on_phase: &(dyn Fn(HandshakePhase) -> Result<()> + Send + Sync),
 ) -> Result<Arc<NoiseSocket>> {
     let mut handshake_state = HandshakeState::new(&device.core)?;
     let mut frame_decoder = warp_core::framing::FrameDecoder::new();
@@ -77,6 +83,7 @@ pub async fn do_handshake(
     let framed = warp_core::framing::encode_frame(&client_hello_bytes, Some(&header))
         .map_err(HandshakeError::Transport)?;
     transport.send(&framed).await?;
+    on_phase(HandshakePhase::ClientHello)?;
 
     // Wait for server response frame
     let resp_frame = loop {
@@ -109,14 +116,17 @@ pub async fn do_handshake(
     debug!("<-- Received handshake response, building ClientFinish");
     let client_finish_bytes =
         handshake_state.read_server_hello_and_build_client_finish(&resp_frame)?;
+    on_phase(HandshakePhase::ServerHello)?;
 
     debug!("--> Sending ClientFinish");
     // Subsequent messages don't need the header
     let framed = warp_core::framing::encode_frame(&client_finish_bytes, None)
         .map_err(HandshakeError::Transport)?;
     transport.send(&framed).await?;
+    on_phase(HandshakePhase::ClientFinish)?;
 
     let (write_key, read_key) = handshake_state.finish()?;
+    on_phase(HandshakePhase::PostFinish)?;
     info!(target: "Client", "Handshake complete, switching to encrypted communication");
 
     Ok(Arc::new(NoiseSocket::new(transport, write_key, read_key)))
@@ -13,6 +13,7 @@ use std::sync::Arc;
 use tokio::sync::mpsc;
 use tokio::task;
 use waproto::whatsapp as wa;
+use warp_core::store::BrowserProfile;
 
 type EventHandlerCallback =
     Arc<dyn Fn(Event, Arc<Client>) -> Pin<Box<dyn Future<Output = ()> + Send>> + Send + Sync>;
@@ -63,7 +64,22 @@ impl Bot {
         self.client.clone()
     }
 
+    /// Starts the bot and spawns the client runner.
     pub async fn run(&mut self) -> Result<task::JoinHandle<()>> {
+        self.start().await?;
+
+        let client_for_run = self.client.clone();
+        let client_handle = tokio::spawn(async move {
+            client_for_run.run().await;
+        });
+
+        Ok(client_handle)
+    }
+
+    /// Starts the sync worker, event handler and pair-code request without
+    /// spawning the client runner, for callers that run `Client::run`
+    /// themselves (e.g. under a supervisor).
+    pub async fn start(&mut self) -> Result<()> {
         if let Some(mut receiver) = self.sync_task_receiver.take() {
             let worker_client = self.client.clone();
             tokio::spawn(async move {
@@ -128,12 +144,7 @@ impl Bot {
             });
         }
 
-        let client_for_run = self.client.clone();
-        let client_handle = tokio::spawn(async move {
-            client_for_run.run().await;
-        });
-
-        Ok(client_handle)
+        Ok(())
     }
 }
 
@@ -145,8 +156,12 @@ pub struct BotBuilder {
     transport_factory: Option<Arc<dyn crate::transport::TransportFactory>>,
     http_client: Option<Arc<dyn crate::http::HttpClient>>,
     override_version: Option<(u32, u32, u32)>,
+    version_config: crate::version::VersionConfig,
     os_info: Option<(Option<String>, Option<wa::device_props::AppVersion>)>,
+    browser: Option<BrowserProfile>,
     pair_code_options: Option<PairCodeOptions>,
+    handshake_gate: Option<Arc<crate::client::HandshakeGate>>,
+    save_debounce: std::time::Duration,
 }
 
 impl BotBuilder {
@@ -158,8 +173,12 @@ impl BotBuilder {
             transport_factory: None,
             http_client: None,
             override_version: None,
+            version_config: crate::version::VersionConfig::default(),
             os_info: None,
+            browser: None,
             pair_code_options: None,
+            handshake_gate: None,
+            save_debounce: std::time::Duration::ZERO,
         }
     }
 
@@ -319,6 +338,30 @@ impl BotBuilder {
         self
     }
 
+    /// Present the device as `browser`: it sets the OS, OS version and
+    /// platform sent to WhatsApp and the name the phone shows for the linked
+    /// device, also when pairing with a code. Applied after
+    /// [`Self::with_os_info`].
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// use warp_core::store::BrowserProfile;
+    ///
+    /// let bot = Bot::builder()
+    ///     .with_backend(backend)
+    ///     .with_browser(BrowserProfile {
+    ///         name: "Firefox".to_string(),
+    ///         os: "Windows".to_string(),
+    ///         version: "10.0.22631".to_string(),
+    ///     })
+    ///     .build()
+    ///     .await?;
+    /// ```
+    pub fn with_browser(mut self, browser: BrowserProfile) -> Self {
+        self.browser = Some(browser);
+        self
+    }
+
     /// Configure pair code authentication to run automatically after connecting.
     ///
     /// When set, the pair code request will be sent automatically after establishing
@@ -359,6 +402,29 @@ impl BotBuilder {
         self
     }
 
+    /// Share a limit on simultaneous connection attempts with other bots
+    /// of the process, so they do not all dial WhatsApp at once.
+    pub fn with_handshake_gate(mut self, gate: Arc<crate::client::HandshakeGate>) -> Self {
+        self.handshake_gate = Some(gate);
+        self
+    }
+
+    /// Coalesce auth state writes: the device is saved and the buffered
+    /// Signal key writes flushed at most once per `debounce`, and on
+    /// disconnect. Zero (the default) writes every change through.
+    pub fn with_save_debounce(mut self, debounce: std::time::Duration) -> Self {
+        self.save_debounce = debounce;
+        self
+    }
+
+    /// WA web version policy: pin, fallbacks and where the current version
+    /// is fetched from. A version set with [`with_version`](Self::with_version)
+    /// still wins as the pin.
+    pub fn with_version_config(mut self, config: crate::version::VersionConfig) -> Self {
+        self.version_config = config;
+        self
+    }
+
     pub async fn build(self) -> Result<Bot> {
         let backend = self.backend.ok_or_else(|| {
             anyhow::anyhow!(
@@ -379,7 +445,7 @@ impl BotBuilder {
         // Note: For multi-account mode, create the backend with SqliteStore::new_for_device()
         // before passing it to with_backend()
         let persistence_manager = Arc::new(
-            PersistenceManager::new(backend)
+            PersistenceManager::with_debounce(backend, self.save_debounce)
                 .await
                 .map_err(|e| anyhow::anyhow!("Failed to create persistence manager: {}", e))?,
         );
@@ -398,15 +464,30 @@ impl BotBuilder {
                 .await;
         }
 
+        if let Some(browser) = &self.browser {
+            info!("Applying browser profile: {}", browser.display_name());
+            persistence_manager
+                .modify_device(|device| device.set_browser(browser))
+                .await;
+        }
+
         info!("Creating client...");
-        let (client, sync_task_receiver) = Client::new(
+        let mut version_config = self.version_config;
+        if self.override_version.is_some() {
+            version_config.pinned = self.override_version;
+        }
+        let (client, sync_task_receiver) = Client::with_version_config(
             persistence_manager.clone(),
             transport_factory,
             http_client,
-            self.override_version,
+            version_config,
         )
         .await;
 
+        if let Some(gate) = self.handshake_gate {
+            client.set_handshake_gate(gate);
+        }
+
         // Register custom enc handlers
         for (enc_type, handler) in self.custom_enc_handlers {
             client.custom_enc_handlers.insert(enc_type, handler);
@@ -416,7 +497,10 @@ impl BotBuilder {
             client,
             sync_task_receiver: Some(sync_task_receiver),
             event_handler: self.event_handler,
-            pair_code_options: self.pair_code_options,
+            pair_code_options: match (self.pair_code_options, &self.browser) {
+                (Some(options), Some(browser)) => Some(options.with_browser(browser)),
+                (options, _) => options,
+            },
         })
     }
 }
@@ -0,0 +1,542 @@
+//! Command line of the `chatwarp-api` binary.
+//!
+//! `serve` (the default) runs the HTTP API. The other subcommands run ops
+//! tasks straight on the storage, without a running server, so they fit
+//! scripts and one-off jobs: applying migrations, managing instances,
+//! moving the device credentials between hosts and sending a test message.
+//! They read the same environment as the server.
+
+use crate::api_store::{ApiBind, ApiStore};
+use crate::config::{DatabaseConfig, DatabaseProvider};
+use crate::server::outbox::OutboxEvent;
+use crate::server::participants::NumberError;
+use crate::store::Backend;
+use clap::{Args, Parser, Subcommand};
+use serde_json::json;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use thiserror::Error;
+use tracing::info;
+use uuid::Uuid;
+use warp_core::store::Device;
+
+#[derive(Debug, Parser)]
+#[command(
+    name = "chatwarp-api",
+    version,
+    about,
+    args_conflicts_with_subcommands = true
+)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    /// Arguments of `serve` when no subcommand is given.
+    #[command(flatten)]
+    pub serve: ServeArgs,
+    /// Validate the environment and exit.
+    #[arg(long)]
+    pub check_config: bool,
+    /// Same as `migrate`, kept for existing deployments.
+    #[arg(long, hide = true)]
+    pub migrate_only: bool,
+}
+
+impl Cli {
+    /// The subcommand to run, resolving the defaults and legacy flags.
+    pub fn into_command(self) -> Command {
+        if self.migrate_only {
+            return Command::Migrate;
+        }
+        self.command.unwrap_or(Command::Serve(self.serve))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Subcommand)]
+pub enum Command {
+    /// Run the HTTP API and the default instance.
+    Serve(ServeArgs),
+    /// Apply the database migrations and exit.
+    Migrate,
+    /// Create, delete or list instances.
+    #[command(subcommand)]
+    Instance(InstanceCommand),
+    /// Export or import the device credentials.
+    #[command(subcommand)]
+    Auth(AuthCommand),
+    /// Manage the encryption of secrets at rest.
+    #[command(subcommand)]
+    Secrets(SecretsCommand),
+    /// Connect with the stored device, send one text message and exit.
+    SendTest(SendTestArgs),
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Args)]
+pub struct ServeArgs {
+    /// Phone number to pair with a pair code, alongside the QR code.
+    #[arg(short, long)]
+    pub phone: Option<String>,
+    /// Custom 8-character pair code.
+    #[arg(short, long, requires = "phone")]
+    pub code: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Subcommand)]
+pub enum InstanceCommand {
+    /// Register an instance.
+    Create(InstanceCreateArgs),
+    /// Delete an instance and everything stored for it.
+    Delete { name: String },
+    /// Print the instances as JSON, one per line.
+    List {
+        /// Only the instances of this workspace.
+        #[arg(long)]
+        workspace: Option<Uuid>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Args)]
+pub struct InstanceCreateArgs {
+    pub name: String,
+    #[arg(long)]
+    pub webhook_url: Option<String>,
+    #[arg(long)]
+    pub phone_number: Option<String>,
+    #[arg(long)]
+    pub workspace: Option<Uuid>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Subcommand)]
+pub enum AuthCommand {
+    /// Write the stored device credentials as JSON.
+    Export {
+        /// Destination file, created with owner-only permissions; stdout
+        /// when omitted.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Store device credentials written by `auth export`.
+    Import {
+        input: PathBuf,
+        /// Replace the device already stored.
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Subcommand)]
+pub enum SecretsCommand {
+    /// Encrypt the stored secrets with SECRETS_MASTER_KEY: plaintext rows
+    /// and rows encrypted with one of SECRETS_PREVIOUS_KEYS.
+    Encrypt,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Args)]
+pub struct SendTestArgs {
+    /// Phone number or JID of the recipient.
+    pub to: String,
+    #[arg(short, long, default_value = "chatwarp-api test message")]
+    pub text: String,
+    /// How long to wait for the connection and the send.
+    #[arg(long, default_value_t = 60)]
+    pub timeout_secs: u64,
+}
+
+#[derive(Debug, Error)]
+pub enum CliError {
+    #[error("{0}")]
+    Storage(String),
+    #[error("this command needs DATABASE_PROVIDER=postgresql")]
+    NeedsPostgres,
+    #[error("SECRETS_MASTER_KEY is not set")]
+    NoMasterKey,
+    #[error("instance {0:?} already exists")]
+    InstanceExists(String),
+    #[error("instance {0:?} not found")]
+    InstanceNotFound(String),
+    #[error("no paired device in the storage")]
+    NotPaired,
+    #[error("a device is already stored; pass --force to replace it")]
+    DeviceExists,
+    #[error("invalid recipient: {0}")]
+    Recipient(#[from] NumberError),
+    #[error("no connection within {0}s")]
+    Timeout(u64),
+    #[error("send failed: {0}")]
+    Send(String),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+/// Storage opened from `DATABASE_*`.
+pub struct Storage {
+    pub backend: Arc<dyn Backend>,
+    /// [`NoopApiStore`] on SQLite.
+    pub api_store: Arc<dyn ApiStore>,
+    /// The same store as `backend`, `None` on SQLite.
+    #[cfg(feature = "postgres-storage")]
+    pub postgres: Option<Arc<crate::store::PostgresStore>>,
+}
+
+/// Connects to the configured database, applying the migrations when
+/// `database.run_migrations` is set.
+pub async fn open_storage(database: &DatabaseConfig) -> Result<Storage, CliError> {
+    match (database.provider, database.url.clone()) {
+        (DatabaseProvider::Postgresql, Some(url)) => {
+            #[cfg(feature = "postgres-storage")]
+            {
+                let defaults = crate::store::PoolOptions::default();
+                let pool_options = crate::store::PoolOptions {
+                    max_size: database.pool.max_size.unwrap_or(defaults.max_size),
+                    min_idle: database.pool.min_idle.or(defaults.min_idle),
+                    acquire_timeout: database
+                        .pool
+                        .acquire_timeout
+                        .unwrap_or(defaults.acquire_timeout),
+                };
+                let store = crate::store::PostgresStore::connect(
+                    &url,
+                    database.run_migrations,
+                    pool_options,
+                )
+                .await
+                .map_err(|e| CliError::Storage(format!("PostgreSQL: {e}")))?;
+                let store = match database.secrets.clone() {
+                    Some(keyring) => {
+                        info!(key_id = %keyring.current_id(), "Secrets encrypted at rest");
+                        store.with_keyring(keyring)
+                    }
+                    None => store,
+                };
+                info!("PostgreSQL backend initialized");
+                let store = Arc::new(store);
+                Ok(Storage {
+                    backend: store.clone(),
+                    api_store: store.clone(),
+                    postgres: Some(store),
+                })
+            }
+            #[cfg(not(feature = "postgres-storage"))]
+            {
+                let _ = url;
+                Err(CliError::Storage(
+                    "PostgreSQL support is not enabled in this build".to_string(),
+                ))
+            }
+        }
+        (_, url) => {
+            #[cfg(feature = "sqlite-storage")]
+            {
+                if database.secrets.is_some() {
+                    return Err(CliError::Storage(
+                        "SECRETS_MASTER_KEY is only supported with PostgreSQL".to_string(),
+                    ));
+                }
+                let url = url.unwrap_or_else(|| "whatsapp.db".to_string());
+                let store = crate::store::SqliteStore::connect(&url, database.run_migrations)
+                    .await
+                    .map_err(|e| CliError::Storage(format!("SQLite {url}: {e}")))?;
+                info!(database_url = %url, "SQLite backend initialized");
+                Ok(Storage {
+                    backend: Arc::new(store),
+                    api_store: Arc::new(crate::api_store::NoopApiStore),
+                    #[cfg(feature = "postgres-storage")]
+                    postgres: None,
+                })
+            }
+            #[cfg(not(feature = "sqlite-storage"))]
+            {
+                let _ = url;
+                Err(CliError::Storage(
+                    "SQLite support is not enabled in this build".to_string(),
+                ))
+            }
+        }
+    }
+}
+
+/// Runs an `instance` subcommand. Instances live in `api_sessions`, which
+/// only the PostgreSQL storage has. Creations and deletions write their
+/// `CONNECTION_UPDATE` to the outbox in the same statement, as the HTTP
+/// routes do, and a running server delivers it.
+pub async fn run_instance(
+    database: &DatabaseConfig,
+    command: InstanceCommand,
+    out: &mut impl Write,
+) -> Result<(), CliError> {
+    if database.provider != DatabaseProvider::Postgresql {
+        return Err(CliError::NeedsPostgres);
+    }
+    let api_store = open_storage(database).await?.api_store;
+    match command {
+        InstanceCommand::Create(args) => {
+            let event = OutboxEvent::new(
+                Some(&args.name),
+                "CONNECTION_UPDATE",
+                json!({"status": "open"}),
+            );
+            let created = api_store
+                .execute(
+                    "WITH created AS ( \
+                        INSERT INTO api_sessions (session, status, webhook_url, phone_number, workspace_id, created_at, updated_at) \
+                        VALUES ($1, 'open', $2, $3, $4::uuid, now(), now()) \
+                        ON CONFLICT (session) DO NOTHING \
+                        RETURNING session \
+                    ) \
+                    INSERT INTO event_outbox (id, session, event, payload) \
+                    SELECT $5, session, $6, $7 FROM created",
+                    vec![
+                        ApiBind::Text(args.name.clone()),
+                        ApiBind::NullableText(args.webhook_url),
+                        ApiBind::NullableText(args.phone_number),
+                        ApiBind::NullableText(args.workspace.map(|id| id.to_string())),
+                        ApiBind::Uuid(event.id),
+                        ApiBind::Text(event.event),
+                        ApiBind::Json(event.payload),
+                    ],
+                )
+                .await?;
+            if created == 0 {
+                return Err(CliError::InstanceExists(args.name));
+            }
+            writeln!(
+                out,
+                "{}",
+                json!({"session": args.name, "status": "created"})
+            )?;
+        }
+        InstanceCommand::Delete { name } => {
+            let event =
+                OutboxEvent::new(Some(&name), "CONNECTION_UPDATE", json!({"status": "close"}));
+            let deleted = api_store
+                .execute(
+                    "WITH deleted AS ( \
+                        DELETE FROM api_sessions WHERE session = $1 RETURNING session \
+                    ) \
+                    INSERT INTO event_outbox (id, session, event, payload) \
+                    SELECT $2, session, $3, $4 FROM deleted",
+                    vec![
+                        ApiBind::Text(name.clone()),
+                        ApiBind::Uuid(event.id),
+                        ApiBind::Text(event.event),
+                        ApiBind::Json(event.payload),
+                    ],
+                )
+                .await?;
+            if deleted == 0 {
+                return Err(CliError::InstanceNotFound(name));
+            }
+            writeln!(out, "{}", json!({"session": name, "status": "deleted"}))?;
+        }
+        InstanceCommand::List { workspace } => {
+            let rows = api_store
+                .query_json(
+                    "SELECT row_to_json(api_sessions)::jsonb - 'webhook_secret' - 'cloud_access_token' as value FROM api_sessions \
+                     WHERE ($1::uuid IS NULL OR workspace_id = $1::uuid) \
+                     ORDER BY created_at DESC",
+                    vec![ApiBind::NullableText(workspace.map(|id| id.to_string()))],
+                )
+                .await?;
+            for row in rows {
+                writeln!(out, "{}", row.get("value").unwrap_or(&row))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Runs an `auth` subcommand on the device of `backend`.
+pub async fn run_auth(
+    backend: &dyn Backend,
+    command: AuthCommand,
+    out: &mut impl Write,
+) -> Result<(), CliError> {
+    match command {
+        AuthCommand::Export { output } => {
+            let device = backend
+                .load()
+                .await
+                .map_err(|e| CliError::Storage(e.to_string()))?
+                .filter(|device| device.pn.is_some())
+                .ok_or(CliError::NotPaired)?;
+            let exported = export_device(&device)?;
+            match output {
+                Some(path) => write_private(&path, exported.as_bytes())?,
+                None => writeln!(out, "{exported}")?,
+            }
+        }
+        AuthCommand::Import { input, force } => {
+            let device = import_device(&std::fs::read_to_string(&input)?)?;
+            let exists = backend
+                .exists()
+                .await
+                .map_err(|e| CliError::Storage(e.to_string()))?;
+            if exists && !force {
+                return Err(CliError::DeviceExists);
+            }
+            backend
+                .save(&device)
+                .await
+                .map_err(|e| CliError::Storage(e.to_string()))?;
+            writeln!(
+                out,
+                "{}",
+                json!({"status": "imported", "pn": device.pn.map(|jid| jid.to_string())})
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Runs a `secrets` subcommand, printing one JSON line per secret column
+/// with the number of rows it rewrote. Only the PostgreSQL storage
+/// encrypts secrets.
+pub async fn run_secrets(
+    database: &DatabaseConfig,
+    command: SecretsCommand,
+    out: &mut impl Write,
+) -> Result<(), CliError> {
+    if database.provider != DatabaseProvider::Postgresql {
+        return Err(CliError::NeedsPostgres);
+    }
+    if database.secrets.is_none() {
+        return Err(CliError::NoMasterKey);
+    }
+    match command {
+        SecretsCommand::Encrypt => {
+            #[cfg(feature = "postgres-storage")]
+            {
+                let store = open_storage(database)
+                    .await?
+                    .postgres
+                    .ok_or(CliError::NeedsPostgres)?;
+                let columns = store
+                    .reseal_secrets()
+                    .await
+                    .map_err(|e| CliError::Storage(e.to_string()))?;
+                for column in columns {
+                    let line = json!({
+                        "table": column.table,
+                        "column": column.column,
+                        "encrypted": column.rows,
+                    });
+                    writeln!(out, "{line}")?;
+                }
+                Ok(())
+            }
+            #[cfg(not(feature = "postgres-storage"))]
+            {
+                let _ = out;
+                Err(CliError::Storage(
+                    "PostgreSQL support is not enabled in this build".to_string(),
+                ))
+            }
+        }
+    }
+}
+
+/// Credentials of `device` as written by `auth export`. Signal sessions
+/// and pre-keys are not included; they are set up again on use.
+pub fn export_device(device: &Device) -> Result<String, CliError> {
+    Ok(serde_json::to_string_pretty(device)?)
+}
+
+/// Reads credentials written by [`export_device`].
+pub fn import_device(raw: &str) -> Result<Device, CliError> {
+    let device: Device = serde_json::from_str(raw)?;
+    if device.pn.is_none() {
+        return Err(CliError::NotPaired);
+    }
+    Ok(device)
+}
+
+/// The exported keys are enough to impersonate the device.
+fn write_private(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)?.write_all(data)
+}
+
+/// Connects with the device of `backend`, sends `args.text` to `args.to`
+/// and disconnects. Returns the id of the sent message.
+#[cfg(feature = "tokio-transport")]
+pub async fn send_test(backend: Arc<dyn Backend>, args: SendTestArgs) -> Result<String, CliError> {
+    use crate::bot::Bot;
+    use crate::config::{self, Env};
+    use crate::server::http_client::SharedHttpClient;
+    use crate::server::participants::Target;
+    use chatwarp_api_tokio_transport::TokioWebSocketTransportFactory;
+    use std::time::Duration;
+    use tokio::sync::{Mutex, oneshot};
+    use warp_core::types::events::Event;
+
+    let env = Env::process();
+    let recipient = config::participants(&env).normalize(&args.to, Target::Chat)?;
+    let version_config = config::wa_version(&env).unwrap_or_else(|e| {
+        tracing::warn!("Ignoring WA version config: {e}");
+        Default::default()
+    });
+    let paired = backend
+        .load()
+        .await
+        .map_err(|e| CliError::Storage(e.to_string()))?
+        .is_some_and(|device| device.pn.is_some());
+    if !paired {
+        return Err(CliError::NotPaired);
+    }
+
+    let (tx, rx) = oneshot::channel::<Result<String, String>>();
+    let tx = Arc::new(Mutex::new(Some(tx)));
+    let text = args.text;
+    let mut bot = Bot::builder()
+        .with_backend(backend)
+        .with_transport_factory(TokioWebSocketTransportFactory::new())
+        .with_http_client(SharedHttpClient::from_config(config::http_client(&env))?)
+        .with_version_config(version_config)
+        .on_event(move |event, client| {
+            let tx = tx.clone();
+            let recipient = recipient.clone();
+            let text = text.clone();
+            async move {
+                if let Event::Connected(_) = event {
+                    let msg = waproto::whatsapp::Message {
+                        conversation: Some(text),
+                        ..Default::default()
+                    };
+                    let result = client
+                        .send_message(recipient, msg)
+                        .await
+                        .map_err(|e| e.to_string());
+                    if let Some(tx) = tx.lock().await.take() {
+                        let _ = tx.send(result);
+                    }
+                }
+            }
+        })
+        .build()
+        .await?;
+    let client = bot.client();
+    bot.run().await?;
+
+    let result = tokio::time::timeout(Duration::from_secs(args.timeout_secs), rx).await;
+    client.disconnect().await;
+    match result {
+        Ok(Ok(Ok(id))) => Ok(id),
+        Ok(Ok(Err(e))) => Err(CliError::Send(e)),
+        Ok(Err(_)) | Err(_) => Err(CliError::Timeout(args.timeout_secs)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    include!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/tests/cli_tests.rs"));
+}
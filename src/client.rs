@@ -1,10 +1,21 @@
+mod app_state;
 mod context_impl;
 mod device_registry;
+mod diagnostics;
+#[cfg(feature = "chaos")]
+pub mod faults;
+mod handshake_gate;
 mod keepalive;
 mod lid_pn;
 mod sender_keys;
 pub(crate) mod sessions;
 
+pub use diagnostics::{
+    AttemptOutcome, ConnectionAttempt, ConnectionDiagnostics, HandshakePhase,
+    MAX_CONNECTION_ATTEMPTS, RetryStats, RetryStatsSnapshot,
+};
+pub use handshake_gate::{HandshakeConfig, HandshakeGate, HandshakeGateStats, HandshakePermit};
+
 use crate::handshake;
 use crate::lid_pn_cache::LidPnCache;
 use crate::pair;
@@ -23,7 +34,7 @@ use warp_core_binary::node::Node;
 use crate::appstate_sync::AppStateProcessor;
 use crate::store::{commands::DeviceCommand, persistence_manager::PersistenceManager};
 use crate::types::enc_handler::EncHandler;
-use crate::types::events::{ConnectFailureReason, Event};
+use crate::types::events::{ConnectFailureReason, Event, StreamErrorReason};
 use crate::utils::jid_utils::server_jid;
 
 use tracing::{debug, error, info, trace, warn};
@@ -138,8 +149,23 @@ pub struct Client {
     pub(crate) message_retry_counts: Cache<String, u8>,
 
     pub enable_auto_reconnect: Arc<AtomicBool>,
+    /// Set by [`Client::hold_connection`]: `run` keeps looping but does not
+    /// reconnect until [`Client::release_connection`].
+    pub(crate) connection_held: Arc<AtomicBool>,
+    pub(crate) connection_released: Arc<Notify>,
     pub auto_reconnect_errors: Arc<AtomicU32>,
     pub last_successful_connect: Arc<Mutex<Option<chrono::DateTime<chrono::Utc>>>>,
+    /// Recent connection attempts, exposed by `/instance/diagnostics/:name`.
+    pub connection_diagnostics: Arc<ConnectionDiagnostics>,
+    /// Retry receipts sent and handled, exposed next to the attempts.
+    pub retry_stats: Arc<RetryStats>,
+    /// Limit on simultaneous connection attempts shared with the other
+    /// clients of the process; see [`Client::set_handshake_gate`].
+    pub(crate) handshake_gate: ArcSwapOption<HandshakeGate>,
+
+    /// Faults armed through the `/chaos` routes.
+    #[cfg(feature = "chaos")]
+    pub faults: Arc<faults::FaultInjector>,
 
     pub(crate) needs_initial_full_sync: Arc<AtomicBool>,
 
@@ -189,8 +215,8 @@ pub struct Client {
     /// HTTP client for making HTTP requests (media upload/download, version fetching)
     pub http_client: Arc<dyn crate::http::HttpClient>,
 
-    /// Version override for testing or manual specification
-    pub(crate) override_version: Option<(u32, u32, u32)>,
+    /// Picks the WA web version for each connection attempt (pin, fetch, fallbacks).
+    pub version_manager: Arc<crate::version::WaVersionManager>,
 }
 
 impl Client {
@@ -199,6 +225,26 @@ impl Client {
         transport_factory: Arc<dyn crate::transport::TransportFactory>,
         http_client: Arc<dyn crate::http::HttpClient>,
         override_version: Option<(u32, u32, u32)>,
+    ) -> (Arc<Self>, mpsc::Receiver<MajorSyncTask>) {
+        let version_config = crate::version::VersionConfig {
+            pinned: override_version,
+            ..Default::default()
+        };
+        Self::with_version_config(
+            persistence_manager,
+            transport_factory,
+            http_client,
+            version_config,
+        )
+        .await
+    }
+
+    /// Like [`new`](Self::new), with the whole WA web version policy.
+    pub async fn with_version_config(
+        persistence_manager: Arc<PersistenceManager>,
+        transport_factory: Arc<dyn crate::transport::TransportFactory>,
+        http_client: Arc<dyn crate::http::HttpClient>,
+        version_config: crate::version::VersionConfig,
     ) -> (Arc<Self>, mpsc::Receiver<MajorSyncTask>) {
         let mut unique_id_bytes = [0u8; 2];
         rand::rng().fill_bytes(&mut unique_id_bytes);
@@ -267,8 +313,15 @@ impl Client {
                 .build(),
 
             enable_auto_reconnect: Arc::new(AtomicBool::new(true)),
+            connection_held: Arc::new(AtomicBool::new(false)),
+            connection_released: Arc::new(Notify::new()),
             auto_reconnect_errors: Arc::new(AtomicU32::new(0)),
             last_successful_connect: Arc::new(Mutex::new(None)),
+            connection_diagnostics: Arc::new(ConnectionDiagnostics::new()),
+            retry_stats: Arc::new(RetryStats::new()),
+            handshake_gate: ArcSwapOption::empty(),
+            #[cfg(feature = "chaos")]
+            faults: Arc::new(faults::FaultInjector::default()),
 
             needs_initial_full_sync: Arc::new(AtomicBool::new(false)),
 
@@ -293,7 +346,7 @@ impl Client {
             stanza_router: Self::create_stanza_router(),
             synchronous_ack: false,
             http_client,
-            override_version,
+            version_manager: Arc::new(crate::version::WaVersionManager::new(version_config)),
         };
 
         let arc = Arc::new(this);
@@ -390,12 +443,22 @@ impl Client {
             return;
         }
         while self.is_running.load(Ordering::Relaxed) {
+            self.wait_while_held().await;
+            if !self.is_running.load(Ordering::Relaxed) {
+                break;
+            }
             self.expected_disconnect.store(false, Ordering::Relaxed);
 
             if let Err(err) = self.connect().await {
                 error!(error = ?err, "Failed to connect, will retry");
+                self.connection_diagnostics.mark_failed(err.to_string());
             } else {
-                if self.read_messages_loop().await.is_err() {
+                if self.is_connection_held() {
+                    debug!("Connection held while connecting; closing it");
+                    if let Some(transport) = self.transport.lock().await.as_ref() {
+                        transport.disconnect().await;
+                    }
+                } else if self.read_messages_loop().await.is_err() {
                     warn!("Message loop exited with an error; will attempt reconnect if enabled");
                 } else if self.expected_disconnect.load(Ordering::Relaxed) {
                     debug!("Message loop exited gracefully (expected disconnect)");
@@ -404,6 +467,7 @@ impl Client {
                 }
 
                 self.cleanup_connection_state().await;
+                self.connection_diagnostics.mark_closed();
             }
 
             if !self.enable_auto_reconnect.load(Ordering::Relaxed) {
@@ -422,6 +486,7 @@ impl Client {
             let error_count = self.auto_reconnect_errors.fetch_add(1, Ordering::SeqCst);
             let delay_secs = u64::from(error_count * 2).min(30);
             let delay = Duration::from_secs(delay_secs);
+            self.connection_diagnostics.record_backoff(delay_secs);
             info!(
                 delay_secs,
                 attempt = error_count + 1,
@@ -445,32 +510,60 @@ impl Client {
             return Err(ClientError::AlreadyConnected.into());
         }
 
+        // Held until the handshake ends, successful or not.
+        let _permit = match self.handshake_gate.load_full() {
+            Some(gate) => Some(gate.enter().await),
+            None => None,
+        };
+
         // Reset login state for new connection attempt. This ensures that
         // handle_success will properly process the <success> stanza even if
         // a previous connection's post-login task bailed out early.
         self.is_logged_in.store(false, Ordering::Relaxed);
         self.offline_sync_completed.store(false, Ordering::Relaxed);
+        self.connection_diagnostics.begin_attempt();
 
-        let version_future = crate::version::resolve_and_update_version(
-            &self.persistence_manager,
-            &self.http_client,
-            self.override_version,
-        );
+        let version_future = self
+            .version_manager
+            .resolve_and_update(&self.persistence_manager, &self.http_client);
 
         let transport_future = self.transport_factory.create_transport();
 
         info!("Connecting WebSocket and fetching latest client version in parallel");
         let (version_result, transport_result) = tokio::join!(version_future, transport_future);
 
-        version_result.map_err(|e| anyhow!("Failed to resolve app version: {}", e))?;
+        let version =
+            version_result.map_err(|e| anyhow!("Failed to resolve app version: {}", e))?;
         let (transport, mut transport_events) = transport_result?;
         info!("Version fetch and transport connection established");
 
         let device_snapshot = self.persistence_manager.get_device_snapshot().await;
-
-        let noise_socket =
-            handshake::do_handshake(&device_snapshot, transport.clone(), &mut transport_events)
-                .await?;
+        let wa_version = crate::version::format_version(version);
+        #[cfg(feature = "sentry")]
+        crate::server::error_reporting::wa_version(&wa_version);
+        self.connection_diagnostics.set_wa_version(wa_version);
+
+        let diagnostics = self.connection_diagnostics.clone();
+        #[cfg(feature = "chaos")]
+        let faults = self.faults.clone();
+        let on_phase = move |phase| -> std::result::Result<(), handshake::HandshakeError> {
+            diagnostics.set_phase(phase);
+            #[cfg(feature = "sentry")]
+            crate::server::error_reporting::handshake_phase(phase);
+            #[cfg(feature = "chaos")]
+            if faults.take_handshake_failure(phase) {
+                return Err(handshake::HandshakeError::Aborted(phase));
+            }
+            Ok(())
+        };
+        on_phase(HandshakePhase::HttpUpgrade)?;
+        let noise_socket = handshake::do_handshake(
+            &device_snapshot,
+            transport.clone(),
+            &mut transport_events,
+            &on_phase,
+        )
+        .await?;
 
         *self.transport.lock().await = Some(transport);
         *self.transport_events.lock().await = Some(transport_events);
@@ -485,6 +578,11 @@ impl Client {
         Ok(())
     }
 
+    /// Makes every connection attempt wait on `gate` before dialing.
+    pub fn set_handshake_gate(&self, gate: Arc<HandshakeGate>) {
+        self.handshake_gate.store(Some(gate));
+    }
+
     pub async fn disconnect(&self) {
         info!("Disconnecting client intentionally");
         self.expected_disconnect.store(true, Ordering::Relaxed);
@@ -497,6 +595,56 @@ impl Client {
         self.cleanup_connection_state().await;
     }
 
+    /// Closes the connection but keeps `run` alive, waiting without
+    /// reconnecting until [`Client::release_connection`]. Used for
+    /// maintenance windows, where stopping the runner is not wanted.
+    pub async fn hold_connection(&self) {
+        if self.connection_held.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        info!("Holding connection closed");
+        self.expected_disconnect.store(true, Ordering::Relaxed);
+        self.shutdown_notifier.notify_waiters();
+
+        if let Some(transport) = self.transport.lock().await.as_ref() {
+            transport.disconnect().await;
+        }
+    }
+
+    /// Lets a held `run` loop reconnect.
+    pub fn release_connection(&self) {
+        if self.connection_held.swap(false, Ordering::SeqCst) {
+            info!("Releasing held connection");
+            self.connection_released.notify_waiters();
+        }
+    }
+
+    pub fn is_connection_held(&self) -> bool {
+        self.connection_held.load(Ordering::SeqCst)
+    }
+
+    async fn wait_while_held(&self) {
+        loop {
+            // Registered before the check so a release in between is not missed.
+            let released = self.connection_released.notified();
+            let shutdown = self.shutdown_notifier.notified();
+            if !self.is_connection_held() || !self.is_running.load(Ordering::Relaxed) {
+                return;
+            }
+            tokio::select! {
+                _ = released => {},
+                _ = shutdown => {},
+            }
+        }
+    }
+
+    /// Clears the state left behind by a `run` that did not return (e.g. a
+    /// panicked runner task) so `run` can be started again.
+    pub async fn reset_run_state(&self) {
+        self.is_running.store(false, Ordering::Relaxed);
+        self.cleanup_connection_state().await;
+    }
+
     async fn cleanup_connection_state(&self) {
         self.is_logged_in.store(false, Ordering::Relaxed);
         *self.transport.lock().await = None;
@@ -505,6 +653,10 @@ impl Client {
         self.retried_group_messages.invalidate_all();
         // Reset offline sync state for next connection
         self.offline_sync_completed.store(false, Ordering::Relaxed);
+        // Debounced auth writes should not wait for the next connection.
+        if let Err(e) = self.persistence_manager.flush().await {
+            warn!(error = %e, "Failed to flush auth state on disconnect");
+        }
     }
 
     async fn read_messages_loop(self: &Arc<Self>) -> Result<(), anyhow::Error> {
@@ -529,15 +681,17 @@ impl Client {
                     event_result = transport_events.recv() => {
                         match event_result {
                             Ok(crate::transport::TransportEvent::DataReceived(data)) => {
-                                // Feed data into the frame decoder
-                                frame_decoder.feed(&data);
+                                #[cfg(feature = "chaos")]
+                                let data = self.faults.corrupt(data);
+                                // Feed data into the frame decoder; whole frames are not copied
+                                frame_decoder.feed_bytes(data);
 
                                 // Process all complete frames
                                 // Note: Frame decryption must be sequential (noise protocol counter),
                                 // but we spawn node processing concurrently after decryption
-                                while let Some(encrypted_frame) = frame_decoder.decode_frame() {
+                                while let Some(mut encrypted_frame) = frame_decoder.decode_frame_mut() {
                                     // Decrypt the frame synchronously (required for noise counter ordering)
-                                    if let Some(node) = self.decrypt_frame(&encrypted_frame).await {
+                                    if let Some(node) = self.decrypt_frame(&mut encrypted_frame).await {
                                         // Handle critical nodes synchronously to avoid race conditions.
                                         // <success> must be processed inline to ensure is_logged_in state
                                         // is set before checking expected_disconnect or spawning other tasks.
@@ -588,7 +742,7 @@ impl Client {
     /// This must be called sequentially due to noise protocol counter requirements.
     pub(crate) async fn decrypt_frame(
         self: &Arc<Self>,
-        encrypted_frame: &bytes::Bytes,
+        encrypted_frame: &mut bytes::BytesMut,
     ) -> Option<warp_core_binary::node::Node> {
         let noise_socket_arc = self.noise_socket.load_full();
         let noise_socket = match noise_socket_arc {
@@ -599,15 +753,12 @@ impl Client {
             }
         };
 
-        let decrypted_payload = match noise_socket.decrypt_frame(encrypted_frame) {
-            Ok(p) => p,
-            Err(e) => {
-                log::error!(target: "Client", "Failed to decrypt frame: {e}");
-                return None;
-            }
-        };
+        if let Err(e) = noise_socket.decrypt_frame_in_place(encrypted_frame) {
+            log::error!(target: "Client", "Failed to decrypt frame: {e}");
+            return None;
+        }
 
-        let unpacked_data_cow = match warp_core_binary::util::unpack(&decrypted_payload) {
+        let unpacked_data_cow = match warp_core_binary::util::unpack(encrypted_frame) {
             Ok(data) => data,
             Err(e) => {
                 log::warn!(target: "Client/Recv", "Failed to decompress frame: {e}");
@@ -864,6 +1015,7 @@ impl Client {
             debug!(target: "Client", "Ignoring duplicate <success> stanza (already logged in)");
             return;
         }
+        self.connection_diagnostics.mark_connected();
 
         // Increment connection generation to invalidate any stale post-login tasks
         // from previous connections (e.g., during 515 reconnect cycles).
@@ -1128,6 +1280,18 @@ impl Client {
             }
             return true;
         }
+        if node.attrs.get("class").is_some_and(|class| class == "message")
+            && let Some(id) = node.attrs.get("id")
+        {
+            let ack = crate::types::events::MessageAck {
+                id: id.clone(),
+                chat: node.attrs().optional_jid("from"),
+                timestamp: chrono::Utc::now(),
+                error: node.attrs.get("error").cloned(),
+            };
+            self.core.event_bus.dispatch(&Event::MessageAck(ack));
+            return true;
+        }
         false
     }
 
@@ -1462,15 +1626,12 @@ impl Client {
     pub(crate) async fn handle_stream_error(&self, node: &warp_core_binary::node::Node) {
         self.is_logged_in.store(false, Ordering::Relaxed);
 
-        let mut attrs = node.attrs();
-        let code = attrs.optional_string("code").unwrap_or("");
-        let conflict_type = node
-            .get_optional_child("conflict")
-            .map(|n| n.attrs().optional_string("type").unwrap_or("").to_string())
-            .unwrap_or_default();
-
-        match (code, conflict_type.as_str()) {
-            ("515", _) => {
+        let stream_error = crate::types::events::StreamError::from_node(node);
+        self.connection_diagnostics
+            .record_close_code(stream_error.close_code());
+
+        match stream_error.reason {
+            StreamErrorReason::RestartRequired => {
                 // 515 is expected during registration/pairing phase - server closes stream after pairing
                 info!(target: "Client", "Got 515 stream error, server is closing stream. Will auto-reconnect.");
                 self.expect_disconnect().await;
@@ -1485,12 +1646,12 @@ impl Client {
                     });
                 }
             }
-            ("401", "device_removed") | (_, "replaced") => {
+            StreamErrorReason::ReplacedByOtherDevice | StreamErrorReason::LoggedOut => {
                 info!(target: "Client", "Got stream error indicating client was removed or replaced. Logging out.");
                 self.expect_disconnect().await;
                 self.enable_auto_reconnect.store(false, Ordering::Relaxed);
 
-                let event = if conflict_type == "replaced" {
+                let event = if stream_error.reason == StreamErrorReason::ReplacedByOtherDevice {
                     Event::StreamReplaced(crate::types::events::StreamReplaced)
                 } else {
                     Event::LoggedOut(crate::types::events::LoggedOut {
@@ -1500,20 +1661,21 @@ impl Client {
                 };
                 self.core.event_bus.dispatch(&event);
             }
-            ("503", _) => {
-                info!(target: "Client", "Got 503 service unavailable, will auto-reconnect.");
+            StreamErrorReason::ServiceUnavailable => {
+                info!(target: "Client", "Got {} service unavailable, will auto-reconnect.", stream_error.code);
             }
-            _ => {
+            StreamErrorReason::RateOverlimit => {
+                // Reconnecting at once would only hit the limit again, so
+                // this goes through the regular backoff.
+                warn!(target: "Client", "Got rate-overlimit stream error, will reconnect after backoff.");
+            }
+            StreamErrorReason::Unknown => {
                 error!(target: "Client", "Unknown stream error: {}", DisplayableNode(node));
-                self.expect_disconnect().await;
-                self.core.event_bus.dispatch(&Event::StreamError(
-                    crate::types::events::StreamError {
-                        code: code.to_string(),
-                        raw: Some(node.clone()),
-                    },
-                ));
             }
         }
+        self.core
+            .event_bus
+            .dispatch(&Event::StreamError(stream_error));
 
         info!(target: "Client", "Notifying shutdown from stream error handler");
         self.shutdown_notifier.notify_waiters();
@@ -1526,8 +1688,18 @@ impl Client {
         let mut attrs = node.attrs();
         let reason_code = attrs.optional_u64("reason").unwrap_or(0) as i32;
         let reason = ConnectFailureReason::from(reason_code);
-
-        if reason.should_reconnect() {
+        self.connection_diagnostics
+            .record_close_code(format!("failure:{reason_code}"));
+
+        // An outdated version is retried as long as the version manager has
+        // another candidate (fresh fetch or untried fallback).
+        let retry_outdated = matches!(reason, ConnectFailureReason::ClientOutdated)
+            && self
+                .version_manager
+                .current()
+                .is_some_and(|version| self.version_manager.mark_rejected(version));
+
+        if reason.should_reconnect() || retry_outdated {
             self.expected_disconnect.store(false, Ordering::Relaxed);
         } else {
             self.enable_auto_reconnect.store(false, Ordering::Relaxed);
@@ -1724,6 +1896,11 @@ impl Client {
             None => return Err(ClientError::NotConnected),
         };
 
+        #[cfg(feature = "chaos")]
+        if let Some(delay) = self.faults.send_delay() {
+            sleep(delay).await;
+        }
+
         trace!(target: "Client/Send", "{}", DisplayableNode(&node));
 
         let mut plaintext_buf = self
@@ -1821,6 +1998,15 @@ impl Client {
         snapshot.pn.clone()
     }
 
+    /// Forgets the pairing after WhatsApp logged this device out: the stored
+    /// device gets fresh keys and no account, so the next connection asks
+    /// for a new QR code instead of retrying revoked credentials. Returns
+    /// the phone number it was paired with.
+    pub async fn clear_credentials(&self) -> Result<Option<Jid>, crate::store::error::StoreError> {
+        self.is_logged_in.store(false, Ordering::Relaxed);
+        self.persistence_manager.reset_device().await
+    }
+
     pub async fn get_lid(&self) -> Option<Jid> {
         let snapshot = self.persistence_manager.get_device_snapshot().await;
         snapshot.lid.clone()
@@ -0,0 +1,47 @@
+//! Sending app state patches (archive, pin, mute, mark read, ...).
+//!
+//! The patch is encoded on top of the locally stored collection state and
+//! sent with `w:sync:app:state`. Afterwards the collection is synced again so
+//! our own patch is applied (and its mutations dispatched) like any other.
+
+use anyhow::{Result, anyhow};
+use log::debug;
+use warp_core::appstate::PatchInfo;
+use warp_core_binary::builder::NodeBuilder;
+use warp_core_binary::node::NodeContent;
+
+use super::Client;
+use crate::request::InfoQuery;
+use crate::utils::jid_utils::server_jid;
+
+impl Client {
+    /// Encodes and sends `info`, then re-syncs its collection.
+    pub(crate) async fn send_app_state_patch(&self, info: &PatchInfo) -> Result<()> {
+        let name = info.name.as_str();
+        let (patch, version) = self.get_app_state_processor().await.encode_patch(info).await?;
+        debug!(target: "Client/AppState", "Sending patch for {} on top of version {}", name, version);
+
+        let collection = NodeBuilder::new("collection")
+            .attr("name", name)
+            .attr("version", version.to_string())
+            .attr("return_snapshot", "false")
+            .children([NodeBuilder::new("patch").bytes(patch).build()])
+            .build();
+        let sync = NodeBuilder::new("sync").children([collection]).build();
+        let iq = InfoQuery::set(
+            "w:sync:app:state",
+            server_jid(),
+            Some(NodeContent::Nodes(vec![sync])),
+        );
+
+        let response = self.send_iq(iq).await?;
+        let rejected = response
+            .get_optional_child_by_tag(&["sync", "collection"])
+            .is_some_and(|collection| collection.attrs().optional_string("type") == Some("error"));
+        if rejected {
+            return Err(anyhow!("app state patch for {} rejected by server", name));
+        }
+
+        self.process_app_state_sync_task(info.name, false).await
+    }
+}
@@ -0,0 +1,238 @@
+//! Bounded history of connection attempts and counters of the retry receipt
+//! flow, used by the diagnostics endpoint to explain failed pairings,
+//! reconnect loops and messages that never decrypt.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+/// Attempts kept in memory per client.
+pub const MAX_CONNECTION_ATTEMPTS: usize = 20;
+
+/// Furthest point of the connection handshake an attempt reached.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum HandshakePhase {
+    /// Opening the WebSocket (HTTP upgrade) and resolving the WA web version.
+    HttpUpgrade,
+    /// ClientHello sent, waiting for the server.
+    ClientHello,
+    /// ServerHello received and verified.
+    ServerHello,
+    /// ClientFinish sent.
+    ClientFinish,
+    /// Noise keys derived; waiting for `<success>`.
+    PostFinish,
+}
+
+/// How an attempt ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AttemptOutcome {
+    InProgress,
+    Connected,
+    Failed,
+    Closed,
+}
+
+/// One connection attempt as seen by the client run loop.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConnectionAttempt {
+    pub id: u64,
+    pub started_at: DateTime<Utc>,
+    pub ended_at: Option<DateTime<Utc>>,
+    pub phase: HandshakePhase,
+    pub outcome: AttemptOutcome,
+    pub wa_version: Option<String>,
+    /// Stream error or connect failure code sent by the server.
+    pub close_code: Option<String>,
+    pub error: Option<String>,
+    /// Reconnect delay scheduled after this attempt.
+    pub backoff_secs: Option<u64>,
+}
+
+/// Thread-safe ring buffer of [`ConnectionAttempt`]s. All updates apply to
+/// the most recent attempt.
+#[derive(Debug)]
+pub struct ConnectionDiagnostics {
+    attempts: Mutex<VecDeque<ConnectionAttempt>>,
+    next_id: AtomicU64,
+}
+
+impl Default for ConnectionDiagnostics {
+    fn default() -> Self {
+        Self {
+            attempts: Mutex::new(VecDeque::with_capacity(MAX_CONNECTION_ATTEMPTS)),
+            next_id: AtomicU64::new(1),
+        }
+    }
+}
+
+impl ConnectionDiagnostics {
+    /// Creates an empty history.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn attempts(&self) -> MutexGuard<'_, VecDeque<ConnectionAttempt>> {
+        // The buffer holds plain data, so a poisoned lock is still usable.
+        self.attempts.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn update_latest(&self, update: impl FnOnce(&mut ConnectionAttempt)) {
+        if let Some(latest) = self.attempts().back_mut() {
+            update(latest);
+        }
+    }
+
+    /// Starts a new attempt in the [`HandshakePhase::HttpUpgrade`] phase.
+    pub fn begin_attempt(&self) -> u64 {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let mut attempts = self.attempts();
+        if attempts.len() == MAX_CONNECTION_ATTEMPTS {
+            attempts.pop_front();
+        }
+        attempts.push_back(ConnectionAttempt {
+            id,
+            started_at: Utc::now(),
+            ended_at: None,
+            phase: HandshakePhase::HttpUpgrade,
+            outcome: AttemptOutcome::InProgress,
+            wa_version: None,
+            close_code: None,
+            error: None,
+            backoff_secs: None,
+        });
+        id
+    }
+
+    /// Records the handshake phase reached by the current attempt.
+    pub fn set_phase(&self, phase: HandshakePhase) {
+        self.update_latest(|attempt| attempt.phase = attempt.phase.max(phase));
+    }
+
+    /// Records the WA web version used by the current attempt.
+    pub fn set_wa_version(&self, version: String) {
+        self.update_latest(|attempt| attempt.wa_version = Some(version));
+    }
+
+    /// Marks the current attempt as logged in.
+    pub fn mark_connected(&self) {
+        self.update_latest(|attempt| attempt.outcome = AttemptOutcome::Connected);
+    }
+
+    /// Marks the current attempt as failed before the session was usable.
+    pub fn mark_failed(&self, error: String) {
+        self.update_latest(|attempt| {
+            attempt.outcome = AttemptOutcome::Failed;
+            attempt.error = Some(error);
+            attempt.ended_at = Some(Utc::now());
+        });
+    }
+
+    /// Records a close code (stream error / connect failure) for the current attempt.
+    pub fn record_close_code(&self, code: String) {
+        self.update_latest(|attempt| attempt.close_code = Some(code));
+    }
+
+    /// Marks the end of an established connection.
+    pub fn mark_closed(&self) {
+        self.update_latest(|attempt| {
+            if attempt.outcome != AttemptOutcome::Failed {
+                attempt.outcome = AttemptOutcome::Closed;
+            }
+            attempt.ended_at.get_or_insert_with(Utc::now);
+        });
+    }
+
+    /// Records the reconnect delay scheduled after the current attempt.
+    pub fn record_backoff(&self, delay_secs: u64) {
+        self.update_latest(|attempt| attempt.backoff_secs = Some(delay_secs));
+    }
+
+    /// Most recent attempts, newest first, at most `limit` entries.
+    pub fn recent(&self, limit: usize) -> Vec<ConnectionAttempt> {
+        self.attempts().iter().rev().take(limit).cloned().collect()
+    }
+}
+
+/// Counters of the retry receipt flow since the client was created.
+///
+/// Outgoing receipts ask a sender to re-encrypt a message we could not
+/// decrypt; incoming ones ask us to resend a message with a fresh session.
+#[derive(Debug, Default)]
+pub struct RetryStats {
+    receipts_sent: AtomicU64,
+    receipt_failures: AtomicU64,
+    exhausted: AtomicU64,
+    retries_received: AtomicU64,
+    retries_ignored: AtomicU64,
+    messages_resent: AtomicU64,
+}
+
+/// Point-in-time copy of [`RetryStats`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RetryStatsSnapshot {
+    /// Retry receipts sent for messages we failed to decrypt.
+    pub receipts_sent: u64,
+    /// Retry receipts that could not be sent.
+    pub receipt_failures: u64,
+    /// Messages that reached the retry limit and fell back to a PDO request.
+    pub exhausted: u64,
+    /// Retry receipts received for messages we sent.
+    pub retries_received: u64,
+    /// Received retries dropped: duplicates, over the limit, unknown device
+    /// or the message no longer cached.
+    pub retries_ignored: u64,
+    /// Messages re-encrypted and sent again after a retry receipt.
+    pub messages_resent: u64,
+}
+
+impl RetryStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record_receipt_sent(&self) {
+        self.receipts_sent.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_receipt_failure(&self) {
+        self.receipt_failures.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_exhausted(&self) {
+        self.exhausted.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_retry_received(&self) {
+        self.retries_received.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_retry_ignored(&self) {
+        self.retries_ignored.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_message_resent(&self) {
+        self.messages_resent.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> RetryStatsSnapshot {
+        RetryStatsSnapshot {
+            receipts_sent: self.receipts_sent.load(Ordering::Relaxed),
+            receipt_failures: self.receipt_failures.load(Ordering::Relaxed),
+            exhausted: self.exhausted.load(Ordering::Relaxed),
+            retries_received: self.retries_received.load(Ordering::Relaxed),
+            retries_ignored: self.retries_ignored.load(Ordering::Relaxed),
+            messages_resent: self.messages_resent.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    include!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/tests/client/diagnostics_tests.rs"));
+}
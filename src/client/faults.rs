@@ -0,0 +1,125 @@
+//! Fault injection for resilience tests, compiled only with the `chaos`
+//! feature.
+//!
+//! Every fault hooks into the path a real failure takes: a dropped socket
+//! closes the live transport without marking the disconnect as expected,
+//! a corrupted frame fails decryption in the read loop, a send delay stalls
+//! [`Client::send_node`](super::Client::send_node) and a handshake failure
+//! aborts the next `connect` at the chosen phase.
+
+use super::HandshakePhase;
+use bytes::Bytes;
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Mutex, PoisonError};
+use std::time::Duration;
+
+/// Faults armed on one client.
+#[derive(Debug, Default)]
+pub struct FaultInjector {
+    corrupt_next_frame: AtomicBool,
+    send_delay_ms: AtomicU64,
+    fail_handshake_at: Mutex<Option<HandshakePhase>>,
+}
+
+/// Snapshot of the armed faults.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FaultState {
+    pub corrupt_next_frame: bool,
+    pub send_delay_ms: u64,
+    pub fail_handshake_at: Option<HandshakePhase>,
+}
+
+impl FaultInjector {
+    /// Corrupts the next chunk received from the server.
+    pub fn corrupt_next_frame(&self) {
+        self.corrupt_next_frame.store(true, Ordering::SeqCst);
+    }
+
+    /// Delays every outgoing node by `delay`; zero turns the delay off.
+    pub fn set_send_delay(&self, delay: Duration) {
+        let millis = u64::try_from(delay.as_millis()).unwrap_or(u64::MAX);
+        self.send_delay_ms.store(millis, Ordering::SeqCst);
+    }
+
+    /// Fails the next connection attempt once it reaches `phase`; `None`
+    /// disarms it.
+    pub fn fail_handshake_at(&self, phase: Option<HandshakePhase>) {
+        *self
+            .fail_handshake_at
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = phase;
+    }
+
+    /// Disarms every fault.
+    pub fn clear(&self) {
+        self.corrupt_next_frame.store(false, Ordering::SeqCst);
+        self.set_send_delay(Duration::ZERO);
+        self.fail_handshake_at(None);
+    }
+
+    pub fn state(&self) -> FaultState {
+        FaultState {
+            corrupt_next_frame: self.corrupt_next_frame.load(Ordering::SeqCst),
+            send_delay_ms: self.send_delay_ms.load(Ordering::SeqCst),
+            fail_handshake_at: *self
+                .fail_handshake_at
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner),
+        }
+    }
+
+    /// `data` with its last byte flipped when a corruption is armed. The
+    /// Noise frame then fails its authentication tag.
+    pub(crate) fn corrupt(&self, data: Bytes) -> Bytes {
+        if data.is_empty() || !self.corrupt_next_frame.swap(false, Ordering::SeqCst) {
+            return data;
+        }
+        let mut corrupted = data.to_vec();
+        if let Some(last) = corrupted.last_mut() {
+            *last ^= 0xff;
+        }
+        Bytes::from(corrupted)
+    }
+
+    pub(crate) fn send_delay(&self) -> Option<Duration> {
+        match self.send_delay_ms.load(Ordering::SeqCst) {
+            0 => None,
+            millis => Some(Duration::from_millis(millis)),
+        }
+    }
+
+    /// Whether the attempt must fail at `phase`. Fires once.
+    pub(crate) fn take_handshake_failure(&self, phase: HandshakePhase) -> bool {
+        let mut armed = self
+            .fail_handshake_at
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if *armed == Some(phase) {
+            *armed = None;
+            return true;
+        }
+        false
+    }
+}
+
+impl super::Client {
+    /// Closes the live transport as if the server dropped it, so the run
+    /// loop takes its unexpected-disconnect path. Returns whether a
+    /// transport was open.
+    pub async fn inject_disconnect(&self) -> bool {
+        match self.transport.lock().await.as_ref() {
+            Some(transport) => {
+                transport.disconnect().await;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    include!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/tests/client/faults_tests.rs"));
+}
@@ -0,0 +1,124 @@
+//! Process-wide limit on simultaneous connection attempts.
+//!
+//! When many instances reconnect at once, at boot for instance, every runner
+//! dials WhatsApp in the same second and trips its rate limits. Clients
+//! sharing a [`HandshakeGate`] wait a random delay of up to `jitter`, then
+//! queue for one of `max_concurrent` permits, held from the dial until the
+//! handshake ends. Permits are handed out in arrival order.
+
+use rand::Rng;
+use serde::Serialize;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tracing::debug;
+
+const DEFAULT_MAX_CONCURRENT: usize = 8;
+const DEFAULT_JITTER_MS: u64 = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HandshakeConfig {
+    /// Connection attempts in flight at once (`HANDSHAKE_MAX_CONCURRENT`).
+    pub max_concurrent: usize,
+    /// Upper bound of the random delay before queuing
+    /// (`HANDSHAKE_JITTER_MS`); zero disables it.
+    pub jitter: Duration,
+}
+
+impl Default for HandshakeConfig {
+    fn default() -> Self {
+        Self {
+            max_concurrent: DEFAULT_MAX_CONCURRENT,
+            jitter: Duration::from_millis(DEFAULT_JITTER_MS),
+        }
+    }
+}
+
+/// Counters of a [`HandshakeGate`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HandshakeGateStats {
+    pub max_concurrent: usize,
+    pub in_flight: usize,
+    /// Attempts queued for a permit, jitter delay excluded.
+    pub waiting: usize,
+}
+
+/// Semaphore shared by the clients of the process.
+#[derive(Debug)]
+pub struct HandshakeGate {
+    config: HandshakeConfig,
+    permits: Arc<Semaphore>,
+    waiting: AtomicUsize,
+}
+
+/// Held for the duration of one connection attempt.
+#[derive(Debug)]
+pub struct HandshakePermit {
+    _permit: Option<OwnedSemaphorePermit>,
+}
+
+impl HandshakeGate {
+    pub fn new(config: HandshakeConfig) -> Self {
+        let config = HandshakeConfig {
+            max_concurrent: config.max_concurrent.max(1),
+            ..config
+        };
+        Self {
+            permits: Arc::new(Semaphore::new(config.max_concurrent)),
+            config,
+            waiting: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn config(&self) -> &HandshakeConfig {
+        &self.config
+    }
+
+    /// Waits the jitter delay, then for a free permit.
+    pub async fn enter(&self) -> HandshakePermit {
+        let delay = self.jitter_delay();
+        if !delay.is_zero() {
+            tokio::time::sleep(delay).await;
+        }
+
+        let waiting = self.waiting.fetch_add(1, Ordering::SeqCst) + 1;
+        if self.permits.available_permits() == 0 {
+            debug!(
+                waiting,
+                max_concurrent = self.config.max_concurrent,
+                "Waiting for a handshake slot"
+            );
+        }
+        let _queued = scopeguard::guard((), |_| {
+            self.waiting.fetch_sub(1, Ordering::SeqCst);
+        });
+        // Acquiring only fails on a closed semaphore, which is never closed.
+        HandshakePermit {
+            _permit: self.permits.clone().acquire_owned().await.ok(),
+        }
+    }
+
+    /// Random delay in `[0, jitter]`.
+    pub fn jitter_delay(&self) -> Duration {
+        let max = u64::try_from(self.config.jitter.as_millis()).unwrap_or(u64::MAX);
+        if max == 0 {
+            return Duration::ZERO;
+        }
+        Duration::from_millis(rand::rng().random_range(0..=max))
+    }
+
+    pub fn stats(&self) -> HandshakeGateStats {
+        HandshakeGateStats {
+            max_concurrent: self.config.max_concurrent,
+            in_flight: self.config.max_concurrent - self.permits.available_permits(),
+            waiting: self.waiting.load(Ordering::SeqCst),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    include!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/tests/client/handshake_gate_tests.rs"));
+}
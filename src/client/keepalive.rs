@@ -13,6 +13,14 @@ const KEEP_ALIVE_MAX_FAIL_TIME: Duration = Duration::from_secs(180);
 const KEEP_ALIVE_RESPONSE_DEADLINE: Duration = Duration::from_secs(20);
 
 impl Client {
+    /// Sends a `w:p` ping and returns the round-trip time.
+    pub async fn ping(&self, timeout: Duration) -> Result<Duration, IqError> {
+        let started = std::time::Instant::now();
+        let iq = InfoQuery::get("w:p", server_jid(), None).with_timeout(timeout);
+        self.send_iq(iq).await?;
+        Ok(started.elapsed())
+    }
+
     async fn send_keepalive(&self) -> bool {
         if !self.is_connected() {
             return false;
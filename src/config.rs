@@ -1,7 +1,111 @@
+//! Every setting read from the environment.
+//!
+//! Values go through one parser, [`Env`]: they are trimmed, blank ones
+//! count as unset and unreadable ones fall back to the default (the
+//! preflight check reports them). [`StartupConfig`] holds what logging
+//! needs before the first line is written, [`ServerConfig`] the rest of
+//! what `serve` hands to the modules. The setting types and their defaults
+//! are defined here too; the server modules re-export them and add the
+//! behaviour, so this module never reaches into `crate::server`.
+
+use std::collections::{BTreeMap, BTreeSet};
 use std::env;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
 
+use crate::client::HandshakeConfig;
 use crate::error::AppError;
+use crate::version::{VersionConfig, VersionConfigError, VersionSource, parse_version};
 use log::error;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tracing::warn;
+use uuid::Uuid;
+use warp_core::store::secrets::{Keyring, MasterKey};
+
+/// Window auth state writes are coalesced in unless `AUTH_SAVE_DEBOUNCE_MS`
+/// says otherwise.
+const DEFAULT_SAVE_DEBOUNCE: Duration = Duration::from_millis(500);
+const DEFAULT_SESSION_TTL_SECONDS: u64 = 1800;
+const DEFAULT_PORT: u16 = 8080;
+
+type Lookup<'a> = Box<dyn Fn(&str) -> Option<String> + 'a>;
+
+/// Source of setting values: the process environment, or a lookup in tests.
+pub struct Env<'a> {
+    lookup: Lookup<'a>,
+}
+
+impl Env<'static> {
+    /// The process environment.
+    pub fn process() -> Self {
+        Self::from_lookup(|name| env::var(name).ok())
+    }
+}
+
+impl<'a> Env<'a> {
+    pub fn from_lookup(lookup: impl Fn(&str) -> Option<String> + 'a) -> Self {
+        Self {
+            lookup: Box::new(lookup),
+        }
+    }
+
+    /// Trimmed value; `Some("")` when the variable is set but blank.
+    pub fn get(&self, name: &str) -> Option<String> {
+        (self.lookup)(name).map(|v| v.trim().to_string())
+    }
+
+    /// Trimmed value, `None` when unset or blank.
+    pub fn text(&self, name: &str) -> Option<String> {
+        self.get(name).filter(|v| !v.is_empty())
+    }
+
+    /// Flags are on only with `true` (any case) or `1`.
+    pub fn flag(&self, name: &str) -> bool {
+        self.text(name)
+            .is_some_and(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+    }
+
+    /// Switches on by default are off only with `false`, `0`, `no` or `off`.
+    pub fn enabled(&self, name: &str) -> bool {
+        self.text(name).is_none_or(|v| {
+            !matches!(
+                v.to_ascii_lowercase().as_str(),
+                "false" | "0" | "no" | "off"
+            )
+        })
+    }
+
+    /// The value parsed as `T`; `None` when unset or unreadable.
+    pub fn number<T: FromStr>(&self, name: &str) -> Option<T> {
+        self.text(name)?.parse().ok()
+    }
+
+    /// Like [`number`](Self::number), `0` and below also count as unset.
+    pub fn positive<T: FromStr + PartialOrd + Default>(&self, name: &str) -> Option<T> {
+        self.number(name).filter(|v| *v > T::default())
+    }
+
+    /// Comma-separated items, trimmed, blank ones dropped.
+    pub fn list(&self, name: &str) -> Option<Vec<String>> {
+        self.get(name).map(|raw| {
+            raw.split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .map(str::to_string)
+                .collect()
+        })
+    }
+
+    fn secs(&self, name: &str) -> Option<Duration> {
+        self.number(name).map(Duration::from_secs)
+    }
+
+    fn millis(&self, name: &str) -> Option<Duration> {
+        self.number(name).map(Duration::from_millis)
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct AppConfig {
@@ -47,3 +151,1671 @@ impl AppConfig {
         })
     }
 }
+
+/// Reason given for `DATABASE_PROVIDER=mysql` or a `mysql://` URL. There
+/// is no MySQL storage crate next to `storages/postgres-storage` and
+/// `storages/sqlite-storage`, so MySQL is named instead of being reported
+/// as an unknown provider.
+const MYSQL_UNSUPPORTED: &str =
+    "MySQL is not supported; use postgresql or sqlite (see docs/ENV.md)";
+
+/// Storage engine behind the WhatsApp store and the HTTP API tables.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DatabaseProvider {
+    Postgresql,
+    Sqlite,
+}
+
+impl DatabaseProvider {
+    /// Parses a `DATABASE_PROVIDER` value.
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "postgresql" | "postgres" | "pg" => Some(Self::Postgresql),
+            "sqlite" => Some(Self::Sqlite),
+            _ => None,
+        }
+    }
+
+    /// Name used in logs and `/manager/status`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Postgresql => "postgresql",
+            Self::Sqlite => "sqlite",
+        }
+    }
+
+    /// Whether `value` names MySQL (or MariaDB), as a `DATABASE_PROVIDER`
+    /// or as the scheme of a `DATABASE_URL`.
+    fn is_mysql(value: &str) -> bool {
+        let value = value.trim().to_ascii_lowercase();
+        ["mysql", "mariadb"]
+            .iter()
+            .any(|name| value == *name || value.starts_with(&format!("{name}://")))
+    }
+
+    /// Provider implied by the scheme of a database URL: SQLite for file
+    /// paths and `file:`/`sqlite:` URLs, `None` for any other scheme.
+    pub fn from_url(url: &str) -> Option<Self> {
+        if url.starts_with("postgres://") || url.starts_with("postgresql://") {
+            Some(Self::Postgresql)
+        } else if url.contains("://") && !url.starts_with("file:") && !url.starts_with("sqlite:") {
+            None
+        } else {
+            Some(Self::Sqlite)
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DatabaseConfig {
+    pub provider: DatabaseProvider,
+    /// `None` uses the default SQLite file.
+    pub url: Option<String>,
+    /// Apply pending schema migrations at startup (`DATABASE_RUN_MIGRATIONS`, default true).
+    pub run_migrations: bool,
+    pub pool: PoolConfig,
+    /// Master keys encrypting auth keys and instance tokens at rest;
+    /// `None` stores them in plaintext.
+    pub secrets: Option<Keyring>,
+}
+
+/// Connection pool overrides; `None` keeps the backend default.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PoolConfig {
+    /// `DATABASE_POOL_MAX`
+    pub max_size: Option<u32>,
+    /// `DATABASE_POOL_MIN` (idle connections kept open)
+    pub min_idle: Option<u32>,
+    /// `DATABASE_POOL_ACQUIRE_TIMEOUT` in seconds
+    pub acquire_timeout: Option<Duration>,
+}
+
+impl PoolConfig {
+    fn read(env: &Env) -> Result<Self, AppError> {
+        let number = |name: &'static str| -> Result<Option<u32>, AppError> {
+            match env.text(name) {
+                Some(raw) => raw.parse::<u32>().map(Some).map_err(|_| AppError::InvalidEnv {
+                    name,
+                    reason: format!("expected a non-negative integer, got {raw:?}"),
+                }),
+                None => Ok(None),
+            }
+        };
+
+        let config = Self {
+            max_size: number("DATABASE_POOL_MAX")?,
+            min_idle: number("DATABASE_POOL_MIN")?,
+            acquire_timeout: number("DATABASE_POOL_ACQUIRE_TIMEOUT")?
+                .map(|secs| Duration::from_secs(u64::from(secs))),
+        };
+        if config.max_size == Some(0) {
+            return Err(AppError::InvalidEnv {
+                name: "DATABASE_POOL_MAX",
+                reason: "must be at least 1".to_owned(),
+            });
+        }
+        if let (Some(min), Some(max)) = (config.min_idle, config.max_size)
+            && min > max
+        {
+            return Err(AppError::InvalidEnv {
+                name: "DATABASE_POOL_MIN",
+                reason: format!("{min} is above DATABASE_POOL_MAX={max}"),
+            });
+        }
+        if config.acquire_timeout == Some(Duration::ZERO) {
+            return Err(AppError::InvalidEnv {
+                name: "DATABASE_POOL_ACQUIRE_TIMEOUT",
+                reason: "must be at least 1 second".to_owned(),
+            });
+        }
+        Ok(config)
+    }
+}
+
+impl DatabaseConfig {
+    /// Reads `DATABASE_PROVIDER`, `DATABASE_URL`, `DATABASE_RUN_MIGRATIONS`
+    /// and the `SECRETS_*` master keys. Without a provider it is inferred
+    /// from the URL scheme (SQLite when no URL is set).
+    pub fn from_env() -> Result<Self, AppError> {
+        Self::read(&Env::process())
+    }
+
+    pub(crate) fn read(env: &Env) -> Result<Self, AppError> {
+        let mut config = Self::from_values(env.get("DATABASE_PROVIDER"), env.get("DATABASE_URL"))?;
+        if let Some(raw) = env.text("DATABASE_RUN_MIGRATIONS") {
+            config.run_migrations = parse_flag(&raw).ok_or_else(|| AppError::InvalidEnv {
+                name: "DATABASE_RUN_MIGRATIONS",
+                reason: format!("expected true or false, got {raw:?}"),
+            })?;
+        }
+        config.pool = PoolConfig::read(env)?;
+        config.secrets = keyring(env)?;
+        Ok(config)
+    }
+
+    fn from_values(provider: Option<String>, url: Option<String>) -> Result<Self, AppError> {
+        let url = url.filter(|u| !u.trim().is_empty());
+        let from_url = url.as_deref().map(DatabaseProvider::from_url);
+        if from_url == Some(None) {
+            let mysql = url.as_deref().is_some_and(DatabaseProvider::is_mysql);
+            return Err(AppError::InvalidEnv {
+                name: "DATABASE_URL",
+                reason: if mysql {
+                    MYSQL_UNSUPPORTED.to_owned()
+                } else {
+                    "unsupported URL scheme (expected postgresql:// or a SQLite file)".to_owned()
+                },
+            });
+        }
+        let provider = match provider.filter(|p| !p.trim().is_empty()) {
+            Some(raw) if DatabaseProvider::is_mysql(&raw) => {
+                return Err(AppError::InvalidEnv {
+                    name: "DATABASE_PROVIDER",
+                    reason: MYSQL_UNSUPPORTED.to_owned(),
+                });
+            }
+            Some(raw) => DatabaseProvider::parse(&raw).ok_or_else(|| AppError::InvalidEnv {
+                name: "DATABASE_PROVIDER",
+                reason: format!("unknown provider {raw:?} (expected postgresql or sqlite)"),
+            })?,
+            None => from_url.flatten().unwrap_or(DatabaseProvider::Sqlite),
+        };
+
+        if let Some(Some(implied)) = from_url
+            && implied != provider
+        {
+            return Err(AppError::InvalidEnv {
+                name: "DATABASE_URL",
+                reason: format!("URL scheme does not match DATABASE_PROVIDER={provider:?}"),
+            });
+        }
+        if provider == DatabaseProvider::Postgresql && url.is_none() {
+            return Err(AppError::MissingEnv("DATABASE_URL"));
+        }
+
+        Ok(Self {
+            provider,
+            url,
+            run_migrations: true,
+            pool: PoolConfig::default(),
+            secrets: None,
+        })
+    }
+}
+
+/// `SECRETS_MASTER_KEY` or the file named by `SECRETS_MASTER_KEY_FILE`
+/// (e.g. mounted by a KMS or secret manager agent) holds the current key,
+/// `SECRETS_PREVIOUS_KEYS` the comma-separated keys it replaced.
+fn keyring(env: &Env) -> Result<Option<Keyring>, AppError> {
+    let invalid = |name: &'static str, reason: String| AppError::InvalidEnv { name, reason };
+
+    let inline = env.text("SECRETS_MASTER_KEY");
+    let file = env.text("SECRETS_MASTER_KEY_FILE");
+    let previous = env.text("SECRETS_PREVIOUS_KEYS");
+    let (name, current) = match (inline, file) {
+        (Some(_), Some(_)) => {
+            return Err(invalid(
+                "SECRETS_MASTER_KEY_FILE",
+                "set either SECRETS_MASTER_KEY or SECRETS_MASTER_KEY_FILE".to_owned(),
+            ));
+        }
+        (Some(key), None) => ("SECRETS_MASTER_KEY", key),
+        (None, Some(path)) => {
+            let key = std::fs::read_to_string(&path)
+                .map_err(|e| invalid("SECRETS_MASTER_KEY_FILE", format!("{path}: {e}")))?;
+            ("SECRETS_MASTER_KEY_FILE", key)
+        }
+        (None, None) if previous.is_some() => {
+            return Err(invalid(
+                "SECRETS_PREVIOUS_KEYS",
+                "requires SECRETS_MASTER_KEY".to_owned(),
+            ));
+        }
+        (None, None) => return Ok(None),
+    };
+    MasterKey::parse(&current).map_err(|e| invalid(name, e.to_string()))?;
+    Keyring::parse(&current, previous.as_deref().unwrap_or_default())
+        .map(Some)
+        .map_err(|e| invalid("SECRETS_PREVIOUS_KEYS", e.to_string()))
+}
+
+const DEFAULT_INSTANCE_LOG_BUFFER: usize = 500;
+/// How long `whatsappNumbers` answers are cached without
+/// `WHATSAPP_NUMBERS_CACHE_SECONDS`.
+pub const DEFAULT_NUMBERS_CACHE_TTL: Duration = Duration::from_secs(3600);
+/// In-memory budget of the static file cache without `STATIC_CACHE_MB`.
+pub const DEFAULT_STATIC_CACHE_MB: u64 = 32;
+
+/// Settings read before the first log line: the runtime settings (whose log
+/// level the subscriber starts with), the log outputs and error reporting.
+pub struct StartupConfig {
+    pub runtime: RuntimeConfig,
+    pub logging: LoggingConfig,
+    /// Entries kept per instance for `/instance/logs` (`INSTANCE_LOG_BUFFER`,
+    /// default 500); `0` disables the tail.
+    pub instance_log_buffer: usize,
+    /// `None` without a valid `SENTRY_DSN`.
+    #[cfg(feature = "sentry")]
+    pub sentry: Option<SentryConfig>,
+}
+
+impl StartupConfig {
+    pub fn from_env() -> Self {
+        Self::read(&Env::process())
+    }
+
+    pub(crate) fn read(env: &Env) -> Self {
+        Self {
+            runtime: runtime(env),
+            logging: logging(env),
+            instance_log_buffer: env
+                .number("INSTANCE_LOG_BUFFER")
+                .unwrap_or(DEFAULT_INSTANCE_LOG_BUFFER),
+            #[cfg(feature = "sentry")]
+            sentry: sentry(env),
+        }
+    }
+}
+
+/// Settings of the HTTP server and the instances it runs.
+pub struct ServerConfig {
+    pub database: DatabaseConfig,
+    /// `PORT`, default 8080.
+    pub port: u16,
+    /// `CHATWARP_PASSWORD`, taken as is; `None` leaves the API open.
+    pub api_password: Option<String>,
+    /// `CHATWARP_SESSION_TTL_SECONDS`, default 1800.
+    pub session_ttl_seconds: u64,
+    /// Whether the API key allowlists check the `X-Forwarded-For` set by a
+    /// reverse proxy instead of the peer address (`AUTH_TRUST_FORWARDED_FOR`).
+    pub trust_forwarded_for: bool,
+    /// How long auth state writes are coalesced (`AUTH_SAVE_DEBOUNCE_MS`,
+    /// `0` writes every change through).
+    pub save_debounce: Duration,
+    pub restart_policy: RestartPolicy,
+    pub tls: TlsMode,
+    pub http: HttpClientConfig,
+    pub cache: CacheConfig,
+    #[cfg(feature = "nats")]
+    pub nats: Option<NatsConfig>,
+    pub ws: WsConfig,
+    pub global_sinks: InstanceScope,
+    pub sse: SseConfig,
+    pub event_history: EventHistoryConfig,
+    pub meta: MetaConfig,
+    /// How long `/chat/whatsappNumbers` answers are cached
+    /// (`WHATSAPP_NUMBERS_CACHE_SECONDS`, default 3600); zero disables it.
+    pub numbers_cache_ttl: Duration,
+    pub profile_pictures: ProfilePictureConfig,
+    pub inbound_dedup: DedupConfig,
+    pub retention: RetentionConfig,
+    pub uploads: UploadConfig,
+    pub thumbnails: ThumbnailConfig,
+    pub timeouts: TimeoutConfig,
+    pub exports: ExportConfig,
+    /// Bytes of static files kept in memory (`STATIC_CACHE_MB`, default 32);
+    /// zero disables the cache.
+    pub static_cache_bytes: u64,
+    pub api_mount: ApiMount,
+    pub health: HealthConfig,
+    pub participants: ParticipantConfig,
+    pub handshake: HandshakeConfig,
+    pub standby: StandbyConfig,
+    pub outbox: OutboxConfig,
+    /// WA web version policy of every client; an invalid one is ignored.
+    pub wa_version: VersionConfig,
+}
+
+impl ServerConfig {
+    /// Fails on an invalid database or TLS setup; other invalid values fall
+    /// back to their defaults.
+    pub fn from_env() -> Result<Self, AppError> {
+        Self::read(&Env::process())
+    }
+
+    pub(crate) fn read(env: &Env) -> Result<Self, AppError> {
+        Ok(Self {
+            database: DatabaseConfig::read(env)?,
+            port: env.number("PORT").unwrap_or(DEFAULT_PORT),
+            // Spaces are part of the password.
+            api_password: (env.lookup)("CHATWARP_PASSWORD").filter(|v| !v.is_empty()),
+            session_ttl_seconds: env
+                .number("CHATWARP_SESSION_TTL_SECONDS")
+                .unwrap_or(DEFAULT_SESSION_TTL_SECONDS),
+            trust_forwarded_for: env.flag("AUTH_TRUST_FORWARDED_FOR"),
+            save_debounce: env
+                .millis("AUTH_SAVE_DEBOUNCE_MS")
+                .unwrap_or(DEFAULT_SAVE_DEBOUNCE),
+            restart_policy: restart_policy(env),
+            tls: tls(env).map_err(|e| AppError::InvalidEnv {
+                name: e.variable(),
+                reason: e.to_string(),
+            })?,
+            http: http_client(env),
+            cache: cache(env),
+            #[cfg(feature = "nats")]
+            nats: nats(env),
+            ws: ws(env),
+            global_sinks: global_sinks(env),
+            sse: sse(env),
+            event_history: event_history(env),
+            meta: meta(env),
+            numbers_cache_ttl: env
+                .secs("WHATSAPP_NUMBERS_CACHE_SECONDS")
+                .unwrap_or(DEFAULT_NUMBERS_CACHE_TTL),
+            profile_pictures: ProfilePictureConfig {
+                ttl: env
+                    .secs("PROFILE_PICTURE_CACHE_SECONDS")
+                    .unwrap_or(ProfilePictureConfig::default().ttl),
+            },
+            inbound_dedup: inbound_dedup(env),
+            retention: retention(env),
+            uploads: uploads(env),
+            thumbnails: thumbnails(env),
+            timeouts: timeouts(env),
+            exports: exports(env),
+            static_cache_bytes: env
+                .number("STATIC_CACHE_MB")
+                .unwrap_or(DEFAULT_STATIC_CACHE_MB)
+                .saturating_mul(1024 * 1024),
+            api_mount: ApiMount {
+                legacy_routes: env.enabled("LEGACY_ROUTES"),
+            },
+            health: health(env),
+            participants: participants(env),
+            handshake: handshake(env),
+            standby: standby(env),
+            outbox: outbox(env),
+            wa_version: wa_version(env).unwrap_or_else(|e| {
+                warn!("Ignoring WA version config: {e}");
+                VersionConfig::default()
+            }),
+        })
+    }
+}
+
+const DEFAULT_PROFILE_PICTURE_TTL: Duration = Duration::from_secs(24 * 3600);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProfilePictureConfig {
+    /// How long answers without an expiry are cached
+    /// (`PROFILE_PICTURE_CACHE_SECONDS`); zero disables the cache.
+    pub ttl: Duration,
+}
+
+impl Default for ProfilePictureConfig {
+    fn default() -> Self {
+        Self {
+            ttl: DEFAULT_PROFILE_PICTURE_TTL,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ApiMount {
+    /// Whether the pre-`/api/v1` root paths are still served.
+    pub legacy_routes: bool,
+}
+
+impl Default for ApiMount {
+    fn default() -> Self {
+        Self {
+            legacy_routes: true,
+        }
+    }
+}
+
+/// QR images change every ~20s, so they are only cached briefly.
+const DEFAULT_QR_CACHE_SECONDS: u32 = 5;
+/// Media above this size is not inlined in webhooks, to keep bodies small.
+const DEFAULT_WEBHOOK_BASE64_MAX_MB: u32 = 5;
+/// Smallest edge (in pixels) accepted for image renders.
+pub const MIN_QR_IMAGE_SIZE: u32 = 64;
+/// Largest edge (in pixels) accepted for image renders.
+pub const MAX_QR_IMAGE_SIZE: u32 = 1024;
+/// Edge used when the caller does not ask for a specific size.
+pub const DEFAULT_QR_IMAGE_SIZE: u32 = 300;
+
+/// Global webhook used when an instance has no webhook of its own.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct GlobalWebhook {
+    pub enabled: bool,
+    pub url: Option<String>,
+    pub by_events: bool,
+    pub base64: bool,
+    /// Static headers sent with every delivery, e.g. `Authorization`.
+    pub headers: BTreeMap<String, String>,
+    /// Signs deliveries with `X-Chatwarp-Signature` when set.
+    pub secret: Option<String>,
+}
+
+/// Settings safe to change without a restart.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RuntimeConfig {
+    /// `EnvFilter` directive, e.g. `info` or `info,chatwarp_api=debug`.
+    pub log_level: String,
+    /// Per-target levels added to `log_level`, e.g. `{"warp_core": "debug"}`.
+    pub log_targets: BTreeMap<String, String>,
+    /// Allowed CORS origins of the API; empty disables CORS, `*` allows any
+    /// origin.
+    pub cors_origins: Vec<String>,
+    /// Allowed CORS origins of the `/manager` routes.
+    pub manager_cors_origins: Vec<String>,
+    /// Allowed origins of `/ws`; `None` follows `cors_origins`.
+    pub ws_cors_origins: Option<Vec<String>>,
+    /// HTTP requests accepted per minute across the API; 0 disables the limit.
+    pub rate_limit_per_minute: u32,
+    pub webhook: GlobalWebhook,
+    /// Largest media inlined as `base64` in `MESSAGES_UPSERT` webhooks, in
+    /// MiB; 0 means unlimited.
+    pub webhook_base64_max_mb: u32,
+    /// Default edge in pixels of `/instance/qrcode/{name}.png|.svg`.
+    pub qr_image_size: u32,
+    /// `Cache-Control: max-age` of the QR images; 0 disables caching.
+    pub qr_cache_seconds: u32,
+    /// Instances across the deployment; 0 means unlimited.
+    pub max_instances: u32,
+    /// Instances per workspace; 0 means unlimited.
+    pub max_instances_per_workspace: u32,
+    /// Messages each instance may send per UTC day; 0 means unlimited.
+    pub max_messages_per_day: u32,
+    /// Largest media file an instance may send, in MiB; 0 means unlimited.
+    pub max_media_size_mb: u32,
+    /// `/readyz` answers 503 and the outbound queues (messages, webhooks)
+    /// stop claiming jobs; WhatsApp sockets stay connected.
+    pub maintenance_mode: bool,
+    /// The Evolution-style routes answer with the Evolution API v2 shapes
+    /// (`EVOLUTION_COMPAT`).
+    pub evolution_compat: bool,
+}
+
+impl Default for RuntimeConfig {
+    fn default() -> Self {
+        Self {
+            log_level: "info".to_string(),
+            log_targets: BTreeMap::new(),
+            cors_origins: Vec::new(),
+            manager_cors_origins: Vec::new(),
+            ws_cors_origins: None,
+            rate_limit_per_minute: 0,
+            webhook: GlobalWebhook::default(),
+            webhook_base64_max_mb: DEFAULT_WEBHOOK_BASE64_MAX_MB,
+            qr_image_size: DEFAULT_QR_IMAGE_SIZE,
+            qr_cache_seconds: DEFAULT_QR_CACHE_SECONDS,
+            max_instances: 0,
+            max_instances_per_workspace: 0,
+            max_messages_per_day: 0,
+            max_media_size_mb: 0,
+            maintenance_mode: false,
+            evolution_compat: false,
+        }
+    }
+}
+
+/// `LOG_LEVEL` (or `RUST_LOG`), `LOG_TARGETS` (`target=level,...`),
+/// `CORS_ORIGINS`, `MANAGER_CORS_ORIGINS` (the origin of `SERVER_URL` when
+/// unset), `WS_CORS_ORIGINS`, `RATE_LIMIT_PER_MINUTE`, `WEBHOOK_GLOBAL_*`
+/// (`WEBHOOK_GLOBAL_HEADERS` is a JSON object of header names to values),
+/// `WEBHOOK_BASE64_MAX_MB`, `QR_IMAGE_SIZE`, `QR_CACHE_SECONDS`, the quotas
+/// `MAX_INSTANCES`, `MAX_INSTANCES_PER_WORKSPACE`, `MAX_MESSAGES_PER_DAY` and
+/// `MAX_MEDIA_SIZE_MB`, `MAINTENANCE_MODE` and `EVOLUTION_COMPAT`.
+fn runtime(env: &Env) -> RuntimeConfig {
+    let defaults = RuntimeConfig::default();
+    let limit = |name: &str| env.number(name).unwrap_or(0);
+    RuntimeConfig {
+        log_level: env
+            .text("LOG_LEVEL")
+            .or_else(|| env.text("RUST_LOG"))
+            .unwrap_or(defaults.log_level),
+        log_targets: env
+            .list("LOG_TARGETS")
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|pair| pair.split_once('='))
+            .map(|(target, level)| (target.trim().to_string(), level.trim().to_string()))
+            .filter(|(target, level)| !target.is_empty() && !level.is_empty())
+            .collect(),
+        cors_origins: env.list("CORS_ORIGINS").unwrap_or_default(),
+        manager_cors_origins: env.list("MANAGER_CORS_ORIGINS").unwrap_or_else(|| {
+            env.text("SERVER_URL")
+                .as_deref()
+                .and_then(url_origin)
+                .into_iter()
+                .collect()
+        }),
+        ws_cors_origins: env.list("WS_CORS_ORIGINS"),
+        rate_limit_per_minute: limit("RATE_LIMIT_PER_MINUTE"),
+        webhook: GlobalWebhook {
+            enabled: env.flag("WEBHOOK_GLOBAL_ENABLED"),
+            url: env.text("WEBHOOK_GLOBAL_URL"),
+            by_events: env.flag("WEBHOOK_GLOBAL_WEBHOOK_BY_EVENTS"),
+            base64: env.flag("WEBHOOK_GLOBAL_WEBHOOK_BASE64"),
+            headers: env
+                .text("WEBHOOK_GLOBAL_HEADERS")
+                .and_then(|raw| serde_json::from_str(&raw).ok())
+                .unwrap_or_default(),
+            secret: env.text("WEBHOOK_GLOBAL_SECRET"),
+        },
+        webhook_base64_max_mb: env
+            .number("WEBHOOK_BASE64_MAX_MB")
+            .unwrap_or(defaults.webhook_base64_max_mb),
+        qr_image_size: env
+            .number("QR_IMAGE_SIZE")
+            .unwrap_or(defaults.qr_image_size)
+            .clamp(MIN_QR_IMAGE_SIZE, MAX_QR_IMAGE_SIZE),
+        qr_cache_seconds: env
+            .number("QR_CACHE_SECONDS")
+            .unwrap_or(defaults.qr_cache_seconds),
+        max_instances: limit("MAX_INSTANCES"),
+        max_instances_per_workspace: limit("MAX_INSTANCES_PER_WORKSPACE"),
+        max_messages_per_day: limit("MAX_MESSAGES_PER_DAY"),
+        max_media_size_mb: limit("MAX_MEDIA_SIZE_MB"),
+        maintenance_mode: env.flag("MAINTENANCE_MODE"),
+        evolution_compat: env.flag("EVOLUTION_COMPAT"),
+    }
+}
+
+/// `scheme://host[:port]` of `url`.
+fn url_origin(url: &str) -> Option<String> {
+    let (scheme, rest) = url.split_once("://")?;
+    let authority = rest.split(['/', '?', '#']).next()?;
+    (matches!(scheme, "http" | "https") && !authority.is_empty())
+        .then(|| format!("{scheme}://{authority}"))
+}
+
+/// Host of an http(s) URL, without port.
+fn url_host(url: &str) -> Option<String> {
+    let (_, rest) = url.split_once("://")?;
+    let authority = rest.split(['/', '?', '#']).next()?;
+    let host = authority
+        .rsplit_once('@')
+        .map_or(authority, |(_, host)| host);
+    let host = host.split(':').next()?.to_ascii_lowercase();
+    (!host.is_empty()).then_some(host)
+}
+
+const DEFAULT_LOG_FILE_MAX_MB: u64 = 100;
+const DEFAULT_LOG_FILE_MAX_FILES: u32 = 5;
+
+/// Line format of the log output.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    /// One JSON object per line, for log shippers.
+    Json,
+    /// Multi-line, human friendly.
+    Pretty,
+    #[default]
+    Compact,
+}
+
+/// Log file written next to stdout, rotated by size.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogFileConfig {
+    pub path: PathBuf,
+    pub max_bytes: u64,
+    /// Rotated files kept (`api.log.1` is the newest).
+    pub max_files: u32,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LoggingConfig {
+    pub format: LogFormat,
+    pub file: Option<LogFileConfig>,
+}
+
+/// `LOG_FORMAT`, `LOG_FILE`, `LOG_FILE_MAX_MB` and `LOG_FILE_MAX_FILES`.
+fn logging(env: &Env) -> LoggingConfig {
+    LoggingConfig {
+        format: env
+            .text("LOG_FORMAT")
+            .and_then(|v| LogFormat::parse(&v))
+            .unwrap_or_default(),
+        file: env.text("LOG_FILE").map(|path| LogFileConfig {
+            path: PathBuf::from(path),
+            max_bytes: env
+                .positive("LOG_FILE_MAX_MB")
+                .unwrap_or(DEFAULT_LOG_FILE_MAX_MB)
+                .saturating_mul(1024 * 1024),
+            max_files: env
+                .number::<u64>("LOG_FILE_MAX_FILES")
+                .map_or(DEFAULT_LOG_FILE_MAX_FILES, |v| v.clamp(1, 100) as u32),
+        }),
+    }
+}
+
+#[cfg(feature = "sentry")]
+const DEFAULT_SENTRY_THROTTLE: Duration = Duration::from_secs(300);
+
+#[cfg(feature = "sentry")]
+#[derive(Debug, Clone, PartialEq)]
+pub struct SentryConfig {
+    pub dsn: sentry::types::Dsn,
+    pub environment: Option<String>,
+    /// Share of error events sent, 0.0 to 1.0 (`SENTRY_SAMPLE_RATE`).
+    pub sample_rate: f32,
+    /// Events whose message contains one of these are dropped
+    /// (`SENTRY_IGNORE`, comma-separated).
+    pub ignore: Vec<String>,
+    /// Minimum time between two events with the same instance and message
+    /// (`SENTRY_THROTTLE_SECS`); zero sends them all.
+    pub throttle: Duration,
+}
+
+/// `SENTRY_DSN`, `SENTRY_ENVIRONMENT`, `SENTRY_SAMPLE_RATE`, `SENTRY_IGNORE`
+/// and `SENTRY_THROTTLE_SECS`. `None` without a valid DSN.
+#[cfg(feature = "sentry")]
+fn sentry(env: &Env) -> Option<SentryConfig> {
+    let dsn = match env.text("SENTRY_DSN")?.parse() {
+        Ok(dsn) => dsn,
+        Err(e) => {
+            warn!(error = %e, "SENTRY_DSN inválido; Sentry desativado");
+            return None;
+        }
+    };
+    Some(SentryConfig {
+        dsn,
+        environment: env.text("SENTRY_ENVIRONMENT"),
+        sample_rate: env
+            .number::<f32>("SENTRY_SAMPLE_RATE")
+            .filter(|rate| (0.0..=1.0).contains(rate))
+            .unwrap_or(1.0),
+        ignore: env.list("SENTRY_IGNORE").unwrap_or_default(),
+        throttle: env
+            .secs("SENTRY_THROTTLE_SECS")
+            .unwrap_or(DEFAULT_SENTRY_THROTTLE),
+    })
+}
+
+/// How often and how fast a crashed runner is restarted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RestartPolicy {
+    /// Restarts allowed before the instance is marked `errored`.
+    pub max_restarts: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    /// Uptime after which earlier crashes are forgotten.
+    pub stable_after: Duration,
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        Self {
+            max_restarts: 5,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+            stable_after: Duration::from_secs(600),
+        }
+    }
+}
+
+/// `RUNNER_MAX_RESTARTS` (default 5) and `RUNNER_RESTART_BACKOFF_MS`
+/// (initial delay, default 1000).
+fn restart_policy(env: &Env) -> RestartPolicy {
+    let defaults = RestartPolicy::default();
+    RestartPolicy {
+        max_restarts: env
+            .number::<u64>("RUNNER_MAX_RESTARTS")
+            .map_or(defaults.max_restarts, |v| v.min(u64::from(u32::MAX)) as u32),
+        initial_backoff: env
+            .millis("RUNNER_RESTART_BACKOFF_MS")
+            .unwrap_or(defaults.initial_backoff),
+        ..defaults
+    }
+}
+
+const DEFAULT_ACME_CACHE_DIR: &str = "./acme-cache";
+const DEFAULT_TLS_RELOAD_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum TlsError {
+    #[error("required with {other}")]
+    Missing {
+        variable: &'static str,
+        other: &'static str,
+    },
+    #[error("cannot be combined with TLS_CERT_PATH/TLS_KEY_PATH")]
+    AcmeWithFiles,
+    #[error("no domain to request a certificate for; set TLS_ACME_DOMAINS or SERVER_URL")]
+    NoAcmeDomain,
+    #[error("cannot read {path}: {reason}")]
+    Unreadable {
+        variable: &'static str,
+        path: String,
+        reason: String,
+    },
+    #[error("{path} holds no PEM {expected}")]
+    NotPem {
+        variable: &'static str,
+        path: String,
+        expected: &'static str,
+    },
+    #[error("this build has no `{feature}` feature")]
+    FeatureDisabled {
+        variable: &'static str,
+        feature: &'static str,
+    },
+}
+
+/// Let's Encrypt settings of [`TlsMode::Acme`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AcmeSettings {
+    pub domains: Vec<String>,
+    /// Account e-mail (`TLS_ACME_EMAIL`), warned before a certificate expires.
+    pub contact: Option<String>,
+    /// Where the account key and certificates are kept across restarts.
+    pub cache_dir: PathBuf,
+    /// Uses the Let's Encrypt staging directory (`TLS_ACME_STAGING`).
+    pub staging: bool,
+}
+
+/// How the HTTP server listens.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TlsMode {
+    /// Plain HTTP.
+    Off,
+    /// HTTPS with PEM files, reloaded every `reload_interval` (zero never).
+    Files {
+        cert: PathBuf,
+        key: PathBuf,
+        reload_interval: Duration,
+    },
+    /// HTTPS with certificates from Let's Encrypt.
+    Acme(AcmeSettings),
+}
+
+/// `TLS_CERT_PATH`, `TLS_KEY_PATH`, `TLS_RELOAD_SECS` and `TLS_ACME_ENABLED`,
+/// `TLS_ACME_DOMAINS` (by default the host of `SERVER_URL`),
+/// `TLS_ACME_EMAIL`, `TLS_ACME_CACHE_DIR`, `TLS_ACME_STAGING`.
+pub(crate) fn tls(env: &Env) -> Result<TlsMode, TlsError> {
+    let cert = env.text("TLS_CERT_PATH");
+    let key = env.text("TLS_KEY_PATH");
+    if env.flag("TLS_ACME_ENABLED") {
+        if cert.is_some() || key.is_some() {
+            return Err(TlsError::AcmeWithFiles);
+        }
+        let domains: Vec<String> = match env.text("TLS_ACME_DOMAINS") {
+            Some(raw) => raw
+                .split(',')
+                .map(|d| d.trim().to_ascii_lowercase())
+                .filter(|d| !d.is_empty())
+                .collect(),
+            None => env
+                .text("SERVER_URL")
+                .as_deref()
+                .and_then(url_host)
+                .into_iter()
+                .collect(),
+        };
+        if domains.is_empty() {
+            return Err(TlsError::NoAcmeDomain);
+        }
+        return Ok(TlsMode::Acme(AcmeSettings {
+            domains,
+            contact: env.text("TLS_ACME_EMAIL"),
+            cache_dir: env
+                .text("TLS_ACME_CACHE_DIR")
+                .unwrap_or_else(|| DEFAULT_ACME_CACHE_DIR.to_string())
+                .into(),
+            staging: env.flag("TLS_ACME_STAGING"),
+        }));
+    }
+
+    match (cert, key) {
+        (None, None) => Ok(TlsMode::Off),
+        (Some(_), None) => Err(TlsError::Missing {
+            variable: "TLS_KEY_PATH",
+            other: "TLS_CERT_PATH",
+        }),
+        (None, Some(_)) => Err(TlsError::Missing {
+            variable: "TLS_CERT_PATH",
+            other: "TLS_KEY_PATH",
+        }),
+        (Some(cert), Some(key)) => Ok(TlsMode::Files {
+            cert: cert.into(),
+            key: key.into(),
+            reload_interval: env
+                .secs("TLS_RELOAD_SECS")
+                .unwrap_or(DEFAULT_TLS_RELOAD_INTERVAL),
+        }),
+    }
+}
+
+/// Timeouts, retries and circuit breaking of the shared client.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpClientConfig {
+    pub timeout: Duration,
+    pub proxy: Option<String>,
+    /// Attempts per call, the first one included.
+    pub max_attempts: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    /// Consecutive failed calls that open the circuit; 0 disables it.
+    pub breaker_threshold: u32,
+    pub breaker_cooldown: Duration,
+}
+
+impl Default for HttpClientConfig {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(60),
+            proxy: None,
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(200),
+            max_backoff: Duration::from_secs(5),
+            breaker_threshold: 5,
+            breaker_cooldown: Duration::from_secs(30),
+        }
+    }
+}
+
+/// `HTTP_TIMEOUT_MS`, `HTTP_PROXY_URL`, `HTTP_MAX_ATTEMPTS`,
+/// `HTTP_RETRY_BACKOFF_MS`, `HTTP_BREAKER_THRESHOLD` and
+/// `HTTP_BREAKER_COOLDOWN_SECONDS`.
+pub(crate) fn http_client(env: &Env) -> HttpClientConfig {
+    let defaults = HttpClientConfig::default();
+    let count = |name: &str| {
+        env.number::<u64>(name)
+            .map(|v| v.min(u64::from(u32::MAX)) as u32)
+    };
+    HttpClientConfig {
+        timeout: env
+            .positive("HTTP_TIMEOUT_MS")
+            .map_or(defaults.timeout, Duration::from_millis),
+        proxy: env.text("HTTP_PROXY_URL"),
+        max_attempts: count("HTTP_MAX_ATTEMPTS").map_or(defaults.max_attempts, |v| v.max(1)),
+        initial_backoff: env
+            .millis("HTTP_RETRY_BACKOFF_MS")
+            .unwrap_or(defaults.initial_backoff),
+        breaker_threshold: count("HTTP_BREAKER_THRESHOLD").unwrap_or(defaults.breaker_threshold),
+        breaker_cooldown: env
+            .secs("HTTP_BREAKER_COOLDOWN_SECONDS")
+            .unwrap_or(defaults.breaker_cooldown),
+        ..defaults
+    }
+}
+
+const DEFAULT_CACHE_LOCAL_TTL_SECS: u64 = 30;
+const DEFAULT_CACHE_LOCAL_MAX_ENTRIES: u64 = 10_000;
+const DEFAULT_REDIS_TTL_SECS: u64 = 60;
+const DEFAULT_REDIS_PREFIX: &str = "chatwarp";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RedisCacheConfig {
+    pub uri: String,
+    /// First segment of every key (`CACHE_REDIS_PREFIX_KEY`).
+    pub prefix: String,
+    pub ttl: Duration,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheConfig {
+    /// Off with `CACHE_LOCAL_ENABLED=false`.
+    pub local_enabled: bool,
+    pub local_ttl: Duration,
+    pub local_max_entries: u64,
+    /// Set when `CACHE_REDIS_ENABLED` is on.
+    pub redis: Option<RedisCacheConfig>,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            local_enabled: true,
+            local_ttl: Duration::from_secs(DEFAULT_CACHE_LOCAL_TTL_SECS),
+            local_max_entries: DEFAULT_CACHE_LOCAL_MAX_ENTRIES,
+            redis: None,
+        }
+    }
+}
+
+/// `CACHE_LOCAL_ENABLED`, `CACHE_LOCAL_TTL` (seconds),
+/// `CACHE_LOCAL_MAX_ENTRIES`, `CACHE_REDIS_ENABLED`, `CACHE_REDIS_URI`,
+/// `CACHE_REDIS_PREFIX_KEY` and `CACHE_REDIS_TTL` (seconds).
+fn cache(env: &Env) -> CacheConfig {
+    let defaults = CacheConfig::default();
+    let redis = env.flag("CACHE_REDIS_ENABLED").then(|| RedisCacheConfig {
+        uri: env
+            .text("CACHE_REDIS_URI")
+            .unwrap_or_else(|| "redis://127.0.0.1:6379".to_string()),
+        prefix: env
+            .text("CACHE_REDIS_PREFIX_KEY")
+            .unwrap_or_else(|| DEFAULT_REDIS_PREFIX.to_string()),
+        ttl: Duration::from_secs(
+            env.positive("CACHE_REDIS_TTL")
+                .unwrap_or(DEFAULT_REDIS_TTL_SECS),
+        ),
+    });
+    CacheConfig {
+        local_enabled: env.enabled("CACHE_LOCAL_ENABLED"),
+        local_ttl: env
+            .positive("CACHE_LOCAL_TTL")
+            .map_or(defaults.local_ttl, Duration::from_secs),
+        local_max_entries: env
+            .positive("CACHE_LOCAL_MAX_ENTRIES")
+            .unwrap_or(defaults.local_max_entries),
+        redis,
+    }
+}
+
+#[cfg(feature = "nats")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NatsConfig {
+    pub url: String,
+    pub subject_prefix: String,
+    /// Publish through JetStream and wait for the ack (`NATS_JETSTREAM`).
+    pub jetstream: bool,
+    /// Stream created for `{prefix}.>` (and the global subjects) when
+    /// JetStream is on (`NATS_STREAM`).
+    pub stream: String,
+    /// Also publish every instance's events to the global subjects,
+    /// whatever the per-instance flags (`NATS_GLOBAL_ENABLED`).
+    pub global: bool,
+    /// First token of the global subjects (`NATS_GLOBAL_SUBJECT_PREFIX`).
+    pub global_subject_prefix: String,
+    /// Instances published to the global subjects.
+    pub scope: InstanceScope,
+}
+
+/// `NATS_ENABLED`, `NATS_URL`, `NATS_SUBJECT_PREFIX`, `NATS_JETSTREAM`,
+/// `NATS_STREAM`, `NATS_GLOBAL_ENABLED`, `NATS_GLOBAL_SUBJECT_PREFIX` and the
+/// global sink scope. `None` unless `NATS_ENABLED` is set.
+#[cfg(feature = "nats")]
+fn nats(env: &Env) -> Option<NatsConfig> {
+    if !env.flag("NATS_ENABLED") {
+        return None;
+    }
+    let text = |name: &str, default: &str| env.text(name).unwrap_or_else(|| default.to_string());
+    let subject_prefix = text("NATS_SUBJECT_PREFIX", "chatwarp");
+    Some(NatsConfig {
+        url: text("NATS_URL", "nats://127.0.0.1:4222"),
+        global_subject_prefix: text(
+            "NATS_GLOBAL_SUBJECT_PREFIX",
+            &format!("{subject_prefix}-global"),
+        ),
+        subject_prefix,
+        jetstream: env.flag("NATS_JETSTREAM"),
+        stream: text("NATS_STREAM", "CHATWARP"),
+        global: env.flag("NATS_GLOBAL_ENABLED"),
+        scope: global_sinks(env),
+    })
+}
+
+/// What to do with a client that fell more than the buffer behind.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LagPolicy {
+    /// Skip the events that no longer fit and notify the client with `WS_LAGGED`.
+    DropOldest,
+    /// Close the connection with code 1008.
+    Disconnect,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WsConfig {
+    /// Events a client may be behind before it counts as lagging (`WS_BUFFER_SIZE`).
+    pub buffer: usize,
+    pub lag_policy: LagPolicy,
+    /// Interval between server pings (`WS_PING_INTERVAL_SECS`).
+    pub ping_interval: Duration,
+    /// Grace period for the pong after a ping, also the deadline for a
+    /// single send (`WS_PONG_TIMEOUT_SECS`).
+    pub pong_timeout: Duration,
+}
+
+impl Default for WsConfig {
+    fn default() -> Self {
+        Self {
+            buffer: 256,
+            lag_policy: LagPolicy::DropOldest,
+            ping_interval: Duration::from_secs(30),
+            pong_timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+/// `WS_BUFFER_SIZE`, `WS_LAG_POLICY`, `WS_PING_INTERVAL_SECS` and
+/// `WS_PONG_TIMEOUT_SECS`. Invalid values are ignored with a warning.
+fn ws(env: &Env) -> WsConfig {
+    let defaults = WsConfig::default();
+    let positive = |name: &str| -> Option<u64> {
+        let raw = env.text(name)?;
+        match raw.parse::<u64>() {
+            Ok(value) if value > 0 => Some(value),
+            _ => {
+                warn!(variable = name, value = %raw, "Ignoring invalid websocket setting");
+                None
+            }
+        }
+    };
+    let lag_policy = match env.text("WS_LAG_POLICY") {
+        Some(raw) => LagPolicy::parse(&raw).unwrap_or_else(|| {
+            warn!(value = %raw, "Ignoring invalid WS_LAG_POLICY (expected drop_oldest or disconnect)");
+            defaults.lag_policy
+        }),
+        None => defaults.lag_policy,
+    };
+
+    WsConfig {
+        buffer: positive("WS_BUFFER_SIZE")
+            .and_then(|v| usize::try_from(v).ok())
+            .unwrap_or(defaults.buffer),
+        lag_policy,
+        ping_interval: positive("WS_PING_INTERVAL_SECS")
+            .map_or(defaults.ping_interval, Duration::from_secs),
+        pong_timeout: positive("WS_PONG_TIMEOUT_SECS")
+            .map_or(defaults.pong_timeout, Duration::from_secs),
+    }
+}
+
+/// Allow and deny lists of the global sinks.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InstanceScope {
+    /// Only these instances; `None` takes all of them.
+    pub allow: Option<BTreeSet<String>>,
+    pub deny: BTreeSet<String>,
+}
+
+/// `GLOBAL_SINK_INSTANCES` and `GLOBAL_SINK_EXCLUDE_INSTANCES`.
+fn global_sinks(env: &Env) -> InstanceScope {
+    InstanceScope {
+        allow: env
+            .list("GLOBAL_SINK_INSTANCES")
+            .filter(|allow| !allow.is_empty())
+            .map(|allow| allow.into_iter().collect()),
+        deny: env
+            .list("GLOBAL_SINK_EXCLUDE_INSTANCES")
+            .unwrap_or_default()
+            .into_iter()
+            .collect(),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SseConfig {
+    /// Events kept for `Last-Event-ID` resume, also how far a client may
+    /// fall behind (`SSE_HISTORY_SIZE`).
+    pub history: usize,
+    /// Interval between heartbeat comments (`SSE_HEARTBEAT_SECS`).
+    pub heartbeat: Duration,
+}
+
+impl Default for SseConfig {
+    fn default() -> Self {
+        Self {
+            history: 1000,
+            heartbeat: Duration::from_secs(15),
+        }
+    }
+}
+
+/// `SSE_HISTORY_SIZE` and `SSE_HEARTBEAT_SECS`.
+fn sse(env: &Env) -> SseConfig {
+    let defaults = SseConfig::default();
+    SseConfig {
+        history: env.positive("SSE_HISTORY_SIZE").unwrap_or(defaults.history),
+        heartbeat: env
+            .positive("SSE_HEARTBEAT_SECS")
+            .map_or(defaults.heartbeat, Duration::from_secs),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EventHistoryConfig {
+    /// Events kept in memory per instance (`EVENT_HISTORY_SIZE`).
+    pub size: usize,
+    /// Whether `event_outbox` answers what memory cannot
+    /// (`EVENT_HISTORY_PERSIST`).
+    pub persist: bool,
+}
+
+impl Default for EventHistoryConfig {
+    fn default() -> Self {
+        Self {
+            size: 500,
+            persist: false,
+        }
+    }
+}
+
+/// `EVENT_HISTORY_SIZE` and `EVENT_HISTORY_PERSIST`.
+fn event_history(env: &Env) -> EventHistoryConfig {
+    EventHistoryConfig {
+        size: env
+            .positive("EVENT_HISTORY_SIZE")
+            .unwrap_or(EventHistoryConfig::default().size),
+        persist: env.flag("EVENT_HISTORY_PERSIST"),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MetaConfig {
+    /// Token Meta echoes back when subscribing the webhook (`META_VERIFY_TOKEN`).
+    pub verify_token: Option<String>,
+    /// App secret used to check `X-Hub-Signature-256` (`META_APP_SECRET`).
+    pub app_secret: Option<String>,
+    pub graph_url: String,
+    pub graph_version: String,
+}
+
+impl Default for MetaConfig {
+    fn default() -> Self {
+        Self {
+            verify_token: None,
+            app_secret: None,
+            graph_url: "https://graph.facebook.com".to_string(),
+            graph_version: "v20.0".to_string(),
+        }
+    }
+}
+
+/// `META_VERIFY_TOKEN`, `META_APP_SECRET`, `META_GRAPH_URL` and
+/// `META_GRAPH_VERSION`.
+fn meta(env: &Env) -> MetaConfig {
+    let defaults = MetaConfig::default();
+    MetaConfig {
+        verify_token: env.text("META_VERIFY_TOKEN"),
+        app_secret: env.text("META_APP_SECRET"),
+        graph_url: env
+            .text("META_GRAPH_URL")
+            .map_or(defaults.graph_url, |url| {
+                url.trim_end_matches('/').to_string()
+            }),
+        graph_version: env
+            .text("META_GRAPH_VERSION")
+            .unwrap_or(defaults.graph_version),
+    }
+}
+
+const DEFAULT_DEDUP_TTL_SECS: u64 = 24 * 60 * 60;
+const DEFAULT_DEDUP_CACHE_SIZE: u64 = 100_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DedupConfig {
+    /// Off with `INBOUND_DEDUP_ENABLED=false`.
+    pub enabled: bool,
+    pub ttl: Duration,
+    /// Keys kept in memory.
+    pub cache_size: u64,
+}
+
+impl Default for DedupConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            ttl: Duration::from_secs(DEFAULT_DEDUP_TTL_SECS),
+            cache_size: DEFAULT_DEDUP_CACHE_SIZE,
+        }
+    }
+}
+
+/// `INBOUND_DEDUP_ENABLED`, `INBOUND_DEDUP_TTL_SECONDS` and
+/// `INBOUND_DEDUP_CACHE_SIZE`.
+fn inbound_dedup(env: &Env) -> DedupConfig {
+    let defaults = DedupConfig::default();
+    DedupConfig {
+        enabled: env.enabled("INBOUND_DEDUP_ENABLED"),
+        ttl: env
+            .positive("INBOUND_DEDUP_TTL_SECONDS")
+            .map_or(defaults.ttl, Duration::from_secs),
+        cache_size: env
+            .positive("INBOUND_DEDUP_CACHE_SIZE")
+            .unwrap_or(defaults.cache_size),
+    }
+}
+
+const DEFAULT_RETENTION_INTERVAL_MINUTES: u64 = 60;
+const DEFAULT_RETENTION_BATCH_SIZE: i32 = 1000;
+/// Longest retention accepted; anything longer is better expressed as `0`.
+pub const MAX_RETENTION_DAYS: u32 = 3650;
+
+/// Days to keep each kind of data. `None` falls back to the deployment
+/// default (and, there, keeps forever); `Some(0)` keeps forever.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct RetentionPolicy {
+    #[serde(default)]
+    pub messages_days: Option<u32>,
+    #[serde(default)]
+    pub webhook_logs_days: Option<u32>,
+    #[serde(default)]
+    pub media_days: Option<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetentionConfig {
+    /// Deployment-wide retention, overridden per instance.
+    pub defaults: RetentionPolicy,
+    pub interval: Duration,
+    /// Rows deleted per statement.
+    pub batch_size: i32,
+}
+
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        Self {
+            defaults: RetentionPolicy::default(),
+            interval: Duration::from_secs(DEFAULT_RETENTION_INTERVAL_MINUTES * 60),
+            batch_size: DEFAULT_RETENTION_BATCH_SIZE,
+        }
+    }
+}
+
+/// `RETENTION_MESSAGES_DAYS`, `RETENTION_WEBHOOK_LOGS_DAYS`,
+/// `RETENTION_MEDIA_DAYS`, `RETENTION_INTERVAL_MINUTES` and
+/// `RETENTION_BATCH_SIZE`.
+fn retention(env: &Env) -> RetentionConfig {
+    let defaults = RetentionConfig::default();
+    let days = |name: &str| {
+        env.number::<u32>(name)
+            .map(|days| days.min(MAX_RETENTION_DAYS))
+    };
+    RetentionConfig {
+        defaults: RetentionPolicy {
+            messages_days: days("RETENTION_MESSAGES_DAYS"),
+            webhook_logs_days: days("RETENTION_WEBHOOK_LOGS_DAYS"),
+            media_days: days("RETENTION_MEDIA_DAYS"),
+        },
+        interval: env
+            .positive::<u32>("RETENTION_INTERVAL_MINUTES")
+            .map_or(defaults.interval, |minutes| {
+                Duration::from_secs(u64::from(minutes) * 60)
+            }),
+        batch_size: env
+            .positive("RETENTION_BATCH_SIZE")
+            .unwrap_or(defaults.batch_size),
+    }
+}
+
+const DEFAULT_UPLOAD_MAX_MB: u64 = 512;
+const DEFAULT_UPLOAD_TTL_MINUTES: u64 = 60;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UploadConfig {
+    pub dir: PathBuf,
+    pub max_bytes: u64,
+    pub ttl: Duration,
+}
+
+impl Default for UploadConfig {
+    fn default() -> Self {
+        Self {
+            dir: std::env::temp_dir().join("chatwarp-uploads"),
+            max_bytes: DEFAULT_UPLOAD_MAX_MB * 1024 * 1024,
+            ttl: Duration::from_secs(DEFAULT_UPLOAD_TTL_MINUTES * 60),
+        }
+    }
+}
+
+/// `MEDIA_UPLOAD_DIR`, `MEDIA_UPLOAD_MAX_MB` and `MEDIA_UPLOAD_TTL_MINUTES`.
+fn uploads(env: &Env) -> UploadConfig {
+    let defaults = UploadConfig::default();
+    UploadConfig {
+        dir: env
+            .text("MEDIA_UPLOAD_DIR")
+            .map_or(defaults.dir, PathBuf::from),
+        max_bytes: env
+            .positive::<u64>("MEDIA_UPLOAD_MAX_MB")
+            .map_or(defaults.max_bytes, |mb| mb.saturating_mul(1024 * 1024)),
+        ttl: env
+            .positive::<u64>("MEDIA_UPLOAD_TTL_MINUTES")
+            .map_or(defaults.ttl, |minutes| {
+                Duration::from_secs(minutes.saturating_mul(60))
+            }),
+    }
+}
+
+/// Longest edge of a media thumbnail when `MEDIA_THUMBNAIL_SIZE` is not set.
+const DEFAULT_THUMBNAIL_SIZE: u32 = 72;
+/// Binary used when `FFMPEG_PATH` is not set.
+const DEFAULT_FFMPEG: &str = "ffmpeg";
+
+/// Size of media thumbnails and the ffmpeg used for videos.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ThumbnailConfig {
+    /// Longest edge in pixels; `None` turns thumbnails off.
+    pub max_size: Option<u32>,
+    pub ffmpeg: String,
+}
+
+impl Default for ThumbnailConfig {
+    fn default() -> Self {
+        Self {
+            max_size: Some(DEFAULT_THUMBNAIL_SIZE),
+            ffmpeg: DEFAULT_FFMPEG.to_string(),
+        }
+    }
+}
+
+/// `MEDIA_THUMBNAIL_SIZE` (`0` turns thumbnails off) and `FFMPEG_PATH`,
+/// also used for audio conversion.
+fn thumbnails(env: &Env) -> ThumbnailConfig {
+    let defaults = ThumbnailConfig::default();
+    ThumbnailConfig {
+        max_size: match env.number::<u32>("MEDIA_THUMBNAIL_SIZE") {
+            Some(0) => None,
+            Some(size) => Some(size),
+            None => defaults.max_size,
+        },
+        ffmpeg: env.text("FFMPEG_PATH").unwrap_or(defaults.ffmpeg),
+    }
+}
+
+const DEFAULT_TIMEOUT_MESSAGES_SECS: u64 = 30;
+const DEFAULT_TIMEOUT_CONNECT_SECS: u64 = 60;
+const DEFAULT_TIMEOUT_QUERIES_SECS: u64 = 10;
+const DEFAULT_TIMEOUT_OTHER_SECS: u64 = 30;
+
+/// Budget of each route group (messages, connect, queries, other); `None`
+/// lets its requests run unbounded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeoutConfig {
+    pub messages: Option<Duration>,
+    pub connect: Option<Duration>,
+    pub queries: Option<Duration>,
+    pub other: Option<Duration>,
+}
+
+impl Default for TimeoutConfig {
+    fn default() -> Self {
+        Self {
+            messages: Some(Duration::from_secs(DEFAULT_TIMEOUT_MESSAGES_SECS)),
+            connect: Some(Duration::from_secs(DEFAULT_TIMEOUT_CONNECT_SECS)),
+            queries: Some(Duration::from_secs(DEFAULT_TIMEOUT_QUERIES_SECS)),
+            other: Some(Duration::from_secs(DEFAULT_TIMEOUT_OTHER_SECS)),
+        }
+    }
+}
+
+/// `REQUEST_TIMEOUT_MESSAGES_SECS`, `REQUEST_TIMEOUT_CONNECT_SECS`,
+/// `REQUEST_TIMEOUT_QUERIES_SECS` and `REQUEST_TIMEOUT_SECS` (the other
+/// routes); `0` turns a budget off.
+fn timeouts(env: &Env) -> TimeoutConfig {
+    let budget = |name: &str, default: Option<Duration>| match env.secs(name) {
+        Some(Duration::ZERO) => None,
+        Some(budget) => Some(budget),
+        None => default,
+    };
+    let defaults = TimeoutConfig::default();
+    TimeoutConfig {
+        messages: budget("REQUEST_TIMEOUT_MESSAGES_SECS", defaults.messages),
+        connect: budget("REQUEST_TIMEOUT_CONNECT_SECS", defaults.connect),
+        queries: budget("REQUEST_TIMEOUT_QUERIES_SECS", defaults.queries),
+        other: budget("REQUEST_TIMEOUT_SECS", defaults.other),
+    }
+}
+
+const DEFAULT_EXPORT_TTL_HOURS: u64 = 24;
+const DEFAULT_EXPORT_URL_TTL_MINUTES: u64 = 60;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExportConfig {
+    pub dir: PathBuf,
+    /// How long finished files are kept.
+    pub ttl: Duration,
+    /// How long a download URL stays valid.
+    pub url_ttl: Duration,
+    /// Key of the download URL signatures; random unless configured, so
+    /// the URLs stop working on restart.
+    pub(crate) secret: Vec<u8>,
+    /// `SERVER_URL`, prefixed to download URLs when set.
+    pub public_url: Option<String>,
+}
+
+impl Default for ExportConfig {
+    fn default() -> Self {
+        Self {
+            dir: std::env::temp_dir().join("chatwarp-exports"),
+            ttl: Duration::from_secs(DEFAULT_EXPORT_TTL_HOURS * 3600),
+            url_ttl: Duration::from_secs(DEFAULT_EXPORT_URL_TTL_MINUTES * 60),
+            secret: [Uuid::new_v4().into_bytes(), Uuid::new_v4().into_bytes()].concat(),
+            public_url: None,
+        }
+    }
+}
+
+/// `EXPORT_DIR`, `EXPORT_TTL_HOURS`, `EXPORT_URL_TTL_MINUTES`,
+/// `EXPORT_SIGNING_SECRET` and `SERVER_URL`. Without a secret a random one
+/// is used, so download URLs stop working on restart.
+fn exports(env: &Env) -> ExportConfig {
+    let defaults = ExportConfig::default();
+    ExportConfig {
+        dir: env.text("EXPORT_DIR").map_or(defaults.dir, PathBuf::from),
+        ttl: env
+            .positive::<u64>("EXPORT_TTL_HOURS")
+            .map_or(defaults.ttl, |hours| {
+                Duration::from_secs(hours.saturating_mul(3600))
+            }),
+        url_ttl: env
+            .positive::<u64>("EXPORT_URL_TTL_MINUTES")
+            .map_or(defaults.url_ttl, |minutes| {
+                Duration::from_secs(minutes.saturating_mul(60))
+            }),
+        secret: env
+            .text("EXPORT_SIGNING_SECRET")
+            .map_or(defaults.secret, String::into_bytes),
+        public_url: env
+            .text("SERVER_URL")
+            .map(|url| url.trim_end_matches('/').to_string()),
+    }
+}
+
+/// The `database` check.
+const DEFAULT_HEALTH_CRITICAL: &str = "database";
+const DEFAULT_HEALTH_TIMEOUT_MS: u64 = 3000;
+const DEFAULT_HEALTH_SLOW_MS: u64 = 1000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HealthConfig {
+    /// Dependencies whose outage flips `/readyz`.
+    pub critical: Vec<String>,
+    /// Per-check timeout; a check that takes longer is `down`.
+    pub timeout: Duration,
+    /// Latency above which a check that succeeded is `degraded`.
+    pub slow: Duration,
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        Self {
+            critical: vec![DEFAULT_HEALTH_CRITICAL.to_string()],
+            timeout: Duration::from_millis(DEFAULT_HEALTH_TIMEOUT_MS),
+            slow: Duration::from_millis(DEFAULT_HEALTH_SLOW_MS),
+        }
+    }
+}
+
+/// `HEALTH_CRITICAL` (comma separated, default `database`),
+/// `HEALTH_TIMEOUT_MS` and `HEALTH_SLOW_MS`.
+fn health(env: &Env) -> HealthConfig {
+    let defaults = HealthConfig::default();
+    HealthConfig {
+        critical: env
+            .list("HEALTH_CRITICAL")
+            .map_or(defaults.critical, |names| {
+                names.iter().map(|name| name.to_ascii_lowercase()).collect()
+            }),
+        timeout: env
+            .positive("HEALTH_TIMEOUT_MS")
+            .map_or(defaults.timeout, Duration::from_millis),
+        slow: env
+            .positive("HEALTH_SLOW_MS")
+            .map_or(defaults.slow, Duration::from_millis),
+    }
+}
+
+const DEFAULT_LOCAL_MAX_DIGITS: usize = 11;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParticipantConfig {
+    /// Prepended to local numbers (`DEFAULT_COUNTRY_CODE`); `None` keeps
+    /// numbers as sent.
+    pub default_country_code: Option<String>,
+    /// Numbers without `+` and with at most this many digits are local
+    /// (`LOCAL_NUMBER_MAX_DIGITS`).
+    pub local_max_digits: usize,
+}
+
+impl Default for ParticipantConfig {
+    fn default() -> Self {
+        Self {
+            default_country_code: None,
+            local_max_digits: DEFAULT_LOCAL_MAX_DIGITS,
+        }
+    }
+}
+
+/// `DEFAULT_COUNTRY_CODE` and `LOCAL_NUMBER_MAX_DIGITS`.
+pub(crate) fn participants(env: &Env) -> ParticipantConfig {
+    ParticipantConfig {
+        default_country_code: env
+            .text("DEFAULT_COUNTRY_CODE")
+            .map(|v| v.trim_start_matches('+').to_string())
+            .filter(|v| !v.is_empty() && v.chars().all(|c| c.is_ascii_digit())),
+        local_max_digits: env
+            .number("LOCAL_NUMBER_MAX_DIGITS")
+            .unwrap_or(ParticipantConfig::default().local_max_digits),
+    }
+}
+
+/// `HANDSHAKE_MAX_CONCURRENT` and `HANDSHAKE_JITTER_MS`.
+fn handshake(env: &Env) -> HandshakeConfig {
+    let defaults = HandshakeConfig::default();
+    HandshakeConfig {
+        max_concurrent: env
+            .positive("HANDSHAKE_MAX_CONCURRENT")
+            .unwrap_or(defaults.max_concurrent),
+        jitter: env.millis("HANDSHAKE_JITTER_MS").unwrap_or(defaults.jitter),
+    }
+}
+
+/// Lease length when `INSTANCE_LEASE_SECS` is not set.
+pub const DEFAULT_INSTANCE_LEASE_SECS: u64 = 30;
+
+/// `STANDBY_MODE`, `INSTANCE_LEASES`, `NODE_ID` and `INSTANCE_LEASE_SECS`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StandbyConfig {
+    /// Start as standby instead of taking the lease.
+    pub standby: bool,
+    /// Whether leases are used at all; implied by `standby`.
+    pub leases: bool,
+    /// Name this node holds leases under (`NODE_ID`, else `HOSTNAME`).
+    pub node_id: String,
+    pub lease_ttl: Duration,
+}
+
+/// `STANDBY_MODE`, `INSTANCE_LEASES`, `NODE_ID` (else `HOSTNAME`) and
+/// `INSTANCE_LEASE_SECS`.
+fn standby(env: &Env) -> StandbyConfig {
+    let standby = env.flag("STANDBY_MODE");
+    StandbyConfig {
+        standby,
+        leases: standby || env.flag("INSTANCE_LEASES"),
+        node_id: env
+            .text("NODE_ID")
+            .or_else(|| env.text("HOSTNAME"))
+            .unwrap_or_else(|| format!("node-{}", uuid::Uuid::new_v4().simple())),
+        lease_ttl: env
+            .positive::<u32>("INSTANCE_LEASE_SECS")
+            .map_or(Duration::from_secs(DEFAULT_INSTANCE_LEASE_SECS), |secs| {
+                Duration::from_secs(secs.into())
+            }),
+    }
+}
+
+const DEFAULT_OUTBOX_BATCH_SIZE: i32 = 100;
+const DEFAULT_OUTBOX_POLL_MS: u64 = 1000;
+const DEFAULT_OUTBOX_LEASE_SECS: i32 = 30;
+const DEFAULT_OUTBOX_RETENTION_HOURS: i32 = 24;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutboxConfig {
+    /// Rows claimed per round.
+    pub batch_size: i32,
+    /// Wait between rounds when nobody wakes the dispatcher.
+    pub poll: Duration,
+    /// How long a claim holds before another dispatcher may retry the row.
+    pub lease_secs: i32,
+    /// Dispatched rows older than this are deleted.
+    pub retention_hours: i32,
+}
+
+impl Default for OutboxConfig {
+    fn default() -> Self {
+        Self {
+            batch_size: DEFAULT_OUTBOX_BATCH_SIZE,
+            poll: Duration::from_millis(DEFAULT_OUTBOX_POLL_MS),
+            lease_secs: DEFAULT_OUTBOX_LEASE_SECS,
+            retention_hours: DEFAULT_OUTBOX_RETENTION_HOURS,
+        }
+    }
+}
+
+/// `OUTBOX_BATCH_SIZE`, `OUTBOX_POLL_MS`, `OUTBOX_LEASE_SECS` and
+/// `OUTBOX_RETENTION_HOURS`.
+fn outbox(env: &Env) -> OutboxConfig {
+    let defaults = OutboxConfig::default();
+    OutboxConfig {
+        batch_size: env
+            .positive("OUTBOX_BATCH_SIZE")
+            .unwrap_or(defaults.batch_size),
+        poll: env
+            .positive("OUTBOX_POLL_MS")
+            .map_or(defaults.poll, Duration::from_millis),
+        lease_secs: env
+            .positive("OUTBOX_LEASE_SECS")
+            .unwrap_or(defaults.lease_secs),
+        retention_hours: env
+            .positive("OUTBOX_RETENTION_HOURS")
+            .unwrap_or(defaults.retention_hours),
+    }
+}
+
+/// `WA_VERSION_PIN`, `WA_VERSION_FALLBACKS` (comma separated) and
+/// `WA_VERSION_SOURCE`.
+pub(crate) fn wa_version(env: &Env) -> Result<VersionConfig, VersionConfigError> {
+    let pinned = match env.text("WA_VERSION_PIN") {
+        Some(value) => Some(
+            parse_version(&value).ok_or(VersionConfigError::InvalidVersion {
+                name: "WA_VERSION_PIN",
+                value,
+            })?,
+        ),
+        None => None,
+    };
+    let fallbacks = env
+        .list("WA_VERSION_FALLBACKS")
+        .unwrap_or_default()
+        .into_iter()
+        .map(|item| {
+            parse_version(&item).ok_or(VersionConfigError::InvalidVersion {
+                name: "WA_VERSION_FALLBACKS",
+                value: item,
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let source = match env.text("WA_VERSION_SOURCE") {
+        Some(value) => {
+            VersionSource::parse(&value).ok_or(VersionConfigError::InvalidSource(value))?
+        }
+        None => VersionSource::ServiceWorker,
+    };
+    Ok(VersionConfig {
+        pinned,
+        fallbacks,
+        source,
+    })
+}
+
+fn parse_flag(value: &str) -> Option<bool> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "true" | "1" | "yes" => Some(true),
+        "false" | "0" | "no" => Some(false),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    include!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/tests/config_tests.rs"));
+}
@@ -0,0 +1,411 @@
+use crate::client::Client;
+use crate::request::InfoQuery;
+use crate::utils::jid_utils::server_jid;
+use anyhow::Result;
+use log::debug;
+use warp_core_binary::builder::NodeBuilder;
+use warp_core_binary::jid::Jid;
+use warp_core_binary::node::{Node, NodeContent};
+
+const CATALOG_NAMESPACE: &str = "w:biz:catalog";
+const PROFILE_NAMESPACE: &str = "w:biz";
+/// Version of the `business_profile` query and of its mutations.
+const PROFILE_VERSION: &str = "244";
+const PROFILE_MUTATION_VERSION: &str = "3";
+/// Thumbnail size requested for product images.
+const IMAGE_SIZE: &str = "100";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Product {
+    pub id: String,
+    pub name: Option<String>,
+    pub description: Option<String>,
+    /// Price in thousandths of `currency` (WhatsApp's `price_amount1000`).
+    #[serde(rename = "price")]
+    pub price_amount1000: Option<i64>,
+    pub currency: Option<String>,
+    pub retailer_id: Option<String>,
+    pub url: Option<String>,
+    pub is_hidden: bool,
+    pub availability: Option<String>,
+    pub image_urls: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CatalogPage {
+    pub products: Vec<Product>,
+    /// Cursor for the next page, `None` on the last one.
+    pub next_cursor: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Collection {
+    pub id: String,
+    pub name: Option<String>,
+    pub products: Vec<Product>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct BusinessCategory {
+    pub id: String,
+    pub name: Option<String>,
+}
+
+/// Opening hours of one weekday.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BusinessHoursDay {
+    /// `sun`, `mon`, ... `sat`.
+    pub day: String,
+    /// `open_24h`, `appointment_only` or `specific_hours`.
+    pub mode: String,
+    /// Minutes after midnight, with `specific_hours`.
+    pub open_time: Option<u32>,
+    pub close_time: Option<u32>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BusinessHours {
+    pub timezone: Option<String>,
+    pub days: Vec<BusinessHoursDay>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BusinessProfile {
+    pub jid: String,
+    pub description: Option<String>,
+    pub address: Option<String>,
+    pub email: Option<String>,
+    pub websites: Vec<String>,
+    pub categories: Vec<BusinessCategory>,
+    pub business_hours: Option<BusinessHours>,
+}
+
+/// Fields to change in the own business profile; `None` keeps the current
+/// value, an empty string or list clears it.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BusinessProfileUpdate {
+    pub description: Option<String>,
+    pub address: Option<String>,
+    pub email: Option<String>,
+    pub websites: Option<Vec<String>>,
+    pub business_hours: Option<BusinessHours>,
+}
+
+impl BusinessProfileUpdate {
+    pub fn is_empty(&self) -> bool {
+        self.description.is_none()
+            && self.address.is_none()
+            && self.email.is_none()
+            && self.websites.is_none()
+            && self.business_hours.is_none()
+    }
+}
+
+pub struct Business<'a> {
+    client: &'a Client,
+}
+
+impl<'a> Business<'a> {
+    pub(crate) fn new(client: &'a Client) -> Self {
+        Self { client }
+    }
+
+    /// Fetches one page of the product catalog of `jid`. `cursor` is the
+    /// `next_cursor` of the previous page.
+    pub async fn get_catalog(
+        &self,
+        jid: &Jid,
+        limit: u32,
+        cursor: Option<&str>,
+    ) -> Result<CatalogPage> {
+        debug!(target: "Business", "Fetching catalog of {} (limit {})", jid, limit);
+
+        let mut children = vec![
+            text_node("limit", limit.to_string()),
+            text_node("width", IMAGE_SIZE),
+            text_node("height", IMAGE_SIZE),
+        ];
+        if let Some(cursor) = cursor {
+            children.push(text_node("after", cursor));
+        }
+        let catalog = NodeBuilder::new("product_catalog")
+            .attr("jid", jid.to_string())
+            .attr("allow_shop_source", "true")
+            .children(children)
+            .build();
+
+        let iq = InfoQuery::get(
+            CATALOG_NAMESPACE,
+            server_jid(),
+            Some(NodeContent::Nodes(vec![catalog])),
+        );
+        let response = self.client.send_iq(iq).await?;
+        Ok(parse_catalog(&response))
+    }
+
+    /// Fetches the collections of `jid` with up to `item_limit` products each.
+    pub async fn get_collections(
+        &self,
+        jid: &Jid,
+        limit: u32,
+        item_limit: u32,
+    ) -> Result<Vec<Collection>> {
+        debug!(target: "Business", "Fetching collections of {} (limit {})", jid, limit);
+
+        let collections = NodeBuilder::new("collections")
+            .attr("biz_jid", jid.to_string())
+            .children([
+                text_node("collection_limit", limit.to_string()),
+                text_node("item_limit", item_limit.to_string()),
+                text_node("width", IMAGE_SIZE),
+                text_node("height", IMAGE_SIZE),
+            ])
+            .build();
+
+        let iq = InfoQuery::get(
+            CATALOG_NAMESPACE,
+            server_jid(),
+            Some(NodeContent::Nodes(vec![collections])),
+        );
+        let response = self.client.send_iq(iq).await?;
+        Ok(parse_collections(&response))
+    }
+
+    /// Business profile of `jid`, `None` when the account is not a business.
+    pub async fn get_profile(&self, jid: &Jid) -> Result<Option<BusinessProfile>> {
+        debug!(target: "Business", "Fetching business profile of {}", jid);
+
+        let profile = NodeBuilder::new("business_profile")
+            .attr("v", PROFILE_VERSION)
+            .children([NodeBuilder::new("profile")
+                .attr("jid", jid.to_string())
+                .build()])
+            .build();
+
+        let iq = InfoQuery::get(
+            PROFILE_NAMESPACE,
+            server_jid(),
+            Some(NodeContent::Nodes(vec![profile])),
+        );
+        let response = self.client.send_iq(iq).await?;
+        Ok(parse_profile(&response))
+    }
+
+    /// Changes the business profile of the own account.
+    pub async fn update_profile(&self, update: &BusinessProfileUpdate) -> Result<()> {
+        debug!(target: "Business", "Updating own business profile");
+
+        let iq = InfoQuery::set(
+            PROFILE_NAMESPACE,
+            server_jid(),
+            Some(NodeContent::Nodes(vec![build_profile_update(update)])),
+        );
+        self.client.send_iq(iq).await?;
+        Ok(())
+    }
+}
+
+fn text_node(tag: &str, value: impl Into<String>) -> Node {
+    NodeBuilder::new(tag).string_content(value).build()
+}
+
+fn child_text(node: &Node, tag: &str) -> Option<String> {
+    text(node.get_optional_child(tag)?)
+}
+
+fn text(node: &Node) -> Option<String> {
+    match &node.content {
+        Some(NodeContent::String(s)) if !s.is_empty() => Some(s.clone()),
+        Some(NodeContent::Bytes(b)) if !b.is_empty() => String::from_utf8(b.clone()).ok(),
+        _ => None,
+    }
+}
+
+fn parse_product(node: &Node) -> Option<Product> {
+    let image_urls = node
+        .get_optional_child("media")
+        .map(|media| {
+            media
+                .get_children_by_tag("image")
+                .into_iter()
+                .filter_map(|image| {
+                    child_text(image, "original_image_url")
+                        .or_else(|| child_text(image, "request_image_url"))
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    Some(Product {
+        id: child_text(node, "id")?,
+        name: child_text(node, "name"),
+        description: child_text(node, "description"),
+        price_amount1000: child_text(node, "price").and_then(|p| p.parse().ok()),
+        currency: child_text(node, "currency"),
+        retailer_id: child_text(node, "retailer_id"),
+        url: child_text(node, "url"),
+        is_hidden: node.attrs().optional_bool("is_hidden"),
+        availability: node
+            .get_optional_child("status_info")
+            .and_then(|status| child_text(status, "status")),
+        image_urls,
+    })
+}
+
+fn parse_products(node: &Node) -> Vec<Product> {
+    node.get_children_by_tag("product")
+        .into_iter()
+        .filter_map(parse_product)
+        .collect()
+}
+
+fn parse_catalog(response: &Node) -> CatalogPage {
+    let Some(catalog) = response.get_optional_child("product_catalog") else {
+        return CatalogPage::default();
+    };
+    CatalogPage {
+        products: parse_products(catalog),
+        next_cursor: catalog
+            .get_optional_child("paging")
+            .and_then(|paging| child_text(paging, "after")),
+    }
+}
+
+fn parse_collections(response: &Node) -> Vec<Collection> {
+    let Some(collections) = response.get_optional_child("collections") else {
+        return Vec::new();
+    };
+    collections
+        .get_children_by_tag("collection")
+        .into_iter()
+        .filter_map(|collection| {
+            Some(Collection {
+                id: child_text(collection, "id")?,
+                name: child_text(collection, "name"),
+                products: parse_products(collection),
+            })
+        })
+        .collect()
+}
+
+fn parse_profile(response: &Node) -> Option<BusinessProfile> {
+    let profile = response
+        .get_optional_child("business_profile")?
+        .get_optional_child("profile")?;
+    let categories = profile
+        .get_optional_child("categories")
+        .map(|categories| {
+            categories
+                .get_children_by_tag("category")
+                .into_iter()
+                .filter_map(|category| {
+                    Some(BusinessCategory {
+                        id: category.attrs().optional_string("id")?.to_string(),
+                        name: text(category),
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    let business_hours = profile
+        .get_optional_child("business_hours")
+        .map(|hours| BusinessHours {
+            timezone: hours
+                .attrs()
+                .optional_string("timezone")
+                .map(str::to_string),
+            days: hours
+                .get_children_by_tag("business_hours_config")
+                .into_iter()
+                .filter_map(|config| {
+                    let mut attrs = config.attrs();
+                    Some(BusinessHoursDay {
+                        day: attrs.optional_string("day_of_week")?.to_string(),
+                        mode: attrs.optional_string("mode")?.to_string(),
+                        open_time: attrs.optional_u64("open_time").map(|t| t as u32),
+                        close_time: attrs.optional_u64("close_time").map(|t| t as u32),
+                    })
+                })
+                .collect(),
+        });
+
+    Some(BusinessProfile {
+        jid: profile
+            .attrs()
+            .optional_string("jid")
+            .unwrap_or_default()
+            .to_string(),
+        description: child_text(profile, "description"),
+        address: child_text(profile, "address"),
+        email: child_text(profile, "email"),
+        websites: profile
+            .get_children_by_tag("website")
+            .into_iter()
+            .filter_map(text)
+            .collect(),
+        categories,
+        business_hours,
+    })
+}
+
+/// `business_profile` delta carrying only the fields set in `update`.
+fn build_profile_update(update: &BusinessProfileUpdate) -> Node {
+    let mut children = Vec::new();
+    for (tag, value) in [
+        ("address", &update.address),
+        ("email", &update.email),
+        ("description", &update.description),
+    ] {
+        if let Some(value) = value {
+            children.push(text_node(tag, value.as_str()));
+        }
+    }
+    for website in update.websites.iter().flatten() {
+        children.push(text_node("website", website.as_str()));
+    }
+    if let Some(hours) = &update.business_hours {
+        let days = hours.days.iter().map(|day| {
+            let mut config = NodeBuilder::new("business_hours_config")
+                .attr("day_of_week", day.day.as_str())
+                .attr("mode", day.mode.as_str());
+            if day.mode == "specific_hours" {
+                if let Some(open) = day.open_time {
+                    config = config.attr("open_time", open.to_string());
+                }
+                if let Some(close) = day.close_time {
+                    config = config.attr("close_time", close.to_string());
+                }
+            }
+            config.build()
+        });
+        let mut business_hours = NodeBuilder::new("business_hours");
+        if let Some(timezone) = &hours.timezone {
+            business_hours = business_hours.attr("timezone", timezone.as_str());
+        }
+        children.push(business_hours.children(days).build());
+    }
+
+    NodeBuilder::new("business_profile")
+        .attr("v", PROFILE_MUTATION_VERSION)
+        .attr("mutation_type", "delta")
+        .children(children)
+        .build()
+}
+
+impl Client {
+    pub fn business(&self) -> Business<'_> {
+        Business::new(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    include!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/tests/features/business_tests.rs"));
+}
@@ -0,0 +1,85 @@
+use crate::client::Client;
+use anyhow::Result;
+use log::debug;
+use warp_core::appstate::encode;
+use waproto::whatsapp as wa;
+use warp_core_binary::jid::Jid;
+
+/// Chat settings synced through app state (archive, pin, mute, read marker).
+pub struct Chats<'a> {
+    client: &'a Client,
+}
+
+impl<'a> Chats<'a> {
+    pub(crate) fn new(client: &'a Client) -> Self {
+        Self { client }
+    }
+
+    /// Archives or unarchives `jid`. `last_message_timestamp` (seconds) lets
+    /// other devices tell which messages the action covers.
+    pub async fn archive(
+        &self,
+        jid: &Jid,
+        archive: bool,
+        last_message_timestamp: Option<i64>,
+    ) -> Result<()> {
+        debug!(target: "Chats", "Setting archived={} on {}", archive, jid);
+        let info = encode::build_archive(&jid.to_string(), archive, last_message_timestamp);
+        self.client.send_app_state_patch(&info).await
+    }
+
+    /// Pins or unpins `jid`.
+    pub async fn pin(&self, jid: &Jid, pin: bool) -> Result<()> {
+        debug!(target: "Chats", "Setting pinned={} on {}", pin, jid);
+        self.client
+            .send_app_state_patch(&encode::build_pin(&jid.to_string(), pin))
+            .await
+    }
+
+    /// Mutes `jid` until `mute_end_timestamp` (milliseconds, `-1` forever),
+    /// or unmutes it when `None`.
+    pub async fn mute(&self, jid: &Jid, mute_end_timestamp: Option<i64>) -> Result<()> {
+        debug!(target: "Chats", "Setting mute end {:?} on {}", mute_end_timestamp, jid);
+        self.client
+            .send_app_state_patch(&encode::build_mute(&jid.to_string(), mute_end_timestamp))
+            .await
+    }
+
+    /// Marks `jid` as read or unread on every device.
+    pub async fn mark_read(
+        &self,
+        jid: &Jid,
+        read: bool,
+        last_message_timestamp: Option<i64>,
+    ) -> Result<()> {
+        debug!(target: "Chats", "Setting read={} on {}", read, jid);
+        let info =
+            encode::build_mark_chat_as_read(&jid.to_string(), read, last_message_timestamp);
+        self.client.send_app_state_patch(&info).await
+    }
+
+    /// Sets the disappearing messages timer of the direct chat `jid` to
+    /// `expiration` seconds (`0` turns it off). Unlike the other settings
+    /// this is a protocol message to the contact, stamped with `timestamp`
+    /// (seconds) so both sides agree on which change came last.
+    pub async fn set_ephemeral(&self, jid: &Jid, expiration: u32, timestamp: i64) -> Result<()> {
+        debug!(target: "Chats", "Setting ephemeral expiration {} on {}", expiration, jid);
+        let message = wa::Message {
+            protocol_message: Some(Box::new(wa::message::ProtocolMessage {
+                r#type: Some(wa::message::protocol_message::Type::EphemeralSetting as i32),
+                ephemeral_expiration: Some(expiration),
+                ephemeral_setting_timestamp: Some(timestamp),
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+        self.client.send_message(jid.clone(), message).await?;
+        Ok(())
+    }
+}
+
+impl Client {
+    pub fn chats(&self) -> Chats<'_> {
+        Chats::new(self)
+    }
+}
@@ -12,6 +12,8 @@ use warp_core_binary::node::{Node, NodeContent};
 pub struct IsOnWhatsAppResult {
     pub jid: Jid,
     pub is_registered: bool,
+    /// Phone number as sent in the query (echoed back by the server).
+    pub query: Option<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -267,8 +269,17 @@ impl<'a> Contacts<'a> {
                 let is_registered = contact_node
                     .map(|c| c.attrs().optional_string("type") == Some("in"))
                     .unwrap_or(false);
+                let query = contact_node.and_then(|c| match &c.content {
+                    Some(NodeContent::String(s)) => Some(s.clone()),
+                    Some(NodeContent::Bytes(b)) => String::from_utf8(b.clone()).ok(),
+                    _ => None,
+                });
 
-                results.push(IsOnWhatsAppResult { jid, is_registered });
+                results.push(IsOnWhatsAppResult {
+                    jid,
+                    is_registered,
+                    query,
+                });
             }
         }
 
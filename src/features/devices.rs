@@ -0,0 +1,93 @@
+use crate::client::Client;
+use crate::request::{InfoQuery, IqError};
+use crate::utils::jid_utils::server_jid;
+use anyhow::{Result, anyhow};
+use log::debug;
+use warp_core_binary::builder::NodeBuilder;
+use warp_core_binary::jid::Jid;
+use warp_core_binary::node::NodeContent;
+
+/// A device registered on the account: the phone (device 0) or a companion.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LinkedDevice {
+    pub jid: String,
+    pub device_id: u16,
+    /// The phone the account lives on.
+    pub primary: bool,
+    /// The device this client is paired as.
+    pub current: bool,
+}
+
+/// Marks the primary and the current device in the device list of the
+/// account, ordered by device id.
+pub fn linked_devices(devices: &[Jid], own_device: u16) -> Vec<LinkedDevice> {
+    let mut linked: Vec<_> = devices
+        .iter()
+        .map(|jid| LinkedDevice {
+            jid: jid.to_string(),
+            device_id: jid.device,
+            primary: jid.device == 0,
+            current: jid.device == own_device,
+        })
+        .collect();
+    linked.sort_by_key(|device| device.device_id);
+    linked.dedup_by_key(|device| device.device_id);
+    linked
+}
+
+pub struct Devices<'a> {
+    client: &'a Client,
+}
+
+impl<'a> Devices<'a> {
+    pub(crate) fn new(client: &'a Client) -> Self {
+        Self { client }
+    }
+
+    /// Devices registered on the account, fetched from the server rather
+    /// than the device cache.
+    pub async fn list(&self) -> Result<Vec<LinkedDevice>> {
+        let own = self
+            .client
+            .get_pn()
+            .await
+            .ok_or_else(|| anyhow!("device is not paired"))?;
+        debug!(target: "Devices", "Fetching device list of {}", own.user);
+
+        self.client.invalidate_device_cache(&own.user).await;
+        let devices = self.client.get_user_devices(&[own.to_non_ad()]).await?;
+        Ok(linked_devices(&devices, own.device))
+    }
+
+    /// Unlinks a companion device from the account. WhatsApp only accepts
+    /// this from the primary device, so a companion gets a server error.
+    pub async fn remove_companion(&self, device: &Jid) -> Result<(), IqError> {
+        let remove_node = NodeBuilder::new("remove-companion-device")
+            .attr("jid", device.to_string())
+            .attr("reason", "user_initiated")
+            .build();
+
+        let iq = InfoQuery::set(
+            "md",
+            server_jid(),
+            Some(NodeContent::Nodes(vec![remove_node])),
+        );
+
+        self.client.send_iq(iq).await?;
+        self.client.invalidate_device_cache(&device.user).await;
+        debug!(target: "Devices", "Removed companion device: {}", device);
+        Ok(())
+    }
+}
+
+impl Client {
+    pub fn devices(&self) -> Devices<'_> {
+        Devices::new(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    include!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/tests/features/devices_tests.rs"));
+}
@@ -1,14 +1,17 @@
 use crate::client::Client;
 use crate::request::InfoQuery;
+use serde::Serialize;
 use std::collections::HashMap;
 use std::sync::LazyLock;
 use warp_core::client::context::GroupInfo;
 use warp_core_binary::builder::NodeBuilder;
 use warp_core_binary::jid::{GROUP_SERVER, Jid};
-use warp_core_binary::node::NodeContent;
+use warp_core_binary::node::{Node, NodeContent};
 
 static G_US_JID: LazyLock<Jid> = LazyLock::new(|| Jid::new("", GROUP_SERVER));
 
+const INVITE_LINK_PREFIX: &str = "chat.whatsapp.com/";
+
 #[derive(Debug, Clone)]
 pub struct GroupMetadata {
     pub id: Jid,
@@ -24,6 +27,213 @@ pub struct GroupParticipant {
     pub is_admin: bool,
 }
 
+/// Group preview returned for an invite code, before joining.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InviteInfo {
+    pub id: String,
+    pub subject: String,
+    pub subject_owner: Option<String>,
+    pub subject_time: Option<i64>,
+    pub creator: Option<String>,
+    pub creation: Option<i64>,
+    pub size: u64,
+    pub description: Option<String>,
+    /// Only the participants the server chose to reveal.
+    pub participants: Vec<String>,
+    /// Only admins can send messages.
+    pub announce: bool,
+    /// Only admins can edit the group info.
+    pub restrict: bool,
+}
+
+/// Outcome of joining through an invite code.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JoinedGroup {
+    pub jid: Jid,
+    /// The group requires admin approval; the request is pending.
+    pub pending_approval: bool,
+}
+
+/// Extracts the code from an invite link (`https://chat.whatsapp.com/<code>`);
+/// a bare code is returned trimmed.
+pub fn invite_code(code_or_link: &str) -> &str {
+    let trimmed = code_or_link.trim();
+    let code = match trimmed.find(INVITE_LINK_PREFIX) {
+        Some(pos) => &trimmed[pos + INVITE_LINK_PREFIX.len()..],
+        None => trimmed,
+    };
+    code.split(['?', '#', '/']).next().unwrap_or_default()
+}
+
+fn group_jid(id: &str) -> Jid {
+    if id.contains('@') {
+        id.parse().unwrap_or_else(|_| Jid::group(id))
+    } else {
+        Jid::group(id)
+    }
+}
+
+fn node_text(node: &Node) -> Option<String> {
+    match &node.content {
+        Some(NodeContent::String(s)) if !s.is_empty() => Some(s.clone()),
+        Some(NodeContent::Bytes(b)) if !b.is_empty() => String::from_utf8(b.clone()).ok(),
+        _ => None,
+    }
+}
+
+/// Parses the `<group>` node of an invite info response.
+pub fn parse_invite_info(group_node: &Node) -> InviteInfo {
+    let mut attrs = group_node.attrs();
+    let id = group_jid(attrs.optional_string("id").unwrap_or_default()).to_string();
+    let subject = attrs
+        .optional_string("subject")
+        .unwrap_or_default()
+        .to_string();
+    let subject_owner = attrs.optional_jid("s_o").map(|jid| jid.to_string());
+    let subject_time = attrs.optional_unix_time("s_t");
+    let creator = attrs.optional_jid("creator").map(|jid| jid.to_string());
+    let creation = attrs.optional_unix_time("creation");
+    let size = attrs.optional_u64("size");
+
+    let participants: Vec<String> = group_node
+        .get_children_by_tag("participant")
+        .into_iter()
+        .filter_map(|participant| participant.attrs().optional_jid("jid"))
+        .map(|jid| jid.to_string())
+        .collect();
+    let description = group_node
+        .get_optional_child("description")
+        .and_then(|description| description.get_optional_child("body"))
+        .and_then(node_text);
+
+    InviteInfo {
+        id,
+        subject,
+        subject_owner,
+        subject_time,
+        creator,
+        creation,
+        size: size.unwrap_or(participants.len() as u64),
+        description,
+        participants,
+        announce: group_node.get_optional_child("announcement").is_some(),
+        restrict: group_node.get_optional_child("locked").is_some(),
+    }
+}
+
+/// Parses the response of an invite join: `<group jid>` once joined, or
+/// `<membership_approval_request jid>` when an admin has to approve.
+pub fn parse_join_response(resp_node: &Node) -> Result<JoinedGroup, anyhow::Error> {
+    let (node, pending_approval) = match resp_node.get_optional_child("group") {
+        Some(group) => (group, false),
+        None => (
+            resp_node
+                .get_optional_child("membership_approval_request")
+                .ok_or_else(|| anyhow::anyhow!("<group> not found in invite join response"))?,
+            true,
+        ),
+    };
+    let jid = node
+        .attrs()
+        .optional_jid("jid")
+        .ok_or_else(|| anyhow::anyhow!("group jid missing in invite join response"))?;
+    Ok(JoinedGroup {
+        jid,
+        pending_approval,
+    })
+}
+
+/// Disappearing message timers WhatsApp accepts, in seconds (off, 24 hours,
+/// 7 days, 90 days).
+pub const EPHEMERAL_DURATIONS: [u32; 4] = [0, 86_400, 604_800, 7_776_000];
+
+/// Who may add members to a group.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemberAddMode {
+    AdminAdd,
+    AllMemberAdd,
+}
+
+impl MemberAddMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MemberAddMode::AdminAdd => "admin_add",
+            MemberAddMode::AllMemberAdd => "all_member_add",
+        }
+    }
+
+    pub fn parse(mode: &str) -> Option<Self> {
+        match mode.trim() {
+            "admin_add" => Some(MemberAddMode::AdminAdd),
+            "all_member_add" => Some(MemberAddMode::AllMemberAdd),
+            _ => None,
+        }
+    }
+}
+
+/// A group setting changed by an admin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GroupSetting {
+    /// Only admins can send messages.
+    Announce(bool),
+    /// Only admins can edit the group info.
+    Locked(bool),
+    /// Disappearing messages timer in seconds; `0` turns it off.
+    Ephemeral(u32),
+    MemberAddMode(MemberAddMode),
+}
+
+impl GroupSetting {
+    /// Parses an `updateSetting` action: `announcement`, `not_announcement`,
+    /// `locked` or `unlocked`.
+    pub fn from_action(action: &str) -> Option<Self> {
+        match action.trim() {
+            "announcement" => Some(GroupSetting::Announce(true)),
+            "not_announcement" => Some(GroupSetting::Announce(false)),
+            "locked" => Some(GroupSetting::Locked(true)),
+            "unlocked" => Some(GroupSetting::Locked(false)),
+            _ => None,
+        }
+    }
+
+    /// The child of the `w:g2` set IQ.
+    pub fn node(&self) -> Node {
+        match self {
+            GroupSetting::Announce(true) => NodeBuilder::new("announcement").build(),
+            GroupSetting::Announce(false) => NodeBuilder::new("not_announcement").build(),
+            GroupSetting::Locked(true) => NodeBuilder::new("locked").build(),
+            GroupSetting::Locked(false) => NodeBuilder::new("unlocked").build(),
+            GroupSetting::Ephemeral(0) => NodeBuilder::new("not_ephemeral").build(),
+            GroupSetting::Ephemeral(expiration) => NodeBuilder::new("ephemeral")
+                .attr("expiration", expiration.to_string())
+                .build(),
+            GroupSetting::MemberAddMode(mode) => NodeBuilder::new("member_add_mode")
+                .string_content(mode.as_str())
+                .build(),
+        }
+    }
+}
+
+/// Parses a group JID given as `<id>@g.us` or a bare id. `None` for JIDs
+/// of another server.
+pub fn parse_group_jid(raw: &str) -> Option<Jid> {
+    let raw = raw.trim();
+    if raw.is_empty() {
+        return None;
+    }
+    if !raw.contains('@') {
+        return Some(Jid::group(raw));
+    }
+    raw.parse::<Jid>()
+        .ok()
+        .filter(|jid| jid.server == GROUP_SERVER && !jid.user.is_empty())
+}
+
+fn invite_node(code: &str) -> Node {
+    NodeBuilder::new("invite").attr("code", code).build()
+}
+
 pub struct Groups<'a> {
     client: &'a Client,
 }
@@ -215,6 +425,62 @@ impl<'a> Groups<'a> {
             addressing_mode,
         })
     }
+
+    /// Changes a setting of `jid`; the account must be a group admin.
+    pub async fn update_setting(
+        &self,
+        jid: &Jid,
+        setting: GroupSetting,
+    ) -> Result<(), anyhow::Error> {
+        let iq = InfoQuery::set(
+            "w:g2",
+            jid.clone(),
+            Some(NodeContent::Nodes(vec![setting.node()])),
+        );
+
+        self.client.send_iq(iq).await?;
+        Ok(())
+    }
+
+    /// Group preview for an invite code (or link), without joining.
+    pub async fn get_invite_info(&self, code: &str) -> Result<InviteInfo, anyhow::Error> {
+        let iq = InfoQuery::get(
+            "w:g2",
+            G_US_JID.clone(),
+            Some(NodeContent::Nodes(vec![invite_node(invite_code(code))])),
+        );
+
+        let resp_node = self.client.send_iq(iq).await?;
+
+        let group_node = resp_node
+            .get_optional_child("group")
+            .ok_or_else(|| anyhow::anyhow!("<group> not found in invite info response"))?;
+
+        Ok(parse_invite_info(group_node))
+    }
+
+    /// Joins the group of an invite code (or link).
+    pub async fn join_with_invite(&self, code: &str) -> Result<JoinedGroup, anyhow::Error> {
+        let iq = InfoQuery::set(
+            "w:g2",
+            G_US_JID.clone(),
+            Some(NodeContent::Nodes(vec![invite_node(invite_code(code))])),
+        );
+
+        let resp_node = self.client.send_iq(iq).await?;
+        let joined = parse_join_response(&resp_node)?;
+
+        if !joined.pending_approval {
+            // Cached participants no longer include us.
+            self.client
+                .get_group_cache()
+                .await
+                .invalidate(&joined.jid)
+                .await;
+        }
+
+        Ok(joined)
+    }
 }
 
 impl Client {
@@ -1,17 +1,32 @@
 mod blocking;
+mod business;
+mod chats;
 mod chatstate;
 mod contacts;
+mod devices;
 mod groups;
 mod mex;
 mod presence;
 
 pub use blocking::{Blocking, BlocklistEntry};
 
+pub use business::{
+    Business, BusinessCategory, BusinessHours, BusinessHoursDay, BusinessProfile,
+    BusinessProfileUpdate, CatalogPage, Collection, Product,
+};
+
+pub use chats::Chats;
+
 pub use chatstate::{ChatStateType, Chatstate};
 
 pub use contacts::{ContactInfo, Contacts, IsOnWhatsAppResult, ProfilePicture, UserInfo};
 
-pub use groups::{GroupMetadata, GroupParticipant, Groups};
+pub use devices::{Devices, LinkedDevice, linked_devices};
+
+pub use groups::{
+    EPHEMERAL_DURATIONS, GroupMetadata, GroupParticipant, GroupSetting, Groups, InviteInfo,
+    JoinedGroup, MemberAddMode, invite_code, parse_group_jid,
+};
 
 pub use mex::{Mex, MexError, MexErrorExtensions, MexGraphQLError, MexRequest, MexResponse};
 
@@ -40,8 +40,11 @@ pub use features::{
 };
 
 pub mod bot;
+pub mod cli;
 pub mod lid_pn_cache;
 pub mod openapi;
+#[cfg(feature = "client")]
+pub mod sdk;
 pub mod server;
 pub mod spam_report;
 pub mod sync_task;
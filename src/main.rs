@@ -1,17 +1,14 @@
-use base64::Engine as _;
-use chatwarp_api::api_store::{ApiStore, NoopApiStore};
 use chatwarp_api::bot::Bot;
+use chatwarp_api::config::{DatabaseConfig, Env, ServerConfig, StartupConfig};
 use chatwarp_api::models::message_model::{IncomingMessageMetadata, MessageContext};
 use chatwarp_api::pair_code::PairCodeOptions;
 use chatwarp_api::upload::UploadResponse;
 use chatwarp_api_tokio_transport::TokioWebSocketTransportFactory;
-use chatwarp_api_ureq_http_client::UreqHttpClient;
 use chrono::Utc;
 use serde_json::json;
 use std::io::Cursor;
 use std::sync::Arc;
-use tracing::{error, info};
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+use tracing::{error, info, warn};
 use waproto::whatsapp as wa;
 use warp_core::download::{Downloadable, MediaType};
 use warp_core::proto_helpers::MessageExt;
@@ -25,34 +22,122 @@ use warp_core::types::events::Event;
 //   cargo run -- -p 15551234567                    # Short form
 //   cargo run -- -p 15551234567 --code MYCODE12    # Custom 8-char pair code
 //   cargo run -- -p 15551234567 -c MYCODE12        # Short form
-
-use chatwarp_api::server::{AppState, InstanceState, SessionRuntime, create_router};
+//   cargo run -- --check-config                    # Validate the environment and exit
+//   cargo run -- migrate                           # Apply DB migrations and exit
+//   cargo run -- instance create|delete|list       # Manage instances (PostgreSQL)
+//   cargo run -- auth export -o auth.json          # Export the device credentials
+//   cargo run -- auth import auth.json             # Import them on another host
+//   cargo run -- secrets encrypt                   # Encrypt stored secrets (PostgreSQL)
+//   cargo run -- send-test 5511999990000           # Send a test message and exit
+//   cargo run -- help                              # Every subcommand and flag
+
+use chatwarp_api::cli::{self, Cli, Command, ServeArgs};
+use chatwarp_api::server::instance_logs::InstanceLogs;
+use chatwarp_api::server::logging;
+use chatwarp_api::server::preflight::Severity;
+use chatwarp_api::server::runtime_config::{self, LogLevelReloader, RateLimiter, RuntimeConfig};
+use chatwarp_api::server::{AppState, InstanceState, SessionRuntime, create_router, supervisor};
+use clap::Parser;
 use dashmap::DashMap;
 
-fn init_tracing() {
-    let env_filter = tracing_subscriber::EnvFilter::try_from_default_env()
-        .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info"));
-        // .add_directive("ureq_proto::util=warn".parse().unwrap());
-
-    let _ = tracing_subscriber::registry()
-        .with(env_filter)
-        .with(
-            tracing_subscriber::fmt::layer()
-                .compact()
-                .with_target(true)
-                .with_thread_ids(false),
-        )
-        .try_init();
+fn main() {
+    let startup = StartupConfig::from_env();
+    let instance_logs = InstanceLogs::new(startup.instance_log_buffer);
+    let log_level_reloader = logging::init(
+        &startup.logging,
+        &startup.runtime.log_filter(),
+        &instance_logs,
+    );
+    #[cfg(feature = "sentry")]
+    let _sentry = startup
+        .sentry
+        .map(chatwarp_api::server::error_reporting::init);
+
+    let cli = Cli::parse();
+    if cli.check_config {
+        let preflight = chatwarp_api::server::preflight::PreflightReport::check(&Env::process());
+        println!("{preflight}");
+        std::process::exit(if preflight.has_errors() { 1 } else { 0 });
+    }
+
+    let rt = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .expect("Failed to build tokio runtime");
+
+    match cli.into_command() {
+        Command::Serve(args) => serve(
+            &rt,
+            args,
+            startup.runtime,
+            instance_logs,
+            log_level_reloader,
+        ),
+        command => {
+            if let Err(e) = rt.block_on(run_command(command)) {
+                error!(error = %e, "Command failed");
+                std::process::exit(1);
+            }
+        }
+    }
 }
 
-fn main() {
-    init_tracing();
+/// Runs the ops subcommands, which print their result to stdout.
+async fn run_command(command: Command) -> anyhow::Result<()> {
+    let mut database = DatabaseConfig::from_env()?;
+    let mut out = std::io::stdout();
+    match command {
+        Command::Serve(_) => anyhow::bail!("serve is not an ops command"),
+        Command::Migrate => {
+            database.run_migrations = true;
+            cli::open_storage(&database).await?;
+            info!("Migrations applied");
+        }
+        Command::Instance(command) => cli::run_instance(&database, command, &mut out).await?,
+        Command::Auth(command) => {
+            let storage = cli::open_storage(&database).await?;
+            cli::run_auth(storage.backend.as_ref(), command, &mut out).await?;
+        }
+        Command::Secrets(command) => cli::run_secrets(&database, command, &mut out).await?,
+        Command::SendTest(args) => {
+            let storage = cli::open_storage(&database).await?;
+            let id = cli::send_test(storage.backend, args).await?;
+            println!("{}", json!({"status": "sent", "id": id}));
+        }
+    }
+    Ok(())
+}
 
-    // Parse CLI arguments for phone number and optional custom code
-    let args: Vec<String> = std::env::args().collect();
-    let phone_number = parse_arg(&args, "--phone", "-p");
-    let custom_code = parse_arg(&args, "--code", "-c");
+fn serve(
+    rt: &tokio::runtime::Runtime,
+    args: ServeArgs,
+    initial_config: RuntimeConfig,
+    instance_logs: InstanceLogs,
+    log_level_reloader: LogLevelReloader,
+) {
+    let preflight = chatwarp_api::server::preflight::PreflightReport::check(&Env::process());
+    for finding in &preflight.findings {
+        match finding.severity {
+            Severity::Error => error!(variable = %finding.variable, "{}", finding.message),
+            Severity::Warning => warn!(variable = %finding.variable, "{}", finding.message),
+        }
+    }
+    if preflight.has_errors() {
+        error!("Invalid configuration, refusing to start (run with --check-config for the full report)");
+        return;
+    }
+    let config = match ServerConfig::from_env() {
+        Ok(config) => config,
+        Err(e) => {
+            error!(error = %e, "Invalid configuration");
+            return;
+        }
+    };
 
+    let ServeArgs {
+        phone: phone_number,
+        code: custom_code,
+    } = args;
     if let Some(ref phone) = phone_number {
         info!(phone = %phone, "Phone number provided via CLI");
         if let Some(ref code) = custom_code {
@@ -61,84 +146,20 @@ fn main() {
         info!("Using pair code authentication concurrently with QR");
     }
 
-    let rt = tokio::runtime::Builder::new_multi_thread()
-        .enable_all()
-        .build()
-        .expect("Failed to build tokio runtime");
-
     // Pre-load settings from env before spawning tokio threads
     let initial_settings = chatwarp_api::server::Settings::new();
 
     rt.block_on(async {
-        let database_url = std::env::var("DATABASE_URL").ok();
-
-        let (backend, api_store): (Arc<dyn chatwarp_api::store::Backend>, Arc<dyn ApiStore>) =
-            if let Some(url) = database_url {
-                if url.starts_with("postgres://") || url.starts_with("postgresql://") {
-                    #[cfg(feature = "postgres-storage")]
-                    {
-                        match chatwarp_api::store::PostgresStore::new(&url).await {
-                            Ok(store) => {
-                                info!("PostgreSQL backend initialized");
-                                let store = Arc::new(store);
-                                (store.clone(), store as Arc<dyn ApiStore>)
-                            }
-                            Err(e) => {
-                                error!(error = %e, "Failed to create PostgreSQL backend");
-                                return;
-                            }
-                        }
-                    }
-                    #[cfg(not(feature = "postgres-storage"))]
-                    {
-                        error!("PostgreSQL support is not enabled in this build");
-                        return;
-                    }
-                } else {
-                    #[cfg(feature = "sqlite-storage")]
-                    {
-                        match chatwarp_api::store::SqliteStore::new(&url).await {
-                            Ok(store) => {
-                                info!(database_url = %url, "SQLite backend initialized with custom URL");
-                                (Arc::new(store), Arc::new(NoopApiStore))
-                            }
-                            Err(e) => {
-                                error!(database_url = %url, error = %e, "Failed to create SQLite backend");
-                                return;
-                            }
-                        }
-                    }
-                    #[cfg(not(feature = "sqlite-storage"))]
-                    {
-                        error!("SQLite support is not enabled in this build");
-                        return;
-                    }
-                }
-            } else {
-                #[cfg(feature = "sqlite-storage")]
-                {
-                    match chatwarp_api::store::SqliteStore::new("whatsapp.db").await {
-                        Ok(store) => {
-                            info!(database_url = "whatsapp.db", "SQLite backend initialized with default database");
-                            (Arc::new(store), Arc::new(NoopApiStore))
-                        }
-                        Err(e) => {
-                            error!(error = %e, "Failed to create SQLite backend");
-                            return;
-                        }
-                    }
-                }
-                #[cfg(not(feature = "sqlite-storage"))]
-                {
-                    error!("No database URL provided and SQLite support is not enabled");
-                    return;
-                }
-            };
+        let database = &config.database;
+        let (backend, api_store) = match cli::open_storage(database).await {
+            Ok(storage) => (storage.backend, storage.api_store),
+            Err(e) => {
+                error!(error = %e, "Failed to open the storage");
+                return;
+            }
+        };
 
-        let api_password = std::env::var("CHATWARP_PASSWORD")
-            .ok()
-            .filter(|v| !v.is_empty());
-        let api_password_hash = api_password.as_deref().map(|v| {
+        let api_password_hash = config.api_password.as_deref().map(|v| {
             use sha2::{Digest, Sha256};
             let mut hasher = Sha256::new();
             hasher.update(v.as_bytes());
@@ -151,28 +172,102 @@ fn main() {
             info!("HTTP API auth enabled via CHATWARP_PASSWORD");
         }
 
-        let session_ttl_seconds = std::env::var("CHATWARP_SESSION_TTL_SECONDS")
-            .ok()
-            .and_then(|v| v.parse::<u64>().ok())
-            .unwrap_or(1800);
-
         let (message_notify_tx, message_notify_rx) = tokio::sync::mpsc::channel(1024);
+        let (outbox_notify_tx, outbox_notify_rx) = tokio::sync::mpsc::channel(1);
+
+        #[cfg(feature = "nats")]
+        let nats = match config.nats {
+            Some(config) => {
+                let url = config.url.clone();
+                match chatwarp_api::server::nats::NatsSink::start(api_store.clone(), config).await {
+                    Ok(sink) => {
+                        info!(url = %url, "NATS event sink started");
+                        Some(sink)
+                    }
+                    Err(e) => {
+                        error!(url = %url, error = %e, "Failed to start NATS event sink");
+                        None
+                    }
+                }
+            }
+            None => None,
+        };
+
+        let cache = chatwarp_api::server::cache::HotCache::connect(config.cache).await;
+
+        let http = match chatwarp_api::server::http_client::SharedHttpClient::from_config(config.http)
+        {
+            Ok(http) => http,
+            Err(e) => {
+                error!(error = %e, "Invalid outbound HTTP client configuration");
+                return;
+            }
+        };
+
+        let default_instance_name = "default".to_string();
 
         // Initialize AppState
         let app_state = Arc::new(AppState {
             instances: DashMap::new(),
             sessions_runtime: DashMap::new(),
             api_store: api_store.clone(),
+            secrets: database.secrets.clone(),
+            database_provider: database.provider,
+            started_at: Utc::now(),
             clients: DashMap::new(),
             settings: Arc::new(tokio::sync::RwLock::new(initial_settings)),
             api_password_hash,
-            session_ttl_seconds,
+            session_ttl_seconds: config.session_ttl_seconds,
+            auth_trust_forwarded_for: config.trust_forwarded_for,
             message_notify: message_notify_tx,
+            outbox_notify: outbox_notify_tx,
             webhook_config_cache: DashMap::new(),
+            instance_meta_cache: DashMap::new(),
+            auto_rules_cache: DashMap::new(),
+            runtime_config: Arc::new(std::sync::RwLock::new(initial_config)),
+            rate_limiter: RateLimiter::default(),
+            log_level_reloader: Some(log_level_reloader),
+            event_hub: chatwarp_api::server::ws::EventHub::new(config.ws),
+            global_sinks: config.global_sinks,
+            sse: chatwarp_api::server::sse::SseHub::new(config.sse),
+            event_history: chatwarp_api::server::event_history::EventHistory::new(
+                config.event_history,
+            ),
+            meta: config.meta,
+            number_cache: chatwarp_api::server::numbers::NumberCache::new(config.numbers_cache_ttl),
+            profile_pictures: config.profile_pictures,
+            cache,
+            message_counters: Arc::default(),
+            inbound_dedup: chatwarp_api::server::dedup::InboundDedup::new(config.inbound_dedup),
+            retention: config.retention,
+            retention_metrics: Arc::default(),
+            http,
+            body_limit: chatwarp_api::server::body::DEFAULT_BODY_LIMIT,
+            uploads: config.uploads,
+            thumbnails: config.thumbnails,
+            timeouts: config.timeouts,
+            exports: config.exports,
+            file_cache: chatwarp_api::server::static_files::FileCache::new(
+                config.static_cache_bytes,
+            ),
+            api_mount: config.api_mount,
+            health: config.health,
+            instance_logs,
+            participants: config.participants,
+            handshake_gate: Arc::new(chatwarp_api::client::HandshakeGate::new(config.handshake)),
+            standby: chatwarp_api::server::standby::Standby::new(
+                config.standby,
+                default_instance_name.clone(),
+            ),
+            restart_policy: config.restart_policy,
+            #[cfg(feature = "sentry")]
+            sentry_hubs: chatwarp_api::server::error_reporting::InstanceHubs::default(),
+            #[cfg(feature = "nats")]
+            nats,
         });
+        runtime_config::restore_overrides(&app_state).await;
 
         // Initialize default instance
-        let default_instance_name = "default".to_string();
         app_state
             .instances
             .insert(default_instance_name.clone(), InstanceState::new());
@@ -180,6 +275,11 @@ fn main() {
             .sessions_runtime
             .insert(default_instance_name.clone(), SessionRuntime::new());
 
+        chatwarp_api::server::outbox::spawn_dispatcher(
+            app_state.clone(),
+            config.outbox,
+            outbox_notify_rx,
+        );
         chatwarp_api::server::webhooks::spawn_worker(app_state.clone());
         let startup_enabled = app_state.settings.read().await.is_event_enabled("APPLICATION_STARTUP");
         if startup_enabled {
@@ -190,13 +290,65 @@ fn main() {
             chatwarp_api::server::webhooks::enqueue(&app_state, None, "MESSAGES_SET", json!({})).await;
         }
 
+        // Start Axum Server
+        let app = create_router(app_state.clone());
+        let addr = std::net::SocketAddr::from(([0, 0, 0, 0], config.port));
+        let tls = config.tls;
+        let scheme = match tls {
+            chatwarp_api::server::tls::TlsMode::Off => "http",
+            _ => "https",
+        };
+        info!(address = %addr, scheme, "HTTP server listening");
+
+        let mut server_handle = tokio::spawn(async move {
+            if let Err(e) = chatwarp_api::server::tls::serve(addr, app, tls).await {
+                error!(error = %e, "HTTP server failed");
+            }
+        });
+
+        // A standby serves the API and builds no client until promoted.
+        tokio::select! {
+            _ = chatwarp_api::server::standby::wait_for_lease(&app_state) => {}
+            _ = &mut server_handle => {
+                info!("Server stopped");
+                return;
+            }
+            _ = shutdown_signal() => {
+                info!("Shutdown signal received");
+                return;
+            }
+        }
+
         let transport_factory = TokioWebSocketTransportFactory::new();
-        let http_client = UreqHttpClient::new();
+        let http_client = app_state.http.clone();
 
         let mut builder = Bot::builder()
             .with_backend(backend)
             .with_transport_factory(transport_factory)
-            .with_http_client(http_client);
+            .with_http_client(http_client)
+            .with_handshake_gate(app_state.handshake_gate.clone())
+            .with_save_debounce(config.save_debounce)
+            .with_version_config(config.wa_version);
+
+        // Browser and WA web version stored for the instance (POST /sessions)
+        let fingerprint = match chatwarp_api::server::instance_fingerprint::load(
+            &app_state,
+            &default_instance_name,
+        )
+        .await
+        {
+            Ok(fingerprint) => fingerprint,
+            Err(e) => {
+                tracing::debug!(error = %e, "No stored browser or WA version for the instance");
+                Default::default()
+            }
+        };
+        if let Some(browser) = fingerprint.browser {
+            builder = builder.with_browser(browser);
+        }
+        if let Some(version) = fingerprint.wa_version {
+            builder = builder.with_version(version);
+        }
 
         // Add pair code authentication if phone number provided
         if let Some(phone) = phone_number {
@@ -216,23 +368,6 @@ fn main() {
                 let instance_name = name_for_bot.clone();
                 async move {
                     match event {
-                        Event::PairingQrCode { code, timeout } => {
-                            info!(timeout_secs = timeout.as_secs(), qr_code = %code, "Pairing QR code received");
-
-                            if let Some(instance) = state.instances.get(&instance_name) {
-                                *instance.qr_code.write().await = Some(code.clone());
-                                *instance.connection_state.write().await = "qr_pending".to_string();
-                                let mut count = instance.qr_count.write().await;
-                                *count += 1;
-                            }
-
-                            chatwarp_api::server::webhooks::enqueue(
-                                &state,
-                                Some(&instance_name),
-                                "QRCODE_UPDATED",
-                                json!({ "qrcode": code, "timeout": timeout.as_secs() })
-                            ).await;
-                        }
                         Event::PairingCode { code, timeout } => {
                             info!(
                                 timeout_secs = timeout.as_secs(),
@@ -285,6 +420,31 @@ fn main() {
                                 let bg_info = Arc::new(info.clone());
 
                                 tokio::spawn(async move {
+                                    // Redeliveries after a reconnect reach no sink twice.
+                                    if !chatwarp_api::server::dedup::claim(
+                                        &bg_state,
+                                        bg_instance.as_str(),
+                                        bg_remote.as_str(),
+                                        &bg_info.id,
+                                    )
+                                    .await
+                                    {
+                                        return;
+                                    }
+
+                                    if let Err(e) = chatwarp_api::server::media::store_media_message(
+                                        &bg_state,
+                                        bg_instance.as_str(),
+                                        &bg_info.id,
+                                        bg_remote.as_str(),
+                                        is_from_me,
+                                        bg_msg.as_ref(),
+                                    )
+                                    .await
+                                    {
+                                        tracing::debug!(error = %e, "Media message not stored for later download");
+                                    }
+
                                     let base64_enabled = match chatwarp_api::server::webhooks::load_instance_webhook(
                                         &bg_state,
                                         bg_instance.as_str(),
@@ -293,17 +453,13 @@ fn main() {
                                     {
                                         Ok(Some(cfg)) if cfg.enabled && cfg.base64 => true,
                                         _ => {
-                                            let global_enabled = std::env::var("WEBHOOK_GLOBAL_ENABLED")
-                                                .ok()
-                                                .map(|v| v == "true" || v == "1")
-                                                .unwrap_or(false);
-                                            let global_base64 = std::env::var("WEBHOOK_GLOBAL_WEBHOOK_BASE64")
-                                                .ok()
-                                                .map(|v| v == "true" || v == "1")
-                                                .unwrap_or(false);
-                                            global_enabled && global_base64
+                                            let global = bg_state.runtime_config().webhook;
+                                            global.enabled && global.base64
                                         }
                                     };
+                                    let base64_limit = chatwarp_api::server::media::webhook_base64_limit(
+                                        &bg_state.runtime_config(),
+                                    );
 
                                     let message_payload = if let Some(image) = bg_msg.as_ref().image_message.as_deref() {
                                         let mut message = serde_json::Map::new();
@@ -323,21 +479,14 @@ fn main() {
                                         }
 
                                         if base64_enabled {
-                                            match bg_client.download(image).await {
-                                                Ok(bytes) => {
-                                                    let mime = image
-                                                        .mimetype
-                                                        .as_deref()
-                                                        .unwrap_or("application/octet-stream");
-                                                    let encoded = base64::engine::general_purpose::STANDARD
-                                                        .encode(bytes);
-                                                    let data_url = format!("data:{};base64,{}", mime, encoded);
-                                                    message.insert("base64".to_string(), json!(data_url));
-                                                }
-                                                Err(e) => {
-                                                    error!(error = %e, "Failed to download image for webhook base64");
-                                                }
-                                            }
+                                            chatwarp_api::server::media::embed_base64(
+                                                &bg_client,
+                                                image,
+                                                image.mimetype.as_deref(),
+                                                base64_limit,
+                                                &mut message,
+                                            )
+                                            .await;
                                         }
 
                                         serde_json::Value::Object(message)
@@ -359,21 +508,14 @@ fn main() {
                                         }
 
                                         if base64_enabled {
-                                            match bg_client.download(video).await {
-                                                Ok(bytes) => {
-                                                    let mime = video
-                                                        .mimetype
-                                                        .as_deref()
-                                                        .unwrap_or("application/octet-stream");
-                                                    let encoded = base64::engine::general_purpose::STANDARD
-                                                        .encode(bytes);
-                                                    let data_url = format!("data:{};base64,{}", mime, encoded);
-                                                    message.insert("base64".to_string(), json!(data_url));
-                                                }
-                                                Err(e) => {
-                                                    error!(error = %e, "Failed to download video for webhook base64");
-                                                }
-                                            }
+                                            chatwarp_api::server::media::embed_base64(
+                                                &bg_client,
+                                                video,
+                                                video.mimetype.as_deref(),
+                                                base64_limit,
+                                                &mut message,
+                                            )
+                                            .await;
                                         }
 
                                         serde_json::Value::Object(message)
@@ -395,21 +537,14 @@ fn main() {
                                         }
 
                                         if base64_enabled {
-                                            match bg_client.download(audio).await {
-                                                Ok(bytes) => {
-                                                    let mime = audio
-                                                        .mimetype
-                                                        .as_deref()
-                                                        .unwrap_or("application/octet-stream");
-                                                    let encoded = base64::engine::general_purpose::STANDARD
-                                                        .encode(bytes);
-                                                    let data_url = format!("data:{};base64,{}", mime, encoded);
-                                                    message.insert("base64".to_string(), json!(data_url));
-                                                }
-                                                Err(e) => {
-                                                    error!(error = %e, "Failed to download audio for webhook base64");
-                                                }
-                                            }
+                                            chatwarp_api::server::media::embed_base64(
+                                                &bg_client,
+                                                audio,
+                                                audio.mimetype.as_deref(),
+                                                base64_limit,
+                                                &mut message,
+                                            )
+                                            .await;
                                         }
 
                                         serde_json::Value::Object(message)
@@ -434,21 +569,14 @@ fn main() {
                                         }
 
                                         if base64_enabled {
-                                            match bg_client.download(doc).await {
-                                                Ok(bytes) => {
-                                                    let mime = doc
-                                                        .mimetype
-                                                        .as_deref()
-                                                        .unwrap_or("application/octet-stream");
-                                                    let encoded = base64::engine::general_purpose::STANDARD
-                                                        .encode(bytes);
-                                                    let data_url = format!("data:{};base64,{}", mime, encoded);
-                                                    message.insert("base64".to_string(), json!(data_url));
-                                                }
-                                                Err(e) => {
-                                                    error!(error = %e, "Failed to download document for webhook base64");
-                                                }
-                                            }
+                                            chatwarp_api::server::media::embed_base64(
+                                                &bg_client,
+                                                doc,
+                                                doc.mimetype.as_deref(),
+                                                base64_limit,
+                                                &mut message,
+                                            )
+                                            .await;
                                         }
 
                                         serde_json::Value::Object(message)
@@ -470,21 +598,14 @@ fn main() {
                                         }
 
                                         if base64_enabled {
-                                            match bg_client.download(sticker).await {
-                                                Ok(bytes) => {
-                                                    let mime = sticker
-                                                        .mimetype
-                                                        .as_deref()
-                                                        .unwrap_or("application/octet-stream");
-                                                    let encoded = base64::engine::general_purpose::STANDARD
-                                                        .encode(bytes);
-                                                    let data_url = format!("data:{};base64,{}", mime, encoded);
-                                                    message.insert("base64".to_string(), json!(data_url));
-                                                }
-                                                Err(e) => {
-                                                    error!(error = %e, "Failed to download sticker for webhook base64");
-                                                }
-                                            }
+                                            chatwarp_api::server::media::embed_base64(
+                                                &bg_client,
+                                                sticker,
+                                                sticker.mimetype.as_deref(),
+                                                base64_limit,
+                                                &mut message,
+                                            )
+                                            .await;
                                         }
 
                                         serde_json::Value::Object(message)
@@ -508,6 +629,32 @@ fn main() {
                                             json!(bg_sender.as_str())
                                         },
                                     );
+                                    // Alternate addressing (LID <-> phone number) when known,
+                                    // so consumers can match chats stored under either form.
+                                    if let Ok(remote) = chatwarp_api::server::jid::parse(bg_remote.as_str())
+                                        && let Some(alt) =
+                                            chatwarp_api::server::jid::alternate(&bg_client, &remote.to_non_ad()).await
+                                    {
+                                        key_item.insert("remoteJidAlt".to_string(), json!(alt.to_string()));
+                                    }
+                                    if !is_from_me {
+                                        let participant_alt = match bg_info.source.sender_alt.clone() {
+                                            Some(alt) => Some(alt),
+                                            None => {
+                                                chatwarp_api::server::jid::alternate(
+                                                    &bg_client,
+                                                    &bg_info.source.sender.to_non_ad(),
+                                                )
+                                                .await
+                                            }
+                                        };
+                                        if let Some(alt) = participant_alt {
+                                            key_item.insert(
+                                                "participantAlt".to_string(),
+                                                json!(alt.to_non_ad().to_string()),
+                                            );
+                                        }
+                                    }
                                     if !bg_info.push_name.is_empty() {
                                         key_item.insert(
                                             "senderName".to_string(),
@@ -621,25 +768,6 @@ fn main() {
                                 }
                             }
                         }
-                        Event::Connected(_) => {
-                            info!("Bot connected successfully");
-                            if let Some(instance) = state.instances.get(&instance_name) {
-                                *instance.qr_code.write().await = None;
-                                *instance.connection_state.write().await = "connected".to_string();
-                            }
-                            chatwarp_api::server::webhooks::enqueue(
-                                &state,
-                                Some(&instance_name),
-                                "CONNECTION_UPDATE",
-                                json!({ "action": "update", "state": "open" })
-                            ).await;
-                            // Pre-warm E2E sessions for recent DM chats in the background.
-                            // This eliminates the ~20-30s first-message latency for known contacts.
-                            tokio::spawn(chatwarp_api::server::messages_worker::warm_sessions(
-                                state.clone(),
-                                instance_name.clone(),
-                            ));
-                        }
                         Event::Receipt(receipt) => {
                             info!(message_ids = ?receipt.message_ids, receipt_type = ?receipt.r#type, "Received receipt");
                         }
@@ -690,19 +818,6 @@ fn main() {
                                 .await
                                 .ok();
                         }
-                        Event::LoggedOut(_) => {
-                            error!("Bot was logged out");
-                            if let Some(instance) = state.instances.get(&instance_name) {
-                                *instance.connection_state.write().await =
-                                    "disconnected".to_string();
-                            }
-                            chatwarp_api::server::webhooks::enqueue(
-                                &state,
-                                Some(&instance_name),
-                                "CONNECTION_UPDATE",
-                                json!({ "action": "update", "state": "close", "reason": "loggedOut" })
-                            ).await;
-                        }
                         _ => {
                             // debug!("Received unhandled event: {:?}", event);
                         }
@@ -713,47 +828,85 @@ fn main() {
             .await
             .expect("Failed to build bot");
 
+        chatwarp_api::server::session_events::attach(
+            app_state.clone(),
+            default_instance_name.clone(),
+            &bot.client(),
+        );
         app_state
             .clients
             .insert(default_instance_name.clone(), bot.client());
+        if !app_state.standby.is_primary() {
+            chatwarp_api::server::standby::fence(&app_state, "lease lost during startup").await;
+        }
         tokio::spawn(chatwarp_api::server::messages_worker::spawn_messages_worker(
             app_state.clone(),
             message_notify_rx,
         ));
+        chatwarp_api::server::maintenance::spawn_scheduler(app_state.clone());
+        chatwarp_api::server::uploads::spawn_sweeper(app_state.uploads.clone());
+        chatwarp_api::server::exports::spawn_sweeper(app_state.clone());
+        chatwarp_api::server::retention::spawn_pruner(app_state.clone());
+        chatwarp_api::server::dedup::spawn_sweeper(app_state.clone());
+
+        if let Err(e) = bot.start().await {
+            error!(error = %e, "Bot failed to start");
+            return;
+        }
+        let runner_client = bot.client();
+        let shutdown_client = bot.client();
+        let bot_handle = tokio::spawn(supervisor::supervise(
+            app_state.clone(),
+            default_instance_name.clone(),
+            app_state.restart_policy,
+            move |attempt| {
+                let client = runner_client.clone();
+                async move {
+                    if attempt > 0 {
+                        client.reset_run_state().await;
+                    }
+                    client.run().await;
+                }
+            },
+        ));
 
-        let bot_handle = match bot.run().await {
-            Ok(handle) => handle,
-            Err(e) => {
-                error!(error = %e, "Bot failed to start");
-                return;
-            }
-        };
-
-        // Start Axum Server
-        let app = create_router(app_state);
-        let port = std::env::var("PORT")
-            .ok()
-            .and_then(|p| p.parse().ok())
-            .unwrap_or(8080);
-        let addr = std::net::SocketAddr::from(([0, 0, 0, 0], port));
-
-        info!(address = %addr, "HTTP server listening");
-        let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
-
-        let server_handle = tokio::spawn(async move {
-            axum::serve(listener, app.into_make_service())
-                .await
-                .unwrap();
-        });
+        chatwarp_api::server::status::log_banner(
+            &chatwarp_api::server::status::collect(&app_state).await,
+        );
 
-        // Wait for both tasks
+        // Wait for both tasks, or Ctrl-C / SIGINT / SIGTERM
         tokio::select! {
             _ = bot_handle => info!("Bot stopped"),
             _ = server_handle => info!("Server stopped"),
+            _ = shutdown_signal() => info!("Shutdown signal received"),
         }
+        if let Err(e) = shutdown_client.persistence_manager().flush().await {
+            error!(error = %e, "Failed to flush auth state on shutdown");
+        }
+        chatwarp_api::server::standby::release(&app_state).await;
     });
 }
 
+/// Resolves on Ctrl-C, or on SIGTERM (sent by `docker stop` and Kubernetes)
+/// on unix, so the write-behind buffer is flushed either way.
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{SignalKind, signal};
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = terminate.recv() => {}
+                }
+                return;
+            }
+            Err(e) => warn!(error = %e, "Failed to listen for SIGTERM"),
+        }
+    }
+    let _ = tokio::signal::ctrl_c().await;
+}
+
 trait MediaPing: Downloadable {
     fn media_type(&self) -> MediaType;
 
@@ -871,19 +1024,3 @@ async fn handle_media_ping(ctx: &MessageContext, media: &(dyn MediaPing + '_)) {
         info!("Media pong reply sent successfully.");
     }
 }
-
-/// Parse a CLI argument by its long and short flags.
-/// Supports: --flag VALUE, -f VALUE, --flag=VALUE
-fn parse_arg(args: &[String], long: &str, short: &str) -> Option<String> {
-    let long_prefix = format!("{}=", long);
-    let mut iter = args.iter().skip(1); // Skip program name
-    while let Some(arg) = iter.next() {
-        if arg == long || arg == short {
-            return iter.next().cloned();
-        }
-        if let Some(value) = arg.strip_prefix(&long_prefix) {
-            return Some(value.to_string());
-        }
-    }
-    None
-}
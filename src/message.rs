@@ -175,6 +175,7 @@ impl Client {
                     info.source.sender,
                     reason
                 );
+                client.retry_stats.record_exhausted();
                 // Send PDO request immediately (no delay) as last resort
                 client.spawn_pdo_request_with_options(&info, true);
                 return;
@@ -194,12 +195,14 @@ impl Client {
             // Send the retry receipt with the actual retry count and reason
             match client.send_retry_receipt(&info, retry_count, reason).await {
                 Ok(()) => {
+                    client.retry_stats.record_receipt_sent();
                     debug!(
                         "Sent retry receipt #{} for message {} from {} [{:?}]",
                         retry_count, info.id, info.source.sender, reason
                     );
                 }
                 Err(e) => {
+                    client.retry_stats.record_receipt_failure();
                     log::error!(
                         "Failed to send retry receipt #{} for message {} [{:?}]: {:?}",
                         retry_count,
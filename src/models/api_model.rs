@@ -0,0 +1,212 @@
+//! Request and response bodies of the HTTP API, shared by the handlers and
+//! the typed client (`client` feature, [`crate::sdk`]).
+//!
+//! `instance/create`, `sendText`, `findMessages` and `whatsappNumbers`
+//! deserialize their bodies into these types, so the client sends exactly
+//! what the server reads. Fields the handlers validate themselves (an empty
+//! name, a missing number) default instead of failing to deserialize, so
+//! those keep their own error codes.
+//!
+//! Responses are the native ones; with `evolutionCompat` on, the Evolution
+//! style routes answer in the Evolution v2 shapes instead (see
+//! `server::evolution`).
+
+use crate::server::connection::{ConnectionState, TransitionRecord};
+use crate::server::pagination::PageInfo;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::{Map, Value};
+
+/// Body of `POST /instance/create`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateInstance {
+    /// Also read as `name`.
+    #[serde(default, alias = "name")]
+    pub instance_name: String,
+    /// `WHATSAPP-BAILEYS` (default) or `WHATSAPP-BUSINESS` for the Cloud API.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub integration: Option<String>,
+    /// Number to pair with a code; for the Cloud API, the phone number id.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub number: Option<String>,
+    /// Cloud API access token.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+    /// Cloud API business account id.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub business_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub webhook: Option<InstanceWebhook>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tags: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<Map<String, Value>>,
+    /// Browser fingerprint, `{"name", "os", "version"}`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub browser: Option<Value>,
+    /// WA web version to pin, e.g. `2.3000.1015901307`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wa_version: Option<String>,
+}
+
+/// `webhook` of [`CreateInstance`].
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InstanceWebhook {
+    /// The webhook is ignored without one.
+    #[serde(default)]
+    pub url: String,
+    #[serde(default)]
+    pub by_events: bool,
+    #[serde(default)]
+    pub base64: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub headers: Option<Map<String, Value>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub events: Option<Vec<String>>,
+}
+
+/// Answer of `POST /instance/create`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InstanceCreated {
+    pub instance: String,
+    pub status: String,
+}
+
+/// Answer of `GET /instance/connectionState/:name`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InstanceConnection {
+    pub instance: String,
+    pub state: ConnectionState,
+    pub since: DateTime<Utc>,
+    pub transitions: Vec<TransitionRecord>,
+    /// Latest `stream:error` of the server, if any.
+    pub last_error: Option<Value>,
+}
+
+/// A page of a list route: `{data, pagination}`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Page<T> {
+    pub data: Vec<T>,
+    pub pagination: PageInfo,
+}
+
+/// Body of `POST /message/sendText/:instance`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SendText {
+    /// Phone number in any format, or a JID.
+    #[serde(default)]
+    pub number: String,
+    #[serde(default)]
+    pub text: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub link_preview: Option<bool>,
+    /// Message replied to (`{"key": {"id"}}`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quoted: Option<Value>,
+}
+
+/// Row of a queued message, the answer of the send routes.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QueuedMessage {
+    pub id: String,
+    pub session: String,
+    pub chat_id: Option<String>,
+    pub message_type: String,
+    pub status: String,
+    pub created_at: String,
+}
+
+/// Body of `POST /chat/findMessages/:instance`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FindMessages {
+    #[serde(rename = "where", default)]
+    pub filter: MessageWhere,
+    /// Clamped to the page size bounds; anything but a number is ignored.
+    #[serde(
+        default,
+        deserialize_with = "lenient_number",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub limit: Option<i64>,
+    /// Negative offsets count as 0; anything but a number is ignored.
+    #[serde(
+        default,
+        deserialize_with = "lenient_number",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub offset: Option<i64>,
+    /// 1-based page, an alternative to `offset`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub page: Option<u32>,
+    /// `pagination.nextCursor` of the previous page.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<String>,
+    /// e.g. `createdAt:desc`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sort: Option<String>,
+}
+
+/// Evolution clients send the page numbers in any shape, and
+/// `findMessages` always ignored the ones it could not read.
+fn lenient_number<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<i64>, D::Error> {
+    Ok(Value::deserialize(deserializer)?.as_i64())
+}
+
+/// `where` of [`FindMessages`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MessageWhere {
+    #[serde(default)]
+    pub key: MessageKeyFilter,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MessageKeyFilter {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub remote_jid: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+}
+
+/// Answer of `POST /chat/findMessages/:instance`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FoundMessages {
+    pub instance: String,
+    pub count: usize,
+    pub messages: Vec<StoredMessage>,
+    pub pagination: PageInfo,
+}
+
+/// A stored message as listed by `findMessages`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StoredMessage {
+    pub id: String,
+    pub key: MessageKey,
+    pub message_type: String,
+    pub message: Value,
+    pub status: String,
+    pub created_at: String,
+    /// Reactions to the message, aggregated per emoji.
+    #[serde(default)]
+    pub reactions: Value,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MessageKey {
+    pub remote_jid: Option<String>,
+    pub from_me: bool,
+    pub id: Option<String>,
+}
+
+/// Body of `POST /chat/whatsappNumbers/:instance`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WhatsappNumbers {
+    #[serde(default)]
+    pub numbers: Vec<String>,
+}
@@ -1,2 +1,3 @@
+pub mod api_model;
 pub mod message_model;
 pub mod webhook_model;
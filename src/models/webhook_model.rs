@@ -8,4 +8,6 @@ pub struct WebhookConfig {
     pub base64: bool,
     pub headers: HashMap<String, String>,
     pub events: Option<Vec<String>>,
+    /// Signs deliveries with `X-Chatwarp-Signature` when set.
+    pub secret: Option<String>,
 }
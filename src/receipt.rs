@@ -82,18 +82,7 @@ impl Client {
             return;
         }
 
-        let mut attrs = HashMap::new();
-        attrs.insert("id".to_string(), info.id.clone());
-        // The 'to' attribute is always the JID from which the message originated (the chat JID for groups).
-        attrs.insert("to".to_string(), info.source.chat.to_string());
-        attrs.insert("type".to_string(), "delivery".to_string());
-
-        // For group messages, the 'participant' attribute is required to identify the sender.
-        if info.source.is_group {
-            attrs.insert("participant".to_string(), info.source.sender.to_string());
-        }
-
-        let receipt_node = NodeBuilder::new("receipt").attrs(attrs).build();
+        let receipt_node = receipt_node(info, "delivery");
 
 
         info!(target: "Client/Receipt", "Sending delivery receipt for message {} to {}", info.id, info.source.sender);
@@ -105,6 +94,40 @@ impl Client {
             log::debug!("⏱️ send_node (receipt): {:?}", t0.elapsed());
         }
     }
+
+    /// Sends a read receipt (blue ticks) for a received message. Own
+    /// messages, status broadcasts and messages without an ID are skipped.
+    pub async fn send_read_receipt(
+        &self,
+        info: &crate::types::message::MessageInfo,
+    ) -> Result<(), crate::client::ClientError> {
+        use warp_core_binary::jid::STATUS_BROADCAST_USER;
+
+        if info.source.is_from_me
+            || info.id.is_empty()
+            || info.source.chat.user == STATUS_BROADCAST_USER
+        {
+            return Ok(());
+        }
+        debug!(target: "Client/Receipt", "Sending read receipt for message {} to {}", info.id, info.source.sender);
+        self.send_node(receipt_node(info, "read")).await
+    }
+}
+
+/// `<receipt>` of `receipt_type` for a received message.
+fn receipt_node(info: &crate::types::message::MessageInfo, receipt_type: &str) -> Node {
+    let mut attrs = HashMap::new();
+    attrs.insert("id".to_string(), info.id.clone());
+    // The 'to' attribute is always the JID from which the message originated (the chat JID for groups).
+    attrs.insert("to".to_string(), info.source.chat.to_string());
+    attrs.insert("type".to_string(), receipt_type.to_string());
+
+    // For group messages, the 'participant' attribute is required to identify the sender.
+    if info.source.is_group {
+        attrs.insert("participant".to_string(), info.source.sender.to_string());
+    }
+
+    NodeBuilder::new("receipt").attrs(attrs).build()
 }
 
 #[cfg(test)]
@@ -71,6 +71,7 @@ impl Client {
             .optional_string("count")
             .and_then(|s| s.parse().ok())
             .unwrap_or(1);
+        self.retry_stats.record_retry_received();
 
         // Refuse to handle retries that have exceeded the maximum attempts.
         // This prevents infinite retry loops and matches WhatsApp Web's behavior.
@@ -79,6 +80,7 @@ impl Client {
                 "Refusing retry #{} for message {} from {}: exceeds max attempts ({})",
                 retry_count, message_id, receipt.source.sender, MAX_RETRY_COUNT
             );
+            self.retry_stats.record_retry_ignored();
             return Ok(());
         }
 
@@ -107,6 +109,7 @@ impl Client {
                 message_id,
                 receipt.source.sender
             );
+            self.retry_stats.record_retry_ignored();
             return Ok(());
         }
 
@@ -115,6 +118,7 @@ impl Client {
             let mut pending = self.pending_retries.lock().await;
             if pending.contains(&message_id) {
                 log::debug!("Ignoring retry for {message_id}: a retry is already in progress.");
+                self.retry_stats.record_retry_ignored();
                 return Ok(());
             }
             pending.insert(message_id.clone());
@@ -134,6 +138,7 @@ impl Client {
                 log::debug!(
                     "Ignoring retry for message {message_id}: already handled or not found in cache."
                 );
+                self.retry_stats.record_retry_ignored();
                 return Ok(());
             }
         };
@@ -149,6 +154,7 @@ impl Client {
                 "handle_retry_receipt: device not found for device={}, user={}",
                 sender_device_id, sender_user
             );
+            self.retry_stats.record_retry_ignored();
             return Ok(());
         }
 
@@ -334,6 +340,7 @@ impl Client {
             None,
         )
         .await?;
+        self.retry_stats.record_message_resent();
 
         Ok(())
     }
@@ -0,0 +1,297 @@
+//! Typed async client of the HTTP API (`client` feature).
+//!
+//! Requests and answers use the bodies in [`crate::models::api_model`],
+//! which the handlers deserialize themselves, and the server's own types
+//! (e.g. [`EventFilters`], [`NumberStatus`]). The routes are grouped as in the
+//! API: [`ApiClient::instances`], [`ApiClient::messages`] and
+//! [`ApiClient::chats`]. Answers are read in the native format, so the
+//! server must run with `evolutionCompat` off.
+//!
+//! ```no_run
+//! # async fn run() -> Result<(), chatwarp_api::sdk::SdkError> {
+//! use chatwarp_api::models::api_model::SendText;
+//! use chatwarp_api::sdk::ApiClient;
+//!
+//! let api = ApiClient::new("http://localhost:8080").with_token("secret");
+//! let queued = api
+//!     .messages()
+//!     .send_text(
+//!         "default",
+//!         &SendText {
+//!             number: "5511999990000".to_string(),
+//!             text: "oi".to_string(),
+//!             ..Default::default()
+//!         },
+//!     )
+//!     .await?;
+//! println!("{}", queued.id);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::models::api_model::{
+    CreateInstance, FindMessages, FoundMessages, InstanceConnection, InstanceCreated, Page,
+    QueuedMessage, SendText, WhatsappNumbers,
+};
+use crate::server::event_filters::EventFilters;
+use crate::server::numbers::NumberStatus;
+use reqwest::{Method, RequestBuilder, StatusCode};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum SdkError {
+    #[error("request failed: {0}")]
+    Http(#[from] reqwest::Error),
+    /// The server answered with an error status; `error` is the `error`
+    /// field of the body (e.g. `instance_not_found`).
+    #[error("{status}: {error}")]
+    Api {
+        status: StatusCode,
+        error: String,
+        body: Value,
+    },
+}
+
+impl SdkError {
+    fn from_response(status: StatusCode, body: Value) -> Self {
+        let error = body["error"]
+            .as_str()
+            .map(str::to_string)
+            .unwrap_or_else(|| status.canonical_reason().unwrap_or("error").to_string());
+        Self::Api {
+            status,
+            error,
+            body,
+        }
+    }
+
+    /// `error` of an API error, e.g. to match `instance_not_found`.
+    pub fn api_error(&self) -> Option<&str> {
+        match self {
+            Self::Api { error, .. } => Some(error),
+            Self::Http(_) => None,
+        }
+    }
+}
+
+/// Connection to a ChatWarp server.
+#[derive(Debug, Clone)]
+pub struct ApiClient {
+    http: reqwest::Client,
+    base_url: String,
+    token: Option<String>,
+}
+
+impl ApiClient {
+    /// Client of the server at `base_url` (e.g. `http://localhost:8080`,
+    /// or `.../api/v1` with the versioned mount).
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            token: None,
+        }
+    }
+
+    /// `CHATWARP_PASSWORD` or an API key, sent as a bearer token.
+    pub fn with_token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    /// Uses `http` (timeouts, proxy, TLS) instead of a default client.
+    pub fn with_http_client(mut self, http: reqwest::Client) -> Self {
+        self.http = http;
+        self
+    }
+
+    pub fn instances(&self) -> InstanceClient<'_> {
+        InstanceClient { api: self }
+    }
+
+    pub fn messages(&self) -> MessageClient<'_> {
+        MessageClient { api: self }
+    }
+
+    pub fn chats(&self) -> ChatClient<'_> {
+        ChatClient { api: self }
+    }
+
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        let request = self
+            .http
+            .request(method, format!("{}{path}", self.base_url));
+        match &self.token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        }
+    }
+
+    async fn send<T: DeserializeOwned>(&self, request: RequestBuilder) -> Result<T, SdkError> {
+        let response = request.send().await?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.json::<Value>().await.unwrap_or(Value::Null);
+            return Err(SdkError::from_response(status, body));
+        }
+        Ok(response.json().await?)
+    }
+
+    async fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T, SdkError> {
+        self.send(self.request(Method::GET, path)).await
+    }
+
+    async fn post<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        body: &(impl Serialize + ?Sized),
+    ) -> Result<T, SdkError> {
+        self.send(self.request(Method::POST, path).json(body)).await
+    }
+}
+
+/// Path segment with `/`, `?`, `#` and `%` escaped.
+fn segment(value: &str) -> String {
+    value
+        .replace('%', "%25")
+        .replace('/', "%2F")
+        .replace('?', "%3F")
+        .replace('#', "%23")
+}
+
+/// Body of the `/instance/events/:name` routes.
+#[derive(Deserialize)]
+struct FiltersBody {
+    events: EventFilters,
+}
+
+/// `/instance/*` routes.
+#[derive(Debug, Clone, Copy)]
+pub struct InstanceClient<'a> {
+    api: &'a ApiClient,
+}
+
+impl InstanceClient<'_> {
+    pub async fn create(&self, body: &CreateInstance) -> Result<InstanceCreated, SdkError> {
+        self.api.post("/instance/create", body).await
+    }
+
+    /// First page of instances, with their `connectionStatus`.
+    pub async fn fetch(&self) -> Result<Page<Value>, SdkError> {
+        self.api.get("/instance/fetchInstances").await
+    }
+
+    pub async fn connect(&self, name: &str) -> Result<Value, SdkError> {
+        self.api
+            .get(&format!("/instance/connect/{}", segment(name)))
+            .await
+    }
+
+    pub async fn connection_state(&self, name: &str) -> Result<InstanceConnection, SdkError> {
+        self.api
+            .get(&format!("/instance/connectionState/{}", segment(name)))
+            .await
+    }
+
+    /// Deletes the instance with the default steps; the answer lists them.
+    pub async fn delete(&self, name: &str) -> Result<Value, SdkError> {
+        let path = format!("/instance/delete/{}", segment(name));
+        self.api.send(self.api.request(Method::DELETE, &path)).await
+    }
+
+    pub async fn event_filters(&self, name: &str) -> Result<EventFilters, SdkError> {
+        let body: FiltersBody = self
+            .api
+            .get(&format!("/instance/events/{}", segment(name)))
+            .await?;
+        Ok(body.events)
+    }
+
+    /// Replaces the event lists of the three sinks; `None` takes every
+    /// event.
+    pub async fn set_event_filters(
+        &self,
+        name: &str,
+        filters: &EventFilters,
+    ) -> Result<EventFilters, SdkError> {
+        let path = format!("/instance/events/{}", segment(name));
+        let body: FiltersBody = self
+            .api
+            .send(self.api.request(Method::PUT, &path).json(filters))
+            .await?;
+        Ok(body.events)
+    }
+}
+
+/// `/message/*` routes.
+#[derive(Debug, Clone, Copy)]
+pub struct MessageClient<'a> {
+    api: &'a ApiClient,
+}
+
+impl MessageClient<'_> {
+    /// Queues a text message; it is sent by the messages worker.
+    pub async fn send_text(
+        &self,
+        instance: &str,
+        body: &SendText,
+    ) -> Result<QueuedMessage, SdkError> {
+        self.api
+            .post(&format!("/message/sendText/{}", segment(instance)), body)
+            .await
+    }
+
+    /// Queue and delivery status of a message, by its id or WhatsApp id.
+    pub async fn status(&self, instance: &str, message_id: &str) -> Result<Value, SdkError> {
+        self.api
+            .get(&format!(
+                "/message/status/{}/{}",
+                segment(instance),
+                segment(message_id)
+            ))
+            .await
+    }
+}
+
+/// `/chat/*` routes.
+#[derive(Debug, Clone, Copy)]
+pub struct ChatClient<'a> {
+    api: &'a ApiClient,
+}
+
+impl ChatClient<'_> {
+    pub async fn find_messages(
+        &self,
+        instance: &str,
+        body: &FindMessages,
+    ) -> Result<FoundMessages, SdkError> {
+        self.api
+            .post(&format!("/chat/findMessages/{}", segment(instance)), body)
+            .await
+    }
+
+    /// Which of `numbers` have a WhatsApp account.
+    pub async fn whatsapp_numbers(
+        &self,
+        instance: &str,
+        numbers: &[String],
+    ) -> Result<Vec<NumberStatus>, SdkError> {
+        let body = WhatsappNumbers {
+            numbers: numbers.to_vec(),
+        };
+        self.api
+            .post(
+                &format!("/chat/whatsappNumbers/{}", segment(instance)),
+                &body,
+            )
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    include!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/tests/sdk_tests.rs"));
+}
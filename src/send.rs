@@ -19,6 +19,20 @@ impl Client {
         Ok(request_id)
     }
 
+    /// Like [`Client::send_message`], with an id from
+    /// [`Client::generate_message_id`] chosen up front so callers can record
+    /// it before receipts for it arrive. Retries should reuse the same id.
+    pub async fn send_message_with_id(
+        &self,
+        to: Jid,
+        message: wa::Message,
+        id: String,
+    ) -> Result<String, anyhow::Error> {
+        self.send_message_impl(to, &message, Some(id.clone()), false, false, None)
+            .await?;
+        Ok(id)
+    }
+
     pub(crate) async fn send_message_impl(
         &self,
         to: Jid,
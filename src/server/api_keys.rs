@@ -0,0 +1,245 @@
+//! API keys with roles, expiry and an IP allowlist.
+//!
+//! Keys live in `api_keys`. Besides its optional workspace (see
+//! [`workspaces`](super::workspaces)), a key has a [`KeyRole`]: `read`
+//! keys may only issue requests that leave state alone (see
+//! [`changes_state`]), `write` keys anything outside the admin routes and
+//! instance deletion, and `admin` keys everything `CHATWARP_PASSWORD` can.
+//! Workspace keys are never `admin`. Expired keys and requests from
+//! addresses outside the key's `allowed_ips` are rejected.
+
+use crate::api_store::ApiBind;
+use crate::server::AppState;
+use crate::server::workspaces::{hash_api_key, is_admin_only};
+use axum::{
+    Json,
+    http::{HeaderMap, Method, StatusCode},
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+use serde_json::{Value, json};
+use std::net::{IpAddr, SocketAddr};
+use uuid::Uuid;
+
+/// `GET` routes that act: deleting an instance, pairing it, and joining a
+/// group by invite code.
+const STATE_CHANGING_GETS: &[&str] = &[
+    "/instance/delete",
+    "/instance/connect",
+    "/group/acceptInviteCode",
+];
+
+fn is_under(path: &str, prefix: &str) -> bool {
+    path.strip_prefix(prefix)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+/// Whether a request changes state: every method but `GET`, `HEAD` and
+/// `OPTIONS`, plus any request to the `GET` routes that act (axum answers
+/// `HEAD` with the `GET` handler). The audit trail records the
+/// same requests.
+pub fn changes_state(method: &Method, path: &str) -> bool {
+    !matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS)
+        || STATE_CHANGING_GETS
+            .iter()
+            .any(|prefix| is_under(path, prefix))
+}
+
+/// Whether a request deletes an instance, through either deletion route.
+fn deletes_instance(method: &Method, path: &str) -> bool {
+    is_under(path, "/instance/delete")
+        || (*method == Method::DELETE
+            && path
+                .strip_prefix("/sessions/")
+                .is_some_and(|session| !session.is_empty() && !session.contains('/')))
+}
+
+/// What a key may do. Ordered from least to most privileged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyRole {
+    Read,
+    Write,
+    Admin,
+}
+
+impl KeyRole {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "read" | "read-only" | "readonly" => Some(Self::Read),
+            "write" => Some(Self::Write),
+            "admin" => Some(Self::Admin),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Read => "read",
+            Self::Write => "write",
+            Self::Admin => "admin",
+        }
+    }
+
+    /// Role a request needs: admin routes and instance deletion need
+    /// `admin`, requests that change state `write`, and the rest `read`.
+    /// `/graphql` has no mutations, so posting a query is a read.
+    pub fn required_for(method: &Method, path: &str) -> Self {
+        if is_admin_only(path) || deletes_instance(method, path) {
+            Self::Admin
+        } else if path == "/graphql" || !changes_state(method, path) {
+            Self::Read
+        } else {
+            Self::Write
+        }
+    }
+}
+
+/// An address or CIDR range of `allowed_ips`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IpRule {
+    network: IpAddr,
+    prefix: u8,
+}
+
+impl IpRule {
+    /// `10.0.0.1`, `10.0.0.0/8`, `2001:db8::/32`...
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+        let (addr, prefix) = match value.split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix.parse::<u8>().ok()?)),
+            None => (value, None),
+        };
+        let network: IpAddr = addr.parse().ok()?;
+        let max = if network.is_ipv4() { 32 } else { 128 };
+        let prefix = prefix.unwrap_or(max);
+        (prefix <= max).then_some(Self { network, prefix })
+    }
+
+    pub fn matches(&self, ip: IpAddr) -> bool {
+        let ip = match ip {
+            IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(ip, IpAddr::V4),
+            v4 => v4,
+        };
+        let (network, ip, bits) = match (self.network, ip) {
+            (IpAddr::V4(n), IpAddr::V4(i)) => (u32::from(n) as u128, u32::from(i) as u128, 32),
+            (IpAddr::V6(n), IpAddr::V6(i)) => (u128::from(n), u128::from(i), 128),
+            _ => return false,
+        };
+        if self.prefix == 0 {
+            return true;
+        }
+        let shift = bits - u32::from(self.prefix);
+        network >> shift == ip >> shift
+    }
+}
+
+/// An active key, as found by [`resolve`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolvedKey {
+    pub id: Uuid,
+    pub workspace: Option<Uuid>,
+    pub role: KeyRole,
+    /// Empty when any address may use the key.
+    pub allowed_ips: Vec<IpRule>,
+}
+
+impl ResolvedKey {
+    fn from_row(row: &Value) -> Option<Self> {
+        let value = row.get("value").unwrap_or(row);
+        let workspace = value["workspace_id"]
+            .as_str()
+            .and_then(|id| Uuid::parse_str(id).ok());
+        let role = value["role"].as_str().and_then(KeyRole::parse)?;
+        Some(Self {
+            id: value["id"]
+                .as_str()
+                .and_then(|id| Uuid::parse_str(id).ok())?,
+            workspace,
+            // A workspace key never reaches the admin routes.
+            role: if workspace.is_some() {
+                role.min(KeyRole::Write)
+            } else {
+                role
+            },
+            allowed_ips: value["allowed_ips"]
+                .as_array()
+                .map(|ips| {
+                    ips.iter()
+                        .filter_map(Value::as_str)
+                        .filter_map(IpRule::parse)
+                        .collect()
+                })
+                .unwrap_or_default(),
+        })
+    }
+
+    /// Whether `ip` may use the key; an unknown address only passes an
+    /// empty allowlist.
+    pub fn allows_ip(&self, ip: Option<IpAddr>) -> bool {
+        self.allowed_ips.is_empty()
+            || ip.is_some_and(|ip| self.allowed_ips.iter().any(|rule| rule.matches(ip)))
+    }
+
+    /// The 403 `insufficient_role` to answer when the key's role is below
+    /// what the request needs. Workspace keys on admin routes get
+    /// `workspace_forbidden` from the scope guard instead, so they only
+    /// ever need `write`.
+    pub fn forbidden(&self, method: &Method, path: &str) -> Option<Response> {
+        let required = KeyRole::required_for(method, path);
+        let required = if self.workspace.is_some() {
+            required.min(KeyRole::Write)
+        } else {
+            required
+        };
+        (self.role < required).then(|| {
+            (
+                StatusCode::FORBIDDEN,
+                Json(json!({
+                    "error": "insufficient_role",
+                    "role": self.role,
+                    "required": required,
+                })),
+            )
+                .into_response()
+        })
+    }
+}
+
+/// The unrevoked, unexpired key matching `key`. Keys without a role
+/// (global keys from before roles) do not authenticate.
+pub async fn resolve(state: &AppState, key: &str) -> anyhow::Result<Option<ResolvedKey>> {
+    let rows = state
+        .api_store
+        .query_json(
+            "SELECT jsonb_build_object('id', id, 'workspace_id', workspace_id, 'role', role, \
+                'allowed_ips', allowed_ips) as value \
+             FROM api_keys \
+             WHERE key_hash = $1 AND revoked_at IS NULL AND role IS NOT NULL \
+               AND (expires_at IS NULL OR expires_at > now()) \
+             LIMIT 1",
+            vec![ApiBind::Text(hash_api_key(key))],
+        )
+        .await?;
+    Ok(rows.first().and_then(ResolvedKey::from_row))
+}
+
+/// Address of the caller: the first `X-Forwarded-For` hop when
+/// `trust_forwarded_for` is set, else the peer of the connection.
+pub fn client_ip(
+    headers: &HeaderMap,
+    peer: Option<SocketAddr>,
+    trust_forwarded_for: bool,
+) -> Option<IpAddr> {
+    let forwarded = trust_forwarded_for
+        .then(|| headers.get("x-forwarded-for")?.to_str().ok())
+        .flatten()
+        .and_then(|v| v.split(',').next())
+        .and_then(|v| v.trim().parse().ok());
+    forwarded.or(peer.map(|addr| addr.ip()))
+}
+
+#[cfg(test)]
+mod tests {
+    include!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/tests/server/api_keys_tests.rs"));
+}
@@ -0,0 +1,168 @@
+use bytes::Bytes;
+use std::path::Path;
+use std::process::Command;
+use thiserror::Error;
+
+/// Mimetype WhatsApp expects for voice notes.
+pub const PTT_MIMETYPE: &str = "audio/ogg; codecs=opus";
+/// Number of samples in a voice note waveform.
+pub const WAVEFORM_SAMPLES: usize = 64;
+/// Sample rate of the PCM pass used to compute duration and waveform.
+const ANALYSIS_SAMPLE_RATE: u32 = 8000;
+
+const OPUS_ARGS: &[&str] = &[
+    "-vn",
+    "-ac",
+    "1",
+    "-ar",
+    "48000",
+    "-c:a",
+    "libopus",
+    "-b:a",
+    "32k",
+    "-application",
+    "voip",
+    "-avoid_negative_ts",
+    "make_zero",
+    "-f",
+    "ogg",
+];
+const PCM_ARGS: &[&str] = &["-vn", "-ac", "1", "-ar", "8000", "-f", "s16le"];
+const MP3_ARGS: &[&str] = &["-vn", "-c:a", "libmp3lame", "-b:a", "128k", "-f", "mp3"];
+
+#[derive(Debug, Error)]
+pub enum AudioError {
+    #[error("ffmpeg io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("ffmpeg exited with {status}: {stderr}")]
+    Ffmpeg { status: String, stderr: String },
+    #[error("audio task failed: {0}")]
+    Join(#[from] tokio::task::JoinError),
+}
+
+/// A voice note ready to be uploaded as a PTT audio message.
+#[derive(Debug, Clone)]
+pub struct PttAudio {
+    /// Opus audio in an ogg container.
+    pub data: Vec<u8>,
+    pub seconds: u32,
+    /// [`WAVEFORM_SAMPLES`] amplitude values in `0..=100`.
+    pub waveform: Vec<u8>,
+}
+
+/// Converts arbitrary input audio (usually ogg/opus voice notes) to MP3
+/// with the `ffmpeg` binary.
+pub async fn convert_to_mp3(ffmpeg: &str, input: Vec<u8>) -> Result<Vec<u8>, AudioError> {
+    run_ffmpeg(ffmpeg.to_string(), Bytes::from(input), MP3_ARGS).await
+}
+
+/// Transcodes arbitrary input audio into a WhatsApp voice note
+/// (mono ogg/opus) with the `ffmpeg` binary and computes its duration and
+/// waveform.
+pub async fn transcode_to_ptt(ffmpeg: &str, input: Bytes) -> Result<PttAudio, AudioError> {
+    let binary = ffmpeg.to_string();
+    tokio::task::spawn_blocking(move || {
+        let source = write_source(&input)?;
+        let data = ffmpeg_file(&binary, source.path(), OPUS_ARGS)?;
+        let pcm = ffmpeg_file(&binary, source.path(), PCM_ARGS)?;
+        let samples = pcm_samples(&pcm);
+
+        Ok(PttAudio {
+            data,
+            seconds: duration_seconds(samples.len(), ANALYSIS_SAMPLE_RATE),
+            waveform: waveform_from_pcm(&samples),
+        })
+    })
+    .await?
+}
+
+/// Reduces mono PCM samples to [`WAVEFORM_SAMPLES`] buckets of mean
+/// amplitude, normalised so the loudest bucket is 100.
+pub fn waveform_from_pcm(samples: &[i16]) -> Vec<u8> {
+    if samples.is_empty() {
+        return vec![0; WAVEFORM_SAMPLES];
+    }
+
+    let bucket_len = samples.len().div_ceil(WAVEFORM_SAMPLES);
+    let mut buckets: Vec<f64> = samples
+        .chunks(bucket_len)
+        .map(|chunk| {
+            let sum: f64 = chunk.iter().map(|s| f64::from(*s).abs()).sum();
+            sum / chunk.len() as f64
+        })
+        .collect();
+    buckets.resize(WAVEFORM_SAMPLES, 0.0);
+
+    let peak = buckets.iter().copied().fold(0.0_f64, f64::max);
+    if peak <= 0.0 {
+        return vec![0; WAVEFORM_SAMPLES];
+    }
+
+    buckets
+        .into_iter()
+        .map(|value| ((value / peak) * 100.0).floor() as u8)
+        .collect()
+}
+
+/// Duration in whole seconds (rounded up) of `sample_count` mono samples.
+pub fn duration_seconds(sample_count: usize, sample_rate: u32) -> u32 {
+    if sample_rate == 0 {
+        return 0;
+    }
+    (sample_count as u64).div_ceil(u64::from(sample_rate)) as u32
+}
+
+fn pcm_samples(pcm: &[u8]) -> Vec<i16> {
+    pcm.chunks_exact(2)
+        .map(|pair| i16::from_le_bytes([pair[0], pair[1]]))
+        .collect()
+}
+
+pub(crate) fn write_source(input: &[u8]) -> Result<tempfile::NamedTempFile, AudioError> {
+    let source = tempfile::NamedTempFile::new()?;
+    std::fs::write(source.path(), input)?;
+    Ok(source)
+}
+
+/// Runs ffmpeg on a blocking thread, feeding `input` through temp files so
+/// large payloads cannot deadlock on stdin/stdout pipes.
+async fn run_ffmpeg(
+    binary: String,
+    input: Bytes,
+    output_args: &'static [&'static str],
+) -> Result<Vec<u8>, AudioError> {
+    tokio::task::spawn_blocking(move || {
+        let source = write_source(&input)?;
+        ffmpeg_file(&binary, source.path(), output_args)
+    })
+    .await?
+}
+
+pub(crate) fn ffmpeg_file(
+    binary: &str,
+    source: &Path,
+    output_args: &[&str],
+) -> Result<Vec<u8>, AudioError> {
+    let target = tempfile::NamedTempFile::new()?;
+
+    let output = Command::new(binary)
+        .args(["-hide_banner", "-loglevel", "error", "-y", "-i"])
+        .arg(source)
+        .args(output_args)
+        .arg(target.path())
+        .output()?;
+
+    if !output.status.success() {
+        return Err(AudioError::Ffmpeg {
+            status: output.status.to_string(),
+            stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        });
+    }
+
+    Ok(std::fs::read(target.path())?)
+}
+
+#[cfg(test)]
+mod tests {
+    include!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/tests/server/audio_tests.rs"));
+}
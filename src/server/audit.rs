@@ -0,0 +1,214 @@
+//! Audit trail of API calls that change state, stored in `audit_log` and
+//! queried through `/manager/audit`. What changes state is decided by
+//! [`changes_state`], the classification API key roles use too, so `GET`
+//! routes that act (instance deletion, pairing, joining a group) are
+//! recorded alongside every `POST`/`PUT`/`PATCH`/`DELETE`.
+
+use crate::api_store::ApiBind;
+use crate::server::api_keys::changes_state;
+use crate::server::{AppState, body, qr};
+use axum::{
+    body::Body,
+    extract::{MatchedPath, State},
+    http::{Request, header},
+    middleware::Next,
+    response::Response,
+};
+use futures_util::StreamExt;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use uuid::Uuid;
+
+/// Route parameters that name the instance a request acts on.
+const INSTANCE_PARAMS: &[&str] = &[":session", ":instance_name", ":instance", ":name"];
+/// Routes without a body limit, whose handler streams the body to disk.
+const STREAMED_ROUTES: &[&str] = &["/media/upload/:instance_name"];
+pub const DEFAULT_AUDIT_LIMIT: i32 = 100;
+pub const MAX_AUDIT_LIMIT: i32 = 1000;
+
+/// One audited request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditEntry {
+    pub actor: String,
+    pub method: String,
+    pub route: String,
+    pub instance: Option<String>,
+    pub request_hash: String,
+    pub status: u16,
+}
+
+/// Filters accepted by [`query`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AuditFilter {
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub instance: Option<String>,
+    pub limit: i32,
+}
+
+/// Who made the call: a short fingerprint of the API key, the login cookie,
+/// or `anonymous` when auth is disabled.
+pub fn actor(headers: &axum::http::HeaderMap) -> String {
+    let key = headers
+        .get("x-chatwarp-password")
+        .and_then(|v| v.to_str().ok())
+        .or_else(|| {
+            headers
+                .get(header::AUTHORIZATION)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.strip_prefix("Bearer "))
+        });
+    if let Some(key) = key {
+        let digest = Sha256::digest(key.as_bytes());
+        return format!("key:{}", hex::encode(&digest[..4]));
+    }
+    let has_cookie = headers
+        .get(header::COOKIE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|c| c.contains("chatwarp_auth="));
+    if has_cookie {
+        "session".to_string()
+    } else {
+        "anonymous".to_string()
+    }
+}
+
+/// Value of the instance parameter in `path`, given the matched `route`.
+/// `:file` is a QR image name (`{instance}.png`).
+pub fn instance_from_path(route: &str, path: &str) -> Option<String> {
+    route
+        .split('/')
+        .zip(path.split('/'))
+        .find_map(|(pattern, value)| match pattern {
+            ":file" => qr::image_file(value).map(|(name, _)| name),
+            _ if INSTANCE_PARAMS.contains(&pattern) => Some(value),
+            _ => None,
+        })
+        .map(str::to_string)
+        .filter(|value| !value.is_empty())
+}
+
+/// Hex SHA-256 of the request body.
+pub fn request_hash(body: &[u8]) -> String {
+    hex::encode(Sha256::digest(body))
+}
+
+/// Records every request that changes state after it has been handled. Storage
+/// failures are logged and never affect the response.
+pub async fn audit_middleware(
+    State(state): State<Arc<AppState>>,
+    req: Request<Body>,
+    next: Next,
+) -> Response {
+    if !changes_state(req.method(), req.uri().path()) {
+        return next.run(req).await;
+    }
+
+    let method = req.method().to_string();
+    let path = req.uri().path().to_string();
+    let route = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|m| m.as_str().to_string())
+        .unwrap_or_else(|| path.clone());
+    let actor = actor(req.headers());
+
+    let (parts, body) = req.into_parts();
+    let (response, request_hash) = if STREAMED_ROUTES.contains(&route.as_str()) {
+        // Hash the chunks as the handler reads them instead of buffering.
+        let hasher = Arc::new(Mutex::new(Sha256::new()));
+        let body = Body::from_stream(body.into_data_stream().map({
+            let hasher = hasher.clone();
+            move |chunk| {
+                if let Ok(bytes) = &chunk {
+                    lock(&hasher).update(bytes);
+                }
+                chunk
+            }
+        }));
+        let response = next.run(Request::from_parts(parts, body)).await;
+        let digest = lock(&hasher).clone().finalize();
+        (response, hex::encode(digest))
+    } else {
+        let bytes = match body::buffer(body, state.body_limit).await {
+            Ok(bytes) => bytes,
+            Err(response) => return response,
+        };
+        let request_hash = request_hash(&bytes);
+        let response = next
+            .run(Request::from_parts(parts, Body::from(bytes)))
+            .await;
+        (response, request_hash)
+    };
+
+    let entry = AuditEntry {
+        actor,
+        method,
+        instance: instance_from_path(&route, &path),
+        route,
+        request_hash,
+        status: response.status().as_u16(),
+    };
+    tokio::spawn(async move {
+        if let Err(e) = record(&state, entry).await {
+            tracing::debug!(error = %e, "Audit entry not stored");
+        }
+    });
+
+    response
+}
+
+fn lock(hasher: &Mutex<Sha256>) -> MutexGuard<'_, Sha256> {
+    hasher.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Inserts `entry` into `audit_log`.
+pub async fn record(state: &AppState, entry: AuditEntry) -> anyhow::Result<()> {
+    state
+        .api_store
+        .execute(
+            "INSERT INTO audit_log (id, actor, method, route, instance, request_hash, status) \
+             VALUES ($1, $2, $3, $4, $5, $6, $7)",
+            vec![
+                ApiBind::Uuid(Uuid::new_v4()),
+                ApiBind::Text(entry.actor),
+                ApiBind::Text(entry.method),
+                ApiBind::Text(entry.route),
+                ApiBind::NullableText(entry.instance),
+                ApiBind::Text(entry.request_hash),
+                ApiBind::Int(i32::from(entry.status)),
+            ],
+        )
+        .await?;
+    Ok(())
+}
+
+/// Newest entries first, filtered by time range (RFC 3339) and instance.
+pub async fn query(state: &AppState, filter: AuditFilter) -> anyhow::Result<Vec<Value>> {
+    state
+        .api_store
+        .query_json(
+            "SELECT row_to_json(t)::jsonb as value FROM ( \
+                SELECT id, created_at, actor, method, route, instance, request_hash, status \
+                FROM audit_log \
+                WHERE ($1::timestamptz IS NULL OR created_at >= $1::timestamptz) \
+                  AND ($2::timestamptz IS NULL OR created_at < $2::timestamptz) \
+                  AND ($3::text IS NULL OR instance = $3) \
+                ORDER BY created_at DESC \
+                LIMIT $4 \
+            ) t",
+            vec![
+                ApiBind::NullableText(filter.from),
+                ApiBind::NullableText(filter.to),
+                ApiBind::NullableText(filter.instance),
+                ApiBind::Int(filter.limit),
+            ],
+        )
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    include!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/tests/server/audit_tests.rs"));
+}
@@ -0,0 +1,582 @@
+//! Auto-read and auto-reply rules of instances.
+//!
+//! An instance keeps an ordered list of rules in `api_sessions.auto_rules`,
+//! set through `PUT /instance/rules/:name`. Every message received from
+//! someone else is checked against them as it arrives: a rule matches when
+//! all of its conditions hold (sender pattern, text it contains, group or
+//! direct chat, inside or outside business hours) and then runs its actions
+//! in order: mark the message read, queue a canned reply, post the message
+//! to a webhook or add the chat to a label. Rules are checked top to bottom
+//! and a matching rule with `stop` ends the check.
+
+use crate::api_store::ApiBind;
+use crate::client::Client;
+use crate::server::cache::CacheSpace;
+use crate::server::routes::chat::chat_manager;
+use crate::server::{AppState, webhooks};
+use crate::types::events::{Event, EventHandler};
+use crate::types::message::MessageInfo;
+use chrono::{DateTime, Datelike, Duration as ChronoDuration, Timelike, Utc};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::{Value, json};
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use tokio::sync::mpsc;
+use warp_core::net::{HttpClient, HttpRequest};
+use warp_core::proto_helpers::MessageExt;
+use warp_core_binary::jid::STATUS_BROADCAST_USER;
+
+pub const MAX_RULES: usize = 50;
+const MAX_ID_LEN: usize = 64;
+const MAX_REPLY_LEN: usize = 4096;
+/// Largest UTC offset of a timezone, in minutes.
+const MAX_UTC_OFFSET_MINUTES: i32 = 14 * 60;
+const CACHE_TTL: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum AutoRuleError {
+    #[error("invalid rules: {0}")]
+    InvalidBody(String),
+    #[error("at most {MAX_RULES} rules")]
+    TooManyRules,
+    #[error("invalid rule id {0:?}: 1-{MAX_ID_LEN} letters, digits, '.', '_' or '-'")]
+    InvalidId(String),
+    #[error("duplicate rule id {0:?}")]
+    DuplicateId(String),
+    #[error("rule {0:?} has no actions")]
+    NoActions(String),
+    #[error("rule {rule:?}: {reason}")]
+    InvalidCondition { rule: String, reason: &'static str },
+    #[error("rule {rule:?}: {reason}")]
+    InvalidAction { rule: String, reason: &'static str },
+}
+
+/// Chats a rule applies to.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChatKind {
+    #[default]
+    Any,
+    Direct,
+    Group,
+}
+
+/// Weekly opening hours, in the timezone at `utcOffsetMinutes`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BusinessHours {
+    /// Open days, `0` being Sunday; every day when empty.
+    #[serde(default)]
+    pub days: Vec<u8>,
+    /// `HH:MM`; an `end` before `start` closes after midnight.
+    pub start: String,
+    pub end: String,
+    #[serde(default)]
+    pub utc_offset_minutes: i32,
+    /// Match outside the hours instead, e.g. for an away message.
+    #[serde(default)]
+    pub outside: bool,
+}
+
+impl BusinessHours {
+    /// Whether the condition holds at `now`.
+    pub fn matches(&self, now: DateTime<Utc>) -> bool {
+        let (Some(start), Some(end)) = (minute_of_day(&self.start), minute_of_day(&self.end))
+        else {
+            return false;
+        };
+        let local = now + ChronoDuration::minutes(i64::from(self.utc_offset_minutes));
+        let minute = local.hour() * 60 + local.minute();
+        let weekday = local.weekday().num_days_from_sunday() as u8;
+        let open_day = self.days.is_empty() || self.days.contains(&weekday);
+        let open_time = if start <= end {
+            (start..end).contains(&minute)
+        } else {
+            minute >= start || minute < end
+        };
+        (open_day && open_time) != self.outside
+    }
+
+    fn validate(&self) -> Result<(), &'static str> {
+        if minute_of_day(&self.start).is_none() || minute_of_day(&self.end).is_none() {
+            return Err("businessHours start and end must be HH:MM");
+        }
+        if self.days.iter().any(|day| *day > 6) {
+            return Err("businessHours days must be 0 (Sunday) to 6");
+        }
+        if self.utc_offset_minutes.abs() > MAX_UTC_OFFSET_MINUTES {
+            return Err("businessHours utcOffsetMinutes is out of range");
+        }
+        Ok(())
+    }
+}
+
+fn minute_of_day(time: &str) -> Option<u32> {
+    let (hour, minute) = time.split_once(':')?;
+    let (hour, minute) = (hour.parse::<u32>().ok()?, minute.parse::<u32>().ok()?);
+    (hour < 24 && minute < 60 && time.len() == 5).then_some(hour * 60 + minute)
+}
+
+/// What a message must look like for a rule to run. Empty conditions match
+/// every message.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RuleConditions {
+    /// Pattern of the sender number or JID; `*` matches any run of
+    /// characters (`5511*`, `*@lid`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sender: Option<String>,
+    /// Words or phrases of which the text must hold at least one, ignoring
+    /// case. A single string is accepted.
+    #[serde(
+        default,
+        deserialize_with = "one_or_many",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub contains: Vec<String>,
+    #[serde(default)]
+    pub chat: ChatKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub business_hours: Option<BusinessHours>,
+}
+
+fn one_or_many<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(one) => vec![one],
+        OneOrMany::Many(many) => many,
+    })
+}
+
+/// What a matching rule does.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum RuleAction {
+    /// Sends a read receipt for the message.
+    MarkRead,
+    /// Queues `text` to the chat.
+    Reply { text: String },
+    /// Posts the message to `url`.
+    Webhook { url: String },
+    /// Adds the chat to the label.
+    #[serde(rename_all = "camelCase")]
+    Label { label_id: String },
+}
+
+/// One rule of an instance.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AutoRule {
+    pub id: String,
+    #[serde(default = "enabled_by_default")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub conditions: RuleConditions,
+    pub actions: Vec<RuleAction>,
+    /// Skip the rules after this one when it matches.
+    #[serde(default)]
+    pub stop: bool,
+}
+
+fn enabled_by_default() -> bool {
+    true
+}
+
+impl AutoRule {
+    /// Whether the rule is enabled and its conditions hold for `message`.
+    pub fn matches(&self, message: &InboundMessage, now: DateTime<Utc>) -> bool {
+        let conditions = &self.conditions;
+        if !self.enabled {
+            return false;
+        }
+        match conditions.chat {
+            ChatKind::Any => {}
+            ChatKind::Direct if message.info.source.is_group => return false,
+            ChatKind::Group if !message.info.source.is_group => return false,
+            ChatKind::Direct | ChatKind::Group => {}
+        }
+        if let Some(pattern) = &conditions.sender
+            && !message
+                .sender_ids()
+                .iter()
+                .any(|id| wildcard_match(pattern, id))
+        {
+            return false;
+        }
+        if !conditions.contains.is_empty() {
+            let Some(text) = &message.text else {
+                return false;
+            };
+            let text = text.to_lowercase();
+            if !conditions
+                .contains
+                .iter()
+                .any(|needle| text.contains(&needle.to_lowercase()))
+            {
+                return false;
+            }
+        }
+        conditions
+            .business_hours
+            .as_ref()
+            .is_none_or(|hours| hours.matches(now))
+    }
+
+    fn validate(&self) -> Result<(), AutoRuleError> {
+        let valid_id = !self.id.is_empty()
+            && self.id.len() <= MAX_ID_LEN
+            && self
+                .id
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'));
+        if !valid_id {
+            return Err(AutoRuleError::InvalidId(self.id.clone()));
+        }
+        let condition = |reason| AutoRuleError::InvalidCondition {
+            rule: self.id.clone(),
+            reason,
+        };
+        if self
+            .conditions
+            .sender
+            .as_ref()
+            .is_some_and(|pattern| pattern.trim().is_empty())
+        {
+            return Err(condition("sender must not be empty"));
+        }
+        if self.conditions.contains.iter().any(|s| s.trim().is_empty()) {
+            return Err(condition("contains must not hold empty strings"));
+        }
+        if let Some(hours) = &self.conditions.business_hours {
+            hours.validate().map_err(condition)?;
+        }
+
+        if self.actions.is_empty() {
+            return Err(AutoRuleError::NoActions(self.id.clone()));
+        }
+        let action = |reason| AutoRuleError::InvalidAction {
+            rule: self.id.clone(),
+            reason,
+        };
+        for rule_action in &self.actions {
+            match rule_action {
+                RuleAction::MarkRead => {}
+                RuleAction::Reply { text } if text.trim().is_empty() => {
+                    return Err(action("reply text must not be empty"));
+                }
+                RuleAction::Reply { text } if text.len() > MAX_REPLY_LEN => {
+                    return Err(action("reply text is too long"));
+                }
+                RuleAction::Reply { .. } => {}
+                RuleAction::Webhook { url }
+                    if !url.starts_with("http://") && !url.starts_with("https://") =>
+                {
+                    return Err(action("webhook url must be http(s)"));
+                }
+                RuleAction::Webhook { .. } => {}
+                RuleAction::Label { label_id } if label_id.trim().is_empty() => {
+                    return Err(action("labelId must not be empty"));
+                }
+                RuleAction::Label { .. } => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Whether `text` matches `pattern`, where `*` stands for any run of
+/// characters. Case-insensitive.
+pub fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if p < pattern.len() && pattern[p] == text[t] {
+            p += 1;
+            t += 1;
+        } else if let Some((star, matched)) = backtrack {
+            p = star + 1;
+            t = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// Validated rules of a `{"rules": [...]}` body.
+pub fn rules_from_body(body: &Value) -> Result<Vec<AutoRule>, AutoRuleError> {
+    let rules: Vec<AutoRule> = match body.get("rules") {
+        Some(rules) => serde_json::from_value(rules.clone())
+            .map_err(|e| AutoRuleError::InvalidBody(e.to_string()))?,
+        None => return Err(AutoRuleError::InvalidBody("rules is required".to_string())),
+    };
+    if rules.len() > MAX_RULES {
+        return Err(AutoRuleError::TooManyRules);
+    }
+    let mut ids = HashSet::new();
+    for rule in &rules {
+        rule.validate()?;
+        if !ids.insert(rule.id.as_str()) {
+            return Err(AutoRuleError::DuplicateId(rule.id.clone()));
+        }
+    }
+    Ok(rules)
+}
+
+/// The rules that apply to `message`, in order, up to the first matching
+/// rule with `stop`.
+pub fn matching<'a>(
+    rules: &'a [AutoRule],
+    message: &InboundMessage,
+    now: DateTime<Utc>,
+) -> Vec<&'a AutoRule> {
+    let mut matched = Vec::new();
+    for rule in rules.iter().filter(|rule| rule.matches(message, now)) {
+        matched.push(rule);
+        if rule.stop {
+            break;
+        }
+    }
+    matched
+}
+
+/// A message received from someone else, as the rules see it.
+#[derive(Debug, Clone)]
+pub struct InboundMessage {
+    pub info: MessageInfo,
+    pub text: Option<String>,
+}
+
+impl InboundMessage {
+    /// Maps a client event; `None` for own messages, status updates,
+    /// reactions and protocol messages.
+    pub fn from_event(event: &Event) -> Option<Self> {
+        let Event::Message(message, info) = event else {
+            return None;
+        };
+        let base = message.get_base_message();
+        if info.source.is_from_me
+            || info.source.chat.user == STATUS_BROADCAST_USER
+            || base.reaction_message.is_some()
+            || base.protocol_message.is_some()
+        {
+            return None;
+        }
+        Some(Self {
+            info: info.clone(),
+            text: message
+                .text_content()
+                .or_else(|| message.get_caption())
+                .map(str::to_string),
+        })
+    }
+
+    /// Forms the sender pattern is checked against: the number or LID and
+    /// the full JID, of the sender and of its alternate addressing.
+    fn sender_ids(&self) -> Vec<String> {
+        let source = &self.info.source;
+        [Some(&source.sender), source.sender_alt.as_ref()]
+            .into_iter()
+            .flatten()
+            .flat_map(|jid| [jid.user.clone(), jid.to_non_ad().to_string()])
+            .collect()
+    }
+
+    /// Message body posted by a `webhook` action.
+    fn webhook_payload(&self, instance: &str, rule: &str) -> Value {
+        let source = &self.info.source;
+        json!({
+            "event": "AUTO_RULE_MATCHED",
+            "instance": instance,
+            "rule": rule,
+            "data": {
+                "key": {
+                    "remoteJid": source.chat.to_string(),
+                    "fromMe": false,
+                    "id": self.info.id,
+                    "participant": source.is_group.then(|| source.sender.to_non_ad().to_string()),
+                },
+                "pushName": self.info.push_name,
+                "text": self.text,
+                "messageTimestamp": self.info.timestamp.timestamp(),
+            },
+        })
+    }
+}
+
+struct RuleEventForwarder {
+    tx: mpsc::UnboundedSender<InboundMessage>,
+}
+
+impl EventHandler for RuleEventForwarder {
+    fn handle_event(&self, event: &Event) {
+        if let Some(message) = InboundMessage::from_event(event) {
+            let _ = self.tx.send(message);
+        }
+    }
+}
+
+/// Runs the rules of `instance_name` on the messages `client` receives.
+pub fn attach(state: Arc<AppState>, instance_name: String, client: &Client) {
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    client
+        .core
+        .event_bus
+        .add_handler(Arc::new(RuleEventForwarder { tx }));
+
+    tokio::spawn(async move {
+        while let Some(message) = rx.recv().await {
+            let rules = match load(&state, &instance_name).await {
+                Ok(rules) => rules,
+                Err(e) => {
+                    log::warn!("Failed to load the rules of {}: {}", instance_name, e);
+                    continue;
+                }
+            };
+            for rule in matching(&rules, &message, Utc::now()) {
+                log::debug!(
+                    "Rule {} of {} matched message {}",
+                    rule.id,
+                    instance_name,
+                    message.info.id
+                );
+                for action in &rule.actions {
+                    if let Err(e) = run(&state, &instance_name, rule, action, &message).await {
+                        log::warn!(
+                            "Rule {} of {} failed on message {}: {}",
+                            rule.id,
+                            instance_name,
+                            message.info.id,
+                            e
+                        );
+                    }
+                }
+            }
+        }
+    });
+}
+
+async fn run(
+    state: &Arc<AppState>,
+    session: &str,
+    rule: &AutoRule,
+    action: &RuleAction,
+    message: &InboundMessage,
+) -> anyhow::Result<()> {
+    let chat_id = message.info.source.chat.to_string();
+    match action {
+        RuleAction::MarkRead => {
+            let client = state
+                .clients
+                .get(session)
+                .map(|client| client.value().clone())
+                .ok_or_else(|| anyhow::anyhow!("instance is not running"))?;
+            client.send_read_receipt(&message.info).await?;
+        }
+        RuleAction::Reply { text } => {
+            let body = json!({"session": session, "chatId": chat_id, "text": text});
+            let response = chat_manager::send_message_type(state.clone(), body, "text", true).await;
+            if !response.status().is_success() {
+                anyhow::bail!("reply not queued: http {}", response.status());
+            }
+        }
+        RuleAction::Webhook { url } => {
+            let body = message.webhook_payload(session, &rule.id);
+            let req = HttpRequest::post(url)
+                .with_header("Content-Type", "application/json")
+                .with_body(serde_json::to_vec(&body)?);
+            let resp = state.http.execute(req).await?;
+            if !(200..300).contains(&resp.status_code) {
+                anyhow::bail!("webhook answered http {}", resp.status_code);
+            }
+        }
+        RuleAction::Label { label_id } => {
+            state
+                .api_store
+                .execute(
+                    "INSERT INTO api_label_chats (session, label_id, chat_id) VALUES ($1, $2, $3) \
+                     ON CONFLICT (session, label_id, chat_id) DO NOTHING",
+                    vec![
+                        ApiBind::Text(session.to_string()),
+                        ApiBind::Text(label_id.clone()),
+                        ApiBind::Text(chat_id.clone()),
+                    ],
+                )
+                .await?;
+            webhooks::enqueue(
+                state,
+                Some(session),
+                "LABELS_ASSOCIATION",
+                json!({"label_id": label_id, "chat_id": chat_id}),
+            )
+            .await;
+        }
+    }
+    Ok(())
+}
+
+/// Stored rules of `session`; `None` when the instance does not exist.
+pub async fn fetch(state: &AppState, session: &str) -> anyhow::Result<Option<Vec<AutoRule>>> {
+    let rows = state
+        .api_store
+        .query_json(
+            "SELECT auto_rules as value FROM api_sessions WHERE session = $1",
+            vec![ApiBind::Text(session.to_string())],
+        )
+        .await?;
+    rows.first()
+        .map(|row| serde_json::from_value(row.get("value").unwrap_or(row).clone()))
+        .transpose()
+        .map_err(Into::into)
+}
+
+/// Rules of `session`, cached for a few seconds since every incoming
+/// message needs them.
+pub async fn load(state: &AppState, session: &str) -> anyhow::Result<Vec<AutoRule>> {
+    if let Some(entry) = state.auto_rules_cache.get(session) {
+        let (rules, cached_at) = entry.value();
+        if cached_at.elapsed() < CACHE_TTL {
+            return Ok(rules.clone());
+        }
+    }
+    let rules = fetch(state, session).await?.unwrap_or_default();
+    state
+        .auto_rules_cache
+        .insert(session.to_string(), (rules.clone(), Instant::now()));
+    Ok(rules)
+}
+
+/// Replaces the rules of `session`. Returns whether the instance exists.
+pub async fn set(state: &AppState, session: &str, rules: &[AutoRule]) -> anyhow::Result<bool> {
+    let updated = state
+        .api_store
+        .execute(
+            "UPDATE api_sessions SET auto_rules = $2, updated_at = now() WHERE session = $1",
+            vec![
+                ApiBind::Text(session.to_string()),
+                ApiBind::Json(serde_json::to_value(rules)?),
+            ],
+        )
+        .await?;
+    state.auto_rules_cache.remove(session);
+    state.cache.invalidate_space(CacheSpace::Instances).await;
+    Ok(updated > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    include!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/tests/server/auto_rules_tests.rs"));
+}
@@ -0,0 +1,53 @@
+//! Request bodies read by middleware before the handler runs.
+//!
+//! Middleware that inspects a body buffers it with [`buffer`], bounded by
+//! [`AppState::body_limit`](crate::server::AppState::body_limit), so a huge
+//! request answers 413 instead of being held in memory.
+
+use axum::{
+    Json,
+    body::{Body, Bytes},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde_json::json;
+
+/// Bytes a middleware buffers by default, the same as axum's
+/// [`DefaultBodyLimit`](axum::extract::DefaultBodyLimit) for extractors.
+pub const DEFAULT_BODY_LIMIT: usize = 2 * 1024 * 1024;
+
+/// Reads `body` into memory, up to `limit` bytes. The error is the response
+/// to return as is: 413 over the limit, 400 when the body cannot be read.
+pub async fn buffer(body: Body, limit: usize) -> Result<Bytes, Response> {
+    axum::body::to_bytes(body, limit).await.map_err(|e| {
+        if is_length_limit(&e) {
+            (
+                StatusCode::PAYLOAD_TOO_LARGE,
+                Json(json!({"error": "body_too_large", "limit": limit})),
+            )
+                .into_response()
+        } else {
+            (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": "invalid_body", "details": e.to_string()})),
+            )
+                .into_response()
+        }
+    })
+}
+
+fn is_length_limit(error: &axum::Error) -> bool {
+    let mut source: Option<&(dyn std::error::Error + 'static)> = Some(error);
+    while let Some(e) = source {
+        if e.is::<http_body_util::LengthLimitError>() {
+            return true;
+        }
+        source = e.source();
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    include!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/tests/server/body_tests.rs"));
+}
@@ -0,0 +1,367 @@
+//! Cache of hot reads: instance listings, group lists and profile pictures.
+//!
+//! `/instance/fetchInstances` used to rebuild every payload from Postgres
+//! and the group routes to send an IQ per call. Answers are now kept in an
+//! in-process LRU (`CACHE_LOCAL_*`) and, with the `redis` feature and
+//! `CACHE_REDIS_ENABLED`, in Redis too, so replicas share them and a
+//! restart starts warm. Reads try the local tier, then Redis, which refills
+//! the local one.
+//!
+//! Entries live in a [`CacheSpace`] and a scope within it (the instance,
+//! or the workspace for listings). Events stored through the
+//! [`outbox`](super::outbox) drop what they make stale ([`stale_on`]), and
+//! writes to `api_sessions` drop the instance listings; the TTLs bound
+//! whatever a concurrent read puts back. A Redis error is a miss.
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
+use tracing::warn;
+
+#[cfg(feature = "redis")]
+use tracing::info;
+
+pub use crate::config::{CacheConfig, RedisCacheConfig};
+
+/// Kind of cached answer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CacheSpace {
+    /// Pages of `/instance/fetchInstances`, scoped by workspace.
+    Instances,
+    /// Groups the account takes part in, scoped by instance.
+    Groups,
+    /// `/chat/fetchProfilePictureUrl` answers, scoped by instance.
+    ProfilePictures,
+}
+
+impl CacheSpace {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Instances => "instances",
+            Self::Groups => "groups",
+            Self::ProfilePictures => "profile_pictures",
+        }
+    }
+}
+
+/// What an event makes stale.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stale {
+    /// Every entry of the space.
+    Space(CacheSpace),
+    /// The entries of the event's instance.
+    Instance(CacheSpace),
+}
+
+/// Entries made stale by `event`.
+pub fn stale_on(event: &str) -> &'static [Stale] {
+    match event {
+        "GROUPS_UPSERT" | "GROUPS_UPDATE" | "GROUP_PARTICIPANTS_UPDATE" => {
+            &[Stale::Instance(CacheSpace::Groups)]
+        }
+        "CONNECTION_UPDATE" | "QRCODE_UPDATED" => &[Stale::Space(CacheSpace::Instances)],
+        "LOGOUT_INSTANCE" | "INSTANCE_DELETE_PROGRESS" => &[
+            Stale::Space(CacheSpace::Instances),
+            Stale::Instance(CacheSpace::Groups),
+            Stale::Instance(CacheSpace::ProfilePictures),
+        ],
+        _ => &[],
+    }
+}
+
+type LocalKey = (CacheSpace, String, String);
+
+/// The local and Redis tiers.
+pub struct HotCache {
+    config: CacheConfig,
+    local: Option<moka::future::Cache<LocalKey, Value>>,
+    #[cfg(feature = "redis")]
+    redis: Option<redis_tier::RedisTier>,
+}
+
+impl Default for HotCache {
+    fn default() -> Self {
+        Self::new(CacheConfig::default())
+    }
+}
+
+impl HotCache {
+    /// Cache with the local tier only.
+    pub fn new(config: CacheConfig) -> Self {
+        let local = config.local_enabled.then(|| {
+            moka::future::Cache::builder()
+                .max_capacity(config.local_max_entries)
+                .time_to_live(config.local_ttl)
+                .support_invalidation_closures()
+                .build()
+        });
+        Self {
+            config,
+            local,
+            #[cfg(feature = "redis")]
+            redis: None,
+        }
+    }
+
+    /// Cache of `config`, connected to Redis when configured. Without a
+    /// connection the local tier works alone.
+    pub async fn connect(config: CacheConfig) -> Self {
+        #[cfg_attr(not(feature = "redis"), allow(unused_mut))]
+        let mut cache = Self::new(config);
+        #[cfg(feature = "redis")]
+        if let Some(redis) = cache.config.redis.clone() {
+            match redis_tier::RedisTier::connect(redis).await {
+                Ok(tier) => {
+                    info!("Cache Redis conectado");
+                    cache.redis = Some(tier);
+                }
+                Err(e) => {
+                    warn!(error = %e, "Cache Redis indisponível; usando apenas o cache local")
+                }
+            }
+        }
+        #[cfg(not(feature = "redis"))]
+        if cache.config.redis.is_some() {
+            warn!("CACHE_REDIS_ENABLED ignorado: build sem a feature `redis`");
+        }
+        cache
+    }
+
+    pub fn config(&self) -> &CacheConfig {
+        &self.config
+    }
+
+    /// The entry, from the local tier or else Redis.
+    pub async fn get<T: DeserializeOwned>(
+        &self,
+        space: CacheSpace,
+        scope: &str,
+        key: &str,
+    ) -> Option<T> {
+        let local_key = (space, scope.to_string(), key.to_string());
+        if let Some(local) = &self.local
+            && let Some(value) = local.get(&local_key).await
+        {
+            return serde_json::from_value(value).ok();
+        }
+        #[cfg(feature = "redis")]
+        if let Some(redis) = &self.redis
+            && let Some(value) = redis.get(space, scope, key).await
+        {
+            if let Some(local) = &self.local {
+                local.insert(local_key, value.clone()).await;
+            }
+            return serde_json::from_value(value).ok();
+        }
+        None
+    }
+
+    /// Stores `value` in both tiers. `max_ttl` shortens its Redis TTL, e.g.
+    /// for URLs that expire sooner.
+    pub async fn insert<T: Serialize>(
+        &self,
+        space: CacheSpace,
+        scope: &str,
+        key: &str,
+        value: &T,
+        max_ttl: Option<Duration>,
+    ) {
+        let value = match serde_json::to_value(value) {
+            Ok(value) => value,
+            Err(e) => {
+                warn!(space = space.as_str(), error = %e, "Valor não armazenável no cache");
+                return;
+            }
+        };
+        #[cfg(feature = "redis")]
+        if let Some(redis) = &self.redis {
+            redis.insert(space, scope, key, &value, max_ttl).await;
+        }
+        #[cfg(not(feature = "redis"))]
+        let _ = max_ttl;
+        if let Some(local) = &self.local {
+            local
+                .insert((space, scope.to_string(), key.to_string()), value)
+                .await;
+        }
+    }
+
+    /// Drops the entries of `scope` in `space`.
+    pub async fn invalidate(&self, space: CacheSpace, scope: &str) {
+        if let Some(local) = &self.local {
+            let scope = scope.to_string();
+            if let Err(e) =
+                local.invalidate_entries_if(move |(s, sc, _), _| *s == space && *sc == scope)
+            {
+                warn!(space = space.as_str(), error = %e, "Falha ao invalidar o cache local");
+            }
+        }
+        #[cfg(feature = "redis")]
+        if let Some(redis) = &self.redis {
+            redis.invalidate(space, scope).await;
+        }
+    }
+
+    /// Drops every entry of `space`.
+    pub async fn invalidate_space(&self, space: CacheSpace) {
+        if let Some(local) = &self.local
+            && let Err(e) = local.invalidate_entries_if(move |(s, _, _), _| *s == space)
+        {
+            warn!(space = space.as_str(), error = %e, "Falha ao invalidar o cache local");
+        }
+        #[cfg(feature = "redis")]
+        if let Some(redis) = &self.redis {
+            redis.invalidate_space(space).await;
+        }
+    }
+
+    /// Drops what `event` of `session` makes stale, see [`stale_on`].
+    pub async fn on_event(&self, session: Option<&str>, event: &str) {
+        for stale in stale_on(event) {
+            match (*stale, session) {
+                (Stale::Space(space), _) => self.invalidate_space(space).await,
+                (Stale::Instance(space), Some(session)) => self.invalidate(space, session).await,
+                (Stale::Instance(_), None) => {}
+            }
+        }
+    }
+}
+
+/// Key of an answer that depends on the query string, whatever the order
+/// of its parameters.
+pub fn query_key(query: &HashMap<String, String>) -> String {
+    let sorted: BTreeMap<_, _> = query.iter().collect();
+    serde_json::to_string(&sorted).unwrap_or_default()
+}
+
+/// Redis key of an entry: `{prefix}:{space}:{scope}:{key}`.
+pub fn redis_key(prefix: &str, space: CacheSpace, scope: &str, key: &str) -> String {
+    format!("{prefix}:{}:{scope}:{key}", space.as_str())
+}
+
+/// Redis set listing the keys of a scope, or with `None` the scopes of a
+/// space.
+pub fn redis_index(prefix: &str, space: CacheSpace, scope: Option<&str>) -> String {
+    match scope {
+        Some(scope) => format!("{prefix}:index:{}:{scope}", space.as_str()),
+        None => format!("{prefix}:index:{}", space.as_str()),
+    }
+}
+
+#[cfg(feature = "redis")]
+mod redis_tier {
+    use super::{CacheSpace, RedisCacheConfig, redis_index, redis_key};
+    use redis::AsyncCommands;
+    use redis::aio::ConnectionManager;
+    use serde_json::Value;
+    use std::time::Duration;
+    use tracing::debug;
+
+    /// Shared tier; entries are JSON strings indexed in a set per scope so
+    /// a scope or space can be dropped without `KEYS`.
+    pub(super) struct RedisTier {
+        config: RedisCacheConfig,
+        conn: ConnectionManager,
+    }
+
+    impl RedisTier {
+        pub(super) async fn connect(config: RedisCacheConfig) -> redis::RedisResult<Self> {
+            let client = redis::Client::open(config.uri.as_str())?;
+            let conn = ConnectionManager::new(client).await?;
+            Ok(Self { config, conn })
+        }
+
+        pub(super) async fn get(&self, space: CacheSpace, scope: &str, key: &str) -> Option<Value> {
+            let mut conn = self.conn.clone();
+            let key = redis_key(&self.config.prefix, space, scope, key);
+            match conn.get::<_, Option<String>>(&key).await {
+                Ok(raw) => raw.and_then(|raw| serde_json::from_str(&raw).ok()),
+                Err(e) => {
+                    debug!(key = %key, error = %e, "Leitura do cache Redis falhou");
+                    None
+                }
+            }
+        }
+
+        pub(super) async fn insert(
+            &self,
+            space: CacheSpace,
+            scope: &str,
+            key: &str,
+            value: &Value,
+            max_ttl: Option<Duration>,
+        ) {
+            let ttl = max_ttl.map_or(self.config.ttl, |max| max.min(self.config.ttl));
+            let secs = ttl.as_secs();
+            if secs == 0 {
+                return;
+            }
+            let prefix = &self.config.prefix;
+            let entry = redis_key(prefix, space, scope, key);
+            let scope_index = redis_index(prefix, space, Some(scope));
+            let space_index = redis_index(prefix, space, None);
+            // The indexes outlive their newest entry, never the other way.
+            let index_secs = self.config.ttl.as_secs() as i64;
+            let mut conn = self.conn.clone();
+            let result = redis::pipe()
+                .set_ex(&entry, value.to_string(), secs)
+                .ignore()
+                .sadd(&scope_index, &entry)
+                .ignore()
+                .expire(&scope_index, index_secs)
+                .ignore()
+                .sadd(&space_index, scope)
+                .ignore()
+                .expire(&space_index, index_secs)
+                .ignore()
+                .query_async::<()>(&mut conn)
+                .await;
+            if let Err(e) = result {
+                debug!(key = %entry, error = %e, "Escrita no cache Redis falhou");
+            }
+        }
+
+        pub(super) async fn invalidate(&self, space: CacheSpace, scope: &str) {
+            let mut conn = self.conn.clone();
+            if let Err(e) = drop_scope(&mut conn, &self.config.prefix, space, scope).await {
+                debug!(space = space.as_str(), scope, error = %e, "Invalidação do cache Redis falhou");
+            }
+        }
+
+        pub(super) async fn invalidate_space(&self, space: CacheSpace) {
+            let mut conn = self.conn.clone();
+            let prefix = &self.config.prefix;
+            let space_index = redis_index(prefix, space, None);
+            let result: redis::RedisResult<()> = async {
+                let scopes: Vec<String> = conn.smembers(&space_index).await?;
+                for scope in &scopes {
+                    drop_scope(&mut conn, prefix, space, scope).await?;
+                }
+                conn.del::<_, ()>(&space_index).await
+            }
+            .await;
+            if let Err(e) = result {
+                debug!(space = space.as_str(), error = %e, "Invalidação do cache Redis falhou");
+            }
+        }
+    }
+
+    async fn drop_scope(
+        conn: &mut ConnectionManager,
+        prefix: &str,
+        space: CacheSpace,
+        scope: &str,
+    ) -> redis::RedisResult<()> {
+        let scope_index = redis_index(prefix, space, Some(scope));
+        let mut keys: Vec<String> = conn.smembers(&scope_index).await?;
+        keys.push(scope_index);
+        conn.del::<_, ()>(keys).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    include!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/tests/server/cache_tests.rs"));
+}
@@ -0,0 +1,169 @@
+//! Fault injection routes (`chaos` feature).
+//!
+//! Arms the faults of [`crate::client::faults`] on a running instance so
+//! reconnects, decrypt failures, slow sends and handshake errors can be
+//! exercised on demand. Only the admin key (`CHATWARP_PASSWORD`) may call
+//! them; with auth disabled they always answer 403.
+
+use crate::client::HandshakePhase;
+use crate::server::AppState;
+use crate::server::workspaces::Scope;
+use axum::{
+    Json, Router,
+    body::Body,
+    extract::{Path, State},
+    http::{Request, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{get, post, put},
+};
+use serde::Deserialize;
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Longest accepted send delay.
+pub const MAX_SEND_DELAY_MS: u64 = 120_000;
+
+/// Routes under `/chaos/:name`.
+pub fn router() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/chaos/:name", get(get_faults).delete(clear_faults))
+        .route("/chaos/:name/disconnect", post(drop_socket))
+        .route("/chaos/:name/corrupt-frame", post(corrupt_frame))
+        .route("/chaos/:name/send-delay", put(set_send_delay))
+        .route("/chaos/:name/handshake-failure", put(set_handshake_failure))
+        .route_layer(middleware::from_fn(require_admin_key))
+}
+
+async fn require_admin_key(req: Request<Body>, next: Next) -> Response {
+    if req.extensions().get::<Scope>() != Some(&Scope::Admin) {
+        return (
+            StatusCode::FORBIDDEN,
+            Json(json!({"error": "chaos_requires_admin_key"})),
+        )
+            .into_response();
+    }
+    next.run(req).await
+}
+
+fn client(state: &AppState, name: &str) -> Option<Arc<crate::client::Client>> {
+    state.clients.get(name).map(|entry| entry.value().clone())
+}
+
+fn not_found() -> Response {
+    (
+        StatusCode::NOT_FOUND,
+        Json(json!({"error": "instance_not_found"})),
+    )
+        .into_response()
+}
+
+fn faults_response(name: &str, client: &crate::client::Client) -> Response {
+    (
+        StatusCode::OK,
+        Json(json!({"instance": name, "faults": client.faults.state()})),
+    )
+        .into_response()
+}
+
+async fn get_faults(Path(name): Path<String>, State(state): State<Arc<AppState>>) -> Response {
+    match client(&state, &name) {
+        Some(client) => faults_response(&name, &client),
+        None => not_found(),
+    }
+}
+
+async fn clear_faults(Path(name): Path<String>, State(state): State<Arc<AppState>>) -> Response {
+    match client(&state, &name) {
+        Some(client) => {
+            client.faults.clear();
+            tracing::warn!(instance = %name, "Chaos: falhas removidas");
+            faults_response(&name, &client)
+        }
+        None => not_found(),
+    }
+}
+
+/// Closes the WebSocket as if the server dropped it; the runner reconnects
+/// with its usual backoff.
+async fn drop_socket(Path(name): Path<String>, State(state): State<Arc<AppState>>) -> Response {
+    let client = match client(&state, &name) {
+        Some(client) => client,
+        None => return not_found(),
+    };
+    if !client.inject_disconnect().await {
+        return (
+            StatusCode::CONFLICT,
+            Json(json!({"error": "instance_not_connected"})),
+        )
+            .into_response();
+    }
+    tracing::warn!(instance = %name, "Chaos: WebSocket derrubado");
+    faults_response(&name, &client)
+}
+
+/// Flips a byte of the next chunk read from the server.
+async fn corrupt_frame(Path(name): Path<String>, State(state): State<Arc<AppState>>) -> Response {
+    match client(&state, &name) {
+        Some(client) => {
+            client.faults.corrupt_next_frame();
+            tracing::warn!(instance = %name, "Chaos: próximo frame será corrompido");
+            faults_response(&name, &client)
+        }
+        None => not_found(),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct SendDelayReq {
+    ms: u64,
+}
+
+/// `{"ms": 1500}` delays every outgoing node; `0` turns it off.
+async fn set_send_delay(
+    Path(name): Path<String>,
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<SendDelayReq>,
+) -> Response {
+    if payload.ms > MAX_SEND_DELAY_MS {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({"error": "invalid_delay", "max": MAX_SEND_DELAY_MS})),
+        )
+            .into_response();
+    }
+    match client(&state, &name) {
+        Some(client) => {
+            client
+                .faults
+                .set_send_delay(Duration::from_millis(payload.ms));
+            tracing::warn!(instance = %name, ms = payload.ms, "Chaos: atraso de envio definido");
+            faults_response(&name, &client)
+        }
+        None => not_found(),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct HandshakeFailureReq {
+    phase: Option<HandshakePhase>,
+}
+
+/// `{"phase": "ClientHello"}` fails the next connection attempt at that
+/// phase (`HttpUpgrade`, `ClientHello`, `ServerHello`, `ClientFinish` or
+/// `PostFinish`); `null` disarms it.
+async fn set_handshake_failure(
+    Path(name): Path<String>,
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<HandshakeFailureReq>,
+) -> Response {
+    match client(&state, &name) {
+        Some(client) => {
+            client.faults.fail_handshake_at(payload.phase);
+            tracing::warn!(instance = %name, phase = ?payload.phase, "Chaos: falha de handshake armada");
+            faults_response(&name, &client)
+        }
+        None => not_found(),
+    }
+}
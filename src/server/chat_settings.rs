@@ -0,0 +1,253 @@
+//! `/chat/archiveChat`, `/chat/markChatUnread`, `/chat/pinChat`,
+//! `/chat/muteChat` and `/chat/toggleEphemeral`.
+//!
+//! Each request becomes an app state patch sent through the instance's sync
+//! engine, so the change shows up on the phone and every linked device; the
+//! disappearing messages timer is instead a protocol message to the contact.
+//! Once the server accepts the change the chat row in `api_chats` is updated
+//! and a `CHATS_UPDATE` event is emitted.
+
+use crate::api_store::ApiBind;
+use crate::client::Client;
+use crate::features::EPHEMERAL_DURATIONS;
+use crate::server::AppState;
+use crate::server::events::{ChatsUpdate, EventPayload};
+use crate::server::jid::{self, JidError};
+use serde_json::Value;
+use thiserror::Error;
+use warp_core_binary::jid::{Jid, JidExt as _};
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ChatSettingError {
+    #[error("chat is required")]
+    MissingChat,
+    #[error(transparent)]
+    InvalidChat(#[from] JidError),
+    #[error("{0} must be a boolean")]
+    MissingFlag(&'static str),
+    #[error("duration must be a positive number of seconds")]
+    InvalidDuration,
+    #[error("expiration must be one of {EPHEMERAL_DURATIONS:?} seconds")]
+    InvalidExpiration,
+    #[error("group timers are set through /group/toggleEphemeral")]
+    GroupChat,
+}
+
+/// Change requested for a chat.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChatAction {
+    Archive {
+        archive: bool,
+        last_message_timestamp: Option<i64>,
+    },
+    MarkUnread {
+        last_message_timestamp: Option<i64>,
+    },
+    Pin(bool),
+    /// Mute end in milliseconds (`-1` forever), `None` to unmute.
+    Mute(Option<i64>),
+    /// Disappearing messages timer in seconds (`0` off), changed at
+    /// `timestamp` (seconds).
+    Ephemeral {
+        expiration: u32,
+        timestamp: i64,
+    },
+}
+
+impl ChatAction {
+    /// `{"archive": bool, "lastMessage": {"messageTimestamp": secs}?}`.
+    pub fn archive_from_body(body: &Value) -> Result<Self, ChatSettingError> {
+        Ok(Self::Archive {
+            archive: flag(body, "archive")?,
+            last_message_timestamp: last_message_timestamp(body),
+        })
+    }
+
+    /// `{"lastMessage": {"messageTimestamp": secs}?}`.
+    pub fn mark_unread_from_body(body: &Value) -> Self {
+        Self::MarkUnread {
+            last_message_timestamp: last_message_timestamp(body),
+        }
+    }
+
+    /// `{"pin": bool}`.
+    pub fn pin_from_body(body: &Value) -> Result<Self, ChatSettingError> {
+        Ok(Self::Pin(flag(body, "pin")?))
+    }
+
+    /// `{"mute": bool, "duration": secs?}`; muting without a duration mutes
+    /// forever. `now_ms` is the current time in milliseconds.
+    pub fn mute_from_body(body: &Value, now_ms: i64) -> Result<Self, ChatSettingError> {
+        if !flag(body, "mute")? {
+            return Ok(Self::Mute(None));
+        }
+        match body.get("duration").filter(|d| !d.is_null()) {
+            None => Ok(Self::Mute(Some(-1))),
+            Some(duration) => {
+                let seconds = duration
+                    .as_i64()
+                    .filter(|s| *s > 0)
+                    .ok_or(ChatSettingError::InvalidDuration)?;
+                Ok(Self::Mute(Some(now_ms.saturating_add(seconds.saturating_mul(1000)))))
+            }
+        }
+    }
+
+    /// `{"expiration": secs}`, one of [`EPHEMERAL_DURATIONS`]. Only direct
+    /// chats; `now` is the current time in seconds.
+    pub fn ephemeral_from_body(body: &Value, now: i64) -> Result<Self, ChatSettingError> {
+        if chat_from_body(body)?.is_group() {
+            return Err(ChatSettingError::GroupChat);
+        }
+        let expiration = body
+            .get("expiration")
+            .and_then(Value::as_u64)
+            .and_then(|v| u32::try_from(v).ok())
+            .filter(|v| EPHEMERAL_DURATIONS.contains(v))
+            .ok_or(ChatSettingError::InvalidExpiration)?;
+        Ok(Self::Ephemeral {
+            expiration,
+            timestamp: now,
+        })
+    }
+
+    /// Sends the matching app state patch (or protocol message) for `chat`.
+    pub async fn apply(&self, client: &Client, chat: &Jid) -> anyhow::Result<()> {
+        let chats = client.chats();
+        match *self {
+            Self::Archive {
+                archive,
+                last_message_timestamp,
+            } => chats.archive(chat, archive, last_message_timestamp).await,
+            Self::MarkUnread {
+                last_message_timestamp,
+            } => chats.mark_read(chat, false, last_message_timestamp).await,
+            Self::Pin(pin) => chats.pin(chat, pin).await,
+            Self::Mute(end) => chats.mute(chat, end).await,
+            Self::Ephemeral {
+                expiration,
+                timestamp,
+            } => chats.set_ephemeral(chat, expiration, timestamp).await,
+        }
+    }
+
+    /// `CHATS_UPDATE` payload for `chat`.
+    pub fn event(&self, chat: &str) -> Value {
+        let id = chat.to_string();
+        let update = match *self {
+            Self::Archive { archive, .. } if archive => ChatsUpdate {
+                id,
+                archived: Some(true),
+                pinned: Some(false),
+                ..Default::default()
+            },
+            Self::Archive { .. } => ChatsUpdate {
+                id,
+                archived: Some(false),
+                ..Default::default()
+            },
+            Self::MarkUnread { .. } => ChatsUpdate {
+                id,
+                marked_unread: Some(true),
+                ..Default::default()
+            },
+            Self::Pin(pin) => ChatsUpdate {
+                id,
+                pinned: Some(pin),
+                ..Default::default()
+            },
+            Self::Mute(end) => ChatsUpdate {
+                id,
+                muted: Some(end.is_some()),
+                mute_end_time: Some(end),
+                ..Default::default()
+            },
+            Self::Ephemeral { expiration, .. } => ChatsUpdate {
+                id,
+                ephemeral_expiration: Some(expiration),
+                ..Default::default()
+            },
+        };
+        update.to_data()
+    }
+
+    /// Upserts the chat row of `session` with the new setting.
+    pub async fn store(&self, state: &AppState, session: &str, chat: &str) -> anyhow::Result<()> {
+        let (sql, values) = match *self {
+            Self::Archive { archive, .. } if archive => (
+                "INSERT INTO api_chats (session, id, archived, pinned) VALUES ($1, $2, $3, false) \
+                 ON CONFLICT (session, id) DO UPDATE SET archived = EXCLUDED.archived, pinned = false",
+                vec![ApiBind::Bool(true)],
+            ),
+            Self::Archive { .. } => (
+                "INSERT INTO api_chats (session, id, archived) VALUES ($1, $2, $3) \
+                 ON CONFLICT (session, id) DO UPDATE SET archived = EXCLUDED.archived",
+                vec![ApiBind::Bool(false)],
+            ),
+            Self::MarkUnread { .. } => (
+                "INSERT INTO api_chats (session, id, marked_unread) VALUES ($1, $2, $3) \
+                 ON CONFLICT (session, id) DO UPDATE SET marked_unread = EXCLUDED.marked_unread",
+                vec![ApiBind::Bool(true)],
+            ),
+            Self::Pin(pin) => (
+                "INSERT INTO api_chats (session, id, pinned) VALUES ($1, $2, $3) \
+                 ON CONFLICT (session, id) DO UPDATE SET pinned = EXCLUDED.pinned",
+                vec![ApiBind::Bool(pin)],
+            ),
+            Self::Mute(end) => (
+                "INSERT INTO api_chats (session, id, mute_end_at) VALUES ($1, $2, $3::bigint) \
+                 ON CONFLICT (session, id) DO UPDATE SET mute_end_at = EXCLUDED.mute_end_at",
+                vec![ApiBind::NullableText(end.map(|end| end.to_string()))],
+            ),
+            Self::Ephemeral {
+                expiration,
+                timestamp,
+            } => (
+                "INSERT INTO api_chats (session, id, ephemeral_expiration, ephemeral_setting_at) \
+                 VALUES ($1, $2, $3, $4::bigint) \
+                 ON CONFLICT (session, id) DO UPDATE SET \
+                 ephemeral_expiration = EXCLUDED.ephemeral_expiration, \
+                 ephemeral_setting_at = EXCLUDED.ephemeral_setting_at",
+                vec![
+                    ApiBind::Int(i32::try_from(expiration).unwrap_or(i32::MAX)),
+                    ApiBind::Text(timestamp.to_string()),
+                ],
+            ),
+        };
+        let mut binds = vec![
+            ApiBind::Text(session.to_string()),
+            ApiBind::Text(chat.to_string()),
+        ];
+        binds.extend(values);
+        state.api_store.execute(sql, binds).await?;
+        Ok(())
+    }
+}
+
+/// Chat JID of the request: `chat`, falling back to Evolution's `number`.
+pub fn chat_from_body(body: &Value) -> Result<Jid, ChatSettingError> {
+    let raw = ["chat", "number"]
+        .iter()
+        .find_map(|key| body.get(*key).and_then(Value::as_str))
+        .filter(|raw| !raw.trim().is_empty())
+        .ok_or(ChatSettingError::MissingChat)?;
+    Ok(jid::parse(raw)?.to_non_ad())
+}
+
+fn flag(body: &Value, key: &'static str) -> Result<bool, ChatSettingError> {
+    body.get(key)
+        .and_then(Value::as_bool)
+        .ok_or(ChatSettingError::MissingFlag(key))
+}
+
+/// `lastMessage.messageTimestamp` in seconds, as a number or numeric string.
+fn last_message_timestamp(body: &Value) -> Option<i64> {
+    let ts = body.get("lastMessage")?.get("messageTimestamp")?;
+    ts.as_i64()
+        .or_else(|| ts.as_str().and_then(|s| s.trim().parse().ok()))
+}
+
+#[cfg(test)]
+mod tests {
+    include!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/tests/server/chat_settings_tests.rs"));
+}
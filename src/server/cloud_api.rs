@@ -0,0 +1,956 @@
+//! WhatsApp Cloud API (Meta business) channel.
+//!
+//! Instances created with `integration: "WHATSAPP-BUSINESS"` have no
+//! WhatsApp Web client: the messages worker sends their queue through the
+//! Graph API, and Meta delivers inbound messages and status updates to
+//! `/webhook/meta`, which are normalized into the same `MESSAGES_UPSERT` /
+//! `MESSAGES_UPDATE` payloads the WhatsApp Web instances emit.
+
+use crate::api_store::ApiBind;
+use crate::server::events::{EventPayload, MessagesUpsert};
+use crate::server::inbound::secret_matches;
+use crate::server::message_counters::Outcome;
+use crate::server::quotas::{self, QuotaError};
+use crate::server::uploads::{self, UploadError};
+use crate::server::workspaces::hash_api_key;
+use crate::server::{AppState, messages_worker, webhooks};
+use axum::{
+    Json,
+    body::Bytes,
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+use base64::Engine as _;
+use hmac::{Hmac, Mac};
+use serde_json::{Value, json};
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::sync::Arc;
+use thiserror::Error;
+use tracing::{debug, warn};
+use warp_core::net::{HttpClient, HttpRequest};
+
+pub use crate::config::MetaConfig;
+
+/// `api_sessions.integration` of Cloud API instances.
+pub const INTEGRATION: &str = "WHATSAPP-BUSINESS";
+/// `api_sessions.integration` of WhatsApp Web instances (the default).
+pub const DEFAULT_INTEGRATION: &str = "WHATSAPP-BAILEYS";
+const SIGNATURE_HEADER: &str = "x-hub-signature-256";
+
+impl MetaConfig {
+    fn messages_url(&self, phone_number_id: &str) -> String {
+        format!("{}/{}/{}/messages", self.graph_url, self.graph_version, phone_number_id)
+    }
+}
+
+/// Credentials of a Cloud API instance.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CloudChannel {
+    pub phone_number_id: String,
+    pub access_token: String,
+}
+
+/// Cloud API credentials of `session`, `None` for WhatsApp Web instances.
+pub async fn channel(state: &AppState, session: &str) -> anyhow::Result<Option<CloudChannel>> {
+    let rows = state
+        .api_store
+        .query_json(
+            "SELECT jsonb_build_object('phone_number_id', cloud_phone_number_id, \
+                                       'access_token', cloud_access_token) as value \
+             FROM api_sessions \
+             WHERE session = $1 AND integration = $2",
+            vec![
+                ApiBind::Text(session.to_string()),
+                ApiBind::Text(INTEGRATION.to_string()),
+            ],
+        )
+        .await?;
+    let Some(row) = rows.first() else {
+        return Ok(None);
+    };
+    let (Some(phone_number_id), Some(access_token)) = (
+        row["phone_number_id"].as_str(),
+        row["access_token"].as_str(),
+    ) else {
+        return Ok(None);
+    };
+    Ok(Some(CloudChannel {
+        phone_number_id: phone_number_id.to_string(),
+        access_token: state.open_secret(access_token)?,
+    }))
+}
+
+/// Names of every Cloud API instance.
+pub async fn sessions(state: &AppState) -> anyhow::Result<Vec<String>> {
+    let rows = state
+        .api_store
+        .query_json(
+            "SELECT to_jsonb(session) as value FROM api_sessions WHERE integration = $1",
+            vec![ApiBind::Text(INTEGRATION.to_string())],
+        )
+        .await?;
+    Ok(rows
+        .iter()
+        .filter_map(|row| row.as_str().map(str::to_string))
+        .collect())
+}
+
+async fn session_for_phone_number(
+    state: &AppState,
+    phone_number_id: &str,
+) -> anyhow::Result<Option<String>> {
+    let rows = state
+        .api_store
+        .query_json(
+            "SELECT to_jsonb(session) as value FROM api_sessions \
+             WHERE cloud_phone_number_id = $1 AND integration = $2",
+            vec![
+                ApiBind::Text(phone_number_id.to_string()),
+                ApiBind::Text(INTEGRATION.to_string()),
+            ],
+        )
+        .await?;
+    Ok(rows.first().and_then(Value::as_str).map(str::to_string))
+}
+
+/// `hub.challenge` when the subscription request carries our verify token,
+/// compared in constant time.
+pub fn verify_challenge(config: &MetaConfig, query: &HashMap<String, String>) -> Option<String> {
+    let expected = config.verify_token.as_deref()?;
+    let mode = query.get("hub.mode")?;
+    let token = query.get("hub.verify_token")?;
+    (mode == "subscribe" && secret_matches(token, &hash_api_key(expected)))
+        .then(|| query.get("hub.challenge").cloned())
+        .flatten()
+}
+
+/// Checks `X-Hub-Signature-256: sha256=<hex>` against the raw body.
+pub fn verify_signature(app_secret: &str, body: &[u8], header: Option<&str>) -> bool {
+    let Some(signature) = header
+        .and_then(|h| h.strip_prefix("sha256="))
+        .and_then(|hex_sig| hex::decode(hex_sig).ok())
+    else {
+        return false;
+    };
+    let Ok(mut mac) = Hmac::<Sha256>::new_from_slice(app_secret.as_bytes()) else {
+        return false;
+    };
+    mac.update(body);
+    mac.verify_slice(&signature).is_ok()
+}
+
+/// Response rejecting a `POST /webhook/meta` whose signature cannot be
+/// trusted: 503 without `META_APP_SECRET`, since the route has no other
+/// authentication, and 401 when the signature does not match.
+pub fn signature_rejection(
+    config: &MetaConfig,
+    headers: &HeaderMap,
+    body: &[u8],
+) -> Option<Response> {
+    let Some(secret) = config.app_secret.as_deref() else {
+        return Some(
+            (
+                StatusCode::SERVICE_UNAVAILABLE,
+                Json(json!({"error": "meta_app_secret_not_configured"})),
+            )
+                .into_response(),
+        );
+    };
+    let header = headers.get(SIGNATURE_HEADER).and_then(|v| v.to_str().ok());
+    (!verify_signature(secret, body, header)).then(|| {
+        (
+            StatusCode::UNAUTHORIZED,
+            Json(json!({"error": "invalid_signature"})),
+        )
+            .into_response()
+    })
+}
+
+/// Cloud API `to` field: the digits of a user JID.
+pub fn recipient(chat_id: &str) -> String {
+    let user = chat_id.split('@').next().unwrap_or(chat_id);
+    let user = user.split(':').next().unwrap_or(user);
+    user.chars().filter(char::is_ascii_digit).collect()
+}
+
+fn str_field<'a>(value: &'a Value, key: &str) -> Option<&'a str> {
+    value.get(key).and_then(Value::as_str).filter(|s| !s.trim().is_empty())
+}
+
+/// Graph API body for a queued `api_messages` payload. `None` for types the
+/// Cloud API cannot take as-is (media must be a public URL).
+pub fn outbound_message(message_type: &str, payload: &Value) -> Option<Value> {
+    let to = recipient(str_field(payload, "chatId")?);
+    let caption = str_field(payload, "caption");
+    let media = |kind: &str| -> Option<(String, Value)> {
+        let mut object = json!({ "link": str_field(payload, "url")? });
+        if let Some(caption) = caption.filter(|_| kind != "audio" && kind != "sticker") {
+            object["caption"] = json!(caption);
+        }
+        if kind == "document"
+            && let Some(name) = str_field(payload, "filename").or_else(|| str_field(payload, "fileName"))
+        {
+            object["filename"] = json!(name);
+        }
+        Some((kind.to_string(), object))
+    };
+
+    let (kind, content) = match message_type {
+        "text" => (
+            "text".to_string(),
+            json!({
+                "body": str_field(payload, "text")?,
+                "preview_url": payload.get("linkPreview").and_then(Value::as_bool).unwrap_or(false),
+            }),
+        ),
+        "image" => media("image")?,
+        "video" => media("video")?,
+        "voice" | "audio" => media("audio")?,
+        "file" => media("document")?,
+        "sticker" => media("sticker")?,
+        "template" | "buttons" => ("interactive".to_string(), interactive_buttons(payload)?),
+        "list" => ("interactive".to_string(), interactive_list(payload)?),
+        _ => return None,
+    };
+
+    let mut body = json!({
+        "messaging_product": "whatsapp",
+        "recipient_type": "individual",
+        "to": to,
+        "type": kind,
+    });
+    body[kind.as_str()] = content;
+    if let Some(quoted) = payload
+        .get("quoted")
+        .and_then(|q| str_field(q, "messageId").or_else(|| str_field(q, "message_id")))
+    {
+        body["context"] = json!({ "message_id": quoted });
+    }
+    Some(body)
+}
+
+/// Rendered template as an interactive reply-button message. The Cloud API
+/// only allows reply buttons there, so URL and call buttons are appended to
+/// the body text.
+fn interactive_buttons(payload: &Value) -> Option<Value> {
+    let mut text = str_field(payload, "text")?.to_string();
+    let mut replies = Vec::new();
+    for (index, button) in payload
+        .get("buttons")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .enumerate()
+    {
+        let label = str_field(button, "text").unwrap_or_default();
+        match str_field(button, "type") {
+            Some("reply") => replies.push(json!({
+                "type": "reply",
+                "reply": {
+                    "id": str_field(button, "id")
+                        .map(str::to_string)
+                        .unwrap_or_else(|| format!("btn-{}", index + 1)),
+                    "title": label,
+                },
+            })),
+            Some("url") => text.push_str(&format!("\n{}: {}", label, str_field(button, "url")?)),
+            Some("call") => text.push_str(&format!("\n{}: {}", label, str_field(button, "phone")?)),
+            _ => {}
+        }
+    }
+    if replies.is_empty() {
+        return None;
+    }
+
+    let mut interactive = json!({
+        "type": "button",
+        "body": { "text": text },
+        "action": { "buttons": replies },
+    });
+    if let Some(title) = str_field(payload, "title") {
+        interactive["header"] = json!({ "type": "text", "text": title });
+    }
+    if let Some(footer) = str_field(payload, "footer") {
+        interactive["footer"] = json!({ "text": footer });
+    }
+    Some(interactive)
+}
+
+/// `/sendList` payload as an interactive list message. Rows without `id`
+/// get `row-<n>`, the same ids the WhatsApp Web runner uses.
+fn interactive_list(payload: &Value) -> Option<Value> {
+    let mut row_count = 0;
+    let mut sections = Vec::new();
+    for section in payload.get("sections").and_then(Value::as_array)? {
+        let mut rows = Vec::new();
+        for row in section.get("rows").and_then(Value::as_array)? {
+            row_count += 1;
+            let mut object = json!({
+                "id": str_field(row, "id")
+                    .map(str::to_string)
+                    .unwrap_or_else(|| format!("row-{row_count}")),
+                "title": str_field(row, "title")?,
+            });
+            if let Some(description) = str_field(row, "description") {
+                object["description"] = json!(description);
+            }
+            rows.push(object);
+        }
+        let mut object = json!({ "rows": rows });
+        if let Some(title) = str_field(section, "title") {
+            object["title"] = json!(title);
+        }
+        sections.push(object);
+    }
+
+    let mut interactive = json!({
+        "type": "list",
+        "body": { "text": str_field(payload, "text")? },
+        "action": {
+            "button": str_field(payload, "buttonText")?,
+            "sections": sections,
+        },
+    });
+    if let Some(title) = str_field(payload, "title") {
+        interactive["header"] = json!({ "type": "text", "text": title });
+    }
+    if let Some(footer) = str_field(payload, "footer") {
+        interactive["footer"] = json!({ "text": footer });
+    }
+    Some(interactive)
+}
+
+/// Template and button payloads without reply buttons go out as text with
+/// the links appended, same as [`interactive_buttons`] does for mixed ones.
+fn fallback_text(payload: &Value) -> Option<Value> {
+    let mut text = str_field(payload, "text")?.to_string();
+    for button in payload.get("buttons").and_then(Value::as_array).into_iter().flatten() {
+        let label = str_field(button, "text").unwrap_or_default();
+        if let Some(target) = str_field(button, "url").or_else(|| str_field(button, "phone")) {
+            text.push_str(&format!("\n{}: {}", label, target));
+        }
+    }
+    let mut payload = payload.clone();
+    payload["text"] = json!(text);
+    Some(payload)
+}
+
+/// Sends one queued message through the Graph API; returns the WA message id.
+pub async fn send(
+    http: &dyn HttpClient,
+    config: &MetaConfig,
+    channel: &CloudChannel,
+    message_type: &str,
+    payload: &Value,
+) -> anyhow::Result<String> {
+    let body = match outbound_message(message_type, payload) {
+        Some(body) => body,
+        None if matches!(message_type, "template" | "buttons") => fallback_text(payload)
+            .and_then(|payload| outbound_message("text", &payload))
+            .ok_or_else(|| anyhow::anyhow!("{message_type} without text"))?,
+        None => anyhow::bail!("message type {message_type} not supported by the Cloud API"),
+    };
+
+    post_message(http, config, channel, &body).await
+}
+
+/// Posts a Graph API message body; returns the WA message id. A refusal
+/// by Meta is a [`GraphError`].
+pub async fn post_message(
+    http: &dyn HttpClient,
+    config: &MetaConfig,
+    channel: &CloudChannel,
+    body: &Value,
+) -> anyhow::Result<String> {
+    let req = HttpRequest::post(config.messages_url(&channel.phone_number_id))
+        .with_header("Content-Type", "application/json")
+        .with_header("Authorization", format!("Bearer {}", channel.access_token))
+        .with_body(serde_json::to_vec(body)?);
+    let resp = http.execute(req).await?;
+    let response: Value = serde_json::from_slice(&resp.body).unwrap_or(Value::Null);
+    if !(200..300).contains(&resp.status_code) {
+        return Err(GraphError::from_response(resp.status_code, &response).into());
+    }
+    Ok(response["messages"][0]["id"]
+        .as_str()
+        .unwrap_or_default()
+        .to_string())
+}
+
+/// Error answered by the Graph API.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("graph api http {status}: {message}")]
+pub struct GraphError {
+    pub status: u16,
+    /// Meta error code, e.g. `132001` for a template that does not exist.
+    pub code: Option<i64>,
+    pub subcode: Option<i64>,
+    pub message: String,
+    pub fbtrace_id: Option<String>,
+}
+
+impl GraphError {
+    pub fn from_response(status: u16, body: &Value) -> Self {
+        let error = &body["error"];
+        Self {
+            status,
+            code: error["code"].as_i64(),
+            subcode: error["error_subcode"].as_i64(),
+            message: error["error_user_msg"]
+                .as_str()
+                .or_else(|| error["message"].as_str())
+                .unwrap_or("unknown error")
+                .to_string(),
+            fbtrace_id: error["fbtrace_id"].as_str().map(str::to_string),
+        }
+    }
+
+    /// Error envelope: template and parameter errors are the caller's
+    /// (`400`), rate limits `429`, and token or account problems and the
+    /// rest Meta's (`502`).
+    pub fn response(&self) -> (StatusCode, Json<Value>) {
+        let (status, error) = match self.code {
+            Some(4 | 80007 | 130429 | 131048 | 131056) => {
+                (StatusCode::TOO_MANY_REQUESTS, "graph_rate_limited")
+            }
+            Some(100 | 131008 | 131009 | 131026 | 131051 | 132000..=132999) => {
+                (StatusCode::BAD_REQUEST, "graph_invalid_request")
+            }
+            Some(190 | 200..=299 | 10) => (StatusCode::BAD_GATEWAY, "graph_auth_failed"),
+            _ => (StatusCode::BAD_GATEWAY, "graph_api_error"),
+        };
+        (
+            status,
+            Json(json!({
+                "error": error,
+                "details": self.message,
+                "graph": {
+                    "status": self.status,
+                    "code": self.code,
+                    "subcode": self.subcode,
+                    "fbtraceId": self.fbtrace_id,
+                },
+            })),
+        )
+    }
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum TemplateRequestError {
+    #[error("number is required")]
+    MissingNumber,
+    #[error("name is required")]
+    MissingName,
+    #[error("language is required")]
+    MissingLanguage,
+    #[error("components must be an array of objects with a type")]
+    InvalidComponents,
+    #[error("component type {0:?}: expected header, body or button")]
+    InvalidComponentType(String),
+    #[error("button components need sub_type and index")]
+    InvalidButton,
+}
+
+/// Graph API body of an approved template (HSM) for `sendTemplate`:
+/// `number`, `name`, `language` (a code or `{"code"}`) and `components`
+/// in the Graph API format.
+pub fn template_message(payload: &Value) -> Result<Value, TemplateRequestError> {
+    let to = str_field(payload, "number")
+        .map(recipient)
+        .filter(|to| !to.is_empty())
+        .ok_or(TemplateRequestError::MissingNumber)?;
+    let name = str_field(payload, "name").ok_or(TemplateRequestError::MissingName)?;
+    let language = str_field(payload, "language")
+        .or_else(|| payload.get("language").and_then(|l| str_field(l, "code")))
+        .ok_or(TemplateRequestError::MissingLanguage)?;
+
+    let components = match payload.get("components") {
+        None | Some(Value::Null) => Vec::new(),
+        Some(Value::Array(components)) => components.clone(),
+        Some(_) => return Err(TemplateRequestError::InvalidComponents),
+    };
+    for component in &components {
+        let kind = str_field(component, "type").ok_or(TemplateRequestError::InvalidComponents)?;
+        match kind.to_ascii_lowercase().as_str() {
+            "header" | "body" => {}
+            "button" => {
+                if str_field(component, "sub_type").is_none() || component.get("index").is_none() {
+                    return Err(TemplateRequestError::InvalidButton);
+                }
+            }
+            _ => return Err(TemplateRequestError::InvalidComponentType(kind.to_string())),
+        }
+    }
+
+    let mut template = json!({
+        "name": name,
+        "language": { "code": language },
+    });
+    if !components.is_empty() {
+        template["components"] = Value::Array(components);
+    }
+    Ok(json!({
+        "messaging_product": "whatsapp",
+        "recipient_type": "individual",
+        "to": to,
+        "type": "template",
+        "template": template,
+    }))
+}
+
+/// Header media given as `base64` or as the `mediaId` of an upload, which
+/// has to be sent to Meta before the template.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HeaderMedia {
+    Base64 {
+        kind: String,
+        data: String,
+        mimetype: Option<String>,
+        filename: Option<String>,
+    },
+    Upload {
+        kind: String,
+        media_id: String,
+    },
+}
+
+fn header_media_object(message: &mut Value) -> Option<(String, &mut Value)> {
+    let header = message
+        .get_mut("template")?
+        .get_mut("components")?
+        .as_array_mut()?
+        .iter_mut()
+        .find(|c| str_field(c, "type").is_some_and(|t| t.eq_ignore_ascii_case("header")))?;
+    let parameter = header.get_mut("parameters")?.as_array_mut()?.first_mut()?;
+    let kind = str_field(parameter, "type")?.to_ascii_lowercase();
+    if !matches!(kind.as_str(), "image" | "video" | "document") {
+        return None;
+    }
+    let media = parameter.get_mut(kind.as_str())?;
+    Some((kind, media))
+}
+
+/// Header media of a [`template_message`] that still needs an upload.
+pub fn header_media(message: &Value) -> Option<HeaderMedia> {
+    let mut message = message.clone();
+    let (kind, media) = header_media_object(&mut message)?;
+    if let Some(media_id) = str_field(media, "mediaId") {
+        return Some(HeaderMedia::Upload {
+            kind,
+            media_id: media_id.to_string(),
+        });
+    }
+    let data = str_field(media, "base64")?.to_string();
+    Some(HeaderMedia::Base64 {
+        kind,
+        mimetype: str_field(media, "mimetype").map(str::to_string),
+        filename: str_field(media, "filename").map(str::to_string),
+        data,
+    })
+}
+
+/// Points the header media of `message` at the Meta media `id`.
+pub fn set_header_media_id(message: &mut Value, id: &str) {
+    if let Some((kind, media)) = header_media_object(message) {
+        let mut object = json!({ "id": id });
+        if kind == "document"
+            && let Some(filename) = str_field(media, "filename")
+        {
+            object["filename"] = json!(filename);
+        }
+        *media = object;
+    }
+}
+
+/// Mimetype of a header media without one.
+pub fn default_mimetype(kind: &str) -> &'static str {
+    match kind {
+        "image" => "image/jpeg",
+        "video" => "video/mp4",
+        _ => "application/pdf",
+    }
+}
+
+/// Uploads media to Meta for the phone number of `channel`; returns the
+/// media id.
+pub async fn upload_media(
+    http: &dyn HttpClient,
+    config: &MetaConfig,
+    channel: &CloudChannel,
+    data: Vec<u8>,
+    mimetype: &str,
+    filename: &str,
+) -> anyhow::Result<String> {
+    let boundary = format!("chatwarp-{}", uuid::Uuid::new_v4().simple());
+    let mut body = format!(
+        "--{boundary}\r\nContent-Disposition: form-data; name=\"messaging_product\"\r\n\r\nwhatsapp\r\n\
+         --{boundary}\r\nContent-Disposition: form-data; name=\"type\"\r\n\r\n{mimetype}\r\n\
+         --{boundary}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"{}\"\r\n\
+         Content-Type: {mimetype}\r\n\r\n",
+        filename.replace(['"', '\r', '\n'], "_")
+    )
+    .into_bytes();
+    body.extend_from_slice(&data);
+    body.extend_from_slice(format!("\r\n--{boundary}--\r\n").as_bytes());
+
+    let url = format!(
+        "{}/{}/{}/media",
+        config.graph_url, config.graph_version, channel.phone_number_id
+    );
+    let req = HttpRequest::post(url)
+        .with_header(
+            "Content-Type",
+            format!("multipart/form-data; boundary={boundary}"),
+        )
+        .with_header("Authorization", format!("Bearer {}", channel.access_token))
+        .with_body(body);
+    let resp = http.execute(req).await?;
+    let response: Value = serde_json::from_slice(&resp.body).unwrap_or(Value::Null);
+    if !(200..300).contains(&resp.status_code) {
+        return Err(GraphError::from_response(resp.status_code, &response).into());
+    }
+    response["id"]
+        .as_str()
+        .map(str::to_string)
+        .ok_or_else(|| anyhow::anyhow!("graph api media upload without id"))
+}
+
+/// Why a `sendTemplate` was not sent.
+#[derive(Debug, Error)]
+pub enum TemplateSendError {
+    #[error(transparent)]
+    Request(#[from] TemplateRequestError),
+    #[error("invalid header media: {0}")]
+    InvalidMedia(String),
+    #[error(transparent)]
+    Upload(#[from] UploadError),
+    #[error(transparent)]
+    Quota(#[from] QuotaError),
+    #[error(transparent)]
+    Graph(GraphError),
+    #[error(transparent)]
+    Other(anyhow::Error),
+}
+
+impl From<anyhow::Error> for TemplateSendError {
+    fn from(e: anyhow::Error) -> Self {
+        match e.downcast::<GraphError>() {
+            Ok(graph) => Self::Graph(graph),
+            Err(e) => Self::Other(e),
+        }
+    }
+}
+
+impl TemplateSendError {
+    pub fn response(&self) -> (StatusCode, Json<Value>) {
+        let (status, error) = match self {
+            Self::Graph(graph) => return graph.response(),
+            Self::Quota(quota) => return quota.response(),
+            Self::Request(_) => (StatusCode::BAD_REQUEST, "invalid_template"),
+            Self::InvalidMedia(_) => (StatusCode::BAD_REQUEST, "invalid_media"),
+            Self::Upload(UploadError::NotFound) => (StatusCode::NOT_FOUND, "upload_not_found"),
+            Self::Upload(_) => (StatusCode::INTERNAL_SERVER_ERROR, "upload_failed"),
+            Self::Other(_) => (StatusCode::BAD_GATEWAY, "graph_api_unreachable"),
+        };
+        (
+            status,
+            Json(json!({"error": error, "details": self.to_string()})),
+        )
+    }
+}
+
+async fn header_media_bytes(
+    state: &AppState,
+    session: &str,
+    media: &HeaderMedia,
+) -> Result<(Vec<u8>, String, String), TemplateSendError> {
+    match media {
+        HeaderMedia::Base64 {
+            kind,
+            data,
+            mimetype,
+            filename,
+        } => {
+            let (from_data_url, raw) = messages_worker::split_data_url(data);
+            let bytes = base64::engine::general_purpose::STANDARD
+                .decode(raw.trim())
+                .map_err(|e| TemplateSendError::InvalidMedia(e.to_string()))?;
+            let mimetype = mimetype
+                .clone()
+                .or(from_data_url)
+                .unwrap_or_else(|| default_mimetype(kind).to_string());
+            let filename = filename.clone().unwrap_or_else(|| kind.clone());
+            Ok((bytes, mimetype, filename))
+        }
+        HeaderMedia::Upload { kind, media_id } => {
+            let (upload, path) = uploads::find(&state.uploads, session, media_id).await?;
+            let bytes = tokio::fs::read(&path).await.map_err(UploadError::from)?;
+            let mimetype = upload
+                .mimetype
+                .unwrap_or_else(|| default_mimetype(kind).to_string());
+            let filename = upload.file_name.unwrap_or_else(|| kind.clone());
+            Ok((bytes, mimetype, filename))
+        }
+    }
+}
+
+/// Sends a `sendTemplate` body through the Cloud API right away, uploading
+/// its header media first, and stores it. Returns the Graph API body sent
+/// and the WA message id.
+pub async fn send_template(
+    state: &AppState,
+    session: &str,
+    channel: &CloudChannel,
+    payload: &Value,
+) -> Result<(Value, String), TemplateSendError> {
+    let mut message = template_message(payload)?;
+    quotas::check_message(state, session).await?;
+
+    if let Some(media) = header_media(&message) {
+        let (bytes, mimetype, filename) = header_media_bytes(state, session, &media).await?;
+        quotas::check_media_size(&state.runtime_config(), bytes.len() as u64)?;
+        let id = upload_media(
+            &state.http,
+            &state.meta,
+            channel,
+            bytes,
+            &mimetype,
+            &filename,
+        )
+        .await?;
+        set_header_media_id(&mut message, &id);
+    }
+
+    let wa_message_id = match post_message(&state.http, &state.meta, channel, &message).await {
+        Ok(id) => id,
+        Err(e) => {
+            state.message_counters.record(session, Outcome::Failed);
+            return Err(e.into());
+        }
+    };
+    state.message_counters.record(session, Outcome::Sent);
+    // The message left: a failed insert only costs its status updates.
+    if let Err(e) = record_sent(state, session, &message, &wa_message_id).await {
+        warn!(session, error = %e, "Falha ao registrar template enviado");
+    }
+    Ok((message, wa_message_id))
+}
+
+/// Stores a template sent outside the queue, so Meta's status updates and
+/// `/message/status` find it by `wa_message_id`.
+async fn record_sent(
+    state: &AppState,
+    session: &str,
+    message: &Value,
+    wa_message_id: &str,
+) -> anyhow::Result<()> {
+    let chat_id = message["to"]
+        .as_str()
+        .map(|to| format!("{to}@s.whatsapp.net"));
+    state
+        .api_store
+        .execute(
+            "INSERT INTO api_messages (session, chat_id, from_me, message_type, payload, status, \
+                wa_message_id, delivery_status, status_updated_at) \
+             VALUES ($1, $2, true, 'template', $3, 'sent', $4, 'pending', now())",
+            vec![
+                ApiBind::Text(session.to_string()),
+                ApiBind::NullableText(chat_id),
+                ApiBind::Json(message.clone()),
+                ApiBind::Text(wa_message_id.to_string()),
+            ],
+        )
+        .await?;
+    Ok(())
+}
+
+/// Event normalized from a Meta webhook notification.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InboundEvent {
+    pub phone_number_id: String,
+    pub event: &'static str,
+    pub data: Value,
+}
+
+/// Maps a Meta `whatsapp_business_account` notification to the event
+/// payloads emitted by WhatsApp Web instances.
+pub fn normalize(notification: &Value) -> Vec<InboundEvent> {
+    let mut events = Vec::new();
+    let changes = notification
+        .get("entry")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .flat_map(|entry| entry.get("changes").and_then(Value::as_array).into_iter().flatten());
+
+    for change in changes {
+        let value = &change["value"];
+        let Some(phone_number_id) = value["metadata"]["phone_number_id"].as_str() else {
+            continue;
+        };
+        let names: HashMap<&str, &str> = value["contacts"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|c| Some((c["wa_id"].as_str()?, c["profile"]["name"].as_str()?)))
+            .collect();
+
+        for message in value["messages"].as_array().into_iter().flatten() {
+            let Some(from) = message["from"].as_str() else {
+                continue;
+            };
+            let jid = format!("{from}@s.whatsapp.net");
+            let mut key = json!({
+                "remoteJid": jid,
+                "fromMe": false,
+                "MessageId": message["id"],
+                "participant": jid,
+            });
+            if let Some(name) = names.get(from) {
+                key["senderName"] = json!(name);
+            }
+            let mut item = json!({ "key": key, "message": inbound_message(message) });
+            if let Some(id) = message["context"]["id"].as_str() {
+                item["contextInfo"] = json!({ "stanzaId": id });
+            }
+            events.push(InboundEvent {
+                phone_number_id: phone_number_id.to_string(),
+                event: MessagesUpsert::EVENT,
+                data: MessagesUpsert {
+                    messages: vec![item],
+                    kind: "notify",
+                }
+                .to_data(),
+            });
+        }
+
+        for status in value["statuses"].as_array().into_iter().flatten() {
+            let Some(recipient) = status["recipient_id"].as_str() else {
+                continue;
+            };
+            let mut data = json!({
+                "key": {
+                    "remoteJid": format!("{recipient}@s.whatsapp.net"),
+                    "fromMe": true,
+                    "id": status["id"],
+                },
+                "status": status["status"].as_str().unwrap_or_default().to_uppercase(),
+            });
+            if let Some(errors) = status.get("errors") {
+                data["errors"] = errors.clone();
+            }
+            events.push(InboundEvent {
+                phone_number_id: phone_number_id.to_string(),
+                event: "MESSAGES_UPDATE",
+                data,
+            });
+        }
+    }
+    events
+}
+
+fn inbound_message(message: &Value) -> Value {
+    let kind = message["type"].as_str().unwrap_or_default();
+    match kind {
+        "text" => json!({
+            "messageType": "conversation",
+            "text": message["text"]["body"],
+        }),
+        "image" | "video" | "audio" | "document" | "sticker" => {
+            let media = &message[kind];
+            json!({
+                "messageType": format!("{kind}Message"),
+                "mediaId": media["id"],
+                "mimetype": media["mime_type"],
+                "caption": media["caption"],
+                "fileName": media["filename"],
+            })
+        }
+        "interactive" => {
+            let reply = message["interactive"]
+                .get("button_reply")
+                .or_else(|| message["interactive"].get("list_reply"))
+                .cloned()
+                .unwrap_or(Value::Null);
+            json!({
+                "messageType": "buttonsResponseMessage",
+                "selectedButtonId": reply["id"],
+                "text": reply["title"],
+            })
+        }
+        "button" => json!({
+            "messageType": "buttonsResponseMessage",
+            "selectedButtonId": message["button"]["payload"],
+            "text": message["button"]["text"],
+        }),
+        "location" => json!({
+            "messageType": "locationMessage",
+            "degreesLatitude": message["location"]["latitude"],
+            "degreesLongitude": message["location"]["longitude"],
+            "name": message["location"]["name"],
+        }),
+        "reaction" => json!({
+            "messageType": "reactionMessage",
+            "text": message["reaction"]["emoji"],
+            "reactedMessageId": message["reaction"]["message_id"],
+        }),
+        other => json!({ "messageType": other, "raw": message }),
+    }
+}
+
+/// `GET /webhook/meta`: answers Meta's subscription handshake.
+pub async fn verify_webhook(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<HashMap<String, String>>,
+) -> Response {
+    match verify_challenge(&state.meta, &query) {
+        Some(challenge) => (StatusCode::OK, challenge).into_response(),
+        None => (
+            StatusCode::FORBIDDEN,
+            Json(json!({"error": "verification_failed"})),
+        )
+            .into_response(),
+    }
+}
+
+/// `POST /webhook/meta`: inbound messages and delivery statuses.
+pub async fn receive_webhook(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    if let Some(response) = signature_rejection(&state.meta, &headers, &body) {
+        return response;
+    }
+    let Ok(notification) = serde_json::from_slice::<Value>(&body) else {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({"error": "invalid_body"})),
+        )
+            .into_response();
+    };
+
+    for inbound in normalize(&notification) {
+        let session = match session_for_phone_number(&state, &inbound.phone_number_id).await {
+            Ok(Some(session)) => session,
+            Ok(None) => {
+                debug!(phone_number_id = %inbound.phone_number_id, "Meta webhook for unknown phone number");
+                continue;
+            }
+            Err(e) => {
+                warn!(error = %e, "Cloud API session lookup failed");
+                continue;
+            }
+        };
+        webhooks::enqueue(&state, Some(&session), inbound.event, inbound.data).await;
+    }
+    // Meta retries anything other than 200, so unknown numbers are acknowledged too.
+    StatusCode::OK.into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    include!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/tests/server/cloud_api_tests.rs"));
+}
@@ -0,0 +1,384 @@
+//! Connection state machine of a WhatsApp instance.
+//!
+//! `Disconnected → Connecting → QrPending/PairingPending → Connected →
+//! LoggedOut`, plus `Errored` once the runner supervisor gives up. Every
+//! change is validated, timestamped and kept in a short history, and emits a
+//! CONNECTION_UPDATE carrying an Evolution-compatible `statusReason`.
+
+use crate::server::events::{ConnectionUpdate, EventPayload};
+use crate::server::{AppState, webhooks};
+use crate::types::events::{StreamError, StreamErrorReason};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value, json};
+use std::collections::VecDeque;
+use std::fmt;
+
+/// Transitions kept per instance.
+pub const HISTORY_LEN: usize = 20;
+
+/// Connection state of a WhatsApp instance as seen by the API.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConnectionState {
+    Disconnected,
+    /// The runner is opening the socket.
+    Connecting,
+    QrPending,
+    /// A pair code was issued and waits to be typed on the phone.
+    PairingPending,
+    Connected,
+    /// The phone removed this device; a new pairing is needed.
+    LoggedOut,
+    /// The runner crashed more often than the supervisor allows.
+    Errored,
+}
+
+impl ConnectionState {
+    /// Name used in API responses and logs.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Disconnected => "disconnected",
+            Self::Connecting => "connecting",
+            Self::QrPending => "qr_pending",
+            Self::PairingPending => "pairing_pending",
+            Self::Connected => "connected",
+            Self::LoggedOut => "logged_out",
+            Self::Errored => "errored",
+        }
+    }
+
+    /// Evolution-compatible `state` value for CONNECTION_UPDATE payloads.
+    pub fn evolution_state(self) -> &'static str {
+        match self {
+            Self::Connecting | Self::QrPending | Self::PairingPending => "connecting",
+            Self::Connected => "open",
+            Self::Disconnected | Self::LoggedOut | Self::Errored => "close",
+        }
+    }
+
+    /// Whether moving from `self` to `next` is a legal change.
+    ///
+    /// Same-state moves are not transitions; callers treat them as no-ops.
+    /// The client reconnects on its own without announcing it, so a
+    /// disconnected instance may go straight to a QR code or `Connected`.
+    pub fn can_transition_to(self, next: Self) -> bool {
+        use ConnectionState::*;
+        if self == next {
+            return false;
+        }
+        match self {
+            Disconnected => next != Disconnected,
+            Connecting => true,
+            QrPending | PairingPending => next != Connecting,
+            Connected => matches!(next, Connecting | Disconnected | LoggedOut | Errored),
+            LoggedOut => matches!(
+                next,
+                Connecting | QrPending | PairingPending | Disconnected | Errored
+            ),
+            Errored => matches!(next, Connecting | Disconnected),
+        }
+    }
+}
+
+impl fmt::Display for ConnectionState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Why a transition happened.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Reason {
+    /// The runner started (or restarted) connecting.
+    Started,
+    QrIssued,
+    PairCodeIssued,
+    Opened,
+    ConnectionLost,
+    ConnectionClosed,
+    /// Another client took over the session (`stream:error` conflict).
+    ConnectionReplaced,
+    LoggedOut,
+    /// The account was temporarily banned.
+    Forbidden,
+    RunnerCrashed,
+    /// A scheduled maintenance window closed or reopened the connection.
+    Maintenance,
+    /// The server closed the stream for too many connections or requests.
+    RateOverlimit,
+    /// The server closed the stream with a 500 or 503.
+    ServiceUnavailable,
+    /// The instance lease moved to another node, which now holds the
+    /// connection.
+    Fenced,
+}
+
+impl Reason {
+    /// Name sent as `reason` in CONNECTION_UPDATE.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Started => "started",
+            Self::QrIssued => "qrIssued",
+            Self::PairCodeIssued => "pairCodeIssued",
+            Self::Opened => "opened",
+            Self::ConnectionLost => "connectionLost",
+            Self::ConnectionClosed => "connectionClosed",
+            Self::ConnectionReplaced => "connectionReplaced",
+            Self::LoggedOut => "loggedOut",
+            Self::Forbidden => "forbidden",
+            Self::RunnerCrashed => "runnerCrashed",
+            Self::Maintenance => "maintenance",
+            Self::RateOverlimit => "rateOverlimit",
+            Self::ServiceUnavailable => "serviceUnavailable",
+            Self::Fenced => "fenced",
+        }
+    }
+
+    /// Evolution/Baileys `statusReason` (`DisconnectReason` codes; 200 when
+    /// nothing went wrong).
+    pub fn status_code(self) -> u16 {
+        match self {
+            Self::Started | Self::QrIssued | Self::PairCodeIssued | Self::Opened => 200,
+            Self::LoggedOut => 401,
+            Self::Forbidden => 403,
+            Self::ConnectionLost => 408,
+            Self::ConnectionClosed | Self::Maintenance => 428,
+            Self::RateOverlimit => 429,
+            Self::ConnectionReplaced | Self::Fenced => 440,
+            Self::RunnerCrashed => 500,
+            Self::ServiceUnavailable => 503,
+        }
+    }
+}
+
+impl fmt::Display for Reason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// One recorded state change.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransitionRecord {
+    pub from: ConnectionState,
+    pub to: ConnectionState,
+    pub reason: Reason,
+    pub at: DateTime<Utc>,
+}
+
+/// Latest `stream:error` of an instance and what it takes to recover.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LastError {
+    pub reason: StreamErrorReason,
+    pub code: String,
+    /// Whether the client reconnects on its own.
+    pub reconnecting: bool,
+    /// Whether the instance must be paired again.
+    pub requires_pairing: bool,
+    pub at: DateTime<Utc>,
+}
+
+impl LastError {
+    pub fn from_stream_error(error: &StreamError, at: DateTime<Utc>) -> Self {
+        Self {
+            reason: error.reason,
+            code: error.close_code(),
+            reconnecting: error.reason.should_reconnect(),
+            requires_pairing: error.reason.requires_pairing(),
+            at,
+        }
+    }
+}
+
+/// Outcome of applying a state change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transition {
+    Changed {
+        previous: ConnectionState,
+        current: ConnectionState,
+    },
+    Unchanged(ConnectionState),
+    Invalid {
+        current: ConnectionState,
+        requested: ConnectionState,
+    },
+}
+
+/// Current state of an instance, when it was entered, the latest
+/// [`HISTORY_LEN`] transitions (oldest first) and the last server error.
+#[derive(Debug, Clone)]
+pub struct ConnectionStatus {
+    state: ConnectionState,
+    since: DateTime<Utc>,
+    history: VecDeque<TransitionRecord>,
+    last_error: Option<LastError>,
+}
+
+impl Default for ConnectionStatus {
+    fn default() -> Self {
+        Self {
+            state: ConnectionState::Disconnected,
+            since: Utc::now(),
+            history: VecDeque::with_capacity(HISTORY_LEN),
+            last_error: None,
+        }
+    }
+}
+
+impl ConnectionStatus {
+    pub fn state(&self) -> ConnectionState {
+        self.state
+    }
+
+    /// When the current state was entered.
+    pub fn since(&self) -> DateTime<Utc> {
+        self.since
+    }
+
+    pub fn history(&self) -> impl Iterator<Item = &TransitionRecord> {
+        self.history.iter()
+    }
+
+    /// Kept across reconnects until the next error replaces it.
+    pub fn last_error(&self) -> Option<&LastError> {
+        self.last_error.as_ref()
+    }
+
+    pub fn record_error(&mut self, error: LastError) {
+        self.last_error = Some(error);
+    }
+
+    /// Moves to `next` at `at` if the state machine allows it.
+    pub fn transition(
+        &mut self,
+        next: ConnectionState,
+        reason: Reason,
+        at: DateTime<Utc>,
+    ) -> Transition {
+        let previous = self.state;
+        if previous == next {
+            return Transition::Unchanged(previous);
+        }
+        if !previous.can_transition_to(next) {
+            return Transition::Invalid {
+                current: previous,
+                requested: next,
+            };
+        }
+        self.state = next;
+        self.since = at;
+        if self.history.len() == HISTORY_LEN {
+            self.history.pop_front();
+        }
+        self.history.push_back(TransitionRecord {
+            from: previous,
+            to: next,
+            reason,
+            at,
+        });
+        Transition::Changed {
+            previous,
+            current: next,
+        }
+    }
+
+    /// `{state, since, transitions, lastError}` for status endpoints.
+    pub fn to_json(&self) -> Value {
+        json!({
+            "state": self.state,
+            "since": self.since,
+            "transitions": self.history,
+            "lastError": self.last_error,
+        })
+    }
+}
+
+/// CONNECTION_UPDATE payload of a change; `extra` fields are merged in.
+pub fn update_payload(
+    previous: ConnectionState,
+    current: ConnectionState,
+    reason: Reason,
+    at: DateTime<Utc>,
+    extra: Value,
+) -> Value {
+    ConnectionUpdate {
+        action: "update",
+        state: current.evolution_state(),
+        previous_state: previous.evolution_state(),
+        connection_state: current,
+        reason,
+        status_reason: reason.status_code(),
+        at,
+        extra: match extra {
+            Value::Object(fields) => fields,
+            _ => Map::new(),
+        },
+    }
+    .to_data()
+}
+
+/// Moves `instance_name` to `next` and emits CONNECTION_UPDATE only when the
+/// state really changed. `extra` fields (e.g. `restarts`) are merged into the
+/// event payload.
+pub async fn update_connection_state(
+    state: &AppState,
+    instance_name: &str,
+    next: ConnectionState,
+    reason: Reason,
+    extra: Value,
+) -> Transition {
+    let at = Utc::now();
+    let transition = {
+        let Some(instance) = state.instances.get(instance_name) else {
+            log::warn!(
+                "Connection state change to {} for unknown instance {}",
+                next,
+                instance_name
+            );
+            return Transition::Invalid {
+                current: ConnectionState::Disconnected,
+                requested: next,
+            };
+        };
+        let mut status = instance.connection_state.write().await;
+        status.transition(next, reason, at)
+    };
+
+    match transition {
+        Transition::Changed { previous, current } => {
+            #[cfg(feature = "sentry")]
+            state
+                .sentry_hubs
+                .state_changed(instance_name, previous, current, reason);
+            let payload = update_payload(previous, current, reason, at, extra);
+            webhooks::enqueue(state, Some(instance_name), "CONNECTION_UPDATE", payload).await;
+        }
+        Transition::Unchanged(current) => {
+            log::debug!(
+                "Instance {} already {}, skipping CONNECTION_UPDATE",
+                instance_name,
+                current
+            );
+        }
+        Transition::Invalid { current, requested } => {
+            log::warn!(
+                "Invalid connection transition for instance {}: {} -> {} ({})",
+                instance_name,
+                current,
+                requested,
+                reason
+            );
+        }
+    }
+
+    transition
+}
+
+#[cfg(test)]
+mod tests {
+    include!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/tests/server/connection_tests.rs"));
+}
@@ -0,0 +1,311 @@
+//! `/chat/syncContacts`: phone numbers resolved into stored contacts.
+//!
+//! Numbers come from the body (`numbers`), an inline CSV (`csv`) or a CSV
+//! sent to `/media/upload` (`mediaId`). They are looked up in usync batches
+//! for the account JID, LID, about text and business flag, then for the
+//! devices of those on WhatsApp, and every answer is written to
+//! `api_contacts` and the `/chat/whatsappNumbers` cache. A sync longer than
+//! one batch runs in the background and reports each batch with a
+//! `CONTACTS_SYNC_PROGRESS` event.
+
+use crate::api_store::ApiBind;
+use crate::client::Client;
+use crate::features::ContactInfo;
+use crate::server::AppState;
+use crate::server::events::{self, ContactsSyncProgress};
+use crate::server::numbers::{self, NumberStatus};
+use crate::server::uploads::{self, UploadError};
+use serde::Serialize;
+use serde_json::{Value, json};
+use std::collections::HashSet;
+use thiserror::Error;
+use tracing::{debug, warn};
+use warp_core_binary::jid::Jid;
+
+/// Numbers accepted per sync.
+pub const MAX_CONTACTS: usize = 5000;
+/// Numbers sent in a single usync query; longer syncs run in the background.
+pub const BATCH_SIZE: usize = 50;
+/// Largest uploaded CSV read.
+const MAX_CSV_BYTES: u64 = 2 * 1024 * 1024;
+/// Header names of the phone column, lowercase.
+const PHONE_HEADERS: &[&str] = &[
+    "phone", "number", "mobile", "whatsapp", "telefone", "numero", "número", "celular",
+];
+
+#[derive(Debug, Error)]
+pub enum ContactSyncError {
+    #[error("numbers, csv or mediaId is required")]
+    Missing,
+    #[error("numbers must be an array of strings")]
+    NotStrings,
+    #[error("at most {MAX_CONTACTS} numbers per sync")]
+    TooMany,
+    #[error("invalid number {0:?}")]
+    Invalid(String),
+    #[error("the uploaded CSV is larger than {MAX_CSV_BYTES} bytes or not UTF-8")]
+    UnreadableCsv,
+    #[error(transparent)]
+    Upload(#[from] UploadError),
+}
+
+/// What usync knows about a number.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncedContact {
+    /// Number as digits, the way it was asked.
+    pub number: String,
+    pub exists: bool,
+    pub jid: String,
+    pub lid: Option<String>,
+    pub is_business: bool,
+    /// About text, when visible.
+    pub status: Option<String>,
+    /// Device ids, `0` being the phone.
+    pub devices: Vec<u16>,
+}
+
+/// Digits of the phone column of `csv`, in file order. The column is the
+/// one with a phone-like header or, without a header, the first cell of
+/// the first row holding digits. Cells are split on `,` or, when the first
+/// line has no comma, on `;`.
+pub fn numbers_from_csv(csv: &str) -> Vec<String> {
+    let first = csv
+        .lines()
+        .find(|line| !line.trim().is_empty())
+        .unwrap_or("");
+    let delimiter = if !first.contains(',') && first.contains(';') {
+        ';'
+    } else {
+        ','
+    };
+    let mut rows = csv
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            line.split(delimiter)
+                .map(|cell| cell.trim().trim_matches('"').trim())
+                .collect::<Vec<_>>()
+        })
+        .peekable();
+
+    let Some(first) = rows.peek() else {
+        return Vec::new();
+    };
+    let column = if first.iter().any(|cell| numbers::digits(cell).is_some()) {
+        first
+            .iter()
+            .position(|cell| numbers::digits(cell).is_some())
+            .unwrap_or(0)
+    } else {
+        let column = first
+            .iter()
+            .position(|cell| PHONE_HEADERS.contains(&cell.to_lowercase().as_str()))
+            .unwrap_or(0);
+        rows.next();
+        column
+    };
+
+    rows.filter_map(|row| row.get(column).and_then(|cell| numbers::digits(cell)))
+        .collect()
+}
+
+/// Numbers of the body's `numbers` followed by those of every file of
+/// `csvs`, as digits and without duplicates.
+pub fn collect_numbers(body: &Value, csvs: &[String]) -> Result<Vec<String>, ContactSyncError> {
+    let mut numbers = Vec::new();
+    match body.get("numbers") {
+        None | Some(Value::Null) => {}
+        Some(Value::Array(entries)) => {
+            for entry in entries {
+                let raw = entry.as_str().ok_or(ContactSyncError::NotStrings)?;
+                numbers.push(
+                    numbers::digits(raw)
+                        .ok_or_else(|| ContactSyncError::Invalid(raw.to_string()))?,
+                );
+            }
+        }
+        Some(_) => return Err(ContactSyncError::NotStrings),
+    }
+    for csv in csvs {
+        numbers.extend(numbers_from_csv(csv));
+    }
+
+    let mut seen = HashSet::new();
+    numbers.retain(|number| seen.insert(number.clone()));
+    if numbers.is_empty() {
+        return Err(ContactSyncError::Missing);
+    }
+    if numbers.len() > MAX_CONTACTS {
+        return Err(ContactSyncError::TooMany);
+    }
+    Ok(numbers)
+}
+
+/// Numbers to sync for `session`, reading the uploaded CSV of `mediaId`.
+pub async fn numbers_from_request(
+    state: &AppState,
+    session: &str,
+    body: &Value,
+) -> Result<Vec<String>, ContactSyncError> {
+    let mut csvs: Vec<String> = body["csv"]
+        .as_str()
+        .map(str::to_string)
+        .into_iter()
+        .collect();
+    if let Some(media_id) = body["mediaId"].as_str() {
+        let (upload, path) = uploads::find(&state.uploads, session, media_id).await?;
+        if upload.size > MAX_CSV_BYTES {
+            return Err(ContactSyncError::UnreadableCsv);
+        }
+        let bytes = tokio::fs::read(&path).await.map_err(UploadError::from)?;
+        csvs.push(String::from_utf8(bytes).map_err(|_| ContactSyncError::UnreadableCsv)?);
+    }
+    collect_numbers(body, &csvs)
+}
+
+/// Pairs usync answers with the asked numbers. Numbers without an answer
+/// do not exist; `devices` are matched by the account JID or LID.
+pub fn match_contacts(
+    numbers: &[String],
+    infos: &[ContactInfo],
+    devices: &[Jid],
+) -> Vec<SyncedContact> {
+    numbers
+        .iter()
+        .map(|number| {
+            let Some(info) = infos.iter().find(|info| info.jid.user_base() == number) else {
+                return SyncedContact {
+                    number: number.clone(),
+                    exists: false,
+                    jid: Jid::pn(number.as_str()).to_string(),
+                    lid: None,
+                    is_business: false,
+                    status: None,
+                    devices: Vec::new(),
+                };
+            };
+            let mut device_ids: Vec<u16> = devices
+                .iter()
+                .filter(|device| {
+                    device.user_base() == info.jid.user_base()
+                        || info
+                            .lid
+                            .as_ref()
+                            .is_some_and(|lid| device.user_base() == lid.user_base())
+                })
+                .map(|device| device.device)
+                .collect();
+            device_ids.sort_unstable();
+            device_ids.dedup();
+            SyncedContact {
+                number: number.clone(),
+                exists: info.is_registered,
+                jid: info.jid.to_non_ad().to_string(),
+                lid: info.lid.as_ref().map(|lid| lid.to_non_ad().to_string()),
+                is_business: info.is_business,
+                status: info.status.clone(),
+                devices: device_ids,
+            }
+        })
+        .collect()
+}
+
+async fn sync_batch(client: &Client, batch: &[String]) -> anyhow::Result<Vec<SyncedContact>> {
+    let phones: Vec<&str> = batch.iter().map(String::as_str).collect();
+    let infos = client.contacts().get_info(&phones).await?;
+    let registered: Vec<Jid> = infos
+        .iter()
+        .filter(|info| info.is_registered)
+        .map(|info| info.jid.to_non_ad())
+        .collect();
+    // Devices are a bonus: a failed lookup still stores the contacts.
+    let devices = if registered.is_empty() {
+        Vec::new()
+    } else {
+        client
+            .get_user_devices(&registered)
+            .await
+            .unwrap_or_else(|e| {
+                debug!(error = %e, "Falha ao consultar dispositivos dos contatos");
+                Vec::new()
+            })
+    };
+    Ok(match_contacts(batch, &infos, &devices))
+}
+
+async fn store(state: &AppState, session: &str, contacts: &[SyncedContact]) {
+    for contact in contacts {
+        state.number_cache.insert(
+            session,
+            &NumberStatus {
+                exists: contact.exists,
+                jid: contact.jid.clone(),
+                number: contact.number.clone(),
+            },
+        );
+        if let Err(e) = state
+            .api_store
+            .execute(
+                "INSERT INTO api_contacts (session, id, exists, lid, is_business, status, devices, synced_at, updated_at) \
+                 VALUES ($1, $2, $3, $4, $5, $6, $7, now(), now()) \
+                 ON CONFLICT (session, id) DO UPDATE \
+                    SET exists = EXCLUDED.exists, lid = EXCLUDED.lid, \
+                        is_business = EXCLUDED.is_business, status = EXCLUDED.status, \
+                        devices = EXCLUDED.devices, synced_at = now(), updated_at = now()",
+                vec![
+                    ApiBind::Text(session.to_string()),
+                    ApiBind::Text(contact.jid.clone()),
+                    ApiBind::Bool(contact.exists),
+                    ApiBind::NullableText(contact.lid.clone()),
+                    ApiBind::Bool(contact.is_business),
+                    ApiBind::NullableText(contact.status.clone()),
+                    ApiBind::Json(json!(contact.devices)),
+                ],
+            )
+            .await
+        {
+            debug!(session, error = %e, "Falha ao salvar contato sincronizado");
+        }
+    }
+}
+
+/// Syncs `numbers` batch by batch. With a `sync_id`, every batch and a
+/// failure are reported as `CONTACTS_SYNC_PROGRESS`.
+pub async fn run(
+    state: &AppState,
+    client: &Client,
+    session: &str,
+    numbers: &[String],
+    sync_id: Option<&str>,
+) -> anyhow::Result<Vec<SyncedContact>> {
+    let mut synced: Vec<SyncedContact> = Vec::with_capacity(numbers.len());
+    for batch in numbers.chunks(BATCH_SIZE) {
+        let result = sync_batch(client, batch).await;
+        if let Ok(contacts) = &result {
+            store(state, session, contacts).await;
+            synced.extend(contacts.iter().cloned());
+        }
+        if let Some(sync_id) = sync_id {
+            let progress = ContactsSyncProgress {
+                sync_id: sync_id.to_string(),
+                processed: synced.len(),
+                total: numbers.len(),
+                found: synced.iter().filter(|contact| contact.exists).count(),
+                done: result.is_err() || synced.len() == numbers.len(),
+                error: result.as_ref().err().map(ToString::to_string),
+            };
+            events::emit(state, Some(session), &progress).await;
+        }
+        if let Err(e) = result {
+            warn!(session, error = %e, "Sincronização de contatos interrompida");
+            return Err(e);
+        }
+    }
+    Ok(synced)
+}
+
+#[cfg(test)]
+mod tests {
+    include!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/tests/server/contact_sync_tests.rs"));
+}
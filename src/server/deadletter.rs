@@ -0,0 +1,100 @@
+//! Dead-letter store for events whose webhook delivery exhausted every retry.
+//!
+//! `WebhookQueue::mark_retry` copies the outbox row into `event_deadletters`
+//! when it gives up. Replaying feeds the stored envelopes back through
+//! `webhooks::enqueue`, so they reach the WebSocket hub, NATS and the
+//! instance's current webhook targets like a fresh event.
+
+use crate::api_store::ApiBind;
+use crate::server::AppState;
+use crate::server::webhooks;
+use serde_json::Value;
+use uuid::Uuid;
+
+pub const DEFAULT_LIMIT: i32 = 50;
+pub const MAX_LIMIT: i32 = 500;
+
+/// Dead-lettered events of an instance, newest first. Replayed entries are
+/// left out unless `include_replayed` is set.
+pub async fn list(
+    state: &AppState,
+    session: &str,
+    include_replayed: bool,
+    limit: i32,
+) -> anyhow::Result<Vec<Value>> {
+    state
+        .api_store
+        .query_json(
+            "SELECT row_to_json(t)::jsonb as value FROM ( \
+                SELECT id, outbox_id, event, payload, reason, attempts, created_at, replayed_at \
+                FROM event_deadletters \
+                WHERE session = $1 AND ($2 OR replayed_at IS NULL) \
+                ORDER BY created_at DESC \
+                LIMIT $3 \
+            ) t",
+            vec![
+                ApiBind::Text(session.to_string()),
+                ApiBind::Bool(include_replayed),
+                ApiBind::Int(limit),
+            ],
+        )
+        .await
+}
+
+/// Ids selected by a replay body: `None` replays everything pending.
+/// Accepts a missing/null body, or `{"ids": [...]}` with UUID strings.
+pub fn ids_from_body(body: Option<&Value>) -> Result<Option<Vec<Uuid>>, String> {
+    let Some(ids) = body.and_then(|b| b.get("ids")).filter(|v| !v.is_null()) else {
+        return Ok(None);
+    };
+    let ids = ids.as_array().ok_or_else(|| "ids must be an array".to_string())?;
+    ids.iter()
+        .map(|id| {
+            id.as_str()
+                .and_then(|s| Uuid::parse_str(s).ok())
+                .ok_or_else(|| format!("invalid id: {id}"))
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map(Some)
+}
+
+/// Marks the pending entries (all, or just `ids`) as replayed and enqueues
+/// their envelopes again. Returns the replayed dead-letter ids.
+pub async fn replay(
+    state: &AppState,
+    session: &str,
+    ids: Option<Vec<Uuid>>,
+) -> anyhow::Result<Vec<Uuid>> {
+    let ids = ids.map(|ids| Value::from(ids.iter().map(Uuid::to_string).collect::<Vec<_>>()));
+    let rows = state
+        .api_store
+        .query_json(
+            "WITH replayed AS ( \
+                UPDATE event_deadletters SET replayed_at = now() \
+                WHERE session = $1 AND replayed_at IS NULL \
+                  AND ($2::jsonb IS NULL OR id::text IN (SELECT jsonb_array_elements_text($2::jsonb))) \
+                RETURNING id, event, payload, created_at \
+            ) SELECT jsonb_build_object('id', id, 'event', event, 'payload', payload) as value \
+              FROM replayed ORDER BY created_at",
+            vec![ApiBind::Text(session.to_string()), ApiBind::NullableJson(ids)],
+        )
+        .await?;
+
+    let mut replayed = Vec::with_capacity(rows.len());
+    for row in rows {
+        let Some(event) = row["event"].as_str() else {
+            continue;
+        };
+        let data = row["payload"].get("data").cloned().unwrap_or(Value::Null);
+        webhooks::enqueue(state, Some(session), event, data).await;
+        if let Some(id) = row["id"].as_str().and_then(|id| Uuid::parse_str(id).ok()) {
+            replayed.push(id);
+        }
+    }
+    Ok(replayed)
+}
+
+#[cfg(test)]
+mod tests {
+    include!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/tests/server/deadletter_tests.rs"));
+}
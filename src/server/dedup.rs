@@ -0,0 +1,188 @@
+//! Inbound message deduplication.
+//!
+//! WhatsApp redelivers messages it did not see acknowledged, typically
+//! after a reconnect, and each copy used to become another
+//! `MESSAGES_UPSERT`. [`claim`] remembers every
+//! `(instance, remoteJid, messageId)` for `INBOUND_DEDUP_TTL_SECONDS`, in
+//! memory and in `api_inbound_dedup` so restarts and other replicas agree,
+//! and the pipeline drops the copies before they reach any sink. Dropped
+//! copies are counted per instance for `/metrics` and `/metrics/prometheus`.
+
+use crate::api_store::ApiBind;
+use crate::server::AppState;
+use crate::server::message_counters::label;
+use moka::future::Cache;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+
+pub use crate::config::DedupConfig;
+
+/// How often expired rows are deleted from `api_inbound_dedup`.
+const SWEEP_EVERY: Duration = Duration::from_secs(15 * 60);
+
+/// Message keys seen recently, with the duplicates dropped per instance.
+pub struct InboundDedup {
+    config: DedupConfig,
+    seen: Cache<(String, String, String), ()>,
+    hits: Mutex<HashMap<String, u64>>,
+}
+
+impl Default for InboundDedup {
+    fn default() -> Self {
+        Self::new(DedupConfig::default())
+    }
+}
+
+impl InboundDedup {
+    pub fn new(config: DedupConfig) -> Self {
+        Self {
+            config,
+            seen: Cache::builder()
+                .max_capacity(config.cache_size)
+                .time_to_live(config.ttl)
+                .build(),
+            hits: Mutex::default(),
+        }
+    }
+
+    pub fn config(&self) -> DedupConfig {
+        self.config
+    }
+
+    /// Whether this process has not seen the key within the TTL; marks it
+    /// seen either way.
+    pub async fn claim_local(&self, instance: &str, remote_jid: &str, message_id: &str) -> bool {
+        self.seen
+            .entry((
+                instance.to_string(),
+                remote_jid.to_string(),
+                message_id.to_string(),
+            ))
+            .or_insert(())
+            .await
+            .is_fresh()
+    }
+
+    pub fn record_hit(&self, instance: &str) {
+        *self
+            .hits
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry(instance.to_string())
+            .or_default() += 1;
+    }
+
+    /// Duplicates dropped per instance since start, by instance name.
+    pub fn hits(&self) -> Vec<(String, u64)> {
+        let mut hits: Vec<_> = self
+            .hits
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .map(|(instance, hits)| (instance.clone(), *hits))
+            .collect();
+        hits.sort();
+        hits
+    }
+}
+
+/// Whether a received message is the first copy of its key. Duplicates are
+/// counted. The store settles keys this process has not seen; when it
+/// cannot answer, the message goes through rather than risk losing it.
+pub async fn claim(state: &AppState, instance: &str, remote_jid: &str, message_id: &str) -> bool {
+    let dedup = &state.inbound_dedup;
+    if !dedup.config.enabled || message_id.is_empty() {
+        return true;
+    }
+    let first = dedup.claim_local(instance, remote_jid, message_id).await
+        && match claim_stored(state, instance, remote_jid, message_id).await {
+            Ok(first) => first,
+            Err(e) => {
+                tracing::debug!(instance, error = %e, "Deduplicação no banco indisponível");
+                true
+            }
+        };
+    if !first {
+        dedup.record_hit(instance);
+        tracing::debug!(
+            instance,
+            remote_jid,
+            message_id,
+            "Mensagem repetida descartada"
+        );
+    }
+    first
+}
+
+/// Inserts the key, or takes over a row older than the TTL. A row still
+/// within the TTL affects nothing, so zero rows means a duplicate.
+async fn claim_stored(
+    state: &AppState,
+    instance: &str,
+    remote_jid: &str,
+    message_id: &str,
+) -> anyhow::Result<bool> {
+    let claimed = state
+        .api_store
+        .execute(
+            "INSERT INTO api_inbound_dedup (session, remote_jid, message_id) VALUES ($1, $2, $3) \
+             ON CONFLICT (session, remote_jid, message_id) DO UPDATE SET seen_at = now() \
+             WHERE api_inbound_dedup.seen_at < now() - make_interval(secs => $4)",
+            vec![
+                ApiBind::Text(instance.to_string()),
+                ApiBind::Text(remote_jid.to_string()),
+                ApiBind::Text(message_id.to_string()),
+                ApiBind::Int(ttl_secs(&state.inbound_dedup.config)),
+            ],
+        )
+        .await?;
+    Ok(claimed > 0)
+}
+
+fn ttl_secs(config: &DedupConfig) -> i32 {
+    i32::try_from(config.ttl.as_secs()).unwrap_or(i32::MAX)
+}
+
+/// Deletes expired keys from `api_inbound_dedup` every few minutes.
+pub fn spawn_sweeper(state: Arc<AppState>) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(SWEEP_EVERY).await;
+            if !state.inbound_dedup.config.enabled {
+                continue;
+            }
+            match state
+                .api_store
+                .execute(
+                    "DELETE FROM api_inbound_dedup WHERE seen_at < now() - make_interval(secs => $1)",
+                    vec![ApiBind::Int(ttl_secs(&state.inbound_dedup.config))],
+                )
+                .await
+            {
+                Ok(0) => {}
+                Ok(removed) => tracing::debug!(removed, "Chaves de deduplicação expiradas removidas"),
+                Err(e) => tracing::warn!(error = %e, "Falha ao limpar chaves de deduplicação"),
+            }
+        }
+    })
+}
+
+/// Prometheus text exposition of `hits`.
+pub fn render_prometheus(hits: &[(String, u64)]) -> String {
+    let name = "chatwarp_inbound_duplicates_total";
+    let mut out = format!(
+        "# HELP {name} Redelivered inbound messages dropped before reaching the sinks.\n\
+         # TYPE {name} counter\n"
+    );
+    for (instance, hits) in hits {
+        let _ = writeln!(out, "{name}{{instance=\"{}\"}} {hits}", label(instance));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    include!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/tests/server/dedup_tests.rs"));
+}
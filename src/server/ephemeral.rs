@@ -0,0 +1,144 @@
+//! Disappearing messages in direct chats.
+//!
+//! The timer set through `/chat/toggleEphemeral` is kept in `api_chats`.
+//! Messages the worker sends to such a chat carry it in their `contextInfo`
+//! (`expiration` and `ephemeralSettingTimestamp`), as the official clients
+//! do, otherwise the contact's phone keeps them forever. Their
+//! `api_messages` row gets an `expires_at` once sent.
+
+use crate::api_store::ApiBind;
+use crate::server::AppState;
+use serde_json::Value;
+use uuid::Uuid;
+use waproto::whatsapp as wa;
+use warp_core::proto_helpers::MessageExt;
+
+/// Disappearing messages timer of a chat.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EphemeralTimer {
+    /// Seconds a message lives.
+    pub expiration: u32,
+    /// When the timer was set, in seconds.
+    pub setting_timestamp: Option<i64>,
+}
+
+/// Active timer of `chat`, `None` when disappearing messages are off.
+pub async fn load(
+    state: &AppState,
+    session: &str,
+    chat: &str,
+) -> anyhow::Result<Option<EphemeralTimer>> {
+    let rows = state
+        .api_store
+        .query_json(
+            "SELECT jsonb_build_object('expiration', ephemeral_expiration, 'settingAt', ephemeral_setting_at) as value \
+             FROM api_chats WHERE session = $1 AND id = $2 AND ephemeral_expiration > 0",
+            vec![
+                ApiBind::Text(session.to_string()),
+                ApiBind::Text(chat.to_string()),
+            ],
+        )
+        .await?;
+    Ok(rows.first().and_then(timer_from_row))
+}
+
+fn timer_from_row(row: &Value) -> Option<EphemeralTimer> {
+    let value = row.get("value").unwrap_or(row);
+    let expiration = value
+        .get("expiration")
+        .and_then(Value::as_u64)
+        .and_then(|v| u32::try_from(v).ok())
+        .filter(|v| *v > 0)?;
+    Some(EphemeralTimer {
+        expiration,
+        setting_timestamp: value.get("settingAt").and_then(Value::as_i64),
+    })
+}
+
+/// Stamps `timer` on the content of `msg`. A plain `conversation` has no
+/// `contextInfo`, so it becomes an extended text message.
+pub fn apply(msg: &mut wa::Message, timer: EphemeralTimer) {
+    if let Some(text) = msg.conversation.take() {
+        msg.extended_text_message = Some(Box::new(wa::message::ExtendedTextMessage {
+            text: Some(text),
+            ..Default::default()
+        }));
+    }
+    if let Some(context_info) = context_info_mut(msg) {
+        let context_info = context_info.get_or_insert_with(Box::default);
+        context_info.expiration = Some(timer.expiration);
+        context_info.ephemeral_setting_timestamp = timer.setting_timestamp;
+    }
+}
+
+/// Timer carried by a message, as set by [`apply`] or by the sender's
+/// client; `ephemeralMessage` and other wrappers are looked through.
+pub fn expiration(msg: &wa::Message) -> Option<u32> {
+    let msg = msg.get_base_message();
+    let context_info = if let Some(m) = &msg.extended_text_message {
+        &m.context_info
+    } else if let Some(m) = &msg.image_message {
+        &m.context_info
+    } else if let Some(m) = &msg.video_message {
+        &m.context_info
+    } else if let Some(m) = &msg.audio_message {
+        &m.context_info
+    } else if let Some(m) = &msg.document_message {
+        &m.context_info
+    } else if let Some(m) = &msg.sticker_message {
+        &m.context_info
+    } else if let Some(m) = &msg.product_message {
+        &m.context_info
+    } else if let Some(m) = &msg.template_message {
+        &m.context_info
+    } else {
+        return None;
+    };
+    context_info
+        .as_ref()
+        .and_then(|info| info.expiration)
+        .filter(|expiration| *expiration > 0)
+}
+
+/// `contextInfo` slot of the content kinds the worker sends.
+fn context_info_mut(msg: &mut wa::Message) -> Option<&mut Option<Box<wa::ContextInfo>>> {
+    if let Some(m) = msg.extended_text_message.as_mut() {
+        Some(&mut m.context_info)
+    } else if let Some(m) = msg.image_message.as_mut() {
+        Some(&mut m.context_info)
+    } else if let Some(m) = msg.video_message.as_mut() {
+        Some(&mut m.context_info)
+    } else if let Some(m) = msg.audio_message.as_mut() {
+        Some(&mut m.context_info)
+    } else if let Some(m) = msg.document_message.as_mut() {
+        Some(&mut m.context_info)
+    } else if let Some(m) = msg.sticker_message.as_mut() {
+        Some(&mut m.context_info)
+    } else if let Some(m) = msg.product_message.as_mut() {
+        Some(&mut m.context_info)
+    } else if let Some(m) = msg.template_message.as_mut() {
+        Some(&mut m.context_info)
+    } else {
+        None
+    }
+}
+
+/// Sets `expires_at` of the message row `expiration` seconds from now.
+pub async fn mark_expiry(state: &AppState, id: Uuid, expiration: u32) -> anyhow::Result<()> {
+    state
+        .api_store
+        .execute(
+            "UPDATE api_messages SET expires_at = now() + make_interval(secs => $1) WHERE id = $2",
+            vec![
+                ApiBind::Int(i32::try_from(expiration).unwrap_or(i32::MAX)),
+                ApiBind::Uuid(id),
+            ],
+        )
+        .await
+        .map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    include!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/tests/server/ephemeral_tests.rs"));
+}
@@ -0,0 +1,234 @@
+//! Sentry error reporting (`sentry` feature).
+//!
+//! Every instance has its own hub, forked from the main one, whose scope is
+//! tagged with the instance name, the integration and, once a connection
+//! resolved it, the WA web version. The hubs live in [`InstanceHubs`], held by
+//! `AppState`. The supervisor runs the instance runner
+//! bound to that hub, so errors logged inside it are attributed without
+//! passing the name around, and connection state transitions and handshake
+//! phases are recorded there as breadcrumbs. Panics the supervisor recovers
+//! from are captured as warnings; only giving up is an error.
+//!
+//! Reconnect loops log the same error over and over, so [`EventFilter`]
+//! drops events matching `SENTRY_IGNORE` and sends a given message at most
+//! once per `SENTRY_THROTTLE_SECS` for each instance.
+
+use crate::client::HandshakePhase;
+use crate::server::cloud_api;
+use crate::server::connection::{ConnectionState, Reason};
+use dashmap::DashMap;
+use sentry::integrations::tracing::EventFilter as TracingFilter;
+use sentry::protocol::{Breadcrumb, Event, Level, Value};
+use sentry::{Hub, SentryFuture, SentryFutureExt};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+pub use crate::config::SentryConfig;
+
+/// Throttle entries kept before expired ones are pruned.
+const MAX_THROTTLE_ENTRIES: usize = 1024;
+/// The supervisor captures runner panics itself, at the right level.
+const SUPERVISOR_TARGET: &str = "chatwarp_api::server::supervisor";
+
+/// Starts the Sentry client. Keep the guard alive for the whole process;
+/// dropping it flushes pending events.
+pub fn init(config: SentryConfig) -> sentry::ClientInitGuard {
+    let filter = EventFilter::new(config.ignore, config.throttle);
+    sentry::init(sentry::ClientOptions {
+        dsn: Some(config.dsn),
+        release: sentry::release_name!(),
+        environment: config.environment.map(Into::into),
+        sample_rate: config.sample_rate,
+        before_send: Some(Arc::new(move |event| filter.check(event))),
+        ..Default::default()
+    })
+}
+
+/// Tracing layer sending `error!` events to the current hub and keeping
+/// `warn!`/`info!` as breadcrumbs. Inert until [`init`] runs.
+pub fn layer<S>() -> sentry::integrations::tracing::SentryLayer<S>
+where
+    S: tracing::Subscriber + for<'a> tracing_subscriber::registry::LookupSpan<'a>,
+{
+    sentry::integrations::tracing::layer().event_filter(|metadata| match *metadata.level() {
+        tracing::Level::ERROR if metadata.target() != SUPERVISOR_TARGET => TracingFilter::Event,
+        tracing::Level::ERROR | tracing::Level::WARN | tracing::Level::INFO => {
+            TracingFilter::Breadcrumb
+        }
+        _ => TracingFilter::Ignore,
+    })
+}
+
+/// Hub of every instance, created and tagged on first use.
+#[derive(Default)]
+pub struct InstanceHubs {
+    hubs: DashMap<String, Arc<Hub>>,
+}
+
+impl InstanceHubs {
+    /// The hub of `instance`.
+    pub fn hub(&self, instance: &str) -> Arc<Hub> {
+        self.hubs
+            .entry(instance.to_string())
+            .or_insert_with(|| {
+                let hub = Arc::new(Hub::new_from_top(Hub::main()));
+                hub.configure_scope(|scope| {
+                    scope.set_tag("instance", instance);
+                    scope.set_tag("integration", cloud_api::DEFAULT_INTEGRATION);
+                });
+                hub
+            })
+            .clone()
+    }
+
+    /// Drops the hub of `instance`, so a deleted instance keeps nothing and
+    /// one re-created under the same name starts from a fresh scope.
+    pub fn remove(&self, instance: &str) {
+        self.hubs.remove(instance);
+    }
+
+    /// Runs `future` with the hub of `instance` as the current one.
+    pub fn bind<F: Future>(&self, instance: &str, future: F) -> SentryFuture<F> {
+        future.bind_hub(self.hub(instance))
+    }
+
+    /// Breadcrumb for a connection state change of `instance`.
+    pub fn state_changed(
+        &self,
+        instance: &str,
+        previous: ConnectionState,
+        current: ConnectionState,
+        reason: Reason,
+    ) {
+        let level = match current {
+            ConnectionState::Errored | ConnectionState::LoggedOut => Level::Warning,
+            _ => Level::Info,
+        };
+        self.hub(instance).add_breadcrumb(Breadcrumb {
+            category: Some("connection".into()),
+            message: Some(format!("{previous} -> {current}")),
+            level,
+            data: [("reason".to_string(), Value::from(reason.as_str()))]
+                .into_iter()
+                .collect(),
+            ..Default::default()
+        });
+    }
+
+    /// Reports a runner panic: a warning when the supervisor restarts it, an
+    /// error once it gives up.
+    pub fn runner_failed(&self, instance: &str, panic: &str, restarts: u32, gave_up: bool) {
+        let hub = self.hub(instance);
+        let level = if gave_up {
+            Level::Error
+        } else {
+            Level::Warning
+        };
+        hub.with_scope(
+            |scope| {
+                scope.set_tag("runner_restarts", restarts);
+                scope.set_extra("panic", panic.into());
+            },
+            || {
+                hub.capture_message(
+                    &format!("Runner da instância entrou em pânico: {panic}"),
+                    level,
+                )
+            },
+        );
+    }
+}
+
+/// Breadcrumb for a handshake phase reached, on the current hub.
+pub fn handshake_phase(phase: HandshakePhase) {
+    sentry::add_breadcrumb(Breadcrumb {
+        category: Some("handshake".into()),
+        message: Some(format!("{phase:?}")),
+        ..Default::default()
+    });
+}
+
+/// Tags the current hub with the WA web version a connection uses.
+pub fn wa_version(version: &str) {
+    sentry::configure_scope(|scope| scope.set_tag("wa_version", version));
+}
+
+/// Drops ignored events and repeats of the same message from the same
+/// instance inside the throttle window.
+pub struct EventFilter {
+    ignore: Vec<String>,
+    throttle: Duration,
+    last_sent: Mutex<HashMap<(String, String), Instant>>,
+}
+
+impl EventFilter {
+    pub fn new(ignore: Vec<String>, throttle: Duration) -> Self {
+        Self {
+            ignore,
+            throttle,
+            last_sent: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// `before_send` hook.
+    pub fn check(&self, event: Event<'static>) -> Option<Event<'static>> {
+        let instance = event.tags.get("instance").cloned().unwrap_or_default();
+        let keep = self.keep(&instance, &event_message(&event), Instant::now());
+        keep.then_some(event)
+    }
+
+    /// Whether an event with `message` from `instance` goes out at `now`.
+    pub fn keep(&self, instance: &str, message: &str, now: Instant) -> bool {
+        if self
+            .ignore
+            .iter()
+            .any(|pattern| message.contains(pattern.as_str()))
+        {
+            return false;
+        }
+        if self.throttle.is_zero() {
+            return true;
+        }
+        let mut last_sent = self
+            .last_sent
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let key = (instance.to_string(), message.to_string());
+        if let Some(sent) = last_sent.get(&key)
+            && now.duration_since(*sent) < self.throttle
+        {
+            return false;
+        }
+        if last_sent.len() >= MAX_THROTTLE_ENTRIES {
+            last_sent.retain(|_, sent| now.duration_since(*sent) < self.throttle);
+        }
+        last_sent.insert(key, now);
+        true
+    }
+}
+
+/// Text an event is grouped by here: its message, log entry or first
+/// exception.
+fn event_message(event: &Event<'_>) -> String {
+    event
+        .message
+        .clone()
+        .or_else(|| event.logentry.as_ref().map(|entry| entry.message.clone()))
+        .or_else(|| {
+            event.exception.values.first().map(|exception| {
+                format!(
+                    "{}: {}",
+                    exception.ty,
+                    exception.value.as_deref().unwrap_or_default()
+                )
+            })
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    include!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/tests/server/error_reporting_tests.rs"));
+}
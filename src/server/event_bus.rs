@@ -0,0 +1,123 @@
+//! Events handed by the outbox dispatcher to the live sinks: the `/ws` and
+//! `/events/sse` hubs and NATS.
+//!
+//! An event travels as one shared [`BusEvent`]. Its JSON text is rendered
+//! the first time a sink needs it and reused by the others, so an event no
+//! sink sends is never serialized. Every sink buffers in a bounded queue
+//! (the broadcast buffer of each websocket or SSE client, the NATS publish
+//! queue) and counts in its [`SinkStats`] the events it took and those it
+//! dropped because a queue overflowed; `/metrics` and `/metrics/prometheus`
+//! report both.
+
+use crate::server::AppState;
+use crate::server::message_counters::Metric;
+use serde::Serialize;
+use serde_json::Value;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
+
+/// An event envelope on its way to the sinks.
+#[derive(Debug)]
+pub struct BusEvent {
+    /// Empty for events not tied to an instance.
+    pub instance: String,
+    pub event: String,
+    pub payload: Value,
+    json: OnceLock<Arc<str>>,
+}
+
+impl BusEvent {
+    pub fn new(payload: Value) -> Arc<Self> {
+        Arc::new(Self {
+            instance: payload["instance"].as_str().unwrap_or_default().to_string(),
+            event: payload["event"].as_str().unwrap_or_default().to_string(),
+            payload,
+            json: OnceLock::new(),
+        })
+    }
+
+    /// The envelope as JSON, serialized on the first call.
+    pub fn json(&self) -> Arc<str> {
+        self.json
+            .get_or_init(|| self.payload.to_string().into())
+            .clone()
+    }
+
+    /// Whether a sink already needed the JSON text.
+    pub fn is_serialized(&self) -> bool {
+        self.json.get().is_some()
+    }
+}
+
+/// Events a sink took and dropped since the server started.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct SinkCounts {
+    pub queued: u64,
+    /// Events lost because a queue was full or a client fell behind it.
+    pub dropped: u64,
+}
+
+/// Counters of one sink.
+#[derive(Debug, Default)]
+pub struct SinkStats {
+    queued: AtomicU64,
+    dropped: AtomicU64,
+}
+
+impl SinkStats {
+    pub fn record_queued(&self) {
+        self.queued.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_dropped(&self, events: u64) {
+        self.dropped.fetch_add(events, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> SinkCounts {
+        SinkCounts {
+            queued: self.queued.load(Ordering::Relaxed),
+            dropped: self.dropped.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Counters of every sink of this build, by name.
+pub fn sink_counts(state: &AppState) -> Vec<(&'static str, SinkCounts)> {
+    #[allow(unused_mut)]
+    let mut sinks = vec![("ws", state.event_hub.stats()), ("sse", state.sse.stats())];
+    #[cfg(feature = "nats")]
+    if let Some(nats) = &state.nats {
+        sinks.push(("nats", nats.stats()));
+    }
+    sinks
+}
+
+/// Prometheus text exposition of `sinks`.
+pub fn render_prometheus(sinks: &[(&str, SinkCounts)]) -> String {
+    let mut out = String::new();
+    let counters: [Metric<SinkCounts>; 2] = [
+        (
+            "chatwarp_sink_events_queued_total",
+            "Events handed to a live sink.",
+            |c| c.queued,
+        ),
+        (
+            "chatwarp_sink_events_dropped_total",
+            "Events a live sink dropped because a queue overflowed.",
+            |c| c.dropped,
+        ),
+    ];
+    for (name, help, value) in counters {
+        let _ = writeln!(out, "# HELP {name} {help}\n# TYPE {name} counter");
+        for (sink, counts) in sinks {
+            let _ = writeln!(out, "{name}{{sink=\"{sink}\"}} {}", value(counts));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    include!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/tests/server/event_bus_tests.rs"));
+}
@@ -0,0 +1,196 @@
+//! Per-instance event filters of the sinks.
+//!
+//! Each sink an instance delivers to has its own list of events on
+//! `api_sessions`: `webhook_events` for its webhook, `ws_events` for `/ws`
+//! and `nats_events` for NATS. An entry is an event name (`QRCODE_UPDATED`,
+//! also `qrcode.updated` as Evolution writes them) or a prefix ending in
+//! `*` (`MESSAGES_*`); `*` alone takes everything. A missing or empty list
+//! lets every event through. The lists are read and replaced with
+//! `GET`/`PUT /instance/events/:name`; sinks cache them for up to 30
+//! seconds, except the webhook and `/ws` caches, which an update clears.
+
+use crate::api_store::ApiBind;
+use crate::server::AppState;
+use crate::server::cache::CacheSpace;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use thiserror::Error;
+use tracing::debug;
+
+/// Entries accepted per list.
+pub const MAX_ENTRIES: usize = 100;
+const MAX_ENTRY_LEN: usize = 64;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum EventFilterError {
+    #[error("body must be an object with webhook, websocket and/or nats")]
+    InvalidBody,
+    #[error("{0} must be null or an array of at most {MAX_ENTRIES} event names")]
+    NotAList(&'static str),
+    #[error("invalid event {0:?}; expected e.g. MESSAGES_UPSERT or MESSAGES_*")]
+    InvalidEvent(String),
+}
+
+/// Whether `event` passes `filter`; `None` and empty lists pass everything.
+pub fn allows(filter: Option<&[String]>, event: &str) -> bool {
+    match filter {
+        None | Some([]) => true,
+        Some(entries) => entries.iter().any(|entry| entry_matches(entry, event)),
+    }
+}
+
+/// Whether one filter entry takes `event`.
+pub fn entry_matches(entry: &str, event: &str) -> bool {
+    let entry = normalize(entry);
+    let event = normalize(event);
+    match entry.strip_suffix('*') {
+        Some(prefix) => event.starts_with(prefix),
+        None => entry == event,
+    }
+}
+
+/// `messages.upsert` and `MESSAGES_UPSERT` name the same event.
+fn normalize(name: &str) -> String {
+    name.trim().to_ascii_uppercase().replace('.', "_")
+}
+
+/// Event lists of the sinks of one instance; `None` takes every event.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EventFilters {
+    pub webhook: Option<Vec<String>>,
+    pub websocket: Option<Vec<String>>,
+    pub nats: Option<Vec<String>>,
+}
+
+impl EventFilters {
+    /// Replaces the lists present in `body` (`{"webhook"?, "websocket"?,
+    /// "nats"?}`); `null` or `[]` takes every event again. Entries are
+    /// stored normalized, e.g. `messages.upsert` as `MESSAGES_UPSERT`.
+    pub fn apply(&mut self, body: &Value) -> Result<(), EventFilterError> {
+        let fields = body.as_object().ok_or(EventFilterError::InvalidBody)?;
+        if fields.is_empty()
+            || fields
+                .keys()
+                .any(|key| !matches!(key.as_str(), "webhook" | "websocket" | "nats"))
+        {
+            return Err(EventFilterError::InvalidBody);
+        }
+        for (sink, filter) in [
+            ("webhook", &mut self.webhook),
+            ("websocket", &mut self.websocket),
+            ("nats", &mut self.nats),
+        ] {
+            if let Some(value) = fields.get(sink) {
+                *filter = parse_list(sink, value)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn from_row(row: &Value) -> Self {
+        let list = |value: &Value| {
+            value.as_array().map(|entries| {
+                entries
+                    .iter()
+                    .filter_map(|entry| entry.as_str().map(str::to_string))
+                    .collect()
+            })
+        };
+        Self {
+            webhook: list(&row["webhook"]),
+            websocket: list(&row["websocket"]),
+            nats: list(&row["nats"]),
+        }
+    }
+}
+
+fn parse_list(sink: &'static str, value: &Value) -> Result<Option<Vec<String>>, EventFilterError> {
+    let entries = match value {
+        Value::Null => return Ok(None),
+        Value::Array(entries) if entries.len() <= MAX_ENTRIES => entries,
+        _ => return Err(EventFilterError::NotAList(sink)),
+    };
+    if entries.is_empty() {
+        return Ok(None);
+    }
+    let mut list: Vec<String> = Vec::with_capacity(entries.len());
+    for entry in entries {
+        let raw = entry.as_str().ok_or(EventFilterError::NotAList(sink))?;
+        let name = normalize(raw);
+        let body = name.strip_suffix('*').unwrap_or(&name);
+        let valid = name.len() <= MAX_ENTRY_LEN
+            && (name == "*" || !body.is_empty())
+            && body
+                .chars()
+                .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_');
+        if !valid {
+            return Err(EventFilterError::InvalidEvent(raw.to_string()));
+        }
+        if !list.contains(&name) {
+            list.push(name);
+        }
+    }
+    Ok(Some(list))
+}
+
+/// Filters of `session`; `None` when the instance does not exist.
+pub async fn fetch(state: &AppState, session: &str) -> anyhow::Result<Option<EventFilters>> {
+    let rows = state
+        .api_store
+        .query_json(
+            "SELECT jsonb_build_object('webhook', webhook_events, 'websocket', ws_events, \
+             'nats', nats_events) as value FROM api_sessions WHERE session = $1",
+            vec![ApiBind::Text(session.to_string())],
+        )
+        .await?;
+    Ok(rows
+        .first()
+        .map(|row| EventFilters::from_row(row.get("value").unwrap_or(row))))
+}
+
+/// Saves the filters of `session` and drops the cached copies. Returns
+/// whether the instance exists.
+pub async fn set(state: &AppState, session: &str, filters: &EventFilters) -> anyhow::Result<bool> {
+    let list = |filter: &Option<Vec<String>>| filter.as_ref().map(|entries| json!(entries));
+    let updated = state
+        .api_store
+        .execute(
+            "UPDATE api_sessions SET webhook_events = $2, ws_events = $3, nats_events = $4, \
+             updated_at = now() WHERE session = $1",
+            vec![
+                ApiBind::Text(session.to_string()),
+                ApiBind::NullableJson(list(&filters.webhook)),
+                ApiBind::NullableJson(list(&filters.websocket)),
+                ApiBind::NullableJson(list(&filters.nats)),
+            ],
+        )
+        .await?;
+    state.webhook_config_cache.remove(session);
+    state.event_hub.forget_filter(session);
+    state.cache.invalidate_space(CacheSpace::Instances).await;
+    Ok(updated > 0)
+}
+
+/// `/ws` filter of `session`, for the websocket hub.
+pub(crate) async fn websocket(state: &AppState, session: &str) -> Option<Vec<String>> {
+    match fetch(state, session).await {
+        Ok(filters) => filters.and_then(|filters| filters.websocket),
+        Err(e) => {
+            debug!(instance = %session, error = %e, "Filtro de eventos do /ws indisponível");
+            None
+        }
+    }
+}
+
+/// Body of the event filter routes.
+pub fn response(instance: &str, filters: &EventFilters) -> Value {
+    json!({
+        "instance": instance,
+        "events": filters,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    include!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/tests/server/event_filters_tests.rs"));
+}
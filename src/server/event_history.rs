@@ -0,0 +1,328 @@
+//! Per-instance event history, so `/ws` consumers can catch up after a
+//! disconnect before resuming the live stream.
+//!
+//! [`outbox::publish`](super::outbox::publish) records every envelope in a
+//! ring buffer of the last `EVENT_HISTORY_SIZE` events of its instance.
+//! `GET /events/history/:instance?since=` returns the events after an
+//! `eventId` or a timestamp, oldest first. With `EVENT_HISTORY_PERSIST`
+//! on, a `since` the buffer no longer covers (or that predates a restart)
+//! is answered from the dispatched rows of `event_outbox`, which are kept
+//! for `OUTBOX_RETENTION_HOURS`.
+//!
+//! Consumers should open the websocket first, then fetch the history and
+//! drop repeated `eventId`s.
+
+use crate::api_store::ApiBind;
+use crate::server::AppState;
+use crate::server::pagination::PageInfo;
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex, PoisonError};
+use uuid::Uuid;
+
+pub use crate::config::EventHistoryConfig;
+
+pub const DEFAULT_LIMIT: usize = 100;
+pub const MAX_LIMIT: usize = 1000;
+
+/// Where a client resumes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Since {
+    /// After the event with this `eventId`.
+    EventId(Uuid),
+    /// After this instant.
+    Timestamp(DateTime<Utc>),
+}
+
+impl Since {
+    /// An `eventId`, an RFC 3339 timestamp or a unix timestamp in seconds
+    /// or milliseconds.
+    pub fn parse(raw: &str) -> Option<Self> {
+        let raw = raw.trim();
+        if let Ok(id) = Uuid::parse_str(raw) {
+            return Some(Self::EventId(id));
+        }
+        if let Ok(at) = DateTime::parse_from_rfc3339(raw) {
+            return Some(Self::Timestamp(at.with_timezone(&Utc)));
+        }
+        let unix = raw.parse::<i64>().ok()?;
+        // Seconds stay below 10^11 until the year 5138.
+        let at = if unix.abs() < 100_000_000_000 {
+            DateTime::from_timestamp(unix, 0)
+        } else {
+            DateTime::from_timestamp_millis(unix)
+        };
+        at.map(Self::Timestamp)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Entry {
+    event_id: Option<Uuid>,
+    at: DateTime<Utc>,
+    payload: Value,
+}
+
+#[derive(Debug, Default)]
+struct Buffer {
+    entries: VecDeque<Entry>,
+    /// Set once an entry was dropped to make room.
+    evicted: bool,
+}
+
+/// Events after a [`Since`], oldest first.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoryPage {
+    pub events: Vec<Value>,
+    /// False when events after `since` may be missing: they left the
+    /// buffer, or the `eventId` is unknown and everything retained was
+    /// returned instead.
+    pub complete: bool,
+    /// More events follow the last one returned.
+    pub has_more: bool,
+    pub source: &'static str,
+}
+
+/// Ring buffers of recent events, one per instance.
+pub struct EventHistory {
+    config: EventHistoryConfig,
+    started: DateTime<Utc>,
+    buffers: Mutex<HashMap<String, Buffer>>,
+}
+
+impl EventHistory {
+    pub fn new(config: EventHistoryConfig) -> Self {
+        Self {
+            config,
+            started: Utc::now(),
+            buffers: Mutex::default(),
+        }
+    }
+
+    pub fn config(&self) -> &EventHistoryConfig {
+        &self.config
+    }
+
+    /// Records an event envelope; events without an instance are ignored.
+    pub fn record(&self, payload: &Value) {
+        self.record_at(payload, Utc::now());
+    }
+
+    fn record_at(&self, payload: &Value, at: DateTime<Utc>) {
+        let Some(instance) = payload["instance"].as_str().filter(|i| !i.is_empty()) else {
+            return;
+        };
+        let entry = Entry {
+            event_id: payload["eventId"]
+                .as_str()
+                .and_then(|id| Uuid::parse_str(id).ok()),
+            at,
+            payload: payload.clone(),
+        };
+        let mut buffers = self.buffers.lock().unwrap_or_else(PoisonError::into_inner);
+        let buffer = buffers.entry(instance.to_string()).or_default();
+        if buffer.entries.len() >= self.config.size {
+            buffer.entries.pop_front();
+            buffer.evicted = true;
+        }
+        buffer.entries.push_back(entry);
+    }
+
+    /// Events of `instance` after `since` held in memory. Without `since`
+    /// the whole buffer is returned, never complete since older events may
+    /// exist.
+    pub fn page(&self, instance: &str, since: Option<Since>, limit: usize) -> HistoryPage {
+        let buffers = self.buffers.lock().unwrap_or_else(PoisonError::into_inner);
+        let empty = Buffer::default();
+        let buffer = buffers.get(instance).unwrap_or(&empty);
+
+        let (start, complete) = match since {
+            None => (0, false),
+            Some(Since::EventId(id)) => {
+                match buffer.entries.iter().position(|e| e.event_id == Some(id)) {
+                    Some(pos) => (pos + 1, true),
+                    None => (0, false),
+                }
+            }
+            Some(Since::Timestamp(at)) => {
+                // Everything since the start was kept until the first eviction.
+                let covered = (at >= self.started && !buffer.evicted)
+                    || buffer.entries.front().is_some_and(|e| e.at <= at);
+                (buffer.entries.partition_point(|e| e.at <= at), covered)
+            }
+        };
+        let mut events: Vec<Value> = buffer
+            .entries
+            .iter()
+            .skip(start)
+            .take(limit + 1)
+            .map(|e| e.payload.clone())
+            .collect();
+        let has_more = events.len() > limit;
+        events.truncate(limit);
+        HistoryPage {
+            events,
+            complete,
+            has_more,
+            source: "memory",
+        }
+    }
+}
+
+/// The buffer, falling back to `event_outbox` when it cannot vouch for
+/// the range and persistence is on.
+pub async fn lookup(
+    state: &AppState,
+    instance: &str,
+    since: Option<Since>,
+    limit: usize,
+) -> anyhow::Result<HistoryPage> {
+    let page = state.event_history.page(instance, since, limit);
+    if page.complete || !state.event_history.config().persist {
+        return Ok(page);
+    }
+    Ok(persisted(state, instance, since, limit)
+        .await?
+        .unwrap_or(page))
+}
+
+/// Dispatched outbox rows after `since`. `None` when the store answered
+/// nothing, as the sqlite store does.
+async fn persisted(
+    state: &AppState,
+    instance: &str,
+    since: Option<Since>,
+    limit: usize,
+) -> anyhow::Result<Option<HistoryPage>> {
+    let fetch = i32::try_from(limit + 1).unwrap_or(i32::MAX);
+    let (sql, anchor) = match since {
+        Some(Since::EventId(id)) => (
+            "WITH anchor AS (SELECT seq FROM event_outbox WHERE session = $1 AND id = $2) \
+             SELECT jsonb_build_object( \
+                'events', COALESCE((SELECT jsonb_agg(payload ORDER BY seq) FROM ( \
+                    SELECT payload, seq FROM event_outbox \
+                    WHERE session = $1 AND dispatched_at IS NOT NULL \
+                      AND seq > COALESCE((SELECT seq FROM anchor), 0) \
+                    ORDER BY seq LIMIT $3) t), '[]'::jsonb), \
+                'covered', EXISTS (SELECT 1 FROM anchor)) as value",
+            ApiBind::Uuid(id),
+        ),
+        _ => (
+            "SELECT jsonb_build_object( \
+                'events', COALESCE((SELECT jsonb_agg(payload ORDER BY seq) FROM ( \
+                    SELECT payload, seq FROM event_outbox \
+                    WHERE session = $1 AND dispatched_at IS NOT NULL \
+                      AND created_at > $2::timestamptz \
+                    ORDER BY seq LIMIT $3) t), '[]'::jsonb), \
+                'covered', EXISTS (SELECT 1 FROM event_outbox \
+                    WHERE session = $1 AND created_at <= $2::timestamptz)) as value",
+            ApiBind::Text(match since {
+                Some(Since::Timestamp(at)) => at.to_rfc3339(),
+                _ => "-infinity".to_string(),
+            }),
+        ),
+    };
+    let rows = state
+        .api_store
+        .query_json(
+            sql,
+            vec![
+                ApiBind::Text(instance.to_string()),
+                anchor,
+                ApiBind::Int(fetch),
+            ],
+        )
+        .await?;
+    let Some(row) = rows.first() else {
+        return Ok(None);
+    };
+    let value = row.get("value").unwrap_or(row);
+    let mut events = value["events"].as_array().cloned().unwrap_or_default();
+    let has_more = events.len() > limit;
+    events.truncate(limit);
+    Ok(Some(HistoryPage {
+        events,
+        complete: value["covered"].as_bool().unwrap_or(false),
+        has_more,
+        source: "database",
+    }))
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct HistoryQuery {
+    since: Option<String>,
+    /// `pagination.nextCursor` of the previous page, same as `since`.
+    cursor: Option<String>,
+    limit: Option<usize>,
+}
+
+/// `GET /events/history/:instance`: events after `since` (an `eventId` or
+/// a timestamp), oldest first; `limit` caps the page and
+/// `pagination.nextCursor` continues it.
+pub async fn history_handler(
+    Path(instance): Path<String>,
+    State(state): State<Arc<AppState>>,
+    Query(mut query): Query<HistoryQuery>,
+) -> impl IntoResponse {
+    if let Some(cursor) = query.cursor.take().filter(|v| !v.trim().is_empty()) {
+        query.since = Some(cursor);
+    }
+    let since = match query.since.as_deref().filter(|v| !v.trim().is_empty()) {
+        None => None,
+        Some(raw) => match Since::parse(raw) {
+            Some(since) => Some(since),
+            None => {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    Json(json!({"error": "invalid_since", "since": raw})),
+                );
+            }
+        },
+    };
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+
+    match lookup(&state, &instance, since, limit).await {
+        Ok(page) => {
+            let next_cursor = page
+                .events
+                .last()
+                .and_then(|event| event["eventId"].as_str())
+                .map(str::to_string);
+            let pagination = PageInfo::keyset(
+                u32::try_from(limit).unwrap_or(u32::MAX),
+                page.has_more,
+                next_cursor,
+            );
+            (
+                StatusCode::OK,
+                Json(json!({
+                    "instance": instance,
+                    "since": query.since,
+                    "source": page.source,
+                    "complete": page.complete,
+                    "hasMore": page.has_more,
+                    "count": page.events.len(),
+                    "events": page.events,
+                    "pagination": pagination,
+                })),
+            )
+        }
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": "db_error", "details": e.to_string()})),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    include!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/tests/server/event_history_tests.rs"));
+}
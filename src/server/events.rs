@@ -0,0 +1,351 @@
+//! Typed payloads of the events delivered to webhooks, `/ws` and NATS.
+//!
+//! Every sink receives the same envelope built by [`envelope`]:
+//! `{"event", "instance", "schemaVersion", "data"}`, plus the instance's
+//! `tags` and `metadata` when it has any and the `eventId` assigned by the
+//! [`outbox`](super::outbox). Events with a typed payload below
+//! have a fixed `data` shape described by [`schema_document`] (served at
+//! `GET /events/schema`); the rest still carry free-form JSON.
+//! Bump [`SCHEMA_VERSION`] on any breaking change to a typed payload.
+
+use crate::server::connection::{ConnectionState, Reason};
+use crate::server::instance_deletion::{DeletionStep, StepStatus};
+use crate::server::{AppState, webhooks};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::{Map, Value, json};
+
+/// Version of the envelope and typed payload shapes.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Event payload with a fixed shape.
+pub trait EventPayload: Serialize {
+    /// Event name, e.g. `CONNECTION_UPDATE`.
+    const EVENT: &'static str;
+
+    /// JSON schema of the payload (the envelope's `data`).
+    fn schema() -> Value;
+
+    /// Payload as the envelope's `data`.
+    fn to_data(&self) -> Value {
+        serde_json::to_value(self).unwrap_or_else(|_| json!({}))
+    }
+}
+
+/// Envelope shared by every sink.
+pub fn envelope(event: &str, instance: Option<&str>, data: Value) -> Value {
+    json!({
+        "event": event,
+        "instance": instance.unwrap_or(""),
+        "schemaVersion": SCHEMA_VERSION,
+        "data": data
+    })
+}
+
+/// Emits a typed event for `instance` to every sink.
+pub async fn emit<P: EventPayload>(state: &AppState, instance: Option<&str>, payload: &P) {
+    webhooks::enqueue(state, instance, P::EVENT, payload.to_data()).await;
+}
+
+/// `QRCODE_UPDATED`: a new pairing QR code.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct QrcodeUpdated {
+    pub qrcode: String,
+    /// Seconds until the code expires.
+    pub timeout: u64,
+}
+
+impl EventPayload for QrcodeUpdated {
+    const EVENT: &'static str = "QRCODE_UPDATED";
+
+    fn schema() -> Value {
+        object(
+            json!({
+                "qrcode": {"type": "string"},
+                "timeout": {"type": "integer", "minimum": 0},
+            }),
+            &["qrcode", "timeout"],
+        )
+    }
+}
+
+/// `CONNECTION_UPDATE`: the instance moved to another connection state.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConnectionUpdate {
+    pub action: &'static str,
+    /// Evolution-compatible state (`open`, `connecting`, `close`).
+    pub state: &'static str,
+    pub previous_state: &'static str,
+    pub connection_state: ConnectionState,
+    pub reason: Reason,
+    pub status_reason: u16,
+    pub at: DateTime<Utc>,
+    /// Transition specific fields, e.g. `restarts`.
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl EventPayload for ConnectionUpdate {
+    const EVENT: &'static str = "CONNECTION_UPDATE";
+
+    fn schema() -> Value {
+        let mut schema = object(
+            json!({
+                "action": {"const": "update"},
+                "state": {"enum": ["open", "connecting", "close"]},
+                "previousState": {"enum": ["open", "connecting", "close"]},
+                "connectionState": {"enum": [
+                    "disconnected", "connecting", "qr_pending", "pairing_pending",
+                    "connected", "logged_out", "errored"
+                ]},
+                "reason": {"type": "string"},
+                "statusReason": {"type": "integer"},
+                "at": {"type": "string", "format": "date-time"},
+            }),
+            &[
+                "action",
+                "state",
+                "previousState",
+                "connectionState",
+                "reason",
+                "statusReason",
+                "at",
+            ],
+        );
+        schema["additionalProperties"] = json!(true);
+        schema
+    }
+}
+
+/// `LOGOUT_INSTANCE`: WhatsApp logged the device out; the instance needs a
+/// new QR code.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogoutInstance {
+    /// Number the device was paired with, when known.
+    pub wuid: Option<String>,
+    /// The server refused the login instead of ending a live session.
+    pub on_connect: bool,
+    /// Whether the stored credentials were cleared.
+    pub credentials_cleared: bool,
+    pub at: DateTime<Utc>,
+}
+
+impl EventPayload for LogoutInstance {
+    const EVENT: &'static str = "LOGOUT_INSTANCE";
+
+    fn schema() -> Value {
+        object(
+            json!({
+                "wuid": {"type": ["string", "null"]},
+                "onConnect": {"type": "boolean"},
+                "credentialsCleared": {"type": "boolean"},
+                "at": {"type": "string", "format": "date-time"},
+            }),
+            &["wuid", "onConnect", "credentialsCleared", "at"],
+        )
+    }
+}
+
+/// `MESSAGES_UPSERT`: messages received or synced.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MessagesUpsert {
+    pub messages: Vec<Value>,
+    /// `notify` for new messages, `append` for history.
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+}
+
+impl EventPayload for MessagesUpsert {
+    const EVENT: &'static str = "MESSAGES_UPSERT";
+
+    fn schema() -> Value {
+        object(
+            json!({
+                "messages": {"type": "array", "items": {
+                    "type": "object",
+                    "properties": {"key": {"type": "object"}, "message": {"type": "object"}},
+                    "required": ["key", "message"],
+                }},
+                "type": {"enum": ["notify", "append"]},
+            }),
+            &["messages", "type"],
+        )
+    }
+}
+
+/// `CHATS_UPDATE`: chat settings changed; only changed fields are present.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChatsUpdate {
+    pub id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub archived: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pinned: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub marked_unread: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub muted: Option<bool>,
+    /// Milliseconds, `-1` when muted forever; only sent with `muted`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mute_end_time: Option<Option<i64>>,
+    /// Disappearing messages timer in seconds, `0` when off.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ephemeral_expiration: Option<u32>,
+}
+
+impl EventPayload for ChatsUpdate {
+    const EVENT: &'static str = "CHATS_UPDATE";
+
+    fn schema() -> Value {
+        object(
+            json!({
+                "id": {"type": "string"},
+                "archived": {"type": "boolean"},
+                "pinned": {"type": "boolean"},
+                "markedUnread": {"type": "boolean"},
+                "muted": {"type": "boolean"},
+                "muteEndTime": {"type": ["integer", "null"]},
+                "ephemeralExpiration": {"type": "integer"},
+            }),
+            &["id"],
+        )
+    }
+}
+
+/// `CONTACTS_SYNC_PROGRESS`: a background `/chat/syncContacts` finished a
+/// batch, or stopped.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContactsSyncProgress {
+    pub sync_id: String,
+    /// Numbers queried so far.
+    pub processed: usize,
+    pub total: usize,
+    /// Numbers with a WhatsApp account so far.
+    pub found: usize,
+    pub done: bool,
+    /// Why the sync stopped early.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl EventPayload for ContactsSyncProgress {
+    const EVENT: &'static str = "CONTACTS_SYNC_PROGRESS";
+
+    fn schema() -> Value {
+        object(
+            json!({
+                "syncId": {"type": "string", "format": "uuid"},
+                "processed": {"type": "integer", "minimum": 0},
+                "total": {"type": "integer", "minimum": 0},
+                "found": {"type": "integer", "minimum": 0},
+                "done": {"type": "boolean"},
+                "error": {"type": "string"},
+            }),
+            &["syncId", "processed", "total", "found", "done"],
+        )
+    }
+}
+
+/// `INSTANCE_DELETE_PROGRESS`: a step of an instance deletion finished.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InstanceDeleteProgress {
+    pub deletion_id: String,
+    pub step: DeletionStep,
+    pub status: StepStatus,
+    /// Steps finished so far, this one included.
+    pub completed: usize,
+    pub total: usize,
+    /// Last event of the deletion, because it finished or a step failed.
+    pub done: bool,
+    /// Rows deleted by `purgeAuth` and `purgeData`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rows: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl EventPayload for InstanceDeleteProgress {
+    const EVENT: &'static str = "INSTANCE_DELETE_PROGRESS";
+
+    fn schema() -> Value {
+        let steps: Vec<&str> = DeletionStep::ALL.iter().map(|step| step.as_str()).collect();
+        object(
+            json!({
+                "deletionId": {"type": "string", "format": "uuid"},
+                "step": {"enum": steps},
+                "status": {"enum": ["done", "skipped", "failed"]},
+                "completed": {"type": "integer", "minimum": 1},
+                "total": {"type": "integer", "minimum": 1},
+                "done": {"type": "boolean"},
+                "rows": {"type": "integer", "minimum": 0},
+                "error": {"type": "string"},
+            }),
+            &["deletionId", "step", "status", "completed", "total", "done"],
+        )
+    }
+}
+
+fn object(properties: Value, required: &[&str]) -> Value {
+    json!({
+        "type": "object",
+        "properties": properties,
+        "required": required,
+        "additionalProperties": false,
+    })
+}
+
+fn definition<P: EventPayload>(definitions: &mut Map<String, Value>) {
+    definitions.insert(P::EVENT.to_string(), P::schema());
+}
+
+/// JSON schema (draft 2020-12) of the envelope, with one definition per
+/// typed event.
+pub fn schema_document() -> Value {
+    let mut definitions = Map::new();
+    definition::<QrcodeUpdated>(&mut definitions);
+    definition::<ConnectionUpdate>(&mut definitions);
+    definition::<LogoutInstance>(&mut definitions);
+    definition::<MessagesUpsert>(&mut definitions);
+    definition::<ChatsUpdate>(&mut definitions);
+    definition::<ContactsSyncProgress>(&mut definitions);
+    definition::<InstanceDeleteProgress>(&mut definitions);
+
+    let typed: Vec<Value> = definitions
+        .keys()
+        .map(|event| {
+            json!({
+                "if": {"properties": {"event": {"const": event}}},
+                "then": {"properties": {"data": {"$ref": format!("#/$defs/{event}")}}},
+            })
+        })
+        .collect();
+
+    json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "title": "ChatWarp event",
+        "schemaVersion": SCHEMA_VERSION,
+        "type": "object",
+        "properties": {
+            "event": {"type": "string"},
+            "instance": {"type": "string"},
+            "schemaVersion": {"const": SCHEMA_VERSION},
+            "eventId": {"type": "string", "format": "uuid"},
+            "tags": {"type": "array", "items": {"type": "string"}},
+            "metadata": {"type": "object", "additionalProperties": {"type": "string"}},
+            "data": {},
+        },
+        "required": ["event", "instance", "schemaVersion", "data"],
+        "allOf": typed,
+        "$defs": definitions,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    include!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/tests/server/events_tests.rs"));
+}
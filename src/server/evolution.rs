@@ -0,0 +1,219 @@
+//! Evolution API v2 response shapes.
+//!
+//! Clients migrating from Evolution parse the responses of the
+//! Evolution-style routes (`/instance/*`, `/message/*`, `/group/*`) with
+//! its schemas. With `evolutionCompat` on (`EVOLUTION_COMPAT`), those
+//! routes answer with the builders below instead of the native bodies.
+//! Fields Evolution has and this server does not track (proxy, chatwoot,
+//! rabbitmq, ...) are `null`, as Evolution sends them when unset.
+
+use crate::features::GroupMetadata;
+use crate::server::cloud_api;
+use crate::server::connection::ConnectionState;
+use crate::server::qr::{self, QrFormat, QrRenderOptions};
+use base64::Engine as _;
+use chrono::{DateTime, Utc};
+use serde_json::{Value, json};
+
+/// Source reported on sent messages, as Evolution does for API sends.
+const MESSAGE_SOURCE: &str = "unknown";
+/// `clientName` of the instances.
+const CLIENT_NAME: &str = "chatwarp";
+
+/// `/instance/fetchInstances` entry for an `api_sessions` row (with its
+/// `_count`).
+pub fn instance(row: &Value, state: ConnectionState, profile_pic_url: Option<&str>) -> Value {
+    let name = row["session"].as_str().unwrap_or_default();
+    let phone_number = row["phone_number"].as_str().filter(|n| !n.is_empty());
+    json!({
+        "id": name,
+        "name": name,
+        "connectionStatus": state.evolution_state(),
+        "ownerJid": phone_number.map(|number| format!("{number}@s.whatsapp.net")),
+        "profileName": null,
+        "profilePicUrl": profile_pic_url,
+        "integration": row["integration"].as_str().unwrap_or(cloud_api::DEFAULT_INTEGRATION),
+        "number": row["cloud_phone_number_id"].as_str().or(phone_number),
+        "businessId": row["cloud_business_id"],
+        "token": null,
+        "clientName": CLIENT_NAME,
+        "disconnectionReasonCode": null,
+        "disconnectionObject": null,
+        "disconnectionAt": null,
+        "createdAt": row["created_at"],
+        "updatedAt": row["updated_at"],
+        "Chatwoot": null,
+        "Proxy": null,
+        "Rabbitmq": null,
+        "Sqs": null,
+        "Websocket": null,
+        "Setting": null,
+        "_count": {
+            "Message": row["_count"]["Message"].as_u64().unwrap_or(0),
+            "Contact": row["_count"]["Contact"].as_u64().unwrap_or(0),
+            "Chat": row["_count"]["Chat"].as_u64().unwrap_or(0),
+        },
+    })
+}
+
+/// `/instance/connectionState` body, also `/instance/connect` once open.
+pub fn connection_state(name: &str, state: ConnectionState) -> Value {
+    json!({
+        "instance": {
+            "instanceName": name,
+            "state": state.evolution_state(),
+        },
+    })
+}
+
+/// Pairing data of `/instance/connect` and `/instance/create`: the raw QR
+/// string, its PNG as a data URL and the pair code, when there are any.
+pub fn qrcode(code: Option<&str>, pairing_code: Option<&str>, count: u32) -> Value {
+    let base64 = code.map(|code| {
+        let rendered = qr::render_qr_with_fallback(
+            code,
+            QrRenderOptions {
+                format: QrFormat::Png,
+                ..Default::default()
+            },
+        );
+        format!(
+            "data:{};base64,{}",
+            rendered.format.content_type(),
+            base64::engine::general_purpose::STANDARD.encode(rendered.body)
+        )
+    });
+    json!({
+        "pairingCode": pairing_code,
+        "code": code,
+        "base64": base64,
+        "count": count,
+    })
+}
+
+/// `/instance/create` body for the stored `api_sessions` row.
+pub fn created(row: &Value, qrcode: Value) -> Value {
+    let name = row["session"].as_str().unwrap_or_default();
+    let webhook = match row["webhook_url"].as_str() {
+        Some(url) => json!({
+            "webhookUrl": url,
+            "webhookHeaders": row["webhook_headers"],
+            "webhookByEvents": row["webhook_by_events"].as_bool().unwrap_or(false),
+            "webhookBase64": row["webhook_base64"].as_bool().unwrap_or(false),
+        }),
+        None => json!({}),
+    };
+    json!({
+        "instance": {
+            "instanceName": name,
+            "instanceId": name,
+            "integration": row["integration"].as_str().unwrap_or(cloud_api::DEFAULT_INTEGRATION),
+            "webhookWaBusiness": null,
+            "accessTokenWaBusiness": "",
+            "status": "created",
+        },
+        "hash": null,
+        "webhook": webhook,
+        "websocket": {},
+        "rabbitmq": {},
+        "sqs": {},
+        "settings": {
+            "rejectCall": false,
+            "msgCall": "",
+            "groupsIgnore": false,
+            "alwaysOnline": false,
+            "readMessages": false,
+            "readStatus": false,
+            "syncFullHistory": false,
+            "wavoipToken": "",
+        },
+        "qrcode": qrcode,
+    })
+}
+
+/// `/message/*` body for a message queued from `body`. The key id is the
+/// queue id, which `/message/status` and the webhooks also carry.
+pub fn sent_message(row: &Value, body: &Value) -> Value {
+    let kind = row["message_type"].as_str().unwrap_or_default();
+    let message = match body["text"].as_str() {
+        Some(text) if kind == "text" => json!({"conversation": text}),
+        _ => json!({}),
+    };
+    let timestamp = row["created_at"]
+        .as_str()
+        .and_then(|created_at| DateTime::parse_from_rfc3339(created_at).ok())
+        .map(|created_at| created_at.timestamp())
+        .unwrap_or_else(|| Utc::now().timestamp());
+    json!({
+        "key": {
+            "remoteJid": row["chat_id"],
+            "fromMe": true,
+            "id": row["id"],
+        },
+        "pushName": "",
+        "status": "PENDING",
+        "message": message,
+        "contextInfo": null,
+        "messageType": message_type(kind),
+        "messageTimestamp": timestamp,
+        "instanceId": row["session"],
+        "source": MESSAGE_SOURCE,
+    })
+}
+
+/// Evolution `messageType` of a queued message type.
+pub fn message_type(kind: &str) -> &'static str {
+    match kind {
+        "text" => "conversation",
+        "image" => "imageMessage",
+        "video" => "videoMessage",
+        "voice" | "audio" => "audioMessage",
+        "file" => "documentMessage",
+        "sticker" => "stickerMessage",
+        "template" => "templateMessage",
+        "buttons" => "interactiveMessage",
+        "list" => "listMessage",
+        "product" => "productMessage",
+        "location" => "locationMessage",
+        "poll" => "pollCreationMessage",
+        "contact_vcard" => "contactMessage",
+        _ => "unknown",
+    }
+}
+
+/// `/group/fetchAllGroups` entry; `participants` is left out unless asked
+/// for (`getParticipants=true`), like Evolution does.
+pub fn group(metadata: &GroupMetadata, with_participants: bool) -> Value {
+    let mut group = json!({
+        "id": metadata.id.to_string(),
+        "subject": metadata.subject,
+        "subjectOwner": null,
+        "subjectTime": null,
+        "pictureUrl": null,
+        "size": metadata.participants.len(),
+        "creation": null,
+        "owner": null,
+        "desc": null,
+        "descId": null,
+        "restrict": null,
+        "announce": null,
+    });
+    if with_participants {
+        group["participants"] = metadata
+            .participants
+            .iter()
+            .map(|participant| {
+                json!({
+                    "id": participant.jid.to_string(),
+                    "admin": participant.is_admin.then_some("admin"),
+                })
+            })
+            .collect();
+    }
+    group
+}
+
+#[cfg(test)]
+mod tests {
+    include!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/tests/server/evolution_tests.rs"));
+}
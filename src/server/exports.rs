@@ -0,0 +1,476 @@
+//! `/chat/export/:instance`: chat history exported by a background job.
+//!
+//! A request schedules a job in `api_export_jobs` and returns right away.
+//! The job pages through the stored messages of one chat and/or a date
+//! range, oldest first, and writes them as a JSON array or CSV into
+//! `EXPORT_DIR`. `GET /jobs/:id` reports its progress and, once it is done,
+//! a download URL signed with `EXPORT_SIGNING_SECRET` that is valid for
+//! `EXPORT_URL_TTL_MINUTES` and needs no credentials. Files are deleted, and
+//! their jobs marked `expired`, `EXPORT_TTL_HOURS` after they finish.
+
+use crate::api_store::ApiBind;
+use crate::server::AppState;
+use crate::server::jid::{self, JidError};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use serde_json::{Value, json};
+use sha2::Sha256;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+use tokio::io::{AsyncWriteExt, BufWriter};
+use uuid::Uuid;
+
+pub use crate::config::ExportConfig;
+
+/// Messages read from the store per query.
+const PAGE_SIZE: usize = 1000;
+const SWEEP_EVERY: Duration = Duration::from_secs(10 * 60);
+const CSV_HEADER: &str =
+    "id,wa_message_id,chat_id,from_me,message_type,status,delivery_status,created_at,text,payload";
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ExportError {
+    #[error("format must be json or csv")]
+    InvalidFormat,
+    #[error(transparent)]
+    InvalidChat(#[from] JidError),
+    #[error("{0} must be an RFC 3339 date or a unix timestamp in seconds")]
+    InvalidDate(&'static str),
+    #[error("from must be before to")]
+    InvalidRange,
+    #[error("remoteJid, from or to is required")]
+    MissingFilter,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Json,
+    Csv,
+}
+
+impl ExportFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::Csv => "csv",
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::Json => "application/json",
+            Self::Csv => "text/csv; charset=utf-8",
+        }
+    }
+}
+
+/// Messages to export.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportRequest {
+    pub format: ExportFormat,
+    pub remote_jid: Option<String>,
+    /// Inclusive.
+    pub from: Option<DateTime<Utc>>,
+    /// Exclusive.
+    pub to: Option<DateTime<Utc>>,
+}
+
+impl ExportRequest {
+    /// `{"remoteJid"?, "from"?, "to"?, "format"?}` (`chatId` and `number` are
+    /// accepted for `remoteJid`); at least one filter is
+    /// required so an export never dumps a whole instance by accident.
+    pub fn from_body(body: &Value) -> Result<Self, ExportError> {
+        let format = match body["format"].as_str().map(str::to_ascii_lowercase) {
+            None => ExportFormat::Json,
+            Some(format) if format == "json" => ExportFormat::Json,
+            Some(format) if format == "csv" => ExportFormat::Csv,
+            Some(_) => return Err(ExportError::InvalidFormat),
+        };
+        let remote_jid = body["remoteJid"]
+            .as_str()
+            .or_else(|| body["chatId"].as_str())
+            .or_else(|| body["number"].as_str())
+            .filter(|raw| !raw.trim().is_empty())
+            .map(jid::normalize)
+            .transpose()?;
+        let from = date(body, "from")?;
+        let to = date(body, "to")?;
+        if remote_jid.is_none() && from.is_none() && to.is_none() {
+            return Err(ExportError::MissingFilter);
+        }
+        if let (Some(from), Some(to)) = (from, to)
+            && from >= to
+        {
+            return Err(ExportError::InvalidRange);
+        }
+        Ok(Self {
+            format,
+            remote_jid,
+            from,
+            to,
+        })
+    }
+}
+
+fn date(body: &Value, field: &'static str) -> Result<Option<DateTime<Utc>>, ExportError> {
+    match &body[field] {
+        Value::Null => Ok(None),
+        Value::String(raw) => DateTime::parse_from_rfc3339(raw)
+            .map(|date| Some(date.with_timezone(&Utc)))
+            .map_err(|_| ExportError::InvalidDate(field)),
+        Value::Number(secs) => secs
+            .as_i64()
+            .and_then(|secs| DateTime::from_timestamp(secs, 0))
+            .map(Some)
+            .ok_or(ExportError::InvalidDate(field)),
+        _ => Err(ExportError::InvalidDate(field)),
+    }
+}
+
+impl ExportConfig {
+    fn file_path(&self, id: Uuid, format: ExportFormat) -> PathBuf {
+        self.dir.join(format!("{id}.{}", format.extension()))
+    }
+
+    fn signature(&self, id: Uuid, expires: i64) -> Option<Hmac<Sha256>> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.secret).ok()?;
+        mac.update(format!("{id}:{expires}").as_bytes());
+        Some(mac)
+    }
+
+    /// Download URL of job `id`, valid until `now + url_ttl`. Always under
+    /// `/api/v1` so it works whether or not legacy root paths are served.
+    pub fn download_url(&self, id: Uuid, now: DateTime<Utc>) -> Option<(String, DateTime<Utc>)> {
+        let expires = now + chrono::Duration::from_std(self.url_ttl).ok()?;
+        let signature = hex::encode(
+            self.signature(id, expires.timestamp())?
+                .finalize()
+                .into_bytes(),
+        );
+        let url = format!(
+            "{}/api/v1/jobs/{id}/download?expires={}&signature={signature}",
+            self.public_url.as_deref().unwrap_or_default(),
+            expires.timestamp()
+        );
+        Some((url, expires))
+    }
+
+    /// Whether `signature` (hex) signs job `id` until `expires` (unix
+    /// seconds) and that is still ahead of `now`.
+    pub fn verify(&self, id: Uuid, expires: i64, signature: &str, now: DateTime<Utc>) -> bool {
+        let Ok(signature) = hex::decode(signature) else {
+            return false;
+        };
+        expires > now.timestamp()
+            && self
+                .signature(id, expires)
+                .is_some_and(|mac| mac.verify_slice(&signature).is_ok())
+    }
+}
+
+/// Whether `path` is a signed download, which carries its own credentials.
+pub fn is_download_path(path: &str) -> bool {
+    path.strip_prefix("/jobs/")
+        .and_then(|rest| rest.strip_suffix("/download"))
+        .is_some_and(|id| !id.is_empty() && !id.contains('/'))
+}
+
+/// Text of a stored message payload, for the CSV `text` column.
+fn payload_text(payload: &Value) -> Option<&str> {
+    ["text", "caption", "body"]
+        .into_iter()
+        .find_map(|key| payload[key].as_str())
+        .or_else(|| payload["message"]["text"].as_str())
+        .or_else(|| payload["message"]["conversation"].as_str())
+}
+
+fn csv_cell(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// One exported message as a CSV line, without the line break.
+pub fn csv_row(message: &Value) -> String {
+    let field = |key: &str| match &message[key] {
+        Value::Null => String::new(),
+        Value::String(text) => text.clone(),
+        other => other.to_string(),
+    };
+    let payload = &message["payload"];
+    [
+        field("id"),
+        field("waMessageId"),
+        field("chatId"),
+        field("fromMe"),
+        field("messageType"),
+        field("status"),
+        field("deliveryStatus"),
+        field("createdAt"),
+        payload_text(payload).unwrap_or_default().to_string(),
+        if payload.is_null() {
+            String::new()
+        } else {
+            payload.to_string()
+        },
+    ]
+    .iter()
+    .map(|cell| csv_cell(cell))
+    .collect::<Vec<_>>()
+    .join(",")
+}
+
+/// Schedules an export of `session`; returns the job id.
+pub async fn schedule(
+    state: &Arc<AppState>,
+    session: &str,
+    request: ExportRequest,
+) -> anyhow::Result<Uuid> {
+    let id = Uuid::new_v4();
+    state
+        .api_store
+        .execute(
+            "INSERT INTO api_export_jobs (id, session, format, filters, status) \
+             VALUES ($1, $2, $3, $4, 'queued')",
+            vec![
+                ApiBind::Uuid(id),
+                ApiBind::Text(session.to_string()),
+                ApiBind::Text(request.format.extension().to_string()),
+                ApiBind::Json(json!({
+                    "remoteJid": request.remote_jid,
+                    "from": request.from,
+                    "to": request.to,
+                })),
+            ],
+        )
+        .await?;
+
+    let state = state.clone();
+    let session = session.to_string();
+    tokio::spawn(async move {
+        run(&state, id, &session, &request).await;
+    });
+    Ok(id)
+}
+
+async fn run(state: &AppState, id: Uuid, session: &str, request: &ExportRequest) {
+    let config = &state.exports;
+    let path = config.file_path(id, request.format);
+    let partial = path.with_extension(format!("{}.part", request.format.extension()));
+    let _ = set_status(state, id, "running", json!({})).await;
+
+    let result = write(state, session, request, &partial).await;
+    let result = match result {
+        Ok(count) => tokio::fs::rename(&partial, &path)
+            .await
+            .map(|()| count)
+            .map_err(anyhow::Error::from),
+        Err(e) => Err(e),
+    };
+    let update = match result {
+        Ok(count) => {
+            let size = tokio::fs::metadata(&path).await.map_or(0, |m| m.len());
+            tracing::info!(session, job = %id, messages = count, "Exportação de conversas concluída");
+            set_status(state, id, "done", json!({"messages": count, "size": size})).await
+        }
+        Err(e) => {
+            tracing::warn!(session, job = %id, error = %e, "Falha na exportação de conversas");
+            let _ = tokio::fs::remove_file(&partial).await;
+            set_status(state, id, "failed", json!({"error": e.to_string()})).await
+        }
+    };
+    if let Err(e) = update {
+        tracing::warn!(job = %id, error = %e, "Falha ao atualizar job de exportação");
+    }
+}
+
+/// Writes the messages matching `request` to `path`; returns how many.
+async fn write(
+    state: &AppState,
+    session: &str,
+    request: &ExportRequest,
+    path: &std::path::Path,
+) -> anyhow::Result<u64> {
+    tokio::fs::create_dir_all(&state.exports.dir).await?;
+    let mut out = BufWriter::new(tokio::fs::File::create(path).await?);
+    out.write_all(match request.format {
+        ExportFormat::Json => b"[",
+        ExportFormat::Csv => CSV_HEADER.as_bytes(),
+    })
+    .await?;
+
+    let mut count: u64 = 0;
+    let mut cursor: Option<(String, String)> = None;
+    loop {
+        let page = page(state, session, request, cursor.as_ref()).await?;
+        for message in &page {
+            let line = match request.format {
+                ExportFormat::Json if count == 0 => format!("\n{message}"),
+                ExportFormat::Json => format!(",\n{message}"),
+                ExportFormat::Csv => format!("\n{}", csv_row(message)),
+            };
+            out.write_all(line.as_bytes()).await?;
+            count += 1;
+        }
+        let last = page.last().and_then(|message| {
+            Some((
+                message["createdAt"].as_str()?.to_string(),
+                message["id"].as_str()?.to_string(),
+            ))
+        });
+        match last {
+            Some(last) if page.len() == PAGE_SIZE => cursor = Some(last),
+            _ => break,
+        }
+    }
+
+    out.write_all(match request.format {
+        ExportFormat::Json => b"\n]\n",
+        ExportFormat::Csv => b"\n",
+    })
+    .await?;
+    out.flush().await?;
+    Ok(count)
+}
+
+/// Next page of messages after `cursor` (`createdAt`, `id`).
+async fn page(
+    state: &AppState,
+    session: &str,
+    request: &ExportRequest,
+    cursor: Option<&(String, String)>,
+) -> anyhow::Result<Vec<Value>> {
+    let rows = state
+        .api_store
+        .query_json(
+            "SELECT jsonb_build_object('id', id, 'waMessageId', wa_message_id, 'chatId', chat_id, \
+                'fromMe', from_me, 'messageType', message_type, 'status', status, \
+                'deliveryStatus', delivery_status, 'createdAt', created_at, 'payload', payload) as value \
+             FROM api_messages \
+             WHERE session = $1 \
+               AND ($2::text IS NULL OR chat_id = $2) \
+               AND ($3::timestamptz IS NULL OR created_at >= $3::timestamptz) \
+               AND ($4::timestamptz IS NULL OR created_at < $4::timestamptz) \
+               AND ($5::timestamptz IS NULL OR (created_at, id) > ($5::timestamptz, $6::uuid)) \
+             ORDER BY created_at, id \
+             LIMIT $7",
+            vec![
+                ApiBind::Text(session.to_string()),
+                ApiBind::NullableText(request.remote_jid.clone()),
+                ApiBind::NullableText(request.from.map(|date| date.to_rfc3339())),
+                ApiBind::NullableText(request.to.map(|date| date.to_rfc3339())),
+                ApiBind::NullableText(cursor.map(|(created_at, _)| created_at.clone())),
+                ApiBind::NullableText(cursor.map(|(_, id)| id.clone())),
+                ApiBind::Int(PAGE_SIZE as i32),
+            ],
+        )
+        .await?;
+    Ok(rows
+        .into_iter()
+        .map(|row| row.get("value").cloned().unwrap_or(row))
+        .collect())
+}
+
+async fn set_status(state: &AppState, id: Uuid, status: &str, result: Value) -> anyhow::Result<()> {
+    state
+        .api_store
+        .execute(
+            "UPDATE api_export_jobs SET status = $2, \
+                message_count = COALESCE(($3::jsonb ->> 'messages')::bigint, message_count), \
+                size_bytes = COALESCE(($3::jsonb ->> 'size')::bigint, size_bytes), \
+                error = $3::jsonb ->> 'error', \
+                finished_at = CASE WHEN $2 IN ('done', 'failed') THEN now() ELSE finished_at END \
+             WHERE id = $1",
+            vec![
+                ApiBind::Uuid(id),
+                ApiBind::Text(status.to_string()),
+                ApiBind::Json(result),
+            ],
+        )
+        .await?;
+    Ok(())
+}
+
+/// Job `id` as stored, `None` when unknown.
+pub async fn find(state: &AppState, id: Uuid) -> anyhow::Result<Option<Value>> {
+    let rows = state
+        .api_store
+        .query_json(
+            "SELECT jsonb_build_object('id', id, 'instance', session, 'format', format, \
+                'filters', filters, 'status', status, 'messages', message_count, \
+                'size', size_bytes, 'error', error, 'createdAt', created_at, \
+                'finishedAt', finished_at) as value \
+             FROM api_export_jobs WHERE id = $1",
+            vec![ApiBind::Uuid(id)],
+        )
+        .await?;
+    Ok(rows
+        .into_iter()
+        .next()
+        .map(|row| row.get("value").cloned().unwrap_or(row)))
+}
+
+/// File and content type of a finished job.
+pub fn download(config: &ExportConfig, job: &Value) -> Option<(PathBuf, ExportFormat)> {
+    if job["status"] != "done" {
+        return None;
+    }
+    let id = Uuid::parse_str(job["id"].as_str()?).ok()?;
+    let format = match job["format"].as_str()? {
+        "csv" => ExportFormat::Csv,
+        _ => ExportFormat::Json,
+    };
+    Some((config.file_path(id, format), format))
+}
+
+/// Deletes finished exports older than `EXPORT_TTL_HOURS` every few
+/// minutes and marks their jobs `expired`.
+pub fn spawn_sweeper(state: Arc<AppState>) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            match sweep(&state).await {
+                Ok(0) => {}
+                Ok(removed) => tracing::debug!(removed, "Exportações expiradas removidas"),
+                Err(e) => tracing::warn!(error = %e, "Falha ao limpar exportações"),
+            }
+            tokio::time::sleep(SWEEP_EVERY).await;
+        }
+    })
+}
+
+async fn sweep(state: &AppState) -> anyhow::Result<usize> {
+    let config = &state.exports;
+    let rows = state
+        .api_store
+        .query_json(
+            "WITH expired AS ( \
+                UPDATE api_export_jobs SET status = 'expired' \
+                WHERE status = 'done' AND finished_at < now() - $1::int * interval '1 second' \
+                RETURNING id, format \
+            ) SELECT jsonb_build_object('id', id, 'format', format) as value FROM expired",
+            vec![ApiBind::Int(
+                i32::try_from(config.ttl.as_secs()).unwrap_or(i32::MAX),
+            )],
+        )
+        .await?;
+    for row in &rows {
+        let row = row.get("value").unwrap_or(row);
+        let job = json!({"id": row["id"], "format": row["format"], "status": "done"});
+        if let Some((path, _)) = download(config, &job) {
+            let _ = tokio::fs::remove_file(path).await;
+        }
+    }
+    Ok(rows.len())
+}
+
+#[cfg(test)]
+mod tests {
+    include!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/tests/server/exports_tests.rs"));
+}
@@ -0,0 +1,530 @@
+//! Read-only GraphQL endpoint (`graphql` feature).
+//!
+//! `POST /graphql` answers queries over instances, chats, messages and
+//! contacts, so a dashboard can load every instance with its connection
+//! state, counts and last message in one request instead of one REST call
+//! per instance. Counts and last messages go through a [`DataLoader`]: the
+//! rows of one response are fetched with one query per kind, not one per
+//! instance or chat. `GET /graphql` returns the schema (SDL).
+//!
+//! Lists take the same `limit`, `offset` and `sort` as the REST endpoints
+//! (see [`pagination`](super::pagination)). Workspace keys only see their
+//! own instances. There are no mutations.
+
+use crate::api_store::ApiBind;
+use crate::server::AppState;
+use crate::server::connection::ConnectionState;
+use crate::server::instance_meta::INSTANCES;
+use crate::server::jid;
+use crate::server::pagination::{ListSpec, PageRequest};
+use crate::server::routes::chat::chat_manager::MESSAGES;
+use crate::server::routes::chat::messaging::CHATS;
+use crate::server::routes::contacts::CONTACTS;
+use crate::server::workspaces::{self, Scope};
+use async_graphql::dataloader::{DataLoader, Loader};
+use async_graphql::{
+    ComplexObject, Context, EmptyMutation, EmptySubscription, Error, Json as GqlJson, Object,
+    Result, Schema, SimpleObject,
+};
+use axum::{Extension, Json, Router, extract::State, routing::get};
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Deepest selection accepted, e.g. `instances { chats { lastMessage { id } } }` is 4.
+const MAX_DEPTH: usize = 6;
+/// Fields a single query may resolve.
+const MAX_COMPLEXITY: usize = 500;
+
+pub type ApiSchema = Schema<QueryRoot, EmptyMutation, EmptySubscription>;
+
+/// The schema with its depth and complexity limits; request data
+/// (state, viewer, loader) is attached per request.
+pub fn schema() -> ApiSchema {
+    Schema::build(QueryRoot, EmptyMutation, EmptySubscription)
+        .limit_depth(MAX_DEPTH)
+        .limit_complexity(MAX_COMPLEXITY)
+        .finish()
+}
+
+/// `/graphql`.
+pub fn router() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/graphql", get(sdl).post(execute))
+        .layer(Extension(schema()))
+}
+
+async fn sdl(Extension(schema): Extension<ApiSchema>) -> String {
+    schema.sdl()
+}
+
+async fn execute(
+    State(state): State<Arc<AppState>>,
+    Extension(schema): Extension<ApiSchema>,
+    scope: Option<Extension<Scope>>,
+    Json(request): Json<async_graphql::Request>,
+) -> Json<async_graphql::Response> {
+    let viewer = Viewer {
+        workspace: scope.and_then(|Extension(scope)| scope.workspace()),
+    };
+    // One loader per request, so nothing is cached across callers.
+    let loader = DataLoader::new(
+        StoreLoader {
+            state: state.clone(),
+        },
+        tokio::spawn,
+    );
+    let request = request.data(state).data(viewer).data(loader);
+    Json(schema.execute(request).await)
+}
+
+/// Who is asking; workspace keys are limited to their instances.
+struct Viewer {
+    workspace: Option<Uuid>,
+}
+
+fn store_state<'a>(ctx: &Context<'a>) -> Result<&'a Arc<AppState>> {
+    ctx.data::<Arc<AppState>>()
+}
+
+fn loader<'a>(ctx: &Context<'a>) -> Result<&'a DataLoader<StoreLoader>> {
+    ctx.data::<DataLoader<StoreLoader>>()
+}
+
+/// Errors like the REST `instance_not_found` unless the viewer may read
+/// `instance`.
+async fn ensure_visible(ctx: &Context<'_>, instance: &str) -> Result<()> {
+    let Some(workspace) = ctx.data::<Viewer>()?.workspace else {
+        return Ok(());
+    };
+    match workspaces::instance_owner(store_state(ctx)?, instance).await? {
+        Some(Some(owner)) if owner == workspace => Ok(()),
+        _ => Err(Error::new("instance_not_found")),
+    }
+}
+
+/// A page request from GraphQL arguments, validated like the REST query.
+fn page_request(
+    spec: &ListSpec,
+    limit: Option<i32>,
+    offset: Option<i32>,
+    sort: Option<String>,
+) -> Result<PageRequest> {
+    Ok(PageRequest::from_body(
+        spec,
+        &json!({"limit": limit, "offset": offset, "sort": sort}),
+    )?)
+}
+
+fn decode<T: DeserializeOwned>(rows: Vec<Value>) -> Result<Vec<T>> {
+    rows.into_iter()
+        .map(|row| Ok(serde_json::from_value(row)?))
+        .collect()
+}
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    /// Instances visible to the caller, optionally with all of `tags`.
+    async fn instances(
+        &self,
+        ctx: &Context<'_>,
+        tags: Option<Vec<String>>,
+        limit: Option<i32>,
+        offset: Option<i32>,
+        sort: Option<String>,
+    ) -> Result<Vec<Instance>> {
+        let page = page_request(&INSTANCES, limit, offset, sort)?;
+        let workspace = ctx.data::<Viewer>()?.workspace;
+        list_instances(ctx, &page, workspace, None, tags.unwrap_or_default()).await
+    }
+
+    async fn instance(&self, ctx: &Context<'_>, name: String) -> Result<Option<Instance>> {
+        let page = page_request(&INSTANCES, Some(1), None, None)?;
+        let workspace = ctx.data::<Viewer>()?.workspace;
+        let instances = list_instances(ctx, &page, workspace, Some(name), Vec::new()).await?;
+        Ok(instances.into_iter().next())
+    }
+
+    async fn chats(
+        &self,
+        ctx: &Context<'_>,
+        instance: String,
+        limit: Option<i32>,
+        offset: Option<i32>,
+        sort: Option<String>,
+    ) -> Result<Vec<Chat>> {
+        ensure_visible(ctx, &instance).await?;
+        let page = page_request(&CHATS, limit, offset, sort)?;
+        list_chats(ctx, &instance, &page).await
+    }
+
+    /// Messages of `instance`, of one chat when `chatId` is given (also
+    /// under the contact's LID or phone number).
+    async fn messages(
+        &self,
+        ctx: &Context<'_>,
+        instance: String,
+        chat_id: Option<String>,
+        limit: Option<i32>,
+        offset: Option<i32>,
+        sort: Option<String>,
+    ) -> Result<Vec<Message>> {
+        ensure_visible(ctx, &instance).await?;
+        let page = page_request(&MESSAGES, limit, offset, sort)?;
+        list_messages(ctx, &instance, chat_id, &page).await
+    }
+
+    async fn contacts(
+        &self,
+        ctx: &Context<'_>,
+        instance: String,
+        limit: Option<i32>,
+        offset: Option<i32>,
+        sort: Option<String>,
+    ) -> Result<Vec<Contact>> {
+        ensure_visible(ctx, &instance).await?;
+        let page = page_request(&CONTACTS, limit, offset, sort)?;
+        let (rows, _) = page
+            .fetch(
+                store_state(ctx)?,
+                "row_to_json(api_contacts)::jsonb",
+                "FROM api_contacts WHERE session = $1",
+                vec![ApiBind::Text(instance)],
+            )
+            .await?;
+        decode(rows)
+    }
+}
+
+async fn list_instances(
+    ctx: &Context<'_>,
+    page: &PageRequest,
+    workspace: Option<Uuid>,
+    name: Option<String>,
+    tags: Vec<String>,
+) -> Result<Vec<Instance>> {
+    let (rows, _) = page
+        .fetch(
+            store_state(ctx)?,
+            "row_to_json(api_sessions)::jsonb - 'webhook_secret' - 'cloud_access_token'",
+            "FROM api_sessions \
+             WHERE ($1::uuid IS NULL OR workspace_id = $1::uuid) \
+               AND ($2::text IS NULL OR session = $2) \
+               AND tags @> $3::jsonb",
+            vec![
+                ApiBind::NullableText(workspace.map(|id| id.to_string())),
+                ApiBind::NullableText(name),
+                ApiBind::Json(json!(tags)),
+            ],
+        )
+        .await?;
+    decode(rows)
+}
+
+async fn list_chats(ctx: &Context<'_>, instance: &str, page: &PageRequest) -> Result<Vec<Chat>> {
+    let (rows, _) = page
+        .fetch(
+            store_state(ctx)?,
+            "row_to_json(api_chats)::jsonb",
+            "FROM api_chats WHERE session = $1",
+            vec![ApiBind::Text(instance.to_string())],
+        )
+        .await?;
+    decode(rows)
+}
+
+async fn list_messages(
+    ctx: &Context<'_>,
+    instance: &str,
+    chat_id: Option<String>,
+    page: &PageRequest,
+) -> Result<Vec<Message>> {
+    let state = store_state(ctx)?;
+    let (from, binds) = match chat_id {
+        Some(chat_id) => {
+            let (chat_id, alternate) = jid::chat_aliases(state, instance, &chat_id).await?;
+            (
+                "FROM api_messages WHERE session = $1 AND (chat_id = $2 OR chat_id = $3)",
+                vec![
+                    ApiBind::Text(instance.to_string()),
+                    ApiBind::Text(chat_id),
+                    ApiBind::NullableText(alternate),
+                ],
+            )
+        }
+        None => (
+            "FROM api_messages WHERE session = $1",
+            vec![ApiBind::Text(instance.to_string())],
+        ),
+    };
+    let (rows, _) = page
+        .fetch(state, "row_to_json(api_messages)::jsonb", from, binds)
+        .await?;
+    decode(rows)
+}
+
+/// An `api_sessions` row.
+#[derive(Debug, Clone, Deserialize, SimpleObject)]
+#[graphql(complex)]
+pub struct Instance {
+    #[serde(rename = "session")]
+    pub name: String,
+    pub status: Option<String>,
+    pub phone_number: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub metadata: GqlJson<Value>,
+    pub created_at: Option<String>,
+    pub updated_at: Option<String>,
+}
+
+#[ComplexObject]
+impl Instance {
+    /// `open`, `connecting` or `close`, as in `/instance/fetchInstances`.
+    async fn connection_status(&self, ctx: &Context<'_>) -> Result<String> {
+        let status = match store_state(ctx)?.instances.get(&self.name) {
+            Some(instance) => instance.connection_state.read().await.state(),
+            None => ConnectionState::Disconnected,
+        };
+        Ok(status.evolution_state().to_string())
+    }
+
+    /// Messages, chats and contacts stored for the instance.
+    async fn counts(&self, ctx: &Context<'_>) -> Result<Counts> {
+        Ok(loader(ctx)?
+            .load_one(CountsOf(self.name.clone()))
+            .await?
+            .unwrap_or_default())
+    }
+
+    async fn last_message(&self, ctx: &Context<'_>) -> Result<Option<Message>> {
+        Ok(loader(ctx)?
+            .load_one(LastMessageOf(self.name.clone()))
+            .await?)
+    }
+
+    async fn chats(
+        &self,
+        ctx: &Context<'_>,
+        limit: Option<i32>,
+        offset: Option<i32>,
+        sort: Option<String>,
+    ) -> Result<Vec<Chat>> {
+        let page = page_request(&CHATS, limit, offset, sort)?;
+        list_chats(ctx, &self.name, &page).await
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize, SimpleObject)]
+pub struct Counts {
+    pub messages: i64,
+    pub chats: i64,
+    pub contacts: i64,
+}
+
+/// An `api_chats` row.
+#[derive(Debug, Clone, Deserialize, SimpleObject)]
+#[graphql(complex)]
+pub struct Chat {
+    #[serde(rename = "session")]
+    pub instance: String,
+    pub id: String,
+    pub title: Option<String>,
+    pub last_message_at: Option<String>,
+    #[serde(default)]
+    pub unread_count: i32,
+    #[serde(default)]
+    pub archived: bool,
+    #[serde(default)]
+    pub pinned: bool,
+}
+
+#[ComplexObject]
+impl Chat {
+    async fn last_message(&self, ctx: &Context<'_>) -> Result<Option<Message>> {
+        Ok(loader(ctx)?
+            .load_one(ChatLastMessageOf {
+                instance: self.instance.clone(),
+                chat_id: self.id.clone(),
+            })
+            .await?)
+    }
+
+    async fn messages(
+        &self,
+        ctx: &Context<'_>,
+        limit: Option<i32>,
+        offset: Option<i32>,
+        sort: Option<String>,
+    ) -> Result<Vec<Message>> {
+        let page = page_request(&MESSAGES, limit, offset, sort)?;
+        list_messages(ctx, &self.instance, Some(self.id.clone()), &page).await
+    }
+}
+
+/// An `api_messages` row.
+#[derive(Debug, Clone, Deserialize, SimpleObject)]
+pub struct Message {
+    pub id: String,
+    #[serde(rename = "session")]
+    pub instance: String,
+    pub chat_id: Option<String>,
+    #[serde(default)]
+    pub from_me: bool,
+    pub message_type: Option<String>,
+    pub status: Option<String>,
+    pub delivery_status: Option<String>,
+    pub wa_message_id: Option<String>,
+    #[serde(default)]
+    pub payload: GqlJson<Value>,
+    pub created_at: Option<String>,
+}
+
+/// An `api_contacts` row.
+#[derive(Debug, Clone, Deserialize, SimpleObject)]
+pub struct Contact {
+    #[serde(rename = "session")]
+    pub instance: String,
+    pub id: String,
+    pub name: Option<String>,
+    #[serde(default)]
+    pub exists: bool,
+    pub profile_picture_url: Option<String>,
+    pub updated_at: Option<String>,
+}
+
+/// Counts of an instance.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CountsOf(pub String);
+
+/// Newest message of an instance.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct LastMessageOf(pub String);
+
+/// Newest message of a chat.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ChatLastMessageOf {
+    pub instance: String,
+    pub chat_id: String,
+}
+
+/// Batches the per-row lookups of a response into one store query per key
+/// type.
+pub struct StoreLoader {
+    state: Arc<AppState>,
+}
+
+impl StoreLoader {
+    async fn query(&self, sql: &str, keys: Value) -> Result<Vec<Value>, Arc<anyhow::Error>> {
+        let rows = self
+            .state
+            .api_store
+            .query_json(sql, vec![ApiBind::Json(keys)])
+            .await
+            .map_err(Arc::new)?;
+        Ok(rows
+            .iter()
+            .map(|row| row.get("value").unwrap_or(row).clone())
+            .collect())
+    }
+}
+
+impl Loader<CountsOf> for StoreLoader {
+    type Value = Counts;
+    type Error = Arc<anyhow::Error>;
+
+    async fn load(&self, keys: &[CountsOf]) -> Result<HashMap<CountsOf, Counts>, Self::Error> {
+        let names: Vec<&str> = keys.iter().map(|key| key.0.as_str()).collect();
+        let rows = self
+            .query(
+                "SELECT jsonb_build_object( \
+                    'session', s.session, \
+                    'messages', (SELECT count(*) FROM api_messages m WHERE m.session = s.session), \
+                    'chats', (SELECT count(*) FROM api_chats c WHERE c.session = s.session), \
+                    'contacts', (SELECT count(*) FROM api_contacts c WHERE c.session = s.session) \
+                 ) AS value FROM jsonb_array_elements_text($1::jsonb) AS s(session)",
+                json!(names),
+            )
+            .await?;
+        Ok(rows
+            .into_iter()
+            .filter_map(|row| {
+                let name = row["session"].as_str()?.to_string();
+                Some((CountsOf(name), serde_json::from_value(row).ok()?))
+            })
+            .collect())
+    }
+}
+
+impl Loader<LastMessageOf> for StoreLoader {
+    type Value = Message;
+    type Error = Arc<anyhow::Error>;
+
+    async fn load(
+        &self,
+        keys: &[LastMessageOf],
+    ) -> Result<HashMap<LastMessageOf, Message>, Self::Error> {
+        let names: Vec<&str> = keys.iter().map(|key| key.0.as_str()).collect();
+        let rows = self
+            .query(
+                "SELECT DISTINCT ON (session) row_to_json(api_messages)::jsonb AS value \
+                 FROM api_messages \
+                 WHERE session IN (SELECT jsonb_array_elements_text($1::jsonb)) \
+                 ORDER BY session, created_at DESC",
+                json!(names),
+            )
+            .await?;
+        Ok(rows
+            .into_iter()
+            .filter_map(|row| serde_json::from_value::<Message>(row).ok())
+            .map(|message| (LastMessageOf(message.instance.clone()), message))
+            .collect())
+    }
+}
+
+impl Loader<ChatLastMessageOf> for StoreLoader {
+    type Value = Message;
+    type Error = Arc<anyhow::Error>;
+
+    async fn load(
+        &self,
+        keys: &[ChatLastMessageOf],
+    ) -> Result<HashMap<ChatLastMessageOf, Message>, Self::Error> {
+        let pairs: Vec<Value> = keys
+            .iter()
+            .map(|key| json!({"session": key.instance, "chat_id": key.chat_id}))
+            .collect();
+        let rows = self
+            .query(
+                "SELECT DISTINCT ON (m.session, m.chat_id) row_to_json(m)::jsonb AS value \
+                 FROM api_messages m \
+                 JOIN jsonb_to_recordset($1::jsonb) AS k(session text, chat_id text) \
+                   ON m.session = k.session AND m.chat_id = k.chat_id \
+                 ORDER BY m.session, m.chat_id, m.created_at DESC",
+                Value::Array(pairs),
+            )
+            .await?;
+        Ok(rows
+            .into_iter()
+            .filter_map(|row| serde_json::from_value::<Message>(row).ok())
+            .filter_map(|message| {
+                let key = ChatLastMessageOf {
+                    instance: message.instance.clone(),
+                    chat_id: message.chat_id.clone()?,
+                };
+                Some((key, message))
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    include!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/tests/server/graphql_tests.rs"));
+}
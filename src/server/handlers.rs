@@ -1,26 +1,119 @@
+use crate::api_store::ApiBind;
+use crate::client::MAX_CONNECTION_ATTEMPTS;
+use crate::features::{self, EPHEMERAL_DURATIONS, GroupSetting, MemberAddMode};
+use crate::models::api_model::{
+    CreateInstance, FindMessages, InstanceCreated, SendText, WhatsappNumbers,
+};
 use crate::openapi::{openapi_document, swagger_ui};
 use crate::server::AppState;
+use crate::server::audit;
+use crate::server::auto_rules;
+use crate::server::cache::{self, CacheSpace};
+use crate::server::chat_settings::{self, ChatAction};
+use crate::server::cloud_api;
+use crate::server::connection::ConnectionState;
+use crate::server::contact_sync::{self, ContactSyncError};
+use crate::server::deadletter;
+use crate::server::event_bus;
+use crate::server::event_filters;
+use crate::server::events::{self, ChatsUpdate, EventPayload};
+use crate::server::evolution;
+use crate::server::exports::{self, ExportRequest};
+use crate::server::inbound;
+use crate::server::instance_deletion::{self, DeletionOptions};
+use crate::server::instance_meta;
+use crate::server::jid;
+use crate::server::maintenance::{self, MaintenanceWindow};
+use crate::server::media::{self, MediaError};
+use crate::server::message_counters;
+use crate::server::message_status;
+use crate::server::numbers;
+use crate::server::pagination::{self, PageRequest};
+use crate::server::profile_pictures;
+use crate::server::qr::{self, QrRenderOptions};
+use crate::server::quotas;
+use crate::server::reactions;
+use crate::server::retention::{self, RetentionPolicy};
+use crate::server::routes::chat::chat_manager;
+use crate::server::routes::sessions;
+use crate::server::runtime_config::{self, RuntimeConfigError};
+use crate::server::standby::{self, StandbyError};
+use crate::server::static_files;
+use crate::server::status;
+use crate::server::templates::{self, TemplateError};
+use crate::server::uploads::{self, UploadError};
+use crate::server::versioning;
+use crate::server::webhooks;
+use crate::server::workspaces::{self, Scope};
+use crate::version;
 use axum::{
     Json,
-    extract::{Path, State},
-    http::StatusCode,
-    response::{Html, IntoResponse},
+    body::Body,
+    extract::{Extension, Path, Query, State},
+    http::{HeaderMap, StatusCode, header},
+    response::{Html, IntoResponse, Response},
 };
 use serde_json::{Value, json};
+use std::collections::HashMap;
 use std::sync::Arc;
+use std::sync::atomic::Ordering;
+use warp_core_binary::jid::Jid;
+use waproto::whatsapp as wa;
 
-pub async fn openapi_handler() -> Json<Value> {
-    Json(openapi_document())
+/// OpenAPI document with the paths as currently mounted.
+pub async fn openapi_handler(State(state): State<Arc<AppState>>) -> Json<Value> {
+    Json(versioning::mount_openapi(openapi_document(), state.api_mount))
 }
 
 pub async fn swagger_handler() -> Html<&'static str> {
     swagger_ui()
 }
 
-pub async fn metrics_handler() -> Json<Value> {
+/// JSON schema of the event envelope and the typed event payloads.
+pub async fn event_schema_handler() -> Json<Value> {
+    Json(events::schema_document())
+}
+
+pub async fn metrics_handler(State(state): State<Arc<AppState>>) -> Json<Value> {
+    let wa_versions: serde_json::Map<String, Value> = state
+        .clients
+        .iter()
+        .map(|entry| {
+            let version = entry
+                .value()
+                .version_manager
+                .current()
+                .map(version::format_version);
+            (entry.key().clone(), json!(version))
+        })
+        .collect();
+    let quotas = match quotas::usage(&state).await {
+        Ok(usage) => quotas::metrics(&usage),
+        Err(e) => {
+            tracing::debug!(error = %e, "Uso de cotas indisponível");
+            Value::Null
+        }
+    };
+
     Json(json!({
-        "uptime_seconds": 0,
-        "instances_total": 0,
+        "uptime_seconds": (chrono::Utc::now() - state.started_at).num_seconds().max(0),
+        "instances_total": state.clients.len(),
+        "quotas": quotas,
+        "wa_versions": wa_versions,
+        "db_pool": state.api_store.pool_stats(),
+        "ws_clients": state.event_hub.connected_clients(),
+        "event_sinks": event_bus::sink_counts(&state)
+            .into_iter()
+            .map(|(sink, counts)| (sink.to_string(), json!(counts)))
+            .collect::<serde_json::Map<_, _>>(),
+        "http_open_circuits": state.http.open_circuits(),
+        "retention": state.retention_metrics.snapshot(),
+        "inbound_duplicates": state
+            .inbound_dedup
+            .hits()
+            .into_iter()
+            .map(|(instance, hits)| (instance, json!(hits)))
+            .collect::<serde_json::Map<_, _>>(),
         "requests_total": 0,
         "inflight_requests": 0,
         "responses_2xx": 0,
@@ -30,144 +123,2867 @@ pub async fn metrics_handler() -> Json<Value> {
     }))
 }
 
-pub async fn create_instance(
-    State(_state): State<Arc<AppState>>,
+/// Current runtime config. Requires `CHATWARP_PASSWORD` (the admin key).
+pub async fn get_manager_config(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    if state.api_password_hash.is_none() {
+        return admin_key_required();
+    }
+    (StatusCode::OK, Json(json!(state.runtime_config())))
+}
+
+/// Applies a partial runtime config update without restarting and persists it.
+pub async fn patch_manager_config(
+    State(state): State<Arc<AppState>>,
     Json(payload): Json<Value>,
 ) -> impl IntoResponse {
-    let name = payload["name"].as_str().unwrap_or("");
-    if name.is_empty() {
+    if state.api_password_hash.is_none() {
+        return admin_key_required();
+    }
+    match runtime_config::update(&state, &payload).await {
+        Ok((config, persisted)) => (
+            StatusCode::OK,
+            Json(json!({"config": config, "persisted": persisted})),
+        ),
+        Err(RuntimeConfigError::LogLevel(details)) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": "log_level_reload_failed", "details": details})),
+        ),
+        Err(e) => (
+            StatusCode::BAD_REQUEST,
+            Json(json!({"error": "invalid_config", "details": e.to_string()})),
+        ),
+    }
+}
+
+/// Configured quotas and current usage per instance and workspace.
+pub async fn get_manager_quotas(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    if state.api_password_hash.is_none() {
+        return admin_key_required();
+    }
+    match quotas::usage(&state).await {
+        Ok(usage) => (StatusCode::OK, Json(usage)),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": "db_error", "details": e.to_string()})),
+        ),
+    }
+}
+
+/// Build, configuration, dependency health and instance counts of this
+/// deployment.
+pub async fn get_manager_status(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    if state.api_password_hash.is_none() {
+        return admin_key_required();
+    }
+    (StatusCode::OK, Json(json!(status::collect(&state).await)))
+}
+
+/// Promotes a standby node: takes the instance lease and starts the
+/// connection once the previous holder's lease has run out. `?force=true`
+/// takes a lease another node still holds, fencing that node.
+pub async fn promote_manager(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<HashMap<String, String>>,
+) -> impl IntoResponse {
+    if state.api_password_hash.is_none() {
+        return admin_key_required();
+    }
+    let force = query.get("force").is_some_and(|v| v == "true" || v == "1");
+    match standby::promote(&state, force).await {
+        Ok((epoch, wait)) => (
+            StatusCode::ACCEPTED,
+            Json(json!({
+                "role": state.standby.role(),
+                "instance": state.standby.instance(),
+                "epoch": epoch,
+                "startsInMs": wait.as_millis() as u64,
+            })),
+        ),
+        Err(e @ StandbyError::Disabled) => (
+            StatusCode::CONFLICT,
+            Json(json!({"error": "standby_disabled", "details": e.to_string()})),
+        ),
+        Err(StandbyError::NotStandby(role)) => (
+            StatusCode::CONFLICT,
+            Json(json!({"error": "not_standby", "role": role})),
+        ),
+        Err(StandbyError::Held { holder, remaining }) => (
+            StatusCode::CONFLICT,
+            Json(json!({
+                "error": "lease_held",
+                "holder": holder,
+                "remainingMs": remaining.as_millis() as u64,
+            })),
+        ),
+        Err(StandbyError::Store(e)) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": "db_error", "details": e.to_string()})),
+        ),
+    }
+}
+
+/// Audit entries (newest first). Query: `from`, `to` (RFC 3339), `instance`, `limit`.
+pub async fn get_manager_audit(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<HashMap<String, String>>,
+) -> impl IntoResponse {
+    if state.api_password_hash.is_none() {
+        return admin_key_required();
+    }
+
+    let mut bounds = [None, None];
+    for (bound, key) in bounds.iter_mut().zip(["from", "to"]) {
+        let Some(value) = query.get(key).filter(|v| !v.is_empty()) else {
+            continue;
+        };
+        match chrono::DateTime::parse_from_rfc3339(value) {
+            Ok(parsed) => *bound = Some(parsed.to_rfc3339()),
+            Err(_) => {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    Json(json!({"error": "invalid_timestamp", "field": key})),
+                );
+            }
+        }
+    }
+    let [from, to] = bounds;
+
+    let filter = audit::AuditFilter {
+        from,
+        to,
+        instance: query.get("instance").filter(|v| !v.is_empty()).cloned(),
+        limit: query
+            .get("limit")
+            .and_then(|v| v.parse::<i32>().ok())
+            .unwrap_or(audit::DEFAULT_AUDIT_LIMIT)
+            .clamp(1, audit::MAX_AUDIT_LIMIT),
+    };
+
+    match audit::query(&state, filter).await {
+        Ok(entries) => (
+            StatusCode::OK,
+            Json(json!({"count": entries.len(), "entries": entries})),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": "db_error", "details": e.to_string()})),
+        ),
+    }
+}
+
+/// Webhook delivery attempts of an instance (newest first).
+/// Query: `event`, `failed=true`, `limit`.
+pub async fn get_webhook_deliveries(
+    Path(instance): Path<String>,
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<HashMap<String, String>>,
+) -> impl IntoResponse {
+    let limit = query
+        .get("limit")
+        .and_then(|v| v.parse::<i32>().ok())
+        .unwrap_or(webhooks::DEFAULT_DELIVERIES_LIMIT)
+        .clamp(1, webhooks::MAX_DELIVERIES_LIMIT);
+    let event = query.get("event").filter(|v| !v.is_empty()).cloned();
+    let failed_only = query.get("failed").is_some_and(|v| v == "true" || v == "1");
+
+    match webhooks::list_deliveries(&state, &instance, event, failed_only, limit).await {
+        Ok(deliveries) => (
+            StatusCode::OK,
+            Json(json!({"instance": instance, "count": deliveries.len(), "deliveries": deliveries})),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": "db_error", "details": e.to_string()})),
+        ),
+    }
+}
+
+/// Queues the payload of a past delivery again for the instance's current
+/// webhook targets.
+pub async fn redeliver_webhook(
+    Path(delivery_id): Path<String>,
+    State(state): State<Arc<AppState>>,
+    scope: Option<Extension<Scope>>,
+) -> impl IntoResponse {
+    let Ok(delivery_id) = uuid::Uuid::parse_str(&delivery_id) else {
         return (
             StatusCode::BAD_REQUEST,
-            Json(json!({"error": "invalid_name"})),
+            Json(json!({"error": "invalid_id"})),
         );
+    };
+    let workspace = scope.and_then(|Extension(scope)| scope.workspace());
+
+    match webhooks::redeliver(&state, delivery_id, workspace).await {
+        Ok(Some(outbox_id)) => (
+            StatusCode::ACCEPTED,
+            Json(json!({"deliveryId": delivery_id, "outboxId": outbox_id, "status": "queued"})),
+        ),
+        Ok(None) => (
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "delivery_not_found"})),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": "db_error", "details": e.to_string()})),
+        ),
+    }
+}
+
+/// Events of an instance that exhausted their delivery attempts.
+/// Query: `all=true` includes already replayed entries, `limit`.
+pub async fn get_deadletters(
+    Path(instance): Path<String>,
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<HashMap<String, String>>,
+) -> impl IntoResponse {
+    let limit = query
+        .get("limit")
+        .and_then(|v| v.parse::<i32>().ok())
+        .unwrap_or(deadletter::DEFAULT_LIMIT)
+        .clamp(1, deadletter::MAX_LIMIT);
+    let include_replayed = query.get("all").is_some_and(|v| v == "true" || v == "1");
+
+    match deadletter::list(&state, &instance, include_replayed, limit).await {
+        Ok(events) => (
+            StatusCode::OK,
+            Json(json!({"instance": instance, "count": events.len(), "events": events})),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": "db_error", "details": e.to_string()})),
+        ),
+    }
+}
+
+/// Delivery status of a queued message, by the id returned when it was
+/// queued or by its WhatsApp id.
+pub async fn message_status(
+    Path((instance, message_id)): Path<(String, String)>,
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    match message_status::find(&state, &instance, &message_id).await {
+        Ok(Some(mut status)) => {
+            status["instance"] = json!(instance);
+            (StatusCode::OK, Json(status))
+        }
+        Ok(None) => (
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "message_not_found"})),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": "db_error", "details": e.to_string()})),
+        ),
+    }
+}
+
+/// Streams the raw request body to disk and returns a `mediaId` that the
+/// send endpoints accept instead of `url`/`base64`. `Content-Type` is kept
+/// as the mimetype and `?fileName=` as the document name.
+pub async fn upload_media(
+    Path(instance): Path<String>,
+    Query(query): Query<HashMap<String, String>>,
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    body: Body,
+) -> impl IntoResponse {
+    let limit = state
+        .uploads
+        .limit(quotas::media_limit(&state.runtime_config()));
+    let too_large = || {
+        (
+            StatusCode::PAYLOAD_TOO_LARGE,
+            Json(json!({"error": "media_too_large", "limit": limit})),
+        )
+    };
+    let declared = headers
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok());
+    if declared.is_some_and(|size| size > limit) {
+        return too_large();
+    }
+
+    let mimetype = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.split(';').next().unwrap_or(v).trim().to_string())
+        .filter(|v| !v.is_empty() && v != "application/octet-stream");
+    let file_name = query.get("fileName").cloned().filter(|v| !v.is_empty());
+
+    match uploads::spool(
+        &state.uploads,
+        &instance,
+        mimetype,
+        file_name,
+        body.into_data_stream(),
+        limit,
+    )
+    .await
+    {
+        Ok(upload) => (StatusCode::CREATED, Json(json!(upload))),
+        Err(UploadError::TooLarge { .. }) => too_large(),
+        Err(UploadError::Body(details)) => (
+            StatusCode::BAD_REQUEST,
+            Json(json!({"error": "upload_interrupted", "details": details})),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": "upload_failed", "details": e.to_string()})),
+        ),
+    }
+}
+
+/// Returns an upload of `/media/upload`, with ETag revalidation and
+/// `Range` support so players can seek.
+pub async fn download_upload(
+    Path((instance, media_id)): Path<(String, String)>,
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Response {
+    match uploads::find(&state.uploads, &instance, &media_id).await {
+        Ok((upload, path)) => {
+            let content_type = upload
+                .mimetype
+                .as_deref()
+                .unwrap_or("application/octet-stream");
+            static_files::serve_file(
+                &state.file_cache,
+                &path,
+                content_type,
+                "private, no-cache",
+                &headers,
+            )
+            .await
+        }
+        Err(UploadError::NotFound) => (
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "upload_not_found"})),
+        )
+            .into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": "upload_failed", "details": e.to_string()})),
+        )
+            .into_response(),
     }
+}
+
+/// Feeds pending dead-lettered events back through the event pipeline.
+/// Body (optional): `{"ids": [...]}` to replay only those entries.
+pub async fn replay_deadletters(
+    Path(instance): Path<String>,
+    State(state): State<Arc<AppState>>,
+    body: Option<Json<Value>>,
+) -> impl IntoResponse {
+    let ids = match deadletter::ids_from_body(body.as_ref().map(|Json(b)| b)) {
+        Ok(ids) => ids,
+        Err(details) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": "invalid_ids", "details": details})),
+            );
+        }
+    };
+
+    match deadletter::replay(&state, &instance, ids).await {
+        Ok(replayed) => (
+            StatusCode::ACCEPTED,
+            Json(json!({"instance": instance, "count": replayed.len(), "replayed": replayed})),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": "db_error", "details": e.to_string()})),
+        ),
+    }
+}
+
+fn admin_key_required() -> (StatusCode, Json<Value>) {
+    (
+        StatusCode::FORBIDDEN,
+        Json(json!({"error": "admin_key_required"})),
+    )
+}
+
+/// Creates an instance from an Evolution `instance/create` body, the same
+/// way as `POST /sessions` (see [`create_body`]).
+pub async fn create_instance(
+    State(state): State<Arc<AppState>>,
+    scope: Option<Extension<Scope>>,
+    Json(payload): Json<CreateInstance>,
+) -> impl IntoResponse {
+    let Some(body) = create_body(&payload) else {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({"error": "invalid_name"})),
+        );
+    };
+    let name = body["session"].as_str().unwrap_or_default().to_string();
 
-    // Logic to create instance would go here
+    let (status, Json(row)) =
+        sessions::create_session(State(state.clone()), scope, Json(body)).await;
+    if status != StatusCode::CREATED {
+        return (status, Json(row));
+    }
+    if state.runtime_config().evolution_compat {
+        let qrcode = evolution_qrcode(&state, &name).await;
+        return (StatusCode::CREATED, Json(evolution::created(&row, qrcode)));
+    }
     (
         StatusCode::CREATED,
-        Json(json!({"instance": name, "status": "created"})),
+        Json(json!(InstanceCreated {
+            instance: name,
+            status: "created".to_string(),
+        })),
     )
 }
 
+/// `POST /sessions` body from an Evolution `instance/create` one. `None`
+/// without a name.
+fn create_body(payload: &CreateInstance) -> Option<Value> {
+    let name = payload.instance_name.trim();
+    if name.is_empty() {
+        return None;
+    }
+    let integration = payload
+        .integration
+        .as_deref()
+        .unwrap_or(cloud_api::DEFAULT_INTEGRATION);
+    let mut body = json!({"session": name, "integration": integration});
+    if integration == cloud_api::INTEGRATION {
+        body["number"] = json!(payload.number);
+        body["token"] = json!(payload.token);
+        body["businessId"] = json!(payload.business_id);
+    } else if let Some(number) = &payload.number {
+        body["phone_number"] = json!(number);
+    }
+    if let Some(webhook) = payload.webhook.as_ref().filter(|w| !w.url.is_empty()) {
+        body["webhook"] = json!({
+            "url": webhook.url,
+            "webhookByEvents": webhook.by_events,
+            "webhookBase64": webhook.base64,
+        });
+        if let Some(headers) = &webhook.headers {
+            body["webhook"]["headers"] = json!(headers);
+        }
+        if let Some(events) = &webhook.events {
+            body["webhook"]["events"] = json!(events);
+        }
+    }
+    if let Some(tags) = &payload.tags {
+        body["tags"] = json!(tags);
+    }
+    if let Some(metadata) = &payload.metadata {
+        body["metadata"] = json!(metadata);
+    }
+    if let Some(browser) = &payload.browser {
+        body["browser"] = browser.clone();
+    }
+    if let Some(wa_version) = &payload.wa_version {
+        body["waVersion"] = json!(wa_version);
+    }
+    Some(body)
+}
+
+/// Evolution `qrcode` object with the pending QR and pair code of `name`.
+async fn evolution_qrcode(state: &AppState, name: &str) -> Value {
+    let pending = state
+        .instances
+        .get(name)
+        .map(|instance| (instance.qr_code.clone(), instance.qr_count.clone()));
+    let (code, count) = match pending {
+        Some((code, count)) => (code.read().await.clone(), *count.read().await),
+        None => (None, 0),
+    };
+    let pair_code = state
+        .sessions_runtime
+        .get(name)
+        .and_then(|runtime| runtime.pair_code.clone());
+    evolution::qrcode(code.as_deref(), pair_code.as_deref(), count)
+}
+
+/// Deletes or disables an instance in steps chosen by the body (or
+/// `?mode=`), see [`instance_deletion`].
 pub async fn delete_instance(
     Path(name): Path<String>,
-    State(_state): State<Arc<AppState>>,
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<HashMap<String, String>>,
+    body: Option<Json<Value>>,
 ) -> impl IntoResponse {
-    (
-        StatusCode::OK,
-        Json(json!({"instance": name, "status": "deleted"})),
-    )
+    let options = match (body, query.get("mode")) {
+        (Some(Json(body)), _) => DeletionOptions::from_body(Some(&body)),
+        (None, Some(mode)) => DeletionOptions::from_mode(mode),
+        (None, None) => Ok(DeletionOptions::default()),
+    };
+    let options = match options {
+        Ok(options) => options,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": "invalid_deletion_options", "details": e.to_string()})),
+            );
+        }
+    };
+    match instance_deletion::exists(&state, &name).await {
+        Ok(true) => {}
+        Ok(false) => {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(json!({"error": "instance_not_found"})),
+            );
+        }
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": "db_error", "details": e.to_string()})),
+            );
+        }
+    }
+
+    let deletion = instance_deletion::run(&state, &name, options).await;
+    (deletion.status_code(), Json(deletion.to_json()))
 }
 
-pub async fn connection_state(
+/// Instances filtered by name, tags and metadata, with their connection
+/// status. Workspace keys only see their own instances.
+pub async fn fetch_instances(
+    State(state): State<Arc<AppState>>,
+    scope: Option<Extension<Scope>>,
+    Query(query): Query<HashMap<String, String>>,
+) -> impl IntoResponse {
+    let filter = instance_meta::InstanceFilter::from_query(&query);
+    let page = match PageRequest::from_query(&instance_meta::INSTANCES, &query) {
+        Ok(page) => page,
+        Err(e) => return pagination::invalid_pagination(e),
+    };
+    let workspace = scope
+        .and_then(|Extension(scope)| scope.workspace())
+        .map(|id| id.to_string());
+
+    // The connection state, counters and picture below are live; only the
+    // rows are cached.
+    let cache_scope = workspace.clone().unwrap_or_else(|| "all".to_string());
+    let cache_key = cache::query_key(&query);
+    let cached = state
+        .cache
+        .get::<(Vec<Value>, u64)>(CacheSpace::Instances, &cache_scope, &cache_key)
+        .await;
+    let (mut rows, total) = match cached {
+        Some(page) => page,
+        None => match instance_meta::fetch(&state, workspace, &filter, &page).await {
+            Ok(page) => {
+                state
+                    .cache
+                    .insert(CacheSpace::Instances, &cache_scope, &cache_key, &page, None)
+                    .await;
+                page
+            }
+            Err(e) => {
+                return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(json!({"error": "db_error", "details": e.to_string()})),
+                );
+            }
+        },
+    };
+    let compat = state.runtime_config().evolution_compat;
+    for row in &mut rows {
+        let Some(name) = row["session"].as_str().map(str::to_string) else {
+            continue;
+        };
+        let connection = state
+            .instances
+            .get(&name)
+            .map(|instance| instance.connection_state.clone());
+        let status = match connection {
+            Some(connection) => connection.read().await.state(),
+            None => ConnectionState::Disconnected,
+        };
+        message_counters::merge_into_count(&mut row["_count"], state.message_counters.get(&name));
+        let profile_pic_url = state
+            .sessions_runtime
+            .get(&name)
+            .and_then(|runtime| runtime.profile_pic_url.clone());
+        if compat {
+            *row = evolution::instance(row, status, profile_pic_url.as_deref());
+        } else {
+            row["connectionStatus"] = json!(status.evolution_state());
+            row["profilePicUrl"] = json!(profile_pic_url);
+        }
+    }
+    if compat {
+        return (StatusCode::OK, Json(Value::Array(rows)));
+    }
+    let info = page.info(rows.len(), total);
+    (StatusCode::OK, Json(pagination::envelope(rows, &info)))
+}
+
+/// Replaces the tags and/or metadata of an instance.
+pub async fn set_instance_metadata(
     Path(name): Path<String>,
     State(state): State<Arc<AppState>>,
+    Json(body): Json<Value>,
 ) -> impl IntoResponse {
-    if let Some(instance) = state.instances.get(&name) {
-        let state_str = instance.connection_state.read().await;
-        (
+    let parsed = instance_meta::tags_from_body(&body)
+        .and_then(|tags| Ok((tags, instance_meta::metadata_from_body(&body)?)));
+    let (tags, metadata) = match parsed {
+        Ok((None, None)) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": "invalid_request", "details": "tags or metadata required"})),
+            );
+        }
+        Ok(fields) => fields,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": "invalid_request", "details": e.to_string()})),
+            );
+        }
+    };
+
+    match instance_meta::update(&state, &name, tags, metadata).await {
+        Ok(Some(meta)) => (
             StatusCode::OK,
-            Json(json!({"instance": name, "state": *state_str})),
-        )
-    } else {
-        (
+            Json(json!({"instance": name, "tags": meta.tags, "metadata": meta.metadata})),
+        ),
+        Ok(None) => (
             StatusCode::NOT_FOUND,
             Json(json!({"error": "instance_not_found"})),
-        )
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": "db_error", "details": e.to_string()})),
+        ),
     }
 }
 
-pub async fn connect_instance(
-    Path(_name): Path<String>,
-    State(_state): State<Arc<AppState>>,
+/// Auto-read and auto-reply rules of an instance.
+pub async fn get_auto_rules(
+    Path(name): Path<String>,
+    State(state): State<Arc<AppState>>,
 ) -> impl IntoResponse {
-    (StatusCode::OK, Json(json!({"status": "connecting"})))
+    match auto_rules::fetch(&state, &name).await {
+        Ok(Some(rules)) => (
+            StatusCode::OK,
+            Json(json!({"instance": name, "rules": rules})),
+        ),
+        Ok(None) => (
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "instance_not_found"})),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": "db_error", "details": e.to_string()})),
+        ),
+    }
 }
 
-pub async fn instance_state(
+/// Replaces the rules of an instance (`{"rules": [...]}`, empty to clear).
+pub async fn set_auto_rules(
     Path(name): Path<String>,
     State(state): State<Arc<AppState>>,
+    Json(body): Json<Value>,
 ) -> impl IntoResponse {
-    if let Some(instance) = state.instances.get(&name) {
-        let qr = instance.qr_code.read().await;
-        let connected = *instance.connection_state.read().await == "connected";
-        (
+    let rules = match auto_rules::rules_from_body(&body) {
+        Ok(rules) => rules,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": "invalid_rules", "details": e.to_string()})),
+            );
+        }
+    };
+    match auto_rules::set(&state, &name, &rules).await {
+        Ok(true) => (
             StatusCode::OK,
-            Json(json!({
-                "state": *instance.connection_state.read().await,
-                "qr": *qr,
-                "connected": connected,
-                "last_error": null
-            })),
-        )
-    } else {
-        (
+            Json(json!({"instance": name, "rules": rules})),
+        ),
+        Ok(false) => (
             StatusCode::NOT_FOUND,
             Json(json!({"error": "instance_not_found"})),
-        )
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": "db_error", "details": e.to_string()})),
+        ),
     }
 }
 
-pub async fn send_message(
-    Path((operation, instance_name)): Path<(String, String)>,
-    Json(_payload): Json<Value>,
+/// Sets the maintenance window of an instance (`{"cron", "durationMinutes"}`).
+pub async fn set_maintenance_window(
+    Path(name): Path<String>,
+    State(state): State<Arc<AppState>>,
+    Json(body): Json<Value>,
+) -> impl IntoResponse {
+    let window = match MaintenanceWindow::from_body(&body) {
+        Ok(window) => window,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": "invalid_request", "details": e.to_string()})),
+            );
+        }
+    };
+    maintenance_window_response(&state, &name, Some(window)).await
+}
+
+/// Removes the maintenance window of an instance.
+pub async fn clear_maintenance_window(
+    Path(name): Path<String>,
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    maintenance_window_response(&state, &name, None).await
+}
+
+async fn maintenance_window_response(
+    state: &AppState,
+    name: &str,
+    window: Option<MaintenanceWindow>,
+) -> (StatusCode, Json<Value>) {
+    match maintenance::set_window(state, name, window.as_ref()).await {
+        Ok(true) => (
+            StatusCode::OK,
+            Json(json!({"instance": name, "maintenanceWindow": window})),
+        ),
+        Ok(false) => (
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "instance_not_found"})),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": "db_error", "details": e.to_string()})),
+        ),
+    }
+}
+
+/// Event filters of the webhook, `/ws` and NATS of an instance.
+pub async fn get_event_filters(
+    Path(name): Path<String>,
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    match event_filters::fetch(&state, &name).await {
+        Ok(Some(filters)) => (
+            StatusCode::OK,
+            Json(event_filters::response(&name, &filters)),
+        ),
+        Ok(None) => (
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "instance_not_found"})),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": "db_error", "details": e.to_string()})),
+        ),
+    }
+}
+
+/// Replaces the event filters given in `{"webhook"?, "websocket"?,
+/// "nats"?}`; the others are kept.
+pub async fn set_event_filters(
+    Path(name): Path<String>,
+    State(state): State<Arc<AppState>>,
+    Json(body): Json<Value>,
 ) -> impl IntoResponse {
-    if operation != "sendText" {
+    let mut filters = match event_filters::fetch(&state, &name).await {
+        Ok(Some(filters)) => filters,
+        Ok(None) => {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(json!({"error": "instance_not_found"})),
+            );
+        }
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": "db_error", "details": e.to_string()})),
+            );
+        }
+    };
+    if let Err(e) = filters.apply(&body) {
         return (
-            StatusCode::NOT_IMPLEMENTED,
-            Json(json!({"error": "not_implemented"})),
+            StatusCode::BAD_REQUEST,
+            Json(json!({"error": "invalid_event_filter", "details": e.to_string()})),
         );
     }
+    match event_filters::set(&state, &name, &filters).await {
+        Ok(true) => (
+            StatusCode::OK,
+            Json(event_filters::response(&name, &filters)),
+        ),
+        Ok(false) => (
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "instance_not_found"})),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": "db_error", "details": e.to_string()})),
+        ),
+    }
+}
 
-    (
-        StatusCode::OK,
-        Json(json!({"key": {"id": format!("msg-{}", instance_name)}})),
-    )
+/// Retention override of an instance and the policy in effect.
+pub async fn get_retention(
+    Path(name): Path<String>,
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    match retention::fetch(&state, &name).await {
+        Ok(Some(policy)) => (
+            StatusCode::OK,
+            Json(retention::response(&name, policy, state.retention.defaults)),
+        ),
+        Ok(None) => (
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "instance_not_found"})),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": "db_error", "details": e.to_string()})),
+        ),
+    }
 }
 
-pub async fn find_messages(
-    Path(instance_name): Path<String>,
-    Json(_payload): Json<Value>,
+/// Sets the retention of an instance (`{"messagesDays"?, "webhookLogsDays"?,
+/// "mediaDays"?}`); unset kinds follow the deployment, `0` keeps forever.
+pub async fn set_retention(
+    Path(name): Path<String>,
+    State(state): State<Arc<AppState>>,
+    Json(body): Json<Value>,
 ) -> impl IntoResponse {
-    (
-        StatusCode::OK,
-        Json(json!({
-            "instance": instance_name,
-            "count": 0,
-            "messages": []
-        })),
-    )
+    let policy = match RetentionPolicy::from_body(&body) {
+        Ok(policy) => policy,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": "invalid_retention", "details": e.to_string()})),
+            );
+        }
+    };
+    retention_response(&state, &name, Some(policy)).await
 }
 
-pub async fn find_chats(Path(instance_name): Path<String>) -> impl IntoResponse {
-    (
-        StatusCode::OK,
-        Json(json!({
-            "instance": instance_name,
-            "chats": []
-        })),
-    )
+/// Puts an instance back on the deployment retention.
+pub async fn clear_retention(
+    Path(name): Path<String>,
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    retention_response(&state, &name, None).await
 }
 
-pub async fn create_group(
-    Path(instance_name): Path<String>,
-    Json(_payload): Json<Value>,
+async fn retention_response(
+    state: &AppState,
+    name: &str,
+    policy: Option<RetentionPolicy>,
+) -> (StatusCode, Json<Value>) {
+    match retention::set(state, name, policy.as_ref()).await {
+        Ok(true) => (
+            StatusCode::OK,
+            Json(retention::response(
+                name,
+                policy.unwrap_or_default(),
+                state.retention.defaults,
+            )),
+        ),
+        Ok(false) => (
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "instance_not_found"})),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": "db_error", "details": e.to_string()})),
+        ),
+    }
+}
+
+/// Inbound webhook of an instance, without its secret.
+pub async fn get_inbound_hook(
+    Path(name): Path<String>,
+    State(state): State<Arc<AppState>>,
 ) -> impl IntoResponse {
-    (
-        StatusCode::CREATED,
-        Json(json!({
-            "instance": instance_name,
-            "status": "created"
-        })),
-    )
+    match inbound::fetch(&state, &name).await {
+        Ok(Some(hook)) => (
+            StatusCode::OK,
+            Json(json!({
+                "instance": name,
+                "enabled": true,
+                "path": format!("{}{name}", inbound::RECEIVER_PREFIX),
+                "createdAt": hook["createdAt"],
+                "lastUsedAt": hook["lastUsedAt"],
+            })),
+        ),
+        Ok(None) => (
+            StatusCode::OK,
+            Json(json!({"instance": name, "enabled": false})),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": "db_error", "details": e.to_string()})),
+        ),
+    }
 }
 
-pub async fn fetch_groups(Path(_instance_name): Path<String>) -> impl IntoResponse {
-    (
+/// Enables the inbound webhook of an instance, or rotates its secret
+/// (`{"secret"?}`, generated when omitted). The secret is only shown here.
+pub async fn set_inbound_hook(
+    Path(name): Path<String>,
+    State(state): State<Arc<AppState>>,
+    body: Option<Json<Value>>,
+) -> impl IntoResponse {
+    let secret = match inbound::secret_from_body(body.as_ref().map(|Json(body)| body)) {
+        Ok(secret) => secret,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": "invalid_secret", "details": e.to_string()})),
+            );
+        }
+    };
+    match inbound::enable(&state, &name, &secret).await {
+        Ok(true) => (
+            StatusCode::OK,
+            Json(json!({
+                "instance": name,
+                "enabled": true,
+                "path": format!("{}{name}", inbound::RECEIVER_PREFIX),
+                "secret": secret,
+            })),
+        ),
+        Ok(false) => (
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "instance_not_found"})),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": "db_error", "details": e.to_string()})),
+        ),
+    }
+}
+
+/// Disables the inbound webhook of an instance; its secret stops working.
+pub async fn delete_inbound_hook(
+    Path(name): Path<String>,
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    match inbound::disable(&state, &name).await {
+        Ok(removed) => (
+            StatusCode::OK,
+            Json(json!({"instance": name, "enabled": false, "removed": removed})),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": "db_error", "details": e.to_string()})),
+        ),
+    }
+}
+
+pub async fn connection_state(
+    Path(name): Path<String>,
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    if let Some(instance) = state.instances.get(&name) {
+        let status = instance.connection_state.read().await;
+        if state.runtime_config().evolution_compat {
+            return (
+                StatusCode::OK,
+                Json(evolution::connection_state(&name, status.state())),
+            );
+        }
+        let mut status = status.to_json();
+        status["instance"] = json!(name);
+        (StatusCode::OK, Json(status))
+    } else {
+        (
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "instance_not_found"})),
+        )
+    }
+}
+
+/// Under `evolutionCompat`, the QR and pair code to link the instance, or
+/// its state once it is open.
+pub async fn connect_instance(
+    Path(name): Path<String>,
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    if !state.runtime_config().evolution_compat {
+        return (StatusCode::OK, Json(json!({"status": "connecting"})));
+    }
+    let Some(connection) = state
+        .instances
+        .get(&name)
+        .map(|instance| instance.connection_state.clone())
+    else {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "instance_not_found"})),
+        );
+    };
+    let current = connection.read().await.state();
+    if current == ConnectionState::Connected {
+        (
+            StatusCode::OK,
+            Json(evolution::connection_state(&name, current)),
+        )
+    } else {
+        (StatusCode::OK, Json(evolution_qrcode(&state, &name).await))
+    }
+}
+
+pub async fn instance_state(
+    Path(name): Path<String>,
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    if let Some(instance) = state.instances.get(&name) {
+        let qr = instance.qr_code.read().await;
+        let status = instance.connection_state.read().await;
+        let connection_state = status.state();
+        let connected = connection_state == ConnectionState::Connected;
+        (
+            StatusCode::OK,
+            Json(json!({
+                "state": connection_state,
+                "qr": *qr,
+                "connected": connected,
+                "last_error": status.last_error()
+            })),
+        )
+    } else {
+        (
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "instance_not_found"})),
+        )
+    }
+}
+
+/// Serves the pending QR code of an instance as an image.
+/// `file` is `{name}.png` or `{name}.svg`; `?size=` overrides the configured edge.
+pub async fn instance_qrcode(
+    Path(file): Path<String>,
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<HashMap<String, String>>,
+) -> Response {
+    let Some((name, format)) = qr::image_file(&file) else {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "unsupported_format", "file": file})),
+        )
+            .into_response();
+    };
+    let Some(instance) = state.instances.get(name) else {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "instance_not_found"})),
+        )
+            .into_response();
+    };
+    let connection_state = instance.connection_state.read().await.state();
+    let code = instance.qr_code.read().await.clone();
+    let code = match code {
+        Some(code) if connection_state == ConnectionState::QrPending => code,
+        _ => {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(json!({"error": "qr_not_available", "state": connection_state})),
+            )
+                .into_response();
+        }
+    };
+
+    let config = state.runtime_config();
+    let rendered = qr::render_qr_with_fallback(
+        &code,
+        QrRenderOptions {
+            format,
+            size: query
+                .get("size")
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(config.qr_image_size),
+            quiet_zone: true,
+        },
+    );
+    let cache_control = match config.qr_cache_seconds {
+        0 => "no-store".to_string(),
+        seconds => format!("private, max-age={seconds}"),
+    };
+    (
+        StatusCode::OK,
+        [
+            (header::CONTENT_TYPE, rendered.format.content_type().to_string()),
+            (header::CACHE_CONTROL, cache_control),
+        ],
+        rendered.body,
+    )
+        .into_response()
+}
+
+/// Default number of attempts returned by the diagnostics endpoint.
+const DEFAULT_DIAGNOSTICS_LIMIT: usize = 10;
+
+/// Returns the last connection attempts of an instance (handshake phase,
+/// close codes, WA web version) together with the reconnect backoff state
+/// and the retry receipt counters.
+pub async fn instance_diagnostics(
+    Path(name): Path<String>,
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<HashMap<String, String>>,
+) -> impl IntoResponse {
+    let Some(client) = state.clients.get(&name).map(|entry| entry.value().clone()) else {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "instance_not_found"})),
+        );
+    };
+
+    let limit = query
+        .get("limit")
+        .and_then(|v| v.parse::<usize>().ok())
+        .unwrap_or(DEFAULT_DIAGNOSTICS_LIMIT)
+        .clamp(1, MAX_CONNECTION_ATTEMPTS);
+    let attempts = client.connection_diagnostics.recent(limit);
+    let connection = match state.instances.get(&name) {
+        Some(instance) => Some(instance.connection_state.read().await.to_json()),
+        None => None,
+    };
+
+    (
+        StatusCode::OK,
+        Json(json!({
+            "instance": name,
+            "state": connection.as_ref().map(|c| c["state"].clone()),
+            "connection": connection,
+            "connected": client.is_connected(),
+            "loggedIn": client.is_logged_in(),
+            "waVersion": attempts.first().and_then(|a| a.wa_version.clone()),
+            "versionConfig": client.version_manager.config(),
+            "lastSuccessfulConnect": *client.last_successful_connect.lock().await,
+            "backoff": {
+                "autoReconnect": client.enable_auto_reconnect.load(Ordering::Relaxed),
+                "consecutiveErrors": client.auto_reconnect_errors.load(Ordering::Relaxed),
+                "lastDelaySecs": attempts.iter().find_map(|a| a.backoff_secs),
+            },
+            "attempts": attempts,
+            "retries": client.retry_stats.snapshot(),
+            "handshakeGate": state.handshake_gate.stats(),
+        })),
+    )
+}
+
+/// Current WA web version and the policy used to pick it.
+pub async fn get_instance_version(
+    Path(name): Path<String>,
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    let Some(client) = state.clients.get(&name).map(|entry| entry.value().clone()) else {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "instance_not_found"})),
+        );
+    };
+    (
+        StatusCode::OK,
+        Json(version_json(&name, &client.version_manager)),
+    )
+}
+
+/// Replaces the version policy (`pin`, `fallbacks`, `source`) of an instance.
+/// Takes effect on the next connection attempt.
+pub async fn set_instance_version(
+    Path(name): Path<String>,
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<Value>,
+) -> impl IntoResponse {
+    let Some(client) = state.clients.get(&name).map(|entry| entry.value().clone()) else {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "instance_not_found"})),
+        );
+    };
+    let config = match version_config_from_json(&payload) {
+        Ok(config) => config,
+        Err(details) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": "invalid_version_config", "details": details})),
+            );
+        }
+    };
+    client.version_manager.set_config(config);
+    (
+        StatusCode::OK,
+        Json(version_json(&name, &client.version_manager)),
+    )
+}
+
+fn version_json(name: &str, manager: &version::WaVersionManager) -> Value {
+    json!({
+        "instance": name,
+        "current": manager.current().map(version::format_version),
+        "rejected": manager.rejected().into_iter().map(version::format_version).collect::<Vec<_>>(),
+        "config": manager.config(),
+    })
+}
+
+fn version_config_from_json(payload: &Value) -> Result<version::VersionConfig, String> {
+    let parse = |value: &Value| {
+        value
+            .as_str()
+            .and_then(version::parse_version)
+            .ok_or_else(|| format!("invalid version {}", value))
+    };
+
+    let pinned = match &payload["pin"] {
+        Value::Null => None,
+        value => Some(parse(value)?),
+    };
+    let fallbacks = match &payload["fallbacks"] {
+        Value::Null => Vec::new(),
+        Value::Array(items) => items.iter().map(parse).collect::<Result<Vec<_>, _>>()?,
+        _ => return Err("fallbacks must be an array".to_string()),
+    };
+    let source = match &payload["source"] {
+        Value::Null => version::VersionSource::ServiceWorker,
+        value => value
+            .as_str()
+            .and_then(version::VersionSource::parse)
+            .ok_or_else(|| format!("invalid source {}", value))?,
+    };
+
+    Ok(version::VersionConfig {
+        pinned,
+        fallbacks,
+        source,
+    })
+}
+
+/// Devices registered on the account of an instance: the phone and every
+/// linked companion.
+pub async fn list_instance_devices(
+    Path(name): Path<String>,
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    let Some(client) = state.clients.get(&name).map(|entry| entry.value().clone()) else {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "instance_not_found"})),
+        );
+    };
+    if !client.is_logged_in() {
+        return (
+            StatusCode::CONFLICT,
+            Json(json!({"error": "instance_not_connected"})),
+        );
+    }
+    match client.devices().list().await {
+        Ok(devices) => (
+            StatusCode::OK,
+            Json(json!({"instance": name, "devices": devices})),
+        ),
+        Err(e) => {
+            tracing::warn!(instance = %name, error = %e, "Falha ao listar dispositivos");
+            (
+                StatusCode::BAD_GATEWAY,
+                Json(json!({"error": "devices_unavailable", "details": e.to_string()})),
+            )
+        }
+    }
+}
+
+/// The companion `device` (a device id) of `own`'s account, unless it is the
+/// phone or the instance itself.
+fn companion_to_remove(own: &Jid, device: &str) -> Result<Jid, &'static str> {
+    let device_id = device.trim().parse::<u16>().map_err(|_| "invalid_device")?;
+    if device_id == 0 {
+        return Err("primary_device");
+    }
+    if device_id == own.device {
+        return Err("current_device");
+    }
+    let mut jid = own.to_non_ad();
+    jid.device = device_id;
+    Ok(jid)
+}
+
+/// Unlinks a companion device from the account of an instance. WhatsApp
+/// only honours this from the primary device.
+pub async fn remove_instance_device(
+    Path((name, device)): Path<(String, String)>,
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    let Some(client) = state.clients.get(&name).map(|entry| entry.value().clone()) else {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "instance_not_found"})),
+        );
+    };
+    let Some(own) = client.get_pn().await.filter(|_| client.is_logged_in()) else {
+        return (
+            StatusCode::CONFLICT,
+            Json(json!({"error": "instance_not_connected"})),
+        );
+    };
+    let companion = match companion_to_remove(&own, &device) {
+        Ok(companion) => companion,
+        Err(error) => return (StatusCode::BAD_REQUEST, Json(json!({"error": error}))),
+    };
+    match client.devices().remove_companion(&companion).await {
+        Ok(()) => (
+            StatusCode::OK,
+            Json(json!({"instance": name, "removed": companion.to_string()})),
+        ),
+        Err(crate::request::IqError::ServerError { code, text }) => (
+            if code == 401 || code == 403 {
+                StatusCode::FORBIDDEN
+            } else {
+                StatusCode::BAD_GATEWAY
+            },
+            Json(json!({"error": "device_removal_rejected", "code": code, "details": text})),
+        ),
+        Err(e) => {
+            tracing::warn!(instance = %name, error = %e, "Falha ao remover dispositivo");
+            (
+                StatusCode::BAD_GATEWAY,
+                Json(json!({"error": "device_removal_failed", "details": e.to_string()})),
+            )
+        }
+    }
+}
+
+pub async fn send_message(
+    Path((operation, instance_name)): Path<(String, String)>,
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<Value>,
+) -> Response {
+    match operation.as_str() {
+        "sendText" => match serde_json::from_value::<SendText>(payload) {
+            Ok(payload) => send_text(state, instance_name, &payload).await,
+            Err(e) => (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Json(json!({"error": "invalid_body", "details": e.to_string()})),
+            )
+                .into_response(),
+        },
+        "sendWhatsAppAudio" => send_whatsapp_audio(state, instance_name, payload).await,
+        "sendTemplate" => send_cloud_template(state, instance_name, payload).await,
+        "sendTemplateByName" => send_template_by_name(state, instance_name, payload).await,
+        "sendProduct" => send_product(state, instance_name, payload).await,
+        "sendCatalog" => send_catalog(state, instance_name, payload).await,
+        "sendButtons" | "sendList" => {
+            let Some(number) = payload["number"].as_str().filter(|s| !s.trim().is_empty()) else {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    Json(json!({"error": "number_required"})),
+                )
+                    .into_response();
+            };
+            let chat_id = number_to_jid(number);
+            if operation == "sendButtons" {
+                let body = buttons_body(&instance_name, &chat_id, &payload);
+                queue_evolution_message(state, body, "buttons").await
+            } else {
+                let body = list_body(&instance_name, &chat_id, &payload);
+                queue_evolution_message(state, body, "list").await
+            }
+        }
+        _ => (
+            StatusCode::NOT_IMPLEMENTED,
+            Json(json!({"error": "not_implemented"})),
+        )
+            .into_response(),
+    }
+}
+
+/// Queues a `/message/*` body; answers with the stored message, or with the
+/// Evolution message shape under `evolutionCompat`.
+async fn queue_evolution_message(
+    state: Arc<AppState>,
+    body: Value,
+    message_type: &str,
+) -> Response {
+    if let Some(response) = chat_manager::check_interactive(message_type, &body) {
+        return response;
+    }
+    let compat = state.runtime_config().evolution_compat;
+    match chat_manager::queue_message(state, body.clone(), message_type, true).await {
+        Ok(message) if compat => (
+            StatusCode::OK,
+            Json(evolution::sent_message(&message, &body)),
+        )
+            .into_response(),
+        Ok(message) => (StatusCode::OK, Json(message)).into_response(),
+        Err(response) => response,
+    }
+}
+
+/// Queues an Evolution `sendText` body.
+async fn send_text(state: Arc<AppState>, instance_name: String, payload: &SendText) -> Response {
+    match send_text_body(&instance_name, payload) {
+        Ok(body) => queue_evolution_message(state, body, "text").await,
+        Err(error) => (StatusCode::BAD_REQUEST, Json(json!({"error": error}))).into_response(),
+    }
+}
+
+/// `/sendText` body from an Evolution `sendText` one; the error is the
+/// `error` code of a missing `number` or `text`.
+fn send_text_body(instance_name: &str, payload: &SendText) -> Result<Value, &'static str> {
+    if payload.number.trim().is_empty() {
+        return Err("number_required");
+    }
+    if payload.text.is_empty() {
+        return Err("text_required");
+    }
+    let mut body = json!({
+        "session": instance_name,
+        "chatId": number_to_jid(&payload.number),
+        "text": payload.text,
+    });
+    if let Some(link_preview) = payload.link_preview {
+        body["linkPreview"] = json!(link_preview);
+    }
+    if let Some(quoted) = &payload.quoted {
+        body["quoted"] = quoted.clone();
+    }
+    Ok(body)
+}
+
+/// `/sendButtons` body from an Evolution `sendButtons` one: `description`
+/// (or `text`), `title`, `footer` and `buttons` with `displayText`, `id`,
+/// `url` and `phoneNumber`.
+fn buttons_body(instance_name: &str, chat_id: &str, payload: &Value) -> Value {
+    let buttons: Vec<Value> = payload["buttons"]
+        .as_array()
+        .map(Vec::as_slice)
+        .unwrap_or_default()
+        .iter()
+        .map(|button| {
+            json!({
+                "type": button["type"],
+                "text": button.get("displayText").unwrap_or(&button["text"]),
+                "id": button["id"],
+                "url": button["url"],
+                "phone": button.get("phoneNumber").unwrap_or(&button["phone"]),
+            })
+        })
+        .collect();
+    let mut body = json!({
+        "session": instance_name,
+        "chatId": chat_id,
+        "text": payload.get("description").unwrap_or(&payload["text"]),
+        "title": payload["title"],
+        "footer": payload["footer"],
+        "buttons": buttons,
+    });
+    if let Some(quoted) = payload.get("quoted") {
+        body["quoted"] = quoted.clone();
+    }
+    body
+}
+
+/// `/sendList` body from an Evolution `sendList` one: `description`,
+/// `title`, `footerText`, `buttonText` and `sections` whose rows carry a
+/// `rowId`.
+fn list_body(instance_name: &str, chat_id: &str, payload: &Value) -> Value {
+    let sections: Vec<Value> = payload["sections"]
+        .as_array()
+        .map(Vec::as_slice)
+        .unwrap_or_default()
+        .iter()
+        .map(|section| {
+            let rows: Vec<Value> = section["rows"]
+                .as_array()
+                .map(Vec::as_slice)
+                .unwrap_or_default()
+                .iter()
+                .map(|row| {
+                    json!({
+                        "id": row.get("rowId").unwrap_or(&row["id"]),
+                        "title": row["title"],
+                        "description": row["description"],
+                    })
+                })
+                .collect();
+            json!({"title": section["title"], "rows": rows})
+        })
+        .collect();
+    let mut body = json!({
+        "session": instance_name,
+        "chatId": chat_id,
+        "text": payload.get("description").unwrap_or(&payload["text"]),
+        "title": payload["title"],
+        "footer": payload.get("footerText").unwrap_or(&payload["footer"]),
+        "buttonText": payload["buttonText"],
+        "sections": sections,
+    });
+    if let Some(quoted) = payload.get("quoted") {
+        body["quoted"] = quoted.clone();
+    }
+    body
+}
+
+/// Queues an Evolution-style `sendWhatsAppAudio` body as a PTT voice note.
+///
+/// `audio` may be a URL, a data URL or raw base64; `encoding: false` skips
+/// the ogg/opus transcoding done by the messages worker.
+async fn send_whatsapp_audio(
+    state: Arc<AppState>,
+    instance_name: String,
+    payload: Value,
+) -> Response {
+    let Some(number) = payload["number"].as_str().filter(|s| !s.trim().is_empty()) else {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({"error": "number_required"})),
+        )
+            .into_response();
+    };
+    let Some(audio) = payload["audio"].as_str().filter(|s| !s.trim().is_empty()) else {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({"error": "audio_required"})),
+        )
+            .into_response();
+    };
+
+    let mut body = json!({
+        "session": instance_name,
+        "chatId": number_to_jid(number),
+        "encoding": payload["encoding"].as_bool().unwrap_or(true),
+    });
+    let source_key = if audio.starts_with("http://") || audio.starts_with("https://") {
+        "url"
+    } else {
+        "base64"
+    };
+    body[source_key] = json!(audio);
+    if let Some(quoted) = payload.get("quoted") {
+        body["quoted"] = quoted.clone();
+    }
+
+    queue_evolution_message(state, body, "voice").await
+}
+
+/// Sends an approved WhatsApp Business template (HSM) through the Cloud API
+/// channel of the instance; answers with the WA message id.
+async fn send_cloud_template(
+    state: Arc<AppState>,
+    instance_name: String,
+    payload: Value,
+) -> Response {
+    let channel = match cloud_api::channel(&state, &instance_name).await {
+        Ok(Some(channel)) => channel,
+        Ok(None) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({
+                    "error": "cloud_api_only",
+                    "details": "sendTemplate needs a WHATSAPP-BUSINESS instance",
+                })),
+            )
+                .into_response();
+        }
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": "db_error", "details": e.to_string()})),
+            )
+                .into_response();
+        }
+    };
+
+    match cloud_api::send_template(&state, &instance_name, &channel, &payload).await {
+        Ok((message, id)) => (
+            StatusCode::OK,
+            Json(json!({
+                "key": {
+                    "remoteJid": number_to_jid(message["to"].as_str().unwrap_or_default()),
+                    "fromMe": true,
+                    "id": id,
+                },
+                "messageType": "templateMessage",
+                "message": {"template": message["template"]},
+                "status": "PENDING",
+            })),
+        )
+            .into_response(),
+        Err(e) => {
+            tracing::warn!(
+                instance = %instance_name,
+                error = %e,
+                "Falha ao enviar template pela Cloud API"
+            );
+            e.response().into_response()
+        }
+    }
+}
+
+/// Renders a stored template (`name`, `variables`) and queues it for `number`.
+async fn send_template_by_name(
+    state: Arc<AppState>,
+    instance_name: String,
+    payload: Value,
+) -> Response {
+    let Some(number) = payload["number"].as_str().filter(|s| !s.trim().is_empty()) else {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({"error": "number_required"})),
+        )
+            .into_response();
+    };
+    let Some(name) = payload["name"].as_str().filter(|s| !s.trim().is_empty()) else {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({"error": "name_required"})),
+        )
+            .into_response();
+    };
+    let values = match payload.get("variables").filter(|v| !v.is_null()) {
+        Some(variables) => match templates::values_from(variables) {
+            Ok(values) => values,
+            Err(e) => {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    Json(json!({"error": "invalid_variables", "details": e.to_string()})),
+                )
+                    .into_response();
+            }
+        },
+        None => Default::default(),
+    };
+
+    let template = match templates::get(&state, name).await {
+        Ok(Some(template)) => template,
+        Ok(None) => {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(json!({"error": "template_not_found", "name": name})),
+            )
+                .into_response();
+        }
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": "db_error", "details": e.to_string()})),
+            )
+                .into_response();
+        }
+    };
+    let rendered = match template.render(&values) {
+        Ok(rendered) => rendered,
+        Err(e @ TemplateError::MissingVariables(_)) => {
+            return (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Json(json!({"error": "render_failed", "details": e.to_string()})),
+            )
+                .into_response();
+        }
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": "invalid_template", "details": e.to_string()})),
+            )
+                .into_response();
+        }
+    };
+
+    let (message_type, mut body) = rendered.into_payload(&instance_name, &number_to_jid(number));
+    if let Some(quoted) = payload.get("quoted") {
+        body["quoted"] = quoted.clone();
+    }
+    queue_evolution_message(state, body, message_type).await
+}
+
+/// Queues a product card. Body: `number`, `businessOwnerJid` (defaults to
+/// the instance's own catalog), `productId`, `title`, `price`/`currency`,
+/// optional `image` URL, `description`, `retailerId`, `url`, `body`, `footer`.
+async fn send_product(state: Arc<AppState>, instance_name: String, payload: Value) -> Response {
+    let Some(number) = payload["number"].as_str().filter(|s| !s.trim().is_empty()) else {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({"error": "number_required"})),
+        )
+            .into_response();
+    };
+    let Some(product_id) = payload["productId"].as_str().filter(|s| !s.trim().is_empty()) else {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({"error": "product_id_required"})),
+        )
+            .into_response();
+    };
+    let owner = match payload["businessOwnerJid"].as_str().filter(|s| !s.trim().is_empty()) {
+        Some(owner) => number_to_jid(owner),
+        None => match own_jid(&state, &instance_name).await {
+            Some(jid) => jid,
+            None => {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    Json(json!({"error": "business_owner_jid_required"})),
+                )
+                    .into_response();
+            }
+        },
+    };
+
+    let mut body = payload.clone();
+    body["session"] = json!(instance_name);
+    body["chatId"] = json!(number_to_jid(number));
+    body["businessOwnerJid"] = json!(owner);
+    body["productId"] = json!(product_id);
+    queue_evolution_message(state, body, "product").await
+}
+
+/// Sends the `wa.me/c/` link of a business catalog (the instance's own by
+/// default) with a link preview. Body: `number`, optional `catalogNumber`, `text`.
+async fn send_catalog(state: Arc<AppState>, instance_name: String, payload: Value) -> Response {
+    let Some(number) = payload["number"].as_str().filter(|s| !s.trim().is_empty()) else {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({"error": "number_required"})),
+        )
+            .into_response();
+    };
+    let owner = match payload["catalogNumber"].as_str().filter(|s| !s.trim().is_empty()) {
+        Some(owner) => number_to_jid(owner),
+        None => match own_jid(&state, &instance_name).await {
+            Some(jid) => jid,
+            None => {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    Json(json!({"error": "catalog_number_required"})),
+                )
+                    .into_response();
+            }
+        },
+    };
+    let link = catalog_link(&owner);
+    let text = match payload["text"].as_str().filter(|s| !s.trim().is_empty()) {
+        Some(text) => format!("{}\n{}", text, link),
+        None => link,
+    };
+
+    let body = json!({
+        "session": instance_name,
+        "chatId": number_to_jid(number),
+        "text": text,
+        "linkPreview": true,
+    });
+    chat_manager::send_message_type(state, body, "text", true).await
+}
+
+/// `https://wa.me/c/<number>` for a business JID.
+fn catalog_link(jid: &str) -> String {
+    let user = jid.split('@').next().unwrap_or(jid);
+    format!("https://wa.me/c/{}", user.split(':').next().unwrap_or(user))
+}
+
+/// Phone-number JID of the connected account of an instance.
+async fn own_jid(state: &AppState, instance_name: &str) -> Option<String> {
+    let client = state.clients.get(instance_name)?.value().clone();
+    let jid = client.get_pn().await?;
+    Some(jid.to_non_ad().to_string())
+}
+
+/// Business catalog of `number` (`limit`, `cursor` for the next page).
+pub async fn get_business_catalog(
+    Path(instance_name): Path<String>,
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<Value>,
+) -> impl IntoResponse {
+    let (client, jid) = match business_target(&state, &instance_name, &payload).await {
+        Ok(target) => target,
+        Err(response) => return response,
+    };
+    let limit = payload["limit"].as_u64().unwrap_or(10).clamp(1, 100) as u32;
+    let cursor = payload["cursor"].as_str().filter(|s| !s.is_empty());
+
+    match client.business().get_catalog(&jid, limit, cursor).await {
+        Ok(page) => (
+            StatusCode::OK,
+            Json(json!({
+                "wuid": jid.to_string(),
+                "catalogLength": page.products.len(),
+                "catalog": page.products,
+                "nextCursor": page.next_cursor,
+            })),
+        ),
+        Err(e) => (
+            StatusCode::BAD_GATEWAY,
+            Json(json!({"error": "catalog_fetch_failed", "details": e.to_string()})),
+        ),
+    }
+}
+
+/// Product collections of `number` (`limit` collections, `itemLimit` products each).
+pub async fn get_business_collections(
+    Path(instance_name): Path<String>,
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<Value>,
+) -> impl IntoResponse {
+    let (client, jid) = match business_target(&state, &instance_name, &payload).await {
+        Ok(target) => target,
+        Err(response) => return response,
+    };
+    let limit = payload["limit"].as_u64().unwrap_or(10).clamp(1, 50) as u32;
+    let item_limit = payload["itemLimit"].as_u64().unwrap_or(10).clamp(1, 100) as u32;
+
+    match client.business().get_collections(&jid, limit, item_limit).await {
+        Ok(collections) => (
+            StatusCode::OK,
+            Json(json!({
+                "wuid": jid.to_string(),
+                "collectionsLength": collections.len(),
+                "collections": collections,
+            })),
+        ),
+        Err(e) => (
+            StatusCode::BAD_GATEWAY,
+            Json(json!({"error": "collections_fetch_failed", "details": e.to_string()})),
+        ),
+    }
+}
+
+/// Business profile of `number` (category, description, address, email,
+/// websites and opening hours); the instance's own account without it.
+pub async fn fetch_business_profile(
+    Path(instance_name): Path<String>,
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<Value>,
+) -> impl IntoResponse {
+    let (client, jid) = match business_target(&state, &instance_name, &payload).await {
+        Ok(target) => target,
+        Err(response) => return response,
+    };
+
+    match client.business().get_profile(&jid).await {
+        Ok(Some(profile)) => {
+            let mut body = json!(profile);
+            body["wuid"] = json!(jid.to_string());
+            body["isBusiness"] = json!(true);
+            body["category"] = json!(profile.categories.first().and_then(|c| c.name.clone()));
+            (StatusCode::OK, Json(body))
+        }
+        Ok(None) => (
+            StatusCode::OK,
+            Json(json!({"wuid": jid.to_string(), "isBusiness": false})),
+        ),
+        Err(e) => (
+            StatusCode::BAD_GATEWAY,
+            Json(json!({"error": "business_profile_fetch_failed", "details": e.to_string()})),
+        ),
+    }
+}
+
+/// Changes the business profile of the instance's own account; accounts
+/// without one get `409 not_a_business_account`.
+pub async fn update_business_profile(
+    Path(instance_name): Path<String>,
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<Value>,
+) -> impl IntoResponse {
+    let update = match business_profile_update_from_json(&payload) {
+        Ok(update) => update,
+        Err(details) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": "invalid_business_profile", "details": details})),
+            );
+        }
+    };
+    let (client, jid) = match business_target(&state, &instance_name, &json!({})).await {
+        Ok(target) => target,
+        Err(response) => return response,
+    };
+
+    match client.business().get_profile(&jid).await {
+        Ok(Some(_)) => {}
+        Ok(None) => {
+            return (
+                StatusCode::CONFLICT,
+                Json(json!({"error": "not_a_business_account"})),
+            );
+        }
+        Err(e) => {
+            return (
+                StatusCode::BAD_GATEWAY,
+                Json(json!({"error": "business_profile_fetch_failed", "details": e.to_string()})),
+            );
+        }
+    }
+    if let Err(e) = client.business().update_profile(&update).await {
+        return (
+            StatusCode::BAD_GATEWAY,
+            Json(json!({"error": "business_profile_update_failed", "details": e.to_string()})),
+        );
+    }
+
+    match client.business().get_profile(&jid).await {
+        Ok(Some(profile)) => (StatusCode::OK, Json(json!(profile))),
+        _ => (
+            StatusCode::OK,
+            Json(json!({"wuid": jid.to_string(), "updated": true})),
+        ),
+    }
+}
+
+const BUSINESS_DAYS: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+const BUSINESS_HOURS_MODES: [&str; 3] = ["open_24h", "appointment_only", "specific_hours"];
+/// Websites a business profile shows.
+const MAX_BUSINESS_WEBSITES: usize = 2;
+
+fn business_profile_update_from_json(
+    payload: &Value,
+) -> Result<features::BusinessProfileUpdate, String> {
+    let update: features::BusinessProfileUpdate =
+        serde_json::from_value(payload.clone()).map_err(|e| e.to_string())?;
+    if update.is_empty() {
+        return Err(
+            "send at least one of description, address, email, websites, businessHours".to_string(),
+        );
+    }
+    if let Some(email) = update.email.as_deref()
+        && !email.is_empty()
+        && !email.contains('@')
+    {
+        return Err(format!("invalid email {email:?}"));
+    }
+    if update
+        .websites
+        .as_ref()
+        .is_some_and(|w| w.len() > MAX_BUSINESS_WEBSITES)
+    {
+        return Err(format!("at most {MAX_BUSINESS_WEBSITES} websites"));
+    }
+    for day in update.business_hours.iter().flat_map(|hours| &hours.days) {
+        if !BUSINESS_DAYS.contains(&day.day.as_str()) {
+            return Err(format!("invalid day {:?}", day.day));
+        }
+        if !BUSINESS_HOURS_MODES.contains(&day.mode.as_str()) {
+            return Err(format!("invalid mode {:?}", day.mode));
+        }
+        if day.mode == "specific_hours" {
+            match (day.open_time, day.close_time) {
+                (Some(open), Some(close)) if open < close && close <= 24 * 60 => {}
+                _ => {
+                    return Err(format!(
+                        "{}: specific_hours needs openTime < closeTime, in minutes up to 1440",
+                        day.day
+                    ));
+                }
+            }
+        }
+    }
+    Ok(update)
+}
+
+/// Client of the instance and the business JID to query: `number`, or the
+/// instance's own account when omitted.
+async fn business_target(
+    state: &AppState,
+    instance_name: &str,
+    payload: &Value,
+) -> Result<(Arc<crate::client::Client>, Jid), (StatusCode, Json<Value>)> {
+    let Some(client) = state.clients.get(instance_name).map(|c| c.value().clone()) else {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "session_not_found", "session": instance_name})),
+        ));
+    };
+    let raw = match payload["number"].as_str().filter(|s| !s.trim().is_empty()) {
+        Some(number) => number.to_string(),
+        None => match own_jid(state, instance_name).await {
+            Some(jid) => jid,
+            None => {
+                return Err((
+                    StatusCode::BAD_REQUEST,
+                    Json(json!({"error": "number_required"})),
+                ));
+            }
+        },
+    };
+    match jid::parse(&raw) {
+        Ok(jid) => Ok((client, jid.to_non_ad())),
+        Err(_) => Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({"error": "invalid_number"})),
+        )),
+    }
+}
+
+/// Turns an Evolution `number` field into a chat JID; values that do not
+/// parse are passed through for the worker to reject.
+fn number_to_jid(number: &str) -> String {
+    jid::normalize(number).unwrap_or_else(|_| number.trim().to_string())
+}
+
+/// Stored messages of the instance, newest first, each with its
+/// `reactions` aggregated by emoji. Filters on `where.key.remoteJid` and
+/// `where.key.id`; pages with `limit` and `offset`.
+pub async fn find_messages(
+    Path(instance_name): Path<String>,
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<FindMessages>,
+) -> impl IntoResponse {
+    let filter = match chat_manager::MessageFilter::from_request(&payload) {
+        Ok(filter) => filter,
+        Err(e) => return pagination::invalid_pagination(e),
+    };
+    let result = async {
+        let (mut messages, total) =
+            chat_manager::find_messages(&state, &instance_name, &filter).await?;
+        reactions::attach_to_messages(&state, &instance_name, &mut messages).await?;
+        anyhow::Ok((messages, total))
+    }
+    .await;
+
+    match result {
+        Ok((messages, total)) => (
+            StatusCode::OK,
+            Json(found_messages(instance_name, messages, &filter, total)),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": "db_error", "details": e.to_string()})),
+        ),
+    }
+}
+
+/// Answer of `findMessages`, in the shape of
+/// [`FoundMessages`](crate::models::api_model::FoundMessages).
+fn found_messages(
+    instance_name: String,
+    messages: Vec<Value>,
+    filter: &chat_manager::MessageFilter,
+    total: u64,
+) -> Value {
+    json!({
+        "instance": instance_name,
+        "count": messages.len(),
+        "pagination": filter.page.info(messages.len(), total),
+        "messages": messages,
+    })
+}
+
+pub async fn find_chats(Path(instance_name): Path<String>) -> impl IntoResponse {
+    (
+        StatusCode::OK,
+        Json(json!({
+            "instance": instance_name,
+            "chats": []
+        })),
+    )
+}
+
+/// Which of `numbers` have a WhatsApp account: `[{exists, jid, number}]`.
+/// Answers are cached per instance (`WHATSAPP_NUMBERS_CACHE_SECONDS`).
+pub async fn whatsapp_numbers(
+    Path(instance_name): Path<String>,
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<WhatsappNumbers>,
+) -> impl IntoResponse {
+    let numbers = match numbers::numbers_from_body(&payload) {
+        Ok(numbers) => numbers,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": "invalid_numbers", "details": e.to_string()})),
+            );
+        }
+    };
+    let Some(client) = state.clients.get(&instance_name).map(|c| c.value().clone()) else {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "session_not_found", "session": instance_name})),
+        );
+    };
+
+    match numbers::check(&state.number_cache, &client, &instance_name, &numbers).await {
+        Ok(statuses) => (StatusCode::OK, Json(json!(statuses))),
+        Err(e) => (
+            StatusCode::BAD_GATEWAY,
+            Json(json!({"error": "number_check_failed", "details": e.to_string()})),
+        ),
+    }
+}
+
+/// Looks `numbers`, a `csv` or an uploaded CSV (`mediaId`) up over usync and
+/// stores the contacts. Up to one batch answers with the contacts; longer
+/// syncs answer `202` with a `syncId` and report `CONTACTS_SYNC_PROGRESS`.
+pub async fn sync_contacts(
+    Path(instance_name): Path<String>,
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<Value>,
+) -> impl IntoResponse {
+    let Some(client) = state.clients.get(&instance_name).map(|c| c.value().clone()) else {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "session_not_found", "session": instance_name})),
+        );
+    };
+    let numbers = match contact_sync::numbers_from_request(&state, &instance_name, &payload).await {
+        Ok(numbers) => numbers,
+        Err(ContactSyncError::Upload(UploadError::NotFound)) => {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(json!({"error": "upload_not_found"})),
+            );
+        }
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": "invalid_contacts", "details": e.to_string()})),
+            );
+        }
+    };
+
+    if numbers.len() <= contact_sync::BATCH_SIZE {
+        return match contact_sync::run(&state, &client, &instance_name, &numbers, None).await {
+            Ok(contacts) => (
+                StatusCode::OK,
+                Json(json!({
+                    "instance": instance_name,
+                    "total": numbers.len(),
+                    "found": contacts.iter().filter(|c| c.exists).count(),
+                    "contacts": contacts,
+                })),
+            ),
+            Err(e) => (
+                StatusCode::BAD_GATEWAY,
+                Json(json!({"error": "contact_sync_failed", "details": e.to_string()})),
+            ),
+        };
+    }
+
+    let sync_id = uuid::Uuid::new_v4().to_string();
+    let total = numbers.len();
+    let (task_state, task_instance, task_sync_id) =
+        (state.clone(), instance_name.clone(), sync_id.clone());
+    tokio::spawn(async move {
+        let _ = contact_sync::run(
+            &task_state,
+            &client,
+            &task_instance,
+            &numbers,
+            Some(&task_sync_id),
+        )
+        .await;
+    });
+    (
+        StatusCode::ACCEPTED,
+        Json(json!({
+            "instance": instance_name,
+            "syncId": sync_id,
+            "total": total,
+            "batches": total.div_ceil(contact_sync::BATCH_SIZE),
+        })),
+    )
+}
+
+/// Schedules an export of the messages of `remoteJid` and/or `from`..`to`
+/// as JSON or CSV; poll the returned `statusUrl` for the download link.
+pub async fn export_chat(
+    Path(instance_name): Path<String>,
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<Value>,
+) -> impl IntoResponse {
+    let request = match ExportRequest::from_body(&payload) {
+        Ok(request) => request,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": "invalid_export", "details": e.to_string()})),
+            );
+        }
+    };
+    match workspaces::instance_owner(&state, &instance_name).await {
+        Ok(Some(_)) => {}
+        Ok(None) => {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(json!({"error": "instance_not_found"})),
+            );
+        }
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": "db_error", "details": e.to_string()})),
+            );
+        }
+    }
+    match exports::schedule(&state, &instance_name, request.clone()).await {
+        Ok(id) => (
+            StatusCode::ACCEPTED,
+            Json(json!({
+                "jobId": id,
+                "instance": instance_name,
+                "status": "queued",
+                "request": request,
+                "statusUrl": format!("/api/v1/jobs/{id}?session={instance_name}"),
+            })),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": "db_error", "details": e.to_string()})),
+        ),
+    }
+}
+
+/// Status of an export job, with a signed `downloadUrl` once it is done.
+/// A `session` query parameter must name the instance the job belongs to.
+pub async fn get_job(
+    Path(id): Path<String>,
+    Query(query): Query<HashMap<String, String>>,
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    let not_found = || {
+        (
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "job_not_found"})),
+        )
+    };
+    let Ok(id) = uuid::Uuid::parse_str(&id) else {
+        return not_found();
+    };
+    let mut job = match exports::find(&state, id).await {
+        Ok(Some(job)) => job,
+        Ok(None) => return not_found(),
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": "db_error", "details": e.to_string()})),
+            );
+        }
+    };
+    if query
+        .get("session")
+        .is_some_and(|session| job["instance"] != session.as_str())
+    {
+        return not_found();
+    }
+    if exports::download(&state.exports, &job).is_some()
+        && let Some((url, expires)) = state.exports.download_url(id, chrono::Utc::now())
+    {
+        job["downloadUrl"] = json!(url);
+        job["downloadExpiresAt"] = json!(expires);
+    }
+    (StatusCode::OK, Json(job))
+}
+
+/// File of a finished export. Needs no credentials: the `expires` and
+/// `signature` query parameters of its `downloadUrl` authorize it.
+pub async fn download_job(
+    Path(id): Path<String>,
+    Query(query): Query<HashMap<String, String>>,
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Response {
+    let forbidden = || {
+        (
+            StatusCode::FORBIDDEN,
+            Json(json!({"error": "invalid_signature"})),
+        )
+            .into_response()
+    };
+    let Ok(id) = uuid::Uuid::parse_str(&id) else {
+        return forbidden();
+    };
+    let (Some(expires), Some(signature)) = (
+        query.get("expires").and_then(|v| v.parse::<i64>().ok()),
+        query.get("signature"),
+    ) else {
+        return forbidden();
+    };
+    if !state
+        .exports
+        .verify(id, expires, signature, chrono::Utc::now())
+    {
+        return forbidden();
+    }
+    let job = match exports::find(&state, id).await {
+        Ok(job) => job,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": "db_error", "details": e.to_string()})),
+            )
+                .into_response();
+        }
+    };
+    let Some((path, format)) = job.and_then(|job| exports::download(&state.exports, &job)) else {
+        return (
+            StatusCode::GONE,
+            Json(json!({"error": "export_unavailable"})),
+        )
+            .into_response();
+    };
+    let mut response = static_files::serve_file(
+        &state.file_cache,
+        &path,
+        format.content_type(),
+        "private, no-store",
+        &headers,
+    )
+    .await;
+    if let Ok(value) = header::HeaderValue::from_str(&format!(
+        "attachment; filename=\"export-{id}.{}\"",
+        format.extension()
+    )) {
+        response
+            .headers_mut()
+            .insert(header::CONTENT_DISPOSITION, value);
+    }
+    response
+}
+
+/// Profile picture URL of `number` (`type`: `preview` or `image`, the
+/// default), cached until the CDN link expires
+/// (`PROFILE_PICTURE_CACHE_SECONDS` when it carries no expiry).
+pub async fn fetch_profile_picture_url(
+    Path(instance_name): Path<String>,
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<Value>,
+) -> impl IntoResponse {
+    let (jid, size) = match profile_pictures::request_from_body(&payload) {
+        Ok(request) => request,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": "invalid_request", "details": e.to_string()})),
+            );
+        }
+    };
+    let Some(client) = state.clients.get(&instance_name).map(|c| c.value().clone()) else {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "session_not_found", "session": instance_name})),
+        );
+    };
+
+    match profile_pictures::fetch(&state, &client, &instance_name, &jid, size).await {
+        Ok(picture) => (StatusCode::OK, Json(json!(picture))),
+        Err(e) => (
+            StatusCode::BAD_GATEWAY,
+            Json(json!({"error": "profile_picture_failed", "details": e.to_string()})),
+        ),
+    }
+}
+
+/// Archives or unarchives `chat` (`archive`, optional `lastMessage`).
+pub async fn archive_chat(
+    Path(instance_name): Path<String>,
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<Value>,
+) -> impl IntoResponse {
+    let action = ChatAction::archive_from_body(&payload);
+    change_chat(&state, &instance_name, &payload, action).await
+}
+
+/// Marks `chat` as unread on every device (optional `lastMessage`).
+pub async fn mark_chat_unread(
+    Path(instance_name): Path<String>,
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<Value>,
+) -> impl IntoResponse {
+    let action = Ok(ChatAction::mark_unread_from_body(&payload));
+    change_chat(&state, &instance_name, &payload, action).await
+}
+
+/// Pins or unpins `chat` (`pin`).
+pub async fn pin_chat(
+    Path(instance_name): Path<String>,
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<Value>,
+) -> impl IntoResponse {
+    let action = ChatAction::pin_from_body(&payload);
+    change_chat(&state, &instance_name, &payload, action).await
+}
+
+/// Mutes `chat` for `duration` seconds (forever when omitted) or unmutes it
+/// (`mute: false`).
+pub async fn mute_chat(
+    Path(instance_name): Path<String>,
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<Value>,
+) -> impl IntoResponse {
+    let action = ChatAction::mute_from_body(&payload, chrono::Utc::now().timestamp_millis());
+    change_chat(&state, &instance_name, &payload, action).await
+}
+
+/// Sets the disappearing messages timer of the direct chat `chat` to
+/// `expiration` seconds (`0` turns it off).
+pub async fn toggle_chat_ephemeral(
+    Path(instance_name): Path<String>,
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<Value>,
+) -> impl IntoResponse {
+    let action = ChatAction::ephemeral_from_body(&payload, chrono::Utc::now().timestamp());
+    change_chat(&state, &instance_name, &payload, action).await
+}
+
+/// Sends the app state patch of `action`, then updates `api_chats` and emits
+/// `CHATS_UPDATE`.
+async fn change_chat(
+    state: &Arc<AppState>,
+    instance_name: &str,
+    payload: &Value,
+    action: Result<ChatAction, chat_settings::ChatSettingError>,
+) -> (StatusCode, Json<Value>) {
+    let parsed = action.and_then(|action| Ok((action, chat_settings::chat_from_body(payload)?)));
+    let (action, chat) = match parsed {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": "invalid_request", "details": e.to_string()})),
+            );
+        }
+    };
+    let Some(client) = state.clients.get(instance_name).map(|c| c.value().clone()) else {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "session_not_found", "session": instance_name})),
+        );
+    };
+
+    if let Err(e) = action.apply(&client, &chat).await {
+        return (
+            StatusCode::BAD_GATEWAY,
+            Json(json!({"error": "app_state_patch_failed", "details": e.to_string()})),
+        );
+    }
+
+    let chat_id = chat.to_string();
+    if let Err(e) = action.store(state, instance_name, &chat_id).await {
+        tracing::warn!(
+            session = %instance_name,
+            chat = %chat_id,
+            error = %e,
+            "Falha ao salvar estado da conversa"
+        );
+    }
+    let event = action.event(&chat_id);
+    webhooks::enqueue(state, Some(instance_name), ChatsUpdate::EVENT, event.clone()).await;
+
+    (StatusCode::OK, Json(event))
+}
+
+pub async fn create_group(
+    Path(instance_name): Path<String>,
+    Json(_payload): Json<Value>,
+) -> impl IntoResponse {
+    (
+        StatusCode::CREATED,
+        Json(json!({
+            "instance": instance_name,
+            "status": "created"
+        })),
+    )
+}
+
+/// Groups the instance takes part in, in the Evolution group shape;
+/// participants only with `?getParticipants=true`.
+pub async fn fetch_groups(
+    Path(instance_name): Path<String>,
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<HashMap<String, String>>,
+) -> impl IntoResponse {
+    let with_participants = query
+        .get("getParticipants")
+        .is_some_and(|value| value == "true");
+    let Some(client) = state.clients.get(&instance_name).map(|c| c.value().clone()) else {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "session_not_found", "session": instance_name})),
+        );
+    };
+
+    let cache_key = if with_participants {
+        "participants"
+    } else {
+        "summary"
+    };
+    let cached = state
+        .cache
+        .get::<Vec<Value>>(CacheSpace::Groups, &instance_name, cache_key)
+        .await;
+    let groups = match cached {
+        Some(groups) => groups,
+        None => {
+            let mut groups: Vec<_> = match client.groups().get_participating().await {
+                Ok(groups) => groups.into_values().collect(),
+                Err(e) => {
+                    return (
+                        StatusCode::BAD_GATEWAY,
+                        Json(json!({"error": "fetch_groups_failed", "details": e.to_string()})),
+                    );
+                }
+            };
+            groups.sort_by_key(|group| group.id.to_string());
+            let groups: Vec<Value> = groups
+                .iter()
+                .map(|group| evolution::group(group, with_participants))
+                .collect();
+            state
+                .cache
+                .insert(CacheSpace::Groups, &instance_name, cache_key, &groups, None)
+                .await;
+            groups
+        }
+    };
+    if state.runtime_config().evolution_compat {
+        return (StatusCode::OK, Json(Value::Array(groups)));
+    }
+    (
         StatusCode::OK,
         Json(json!({
-            "instance": _instance_name,
-            "groups": []
+            "instance": instance_name,
+            "groups": groups
         })),
     )
 }
+
+fn invite_code_param(query: &HashMap<String, String>, keys: &[&str]) -> Option<String> {
+    keys.iter()
+        .filter_map(|key| query.get(*key))
+        .map(|raw| features::invite_code(raw))
+        .find(|code| !code.is_empty())
+        .map(str::to_string)
+}
+
+/// Group preview for `?code=` (a code or a `chat.whatsapp.com` link).
+pub async fn group_invite_info(
+    Path(instance_name): Path<String>,
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<HashMap<String, String>>,
+) -> impl IntoResponse {
+    let Some(code) = invite_code_param(&query, &["code", "inviteCode"]) else {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({"error": "invite_code_required"})),
+        );
+    };
+    let Some(client) = state.clients.get(&instance_name).map(|c| c.value().clone()) else {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "session_not_found", "session": instance_name})),
+        );
+    };
+
+    match client.groups().get_invite_info(&code).await {
+        Ok(info) => (StatusCode::OK, Json(json!(info))),
+        Err(e) => (
+            StatusCode::BAD_GATEWAY,
+            Json(json!({"error": "invite_info_failed", "details": e.to_string()})),
+        ),
+    }
+}
+
+/// Joins the group of `?inviteCode=`, stores it in `api_groups` and emits
+/// `GROUP_PARTICIPANTS_UPDATE`. Groups that need admin approval answer
+/// `pendingApproval: true` and are stored once the approval arrives.
+pub async fn accept_group_invite(
+    Path(instance_name): Path<String>,
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<HashMap<String, String>>,
+) -> impl IntoResponse {
+    let Some(code) = invite_code_param(&query, &["inviteCode", "code"]) else {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({"error": "invite_code_required"})),
+        );
+    };
+    let Some(client) = state.clients.get(&instance_name).map(|c| c.value().clone()) else {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "session_not_found", "session": instance_name})),
+        );
+    };
+
+    let joined = match client.groups().join_with_invite(&code).await {
+        Ok(joined) => joined,
+        Err(e) => {
+            return (
+                StatusCode::BAD_GATEWAY,
+                Json(json!({"error": "invite_accept_failed", "details": e.to_string()})),
+            );
+        }
+    };
+    let group_jid = joined.jid.to_string();
+    if joined.pending_approval {
+        return (
+            StatusCode::OK,
+            Json(json!({"accepted": false, "pendingApproval": true, "groupJid": group_jid})),
+        );
+    }
+
+    let (subject, participants) = match client.groups().get_metadata(&joined.jid).await {
+        Ok(metadata) => (
+            Some(metadata.subject),
+            Some(Value::Array(
+                metadata
+                    .participants
+                    .iter()
+                    .map(|p| json!({"id": p.jid.to_string(), "admin": p.is_admin}))
+                    .collect(),
+            )),
+        ),
+        Err(e) => {
+            tracing::warn!(
+                session = %instance_name,
+                group = %group_jid,
+                error = %e,
+                "Metadados do grupo indisponíveis após entrar pelo convite"
+            );
+            (None, None)
+        }
+    };
+    let stored = state
+        .api_store
+        .execute(
+            "INSERT INTO api_groups (session, id, subject, participants, created_at) \
+             VALUES ($1, $2, $3, $4, now()) \
+             ON CONFLICT (session, id) DO UPDATE SET \
+             subject = COALESCE(EXCLUDED.subject, api_groups.subject), \
+             participants = COALESCE(EXCLUDED.participants, api_groups.participants)",
+            vec![
+                ApiBind::Text(instance_name.clone()),
+                ApiBind::Text(group_jid.clone()),
+                ApiBind::NullableText(subject),
+                ApiBind::NullableJson(participants),
+            ],
+        )
+        .await;
+    if let Err(e) = stored {
+        tracing::warn!(
+            session = %instance_name,
+            group = %group_jid,
+            error = %e,
+            "Falha ao salvar grupo"
+        );
+    }
+
+    let participants: Vec<String> = own_jid(&state, &instance_name).await.into_iter().collect();
+    webhooks::enqueue(
+        &state,
+        Some(&instance_name),
+        "GROUP_PARTICIPANTS_UPDATE",
+        json!({"id": group_jid, "participants": participants, "action": "add"}),
+    )
+    .await;
+
+    (
+        StatusCode::OK,
+        Json(json!({"accepted": true, "pendingApproval": false, "groupJid": group_jid})),
+    )
+}
+
+/// Client of `instance_name` and the group of `?groupJid=` (or the body's
+/// `groupJid`).
+async fn group_target(
+    state: &AppState,
+    instance_name: &str,
+    query: &HashMap<String, String>,
+    payload: &Value,
+) -> Result<(Arc<crate::client::Client>, Jid), (StatusCode, Json<Value>)> {
+    let Some(raw) = query
+        .get("groupJid")
+        .map(String::as_str)
+        .or_else(|| payload["groupJid"].as_str())
+        .filter(|s| !s.trim().is_empty())
+    else {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({"error": "group_jid_required"})),
+        ));
+    };
+    let Some(group) = features::parse_group_jid(raw) else {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({"error": "invalid_group_jid", "groupJid": raw})),
+        ));
+    };
+    let Some(client) = state.clients.get(instance_name).map(|c| c.value().clone()) else {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "session_not_found", "session": instance_name})),
+        ));
+    };
+    Ok((client, group))
+}
+
+/// Fields of the group that `setting` changes, as sent in `GROUPS_UPDATE`.
+fn group_setting_fields(setting: GroupSetting) -> Value {
+    match setting {
+        GroupSetting::Announce(announce) => json!({"announce": announce}),
+        GroupSetting::Locked(restrict) => json!({"restrict": restrict}),
+        GroupSetting::Ephemeral(expiration) => json!({"ephemeralDuration": expiration}),
+        GroupSetting::MemberAddMode(mode) => json!({"memberAddMode": mode.as_str()}),
+    }
+}
+
+/// Sends `setting` to the group of the request and emits `GROUPS_UPDATE`.
+async fn change_group_setting(
+    state: &AppState,
+    instance_name: &str,
+    query: &HashMap<String, String>,
+    payload: &Value,
+    setting: GroupSetting,
+) -> (StatusCode, Json<Value>) {
+    let (client, group) = match group_target(state, instance_name, query, payload).await {
+        Ok(target) => target,
+        Err(response) => return response,
+    };
+    if let Err(e) = client.groups().update_setting(&group, setting).await {
+        return (
+            StatusCode::BAD_GATEWAY,
+            Json(json!({"error": "group_setting_failed", "details": e.to_string()})),
+        );
+    }
+
+    let mut event = group_setting_fields(setting);
+    event["id"] = Value::String(group.to_string());
+    webhooks::enqueue(state, Some(instance_name), "GROUPS_UPDATE", event.clone()).await;
+
+    (
+        StatusCode::OK,
+        Json(json!({"updated": true, "group": event})),
+    )
+}
+
+/// `{"action": "announcement" | "not_announcement" | "locked" | "unlocked"}`
+/// for `?groupJid=`.
+pub async fn update_group_setting(
+    Path(instance_name): Path<String>,
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<HashMap<String, String>>,
+    Json(payload): Json<Value>,
+) -> impl IntoResponse {
+    let action = payload["action"].as_str().unwrap_or_default();
+    let Some(setting) = GroupSetting::from_action(action) else {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({"error": "invalid_action", "action": action})),
+        );
+    };
+    change_group_setting(&state, &instance_name, &query, &payload, setting).await
+}
+
+/// `{"expiration": seconds}` sets the disappearing messages timer of
+/// `?groupJid=`; `0` turns it off.
+pub async fn toggle_group_ephemeral(
+    Path(instance_name): Path<String>,
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<HashMap<String, String>>,
+    Json(payload): Json<Value>,
+) -> impl IntoResponse {
+    let expiration = payload["expiration"]
+        .as_u64()
+        .and_then(|v| u32::try_from(v).ok())
+        .filter(|v| EPHEMERAL_DURATIONS.contains(v));
+    let Some(expiration) = expiration else {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({
+                "error": "invalid_expiration",
+                "allowed": EPHEMERAL_DURATIONS,
+            })),
+        );
+    };
+    let setting = GroupSetting::Ephemeral(expiration);
+    change_group_setting(&state, &instance_name, &query, &payload, setting).await
+}
+
+/// `{"mode": "admin_add" | "all_member_add"}`: who may add members to
+/// `?groupJid=`.
+pub async fn update_group_member_add_mode(
+    Path(instance_name): Path<String>,
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<HashMap<String, String>>,
+    Json(payload): Json<Value>,
+) -> impl IntoResponse {
+    let mode = payload["mode"].as_str().unwrap_or_default();
+    let Some(mode) = MemberAddMode::parse(mode) else {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({"error": "invalid_mode", "mode": mode})),
+        );
+    };
+    let setting = GroupSetting::MemberAddMode(mode);
+    change_group_setting(&state, &instance_name, &query, &payload, setting).await
+}
+
+/// Decrypts the media of a stored (or inline) message and returns it as base64.
+///
+/// Body: `{"message": {"key": {"id": "..."}, "message": {...}?}, "convertToMp3": bool}`.
+/// The inline `message.message` proto is used when present; otherwise the
+/// message is looked up by `key.id` among the stored inbound messages.
+pub async fn get_base64_from_media_message(
+    Path(instance_name): Path<String>,
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<Value>,
+) -> impl IntoResponse {
+    let convert_to_mp3 = payload["convertToMp3"].as_bool().unwrap_or(false);
+    let inline = payload["message"].get("message").cloned();
+    let message_id = payload["message"]["key"]["id"].as_str().unwrap_or("");
+
+    let message = match inline {
+        Some(value) => match serde_json::from_value::<wa::Message>(value) {
+            Ok(message) => message,
+            Err(err) => {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    Json(json!({"error": "invalid_message", "details": err.to_string()})),
+                );
+            }
+        },
+        None if message_id.is_empty() => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": "message_id_required"})),
+            );
+        }
+        None => match media::load_stored_message(&state, &instance_name, message_id).await {
+            Ok(Some(message)) => message,
+            Ok(None) => {
+                return (
+                    StatusCode::NOT_FOUND,
+                    Json(json!({"error": "message_not_found"})),
+                );
+            }
+            Err(err) => {
+                return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(json!({"error": "db_error", "details": err.to_string()})),
+                );
+            }
+        },
+    };
+
+    let Some(client) = state
+        .clients
+        .get(&instance_name)
+        .map(|entry| entry.value().clone())
+    else {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "instance_not_found"})),
+        );
+    };
+
+    match media::download_media(
+        &client,
+        &message,
+        convert_to_mp3.then_some(state.thumbnails.ffmpeg.as_str()),
+    )
+    .await {
+        Ok(downloaded) => (StatusCode::OK, Json(downloaded.to_json())),
+        Err(MediaError::NoMedia) => (StatusCode::BAD_REQUEST, Json(json!({"error": "no_media"}))),
+        Err(err) => (
+            StatusCode::BAD_GATEWAY,
+            Json(json!({"error": "media_download_failed", "details": err.to_string()})),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    include!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/tests/server/handlers_tests.rs"));
+}
@@ -0,0 +1,216 @@
+//! Dependency health behind `/healthz`, `/readyz` and `/healthz/deep`.
+//!
+//! Every dependency is checked with a timeout and reported as `ok`,
+//! `degraded` (slow, or working on a fallback) or `down`, with its latency.
+//! The rollup is `unhealthy` when a critical dependency (`HEALTH_CRITICAL`,
+//! only the database by default) is down and `degraded` when anything else
+//! is off. `/readyz` stops accepting traffic only while unhealthy, so a
+//! flaky event broker or version fetch never drains the pod.
+
+use crate::api_store::ApiStore;
+use crate::server::AppState;
+use serde::Serialize;
+use serde_json::{Value, json};
+use std::time::{Duration, Instant};
+
+pub use crate::config::HealthConfig;
+
+pub const DATABASE: &str = "database";
+pub const NATS: &str = "nats";
+pub const WA_VERSION: &str = "wa_version";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DependencyStatus {
+    Ok,
+    Degraded,
+    Down,
+}
+
+/// Rollup of every dependency.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthStatus {
+    Healthy,
+    Degraded,
+    Unhealthy,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DependencyHealth {
+    pub name: &'static str,
+    pub status: DependencyStatus,
+    /// Whether being down makes the whole service unhealthy (not ready).
+    pub critical: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latency_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<Value>,
+}
+
+impl DependencyHealth {
+    fn new(name: &'static str, status: DependencyStatus) -> Self {
+        Self {
+            name,
+            status,
+            critical: false,
+            latency_ms: None,
+            error: None,
+            detail: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HealthReport {
+    pub status: HealthStatus,
+    pub dependencies: Vec<DependencyHealth>,
+}
+
+impl HealthReport {
+    /// Builds the report, marking the dependencies listed in `config` as
+    /// critical.
+    pub fn new(config: &HealthConfig, mut dependencies: Vec<DependencyHealth>) -> Self {
+        for dependency in &mut dependencies {
+            dependency.critical = config.is_critical(dependency.name);
+        }
+        Self {
+            status: rollup(&dependencies),
+            dependencies,
+        }
+    }
+
+    /// Ready for traffic: no critical dependency is down.
+    pub fn ready(&self) -> bool {
+        self.status != HealthStatus::Unhealthy
+    }
+
+    pub fn dependency(&self, name: &str) -> Option<&DependencyHealth> {
+        self.dependencies.iter().find(|d| d.name == name)
+    }
+}
+
+/// `unhealthy` if a critical dependency is down, `degraded` if any
+/// dependency is not `ok`, `healthy` otherwise.
+pub fn rollup(dependencies: &[DependencyHealth]) -> HealthStatus {
+    if dependencies
+        .iter()
+        .any(|d| d.critical && d.status == DependencyStatus::Down)
+    {
+        HealthStatus::Unhealthy
+    } else if dependencies
+        .iter()
+        .any(|d| d.status != DependencyStatus::Ok)
+    {
+        HealthStatus::Degraded
+    } else {
+        HealthStatus::Healthy
+    }
+}
+
+impl HealthConfig {
+    pub fn is_critical(&self, name: &str) -> bool {
+        self.critical.iter().any(|critical| critical == name)
+    }
+}
+
+/// Checks every dependency of the running server.
+pub async fn check(state: &AppState) -> HealthReport {
+    let config = &state.health;
+    let mut dependencies = vec![check_database(state.api_store.as_ref(), config).await];
+    #[cfg(feature = "nats")]
+    if let Some(sink) = &state.nats {
+        dependencies.push(check_nats(sink));
+    }
+    dependencies.push(check_wa_version(
+        state.clients.iter().map(|entry| entry.value().clone()),
+    ));
+    HealthReport::new(config, dependencies)
+}
+
+/// Round-trip to the API store.
+pub async fn check_database(store: &dyn ApiStore, config: &HealthConfig) -> DependencyHealth {
+    let started = Instant::now();
+    let ping = tokio::time::timeout(config.timeout, store.ping()).await;
+    let latency = started.elapsed();
+    let mut health = match ping {
+        Ok(Ok(())) if latency > config.slow => {
+            DependencyHealth::new(DATABASE, DependencyStatus::Degraded)
+        }
+        Ok(Ok(())) => DependencyHealth::new(DATABASE, DependencyStatus::Ok),
+        Ok(Err(e)) => DependencyHealth {
+            error: Some(e.to_string()),
+            ..DependencyHealth::new(DATABASE, DependencyStatus::Down)
+        },
+        Err(_) => DependencyHealth {
+            error: Some("timeout".to_string()),
+            ..DependencyHealth::new(DATABASE, DependencyStatus::Down)
+        },
+    };
+    health.latency_ms = Some(millis(latency));
+    health
+}
+
+/// Connection of the NATS event sink and how full its publish queue is.
+#[cfg(feature = "nats")]
+pub fn check_nats(sink: &crate::server::nats::NatsSink) -> DependencyHealth {
+    let queued = sink.queued();
+    let status = if !sink.is_connected() {
+        DependencyStatus::Down
+    } else if queued > sink.capacity() / 2 {
+        DependencyStatus::Degraded
+    } else {
+        DependencyStatus::Ok
+    };
+    DependencyHealth {
+        error: (status == DependencyStatus::Down).then(|| "disconnected".to_string()),
+        detail: Some(json!({"queued": queued})),
+        ..DependencyHealth::new(NATS, status)
+    }
+}
+
+/// Latest sw.js fetch across the instances. A failed fetch leaves the
+/// instances on a fallback or stored version, so it is `down` but not
+/// critical by default.
+pub fn check_wa_version(
+    clients: impl IntoIterator<Item = std::sync::Arc<crate::client::Client>>,
+) -> DependencyHealth {
+    let mut current = None;
+    let latest = clients
+        .into_iter()
+        .filter_map(|client| {
+            current = current.or(client.version_manager.current());
+            client.version_manager.last_fetch()
+        })
+        .max_by_key(|fetch| fetch.at);
+    let detail = json!({
+        "current": current.map(crate::version::format_version),
+        "fetchedAt": latest.as_ref().map(|fetch| fetch.at),
+    });
+    // Nothing fetched yet (pinned or static source): nothing to fail.
+    let (status, error, latency_ms) = match latest {
+        Some(fetch) if fetch.error.is_some() => {
+            (DependencyStatus::Down, fetch.error, Some(fetch.latency_ms))
+        }
+        Some(fetch) => (DependencyStatus::Ok, None, Some(fetch.latency_ms)),
+        None => (DependencyStatus::Ok, None, None),
+    };
+    DependencyHealth {
+        latency_ms,
+        error,
+        detail: Some(detail),
+        ..DependencyHealth::new(WA_VERSION, status)
+    }
+}
+
+fn millis(duration: Duration) -> u64 {
+    u64::try_from(duration.as_millis()).unwrap_or(u64::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    include!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/tests/server/health_tests.rs"));
+}
@@ -0,0 +1,169 @@
+//! Shared client for outbound HTTP calls (webhooks, Graph API, WA version
+//! fetch, media and link preview downloads).
+//!
+//! One pooled agent with a global timeout and optional proxy, wrapped with
+//! retries (exponential backoff with jitter) on transport errors, `429` and
+//! `5xx`, and a circuit breaker per destination (`scheme://host[:port]`):
+//! after `breaker_threshold` consecutive failed calls the destination is
+//! refused for `breaker_cooldown`, then one call is let through to probe it.
+
+use async_trait::async_trait;
+use chatwarp_api_ureq_http_client::{UreqConfig, UreqHttpClient};
+use dashmap::DashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use warp_core::net::{HttpClient, HttpRequest, HttpResponse};
+
+pub use crate::config::HttpClientConfig;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum HttpClientError {
+    #[error("circuit open for {0}")]
+    CircuitOpen(String),
+}
+
+impl HttpClientConfig {
+    /// Delay before retry number `retry` (1-based): doubles each time, capped
+    /// at `max_backoff`, then scaled into `[50%, 100%]` by `jitter` (`0..1`).
+    pub fn backoff(&self, retry: u32, jitter: f64) -> Duration {
+        let factor = 2u32.saturating_pow(retry.saturating_sub(1));
+        let delay = self
+            .initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff);
+        delay.mul_f64(0.5 + jitter.clamp(0.0, 1.0) / 2.0)
+    }
+}
+
+/// Whether a response status is worth another attempt.
+pub fn retryable_status(status: u16) -> bool {
+    status == 429 || ((500..=599).contains(&status) && status != 501)
+}
+
+/// `scheme://host[:port]` of `url`, lowercased; the key of its circuit.
+pub fn destination(url: &str) -> String {
+    let (scheme, rest) = url.split_once("://").unwrap_or(("http", url));
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or(rest);
+    let host = authority.rsplit_once('@').map_or(authority, |(_, host)| host);
+    format!("{}://{}", scheme, host).to_ascii_lowercase()
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct Circuit {
+    failures: u32,
+    open_until: Option<Instant>,
+}
+
+/// Outbound HTTP client kept on `AppState`; clones share pool and circuits.
+#[derive(Clone)]
+pub struct SharedHttpClient {
+    inner: Arc<dyn HttpClient>,
+    config: HttpClientConfig,
+    circuits: Arc<DashMap<String, Circuit>>,
+}
+
+impl SharedHttpClient {
+    /// Wraps `inner` with the retry and circuit policy of `config`.
+    pub fn new(inner: Arc<dyn HttpClient>, config: HttpClientConfig) -> Self {
+        Self {
+            inner,
+            config,
+            circuits: Arc::new(DashMap::new()),
+        }
+    }
+
+    /// Pooled ureq client with the timeout and proxy of `config`. Fails only
+    /// on an invalid proxy URL.
+    pub fn from_config(config: HttpClientConfig) -> anyhow::Result<Self> {
+        let inner = UreqHttpClient::with_config(&UreqConfig {
+            timeout: Some(config.timeout),
+            proxy: config.proxy.clone(),
+            keep_error_responses: true,
+        })?;
+        Ok(Self::new(Arc::new(inner), config))
+    }
+
+    pub fn config(&self) -> &HttpClientConfig {
+        &self.config
+    }
+
+    /// Whether calls to `destination` are currently refused.
+    pub fn is_open(&self, destination: &str) -> bool {
+        self.circuits
+            .get(destination)
+            .and_then(|circuit| circuit.open_until)
+            .is_some_and(|until| Instant::now() < until)
+    }
+
+    /// Destinations with an open circuit.
+    pub fn open_circuits(&self) -> Vec<String> {
+        let now = Instant::now();
+        self.circuits
+            .iter()
+            .filter(|entry| entry.open_until.is_some_and(|until| now < until))
+            .map(|entry| entry.key().clone())
+            .collect()
+    }
+
+    fn record(&self, destination: &str, ok: bool) {
+        if self.config.breaker_threshold == 0 {
+            return;
+        }
+        if ok {
+            self.circuits.remove(destination);
+            return;
+        }
+        let mut circuit = self.circuits.entry(destination.to_string()).or_default();
+        circuit.failures = circuit.failures.saturating_add(1);
+        if circuit.failures >= self.config.breaker_threshold {
+            circuit.open_until = Some(Instant::now() + self.config.breaker_cooldown);
+            tracing::warn!(
+                destination = %destination,
+                failures = circuit.failures,
+                "Circuito HTTP aberto para o destino"
+            );
+        }
+    }
+}
+
+#[async_trait]
+impl HttpClient for SharedHttpClient {
+    async fn execute(&self, request: HttpRequest) -> anyhow::Result<HttpResponse> {
+        let destination = destination(&request.url);
+        if self.is_open(&destination) {
+            return Err(HttpClientError::CircuitOpen(destination).into());
+        }
+
+        let mut attempt = 1;
+        loop {
+            let result = self.inner.execute(request.clone()).await;
+            let retry = match &result {
+                Ok(response) => retryable_status(response.status_code),
+                Err(_) => true,
+            };
+            if !retry {
+                self.record(&destination, true);
+                return result;
+            }
+            if attempt >= self.config.max_attempts {
+                self.record(&destination, false);
+                return result;
+            }
+            let delay = self.config.backoff(attempt, rand::random::<f64>());
+            tracing::debug!(
+                url = %request.url,
+                attempt,
+                delay_ms = delay.as_millis() as u64,
+                "Repetindo requisição HTTP"
+            );
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    include!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/tests/server/http_client_tests.rs"));
+}
@@ -0,0 +1,372 @@
+//! Inbound webhook receiver: `POST /integration/inbound/:instance`.
+//!
+//! Low-code tools that can only push webhooks send
+//! `{"to", "text"?, "mediaUrl"?, "mediaType"?, "mimetype"?, "fileName"?}`
+//! here instead of calling the message routes. The request carries the
+//! instance's inbound secret (`X-Inbound-Secret`, `Authorization: Bearer`
+//! or `?secret=`) rather than an API credential. The secret is generated by
+//! `PUT /instance/inbound/:name`, shown once and kept in
+//! `api_inbound_hooks` as a SHA-256 hash. Accepted messages go through the
+//! same queue, quotas and events as `/message/*`; the answer is their key.
+
+use crate::api_store::ApiBind;
+use crate::server::AppState;
+use crate::server::participants::{NumberError, Target};
+use crate::server::routes::chat::chat_manager;
+use crate::server::workspaces::hash_api_key;
+use axum::{
+    Json,
+    body::Bytes,
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::sync::Arc;
+use thiserror::Error;
+use tracing::{debug, info};
+use uuid::Uuid;
+
+/// Prefix of the receiver route.
+pub const RECEIVER_PREFIX: &str = "/integration/inbound/";
+const SECRET_HEADER: &str = "x-inbound-secret";
+/// Shortest secret accepted from `PUT /instance/inbound/:name`.
+pub const MIN_SECRET_LEN: usize = 16;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum InboundError {
+    #[error("body must be a JSON object with to and text or mediaUrl")]
+    InvalidBody,
+    #[error("to is required")]
+    MissingTo,
+    #[error("text or mediaUrl is required")]
+    MissingContent,
+    #[error("invalid to: {0}")]
+    InvalidNumber(#[from] NumberError),
+    #[error("mediaUrl must be an http(s) URL, got {0:?}")]
+    InvalidMediaUrl(String),
+    #[error("unknown mediaType {0:?}: expected image, video, audio or document")]
+    InvalidMediaType(String),
+    #[error("audio is sent without text")]
+    AudioWithText,
+    #[error("secret must have at least {MIN_SECRET_LEN} characters")]
+    SecretTooShort,
+}
+
+/// Body accepted by the receiver.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InboundMessage {
+    /// Phone number in any format, or a JID.
+    #[serde(default)]
+    pub to: String,
+    /// Message text, or the caption of the media.
+    #[serde(default)]
+    pub text: Option<String>,
+    #[serde(default)]
+    pub media_url: Option<String>,
+    /// `image`, `video`, `audio` or `document`; guessed from `mimetype` or
+    /// the URL's extension when omitted.
+    #[serde(default)]
+    pub media_type: Option<String>,
+    #[serde(default)]
+    pub mimetype: Option<String>,
+    #[serde(default)]
+    pub file_name: Option<String>,
+}
+
+impl InboundMessage {
+    pub fn from_body(body: &[u8]) -> Result<Self, InboundError> {
+        let body: Value = serde_json::from_slice(body).map_err(|_| InboundError::InvalidBody)?;
+        if !body.is_object() {
+            return Err(InboundError::InvalidBody);
+        }
+        let message: Self = serde_json::from_value(body).map_err(|_| InboundError::InvalidBody)?;
+        if message.to.trim().is_empty() {
+            return Err(InboundError::MissingTo);
+        }
+        Ok(message)
+    }
+
+    /// Queue body and message type of the message for `chat_id`.
+    pub fn queue_body(
+        &self,
+        session: &str,
+        chat_id: &str,
+    ) -> Result<(Value, &'static str), InboundError> {
+        let text = self
+            .text
+            .as_deref()
+            .map(str::trim)
+            .filter(|text| !text.is_empty());
+        let media_url = self
+            .media_url
+            .as_deref()
+            .map(str::trim)
+            .filter(|url| !url.is_empty());
+        let Some(url) = media_url else {
+            let text = text.ok_or(InboundError::MissingContent)?;
+            return Ok((
+                json!({"session": session, "chatId": chat_id, "text": text}),
+                "text",
+            ));
+        };
+        if !(url.starts_with("http://") || url.starts_with("https://")) {
+            return Err(InboundError::InvalidMediaUrl(url.to_string()));
+        }
+
+        let message_type = match self.media_type.as_deref().map(str::trim) {
+            Some(kind) => media_message_type(kind)
+                .ok_or_else(|| InboundError::InvalidMediaType(kind.to_string()))?,
+            None => guess_message_type(self.mimetype.as_deref(), url),
+        };
+        let mut body = json!({"session": session, "chatId": chat_id, "url": url});
+        if let Some(text) = text {
+            if message_type == "audio" {
+                return Err(InboundError::AudioWithText);
+            }
+            body["caption"] = json!(text);
+        }
+        if let Some(mimetype) = &self.mimetype {
+            body["mimetype"] = json!(mimetype);
+        }
+        if let Some(file_name) = &self.file_name {
+            body["filename"] = json!(file_name);
+        }
+        Ok((body, message_type))
+    }
+}
+
+/// Queued message type of a `mediaType`.
+fn media_message_type(kind: &str) -> Option<&'static str> {
+    match kind.to_ascii_lowercase().as_str() {
+        "image" => Some("image"),
+        "video" => Some("video"),
+        "audio" => Some("audio"),
+        "document" | "file" => Some("file"),
+        _ => None,
+    }
+}
+
+/// Message type from the mimetype, else the URL's extension; anything
+/// unknown goes as a document.
+fn guess_message_type(mimetype: Option<&str>, url: &str) -> &'static str {
+    if let Some(mimetype) = mimetype.map(str::to_ascii_lowercase) {
+        return match mimetype.split('/').next() {
+            Some("image") => "image",
+            Some("video") => "video",
+            Some("audio") => "audio",
+            _ => "file",
+        };
+    }
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    let extension = path
+        .rsplit_once('.')
+        .map(|(_, ext)| ext.to_ascii_lowercase())
+        .unwrap_or_default();
+    match extension.as_str() {
+        "jpg" | "jpeg" | "png" | "gif" => "image",
+        "mp4" | "3gp" | "mov" => "video",
+        "mp3" | "ogg" | "opus" | "m4a" | "aac" => "audio",
+        _ => "file",
+    }
+}
+
+/// Whether `path` is the receiver, which carries its own credentials.
+pub fn is_receiver_path(path: &str) -> bool {
+    path.strip_prefix(RECEIVER_PREFIX)
+        .is_some_and(|instance| !instance.is_empty() && !instance.contains('/'))
+}
+
+/// Secret of a request: `X-Inbound-Secret`, a bearer token or `?secret=`.
+pub fn request_secret<'a>(
+    headers: &'a HeaderMap,
+    query: &'a HashMap<String, String>,
+) -> Option<&'a str> {
+    let value = |name: &str| headers.get(name).and_then(|v| v.to_str().ok());
+    value(SECRET_HEADER)
+        .or_else(|| value(header::AUTHORIZATION.as_str()).and_then(|v| v.strip_prefix("Bearer ")))
+        .or_else(|| query.get("secret").map(String::as_str))
+        .map(str::trim)
+        .filter(|secret| !secret.is_empty())
+}
+
+/// A fresh random secret (64 hex characters).
+pub fn generate_secret() -> String {
+    hex::encode([Uuid::new_v4().into_bytes(), Uuid::new_v4().into_bytes()].concat())
+}
+
+/// Secret of a `PUT /instance/inbound/:name` body: its `secret`, or a
+/// fresh one when omitted.
+pub fn secret_from_body(body: Option<&Value>) -> Result<String, InboundError> {
+    match body
+        .and_then(|body| body.get("secret"))
+        .and_then(Value::as_str)
+        .map(str::trim)
+    {
+        Some(secret) if secret.chars().count() < MIN_SECRET_LEN => {
+            Err(InboundError::SecretTooShort)
+        }
+        Some(secret) => Ok(secret.to_string()),
+        None => Ok(generate_secret()),
+    }
+}
+
+/// Whether `secret` hashes to `stored`, compared in constant time.
+pub fn secret_matches(secret: &str, stored: &str) -> bool {
+    let provided = hash_api_key(secret);
+    provided.len() == stored.len()
+        && provided
+            .bytes()
+            .zip(stored.bytes())
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// Hook of `session`: `{"createdAt", "lastUsedAt"}`, `None` when it has
+/// none.
+pub async fn fetch(state: &AppState, session: &str) -> anyhow::Result<Option<Value>> {
+    let rows = state
+        .api_store
+        .query_json(
+            "SELECT jsonb_build_object('createdAt', created_at, 'lastUsedAt', last_used_at) as value \
+             FROM api_inbound_hooks WHERE session = $1",
+            vec![ApiBind::Text(session.to_string())],
+        )
+        .await?;
+    Ok(rows.into_iter().next())
+}
+
+/// Sets the secret of `session`, replacing any previous one. Returns
+/// whether the instance exists.
+pub async fn enable(state: &AppState, session: &str, secret: &str) -> anyhow::Result<bool> {
+    let updated = state
+        .api_store
+        .execute(
+            "INSERT INTO api_inbound_hooks (session, secret_hash) \
+             SELECT session, $2 FROM api_sessions WHERE session = $1 \
+             ON CONFLICT (session) DO UPDATE SET secret_hash = EXCLUDED.secret_hash, \
+                created_at = now(), last_used_at = NULL",
+            vec![
+                ApiBind::Text(session.to_string()),
+                ApiBind::Text(hash_api_key(secret)),
+            ],
+        )
+        .await?;
+    Ok(updated > 0)
+}
+
+/// Removes the hook of `session`. Returns whether it had one.
+pub async fn disable(state: &AppState, session: &str) -> anyhow::Result<bool> {
+    let deleted = state
+        .api_store
+        .execute(
+            "DELETE FROM api_inbound_hooks WHERE session = $1",
+            vec![ApiBind::Text(session.to_string())],
+        )
+        .await?;
+    Ok(deleted > 0)
+}
+
+/// Whether `secret` opens the hook of `session`; notes its use when it
+/// does.
+async fn authorize(state: &AppState, session: &str, secret: &str) -> anyhow::Result<bool> {
+    let rows = state
+        .api_store
+        .query_json(
+            "SELECT to_jsonb(secret_hash) as value FROM api_inbound_hooks WHERE session = $1",
+            vec![ApiBind::Text(session.to_string())],
+        )
+        .await?;
+    let Some(stored) = rows.first().and_then(Value::as_str) else {
+        return Ok(false);
+    };
+    if !secret_matches(secret, stored) {
+        return Ok(false);
+    }
+    if let Err(e) = state
+        .api_store
+        .execute(
+            "UPDATE api_inbound_hooks SET last_used_at = now() WHERE session = $1",
+            vec![ApiBind::Text(session.to_string())],
+        )
+        .await
+    {
+        debug!(session, error = %e, "Falha ao registrar uso do webhook de entrada");
+    }
+    Ok(true)
+}
+
+fn error_response(status: StatusCode, error: &str, details: Option<String>) -> Response {
+    let mut body = json!({"error": error});
+    if let Some(details) = details {
+        body["details"] = json!(details);
+    }
+    (status, Json(body)).into_response()
+}
+
+/// `POST /integration/inbound/:instance`: queues the message of the body
+/// and answers with its key.
+pub async fn receive(
+    Path(instance): Path<String>,
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<HashMap<String, String>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let Some(secret) = request_secret(&headers, &query) else {
+        return error_response(StatusCode::UNAUTHORIZED, "secret_required", None);
+    };
+    match authorize(&state, &instance, secret).await {
+        Ok(true) => {}
+        // Unknown instances and wrong secrets look the same to the caller.
+        Ok(false) => return error_response(StatusCode::UNAUTHORIZED, "invalid_secret", None),
+        Err(e) => {
+            return error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "db_error",
+                Some(e.to_string()),
+            );
+        }
+    }
+
+    let queued = InboundMessage::from_body(&body).and_then(|message| {
+        let chat_id = state.participants.normalize(&message.to, Target::Chat)?;
+        message.queue_body(&instance, &chat_id.to_string())
+    });
+    let (queue_body, message_type) = match queued {
+        Ok(queued) => queued,
+        Err(e) => {
+            return error_response(
+                StatusCode::BAD_REQUEST,
+                "invalid_message",
+                Some(e.to_string()),
+            );
+        }
+    };
+
+    info!(session = %instance, message_type, "Mensagem recebida pelo webhook de entrada");
+    match chat_manager::queue_message(state, queue_body, message_type, true).await {
+        Ok(row) => (
+            StatusCode::OK,
+            Json(json!({
+                "key": {
+                    "remoteJid": row["chat_id"],
+                    "fromMe": true,
+                    "id": row["id"],
+                },
+                "messageType": message_type,
+                "status": row["status"],
+            })),
+        )
+            .into_response(),
+        Err(response) => response,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    include!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/tests/server/inbound_tests.rs"));
+}
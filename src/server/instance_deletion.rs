@@ -0,0 +1,544 @@
+//! Orchestrated instance deletion.
+//!
+//! `/instance/delete/:name` and `DELETE /sessions/:session` take
+//! [`DeletionOptions`] and [`run`] performs the chosen steps in a fixed
+//! order: log the device out of WhatsApp, stop the connection, drop the
+//! stored pairing and Signal sessions, purge the instance data, then
+//! disable or delete the instance. Every step emits
+//! `INSTANCE_DELETE_PROGRESS`; the first one that fails ends the sequence,
+//! so a failed logout never leaves a deleted instance with its device
+//! still linked. Once every step succeeded the instance's Sentry hub is
+//! dropped too.
+
+use crate::api_store::ApiBind;
+use crate::server::AppState;
+use crate::server::events::{self, InstanceDeleteProgress};
+use crate::server::exports;
+use crate::server::outbox;
+use axum::http::StatusCode;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use thiserror::Error;
+use uuid::Uuid;
+
+/// Device the server opens its auth store with (`PostgresStore::connect`
+/// always uses the first one).
+const DEVICE_ID: i32 = 1;
+
+/// Tables holding the pairing and Signal state, keyed by `device_id`.
+const AUTH_TABLES: &[&str] = &[
+    "identities",
+    "sessions",
+    "prekeys",
+    "signed_prekeys",
+    "sender_keys",
+    "sender_key_status",
+    "skdm_recipients",
+    "base_keys",
+    "device_registry",
+    "lid_pn_mapping",
+    "app_state_keys",
+    "app_state_versions",
+    "app_state_mutation_macs",
+];
+
+/// Tables holding instance data, keyed by `session`. Export jobs are
+/// deleted separately so their files go too; `event_outbox` keeps the
+/// events still in flight, including this deletion's progress.
+const DATA_TABLES: &[&str] = &[
+    "api_message_reactions",
+    "api_label_chats",
+    "api_labels",
+    "api_messages",
+    "api_chats",
+    "api_contacts",
+    "api_groups",
+    "api_presence",
+    "api_status_updates",
+    "api_channels",
+    "api_profiles",
+    "api_profile_pictures",
+    "api_events",
+    "api_inbound_dedup",
+    "webhook_outbox",
+    "webhook_deliveries",
+    "event_deadletters",
+];
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum DeletionError {
+    #[error("body must be an object with mode, logout, purgeAuth, purgeData and/or soft")]
+    InvalidBody,
+    #[error("unknown mode {0:?}; expected keep-auth, purge-data, logout-first or disable")]
+    UnknownMode(String),
+    #[error("deleting an instance removes its data; use soft to keep it")]
+    DataRequired,
+}
+
+/// What a deletion does besides stopping the instance.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeletionOptions {
+    /// Unlink the device from the WhatsApp account before anything else.
+    pub logout: bool,
+    /// Drop the stored pairing and Signal sessions; the next connection
+    /// asks for a new QR code.
+    pub purge_auth: bool,
+    /// Delete messages, media, chats, contacts, exports and webhook logs.
+    pub purge_data: bool,
+    /// Disable the instance instead of deleting it.
+    pub soft: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct RawOptions {
+    mode: Option<String>,
+    logout: Option<bool>,
+    purge_auth: Option<bool>,
+    purge_data: Option<bool>,
+    soft: Option<bool>,
+}
+
+impl Default for DeletionOptions {
+    fn default() -> Self {
+        Self::KEEP_AUTH
+    }
+}
+
+impl DeletionOptions {
+    /// Deletes the instance and its data; the pairing stays, so an
+    /// instance created again under the same device reconnects without a
+    /// QR code. The default.
+    pub const KEEP_AUTH: Self = Self {
+        logout: false,
+        purge_auth: false,
+        purge_data: true,
+        soft: false,
+    };
+    /// Deletes the instance, its data and the stored auth.
+    pub const PURGE_DATA: Self = Self {
+        purge_auth: true,
+        ..Self::KEEP_AUTH
+    };
+    /// Logs out of WhatsApp, then deletes everything.
+    pub const LOGOUT_FIRST: Self = Self {
+        logout: true,
+        ..Self::PURGE_DATA
+    };
+    /// Stops the instance and marks it `disabled`, keeping everything.
+    pub const DISABLE: Self = Self {
+        logout: false,
+        purge_auth: false,
+        purge_data: false,
+        soft: true,
+    };
+
+    pub fn from_mode(mode: &str) -> Result<Self, DeletionError> {
+        match mode.trim() {
+            "keep-auth" => Ok(Self::KEEP_AUTH),
+            "purge-data" => Ok(Self::PURGE_DATA),
+            "logout-first" => Ok(Self::LOGOUT_FIRST),
+            "disable" => Ok(Self::DISABLE),
+            other => Err(DeletionError::UnknownMode(other.to_string())),
+        }
+    }
+
+    /// Reads `{"mode"?, "logout"?, "purgeAuth"?, "purgeData"?, "soft"?}`.
+    /// The flags override the `mode` preset, which defaults to `disable`
+    /// with `soft` and to `keep-auth` otherwise.
+    pub fn from_body(body: Option<&Value>) -> Result<Self, DeletionError> {
+        let raw: RawOptions = match body {
+            None | Some(Value::Null) => RawOptions::default(),
+            Some(body) => {
+                serde_json::from_value(body.clone()).map_err(|_| DeletionError::InvalidBody)?
+            }
+        };
+        let base = match (&raw.mode, raw.soft) {
+            (Some(mode), _) => Self::from_mode(mode)?,
+            (None, Some(true)) => Self::DISABLE,
+            (None, _) => Self::KEEP_AUTH,
+        };
+        let options = Self {
+            logout: raw.logout.unwrap_or(base.logout),
+            purge_auth: raw.purge_auth.unwrap_or(base.purge_auth),
+            purge_data: raw.purge_data.unwrap_or(base.purge_data),
+            soft: raw.soft.unwrap_or(base.soft),
+        };
+        if !options.soft && !options.purge_data {
+            return Err(DeletionError::DataRequired);
+        }
+        Ok(options)
+    }
+
+    /// Steps in the order they run.
+    pub fn steps(&self) -> Vec<DeletionStep> {
+        let mut steps = Vec::new();
+        if self.logout {
+            steps.push(DeletionStep::Logout);
+        }
+        steps.push(DeletionStep::Stop);
+        if self.purge_auth {
+            steps.push(DeletionStep::PurgeAuth);
+        }
+        if self.purge_data {
+            steps.push(DeletionStep::PurgeData);
+        }
+        steps.push(if self.soft {
+            DeletionStep::Disable
+        } else {
+            DeletionStep::Delete
+        });
+        steps
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DeletionStep {
+    Logout,
+    Stop,
+    PurgeAuth,
+    PurgeData,
+    Disable,
+    Delete,
+}
+
+impl DeletionStep {
+    pub const ALL: [Self; 6] = [
+        Self::Logout,
+        Self::Stop,
+        Self::PurgeAuth,
+        Self::PurgeData,
+        Self::Disable,
+        Self::Delete,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Logout => "logout",
+            Self::Stop => "stop",
+            Self::PurgeAuth => "purgeAuth",
+            Self::PurgeData => "purgeData",
+            Self::Disable => "disable",
+            Self::Delete => "delete",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum StepStatus {
+    Done,
+    /// Nothing to do, e.g. logout of an instance that is not logged in.
+    Skipped,
+    Failed,
+}
+
+/// Outcome of one step.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct StepReport {
+    pub step: DeletionStep,
+    pub status: StepStatus,
+    /// Rows deleted by the purge steps.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rows: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl StepReport {
+    fn done(step: DeletionStep, rows: Option<usize>) -> Self {
+        Self {
+            step,
+            status: StepStatus::Done,
+            rows,
+            error: None,
+        }
+    }
+
+    fn skipped(step: DeletionStep) -> Self {
+        Self {
+            step,
+            status: StepStatus::Skipped,
+            rows: None,
+            error: None,
+        }
+    }
+
+    fn failed(step: DeletionStep, error: impl std::fmt::Display) -> Self {
+        Self {
+            step,
+            status: StepStatus::Failed,
+            rows: None,
+            error: Some(error.to_string()),
+        }
+    }
+}
+
+/// A finished (or stopped) deletion.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Deletion {
+    pub id: Uuid,
+    pub instance: String,
+    pub options: DeletionOptions,
+    pub steps: Vec<StepReport>,
+}
+
+impl Deletion {
+    /// The step that stopped the sequence.
+    pub fn failure(&self) -> Option<&StepReport> {
+        self.steps
+            .iter()
+            .find(|report| report.status == StepStatus::Failed)
+    }
+
+    /// `502` when WhatsApp refused the logout, `500` when a later step
+    /// failed.
+    pub fn status_code(&self) -> StatusCode {
+        match self.failure() {
+            None => StatusCode::OK,
+            Some(report) if report.step == DeletionStep::Logout => StatusCode::BAD_GATEWAY,
+            Some(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    /// Response body: `status` is `deleted`, `disabled` or `failed`, the
+    /// last with `error` and the failed step's `details`.
+    pub fn to_json(&self) -> Value {
+        let status = match (self.failure(), self.options.soft) {
+            (Some(_), _) => "failed",
+            (None, true) => "disabled",
+            (None, false) => "deleted",
+        };
+        let mut body = json!({
+            "instance": self.instance,
+            "status": status,
+            "deletionId": self.id,
+            "options": self.options,
+            "steps": self.steps,
+        });
+        if let Some(failure) = self.failure() {
+            body["error"] = json!(format!("{}_failed", failure.step.as_str()));
+            body["details"] = json!(failure.error);
+        }
+        body
+    }
+}
+
+/// Whether `instance` exists, in the database or only in memory.
+pub async fn exists(state: &AppState, instance: &str) -> anyhow::Result<bool> {
+    if state.instances.contains_key(instance) || state.clients.contains_key(instance) {
+        return Ok(true);
+    }
+    let rows = state
+        .api_store
+        .query_json(
+            "SELECT jsonb_build_object('session', session) AS value FROM api_sessions WHERE session = $1",
+            vec![ApiBind::Text(instance.to_string())],
+        )
+        .await?;
+    Ok(!rows.is_empty())
+}
+
+/// Runs the steps of `options` for `instance`, emitting a progress event
+/// after each one.
+pub async fn run(state: &AppState, instance: &str, options: DeletionOptions) -> Deletion {
+    let mut deletion = Deletion {
+        id: Uuid::new_v4(),
+        instance: instance.to_string(),
+        options,
+        steps: Vec::new(),
+    };
+    let steps = options.steps();
+    tracing::info!(
+        instance,
+        deletion_id = %deletion.id,
+        steps = ?steps.iter().map(|step| step.as_str()).collect::<Vec<_>>(),
+        "Remoção de instância iniciada"
+    );
+    for (index, step) in steps.iter().enumerate() {
+        let report = run_step(state, instance, *step).await;
+        let failed = report.status == StepStatus::Failed;
+        if failed {
+            tracing::warn!(
+                instance,
+                step = step.as_str(),
+                error = report.error.as_deref().unwrap_or_default(),
+                "Etapa da remoção de instância falhou"
+            );
+        }
+        let progress = InstanceDeleteProgress {
+            deletion_id: deletion.id.to_string(),
+            step: *step,
+            status: report.status,
+            completed: index + 1,
+            total: steps.len(),
+            done: failed || index + 1 == steps.len(),
+            rows: report.rows,
+            error: report.error.clone(),
+        };
+        events::emit(state, Some(instance), &progress).await;
+        deletion.steps.push(report);
+        if failed {
+            return deletion;
+        }
+    }
+    #[cfg(feature = "sentry")]
+    state.sentry_hubs.remove(instance);
+    deletion
+}
+
+async fn run_step(state: &AppState, instance: &str, step: DeletionStep) -> StepReport {
+    let client = state
+        .clients
+        .get(instance)
+        .map(|entry| entry.value().clone());
+    match step {
+        DeletionStep::Logout => {
+            let Some(client) = client.filter(|client| client.is_logged_in()) else {
+                return StepReport::skipped(step);
+            };
+            let Some(own) = client.get_pn().await else {
+                return StepReport::skipped(step);
+            };
+            match client.devices().remove_companion(&own).await {
+                Ok(()) => StepReport::done(step, None),
+                Err(e) => StepReport::failed(step, e),
+            }
+        }
+        DeletionStep::Stop => {
+            let Some(client) = client else {
+                return StepReport::skipped(step);
+            };
+            client.disconnect().await;
+            StepReport::done(step, None)
+        }
+        DeletionStep::PurgeAuth => {
+            let Some(client) = client else {
+                return StepReport::skipped(step);
+            };
+            match purge_auth(state, &client).await {
+                Ok(rows) => StepReport::done(step, Some(rows)),
+                Err(e) => StepReport::failed(step, e),
+            }
+        }
+        DeletionStep::PurgeData => match purge_data(state, instance).await {
+            Ok(rows) => StepReport::done(step, Some(rows)),
+            Err(e) => StepReport::failed(step, e),
+        },
+        DeletionStep::Disable => match disable(state, instance).await {
+            Ok(()) => StepReport::done(step, None),
+            Err(e) => StepReport::failed(step, e),
+        },
+        DeletionStep::Delete => match delete(state, instance).await {
+            Ok(()) => StepReport::done(step, None),
+            Err(e) => StepReport::failed(step, e),
+        },
+    }
+}
+
+/// Deletes the Signal state, then replaces the device with an unpaired one.
+async fn purge_auth(state: &AppState, client: &crate::client::Client) -> anyhow::Result<usize> {
+    let statements = AUTH_TABLES
+        .iter()
+        .map(|table| {
+            (
+                format!("DELETE FROM {table} WHERE device_id = $1"),
+                vec![ApiBind::Int(DEVICE_ID)],
+            )
+        })
+        .collect();
+    let rows = state.api_store.execute_batch(statements).await?;
+    client.clear_credentials().await?;
+    Ok(rows.iter().sum())
+}
+
+async fn purge_data(state: &AppState, instance: &str) -> anyhow::Result<usize> {
+    let jobs = state
+        .api_store
+        .query_json(
+            "DELETE FROM api_export_jobs WHERE session = $1 \
+             RETURNING jsonb_build_object('id', id, 'format', format, 'status', status) AS value",
+            vec![ApiBind::Text(instance.to_string())],
+        )
+        .await?;
+    for job in &jobs {
+        let job = job.get("value").unwrap_or(job);
+        if let Some((path, _)) = exports::download(&state.exports, job) {
+            let _ = tokio::fs::remove_file(path).await;
+        }
+    }
+    let statements = DATA_TABLES
+        .iter()
+        .map(|table| {
+            (
+                format!("DELETE FROM {table} WHERE session = $1"),
+                vec![ApiBind::Text(instance.to_string())],
+            )
+        })
+        .collect();
+    let rows = state.api_store.execute_batch(statements).await?;
+    Ok(jobs.len() + rows.iter().sum::<usize>())
+}
+
+async fn disable(state: &AppState, instance: &str) -> anyhow::Result<()> {
+    let event = outbox::prepare(
+        state,
+        Some(instance),
+        "CONNECTION_UPDATE",
+        json!({"status": "close"}),
+    )
+    .await;
+    outbox::commit(
+        state,
+        vec![(
+            "UPDATE api_sessions SET status = 'disabled', updated_at = now() WHERE session = $1"
+                .to_string(),
+            vec![ApiBind::Text(instance.to_string())],
+        )],
+        vec![event],
+    )
+    .await?;
+    if let Some(mut entry) = state.sessions_runtime.get_mut(instance) {
+        entry.connection_state = "disabled".to_string();
+    }
+    Ok(())
+}
+
+async fn delete(state: &AppState, instance: &str) -> anyhow::Result<()> {
+    let event = outbox::prepare(
+        state,
+        Some(instance),
+        "CONNECTION_UPDATE",
+        json!({"status": "close"}),
+    )
+    .await;
+    outbox::commit(
+        state,
+        vec![
+            (
+                "DELETE FROM api_inbound_hooks WHERE session = $1".to_string(),
+                vec![ApiBind::Text(instance.to_string())],
+            ),
+            (
+                "DELETE FROM api_sessions WHERE session = $1".to_string(),
+                vec![ApiBind::Text(instance.to_string())],
+            ),
+        ],
+        vec![event],
+    )
+    .await?;
+    state.sessions_runtime.remove(instance);
+    state.instances.remove(instance);
+    state.clients.remove(instance);
+    state.webhook_config_cache.remove(instance);
+    state.instance_meta_cache.remove(instance);
+    state.auto_rules_cache.remove(instance);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    include!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/tests/server/instance_deletion_tests.rs"));
+}
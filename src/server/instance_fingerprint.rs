@@ -0,0 +1,134 @@
+//! Browser fingerprint and WA web version chosen per instance.
+//!
+//! Both are set when the instance is created (`browser` and `waVersion` in
+//! `POST /sessions`) and live on `api_sessions` (`browser` JSONB,
+//! `wa_version` text), so they survive restarts. The browser replaces the
+//! OS, OS version and platform of the device props and of the client
+//! payload user agent, and the name the phone shows for the linked device;
+//! the version is pinned in the instance [`WaVersionManager`]. Both take
+//! effect on the next connection attempt.
+//!
+//! [`WaVersionManager`]: crate::version::WaVersionManager
+
+use crate::api_store::ApiBind;
+use crate::client::Client;
+use crate::server::AppState;
+use crate::version::{self, AppVersion};
+use serde_json::{Value, json};
+use thiserror::Error;
+use warp_core::store::BrowserProfile;
+
+const MAX_FIELD_LEN: usize = 64;
+/// OS version of a browser sent without `version`, the same as the default
+/// device props.
+const DEFAULT_OS_VERSION: &str = "0.1.0";
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum FingerprintError {
+    #[error("browser must be an object with name, os and optionally version")]
+    BrowserNotObject,
+    #[error("browser.{0} must be 1-{MAX_FIELD_LEN} chars")]
+    InvalidField(&'static str),
+    #[error("invalid waVersion {0:?}; expected e.g. 2.3000.1015901307")]
+    InvalidVersion(String),
+}
+
+/// Browser and WA web version of one instance; `None` keeps the defaults.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InstanceFingerprint {
+    pub browser: Option<BrowserProfile>,
+    pub wa_version: Option<AppVersion>,
+}
+
+impl InstanceFingerprint {
+    /// Reads `browser` (`{"name", "os", "version"}`) and `waVersion` from a
+    /// request body.
+    pub fn from_body(body: &Value) -> Result<Self, FingerprintError> {
+        let browser = match &body["browser"] {
+            Value::Null => None,
+            Value::Object(fields) => {
+                let field = |name: &'static str, default: Option<&str>| {
+                    let value = fields.get(name).and_then(Value::as_str).or(default);
+                    match value.map(str::trim) {
+                        Some(value) if !value.is_empty() && value.len() <= MAX_FIELD_LEN => {
+                            Ok(value.to_string())
+                        }
+                        _ => Err(FingerprintError::InvalidField(name)),
+                    }
+                };
+                Some(BrowserProfile {
+                    name: field("name", None)?,
+                    os: field("os", None)?,
+                    version: field("version", Some(DEFAULT_OS_VERSION))?,
+                })
+            }
+            _ => return Err(FingerprintError::BrowserNotObject),
+        };
+        let wa_version = match &body["waVersion"] {
+            Value::Null => None,
+            Value::String(raw) if raw.trim().is_empty() => None,
+            value => {
+                let raw = value.as_str().map_or_else(|| value.to_string(), str::to_string);
+                Some(version::parse_version(&raw).ok_or(FingerprintError::InvalidVersion(raw))?)
+            }
+        };
+        Ok(Self {
+            browser,
+            wa_version,
+        })
+    }
+
+    fn from_row(row: &Value) -> Self {
+        Self {
+            browser: serde_json::from_value(row["browser"].clone()).ok(),
+            wa_version: row["wa_version"].as_str().and_then(version::parse_version),
+        }
+    }
+
+    /// `browser` column value.
+    pub fn browser_json(&self) -> Option<Value> {
+        self.browser.as_ref().map(|browser| json!(browser))
+    }
+
+    /// `wa_version` column value.
+    pub fn wa_version_text(&self) -> Option<String> {
+        self.wa_version.map(version::format_version)
+    }
+
+    /// Applies the fingerprint to a running client, from its next
+    /// connection attempt.
+    pub async fn apply(&self, client: &Client) {
+        if let Some(browser) = &self.browser {
+            client
+                .persistence_manager
+                .modify_device(|device| device.set_browser(browser))
+                .await;
+        }
+        if let Some(pinned) = self.wa_version {
+            let mut config = client.version_manager.config();
+            config.pinned = Some(pinned);
+            client.version_manager.set_config(config);
+        }
+    }
+}
+
+/// Fingerprint stored for `session`; the defaults when it has none.
+pub async fn load(state: &AppState, session: &str) -> anyhow::Result<InstanceFingerprint> {
+    let rows = state
+        .api_store
+        .query_json(
+            "SELECT jsonb_build_object('browser', browser, 'wa_version', wa_version) as value \
+             FROM api_sessions WHERE session = $1",
+            vec![ApiBind::Text(session.to_string())],
+        )
+        .await?;
+    Ok(rows
+        .first()
+        .map(InstanceFingerprint::from_row)
+        .unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    include!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/tests/server/instance_fingerprint_tests.rs"));
+}
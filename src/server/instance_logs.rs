@@ -0,0 +1,349 @@
+//! Per-instance live log tail behind `GET /instance/logs/:name`.
+//!
+//! A tracing [`Layer`] copies every event tagged with an instance into a
+//! ring buffer per instance. An event is tagged by an `instance` or
+//! `session` field of its own or of an enclosing span (the supervisor runs
+//! each runner inside an `instance` span). The endpoint replays the last
+//! entries over SSE and keeps streaming new ones, so pairing problems can be
+//! followed without grepping the whole service log.
+
+use crate::server::AppState;
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{
+        IntoResponse, Response,
+        sse::{Event as SseEvent, KeepAlive, Sse},
+    },
+};
+use chrono::{DateTime, Utc};
+use futures_util::stream::{self, StreamExt};
+use serde::{Deserialize, Serialize, Serializer};
+use serde_json::{Map, Value, json};
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, PoisonError};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::Layer;
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::registry::LookupSpan;
+
+const DEFAULT_LINES: usize = 100;
+/// Live entries a slow SSE client may be behind before it skips ahead.
+const LIVE_BUFFER: usize = 1024;
+/// Fields that name the instance an event belongs to.
+const INSTANCE_FIELDS: [&str; 4] = ["instance", "instance_name", "session", "session_name"];
+
+/// One captured log event.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LogEntry {
+    /// Increases across all instances; the SSE event id.
+    pub seq: u64,
+    pub at: DateTime<Utc>,
+    #[serde(serialize_with = "serialize_level")]
+    pub level: Level,
+    pub target: String,
+    pub instance: String,
+    pub message: String,
+    #[serde(skip_serializing_if = "Map::is_empty")]
+    pub fields: Map<String, Value>,
+}
+
+fn serialize_level<S: Serializer>(level: &Level, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(level.as_str())
+}
+
+#[derive(Default)]
+struct Buffers {
+    next_seq: u64,
+    by_instance: HashMap<String, VecDeque<Arc<LogEntry>>>,
+}
+
+/// Ring buffers of recent log entries, one per instance.
+#[derive(Clone)]
+pub struct InstanceLogs {
+    /// Entries kept per instance; `0` disables capture.
+    capacity: usize,
+    buffers: Arc<Mutex<Buffers>>,
+    live: broadcast::Sender<Arc<LogEntry>>,
+}
+
+impl fmt::Debug for InstanceLogs {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InstanceLogs")
+            .field("capacity", &self.capacity)
+            .finish_non_exhaustive()
+    }
+}
+
+impl InstanceLogs {
+    pub fn new(capacity: usize) -> Self {
+        let (live, _) = broadcast::channel(LIVE_BUFFER);
+        Self {
+            capacity,
+            buffers: Arc::default(),
+            live,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// The tracing layer that feeds these buffers.
+    pub fn layer(&self) -> InstanceLogLayer {
+        InstanceLogLayer { logs: self.clone() }
+    }
+
+    /// Stores an entry and hands it to the live subscribers.
+    pub fn push(
+        &self,
+        instance: &str,
+        level: Level,
+        target: &str,
+        message: String,
+        fields: Map<String, Value>,
+    ) {
+        if self.capacity == 0 {
+            return;
+        }
+        let entry = {
+            let mut buffers = self.buffers.lock().unwrap_or_else(PoisonError::into_inner);
+            buffers.next_seq += 1;
+            let entry = Arc::new(LogEntry {
+                seq: buffers.next_seq,
+                at: Utc::now(),
+                level,
+                target: target.to_string(),
+                instance: instance.to_string(),
+                message,
+                fields,
+            });
+            let buffer = buffers.by_instance.entry(instance.to_string()).or_default();
+            if buffer.len() >= self.capacity {
+                buffer.pop_front();
+            }
+            buffer.push_back(entry.clone());
+            entry
+        };
+        // No receivers just means nobody is tailing.
+        let _ = self.live.send(entry);
+    }
+
+    /// The last `lines` entries of `instance` at `level` or more severe,
+    /// oldest first, and the last sequence number assigned so far.
+    pub fn recent(&self, instance: &str, lines: usize, level: Level) -> (Vec<Arc<LogEntry>>, u64) {
+        let buffers = self.buffers.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut entries: Vec<_> = buffers
+            .by_instance
+            .get(instance)
+            .into_iter()
+            .flatten()
+            .rev()
+            .filter(|entry| entry.level <= level)
+            .take(lines)
+            .cloned()
+            .collect();
+        entries.reverse();
+        (entries, buffers.next_seq)
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Arc<LogEntry>> {
+        self.live.subscribe()
+    }
+}
+
+/// Marks a span as belonging to an instance.
+struct InstanceTag(String);
+
+/// Tracing layer of [`InstanceLogs`].
+pub struct InstanceLogLayer {
+    logs: InstanceLogs,
+}
+
+impl<S> Layer<S> for InstanceLogLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        if self.logs.capacity == 0 {
+            return;
+        }
+        let mut visitor = FieldVisitor::default();
+        attrs.record(&mut visitor);
+        if let Some(instance) = visitor.instance.take()
+            && let Some(span) = ctx.span(id)
+        {
+            span.extensions_mut().insert(InstanceTag(instance));
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        if self.logs.capacity == 0 {
+            return;
+        }
+        let mut visitor = FieldVisitor::default();
+        event.record(&mut visitor);
+        let instance = visitor.instance.take().or_else(|| {
+            ctx.event_scope(event)?.find_map(|span| {
+                span.extensions()
+                    .get::<InstanceTag>()
+                    .map(|tag| tag.0.clone())
+            })
+        });
+        if let Some(instance) = instance {
+            let metadata = event.metadata();
+            self.logs.push(
+                &instance,
+                *metadata.level(),
+                metadata.target(),
+                visitor.message,
+                visitor.fields,
+            );
+        }
+    }
+}
+
+#[derive(Default)]
+struct FieldVisitor {
+    instance: Option<String>,
+    message: String,
+    fields: Map<String, Value>,
+}
+
+impl FieldVisitor {
+    fn insert(&mut self, field: &Field, value: Value) {
+        match field.name() {
+            "message" => {
+                self.message = match value {
+                    Value::String(message) => message,
+                    other => other.to_string(),
+                }
+            }
+            name => {
+                if self.instance.is_none()
+                    && INSTANCE_FIELDS.contains(&name)
+                    && let Some(instance) = value.as_str()
+                {
+                    self.instance = Some(instance.to_string());
+                }
+                self.fields.insert(name.to_string(), value);
+            }
+        }
+    }
+}
+
+impl Visit for FieldVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.insert(field, Value::String(value.to_string()));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.insert(field, json!(value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.insert(field, json!(value));
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.insert(field, json!(value));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.insert(field, json!(value));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.insert(field, Value::String(format!("{value:?}")));
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct LogsQuery {
+    /// Entries replayed before the live stream (default 100).
+    lines: Option<usize>,
+    /// Least severe level streamed (`trace` to `error`, default `trace`).
+    level: Option<String>,
+}
+
+fn sse_event(entry: &LogEntry) -> Result<SseEvent, axum::Error> {
+    SseEvent::default()
+        .id(entry.seq.to_string())
+        .event("log")
+        .json_data(entry)
+}
+
+/// `GET /instance/logs/:name`: the last `lines` entries of the instance,
+/// then every new one, as `log` SSE events. A client that falls behind gets
+/// a `lagged` event with the number of entries it missed.
+pub async fn stream_instance_logs(
+    Path(name): Path<String>,
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<LogsQuery>,
+) -> Response {
+    let level = match query.level.as_deref().map(str::trim) {
+        None | Some("") => Level::TRACE,
+        Some(raw) => match Level::from_str(raw) {
+            Ok(level) => level,
+            Err(_) => {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    Json(json!({"error": "invalid_level", "level": raw})),
+                )
+                    .into_response();
+            }
+        },
+    };
+    if !state.clients.contains_key(&name) && !state.sessions_runtime.contains_key(&name) {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "instance_not_found"})),
+        )
+            .into_response();
+    }
+
+    let logs = &state.instance_logs;
+    let lines = query.lines.unwrap_or(DEFAULT_LINES).min(logs.capacity());
+    // Subscribe first so nothing logged between the snapshot and the live
+    // stream is lost; entries already replayed are skipped by `seq`.
+    let live = logs.subscribe();
+    let (backlog, replayed_up_to) = logs.recent(&name, lines, level);
+
+    let backlog = stream::iter(backlog).map(|entry| sse_event(&entry));
+    let live = stream::unfold((live, name), move |(mut live, name)| async move {
+        loop {
+            match live.recv().await {
+                Ok(entry)
+                    if entry.seq > replayed_up_to
+                        && entry.instance == name
+                        && entry.level <= level =>
+                {
+                    return Some((sse_event(&entry), (live, name)));
+                }
+                Ok(_) => continue,
+                Err(RecvError::Lagged(skipped)) => {
+                    let event = SseEvent::default()
+                        .event("lagged")
+                        .json_data(json!({"skipped": skipped}));
+                    return Some((event, (live, name)));
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    });
+
+    Sse::new(backlog.chain(live))
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    include!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/tests/server/instance_logs_tests.rs"));
+}
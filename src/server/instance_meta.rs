@@ -0,0 +1,264 @@
+//! Tags and key/value metadata of instances, for organizing large fleets.
+//!
+//! Both live on `api_sessions` (`tags` JSONB array, `metadata` JSONB object),
+//! are set through `POST /sessions` and `PUT /instance/metadata/:name`,
+//! filter `GET /instance/fetchInstances` and are copied into the envelope of
+//! every event of the instance so consumers can route on them.
+
+use crate::api_store::ApiBind;
+use crate::server::AppState;
+use crate::server::cache::CacheSpace;
+use crate::server::pagination::{ListSpec, PageRequest};
+use serde::Serialize;
+use serde_json::{Map, Value, json};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+pub const MAX_TAGS: usize = 32;
+pub const MAX_METADATA_KEYS: usize = 32;
+const MAX_TAG_LEN: usize = 64;
+const MAX_KEY_LEN: usize = 64;
+const MAX_VALUE_LEN: usize = 512;
+/// Query parameter prefix of metadata filters (`?metadata.region=eu`).
+const METADATA_FILTER_PREFIX: &str = "metadata.";
+const CACHE_TTL: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum InstanceMetaError {
+    #[error("tags must be an array of strings")]
+    TagsNotArray,
+    #[error("at most {MAX_TAGS} tags")]
+    TooManyTags,
+    #[error("invalid tag {0:?}: 1-{MAX_TAG_LEN} chars, no commas")]
+    InvalidTag(String),
+    #[error("metadata must be an object with string values")]
+    MetadataNotObject,
+    #[error("at most {MAX_METADATA_KEYS} metadata keys")]
+    TooManyKeys,
+    #[error("invalid metadata key {0:?}: 1-{MAX_KEY_LEN} chars")]
+    InvalidKey(String),
+    #[error("metadata value of {0:?} is over {MAX_VALUE_LEN} chars")]
+    ValueTooLong(String),
+}
+
+/// Tags and metadata of one instance.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct InstanceMeta {
+    pub tags: Vec<String>,
+    pub metadata: Map<String, Value>,
+}
+
+impl InstanceMeta {
+    pub fn is_empty(&self) -> bool {
+        self.tags.is_empty() && self.metadata.is_empty()
+    }
+
+    /// Adds `tags` and `metadata` to an event envelope; no-op when empty.
+    pub fn apply_to(&self, envelope: &mut Value) {
+        if self.is_empty() {
+            return;
+        }
+        if let Some(fields) = envelope.as_object_mut() {
+            fields.insert("tags".to_string(), json!(self.tags));
+            fields.insert("metadata".to_string(), Value::Object(self.metadata.clone()));
+        }
+    }
+
+    fn from_row(row: &Value) -> Self {
+        Self {
+            tags: row["tags"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(Value::as_str)
+                .map(str::to_string)
+                .collect(),
+            metadata: row["metadata"].as_object().cloned().unwrap_or_default(),
+        }
+    }
+}
+
+/// `tags` of a request body: trimmed, lowercased and deduplicated. `None`
+/// when the field is absent or null.
+pub fn tags_from_body(body: &Value) -> Result<Option<Vec<String>>, InstanceMetaError> {
+    let Some(raw) = body.get("tags").filter(|v| !v.is_null()) else {
+        return Ok(None);
+    };
+    let entries = raw.as_array().ok_or(InstanceMetaError::TagsNotArray)?;
+    let mut tags: Vec<String> = Vec::with_capacity(entries.len());
+    for entry in entries {
+        let raw = entry.as_str().ok_or(InstanceMetaError::TagsNotArray)?;
+        let tag = raw.trim().to_lowercase();
+        if tag.is_empty() || tag.chars().count() > MAX_TAG_LEN || tag.contains(',') {
+            return Err(InstanceMetaError::InvalidTag(raw.to_string()));
+        }
+        if !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+    if tags.len() > MAX_TAGS {
+        return Err(InstanceMetaError::TooManyTags);
+    }
+    Ok(Some(tags))
+}
+
+/// `metadata` of a request body. `None` when the field is absent or null.
+pub fn metadata_from_body(body: &Value) -> Result<Option<Map<String, Value>>, InstanceMetaError> {
+    let Some(raw) = body.get("metadata").filter(|v| !v.is_null()) else {
+        return Ok(None);
+    };
+    let fields = raw.as_object().ok_or(InstanceMetaError::MetadataNotObject)?;
+    if fields.len() > MAX_METADATA_KEYS {
+        return Err(InstanceMetaError::TooManyKeys);
+    }
+    for (key, value) in fields {
+        if key.trim().is_empty() || key.chars().count() > MAX_KEY_LEN {
+            return Err(InstanceMetaError::InvalidKey(key.clone()));
+        }
+        let value = value.as_str().ok_or(InstanceMetaError::MetadataNotObject)?;
+        if value.chars().count() > MAX_VALUE_LEN {
+            return Err(InstanceMetaError::ValueTooLong(key.clone()));
+        }
+    }
+    Ok(Some(fields.clone()))
+}
+
+/// Filters of `GET /instance/fetchInstances`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InstanceFilter {
+    pub name: Option<String>,
+    /// Every tag must be present.
+    pub tags: Vec<String>,
+    /// Every pair must match exactly.
+    pub metadata: Map<String, Value>,
+}
+
+impl InstanceFilter {
+    /// `?instanceName=`, `?tag=prod,eu` and `?metadata.<key>=<value>`.
+    pub fn from_query(params: &HashMap<String, String>) -> Self {
+        let mut filter = Self {
+            name: params
+                .get("instanceName")
+                .map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty()),
+            ..Default::default()
+        };
+        if let Some(tags) = params.get("tag") {
+            for tag in tags.split(',').map(|t| t.trim().to_lowercase()) {
+                if !tag.is_empty() && !filter.tags.contains(&tag) {
+                    filter.tags.push(tag);
+                }
+            }
+        }
+        for (key, value) in params {
+            if let Some(key) = key.strip_prefix(METADATA_FILTER_PREFIX).filter(|k| !k.is_empty()) {
+                filter.metadata.insert(key.to_string(), json!(value));
+            }
+        }
+        filter
+    }
+}
+
+/// Paging of `GET /instance/fetchInstances`.
+pub const INSTANCES: ListSpec = ListSpec {
+    default_limit: 100,
+    max_limit: 1000,
+    sort_fields: &[
+        ("createdAt", "created_at"),
+        ("updatedAt", "updated_at"),
+        ("name", "session"),
+    ],
+    default_sort: "-createdAt",
+    tiebreak: &["session"],
+};
+
+/// One page of the instances visible to `workspace_id` (all for `None`)
+/// matching `filter`, without secrets and with their stored messages,
+/// contacts and chats in `_count`, and how many match in total.
+pub async fn fetch(
+    state: &AppState,
+    workspace_id: Option<String>,
+    filter: &InstanceFilter,
+    page: &PageRequest,
+) -> anyhow::Result<(Vec<Value>, u64)> {
+    page.fetch(
+        state,
+        "(row_to_json(api_sessions)::jsonb - 'webhook_secret' - 'cloud_access_token') \
+            || jsonb_build_object('_count', jsonb_build_object( \
+                'Message', (SELECT count(*) FROM api_messages m WHERE m.session = api_sessions.session), \
+                'Contact', (SELECT count(*) FROM api_contacts c WHERE c.session = api_sessions.session), \
+                'Chat', (SELECT count(*) FROM api_chats c WHERE c.session = api_sessions.session)))",
+        "FROM api_sessions \
+         WHERE ($1::uuid IS NULL OR workspace_id = $1::uuid) \
+           AND ($2::text IS NULL OR session = $2) \
+           AND tags @> $3::jsonb AND metadata @> $4::jsonb",
+        vec![
+            ApiBind::NullableText(workspace_id),
+            ApiBind::NullableText(filter.name.clone()),
+            ApiBind::Json(json!(filter.tags)),
+            ApiBind::Json(Value::Object(filter.metadata.clone())),
+        ],
+    )
+    .await
+}
+
+/// Tags and metadata of `session`, cached for a few seconds since every
+/// event of the instance needs them.
+pub async fn load(state: &AppState, session: &str) -> anyhow::Result<InstanceMeta> {
+    if let Some(entry) = state.instance_meta_cache.get(session) {
+        let (meta, cached_at) = entry.value();
+        if cached_at.elapsed() < CACHE_TTL {
+            return Ok(meta.clone());
+        }
+    }
+    let rows = state
+        .api_store
+        .query_json(
+            "SELECT jsonb_build_object('tags', tags, 'metadata', metadata) as value \
+             FROM api_sessions WHERE session = $1",
+            vec![ApiBind::Text(session.to_string())],
+        )
+        .await?;
+    let meta = rows.first().map(InstanceMeta::from_row).unwrap_or_default();
+    state
+        .instance_meta_cache
+        .insert(session.to_string(), (meta.clone(), Instant::now()));
+    Ok(meta)
+}
+
+/// Replaces the given fields of `session`. `None` when the instance does
+/// not exist.
+pub async fn update(
+    state: &AppState,
+    session: &str,
+    tags: Option<Vec<String>>,
+    metadata: Option<Map<String, Value>>,
+) -> anyhow::Result<Option<InstanceMeta>> {
+    let rows = state
+        .api_store
+        .query_json(
+            "WITH updated AS ( \
+                UPDATE api_sessions SET \
+                    tags = COALESCE($2::jsonb, tags), \
+                    metadata = COALESCE($3::jsonb, metadata), \
+                    updated_at = now() \
+                WHERE session = $1 \
+                RETURNING tags, metadata \
+            ) SELECT jsonb_build_object('tags', tags, 'metadata', metadata) as value FROM updated",
+            vec![
+                ApiBind::Text(session.to_string()),
+                ApiBind::NullableJson(tags.map(|tags| json!(tags))),
+                ApiBind::NullableJson(metadata.map(Value::Object)),
+            ],
+        )
+        .await?;
+    state.instance_meta_cache.remove(session);
+    state.cache.invalidate_space(CacheSpace::Instances).await;
+    Ok(rows.first().map(InstanceMeta::from_row))
+}
+
+#[cfg(test)]
+mod tests {
+    include!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/tests/server/instance_meta_tests.rs"));
+}
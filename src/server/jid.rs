@@ -0,0 +1,114 @@
+//! JIDs as they appear in API requests and events.
+//!
+//! Callers send phone numbers (`+55 11 99999-0000`), legacy `@c.us` ids or
+//! full JIDs, while WhatsApp increasingly addresses users by LID (`@lid`).
+//! These helpers turn request values into chat JIDs and resolve the LID ↔
+//! phone-number pairs the client learns from pair-success and message
+//! stanzas (persisted by the client's LID-PN store).
+
+use crate::client::Client;
+use crate::server::AppState;
+use thiserror::Error;
+use warp_core_binary::jid::{
+    DEFAULT_USER_SERVER, HIDDEN_USER_SERVER, Jid, LEGACY_USER_SERVER,
+};
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum JidError {
+    #[error("jid is empty")]
+    Empty,
+    #[error("invalid jid {0:?}")]
+    Invalid(String),
+}
+
+/// Parses a request value: a phone number (punctuation ignored), a legacy
+/// `@c.us` id or any full JID.
+pub fn parse(raw: &str) -> Result<Jid, JidError> {
+    let raw = raw.trim();
+    if raw.is_empty() {
+        return Err(JidError::Empty);
+    }
+    if !raw.contains('@') {
+        let digits: String = raw.chars().filter(char::is_ascii_digit).collect();
+        if digits.is_empty() {
+            return Err(JidError::Invalid(raw.to_string()));
+        }
+        return Ok(Jid::pn(digits));
+    }
+
+    let mut jid: Jid = raw
+        .parse()
+        .map_err(|_| JidError::Invalid(raw.to_string()))?;
+    if jid.server == LEGACY_USER_SERVER {
+        jid.server = DEFAULT_USER_SERVER.to_string();
+    }
+    if jid.user.is_empty() {
+        return Err(JidError::Invalid(raw.to_string()));
+    }
+    Ok(jid)
+}
+
+/// Chat JID of a request value: parsed and without device/agent suffixes.
+pub fn normalize(raw: &str) -> Result<String, JidError> {
+    Ok(parse(raw)?.to_non_ad().to_string())
+}
+
+/// User part of a JID without the device suffix (`5511999:3@s.whatsapp.net` → `5511999`).
+pub fn user(raw: &str) -> Option<String> {
+    let jid = parse(raw).ok()?;
+    Some(jid.user_base().to_string())
+}
+
+/// Device id of a JID, 0 for the primary device.
+pub fn device(raw: &str) -> Option<u16> {
+    parse(raw).ok().map(|jid| jid.device)
+}
+
+/// LID of a phone-number JID, when the mapping is known.
+pub async fn lid_for(client: &Client, jid: &Jid) -> Option<Jid> {
+    if !jid.is_pn() {
+        return None;
+    }
+    let lid = client.lid_pn_cache.get_current_lid(jid.user_base()).await?;
+    Some(Jid::lid(lid))
+}
+
+/// Phone-number JID of a LID, when the mapping is known.
+pub async fn pn_for(client: &Client, jid: &Jid) -> Option<Jid> {
+    if !jid.is_lid() {
+        return None;
+    }
+    let pn = client.get_phone_number_from_lid(jid.user_base()).await?;
+    Some(Jid::pn(pn))
+}
+
+/// The other addressing form of a user JID (LID for a phone number and vice
+/// versa). `None` for groups, newsletters and unknown mappings.
+pub async fn alternate(client: &Client, jid: &Jid) -> Option<Jid> {
+    match jid.server.as_str() {
+        DEFAULT_USER_SERVER => lid_for(client, jid).await,
+        HIDDEN_USER_SERVER => pn_for(client, jid).await,
+        _ => None,
+    }
+}
+
+/// Normalized chat id of a request value plus its alternate form, so lookups
+/// match chats stored under either the phone number or the LID.
+pub async fn chat_aliases(
+    state: &AppState,
+    session: &str,
+    raw: &str,
+) -> Result<(String, Option<String>), JidError> {
+    let jid = parse(raw)?.to_non_ad();
+    let client = state.clients.get(session).map(|entry| entry.value().clone());
+    let alternate = match client {
+        Some(client) => alternate(&client, &jid).await.map(|alt| alt.to_string()),
+        None => None,
+    };
+    Ok((jid.to_string(), alternate))
+}
+
+#[cfg(test)]
+mod tests {
+    include!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/tests/server/jid_tests.rs"));
+}
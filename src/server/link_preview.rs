@@ -0,0 +1,196 @@
+use crate::client::Client;
+use crate::http::HttpRequest;
+use crate::server::thumbnails::{self, Thumbnail};
+use thiserror::Error;
+
+/// Only the head of the page is needed for OpenGraph tags.
+const MAX_HTML_BYTES: usize = 512 * 1024;
+/// Longest edge of the generated JPEG thumbnail.
+const THUMBNAIL_SIZE: u32 = 192;
+/// Some sites only serve OpenGraph tags to crawlers.
+const PREVIEW_USER_AGENT: &str = "WhatsApp/2.23.20.0";
+
+#[derive(Debug, Error)]
+pub enum LinkPreviewError {
+    #[error("fetch failed: {0}")]
+    Fetch(anyhow::Error),
+    #[error("fetch returned status {0}")]
+    Status(u16),
+    #[error("thumbnail failed: {0}")]
+    Image(#[from] image::ImageError),
+}
+
+/// Metadata extracted from a page for an ExtendedTextMessage preview.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LinkPreview {
+    pub url: String,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub image_url: Option<String>,
+    pub thumbnail: Option<Thumbnail>,
+}
+
+/// First `http(s)://` URL found in `text`, without trailing punctuation.
+pub fn first_url(text: &str) -> Option<&str> {
+    text.split_whitespace()
+        .find(|word| word.starts_with("http://") || word.starts_with("https://"))
+        .map(|word| word.trim_end_matches(['.', ',', ';', ':', '!', '?', ')', ']', '"', '\'']))
+        .filter(|url| url.len() > "https://".len())
+}
+
+/// Extracts `og:*` (falling back to `<title>`/`description`) from `html`.
+pub fn parse_preview(url: &str, html: &str) -> LinkPreview {
+    let mut preview = LinkPreview {
+        url: url.to_string(),
+        ..Default::default()
+    };
+    let mut meta_title = None;
+    let mut meta_description = None;
+
+    let lower = html.to_ascii_lowercase();
+    let mut cursor = 0;
+    while let Some(offset) = lower[cursor..].find("<meta") {
+        let start = cursor + offset;
+        let Some(end) = lower[start..].find('>').map(|e| start + e) else {
+            break;
+        };
+        let tag = &html[start..end];
+        cursor = end;
+
+        let key = attribute(tag, "property").or_else(|| attribute(tag, "name"));
+        let Some(content) = attribute(tag, "content").filter(|c| !c.is_empty()) else {
+            continue;
+        };
+        match key.map(|k| k.to_ascii_lowercase()).as_deref() {
+            Some("og:title") => preview.title = Some(content),
+            Some("og:description") => preview.description = Some(content),
+            Some("og:image") | Some("og:image:url") if preview.image_url.is_none() => {
+                preview.image_url = Some(resolve_url(url, &content));
+            }
+            Some("twitter:title") => meta_title = meta_title.or(Some(content)),
+            Some("description") | Some("twitter:description") => {
+                meta_description = meta_description.or(Some(content))
+            }
+            _ => {}
+        }
+    }
+
+    if preview.title.is_none() {
+        preview.title = meta_title.or_else(|| title_tag(html, &lower));
+    }
+    if preview.description.is_none() {
+        preview.description = meta_description;
+    }
+    preview
+}
+
+/// Downscales `bytes` to a JPEG thumbnail suitable for `jpegThumbnail`.
+pub fn make_thumbnail(bytes: &[u8]) -> Result<Thumbnail, LinkPreviewError> {
+    Ok(thumbnails::jpeg_thumbnail(bytes, THUMBNAIL_SIZE)?)
+}
+
+/// Fetches `url` and builds its preview. A missing or broken image only
+/// drops the thumbnail; the text fields are still returned.
+pub async fn fetch_preview(client: &Client, url: &str) -> Result<LinkPreview, LinkPreviewError> {
+    let body = fetch(client, url).await?;
+    let html_len = body.len().min(MAX_HTML_BYTES);
+    let html = String::from_utf8_lossy(&body[..html_len]);
+    let mut preview = parse_preview(url, &html);
+
+    if let Some(image_url) = preview.image_url.clone() {
+        match fetch(client, &image_url).await.and_then(|bytes| make_thumbnail(&bytes)) {
+            Ok(thumbnail) => preview.thumbnail = Some(thumbnail),
+            Err(err) => log::warn!("Link preview thumbnail for {} failed: {}", url, err),
+        }
+    }
+
+    Ok(preview)
+}
+
+async fn fetch(client: &Client, url: &str) -> Result<Vec<u8>, LinkPreviewError> {
+    let request = HttpRequest::get(url).with_header("User-Agent", PREVIEW_USER_AGENT);
+    let response = client
+        .http_client
+        .execute(request)
+        .await
+        .map_err(LinkPreviewError::Fetch)?;
+    if response.status_code >= 300 {
+        return Err(LinkPreviewError::Status(response.status_code));
+    }
+    Ok(response.body)
+}
+
+fn attribute(tag: &str, name: &str) -> Option<String> {
+    let lower = tag.to_ascii_lowercase();
+    let mut cursor = 0;
+    while let Some(offset) = lower[cursor..].find(name) {
+        let start = cursor + offset;
+        cursor = start + name.len();
+        let preceded_by_space = lower[..start]
+            .chars()
+            .last()
+            .is_some_and(|c| c.is_ascii_whitespace());
+        let rest = lower[cursor..].trim_start();
+        if !preceded_by_space || !rest.starts_with('=') {
+            continue;
+        }
+        let value_start = tag.len() - rest.len() + 1;
+        let value = tag[value_start..].trim_start();
+        let quote = value.chars().next()?;
+        let raw = if quote == '"' || quote == '\'' {
+            let inner = &value[1..];
+            &inner[..inner.find(quote).unwrap_or(inner.len())]
+        } else {
+            // Unquoted values run to the next space; a trailing `/` is the self-closing tag.
+            let end = value.find(|c: char| c.is_ascii_whitespace()).unwrap_or(value.len());
+            value[..end].trim_end_matches('/')
+        };
+        return Some(decode_entities(raw.trim()));
+    }
+    None
+}
+
+fn title_tag(html: &str, lower: &str) -> Option<String> {
+    let open = lower.find("<title")?;
+    let start = open + lower[open..].find('>')? + 1;
+    let end = start + lower[start..].find("</title")?;
+    Some(decode_entities(html[start..end].trim())).filter(|t| !t.is_empty())
+}
+
+fn resolve_url(base: &str, target: &str) -> String {
+    if target.starts_with("http://") || target.starts_with("https://") {
+        return target.to_string();
+    }
+    let scheme = if base.starts_with("http://") { "http:" } else { "https:" };
+    if let Some(rest) = target.strip_prefix("//") {
+        return format!("{}//{}", scheme, rest);
+    }
+    let after_scheme = base.find("://").map(|i| i + 3).unwrap_or(0);
+    let origin_end = base[after_scheme..]
+        .find('/')
+        .map(|i| after_scheme + i)
+        .unwrap_or(base.len());
+    if target.starts_with('/') {
+        return format!("{}{}", &base[..origin_end], target);
+    }
+    let dir_end = base[origin_end..]
+        .rfind('/')
+        .map(|i| origin_end + i)
+        .unwrap_or(base.len());
+    format!("{}/{}", &base[..dir_end], target)
+}
+
+fn decode_entities(value: &str) -> String {
+    value
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&#x27;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    include!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/tests/server/link_preview_tests.rs"));
+}
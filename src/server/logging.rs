@@ -0,0 +1,181 @@
+//! Tracing subscriber setup: output format, optional rotating log file and a
+//! reloadable filter.
+//!
+//! The filter is the `logLevel` of the runtime config plus its `logTargets`
+//! overrides (see [`filter_directive`]); `PATCH /manager/config` swaps it
+//! through the returned [`LogLevelReloader`] without a restart.
+
+use crate::server::instance_logs::InstanceLogs;
+use crate::server::runtime_config::LogLevelReloader;
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, Layer, Registry, reload};
+
+pub use crate::config::{LogFileConfig, LogFormat, LoggingConfig};
+
+impl LogFormat {
+    /// `json`, `pretty` or `compact` (case-insensitive).
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "json" => Some(Self::Json),
+            "pretty" => Some(Self::Pretty),
+            "compact" => Some(Self::Compact),
+            _ => None,
+        }
+    }
+}
+
+/// `EnvFilter` directive of a base level plus per-target levels, e.g.
+/// `info` and `{"warp_core": "debug"}` give `info,warp_core=debug`.
+pub fn filter_directive(level: &str, targets: &BTreeMap<String, String>) -> String {
+    let mut directive = level.trim().to_string();
+    for (target, target_level) in targets {
+        if !directive.is_empty() {
+            directive.push(',');
+        }
+        directive.push_str(&format!("{}={}", target.trim(), target_level.trim()));
+    }
+    directive
+}
+
+/// Installs the global subscriber with `directive` as the initial filter,
+/// also feeding `instance_logs`. A file that cannot be opened is reported on
+/// stderr and skipped.
+pub fn init(
+    config: &LoggingConfig,
+    directive: &str,
+    instance_logs: &InstanceLogs,
+) -> LogLevelReloader {
+    let filter = EnvFilter::try_new(directive).unwrap_or_else(|e| {
+        eprintln!("Invalid log filter {directive:?} ({e}); using info");
+        EnvFilter::new("info")
+    });
+    let (filter, filter_handle) = reload::Layer::new(filter);
+
+    let mut layers: Vec<Box<dyn Layer<Registry> + Send + Sync>> =
+        vec![format_layer(config.format, io::stdout, true)];
+    if let Some(file) = &config.file {
+        match RotatingFile::open(file) {
+            Ok(writer) => layers.push(format_layer(config.format, writer, false)),
+            Err(e) => eprintln!("Log file {} not opened: {e}", file.path.display()),
+        }
+    }
+    layers.push(instance_logs.layer().boxed());
+    #[cfg(feature = "sentry")]
+    layers.push(crate::server::error_reporting::layer().boxed());
+
+    let _ = tracing_subscriber::registry()
+        .with(layers)
+        .with(filter)
+        .try_init();
+
+    Arc::new(move |directive: &str| {
+        let filter = EnvFilter::try_new(directive).map_err(|e| e.to_string())?;
+        filter_handle.reload(filter).map_err(|e| e.to_string())
+    })
+}
+
+fn format_layer<W>(
+    format: LogFormat,
+    writer: W,
+    ansi: bool,
+) -> Box<dyn Layer<Registry> + Send + Sync>
+where
+    W: for<'a> MakeWriter<'a> + Send + Sync + 'static,
+{
+    let layer = tracing_subscriber::fmt::layer()
+        .with_writer(writer)
+        .with_ansi(ansi)
+        .with_target(true)
+        .with_thread_ids(false);
+    match format {
+        LogFormat::Json => layer.json().boxed(),
+        LogFormat::Pretty => layer.pretty().boxed(),
+        LogFormat::Compact => layer.compact().boxed(),
+    }
+}
+
+/// Path of the `n`-th rotated file (`api.log` → `api.log.1`).
+pub fn rotated_path(path: &Path, n: u32) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{n}"));
+    PathBuf::from(name)
+}
+
+/// Appends to a file and, once it would pass `max_bytes`, shifts it to
+/// `.1`, `.1` to `.2` and so on, dropping the oldest.
+pub struct RotatingFile {
+    config: LogFileConfig,
+    current: Mutex<(File, u64)>,
+}
+
+impl RotatingFile {
+    pub fn open(config: &LogFileConfig) -> io::Result<Self> {
+        if let Some(dir) = config.path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        let file = Self::append(&config.path)?;
+        let size = file.metadata()?.len();
+        Ok(Self {
+            config: config.clone(),
+            current: Mutex::new((file, size)),
+        })
+    }
+
+    fn append(path: &Path) -> io::Result<File> {
+        OpenOptions::new().create(true).append(true).open(path)
+    }
+
+    fn rotate(&self, current: &mut (File, u64)) -> io::Result<()> {
+        current.0.flush()?;
+        let path = &self.config.path;
+        for n in (1..self.config.max_files).rev() {
+            let from = rotated_path(path, n);
+            if from.exists() {
+                fs::rename(&from, rotated_path(path, n + 1))?;
+            }
+        }
+        fs::rename(path, rotated_path(path, 1))?;
+        *current = (Self::append(path)?, 0);
+        Ok(())
+    }
+}
+
+impl Write for &RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut current = self.current.lock().unwrap_or_else(PoisonError::into_inner);
+        if current.1 > 0 && current.1 + buf.len() as u64 > self.config.max_bytes {
+            self.rotate(&mut current)?;
+        }
+        current.0.write_all(buf)?;
+        current.1 += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.current
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .0
+            .flush()
+    }
+}
+
+impl<'a> MakeWriter<'a> for RotatingFile {
+    type Writer = &'a RotatingFile;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    include!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/tests/server/logging_tests.rs"));
+}
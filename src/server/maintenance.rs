@@ -0,0 +1,271 @@
+//! Scheduled maintenance windows of instances.
+//!
+//! An instance may carry a `maintenance_window` (`{"cron": "0 3 * * *",
+//! "durationMinutes": 10}`, UTC). When the cron expression matches, the
+//! scheduler closes its WhatsApp connection and keeps it closed for
+//! `durationMinutes`, then lets it reconnect; a duration of 0 is a plain
+//! restart. The runner keeps running throughout, so the supervisor is not
+//! involved.
+
+use crate::api_store::ApiBind;
+use crate::server::AppState;
+use crate::server::cache::CacheSpace;
+use crate::server::connection::{ConnectionState, Reason};
+use crate::server::session_events::update_runtime_state;
+use chrono::{DateTime, Datelike, Duration as ChronoDuration, Timelike, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+
+/// Longest window; a longer outage is better done by stopping the instance.
+pub const MAX_DURATION_MINUTES: u32 = 24 * 60;
+const TICK: Duration = Duration::from_secs(20);
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum MaintenanceError {
+    #[error("body must be an object with cron and durationMinutes")]
+    InvalidBody,
+    #[error("invalid cron expression {0:?}: expected 5 fields (minute hour day month weekday)")]
+    InvalidCron(String),
+    #[error("durationMinutes must be at most {MAX_DURATION_MINUTES}")]
+    DurationTooLong,
+}
+
+/// Parsed 5-field cron expression (`minute hour day-of-month month
+/// day-of-week`), with `*`, lists, ranges and `/step`. Evaluated in UTC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CronSchedule {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    days_restricted: bool,
+    weekdays_restricted: bool,
+}
+
+impl CronSchedule {
+    pub fn parse(expr: &str) -> Result<Self, MaintenanceError> {
+        let invalid = || MaintenanceError::InvalidCron(expr.to_string());
+        let fields: Vec<&str> = expr.split_whitespace().collect();
+        let [minutes, hours, days, months, weekdays] = fields[..] else {
+            return Err(invalid());
+        };
+        let mut weekday_mask = parse_field(weekdays, 0, 7).ok_or_else(invalid)?;
+        // Both 0 and 7 mean Sunday.
+        if weekday_mask & (1 << 7) != 0 {
+            weekday_mask = (weekday_mask | 1) & !(1 << 7);
+        }
+        Ok(Self {
+            minutes: parse_field(minutes, 0, 59).ok_or_else(invalid)?,
+            hours: parse_field(hours, 0, 23).ok_or_else(invalid)?,
+            days: parse_field(days, 1, 31).ok_or_else(invalid)?,
+            months: parse_field(months, 1, 12).ok_or_else(invalid)?,
+            weekdays: weekday_mask,
+            days_restricted: days != "*",
+            weekdays_restricted: weekdays != "*",
+        })
+    }
+
+    /// Whether the minute of `at` matches. As in cron, a restricted
+    /// day-of-month and day-of-week match when either does.
+    pub fn matches(&self, at: DateTime<Utc>) -> bool {
+        let bit = |mask: u64, value: u32| mask & (1 << value) != 0;
+        let day = bit(self.days, at.day());
+        let weekday = bit(self.weekdays, at.weekday().num_days_from_sunday());
+        let day_matches = match (self.days_restricted, self.weekdays_restricted) {
+            (true, true) => day || weekday,
+            _ => day && weekday,
+        };
+        bit(self.minutes, at.minute())
+            && bit(self.hours, at.hour())
+            && bit(self.months, at.month())
+            && day_matches
+    }
+}
+
+/// Bitmask of the values of one cron field within `min..=max`.
+fn parse_field(field: &str, min: u32, max: u32) -> Option<u64> {
+    let mut mask = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().ok().filter(|s| *s > 0)?),
+            None => (part, 1),
+        };
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((start, end)) = range.split_once('-') {
+            (start.parse().ok()?, end.parse().ok()?)
+        } else {
+            let value = range.parse().ok()?;
+            // `5/15` runs from 5 to the end of the range.
+            (value, if step > 1 { max } else { value })
+        };
+        if start < min || end > max || start > end {
+            return None;
+        }
+        for value in (start..=end).step_by(step as usize) {
+            mask |= 1 << value;
+        }
+    }
+    Some(mask)
+}
+
+/// Maintenance window of one instance, as stored in `api_sessions`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MaintenanceWindow {
+    pub cron: String,
+    /// How long the connection stays closed; 0 restarts it right away.
+    #[serde(default)]
+    pub duration_minutes: u32,
+}
+
+impl MaintenanceWindow {
+    /// Validated window from a request body.
+    pub fn from_body(body: &Value) -> Result<Self, MaintenanceError> {
+        let window: Self =
+            serde_json::from_value(body.clone()).map_err(|_| MaintenanceError::InvalidBody)?;
+        window.schedule()?;
+        if window.duration_minutes > MAX_DURATION_MINUTES {
+            return Err(MaintenanceError::DurationTooLong);
+        }
+        Ok(window)
+    }
+
+    pub fn schedule(&self) -> Result<CronSchedule, MaintenanceError> {
+        CronSchedule::parse(&self.cron)
+    }
+
+    /// Start of the window `now` falls in, if any. A zero-length window is
+    /// open for the minute it starts in.
+    pub fn opened_at(&self, schedule: &CronSchedule, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let minute = now.with_second(0)?.with_nanosecond(0)?;
+        (0..i64::from(self.duration_minutes.max(1)))
+            .map(|back| minute - ChronoDuration::minutes(back))
+            .find(|start| schedule.matches(*start))
+    }
+}
+
+/// Saves (or clears, with `None`) the window of `session`. Returns whether
+/// the instance exists.
+pub async fn set_window(
+    state: &AppState,
+    session: &str,
+    window: Option<&MaintenanceWindow>,
+) -> anyhow::Result<bool> {
+    let window = window.map(serde_json::to_value).transpose()?;
+    let updated = state
+        .api_store
+        .execute(
+            "UPDATE api_sessions SET maintenance_window = $2, updated_at = now() WHERE session = $1",
+            vec![
+                ApiBind::Text(session.to_string()),
+                ApiBind::NullableJson(window),
+            ],
+        )
+        .await?;
+    state.cache.invalidate_space(CacheSpace::Instances).await;
+    Ok(updated > 0)
+}
+
+async fn load_windows(state: &AppState) -> anyhow::Result<HashMap<String, MaintenanceWindow>> {
+    let rows = state
+        .api_store
+        .query_json(
+            "SELECT jsonb_build_object('session', session, 'window', maintenance_window) as value \
+             FROM api_sessions WHERE maintenance_window IS NOT NULL",
+            vec![],
+        )
+        .await?;
+    Ok(rows
+        .into_iter()
+        .filter_map(|row| {
+            let session = row["session"].as_str()?.to_string();
+            let window = serde_json::from_value(row["window"].clone()).ok()?;
+            Some((session, window))
+        })
+        .collect())
+}
+
+/// Checks the windows of the running instances every few seconds and
+/// closes or reopens their connections.
+pub fn spawn_scheduler(state: Arc<AppState>) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        // Window start already handled per instance, so a window acts once.
+        let mut handled: HashMap<String, DateTime<Utc>> = HashMap::new();
+        loop {
+            match load_windows(&state).await {
+                Ok(windows) => tick(&state, &windows, &mut handled, Utc::now()).await,
+                Err(e) => tracing::warn!(error = %e, "Falha ao carregar janelas de manutenção"),
+            }
+            tokio::time::sleep(TICK).await;
+        }
+    })
+}
+
+async fn tick(
+    state: &AppState,
+    windows: &HashMap<String, MaintenanceWindow>,
+    handled: &mut HashMap<String, DateTime<Utc>>,
+    now: DateTime<Utc>,
+) {
+    let clients: Vec<_> = state
+        .clients
+        .iter()
+        .map(|entry| (entry.key().clone(), entry.value().clone()))
+        .collect();
+    for (name, client) in clients {
+        let opened_at = windows.get(&name).and_then(|window| {
+            let schedule = window.schedule().ok()?;
+            Some((window, window.opened_at(&schedule, now)?))
+        });
+        match opened_at {
+            Some((window, start)) if handled.get(&name) != Some(&start) => {
+                handled.insert(name.clone(), start);
+                tracing::info!(
+                    instance = %name,
+                    cron = %window.cron,
+                    duration_minutes = window.duration_minutes,
+                    "Janela de manutenção iniciada"
+                );
+                client.hold_connection().await;
+                update_runtime_state(
+                    state,
+                    &name,
+                    ConnectionState::Disconnected,
+                    Reason::Maintenance,
+                    json!({ "maintenance": window }),
+                )
+                .await;
+                if window.duration_minutes == 0 {
+                    release(state, &name, &client).await;
+                }
+            }
+            Some(_) => {}
+            None if client.is_connection_held() => release(state, &name, &client).await,
+            None => {}
+        }
+    }
+}
+
+async fn release(state: &AppState, name: &str, client: &crate::client::Client) {
+    tracing::info!(instance = %name, "Janela de manutenção encerrada; reconectando");
+    client.release_connection();
+    update_runtime_state(
+        state,
+        name,
+        ConnectionState::Connecting,
+        Reason::Maintenance,
+        json!({}),
+    )
+    .await;
+}
+
+#[cfg(test)]
+mod tests {
+    include!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/tests/server/maintenance_tests.rs"));
+}
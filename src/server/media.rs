@@ -0,0 +1,291 @@
+use crate::api_store::ApiBind;
+use crate::client::Client;
+use crate::server::AppState;
+use crate::server::audio::{self, AudioError};
+use crate::server::ephemeral;
+use crate::server::quotas;
+use crate::server::runtime_config::RuntimeConfig;
+use base64::{Engine as _, engine::general_purpose};
+use serde_json::{Map, Value, json};
+use thiserror::Error;
+use waproto::whatsapp as wa;
+use warp_core::download::Downloadable;
+use warp_core::proto_helpers::MessageExt;
+
+#[derive(Debug, Error)]
+pub enum MediaError {
+    #[error("message has no downloadable media")]
+    NoMedia,
+    #[error("download failed: {0}")]
+    Download(anyhow::Error),
+    #[error("audio conversion failed: {0}")]
+    Convert(#[from] AudioError),
+}
+
+/// Metadata of the media attached to a message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MediaInfo {
+    pub media_type: &'static str,
+    pub mimetype: String,
+    pub file_name: Option<String>,
+    pub caption: Option<String>,
+}
+
+/// Decrypted media ready to be returned to API consumers.
+#[derive(Debug, Clone)]
+pub struct DownloadedMedia {
+    pub info: MediaInfo,
+    pub bytes: Vec<u8>,
+}
+
+impl DownloadedMedia {
+    /// JSON body shared by the media download endpoints.
+    pub fn to_json(&self) -> Value {
+        json!({
+            "mediaType": self.info.media_type,
+            "fileName": self.info.file_name,
+            "caption": self.info.caption,
+            "mimetype": self.info.mimetype,
+            "size": self.bytes.len(),
+            "base64": general_purpose::STANDARD.encode(&self.bytes),
+        })
+    }
+}
+
+/// Finds the downloadable media inside `message`, unwrapping
+/// ephemeral/view-once/document-with-caption wrappers.
+pub fn find_media(message: &wa::Message) -> Option<(&dyn Downloadable, MediaInfo)> {
+    let base = message.get_base_message();
+
+    if let Some(image) = base.image_message.as_deref() {
+        return Some((
+            image,
+            MediaInfo {
+                media_type: "image",
+                mimetype: mimetype_or(image.mimetype.as_deref(), "image/jpeg"),
+                file_name: None,
+                caption: image.caption.clone(),
+            },
+        ));
+    }
+    if let Some(video) = base.video_message.as_deref() {
+        return Some((
+            video,
+            MediaInfo {
+                media_type: "video",
+                mimetype: mimetype_or(video.mimetype.as_deref(), "video/mp4"),
+                file_name: None,
+                caption: video.caption.clone(),
+            },
+        ));
+    }
+    if let Some(audio) = base.audio_message.as_deref() {
+        return Some((
+            audio,
+            MediaInfo {
+                media_type: "audio",
+                mimetype: mimetype_or(audio.mimetype.as_deref(), "audio/ogg; codecs=opus"),
+                file_name: None,
+                caption: None,
+            },
+        ));
+    }
+    if let Some(document) = base.document_message.as_deref() {
+        return Some((
+            document,
+            MediaInfo {
+                media_type: "document",
+                mimetype: mimetype_or(document.mimetype.as_deref(), "application/octet-stream"),
+                file_name: document.file_name.clone(),
+                caption: document.caption.clone(),
+            },
+        ));
+    }
+    if let Some(sticker) = base.sticker_message.as_deref() {
+        return Some((
+            sticker,
+            MediaInfo {
+                media_type: "sticker",
+                mimetype: mimetype_or(sticker.mimetype.as_deref(), "image/webp"),
+                file_name: None,
+                caption: None,
+            },
+        ));
+    }
+
+    None
+}
+
+fn mimetype_or(value: Option<&str>, fallback: &str) -> String {
+    value
+        .filter(|mime| !mime.trim().is_empty())
+        .unwrap_or(fallback)
+        .to_string()
+}
+
+/// Downloads and decrypts the media of `message` from the WA CDN.
+///
+/// With an ffmpeg binary in `convert_audio_to_mp3`, audio payloads are
+/// re-encoded to MP3.
+pub async fn download_media(
+    client: &Client,
+    message: &wa::Message,
+    convert_audio_to_mp3: Option<&str>,
+) -> Result<DownloadedMedia, MediaError> {
+    let (downloadable, mut info) = find_media(message).ok_or(MediaError::NoMedia)?;
+    let mut bytes = client
+        .download(downloadable)
+        .await
+        .map_err(MediaError::Download)?;
+
+    if let Some(ffmpeg) = convert_audio_to_mp3
+        && info.media_type == "audio"
+    {
+        bytes = audio::convert_to_mp3(ffmpeg, bytes).await?;
+        info.mimetype = "audio/mpeg".to_string();
+    }
+
+    Ok(DownloadedMedia { info, bytes })
+}
+
+/// Largest media inlined in webhooks, from `webhookBase64MaxMb`.
+pub fn webhook_base64_limit(config: &RuntimeConfig) -> Option<u64> {
+    (config.webhook_base64_max_mb > 0)
+        .then(|| u64::from(config.webhook_base64_max_mb) * quotas::BYTES_PER_MB)
+}
+
+/// Whether media of `size` bytes fits `limit`; unknown sizes are checked
+/// again once downloaded.
+fn within_limit(size: Option<u64>, limit: Option<u64>) -> bool {
+    match (size, limit) {
+        (Some(size), Some(limit)) => size <= limit,
+        _ => true,
+    }
+}
+
+/// Downloads `media` and inlines it in the webhook `message` as a `base64`
+/// data URL, filling `mimetype` when missing. Media over `limit` bytes or
+/// that fails to download is left out; the event is still delivered.
+pub async fn embed_base64(
+    client: &Client,
+    media: &dyn Downloadable,
+    mimetype: Option<&str>,
+    limit: Option<u64>,
+    message: &mut Map<String, Value>,
+) {
+    if !within_limit(media.file_length(), limit) {
+        tracing::debug!(
+            size = ?media.file_length(),
+            limit = ?limit,
+            "Mídia acima do limite do base64 do webhook, enviada sem ela"
+        );
+        return;
+    }
+    let bytes = match client.download(media).await {
+        Ok(bytes) => bytes,
+        Err(err) => {
+            tracing::warn!(error = %err, "Falha ao baixar mídia para o base64 do webhook");
+            return;
+        }
+    };
+    if !within_limit(Some(bytes.len() as u64), limit) {
+        tracing::debug!(
+            size = bytes.len(),
+            limit = ?limit,
+            "Mídia acima do limite do base64 do webhook, enviada sem ela"
+        );
+        return;
+    }
+
+    let mimetype = mimetype
+        .filter(|mime| !mime.trim().is_empty())
+        .unwrap_or("application/octet-stream");
+    message.entry("mimetype").or_insert_with(|| json!(mimetype));
+    message.insert(
+        "base64".to_string(),
+        json!(format!(
+            "data:{};base64,{}",
+            mimetype,
+            general_purpose::STANDARD.encode(bytes)
+        )),
+    );
+}
+
+/// Persists an inbound media message so it can be downloaded later through
+/// `/chat/getBase64FromMediaMessage`. Messages without media are ignored.
+pub async fn store_media_message(
+    state: &AppState,
+    session: &str,
+    message_id: &str,
+    chat_id: &str,
+    from_me: bool,
+    message: &wa::Message,
+) -> anyhow::Result<()> {
+    let Some((_, info)) = find_media(message) else {
+        return Ok(());
+    };
+
+    state
+        .api_store
+        .execute(
+            "INSERT INTO api_messages (session, chat_id, from_me, message_type, payload, status, expires_at) \
+             VALUES ($1, $2, $3, $4, $5, 'received', \
+                     CASE WHEN $6 > 0 THEN now() + make_interval(secs => $6) END)",
+            vec![
+                ApiBind::Text(session.to_string()),
+                ApiBind::Text(chat_id.to_string()),
+                ApiBind::Bool(from_me),
+                ApiBind::Text(info.media_type.to_string()),
+                ApiBind::Json(json!({
+                    "messageId": message_id,
+                    "message": serde_json::to_value(message)?,
+                })),
+                // Disappearing messages expire along with the chat's copy.
+                ApiBind::Int(
+                    ephemeral::expiration(message)
+                        .and_then(|v| i32::try_from(v).ok())
+                        .unwrap_or(0),
+                ),
+            ],
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// Loads a message previously stored by [`store_media_message`].
+pub async fn load_stored_message(
+    state: &AppState,
+    session: &str,
+    message_id: &str,
+) -> anyhow::Result<Option<wa::Message>> {
+    let rows = state
+        .api_store
+        .query_json(
+            "SELECT row_to_json(t)::jsonb as value FROM ( \
+                SELECT payload FROM api_messages \
+                WHERE session = $1 AND payload->>'messageId' = $2 \
+                ORDER BY created_at DESC LIMIT 1 \
+             ) t",
+            vec![
+                ApiBind::Text(session.to_string()),
+                ApiBind::Text(message_id.to_string()),
+            ],
+        )
+        .await?;
+
+    let Some(message) = rows
+        .into_iter()
+        .next()
+        .and_then(|row| row.get("payload").and_then(|p| p.get("message")).cloned())
+    else {
+        return Ok(None);
+    };
+
+    Ok(Some(serde_json::from_value(message)?))
+}
+
+#[cfg(test)]
+mod tests {
+    include!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/tests/server/media_tests.rs"));
+}
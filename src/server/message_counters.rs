@@ -0,0 +1,240 @@
+//! Messages sent, received and failed per instance since the server
+//! started.
+//!
+//! The counters live in memory: the worker counts queued messages once they
+//! are sent or given up, and an event handler counts incoming messages.
+//! `/instance/fetchInstances` reports them in `_count`, next to the stored
+//! messages, contacts and chats, and `GET /metrics/prometheus` publishes
+//! them as Prometheus counters labelled by instance, with the stored
+//! contacts and chats as gauges, the [event sink](super::event_bus)
+//! counters and what the [retention pruner](super::retention) reclaimed.
+
+use crate::client::Client;
+use crate::server::{AppState, dedup, event_bus, retention};
+use crate::types::events::{Event, EventHandler};
+use axum::{extract::State, http::header, response::IntoResponse};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex, PoisonError};
+
+/// What happened to a message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Sent,
+    Received,
+    Failed,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct MessageCounts {
+    pub sent: u64,
+    pub received: u64,
+    pub failed: u64,
+}
+
+/// Counters of every instance, shared by the worker and the event handlers.
+#[derive(Debug, Default)]
+pub struct MessageCounters {
+    counts: Mutex<HashMap<String, MessageCounts>>,
+}
+
+impl MessageCounters {
+    pub fn record(&self, instance: &str, outcome: Outcome) {
+        let mut counts = self.counts.lock().unwrap_or_else(PoisonError::into_inner);
+        let counts = counts.entry(instance.to_string()).or_default();
+        match outcome {
+            Outcome::Sent => counts.sent += 1,
+            Outcome::Received => counts.received += 1,
+            Outcome::Failed => counts.failed += 1,
+        }
+    }
+
+    pub fn get(&self, instance: &str) -> MessageCounts {
+        self.counts
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(instance)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Counters of every instance that has any, by name.
+    pub fn snapshot(&self) -> Vec<(String, MessageCounts)> {
+        let mut snapshot: Vec<_> = self
+            .counts
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .map(|(instance, counts)| (instance.clone(), *counts))
+            .collect();
+        snapshot.sort_by(|a, b| a.0.cmp(&b.0));
+        snapshot
+    }
+}
+
+struct ReceivedMessageCounter {
+    counters: Arc<MessageCounters>,
+    instance_name: String,
+}
+
+impl EventHandler for ReceivedMessageCounter {
+    fn handle_event(&self, event: &Event) {
+        if let Event::Message(_, info) = event
+            && !info.source.is_from_me
+        {
+            self.counters.record(&self.instance_name, Outcome::Received);
+        }
+    }
+}
+
+/// Counts the messages `client` receives for `instance_name`.
+pub fn attach(state: &AppState, instance_name: String, client: &Client) {
+    client
+        .core
+        .event_bus
+        .add_handler(Arc::new(ReceivedMessageCounter {
+            counters: state.message_counters.clone(),
+            instance_name,
+        }));
+}
+
+/// Contacts and chats stored per instance.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoredTotals {
+    pub instance: String,
+    pub contacts: u64,
+    pub chats: u64,
+}
+
+async fn stored_totals(state: &AppState) -> anyhow::Result<Vec<StoredTotals>> {
+    let rows = state
+        .api_store
+        .query_json(
+            "SELECT jsonb_build_object('session', s.session, \
+                'contacts', (SELECT count(*) FROM api_contacts c WHERE c.session = s.session), \
+                'chats', (SELECT count(*) FROM api_chats c WHERE c.session = s.session)) as value \
+             FROM api_sessions s \
+             ORDER BY s.session",
+            vec![],
+        )
+        .await?;
+    Ok(rows
+        .iter()
+        .filter_map(|row| {
+            let row = row.get("value").unwrap_or(row);
+            Some(StoredTotals {
+                instance: row["session"].as_str()?.to_string(),
+                contacts: row["contacts"].as_u64().unwrap_or(0),
+                chats: row["chats"].as_u64().unwrap_or(0),
+            })
+        })
+        .collect())
+}
+
+/// Adds `counts` to a fetchInstances `_count` (the stored `Message`,
+/// `Contact` and `Chat` totals).
+pub fn merge_into_count(count: &mut Value, counts: MessageCounts) {
+    if !count.is_object() {
+        *count = Value::Object(serde_json::Map::new());
+    }
+    count["sent"] = counts.sent.into();
+    count["received"] = counts.received.into();
+    count["failed"] = counts.failed.into();
+}
+
+/// Name, help text and value of a Prometheus metric.
+pub(crate) type Metric<T> = (&'static str, &'static str, fn(&T) -> u64);
+
+/// Prometheus text exposition of `counts` and `stored`.
+pub fn render_prometheus(counts: &[(String, MessageCounts)], stored: &[StoredTotals]) -> String {
+    let mut out = String::new();
+    let counters: [Metric<MessageCounts>; 3] = [
+        (
+            "chatwarp_messages_sent_total",
+            "Messages sent since the server started.",
+            |c| c.sent,
+        ),
+        (
+            "chatwarp_messages_received_total",
+            "Messages received since the server started.",
+            |c| c.received,
+        ),
+        (
+            "chatwarp_messages_failed_total",
+            "Messages that could not be sent since the server started.",
+            |c| c.failed,
+        ),
+    ];
+    for (name, help, value) in counters {
+        let _ = writeln!(out, "# HELP {name} {help}\n# TYPE {name} counter");
+        for (instance, counts) in counts {
+            let _ = writeln!(
+                out,
+                "{name}{{instance=\"{}\"}} {}",
+                label(instance),
+                value(counts)
+            );
+        }
+    }
+    let gauges: [Metric<StoredTotals>; 2] = [
+        ("chatwarp_contacts", "Contacts stored.", |t| t.contacts),
+        ("chatwarp_chats", "Chats stored.", |t| t.chats),
+    ];
+    // Without a store there is nothing to report, not zero.
+    for (name, help, value) in gauges.into_iter().filter(|_| !stored.is_empty()) {
+        let _ = writeln!(out, "# HELP {name} {help}\n# TYPE {name} gauge");
+        for totals in stored {
+            let _ = writeln!(
+                out,
+                "{name}{{instance=\"{}\"}} {}",
+                label(&totals.instance),
+                value(totals)
+            );
+        }
+    }
+    out
+}
+
+/// Escapes a label value.
+pub(crate) fn label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// `GET /metrics/prometheus`: the counters of every instance with a client
+/// or a count, plus the stored totals when the store can answer.
+pub async fn prometheus_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let mut counts = state.message_counters.snapshot();
+    for client in state.clients.iter() {
+        if !counts.iter().any(|(instance, _)| instance == client.key()) {
+            counts.push((client.key().clone(), MessageCounts::default()));
+        }
+    }
+    counts.sort_by(|a, b| a.0.cmp(&b.0));
+    let stored = match stored_totals(&state).await {
+        Ok(stored) => stored,
+        Err(e) => {
+            tracing::debug!(error = %e, "Totais de contatos e chats indisponíveis");
+            Vec::new()
+        }
+    };
+    (
+        [(
+            header::CONTENT_TYPE,
+            "text/plain; version=0.0.4; charset=utf-8",
+        )],
+        render_prometheus(&counts, &stored)
+            + &event_bus::render_prometheus(&event_bus::sink_counts(&state))
+            + &retention::render_prometheus(&state.retention_metrics.snapshot())
+            + &dedup::render_prometheus(&state.inbound_dedup.hits()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    include!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/tests/server/message_counters_tests.rs"));
+}
@@ -0,0 +1,266 @@
+//! Delivery lifecycle of messages sent from the queue.
+//!
+//! `status` of `api_messages` only tracks the queue (`queued`, `sent`,
+//! `failed`). Once a message leaves the socket, its WhatsApp id is kept in
+//! `wa_message_id` and `delivery_status` follows the server ack and the
+//! receipts of the recipient: `pending` → `server` → `delivered` → `read` →
+//! `played`, or `failed`. Every change is appended to `status_history`
+//! and emitted as MESSAGES_UPDATE.
+
+use crate::api_store::ApiBind;
+use crate::client::Client;
+use crate::server::{AppState, webhooks};
+use crate::types::events::{Event, EventHandler};
+use crate::types::presence::ReceiptType;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+/// Ordered by progress, so statuses compare with `<`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MessageStatus {
+    /// Written to the socket, not yet acknowledged by the server.
+    Pending,
+    Server,
+    Delivered,
+    Read,
+    Played,
+    Failed,
+}
+
+impl MessageStatus {
+    const ALL: [Self; 6] = [
+        Self::Pending,
+        Self::Server,
+        Self::Delivered,
+        Self::Read,
+        Self::Played,
+        Self::Failed,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Server => "server",
+            Self::Delivered => "delivered",
+            Self::Read => "read",
+            Self::Played => "played",
+            Self::Failed => "failed",
+        }
+    }
+
+    /// Evolution `status` of MESSAGES_UPDATE.
+    pub fn evolution_status(self) -> &'static str {
+        match self {
+            Self::Pending => "PENDING",
+            Self::Server => "SERVER_ACK",
+            Self::Delivered => "DELIVERY_ACK",
+            Self::Read => "READ",
+            Self::Played => "PLAYED",
+            Self::Failed => "ERROR",
+        }
+    }
+
+    /// Status reported by a receipt from the recipient; `None` for receipts
+    /// that say nothing about our message (retries, our own devices, ...).
+    pub fn from_receipt(receipt: &ReceiptType) -> Option<Self> {
+        match receipt {
+            ReceiptType::Delivered => Some(Self::Delivered),
+            ReceiptType::Read => Some(Self::Read),
+            ReceiptType::Played => Some(Self::Played),
+            ReceiptType::ServerError => Some(Self::Failed),
+            _ => None,
+        }
+    }
+
+    /// Whether a message in `self` may move to `next`. Statuses only move
+    /// forward, receipts can arrive out of order, and a message the
+    /// recipient got can no longer fail.
+    pub fn can_advance_to(self, next: Self) -> bool {
+        match (self, next) {
+            (Self::Failed, _) => false,
+            (Self::Pending | Self::Server, Self::Failed) => true,
+            (_, Self::Failed) => false,
+            _ => next > self,
+        }
+    }
+
+    /// Statuses from which `self` can be reached.
+    fn reachable_from(self) -> Vec<&'static str> {
+        Self::ALL
+            .into_iter()
+            .filter(|previous| previous.can_advance_to(self))
+            .map(Self::as_str)
+            .collect()
+    }
+}
+
+/// Status change for some outgoing messages of one instance.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatusUpdate {
+    pub message_ids: Vec<String>,
+    pub status: MessageStatus,
+    pub error: Option<String>,
+}
+
+impl StatusUpdate {
+    /// Maps a client event; `None` for events that do not change a status.
+    pub fn from_event(event: &Event) -> Option<Self> {
+        match event {
+            Event::MessageAck(ack) => Some(Self {
+                message_ids: vec![ack.id.clone()],
+                status: if ack.error.is_some() {
+                    MessageStatus::Failed
+                } else {
+                    MessageStatus::Server
+                },
+                error: ack.error.clone(),
+            }),
+            Event::Receipt(receipt) if !receipt.source.is_from_me => Some(Self {
+                message_ids: receipt.message_ids.clone(),
+                status: MessageStatus::from_receipt(&receipt.r#type)?,
+                error: None,
+            }),
+            _ => None,
+        }
+    }
+}
+
+struct StatusEventForwarder {
+    tx: mpsc::UnboundedSender<StatusUpdate>,
+}
+
+impl EventHandler for StatusEventForwarder {
+    fn handle_event(&self, event: &Event) {
+        if let Some(update) = StatusUpdate::from_event(event) {
+            let _ = self.tx.send(update);
+        }
+    }
+}
+
+/// Subscribes `instance_name` to the acks and receipts of `client`.
+pub fn attach(state: Arc<AppState>, instance_name: String, client: &Client) {
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    client
+        .core
+        .event_bus
+        .add_handler(Arc::new(StatusEventForwarder { tx }));
+
+    tokio::spawn(async move {
+        while let Some(update) = rx.recv().await {
+            if let Err(e) = record(&state, &instance_name, &update).await {
+                log::warn!("Failed to record message status for {}: {}", instance_name, e);
+            }
+        }
+    });
+}
+
+/// Keeps the WhatsApp id of a queued message right before it is sent, so
+/// early receipts find it.
+pub async fn mark_pending(state: &AppState, id: Uuid, wa_message_id: &str) -> anyhow::Result<()> {
+    state
+        .api_store
+        .execute(
+            "UPDATE api_messages SET wa_message_id = $2, delivery_status = 'pending', \
+                 status_updated_at = now(), \
+                 status_history = jsonb_build_array(jsonb_build_object('status', 'pending', 'at', now())) \
+             WHERE id = $1",
+            vec![ApiBind::Uuid(id), ApiBind::Text(wa_message_id.to_string())],
+        )
+        .await
+        .map(|_| ())
+}
+
+/// Marks a queued message whose send failed for good.
+pub async fn mark_failed(state: &AppState, id: Uuid, error: &str) -> anyhow::Result<()> {
+    state
+        .api_store
+        .execute(
+            "UPDATE api_messages SET delivery_status = 'failed', delivery_error = $2, \
+                 status_updated_at = now(), \
+                 status_history = status_history \
+                     || jsonb_build_array(jsonb_build_object('status', 'failed', 'at', now())) \
+             WHERE id = $1",
+            vec![ApiBind::Uuid(id), ApiBind::Text(error.to_string())],
+        )
+        .await
+        .map(|_| ())
+}
+
+/// Applies `update` to the messages of `session` it moves forward and emits
+/// MESSAGES_UPDATE for each.
+pub async fn record(state: &AppState, session: &str, update: &StatusUpdate) -> anyhow::Result<()> {
+    let rows = state
+        .api_store
+        .query_json(
+            "WITH updated AS ( \
+                UPDATE api_messages SET delivery_status = $3, \
+                    delivery_error = COALESCE($4, delivery_error), \
+                    status_updated_at = now(), \
+                    status_history = status_history \
+                        || jsonb_build_array(jsonb_build_object('status', $3::text, 'at', now())) \
+                WHERE session = $1 \
+                  AND wa_message_id IN (SELECT jsonb_array_elements_text($2::jsonb)) \
+                  AND delivery_status IN (SELECT jsonb_array_elements_text($5::jsonb)) \
+                RETURNING id, chat_id, wa_message_id \
+            ) SELECT jsonb_build_object('id', id, 'chatId', chat_id, 'waMessageId', wa_message_id) as value \
+              FROM updated",
+            vec![
+                ApiBind::Text(session.to_string()),
+                ApiBind::Json(json!(update.message_ids)),
+                ApiBind::Text(update.status.as_str().to_string()),
+                ApiBind::NullableText(update.error.clone()),
+                ApiBind::Json(json!(update.status.reachable_from())),
+            ],
+        )
+        .await?;
+
+    for row in rows {
+        let mut data = json!({
+            "key": {
+                "remoteJid": row["chatId"],
+                "fromMe": true,
+                "id": row["waMessageId"],
+            },
+            "messageId": row["id"],
+            "status": update.status.evolution_status(),
+        });
+        if let Some(error) = &update.error {
+            data["error"] = json!(error);
+        }
+        webhooks::enqueue(state, Some(session), "MESSAGES_UPDATE", data).await;
+    }
+    Ok(())
+}
+
+/// Delivery status of a message of `session`, looked up by the id returned
+/// when it was queued or by its WhatsApp id.
+pub async fn find(state: &AppState, session: &str, message_id: &str) -> anyhow::Result<Option<Value>> {
+    let rows = state
+        .api_store
+        .query_json(
+            "SELECT jsonb_build_object( \
+                'messageId', id, 'waMessageId', wa_message_id, 'chatId', chat_id, \
+                'queueStatus', status, 'status', COALESCE(delivery_status, 'pending'), \
+                'error', delivery_error, 'updatedAt', status_updated_at, \
+                'history', status_history, 'createdAt', created_at \
+             ) as value \
+             FROM api_messages \
+             WHERE session = $1 AND (id::text = $2 OR wa_message_id = $2) \
+             LIMIT 1",
+            vec![
+                ApiBind::Text(session.to_string()),
+                ApiBind::Text(message_id.to_string()),
+            ],
+        )
+        .await?;
+    Ok(rows.into_iter().next())
+}
+
+#[cfg(test)]
+mod tests {
+    include!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/tests/server/message_status_tests.rs"));
+}
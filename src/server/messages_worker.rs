@@ -1,40 +1,119 @@
 use crate::api_store::ApiBind;
-use crate::client::Client;
+use crate::client::{Client, ClientError};
 use crate::http::HttpRequest;
 use crate::server::AppState;
+use crate::server::audio;
+use crate::server::cloud_api;
+use crate::server::ephemeral;
+use crate::server::jid;
+use crate::server::link_preview;
+use crate::server::message_counters::Outcome;
+use crate::server::message_status;
 use crate::server::queue::MessageQueue;
+use crate::server::quotas;
+use crate::server::templates::MAX_BUTTONS;
+use crate::server::thumbnails::{self, Thumbnail, ThumbnailConfig};
+use crate::server::uploads::{self, UploadConfig};
+use crate::socket::SocketError;
+use crate::upload::UploadResponse;
 use base64::Engine as _;
 use chrono::{DateTime, Utc};
 use dashmap::DashMap;
 use serde_json::Value;
+use std::path::PathBuf;
 use std::sync::Arc;
 use tokio::sync::{Mutex, Semaphore, mpsc};
 use tokio::time::{Duration, sleep};
 use uuid::Uuid;
 use waproto::whatsapp as wa;
 use warp_core::download::MediaType;
-use warp_core_binary::jid::Jid;
+use warp_core_binary::jid::{Jid, JidExt as _};
 
 /// Maximum concurrent in-flight sends across all chats.
 const MAX_CONCURRENT_SENDS: usize = 32;
+/// Per-session share of [`MAX_CONCURRENT_SENDS`], so one slow session cannot
+/// hold every permit and stall the others.
+const MAX_CONCURRENT_SENDS_PER_SESSION: usize = 8;
+/// Deadline for a single `send_message` call (encryption + socket write).
+const SEND_TIMEOUT: Duration = Duration::from_secs(30);
+/// Deadline for building the message, including media download/upload.
+const BUILD_TIMEOUT: Duration = Duration::from_secs(120);
+/// Attempts for sends that fail while the session is (re)connecting.
+const MAX_SEND_ATTEMPTS: u32 = 3;
 /// Fallback poll interval when the notify channel is idle.
 const POLL_FALLBACK_SECONDS: u64 = 1;
 /// TTL before a queued message is failed if its session never connected.
 const SESSION_WAIT_TTL_MINUTES: i64 = 10;
+/// Rows a list message may carry across all its sections.
+const MAX_LIST_ROWS: usize = 10;
 
 /// Per-chat key: "<session>:<chat_id>"
 type ChatKey = String;
 
+/// Why a send attempt did not go through.
+#[derive(Debug)]
+enum SendFailure {
+    Timeout,
+    Error(anyhow::Error),
+}
+
+impl SendFailure {
+    /// Timeouts and connection-level errors are worth retrying; anything else
+    /// (bad JID, encryption failure, ...) will fail the same way again.
+    fn is_transient(&self) -> bool {
+        match self {
+            Self::Timeout => true,
+            Self::Error(err) => err.chain().any(|cause| {
+                let socket = match cause.downcast_ref::<ClientError>() {
+                    Some(ClientError::NotConnected) => return true,
+                    Some(ClientError::Socket(socket)) => Some(socket),
+                    _ => cause.downcast_ref::<SocketError>(),
+                };
+                matches!(socket, Some(SocketError::SocketClosed | SocketError::Io(_)))
+            }),
+        }
+    }
+}
+
+impl std::fmt::Display for SendFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Timeout => write!(f, "timed out after {}s", SEND_TIMEOUT.as_secs()),
+            Self::Error(err) => write!(f, "{err:?}"),
+        }
+    }
+}
+
+/// Delay before retry number `attempt` (1-based): 1s, 2s, 4s, ...
+fn retry_delay(attempt: u32) -> Duration {
+    Duration::from_secs(1 << attempt.saturating_sub(1).min(5))
+}
+
 pub async fn spawn_messages_worker(app_state: Arc<AppState>, mut message_rx: mpsc::Receiver<()>) {
     let queue = MessageQueue::new(app_state.clone());
     // Per-chat locks: serialise sends *within* a chat, parallelise *across* chats.
     let chat_locks: Arc<DashMap<ChatKey, Arc<Mutex<()>>>> = Arc::new(DashMap::new());
     // Global semaphore caps total in-flight sends to avoid socket saturation.
     let semaphore = Arc::new(Semaphore::new(MAX_CONCURRENT_SENDS));
+    let session_semaphores: Arc<DashMap<String, Arc<Semaphore>>> = Arc::new(DashMap::new());
 
     loop {
-        let processed_any =
-            match drain_message_batch(&app_state, &queue, &chat_locks, &semaphore).await {
+        // Maintenance mode pauses sending; queued jobs wait in the outbox.
+        // A standby or fenced node leaves them to the primary.
+        if app_state.runtime_config().maintenance_mode || !app_state.standby.is_primary() {
+            sleep(Duration::from_secs(POLL_FALLBACK_SECONDS)).await;
+            continue;
+        }
+
+        let processed_any = match drain_message_batch(
+            &app_state,
+            &queue,
+            &chat_locks,
+            &semaphore,
+            &session_semaphores,
+        )
+        .await
+        {
                 Ok(v) => v,
                 Err(err) => {
                     log::error!("Error processing queued messages: {}", err);
@@ -131,7 +210,18 @@ pub async fn warm_sessions(app_state: Arc<AppState>, session: String) {
     }
 }
 
-async fn mark_status(state: &AppState, id: Uuid, status: &str) -> anyhow::Result<()> {
+/// Stores the status of a queued message, counting it once sent or failed.
+async fn mark_status(
+    state: &AppState,
+    session: &str,
+    id: Uuid,
+    status: &str,
+) -> anyhow::Result<()> {
+    match status {
+        "sent" => state.message_counters.record(session, Outcome::Sent),
+        "failed" => state.message_counters.record(session, Outcome::Failed),
+        _ => {}
+    }
     state
         .api_store
         .execute(
@@ -155,12 +245,18 @@ async fn drain_message_batch(
     queue: &MessageQueue,
     chat_locks: &Arc<DashMap<ChatKey, Arc<Mutex<()>>>>,
     semaphore: &Arc<Semaphore>,
+    session_semaphores: &Arc<DashMap<String, Arc<Semaphore>>>,
 ) -> anyhow::Result<bool> {
-    let sessions: Vec<String> = app_state
+    let mut sessions: Vec<String> = app_state
         .clients
         .iter()
         .map(|entry| entry.key().clone())
         .collect();
+    // Cloud API instances have no client but their queue is drained too.
+    match cloud_api::sessions(app_state).await {
+        Ok(cloud) => sessions.extend(cloud),
+        Err(err) => log::debug!("Cloud API session list failed: {}", err),
+    }
 
     if sessions.is_empty() {
         return Ok(false);
@@ -181,6 +277,10 @@ async fn drain_message_batch(
 
         let state = app_state.clone();
         let sem = semaphore.clone();
+        let session_sem = session_semaphores
+            .entry(job.session.clone())
+            .or_insert_with(|| Arc::new(Semaphore::new(MAX_CONCURRENT_SENDS_PER_SESSION)))
+            .clone();
         let session = job.session.clone();
         let row = serde_json::json!({
             "id": job.id.to_string(),
@@ -192,7 +292,9 @@ async fn drain_message_batch(
         });
 
         tokio::spawn(async move {
-            // Acquire global semaphore first (back-pressure).
+            // Session share first, so a stalled session only queues behind itself.
+            let _session_permit = session_sem.acquire().await;
+            // Then the global semaphore (back-pressure).
             let _permit = sem.acquire().await;
             // Then serialise within this chat (preserve message ordering).
             let _chat_guard = chat_lock.lock().await;
@@ -232,90 +334,255 @@ async fn process_single_message(
         return;
     };
 
-    let Ok(jid) = chat_id_str.parse::<Jid>() else {
-        let _ = mark_status(app_state, uuid, "failed").await;
+    let Ok(jid) = jid::parse(chat_id_str) else {
+        let _ = mark_status(app_state, session, uuid, "failed").await;
         return;
     };
 
     let Some(client_ref) = app_state.clients.get(session) else {
+        match cloud_api::channel(app_state, session).await {
+            Ok(Some(channel)) => {
+                send_cloud_message(app_state, session, uuid, &channel, message_type, &payload)
+                    .await;
+                return;
+            }
+            Ok(None) => {}
+            Err(err) => log::warn!("Cloud API lookup for {} failed: {}", session, err),
+        }
         log::warn!(
             "Session {} not found for queued message {}",
             session,
             id_str
         );
         if should_fail_missing_session(created_at, session_wait_ttl_minutes) {
-            let _ = mark_status(app_state, uuid, "failed").await;
+            let _ = mark_status(app_state, session, uuid, "failed").await;
         } else {
-            let _ = mark_status(app_state, uuid, "queued").await;
+            let _ = mark_status(app_state, session, uuid, "queued").await;
         }
         return;
     };
 
     let client = client_ref.value().clone();
-    let message_opt = build_message(&client, message_type, &payload).await;
-
-    if let Some(msg) = message_opt {
-        if let Err(e) = client.send_message(jid.clone(), msg).await {
-            log::error!("Error sending message {}: {:?}", id_str, e);
-            let _ = mark_status(app_state, uuid, "failed").await;
-        } else {
-            let _ = mark_status(app_state, uuid, "sent").await;
+    drop(client_ref);
+    let media = MediaOptions {
+        limit: quotas::media_limit(&app_state.runtime_config()),
+        uploads: &app_state.uploads,
+        thumbnails: &app_state.thumbnails,
+        session,
+    };
+    let build = build_message(&client, message_type, &payload, media);
+    let message_opt = match tokio::time::timeout(BUILD_TIMEOUT, build).await {
+        Ok(message) => message,
+        Err(_) => {
+            log::warn!("Building message {} timed out", id_str);
+            None
         }
-    } else {
+    };
+
+    let Some(mut msg) = message_opt else {
         log::warn!("Could not build message for type '{}'", message_type);
-        let _ = mark_status(app_state, uuid, "failed").await;
+        let _ = mark_status(app_state, session, uuid, "failed").await;
+        return;
+    };
+
+    // Disappearing timers are tracked for direct chats only; groups keep
+    // theirs in the group metadata.
+    let timer = if jid.is_group() {
+        None
+    } else {
+        match ephemeral::load(app_state, session, &jid.to_string()).await {
+            Ok(timer) => timer,
+            Err(err) => {
+                log::warn!("Could not load disappearing timer of {}: {}", jid, err);
+                None
+            }
+        }
+    };
+    if let Some(timer) = timer {
+        ephemeral::apply(&mut msg, timer);
+    }
+
+    // Recorded before sending so the server ack cannot arrive first.
+    let wa_message_id = client.generate_message_id().await;
+    let _ = message_status::mark_pending(app_state, uuid, &wa_message_id).await;
+
+    match send_with_retry(&client, &jid, msg, &wa_message_id).await {
+        Ok(attempts) => {
+            if attempts > 1 {
+                let _ = record_attempts(app_state, uuid, attempts).await;
+            }
+            let _ = mark_status(app_state, session, uuid, "sent").await;
+            if let Some(timer) = timer {
+                let _ = ephemeral::mark_expiry(app_state, uuid, timer.expiration).await;
+            }
+        }
+        Err((attempts, failure)) => {
+            log::error!(
+                "Error sending message {} after {} attempt(s): {}",
+                id_str,
+                attempts,
+                failure
+            );
+            let _ = record_attempts(app_state, uuid, attempts).await;
+            let _ = mark_status(app_state, session, uuid, "failed").await;
+            let _ = message_status::mark_failed(app_state, uuid, &failure.to_string()).await;
+        }
     }
 }
 
+/// Sends a queued message of a Cloud API instance through the Graph API.
+async fn send_cloud_message(
+    app_state: &AppState,
+    session: &str,
+    id: Uuid,
+    channel: &cloud_api::CloudChannel,
+    message_type: &str,
+    payload: &Value,
+) {
+    let result = tokio::time::timeout(
+        SEND_TIMEOUT,
+        cloud_api::send(&app_state.http, &app_state.meta, channel, message_type, payload),
+    )
+    .await;
+    let status = match result {
+        Ok(Ok(_)) => "sent",
+        Ok(Err(err)) => {
+            log::error!("Cloud API send of message {} failed: {}", id, err);
+            "failed"
+        }
+        Err(_) => {
+            log::error!("Cloud API send of message {} timed out", id);
+            "failed"
+        }
+    };
+    let _ = mark_status(app_state, session, id, status).await;
+}
+
+/// Sends `msg` as `wa_message_id` with a per-call timeout, retrying
+/// transient failures with exponential backoff under the same id. Returns
+/// the number of attempts made.
+async fn send_with_retry(
+    client: &Client,
+    jid: &Jid,
+    msg: wa::Message,
+    wa_message_id: &str,
+) -> Result<u32, (u32, SendFailure)> {
+    let mut attempt = 1;
+    loop {
+        let send =
+            client.send_message_with_id(jid.clone(), msg.clone(), wa_message_id.to_string());
+        let failure = match tokio::time::timeout(SEND_TIMEOUT, send).await {
+            Ok(Ok(_)) => return Ok(attempt),
+            Ok(Err(err)) => SendFailure::Error(err),
+            Err(_) => SendFailure::Timeout,
+        };
+
+        if attempt >= MAX_SEND_ATTEMPTS || !failure.is_transient() {
+            return Err((attempt, failure));
+        }
+        log::warn!("Send attempt {} to {} failed ({}), retrying", attempt, jid, failure);
+        sleep(retry_delay(attempt)).await;
+        attempt += 1;
+    }
+}
+
+async fn record_attempts(state: &AppState, id: Uuid, attempts: u32) -> anyhow::Result<()> {
+    state
+        .api_store
+        .execute(
+            "UPDATE api_messages SET attempts = $1 WHERE id = $2",
+            vec![
+                ApiBind::Int(i32::try_from(attempts).unwrap_or(i32::MAX)),
+                ApiBind::Uuid(id),
+            ],
+        )
+        .await
+        .map(|_| ())
+}
+
+/// Where the media of a queued message may come from, and how large it may be.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct MediaOptions<'a> {
+    /// Media larger than this many bytes is rejected.
+    pub limit: Option<u64>,
+    /// Spool of `/media/upload`, for payloads with a `mediaId`.
+    pub uploads: &'a UploadConfig,
+    /// `jpegThumbnail` of images and videos.
+    pub thumbnails: &'a ThumbnailConfig,
+    pub session: &'a str,
+}
+
+/// Builds the WhatsApp message of a queued row.
 pub(crate) async fn build_message(
     client: &Client,
     message_type: &str,
     payload: &Value,
+    media: MediaOptions<'_>,
 ) -> Option<wa::Message> {
     match message_type {
-        "text" => build_text_message(payload),
-        "image" => match build_image_message(client, payload).await {
+        "text" => {
+            let mut msg = build_text_message(payload)?;
+            if payload
+                .get("linkPreview")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false)
+            {
+                attach_link_preview(client, &mut msg).await;
+            }
+            Some(msg)
+        }
+        "image" => match build_image_message(client, payload, media).await {
             Ok(msg) => Some(msg),
             Err(err) => {
                 log::warn!("Failed to build image message: {err}");
                 None
             }
         },
-        "video" => match build_video_message(client, payload).await {
+        "video" => match build_video_message(client, payload, media).await {
             Ok(msg) => Some(msg),
             Err(err) => {
                 log::warn!("Failed to build video message: {err}");
                 None
             }
         },
-        "voice" => match build_audio_message(client, payload, true).await {
+        "voice" => match build_audio_message(client, payload, true, media).await {
             Ok(msg) => Some(msg),
             Err(err) => {
                 log::warn!("Failed to build voice message: {err}");
                 None
             }
         },
-        "audio" => match build_audio_message(client, payload, false).await {
+        "audio" => match build_audio_message(client, payload, false, media).await {
             Ok(msg) => Some(msg),
             Err(err) => {
                 log::warn!("Failed to build audio message: {err}");
                 None
             }
         },
-        "file" => match build_document_message(client, payload).await {
+        "file" => match build_document_message(client, payload, media).await {
             Ok(msg) => Some(msg),
             Err(err) => {
                 log::warn!("Failed to build file message: {err}");
                 None
             }
         },
-        "sticker" => match build_sticker_message(client, payload).await {
+        "sticker" => match build_sticker_message(client, payload, media).await {
             Ok(msg) => Some(msg),
             Err(err) => {
                 log::warn!("Failed to build sticker message: {err}");
                 None
             }
         },
+        "template" => build_template_message(payload),
+        "buttons" => build_buttons_message(payload),
+        "list" => build_list_message(payload),
+        "product" => match build_product_message(client, payload, media).await {
+            Ok(msg) => Some(msg),
+            Err(err) => {
+                log::warn!("Failed to build product message: {err}");
+                None
+            }
+        },
         _ => {
             log::warn!("Message type {} not implemented in worker", message_type);
             None
@@ -345,6 +612,273 @@ pub(crate) fn build_text_message(payload: &Value) -> Option<wa::Message> {
     }
 }
 
+/// Hydrated four-row template from a rendered `templates::Rendered` payload.
+pub(crate) fn build_template_message(payload: &Value) -> Option<wa::Message> {
+    use wa::hydrated_template_button::{
+        HydratedButton, HydratedCallButton, HydratedQuickReplyButton, HydratedUrlButton,
+    };
+    use wa::message::template_message::{self, HydratedFourRowTemplate};
+
+    let text = payload.get("text").and_then(|v| v.as_str()).unwrap_or("");
+    if text.trim().is_empty() {
+        return None;
+    }
+    let field = |value: &Value, key: &str| value.get(key).and_then(|v| v.as_str()).map(str::to_string);
+
+    let buttons = payload
+        .get("buttons")
+        .and_then(|v| v.as_array())
+        .map(Vec::as_slice)
+        .unwrap_or_default()
+        .iter()
+        .enumerate()
+        .filter_map(|(index, button)| {
+            let display_text = field(button, "text");
+            let hydrated = match button.get("type").and_then(|v| v.as_str())? {
+                "reply" => HydratedButton::QuickReplyButton(HydratedQuickReplyButton {
+                    display_text,
+                    id: field(button, "id").or_else(|| Some(format!("btn-{}", index + 1))),
+                }),
+                "url" => HydratedButton::UrlButton(HydratedUrlButton {
+                    display_text,
+                    url: field(button, "url"),
+                    ..Default::default()
+                }),
+                "call" => HydratedButton::CallButton(HydratedCallButton {
+                    display_text,
+                    phone_number: field(button, "phone"),
+                }),
+                _ => return None,
+            };
+            Some(wa::HydratedTemplateButton {
+                index: Some(index as u32),
+                hydrated_button: Some(hydrated),
+            })
+        })
+        .collect();
+
+    let template = Box::new(HydratedFourRowTemplate {
+        hydrated_content_text: Some(text.to_string()),
+        hydrated_footer_text: field(payload, "footer"),
+        hydrated_buttons: buttons,
+        title: field(payload, "title")
+            .map(template_message::hydrated_four_row_template::Title::HydratedTitleText),
+        ..Default::default()
+    });
+    Some(wa::Message {
+        template_message: Some(Box::new(wa::message::TemplateMessage {
+            context_info: build_reply_context_info(payload),
+            hydrated_template: Some(template.clone()),
+            format: Some(template_message::Format::HydratedFourRowTemplate(template)),
+            ..Default::default()
+        })),
+        ..Default::default()
+    })
+}
+
+/// Native flow button message: up to three `reply`, `url` or `call` buttons,
+/// in the same format as template buttons. `None` when a button is invalid.
+pub(crate) fn build_buttons_message(payload: &Value) -> Option<wa::Message> {
+    use wa::message::interactive_message::{
+        self, Body, Footer, Header, NativeFlowMessage, native_flow_message::NativeFlowButton,
+    };
+
+    let text = payload.get("text").and_then(|v| v.as_str()).unwrap_or("");
+    let buttons = payload.get("buttons").and_then(|v| v.as_array())?;
+    if text.trim().is_empty() || buttons.is_empty() || buttons.len() > MAX_BUTTONS {
+        return None;
+    }
+    let field = |value: &Value, key: &str| {
+        value
+            .get(key)
+            .and_then(|v| v.as_str())
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+            .map(str::to_string)
+    };
+
+    let buttons = buttons
+        .iter()
+        .enumerate()
+        .map(|(index, button)| {
+            let display_text = field(button, "text")?;
+            let (name, params) = match button.get("type").and_then(|v| v.as_str())? {
+                "reply" => (
+                    "quick_reply",
+                    serde_json::json!({
+                        "display_text": display_text,
+                        "id": field(button, "id").unwrap_or_else(|| format!("btn-{}", index + 1)),
+                    }),
+                ),
+                "url" => {
+                    let url = field(button, "url")?;
+                    (
+                        "cta_url",
+                        serde_json::json!({
+                            "display_text": display_text,
+                            "url": url,
+                            "merchant_url": url,
+                        }),
+                    )
+                }
+                "call" => (
+                    "cta_call",
+                    serde_json::json!({
+                        "display_text": display_text,
+                        "phone_number": field(button, "phone")?,
+                    }),
+                ),
+                _ => return None,
+            };
+            Some(NativeFlowButton {
+                name: Some(name.to_string()),
+                button_params_json: Some(params.to_string()),
+            })
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    let interactive = wa::message::InteractiveMessage {
+        header: field(payload, "title").map(|title| {
+            Box::new(Header {
+                title: Some(title),
+                has_media_attachment: Some(false),
+                ..Default::default()
+            })
+        }),
+        body: Some(Body {
+            text: Some(text.to_string()),
+        }),
+        footer: field(payload, "footer").map(|footer| {
+            Box::new(Footer {
+                text: Some(footer),
+                ..Default::default()
+            })
+        }),
+        context_info: build_reply_context_info(payload),
+        interactive_message: Some(interactive_message::InteractiveMessage::NativeFlowMessage(
+            NativeFlowMessage {
+                buttons,
+                message_params_json: Some("{}".to_string()),
+                message_version: Some(1),
+            },
+        )),
+        ..Default::default()
+    };
+    Some(view_once(wa::Message {
+        interactive_message: Some(Box::new(interactive)),
+        ..Default::default()
+    }))
+}
+
+/// Single select list message. Rows without `id` get `row-<n>`, numbered
+/// across sections. `None` without a description, button text or rows.
+pub(crate) fn build_list_message(payload: &Value) -> Option<wa::Message> {
+    use wa::message::list_message::{ListType, Row, Section};
+
+    let field = |value: &Value, key: &str| {
+        value
+            .get(key)
+            .and_then(|v| v.as_str())
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+            .map(str::to_string)
+    };
+    let description = field(payload, "text")?;
+    let button_text = field(payload, "buttonText")?;
+
+    let mut row_count = 0;
+    let mut sections = Vec::new();
+    for section in payload.get("sections").and_then(|v| v.as_array())? {
+        let mut rows = Vec::new();
+        for row in section.get("rows").and_then(|v| v.as_array())? {
+            row_count += 1;
+            rows.push(Row {
+                title: Some(field(row, "title")?),
+                description: field(row, "description"),
+                row_id: Some(field(row, "id").unwrap_or_else(|| format!("row-{row_count}"))),
+            });
+        }
+        if rows.is_empty() {
+            return None;
+        }
+        sections.push(Section {
+            title: field(section, "title"),
+            rows,
+        });
+    }
+    if sections.is_empty() || row_count > MAX_LIST_ROWS {
+        return None;
+    }
+
+    Some(view_once(wa::Message {
+        list_message: Some(Box::new(wa::message::ListMessage {
+            title: field(payload, "title"),
+            description: Some(description),
+            button_text: Some(button_text),
+            list_type: Some(ListType::SingleSelect as i32),
+            sections,
+            footer_text: field(payload, "footer"),
+            context_info: build_reply_context_info(payload),
+            ..Default::default()
+        })),
+        ..Default::default()
+    }))
+}
+
+/// Wraps an interactive message the way current WA clients send them: in a
+/// view once envelope with the device list metadata version set. Recipients
+/// drop interactive messages sent bare.
+fn view_once(mut message: wa::Message) -> wa::Message {
+    message.message_context_info = Some(wa::MessageContextInfo {
+        device_list_metadata: Some(wa::DeviceListMetadata::default()),
+        device_list_metadata_version: Some(2),
+        ..Default::default()
+    });
+    wa::Message {
+        view_once_message: Some(Box::new(wa::message::FutureProofMessage {
+            message: Some(Box::new(message)),
+        })),
+        ..Default::default()
+    }
+}
+
+/// Fills ExtendedTextMessage preview fields for the first URL in the text.
+/// Failures are logged and the message is sent without a preview.
+async fn attach_link_preview(client: &Client, msg: &mut wa::Message) {
+    let text = msg
+        .conversation
+        .clone()
+        .or_else(|| msg.extended_text_message.as_ref().and_then(|m| m.text.clone()))
+        .unwrap_or_default();
+    let Some(url) = link_preview::first_url(&text) else {
+        return;
+    };
+
+    let preview = match link_preview::fetch_preview(client, url).await {
+        Ok(preview) => preview,
+        Err(err) => {
+            log::warn!("Link preview for {} failed: {}", url, err);
+            return;
+        }
+    };
+
+    let extended = msg
+        .extended_text_message
+        .get_or_insert_with(|| Box::new(wa::message::ExtendedTextMessage::default()));
+    extended.text = Some(text.clone());
+    extended.matched_text = Some(preview.url);
+    extended.title = preview.title;
+    extended.description = preview.description;
+    extended.preview_type =
+        Some(wa::message::extended_text_message::PreviewType::None as i32);
+    if let Some(thumbnail) = preview.thumbnail {
+        extended.thumbnail_width = Some(thumbnail.width);
+        extended.thumbnail_height = Some(thumbnail.height);
+        extended.jpeg_thumbnail = Some(thumbnail.jpeg);
+    }
+    msg.conversation = None;
+}
+
 pub(crate) fn build_reply_context_info(payload: &Value) -> Option<Box<wa::ContextInfo>> {
     let reply_message_id = payload
         .get("reply")
@@ -381,7 +915,11 @@ pub(crate) fn build_reply_context_info(payload: &Value) -> Option<Box<wa::Contex
     }))
 }
 
-async fn build_image_message(client: &Client, payload: &Value) -> anyhow::Result<wa::Message> {
+async fn build_image_message(
+    client: &Client,
+    payload: &Value,
+    media: MediaOptions<'_>,
+) -> anyhow::Result<wa::Message> {
     let caption = payload
         .get("caption")
         .and_then(|v| v.as_str())
@@ -392,15 +930,16 @@ async fn build_image_message(client: &Client, payload: &Value) -> anyhow::Result
         .and_then(|v| v.as_str())
         .map(|s| s.to_string());
 
-    let data = extract_media_bytes(client, payload, &mut mimetype).await?;
-
-    let upload = client.upload(data, MediaType::Image).await?;
+    let input = extract_media(client, payload, &mut mimetype, media).await?;
+    let thumbnail = input.thumbnail(MediaType::Image, media.thumbnails).await;
+    let upload = input.upload(client, MediaType::Image).await?;
     let context_info = build_reply_context_info(payload);
 
     Ok(wa::Message {
         image_message: Some(Box::new(wa::message::ImageMessage {
             mimetype,
             caption,
+            jpeg_thumbnail: thumbnail.map(|thumbnail| thumbnail.jpeg),
             url: Some(upload.url),
             direct_path: Some(upload.direct_path),
             media_key: Some(upload.media_key),
@@ -414,7 +953,63 @@ async fn build_image_message(client: &Client, payload: &Value) -> anyhow::Result
     })
 }
 
-async fn build_video_message(client: &Client, payload: &Value) -> anyhow::Result<wa::Message> {
+/// Product card from the catalog of `businessOwnerJid`. `image` (URL or
+/// base64) is uploaded as the product picture when present.
+async fn build_product_message(
+    client: &Client,
+    payload: &Value,
+    media: MediaOptions<'_>,
+) -> anyhow::Result<wa::Message> {
+    use wa::message::product_message::ProductSnapshot;
+
+    let field = |key: &str| payload.get(key).and_then(|v| v.as_str()).map(str::to_string);
+    let product_id = field("productId").ok_or_else(|| anyhow::anyhow!("productId is required"))?;
+    let price_amount1000 = payload.get("price").and_then(|v| v.as_f64()).map(|price| {
+        // `price` is in currency units; WhatsApp stores thousandths.
+        (price * 1000.0).round() as i64
+    });
+
+    let product_image = match payload.get("image").and_then(|v| v.as_str()) {
+        Some(image) => {
+            let source = if image.starts_with("http://") || image.starts_with("https://") {
+                serde_json::json!({ "url": image })
+            } else {
+                serde_json::json!({ "base64": image })
+            };
+            build_image_message(client, &source, media).await?.image_message
+        }
+        None => None,
+    };
+
+    Ok(wa::Message {
+        product_message: Some(Box::new(wa::message::ProductMessage {
+            product: Some(Box::new(ProductSnapshot {
+                product_image_count: Some(u32::from(product_image.is_some())),
+                product_image,
+                product_id: Some(product_id),
+                title: field("title"),
+                description: field("description"),
+                currency_code: field("currency"),
+                price_amount1000,
+                retailer_id: field("retailerId"),
+                url: field("url"),
+                ..Default::default()
+            })),
+            business_owner_jid: field("businessOwnerJid"),
+            body: field("body"),
+            footer: field("footer"),
+            context_info: build_reply_context_info(payload),
+            ..Default::default()
+        })),
+        ..Default::default()
+    })
+}
+
+async fn build_video_message(
+    client: &Client,
+    payload: &Value,
+    media: MediaOptions<'_>,
+) -> anyhow::Result<wa::Message> {
     let caption = payload
         .get("caption")
         .and_then(|v| v.as_str())
@@ -425,14 +1020,16 @@ async fn build_video_message(client: &Client, payload: &Value) -> anyhow::Result
         .and_then(|v| v.as_str())
         .map(|s| s.to_string());
 
-    let data = extract_media_bytes(client, payload, &mut mimetype).await?;
-    let upload = client.upload(data, MediaType::Video).await?;
+    let input = extract_media(client, payload, &mut mimetype, media).await?;
+    let thumbnail = input.thumbnail(MediaType::Video, media.thumbnails).await;
+    let upload = input.upload(client, MediaType::Video).await?;
     let context_info = build_reply_context_info(payload);
 
     Ok(wa::Message {
         video_message: Some(Box::new(wa::message::VideoMessage {
             mimetype,
             caption,
+            jpeg_thumbnail: thumbnail.map(|thumbnail| thumbnail.jpeg),
             url: Some(upload.url),
             direct_path: Some(upload.direct_path),
             media_key: Some(upload.media_key),
@@ -450,19 +1047,47 @@ async fn build_audio_message(
     client: &Client,
     payload: &Value,
     ptt: bool,
+    media: MediaOptions<'_>,
 ) -> anyhow::Result<wa::Message> {
     let mut mimetype = payload
         .get("mimetype")
         .and_then(|v| v.as_str())
         .map(|s| s.to_string());
 
-    let data = extract_media_bytes(client, payload, &mut mimetype).await?;
+    let data = extract_media(client, payload, &mut mimetype, media)
+        .await?
+        .into_bytes()
+        .await?;
+    // Voice notes are transcoded to ogg/opus unless the caller opts out with `encoding: false`.
+    let encoding = payload
+        .get("encoding")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(true);
+
+    let (data, seconds, waveform) = if ptt && encoding {
+        let input = bytes::Bytes::from(data);
+        match audio::transcode_to_ptt(&media.thumbnails.ffmpeg, input.clone()).await {
+            Ok(converted) => {
+                mimetype = Some(audio::PTT_MIMETYPE.to_string());
+                (converted.data, Some(converted.seconds), Some(converted.waveform))
+            }
+            Err(err) => {
+                log::warn!("PTT transcoding failed, sending original audio: {err}");
+                (input.to_vec(), None, None)
+            }
+        }
+    } else {
+        (data, None, None)
+    };
+
     let upload = client.upload(data, MediaType::Audio).await?;
     let context_info = build_reply_context_info(payload);
 
     Ok(wa::Message {
         audio_message: Some(Box::new(wa::message::AudioMessage {
             mimetype,
+            seconds,
+            waveform,
             url: Some(upload.url),
             direct_path: Some(upload.direct_path),
             media_key: Some(upload.media_key),
@@ -477,26 +1102,30 @@ async fn build_audio_message(
     })
 }
 
-async fn build_document_message(client: &Client, payload: &Value) -> anyhow::Result<wa::Message> {
+async fn build_document_message(
+    client: &Client,
+    payload: &Value,
+    media: MediaOptions<'_>,
+) -> anyhow::Result<wa::Message> {
     let caption = payload
         .get("caption")
         .and_then(|v| v.as_str())
         .map(|s| s.to_string());
 
+    let mut mimetype = payload
+        .get("mimetype")
+        .and_then(|v| v.as_str())
+        .map(|s| s.to_string());
+
+    let input = extract_media(client, payload, &mut mimetype, media).await?;
     let filename = payload
         .get("filename")
         .or_else(|| payload.get("fileName"))
         .and_then(|v| v.as_str())
         .map(|s| s.to_string())
+        .or_else(|| input.file_name())
         .unwrap_or_else(|| "file".to_string());
-
-    let mut mimetype = payload
-        .get("mimetype")
-        .and_then(|v| v.as_str())
-        .map(|s| s.to_string());
-
-    let data = extract_media_bytes(client, payload, &mut mimetype).await?;
-    let upload = client.upload(data, MediaType::Document).await?;
+    let upload = input.upload(client, MediaType::Document).await?;
     let context_info = build_reply_context_info(payload);
 
     Ok(wa::Message {
@@ -517,7 +1146,11 @@ async fn build_document_message(client: &Client, payload: &Value) -> anyhow::Res
     })
 }
 
-async fn build_sticker_message(client: &Client, payload: &Value) -> anyhow::Result<wa::Message> {
+async fn build_sticker_message(
+    client: &Client,
+    payload: &Value,
+    media: MediaOptions<'_>,
+) -> anyhow::Result<wa::Message> {
     let mut mimetype = payload
         .get("mimetype")
         .and_then(|v| v.as_str())
@@ -528,7 +1161,10 @@ async fn build_sticker_message(client: &Client, payload: &Value) -> anyhow::Resu
         .or_else(|| payload.get("is_animated"))
         .and_then(|v| v.as_bool());
 
-    let data = extract_media_bytes(client, payload, &mut mimetype).await?;
+    let data = extract_media(client, payload, &mut mimetype, media)
+        .await?
+        .into_bytes()
+        .await?;
     let upload = client.upload(data, MediaType::Sticker).await?;
     let context_info = build_reply_context_info(payload);
     let mimetype = mimetype.or_else(|| Some("image/webp".to_string()));
@@ -550,11 +1186,93 @@ async fn build_sticker_message(client: &Client, payload: &Value) -> anyhow::Resu
     })
 }
 
-async fn extract_media_bytes(
+/// Media of a queued message: decoded in memory, or a file spooled by
+/// `/media/upload` that is encrypted from disk.
+enum MediaInput {
+    Bytes(Vec<u8>),
+    File {
+        path: PathBuf,
+        file_name: Option<String>,
+    },
+}
+
+impl MediaInput {
+    fn file_name(&self) -> Option<String> {
+        match self {
+            Self::Bytes(_) => None,
+            Self::File { file_name, .. } => file_name.clone(),
+        }
+    }
+
+    /// For media that is transcoded or inspected before the upload.
+    async fn into_bytes(self) -> anyhow::Result<Vec<u8>> {
+        match self {
+            Self::Bytes(data) => Ok(data),
+            Self::File { path, .. } => Ok(tokio::fs::read(path).await?),
+        }
+    }
+
+    /// `jpegThumbnail` of an image or video; `None` when thumbnails are off
+    /// or one cannot be made, so the message is still sent.
+    async fn thumbnail(
+        &self,
+        media_type: MediaType,
+        config: &ThumbnailConfig,
+    ) -> Option<Thumbnail> {
+        let max_size = config.max_size?;
+        let source = match self {
+            Self::Bytes(data) => thumbnails::Source::Bytes(data.clone()),
+            Self::File { path, .. } => thumbnails::Source::File(path.clone()),
+        };
+        let result = match media_type {
+            MediaType::Video => thumbnails::video_thumbnail(source, &config.ffmpeg, max_size).await,
+            _ => thumbnails::image_thumbnail(source, max_size).await,
+        };
+        match result {
+            Ok(thumbnail) => Some(thumbnail),
+            Err(err) if err.is_unavailable() => {
+                log::debug!("Skipping video thumbnail, ffmpeg is not available: {err}");
+                None
+            }
+            Err(err) => {
+                log::warn!("Failed to make media thumbnail: {err}");
+                None
+            }
+        }
+    }
+
+    async fn upload(
+        self,
+        client: &Client,
+        media_type: MediaType,
+    ) -> anyhow::Result<UploadResponse> {
+        match self {
+            Self::Bytes(data) => client.upload(data, media_type).await,
+            Self::File { path, .. } => client.upload_file(&path, media_type).await,
+        }
+    }
+}
+
+/// Reads the media of `payload` from `mediaId`, `base64` or `url`, filling
+/// `mimetype` when the source tells it.
+async fn extract_media(
     client: &Client,
     payload: &Value,
     mimetype: &mut Option<String>,
-) -> anyhow::Result<Vec<u8>> {
+    media: MediaOptions<'_>,
+) -> anyhow::Result<MediaInput> {
+    if let Some(media_id) = payload.get("mediaId").and_then(|v| v.as_str()) {
+        let (upload, path) = uploads::find(media.uploads, media.session, media_id).await?;
+        check_media_limit(upload.size, media.limit)?;
+        if mimetype.is_none() {
+            *mimetype = upload.mimetype;
+        }
+        return Ok(MediaInput::File {
+            path,
+            file_name: upload.file_name,
+        });
+    }
+
     let base64_input = payload.get("base64").and_then(|v| v.as_str());
     let url_input = payload.get("url").and_then(|v| v.as_str());
 
@@ -576,13 +1294,25 @@ async fn extract_media_bytes(
         }
         response.body
     } else {
-        return Err(anyhow::anyhow!("missing url or base64"));
+        return Err(anyhow::anyhow!("missing mediaId, url or base64"));
     };
 
-    Ok(data)
+    check_media_limit(data.len() as u64, media.limit)?;
+    Ok(MediaInput::Bytes(data))
 }
 
-fn split_data_url(input: &str) -> (Option<String>, &str) {
+fn check_media_limit(size: u64, limit: Option<u64>) -> anyhow::Result<()> {
+    match limit {
+        Some(limit) if size > limit => Err(anyhow::anyhow!(
+            "media is {} bytes, over the {} bytes quota",
+            size,
+            limit
+        )),
+        _ => Ok(()),
+    }
+}
+
+pub(crate) fn split_data_url(input: &str) -> (Option<String>, &str) {
     let Some(rest) = input.strip_prefix("data:") else {
         return (None, input);
     };
@@ -599,3 +1329,8 @@ fn split_data_url(input: &str) -> (Option<String>, &str) {
 
     (mime, data)
 }
+
+#[cfg(test)]
+mod tests {
+    include!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/tests/server/messages_worker_tests.rs"));
+}
@@ -1,41 +1,218 @@
 use crate::api_store::ApiStore;
 use axum::{
     Router,
-    extract::{Form, State},
+    extract::{ConnectInfo, DefaultBodyLimit, Form, State},
     http::{StatusCode, header},
     middleware,
     response::{Html, IntoResponse, Response},
-    routing::{get, post},
+    routing::{delete, get, post, put},
 };
 use base64::{Engine as _, engine::general_purpose};
 use chrono::{DateTime, Utc};
 use dashmap::DashMap;
-use image::Luma;
-use qrcode::QrCode;
 use sha2::{Digest, Sha256};
-use std::{collections::HashSet, sync::Arc};
+use std::{collections::HashSet, net::SocketAddr, sync::Arc};
 use tokio::sync::{RwLock, mpsc};
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
 use tower_http::trace::{DefaultMakeSpan, DefaultOnResponse, TraceLayer};
 use tracing::Level;
 
+pub mod api_keys;
+pub mod audio;
+pub mod audit;
+pub mod auto_rules;
+pub mod body;
+pub mod cache;
+#[cfg(feature = "chaos")]
+pub mod chaos;
+pub mod chat_settings;
+pub mod cloud_api;
+pub mod connection;
+pub mod contact_sync;
+pub mod deadletter;
+pub mod dedup;
+pub mod ephemeral;
+#[cfg(feature = "sentry")]
+pub mod error_reporting;
+pub mod event_bus;
+pub mod event_filters;
+pub mod event_history;
+pub mod events;
+pub mod evolution;
+pub mod exports;
+#[cfg(feature = "graphql")]
+pub mod graphql;
 pub mod handlers;
+pub mod health;
+pub mod http_client;
+pub mod inbound;
+pub mod instance_deletion;
+pub mod instance_fingerprint;
+pub mod instance_logs;
+pub mod instance_meta;
+pub mod jid;
+pub mod link_preview;
+pub mod logging;
+pub mod maintenance;
+pub mod media;
+pub mod message_counters;
+pub mod message_status;
+#[cfg(feature = "nats")]
+pub mod nats;
+pub mod numbers;
+pub mod outbox;
+pub mod pagination;
+pub mod participants;
+pub mod preflight;
+pub mod profile_pictures;
 pub mod messages_worker;
+pub mod qr;
+pub mod quotas;
+pub mod reactions;
+pub mod retention;
 pub mod routes;
+pub mod runtime_config;
+pub mod session_events;
+pub mod sink_scope;
+pub mod sse;
+pub mod standby;
+pub mod static_files;
+pub mod status;
+pub mod supervisor;
+pub mod tls;
+pub mod templates;
+pub mod thumbnails;
+pub mod timeouts;
+pub mod uploads;
+pub mod versioning;
 pub mod webhooks;
 pub mod queue;
+pub mod workspaces;
+pub mod ws;
 
 pub struct AppState {
     pub instances: DashMap<String, InstanceState>,
     pub sessions_runtime: DashMap<String, SessionRuntime>,
     pub api_store: Arc<dyn ApiStore>,
+    /// Master keys sealing webhook secrets and Cloud API tokens in
+    /// `api_sessions` (`SECRETS_MASTER_KEY`); `None` keeps them in plaintext.
+    pub secrets: Option<warp_core::store::secrets::Keyring>,
+    /// Storage engine, reported by `/manager/status`.
+    pub database_provider: crate::config::DatabaseProvider,
+    pub started_at: DateTime<Utc>,
     pub clients: DashMap<String, Arc<crate::client::Client>>,
     pub settings: Arc<RwLock<Settings>>,
     pub api_password_hash: Option<[u8; 32]>,
     pub session_ttl_seconds: u64,
+    /// Whether API key allowlists check `X-Forwarded-For`
+    /// (`AUTH_TRUST_FORWARDED_FOR`).
+    pub auth_trust_forwarded_for: bool,
     pub message_notify: mpsc::Sender<()>,
+    /// Wakes the [`outbox`] dispatcher after events are written.
+    pub outbox_notify: mpsc::Sender<()>,
     /// In-memory cache for webhook configs to avoid DB queries on every message.
     /// Key: instance name, Value: (cached config, timestamp of cache entry).
     pub webhook_config_cache: DashMap<String, (Option<crate::models::webhook_model::WebhookConfig>, std::time::Instant)>,
+    /// Tags and metadata per instance, copied into every event envelope.
+    pub instance_meta_cache: DashMap<String, (instance_meta::InstanceMeta, std::time::Instant)>,
+    /// Auto-read and auto-reply rules per instance, checked on every
+    /// incoming message.
+    pub auto_rules_cache: DashMap<String, (Vec<auto_rules::AutoRule>, std::time::Instant)>,
+    /// Settings editable through `/manager/config`.
+    pub runtime_config: Arc<std::sync::RwLock<runtime_config::RuntimeConfig>>,
+    pub rate_limiter: runtime_config::RateLimiter,
+    pub log_level_reloader: Option<runtime_config::LogLevelReloader>,
+    /// Events fanned out to `/ws` clients.
+    pub event_hub: ws::EventHub,
+    /// Instances the global webhook and `/ws` deliver for.
+    pub global_sinks: sink_scope::InstanceScope,
+    /// Events streamed to `/events/sse` clients, with a resume history.
+    pub sse: sse::SseHub,
+    /// Recent events per instance, served by `/events/history/:instance`.
+    pub event_history: event_history::EventHistory,
+    /// Graph API endpoint and `/webhook/meta` secrets for Cloud API instances.
+    pub meta: cloud_api::MetaConfig,
+    /// Recent `/chat/whatsappNumbers` answers.
+    pub number_cache: numbers::NumberCache,
+    /// Caching of `/chat/fetchProfilePictureUrl` answers.
+    pub profile_pictures: profile_pictures::ProfilePictureConfig,
+    /// Instance listings, group lists and profile pictures, in memory and
+    /// optionally Redis.
+    pub cache: cache::HotCache,
+    /// Messages sent, received and failed per instance since start.
+    pub message_counters: Arc<message_counters::MessageCounters>,
+    /// Recently received message keys, to drop WhatsApp redeliveries.
+    pub inbound_dedup: dedup::InboundDedup,
+    /// How long messages, webhook logs and media are kept.
+    pub retention: retention::RetentionConfig,
+    /// Rows and bytes the retention pruner reclaimed since start.
+    pub retention_metrics: Arc<retention::RetentionMetrics>,
+    /// Pooled outbound HTTP client with retries and circuit breaking.
+    pub http: http_client::SharedHttpClient,
+    /// Most bytes a middleware buffers to inspect a request body.
+    pub body_limit: usize,
+    /// Where `/media/upload` spools request bodies.
+    pub uploads: uploads::UploadConfig,
+    /// Size of the `jpegThumbnail` of outgoing images and videos.
+    pub thumbnails: thumbnails::ThumbnailConfig,
+    /// Time budget of the HTTP requests of each route group.
+    pub timeouts: timeouts::TimeoutConfig,
+    /// Where `/chat/export/:instance` writes files and how downloads are signed.
+    pub exports: exports::ExportConfig,
+    /// Small files served from disk, see [`static_files::serve_file`].
+    pub file_cache: static_files::FileCache,
+    /// `/api/v1` mounting and whether legacy root paths are served.
+    pub api_mount: versioning::ApiMount,
+    /// Critical dependencies and timeouts of the health checks.
+    pub health: health::HealthConfig,
+    /// Recent log entries per instance, tailed by `/instance/logs/:name`.
+    pub instance_logs: instance_logs::InstanceLogs,
+    /// Default country code and rules for numbers in request bodies.
+    pub participants: participants::ParticipantConfig,
+    /// Limit on simultaneous connection attempts, shared by every instance.
+    pub handshake_gate: Arc<crate::client::HandshakeGate>,
+    /// Instance lease and whether this node is primary or standby.
+    pub standby: standby::Standby,
+    /// How often and how fast a crashed runner is restarted.
+    pub restart_policy: supervisor::RestartPolicy,
+    /// Sentry hub of each instance.
+    #[cfg(feature = "sentry")]
+    pub sentry_hubs: error_reporting::InstanceHubs,
+    /// Set when `NATS_ENABLED` is on and the sink started.
+    #[cfg(feature = "nats")]
+    pub nats: Option<nats::NatsSink>,
+}
+
+impl AppState {
+    /// Snapshot of the current runtime config.
+    pub fn runtime_config(&self) -> runtime_config::RuntimeConfig {
+        self.runtime_config
+            .read()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Seals a secret before it is written to `api_sessions`.
+    pub fn seal_secret(&self, value: Option<String>) -> anyhow::Result<Option<String>> {
+        match (&self.secrets, value) {
+            (Some(keyring), Some(value)) if !value.is_empty() => {
+                Ok(Some(keyring.seal_text(&value)?))
+            }
+            (_, value) => Ok(value),
+        }
+    }
+
+    /// Opens a secret read from `api_sessions`; values written before
+    /// encryption was enabled are returned as they are.
+    pub fn open_secret(&self, value: &str) -> anyhow::Result<String> {
+        match &self.secrets {
+            Some(keyring) => Ok(keyring.open_text(value)?),
+            None if warp_core::store::secrets::is_sealed_text(value) => {
+                anyhow::bail!("secret is encrypted but SECRETS_MASTER_KEY is not set")
+            }
+            None => Ok(value.to_string()),
+        }
+    }
 }
 
 #[derive(Clone, Debug, Default)]
@@ -77,7 +254,8 @@ impl Settings {
 pub struct InstanceState {
     pub qr_code: Arc<RwLock<Option<String>>>,
     pub qr_count: Arc<RwLock<u32>>,
-    pub connection_state: Arc<RwLock<String>>,
+    /// Connection state machine with its recent transitions.
+    pub connection_state: Arc<RwLock<connection::ConnectionStatus>>,
 }
 
 #[derive(Clone, Debug)]
@@ -86,6 +264,10 @@ pub struct SessionRuntime {
     pub qr_code: Option<String>,
     pub pair_code: Option<String>,
     pub last_seen: Option<DateTime<Utc>>,
+    /// Times the supervisor restarted the instance runner after a panic.
+    pub runner_restarts: u32,
+    /// Picture of the connected account, looked up after connecting.
+    pub profile_pic_url: Option<String>,
 }
 
 impl SessionRuntime {
@@ -95,6 +277,8 @@ impl SessionRuntime {
             qr_code: None,
             pair_code: None,
             last_seen: None,
+            runner_restarts: 0,
+            profile_pic_url: None,
         }
     }
 }
@@ -104,7 +288,7 @@ impl InstanceState {
         Self {
             qr_code: Arc::new(RwLock::new(None)),
             qr_count: Arc::new(RwLock::new(0)),
-            connection_state: Arc::new(RwLock::new("disconnected".to_string())),
+            connection_state: Arc::new(RwLock::new(connection::ConnectionStatus::default())),
         }
     }
 }
@@ -117,58 +301,309 @@ pub fn create_router(state: Arc<AppState>) -> Router<()> {
         .route("/auth/logout", post(logout_handler))
         .route("/healthz", get(health_handler))
         .route("/readyz", get(ready_handler))
+        .route("/healthz/deep", get(deep_health_handler))
         .route("/openapi.json", get(handlers::openapi_handler))
         .route("/docs/openapi.json", get(handlers::openapi_handler))
         .route("/swagger", get(handlers::swagger_handler))
         .route("/docs/swagger", get(handlers::swagger_handler))
         .route("/metrics", get(handlers::metrics_handler))
+        .route(
+            "/metrics/prometheus",
+            get(message_counters::prometheus_handler),
+        )
+        .route("/events/schema", get(handlers::event_schema_handler))
+        .route("/ws", get(ws::ws_handler))
+        .route("/events/sse", get(sse::sse_handler))
+        .route("/events/sse/:instance", get(sse::instance_sse_handler))
+        .route(
+            "/events/history/:instance",
+            get(event_history::history_handler),
+        )
         .route("/settings/events", get(get_events_settings))
         .route("/settings/toggle-event", post(toggle_event))
         // Instance routes
         .route("/instance/create", post(handlers::create_instance))
-        .route("/instance/delete/:name", get(handlers::delete_instance)) // Should be DELETE, but ROUTES.md says DELETE
+        .route(
+            "/instance/delete/:name",
+            get(handlers::delete_instance).delete(handlers::delete_instance),
+        )
         .route(
             "/instance/connectionState/:name",
             get(handlers::connection_state),
         )
         .route("/instance/connect/:name", get(handlers::connect_instance))
+        .route("/instance/fetchInstances", get(handlers::fetch_instances))
+        .route(
+            "/instance/metadata/:name",
+            put(handlers::set_instance_metadata),
+        )
+        .route(
+            "/instance/rules/:name",
+            get(handlers::get_auto_rules).put(handlers::set_auto_rules),
+        )
+        .route(
+            "/instance/maintenance/:name",
+            put(handlers::set_maintenance_window).delete(handlers::clear_maintenance_window),
+        )
+        .route(
+            "/instance/events/:name",
+            get(handlers::get_event_filters).put(handlers::set_event_filters),
+        )
+        .route(
+            "/instance/retention/:name",
+            get(handlers::get_retention)
+                .put(handlers::set_retention)
+                .delete(handlers::clear_retention),
+        )
+        .route(
+            "/instance/inbound/:name",
+            get(handlers::get_inbound_hook)
+                .put(handlers::set_inbound_hook)
+                .delete(handlers::delete_inbound_hook),
+        )
         .route("/instance/:name/state", get(handlers::instance_state))
+        .route("/instance/qrcode/:file", get(handlers::instance_qrcode))
+        .route(
+            "/instance/diagnostics/:name",
+            get(handlers::instance_diagnostics),
+        )
+        .route(
+            "/instance/logs/:name",
+            get(instance_logs::stream_instance_logs),
+        )
+        .route(
+            "/instance/version/:name",
+            get(handlers::get_instance_version).put(handlers::set_instance_version),
+        )
+        .route(
+            "/instance/devices/:name",
+            get(handlers::list_instance_devices),
+        )
+        .route(
+            "/instance/devices/:name/:device",
+            delete(handlers::remove_instance_device),
+        )
+        .route(
+            "/manager/config",
+            get(handlers::get_manager_config).patch(handlers::patch_manager_config),
+        )
+        .route("/manager/audit", get(handlers::get_manager_audit))
+        .route("/manager/quotas", get(handlers::get_manager_quotas))
+        .route("/manager/status", get(handlers::get_manager_status))
+        .route("/manager/promote", post(handlers::promote_manager))
+        // Webhook routes
+        .route(
+            "/webhook/meta",
+            get(cloud_api::verify_webhook).post(cloud_api::receive_webhook),
+        )
+        .route("/integration/inbound/:instance", post(inbound::receive))
+        .route(
+            "/webhook/deliveries/:instance",
+            get(handlers::get_webhook_deliveries),
+        )
+        .route(
+            "/webhook/redeliver/:delivery_id",
+            post(handlers::redeliver_webhook),
+        )
+        // Dead-letter routes
+        .route(
+            "/events/deadletter/:instance",
+            get(handlers::get_deadletters),
+        )
+        .route(
+            "/events/deadletter/:instance/replay",
+            post(handlers::replay_deadletters),
+        )
         // Message routes
         .route(
             "/message/:operation/:instance_name",
             post(handlers::send_message),
         )
+        .route(
+            "/message/status/:instance_name/:message_id",
+            get(handlers::message_status),
+        )
+        // Streamed to disk, so the default body limit does not apply.
+        .route(
+            "/media/upload/:instance_name",
+            post(handlers::upload_media).layer(DefaultBodyLimit::disable()),
+        )
+        .route(
+            "/media/upload/:instance_name/:media_id",
+            get(handlers::download_upload),
+        )
         // Chat routes
         .route(
             "/chat/findMessages/:instance_name",
             post(handlers::find_messages),
         )
         .route("/chat/findChats/:instance_name", get(handlers::find_chats))
+        .route(
+            "/chat/whatsappNumbers/:instance_name",
+            post(handlers::whatsapp_numbers),
+        )
+        .route(
+            "/chat/syncContacts/:instance_name",
+            post(handlers::sync_contacts),
+        )
+        .route("/chat/export/:instance_name", post(handlers::export_chat))
+        .route("/jobs/:id", get(handlers::get_job))
+        .route("/jobs/:id/download", get(handlers::download_job))
+        .route(
+            "/chat/fetchProfilePictureUrl/:instance_name",
+            post(handlers::fetch_profile_picture_url),
+        )
+        .route("/chat/archiveChat/:instance_name", post(handlers::archive_chat))
+        .route(
+            "/chat/markChatUnread/:instance_name",
+            post(handlers::mark_chat_unread),
+        )
+        .route("/chat/pinChat/:instance_name", post(handlers::pin_chat))
+        .route("/chat/muteChat/:instance_name", post(handlers::mute_chat))
+        .route(
+            "/chat/toggleEphemeral/:instance_name",
+            post(handlers::toggle_chat_ephemeral),
+        )
+        .route(
+            "/chat/getBase64FromMediaMessage/:instance_name",
+            post(handlers::get_base64_from_media_message),
+        )
+        // Business routes
+        .route(
+            "/business/getCatalog/:instance_name",
+            post(handlers::get_business_catalog),
+        )
+        .route(
+            "/business/getCollections/:instance_name",
+            post(handlers::get_business_collections),
+        )
+        .route(
+            "/business/fetchBusinessProfile/:instance_name",
+            post(handlers::fetch_business_profile),
+        )
+        .route(
+            "/business/updateBusinessProfile/:instance_name",
+            post(handlers::update_business_profile),
+        )
         // Group routes
         .route("/group/create/:instance_name", post(handlers::create_group))
         .route(
             "/group/fetchAllGroups/:instance_name",
             get(handlers::fetch_groups),
         )
+        .route(
+            "/group/inviteInfo/:instance_name",
+            get(handlers::group_invite_info),
+        )
+        .route(
+            "/group/acceptInviteCode/:instance_name",
+            get(handlers::accept_group_invite),
+        )
+        .route(
+            "/group/updateSetting/:instance_name",
+            post(handlers::update_group_setting),
+        )
+        .route(
+            "/group/toggleEphemeral/:instance_name",
+            post(handlers::toggle_group_ephemeral),
+        )
+        .route(
+            "/group/updateMemberAddMode/:instance_name",
+            post(handlers::update_group_member_add_mode),
+        );
+
+    #[cfg(feature = "chaos")]
+    let router = router.merge(chaos::router());
+    #[cfg(feature = "graphql")]
+    let router = router.merge(graphql::router());
+
+    let router = router
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            timeouts::timeout_middleware,
+        ))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            participants::normalize_middleware,
+        ))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            workspaces::scope_guard,
+        ))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            audit::audit_middleware,
+        ))
         .with_state(state.clone());
 
     let router = if state.api_password_hash.is_some() {
-        router.layer(middleware::from_fn_with_state(state, auth_middleware))
+        router.layer(middleware::from_fn_with_state(state.clone(), auth_middleware))
     } else {
         router
     };
 
-    router.layer(
-        TraceLayer::new_for_http()
-            .make_span_with(DefaultMakeSpan::new().level(Level::INFO))
-            .on_response(DefaultOnResponse::new().level(Level::INFO)),
-    )
+    let runtime_config = state.runtime_config.clone();
+    let cors = CorsLayer::new()
+        .allow_origin(AllowOrigin::predicate(move |origin, parts| {
+            let Ok(origin) = origin.to_str() else {
+                return false;
+            };
+            runtime_config
+                .read()
+                .unwrap_or_else(std::sync::PoisonError::into_inner)
+                .allows_origin(runtime_config::CorsGroup::for_path(parts.uri.path()), origin)
+        }))
+        .allow_methods(Any)
+        .allow_headers(Any);
+
+    let api_mount = state.api_mount;
+    let api = router
+        .layer(middleware::from_fn_with_state(state, rate_limit_middleware))
+        .layer(cors);
+
+    // The mount middleware rewrites `/api/v1/...` before the routes match.
+    Router::new()
+        .fallback_service(api)
+        .layer(middleware::from_fn_with_state(
+            api_mount,
+            versioning::mount_middleware,
+        ))
+        .layer(
+            TraceLayer::new_for_http()
+                .make_span_with(DefaultMakeSpan::new().level(Level::INFO))
+                .on_response(DefaultOnResponse::new().level(Level::INFO)),
+        )
 }
 
-async fn auth_middleware(
+async fn rate_limit_middleware(
     State(state): State<Arc<AppState>>,
     req: axum::http::Request<axum::body::Body>,
     next: middleware::Next,
+) -> Response {
+    let path = req.uri().path();
+    if matches!(
+        path,
+        "/healthz" | "/healthz/deep" | "/readyz" | "/metrics" | "/metrics/prometheus"
+    ) {
+        return next.run(req).await;
+    }
+
+    let limit = state.runtime_config().rate_limit_per_minute;
+    if state.rate_limiter.check(limit) {
+        next.run(req).await
+    } else {
+        (
+            StatusCode::TOO_MANY_REQUESTS,
+            axum::Json(serde_json::json!({"error": "rate_limited"})),
+        )
+            .into_response()
+    }
+}
+
+async fn auth_middleware(
+    State(state): State<Arc<AppState>>,
+    mut req: axum::http::Request<axum::body::Body>,
+    next: middleware::Next,
 ) -> Response {
     let Some(expected_hash) = state.api_password_hash else {
         return next.run(req).await;
@@ -178,25 +613,31 @@ async fn auth_middleware(
     if path == "/auth/login"
         || path == "/auth/logout"
         || path == "/healthz"
+        || path == "/healthz/deep"
         || path == "/readyz"
         || path == "/health"
         || path == "/ping"
         || path == "/metrics"
+        || path == "/metrics/prometheus"
         || path == "/openapi.json"
         || path == "/docs/openapi.json"
+        || path == "/events/schema"
         || path == "/swagger"
         || path == "/docs/swagger"
+        || path == "/webhook/meta"
+        || exports::is_download_path(path)
+        || inbound::is_receiver_path(path)
     {
         return next.run(req).await;
     }
 
     let headers = req.headers();
-    if let Some(cookie) = get_cookie(headers, "chatwarp_auth") {
-        if let Some(cookie_hash) = parse_hex_32(&cookie) {
-            if constant_time_eq_bytes(&cookie_hash, &expected_hash) {
-                return next.run(req).await;
-            }
-        }
+    let cookie_ok = get_cookie(headers, "chatwarp_auth")
+        .and_then(|cookie| parse_hex_32(&cookie))
+        .is_some_and(|cookie_hash| constant_time_eq_bytes(&cookie_hash, &expected_hash));
+    if cookie_ok {
+        req.extensions_mut().insert(workspaces::Scope::Admin);
+        return next.run(req).await;
     }
     let header_password = headers
         .get("x-chatwarp-password")
@@ -206,17 +647,47 @@ async fn auth_middleware(
         .and_then(|v| v.to_str().ok())
         .and_then(|v| v.strip_prefix("Bearer "));
 
-    let provided = header_password.or(bearer_password);
-    let authorized = provided
-        .map(|p| hash_password(p))
-        .map(|h| constant_time_eq_bytes(&h, &expected_hash))
-        .unwrap_or(false);
+    let provided = header_password.or(bearer_password).map(str::to_string);
+    let key = match provided {
+        Some(key) if constant_time_eq_bytes(&hash_password(&key), &expected_hash) => {
+            req.extensions_mut().insert(workspaces::Scope::Admin);
+            return next.run(req).await;
+        }
+        Some(key) => match api_keys::resolve(&state, &key).await {
+            Ok(key) => key,
+            Err(e) => {
+                tracing::warn!(error = %e, "API key lookup failed");
+                None
+            }
+        },
+        None => None,
+    };
+    let Some(key) = key else {
+        return (StatusCode::UNAUTHORIZED, Html(login_html())).into_response();
+    };
 
-    if authorized {
-        next.run(req).await
-    } else {
-        (StatusCode::UNAUTHORIZED, Html(login_html())).into_response()
+    let peer = req
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|info| info.0);
+    let ip = api_keys::client_ip(req.headers(), peer, state.auth_trust_forwarded_for);
+    if !key.allows_ip(ip) {
+        return (
+            StatusCode::FORBIDDEN,
+            axum::Json(serde_json::json!({"error": "ip_not_allowed"})),
+        )
+            .into_response();
     }
+
+    if let Some(response) = key.forbidden(req.method(), req.uri().path()) {
+        return response;
+    }
+
+    req.extensions_mut().insert(
+        key.workspace
+            .map_or(workspaces::Scope::Admin, workspaces::Scope::Workspace),
+    );
+    next.run(req).await
 }
 
 fn constant_time_eq_bytes(a: &[u8; 32], b: &[u8; 32]) -> bool {
@@ -406,20 +877,18 @@ async fn root_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
         let name = entry.key();
         let qr = entry.value().qr_code.read().await;
         if let Some(code) = qr.as_ref() {
-            // Generate QR image
-            if let Ok(qr_obj) = QrCode::new(code.as_bytes()) {
-                let img = qr_obj.render::<Luma<u8>>().build();
-                let mut buffer = std::io::Cursor::new(Vec::new());
-                if img.write_to(&mut buffer, image::ImageFormat::Png).is_ok() {
-                    let base64_img = general_purpose::STANDARD.encode(buffer.get_ref());
-                    qr_html.push_str(&format!(
-                        "<h2>Instance: {}</h2><img src=\"data:image/png;base64,{}\" style=\"width: 300px; height: 300px;\">",
-                        name, base64_img
-                    ));
-                    found = true;
-                    break;
-                }
-            }
+            let rendered = qr::render_qr_with_fallback(code, qr::QrRenderOptions::default());
+            let body = match rendered.format {
+                qr::QrFormat::Png => format!(
+                    "<img src=\"data:image/png;base64,{}\" style=\"width: 300px; height: 300px;\">",
+                    general_purpose::STANDARD.encode(&rendered.body)
+                ),
+                qr::QrFormat::Svg => String::from_utf8_lossy(&rendered.body).into_owned(),
+                _ => format!("<pre>{}</pre>", String::from_utf8_lossy(&rendered.body)),
+            };
+            qr_html.push_str(&format!("<h2>Instance: {}</h2>{}", name, body));
+            found = true;
+            break;
         }
     }
 
@@ -531,10 +1000,99 @@ async fn toggle_event(
     axum::Json(serde_json::json!({"ok": true}))
 }
 
-async fn health_handler() -> impl IntoResponse {
-    (StatusCode::OK, "{\"ok\": true}")
+/// Liveness: always `200` while the process serves requests, with the
+/// dependency report in the body.
+async fn health_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let report = health::check(&state).await;
+    (
+        StatusCode::OK,
+        axum::Json(serde_json::json!({
+            "ok": report.ready(),
+            "status": report.status,
+            "dependencies": report.dependencies,
+        })),
+    )
 }
 
-async fn ready_handler() -> impl IntoResponse {
-    (StatusCode::OK, "{\"ok\": true}")
+/// Not ready while maintenance mode is on, so load balancers drain the
+/// pod (sockets stay connected), or while a critical dependency is down.
+async fn ready_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    if state.runtime_config().maintenance_mode {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            axum::Json(serde_json::json!({"ok": false, "maintenance": true})),
+        );
+    }
+    let report = health::check(&state).await;
+    let status = if report.ready() {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (
+        status,
+        axum::Json(serde_json::json!({
+            "ok": report.ready(),
+            "status": report.status,
+            "dependencies": report.dependencies,
+        })),
+    )
+}
+
+const DEEP_HEALTH_PING_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Runs the dependency checks and pings every connected WhatsApp session,
+/// each with a timeout. Instances that are not connected are reported but
+/// do not fail the probe.
+async fn deep_health_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let report = health::check(&state).await;
+    let mut healthy = report.ready();
+    let database = match report.dependency(health::DATABASE) {
+        Some(check) if check.status == health::DependencyStatus::Down => {
+            serde_json::json!({"ok": false, "error": check.error})
+        }
+        Some(check) => serde_json::json!({"ok": true, "latencyMs": check.latency_ms}),
+        None => serde_json::json!({"ok": false}),
+    };
+
+    let clients: Vec<_> = state
+        .clients
+        .iter()
+        .map(|entry| (entry.key().clone(), entry.value().clone()))
+        .collect();
+    let mut instances = serde_json::Map::new();
+    for (name, client) in clients {
+        let check = if !client.is_logged_in() {
+            serde_json::json!({"ok": true, "connected": false})
+        } else {
+            match client.ping(DEEP_HEALTH_PING_TIMEOUT).await {
+                Ok(rtt) => serde_json::json!({
+                    "ok": true,
+                    "connected": true,
+                    "latencyMs": rtt.as_millis(),
+                }),
+                Err(e) => {
+                    healthy = false;
+                    serde_json::json!({"ok": false, "connected": true, "error": e.to_string()})
+                }
+            }
+        };
+        instances.insert(name, check);
+    }
+
+    let status = if healthy {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (
+        status,
+        axum::Json(serde_json::json!({
+            "ok": healthy,
+            "status": report.status,
+            "dependencies": report.dependencies,
+            "database": database,
+            "instances": instances,
+        })),
+    )
 }
@@ -0,0 +1,263 @@
+//! NATS event sink (`nats` feature).
+//!
+//! Events dispatched from the [`outbox`](super::outbox) are published to
+//! `{NATS_SUBJECT_PREFIX}.{instance}.{event}` for instances with NATS on,
+//! and with `NATS_GLOBAL_ENABLED` also to
+//! `{NATS_GLOBAL_SUBJECT_PREFIX}.{event}.{instance}` for every instance in
+//! the global sink scope ([`sink_scope`](super::sink_scope)), through
+//! JetStream when `NATS_JETSTREAM` is set. The two subject trees are
+//! independent: an event can go to both. Publishing runs on its own task
+//! behind a bounded channel so a slow or unreachable server never blocks
+//! the caller; the client reconnects on its own and events arriving while
+//! the channel is full are dropped with a warning and counted. Envelopes
+//! are serialized only once a subject takes them.
+
+use crate::api_store::{ApiBind, ApiStore};
+use crate::server::event_bus::{BusEvent, SinkCounts, SinkStats};
+use crate::server::webhooks::event_allowed;
+use dashmap::DashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use tracing::{debug, info, warn};
+
+pub use crate::config::NatsConfig;
+
+/// Events waiting to be published before new ones are dropped.
+const CHANNEL_CAPACITY: usize = 1024;
+const CONFIG_CACHE_TTL: Duration = Duration::from_secs(30);
+/// Instance token used for events not tied to an instance.
+const GLOBAL_INSTANCE: &str = "global";
+
+/// `{prefix}.{instance}.{event}` with every token made subject-safe
+/// (no `.`, wildcards or whitespace).
+pub fn subject(prefix: &str, instance: Option<&str>, event: &str) -> String {
+    format!(
+        "{}.{}.{}",
+        prefix,
+        token(
+            instance
+                .filter(|i| !i.is_empty())
+                .unwrap_or(GLOBAL_INSTANCE)
+        ),
+        token(event)
+    )
+}
+
+/// `{prefix}.{event}.{instance}`, the global subject: event first, so a
+/// consumer can take one event of every instance with
+/// `{prefix}.MESSAGES_UPSERT.*`.
+pub fn global_subject(prefix: &str, instance: Option<&str>, event: &str) -> String {
+    format!(
+        "{}.{}.{}",
+        prefix,
+        token(event),
+        token(
+            instance
+                .filter(|i| !i.is_empty())
+                .unwrap_or(GLOBAL_INSTANCE)
+        )
+    )
+}
+
+fn token(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| match c {
+            '.' | '*' | '>' => '_',
+            c if c.is_whitespace() => '_',
+            c => c,
+        })
+        .collect()
+}
+
+/// Handle kept in `AppState`.
+pub struct NatsSink {
+    tx: mpsc::Sender<Arc<BusEvent>>,
+    client: async_nats::Client,
+    stats: SinkStats,
+}
+
+impl NatsSink {
+    /// Connects (retrying in the background when the server is not up yet)
+    /// and starts the publisher task, which reads per-instance flags from
+    /// `api_sessions`.
+    pub async fn start(api_store: Arc<dyn ApiStore>, config: NatsConfig) -> anyhow::Result<Self> {
+        let client = async_nats::ConnectOptions::new()
+            .retry_on_initial_connect()
+            .event_callback(|event| async move {
+                match event {
+                    async_nats::Event::Connected => info!("NATS connected"),
+                    async_nats::Event::Disconnected => warn!("NATS disconnected, reconnecting"),
+                    other => debug!(event = %other, "NATS client event"),
+                }
+            })
+            .connect(&config.url)
+            .await?;
+
+        let jetstream = if config.jetstream {
+            let context = async_nats::jetstream::new(client.clone());
+            context
+                .get_or_create_stream(async_nats::jetstream::stream::Config {
+                    name: config.stream.clone(),
+                    subjects: stream_subjects(&config),
+                    ..Default::default()
+                })
+                .await?;
+            Some(context)
+        } else {
+            None
+        };
+
+        let (tx, rx) = mpsc::channel(CHANNEL_CAPACITY);
+        tokio::spawn(publish_loop(
+            api_store,
+            config,
+            client.clone(),
+            jetstream,
+            rx,
+        ));
+        Ok(Self {
+            tx,
+            client,
+            stats: SinkStats::default(),
+        })
+    }
+
+    /// Whether the client is connected to the server right now.
+    pub fn is_connected(&self) -> bool {
+        self.client.connection_state() == async_nats::connection::State::Connected
+    }
+
+    /// Events waiting in the publish queue.
+    pub fn queued(&self) -> usize {
+        CHANNEL_CAPACITY - self.tx.capacity()
+    }
+
+    /// Size of the publish queue.
+    pub fn capacity(&self) -> usize {
+        CHANNEL_CAPACITY
+    }
+
+    /// Events queued for publishing and dropped on a full queue.
+    pub fn stats(&self) -> SinkCounts {
+        self.stats.snapshot()
+    }
+
+    /// Queues an event for publishing; never waits.
+    pub fn publish(&self, event: &Arc<BusEvent>) {
+        if self.tx.try_send(event.clone()).is_ok() {
+            self.stats.record_queued();
+        } else {
+            self.stats.record_dropped(1);
+            warn!(event = %event.event, "NATS publish queue full, dropping event");
+        }
+    }
+}
+
+async fn publish_loop(
+    api_store: Arc<dyn ApiStore>,
+    config: NatsConfig,
+    client: async_nats::Client,
+    jetstream: Option<async_nats::jetstream::Context>,
+    mut rx: mpsc::Receiver<Arc<BusEvent>>,
+) {
+    let cache: DashMap<String, (Option<InstanceNats>, Instant)> = DashMap::new();
+
+    while let Some(outgoing) = rx.recv().await {
+        let instance = Some(outgoing.instance.as_str()).filter(|i| !i.is_empty());
+        let mut subjects = Vec::with_capacity(2);
+        if let Some(instance) = instance
+            && let Some(cfg) = instance_config(api_store.as_ref(), &cache, instance).await
+            && cfg.enabled
+            && event_allowed(&cfg.events, &outgoing.event)
+        {
+            subjects.push(subject(
+                &config.subject_prefix,
+                Some(instance),
+                &outgoing.event,
+            ));
+        }
+        if config.global && config.scope.includes(&outgoing.instance) {
+            subjects.push(global_subject(
+                &config.global_subject_prefix,
+                instance,
+                &outgoing.event,
+            ));
+        }
+        if subjects.is_empty() {
+            continue;
+        }
+
+        let payload = bytes::Bytes::from(outgoing.json().as_bytes().to_vec());
+        for subject in subjects {
+            let result = match &jetstream {
+                Some(context) => match context.publish(subject.clone(), payload.clone()).await {
+                    Ok(ack) => ack.await.map(|_| ()).map_err(anyhow::Error::from),
+                    Err(e) => Err(e.into()),
+                },
+                None => client
+                    .publish(subject.clone(), payload.clone())
+                    .await
+                    .map_err(anyhow::Error::from),
+            };
+            if let Err(e) = result {
+                warn!(subject = %subject, error = %e, "NATS publish failed");
+            }
+        }
+    }
+}
+
+/// Subjects of the JetStream stream: the instance subjects, plus the
+/// global ones when they are published.
+pub fn stream_subjects(config: &NatsConfig) -> Vec<String> {
+    let mut subjects = vec![format!("{}.>", config.subject_prefix)];
+    if config.global {
+        subjects.push(format!("{}.>", config.global_subject_prefix));
+    }
+    subjects
+}
+
+#[derive(Debug, Clone)]
+struct InstanceNats {
+    enabled: bool,
+    events: Option<Vec<String>>,
+}
+
+async fn instance_config(
+    api_store: &dyn ApiStore,
+    cache: &DashMap<String, (Option<InstanceNats>, Instant)>,
+    instance: &str,
+) -> Option<InstanceNats> {
+    if let Some(entry) = cache.get(instance)
+        && entry.1.elapsed() < CONFIG_CACHE_TTL
+    {
+        return entry.0.clone();
+    }
+
+    let row = api_store
+        .query_json(
+            "SELECT jsonb_build_object('enabled', nats_enabled, 'events', nats_events) as value \
+             FROM api_sessions WHERE session = $1",
+            vec![ApiBind::Text(instance.to_string())],
+        )
+        .await
+        .ok()
+        .and_then(|mut rows| rows.pop());
+    let config = row.map(|row| InstanceNats {
+        enabled: row["enabled"].as_bool().unwrap_or(false),
+        events: row["events"].as_array().map(|events| {
+            events
+                .iter()
+                .filter_map(|e| e.as_str().map(str::to_string))
+                .collect()
+        }),
+    });
+    cache.insert(instance.to_string(), (config.clone(), Instant::now()));
+    config
+}
+
+#[cfg(test)]
+mod tests {
+    include!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/tests/server/nats_tests.rs"));
+}
@@ -0,0 +1,182 @@
+//! `/chat/whatsappNumbers`: which phone numbers have a WhatsApp account.
+//!
+//! Numbers are checked with a usync `contact` query over the instance's
+//! connection. Answers are cached per instance so chatbots can validate
+//! every recipient before sending without repeating the IQ.
+
+use crate::client::Client;
+use crate::config::DEFAULT_NUMBERS_CACHE_TTL;
+use crate::features::IsOnWhatsAppResult;
+use crate::models::api_model::WhatsappNumbers;
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use warp_core_binary::jid::Jid;
+
+/// Numbers accepted per request.
+pub const MAX_NUMBERS: usize = 500;
+/// Numbers sent in a single usync query.
+const BATCH_SIZE: usize = 50;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum NumbersError {
+    #[error("numbers must be a non-empty array of strings")]
+    Missing,
+    #[error("at most {MAX_NUMBERS} numbers per request")]
+    TooMany,
+    #[error("invalid number {0:?}")]
+    Invalid(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NumberStatus {
+    pub exists: bool,
+    pub jid: String,
+    /// Number as digits, the way it was asked.
+    pub number: String,
+}
+
+/// Recent answers per `(instance, number)`.
+pub struct NumberCache {
+    ttl: Duration,
+    entries: DashMap<(String, String), (NumberStatus, Instant)>,
+}
+
+impl Default for NumberCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_NUMBERS_CACHE_TTL)
+    }
+}
+
+impl NumberCache {
+    /// Cache keeping answers for `ttl`; a zero `ttl` disables caching.
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: DashMap::new(),
+        }
+    }
+
+    /// Cached answer, `None` when missing or expired.
+    pub fn get(&self, session: &str, number: &str) -> Option<NumberStatus> {
+        let key = (session.to_string(), number.to_string());
+        let entry = self.entries.get(&key)?;
+        let (status, stored_at) = entry.value();
+        if stored_at.elapsed() < self.ttl {
+            return Some(status.clone());
+        }
+        drop(entry);
+        self.entries.remove(&key);
+        None
+    }
+
+    /// Stores an answer; no-op when caching is disabled.
+    pub fn insert(&self, session: &str, status: &NumberStatus) {
+        if self.ttl.is_zero() {
+            return;
+        }
+        self.entries.insert(
+            (session.to_string(), status.number.clone()),
+            (status.clone(), Instant::now()),
+        );
+    }
+}
+
+/// Digits of a phone number or of the user part of a JID, `None` when it
+/// has none.
+pub fn digits(raw: &str) -> Option<String> {
+    let user = raw.split('@').next().unwrap_or(raw);
+    let digits: String = user.chars().filter(char::is_ascii_digit).collect();
+    (!digits.is_empty()).then_some(digits)
+}
+
+/// Digits of every entry of `numbers`, without duplicates, in request order.
+pub fn numbers_from_body(body: &WhatsappNumbers) -> Result<Vec<String>, NumbersError> {
+    let entries = &body.numbers;
+    if entries.is_empty() {
+        return Err(NumbersError::Missing);
+    }
+    if entries.len() > MAX_NUMBERS {
+        return Err(NumbersError::TooMany);
+    }
+
+    let mut seen = HashSet::new();
+    let mut numbers = Vec::with_capacity(entries.len());
+    for raw in entries {
+        let digits = digits(raw).ok_or_else(|| NumbersError::Invalid(raw.to_string()))?;
+        if seen.insert(digits.clone()) {
+            numbers.push(digits);
+        }
+    }
+    Ok(numbers)
+}
+
+/// Pairs usync results with the asked numbers. Results are matched by the
+/// echoed query, then by the JID user; numbers without a result do not exist.
+pub fn match_results(numbers: &[String], results: &[IsOnWhatsAppResult]) -> Vec<NumberStatus> {
+    numbers
+        .iter()
+        .map(|number| {
+            let result = results.iter().find(|result| {
+                let query_digits = result
+                    .query
+                    .as_deref()
+                    .map(|q| q.chars().filter(char::is_ascii_digit).collect::<String>());
+                query_digits.as_deref() == Some(number.as_str())
+                    || result.jid.user_base() == number
+            });
+            match result {
+                Some(result) => NumberStatus {
+                    exists: result.is_registered,
+                    jid: result.jid.to_non_ad().to_string(),
+                    number: number.clone(),
+                },
+                None => NumberStatus {
+                    exists: false,
+                    jid: Jid::pn(number.as_str()).to_string(),
+                    number: number.clone(),
+                },
+            }
+        })
+        .collect()
+}
+
+/// Status of every number, from the cache or a usync query.
+pub async fn check(
+    cache: &NumberCache,
+    client: &Client,
+    session: &str,
+    numbers: &[String],
+) -> anyhow::Result<Vec<NumberStatus>> {
+    let mut statuses = HashMap::with_capacity(numbers.len());
+    let mut missing = Vec::new();
+    for number in numbers {
+        match cache.get(session, number) {
+            Some(status) => {
+                statuses.insert(number.clone(), status);
+            }
+            None => missing.push(number.clone()),
+        }
+    }
+
+    for batch in missing.chunks(BATCH_SIZE) {
+        let phones: Vec<&str> = batch.iter().map(String::as_str).collect();
+        let results = client.contacts().is_on_whatsapp(&phones).await?;
+        for status in match_results(batch, &results) {
+            cache.insert(session, &status);
+            statuses.insert(status.number.clone(), status);
+        }
+    }
+
+    Ok(numbers
+        .iter()
+        .filter_map(|number| statuses.remove(number))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    include!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/tests/server/numbers_tests.rs"));
+}
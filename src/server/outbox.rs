@@ -0,0 +1,270 @@
+//! Transactional outbox for events.
+//!
+//! Events are written to `event_outbox` in the same transaction as the
+//! state change that caused them ([`commit`]), so a crash between the write
+//! and the publish can no longer lose them. [`spawn_dispatcher`] reads the
+//! pending rows in order, publishes them to the `/ws` hub and NATS, then
+//! marks each row dispatched and queues its webhook in a single statement.
+//!
+//! Delivery is at least once towards the hub and NATS (a dispatcher that
+//! dies after publishing re-publishes once its lease expires) and exactly
+//! once into `webhook_outbox`, whose row reuses the outbox id. Every
+//! envelope carries that id as `eventId` so consumers can drop repeats.
+
+use crate::api_store::ApiBind;
+use crate::server::AppState;
+use crate::server::event_bus::BusEvent;
+use crate::server::events;
+use crate::server::instance_meta;
+use serde_json::Value;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tracing::{debug, warn};
+use uuid::Uuid;
+
+pub use crate::config::OutboxConfig;
+
+/// Dispatched rows are purged every this many polls.
+const PURGE_EVERY: u32 = 600;
+
+/// An event ready to be written to `event_outbox`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutboxEvent {
+    pub id: Uuid,
+    pub session: Option<String>,
+    pub event: String,
+    /// Full envelope, as delivered to every sink.
+    pub payload: Value,
+}
+
+impl OutboxEvent {
+    /// Wraps `data` in the event envelope with a fresh `eventId`.
+    pub fn new(session: Option<&str>, event: &str, data: Value) -> Self {
+        let id = Uuid::new_v4();
+        let mut payload = events::envelope(event, session, data);
+        if let Some(obj) = payload.as_object_mut() {
+            obj.insert("eventId".to_string(), Value::String(id.to_string()));
+        }
+        Self {
+            id,
+            session: session.map(|s| s.to_string()),
+            event: event.to_string(),
+            payload,
+        }
+    }
+
+    /// The `INSERT` that stores this event, for [`ApiStore::execute_batch`](crate::api_store::ApiStore::execute_batch).
+    pub fn insert_statement(&self) -> (String, Vec<ApiBind>) {
+        (
+            "INSERT INTO event_outbox (id, session, event, payload) VALUES ($1, $2, $3, $4)"
+                .to_string(),
+            vec![
+                ApiBind::Uuid(self.id),
+                ApiBind::NullableText(self.session.clone()),
+                ApiBind::Text(self.event.clone()),
+                ApiBind::Json(self.payload.clone()),
+            ],
+        )
+    }
+
+    /// Parses a row claimed by the dispatcher.
+    pub fn from_row(row: &Value) -> Option<Self> {
+        let value = row.get("value").unwrap_or(row);
+        Some(Self {
+            id: value
+                .get("id")
+                .and_then(Value::as_str)
+                .and_then(|s| Uuid::parse_str(s).ok())?,
+            session: value
+                .get("session")
+                .and_then(Value::as_str)
+                .map(|s| s.to_string()),
+            event: value.get("event").and_then(Value::as_str)?.to_string(),
+            payload: value.get("payload").cloned().unwrap_or(Value::Null),
+        })
+    }
+}
+
+/// Builds the event for `session`, including the instance tags.
+pub async fn prepare(
+    state: &AppState,
+    session: Option<&str>,
+    event: &str,
+    data: Value,
+) -> OutboxEvent {
+    let mut outbox_event = OutboxEvent::new(session, event, data);
+    if let Some(session) = session {
+        match instance_meta::load(state, session).await {
+            Ok(meta) => meta.apply_to(&mut outbox_event.payload),
+            Err(err) => debug!(session = %session, error = %err, "Tags da instância indisponíveis"),
+        }
+    }
+    outbox_event
+}
+
+/// Runs `statements` and stores `events` in one transaction, then wakes
+/// the dispatcher. Returns the affected rows of `statements`.
+pub async fn commit(
+    state: &AppState,
+    mut statements: Vec<(String, Vec<ApiBind>)>,
+    events: Vec<OutboxEvent>,
+) -> anyhow::Result<Vec<usize>> {
+    let count = statements.len();
+    statements.extend(events.iter().map(OutboxEvent::insert_statement));
+    let mut affected = state.api_store.execute_batch(statements).await?;
+    affected.truncate(count);
+    notify(state);
+    for event in &events {
+        invalidate(state, event).await;
+    }
+    Ok(affected)
+}
+
+/// Stores a single event outside of any state change.
+pub async fn record(state: &AppState, event: &OutboxEvent) -> anyhow::Result<()> {
+    let (sql, binds) = event.insert_statement();
+    state.api_store.execute(&sql, binds).await?;
+    notify(state);
+    invalidate(state, event).await;
+    Ok(())
+}
+
+/// Drops the cached reads `event` makes stale, once its change is stored.
+pub async fn invalidate(state: &AppState, event: &OutboxEvent) {
+    state
+        .cache
+        .on_event(event.session.as_deref(), &event.event)
+        .await;
+}
+
+fn notify(state: &AppState) {
+    // A full channel already holds a pending wake-up.
+    let _ = state.outbox_notify.try_send(());
+}
+
+/// Publishes `event` to the `/ws` and `/events/sse` hubs and NATS, and
+/// records it in the instance's event history. The sinks share one
+/// [`BusEvent`], serialized by the first that sends it.
+pub fn publish(state: &AppState, event: &OutboxEvent) {
+    let bus_event = BusEvent::new(event.payload.clone());
+    state.event_hub.publish(&bus_event);
+    state.sse.publish(&bus_event);
+    state.event_history.record(&bus_event.payload);
+    #[cfg(feature = "nats")]
+    if let Some(nats) = &state.nats {
+        nats.publish(&bus_event);
+    }
+}
+
+/// Publishes pending outbox rows, woken by [`commit`] and [`record`] or
+/// every `config.poll`.
+pub fn spawn_dispatcher(
+    state: Arc<AppState>,
+    config: OutboxConfig,
+    mut wake: mpsc::Receiver<()>,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut rounds: u32 = 0;
+        loop {
+            let dispatched = match dispatch_batch(&state, &config).await {
+                Ok(dispatched) => dispatched,
+                Err(err) => {
+                    warn!(error = %err, "Falha ao despachar eventos do outbox");
+                    0
+                }
+            };
+
+            rounds = rounds.wrapping_add(1);
+            if rounds.is_multiple_of(PURGE_EVERY)
+                && let Err(err) = purge(&state, &config).await
+            {
+                warn!(error = %err, "Falha ao limpar eventos despachados do outbox");
+            }
+
+            // A full batch likely left more rows behind.
+            if dispatched < config.batch_size as usize {
+                let _ = tokio::time::timeout(config.poll, wake.recv()).await;
+            }
+        }
+    })
+}
+
+/// Claims, publishes and marks one batch. Returns how many rows were
+/// claimed.
+async fn dispatch_batch(state: &AppState, config: &OutboxConfig) -> anyhow::Result<usize> {
+    let rows = state
+        .api_store
+        .query_json(
+            "WITH claimed AS ( \
+                SELECT id FROM event_outbox \
+                WHERE dispatched_at IS NULL AND (claimed_until IS NULL OR claimed_until <= now()) \
+                ORDER BY seq \
+                LIMIT $1 \
+                FOR UPDATE SKIP LOCKED \
+            ), updated AS ( \
+                UPDATE event_outbox o \
+                SET claimed_until = now() + ($2 || ' seconds')::interval \
+                FROM claimed \
+                WHERE o.id = claimed.id \
+                RETURNING o.id, o.seq, o.session, o.event, o.payload \
+            ) \
+            SELECT row_to_json(updated)::jsonb as value FROM updated ORDER BY seq",
+            vec![
+                ApiBind::Int(config.batch_size),
+                ApiBind::Int(config.lease_secs),
+            ],
+        )
+        .await?;
+
+    let claimed = rows.len();
+    for event in rows.iter().filter_map(OutboxEvent::from_row) {
+        publish(state, &event);
+        // The row stays claimed on failure and is retried after the lease.
+        if let Err(err) = mark_dispatched(state, event.id).await {
+            warn!(id = %event.id, event = %event.event, error = %err, "Falha ao marcar evento do outbox como despachado");
+        }
+    }
+    Ok(claimed)
+}
+
+/// Marks the row dispatched and queues its webhook atomically. Events of
+/// deleted sessions are not queued, since `webhook_outbox` references the
+/// session.
+async fn mark_dispatched(state: &AppState, id: Uuid) -> anyhow::Result<()> {
+    state
+        .api_store
+        .execute(
+            "WITH marked AS ( \
+                UPDATE event_outbox SET dispatched_at = now(), claimed_until = NULL \
+                WHERE id = $1 AND dispatched_at IS NULL \
+                RETURNING id, session, event, payload \
+            ) \
+            INSERT INTO webhook_outbox (id, session, event, payload) \
+            SELECT id, session, event, payload FROM marked \
+            WHERE session IS NULL OR EXISTS (SELECT 1 FROM api_sessions s WHERE s.session = marked.session) \
+            ON CONFLICT (id) DO NOTHING",
+            vec![ApiBind::Uuid(id)],
+        )
+        .await?;
+    Ok(())
+}
+
+async fn purge(state: &AppState, config: &OutboxConfig) -> anyhow::Result<()> {
+    let deleted = state
+        .api_store
+        .execute(
+            "DELETE FROM event_outbox \
+             WHERE dispatched_at IS NOT NULL AND dispatched_at < now() - ($1 || ' hours')::interval",
+            vec![ApiBind::Int(config.retention_hours)],
+        )
+        .await?;
+    if deleted > 0 {
+        debug!(deleted, "Eventos despachados removidos do outbox");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    include!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/tests/server/outbox_tests.rs"));
+}
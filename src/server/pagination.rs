@@ -0,0 +1,391 @@
+//! Pagination shared by the list endpoints.
+//!
+//! Every paginated list reads the same parameters, from the query string or
+//! the JSON body: `limit`, then either `page` (from 1) or `offset`, or the
+//! opaque `cursor` returned by the previous page, and `sort` as a comma
+//! separated list of fields, `-` marking descending ones
+//! (`sort=-lastMessageAt,title`). Each endpoint describes what it accepts in
+//! a [`ListSpec`]. Responses carry [`PageInfo`] under `pagination`, next to
+//! the items (`data` for endpoints that used to answer with a bare array).
+//!
+//! The cursor holds the next offset and the sort it was issued for, so
+//! following it keeps the order even when `sort` is not repeated.
+
+use crate::api_store::ApiBind;
+use crate::server::AppState;
+use axum::{Json, http::StatusCode};
+use base64::Engine as _;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum PaginationError {
+    #[error("{0} must be a positive integer")]
+    InvalidNumber(&'static str),
+    #[error("unknown sort field {field:?}; expected one of {allowed}")]
+    UnknownSort { field: String, allowed: String },
+    #[error("invalid cursor")]
+    InvalidCursor,
+    #[error("cursor was issued for sort {0:?}")]
+    CursorSortMismatch(String),
+}
+
+/// What a list endpoint accepts.
+#[derive(Debug, Clone, Copy)]
+pub struct ListSpec {
+    pub default_limit: u32,
+    pub max_limit: u32,
+    /// Sortable fields as `(name in the API, column or JSON key)`.
+    pub sort_fields: &'static [(&'static str, &'static str)],
+    /// Sort used without `sort`, in the `sort` syntax.
+    pub default_sort: &'static str,
+    /// Columns (or keys) appended to every order so pages never overlap.
+    pub tiebreak: &'static [&'static str],
+}
+
+impl ListSpec {
+    fn column(&self, field: &str) -> Option<&'static str> {
+        self.sort_fields
+            .iter()
+            .find(|(name, _)| *name == field)
+            .map(|(_, column)| *column)
+    }
+
+    fn parse_sort(&self, raw: &str) -> Result<Vec<SortKey>, PaginationError> {
+        let mut keys = Vec::new();
+        for part in raw.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let (field, descending) = match part.strip_prefix('-') {
+                Some(field) => (field, true),
+                None => (part.strip_prefix('+').unwrap_or(part), false),
+            };
+            let Some(column) = self.column(field) else {
+                return Err(PaginationError::UnknownSort {
+                    field: field.to_string(),
+                    allowed: self
+                        .sort_fields
+                        .iter()
+                        .map(|(name, _)| *name)
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                });
+            };
+            if !keys.iter().any(|key: &SortKey| key.column == column) {
+                keys.push(SortKey {
+                    field: field.to_string(),
+                    column,
+                    descending,
+                });
+            }
+        }
+        Ok(keys)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SortKey {
+    pub field: String,
+    pub column: &'static str,
+    pub descending: bool,
+}
+
+/// A validated page request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PageRequest {
+    pub limit: u32,
+    pub offset: u32,
+    pub sort: Vec<SortKey>,
+    tiebreak: &'static [&'static str],
+}
+
+impl PageRequest {
+    pub fn from_query(
+        spec: &ListSpec,
+        query: &HashMap<String, String>,
+    ) -> Result<Self, PaginationError> {
+        Self::from_lookup(spec, |name| query.get(name).cloned())
+    }
+
+    /// Reads the parameters from a JSON body, as numbers or strings.
+    pub fn from_body(spec: &ListSpec, body: &Value) -> Result<Self, PaginationError> {
+        Self::from_lookup(spec, |name| match &body[name] {
+            Value::String(s) => Some(s.clone()),
+            Value::Number(n) => Some(n.to_string()),
+            _ => None,
+        })
+    }
+
+    /// Reads the parameters through `lookup`, for bodies read into a typed
+    /// request.
+    pub(crate) fn from_lookup(
+        spec: &ListSpec,
+        lookup: impl Fn(&str) -> Option<String>,
+    ) -> Result<Self, PaginationError> {
+        let param = |name: &str| lookup(name).filter(|v| !v.trim().is_empty());
+        let number = |name: &'static str| -> Result<Option<u32>, PaginationError> {
+            param(name)
+                .map(|v| {
+                    v.trim()
+                        .parse::<u32>()
+                        .map_err(|_| PaginationError::InvalidNumber(name))
+                })
+                .transpose()
+        };
+
+        let limit = match number("limit")? {
+            Some(0) => return Err(PaginationError::InvalidNumber("limit")),
+            Some(limit) => limit.min(spec.max_limit),
+            None => spec.default_limit,
+        };
+        let requested_sort = param("sort").map(|raw| spec.parse_sort(&raw)).transpose()?;
+
+        let (offset, sort) = if let Some(cursor) = param("cursor") {
+            let (offset, cursor_sort) = decode_cursor(&cursor)?;
+            let sort = spec.parse_sort(&cursor_sort)?;
+            if requested_sort
+                .as_ref()
+                .is_some_and(|requested| *requested != sort)
+            {
+                return Err(PaginationError::CursorSortMismatch(cursor_sort));
+            }
+            (offset, sort)
+        } else {
+            let offset = match (number("page")?, number("offset")?) {
+                (Some(0), _) => return Err(PaginationError::InvalidNumber("page")),
+                (Some(page), _) => (page - 1).saturating_mul(limit),
+                (None, offset) => offset.unwrap_or(0),
+            };
+            let sort = match requested_sort {
+                Some(sort) if !sort.is_empty() => sort,
+                _ => spec.parse_sort(spec.default_sort)?,
+            };
+            (offset, sort)
+        };
+
+        Ok(Self {
+            limit,
+            offset: offset.min(i32::MAX as u32),
+            sort,
+            tiebreak: spec.tiebreak,
+        })
+    }
+
+    /// `sort` as sent back by the API, e.g. `-lastMessageAt,title`.
+    pub fn sort_param(&self) -> String {
+        self.sort
+            .iter()
+            .map(|key| {
+                if key.descending {
+                    format!("-{}", key.field)
+                } else {
+                    key.field.clone()
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    /// `ORDER BY` clause with the tiebreak columns; nulls always last.
+    pub fn order_by(&self) -> String {
+        let mut terms: Vec<String> = self
+            .sort
+            .iter()
+            .map(|key| {
+                let direction = if key.descending { "DESC" } else { "ASC" };
+                format!("{} {direction} NULLS LAST", key.column)
+            })
+            .collect();
+        terms.extend(
+            self.tiebreak
+                .iter()
+                .filter(|column| !self.sort.iter().any(|key| key.column == **column))
+                .map(|column| column.to_string()),
+        );
+        format!("ORDER BY {}", terms.join(", "))
+    }
+
+    /// Metadata of the page holding `count` items out of `total`.
+    pub fn info(&self, count: usize, total: u64) -> PageInfo {
+        let end = u64::from(self.offset) + count as u64;
+        let has_more = end < total;
+        PageInfo {
+            limit: self.limit,
+            offset: Some(self.offset),
+            page: self.offset.is_multiple_of(self.limit).then(|| self.offset / self.limit + 1),
+            total: Some(total),
+            has_more,
+            next_cursor: has_more
+                .then(|| encode_cursor(u32::try_from(end).unwrap_or(u32::MAX), &self.sort_param())),
+            sort: Some(self.sort_param()),
+        }
+    }
+
+    /// Sorts `items` in memory by their JSON fields and keeps this page.
+    /// Returns the page and the total.
+    pub fn apply(&self, mut items: Vec<Value>) -> (Vec<Value>, u64) {
+        items.sort_by(|a, b| {
+            self.sort
+                .iter()
+                .map(|key| {
+                    let ordering = compare_values(&a[key.column], &b[key.column]);
+                    match (key.descending, &a[key.column], &b[key.column]) {
+                        // Nulls stay last either way.
+                        (true, Value::Null, _) | (true, _, Value::Null) => ordering,
+                        (true, _, _) => ordering.reverse(),
+                        (false, _, _) => ordering,
+                    }
+                })
+                .chain(
+                    self.tiebreak
+                        .iter()
+                        .map(|column| compare_values(&a[*column], &b[*column])),
+                )
+                .find(|ordering| ordering.is_ne())
+                .unwrap_or(Ordering::Equal)
+        });
+        let total = items.len() as u64;
+        let page = items
+            .into_iter()
+            .skip(self.offset as usize)
+            .take(self.limit as usize)
+            .collect();
+        (page, total)
+    }
+
+    /// Runs `SELECT {select} AS value {from}` for this page and the count
+    /// of `{from}`. `from` holds the `FROM` and `WHERE` clauses, binding
+    /// `binds` from `$1`.
+    pub async fn fetch(
+        &self,
+        state: &AppState,
+        select: &str,
+        from: &str,
+        binds: Vec<ApiBind>,
+    ) -> anyhow::Result<(Vec<Value>, u64)> {
+        let next = binds.len() + 1;
+        let mut page_binds = binds.clone();
+        page_binds.push(ApiBind::Int(self.limit as i32));
+        page_binds.push(ApiBind::Int(self.offset as i32));
+        let rows = state
+            .api_store
+            .query_json(
+                &format!(
+                    "SELECT {select} AS value {from} {} LIMIT ${next} OFFSET ${}",
+                    self.order_by(),
+                    next + 1
+                ),
+                page_binds,
+            )
+            .await?;
+        let counted = state
+            .api_store
+            .query_json(
+                &format!("SELECT jsonb_build_object('total', count(*)) AS value {from}"),
+                binds,
+            )
+            .await?;
+        let total = counted
+            .first()
+            .map(|row| row.get("value").unwrap_or(row))
+            .and_then(|value| value["total"].as_u64())
+            .unwrap_or(0);
+        Ok((rows, total))
+    }
+}
+
+/// `pagination` of a list response.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PageInfo {
+    pub limit: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub offset: Option<u32>,
+    /// 1-based page, when the offset falls on a page boundary.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub page: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total: Option<u64>,
+    pub has_more: bool,
+    /// Pass as `cursor` for the next page.
+    pub next_cursor: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sort: Option<String>,
+}
+
+impl PageInfo {
+    /// For lists paged by their own position, e.g. an event id.
+    pub fn keyset(limit: u32, has_more: bool, next_cursor: Option<String>) -> Self {
+        Self {
+            limit,
+            offset: None,
+            page: None,
+            total: None,
+            has_more,
+            next_cursor: next_cursor.filter(|_| has_more),
+            sort: None,
+        }
+    }
+}
+
+/// `400 invalid_pagination` for a rejected page request.
+pub fn invalid_pagination(error: PaginationError) -> (StatusCode, Json<Value>) {
+    (
+        StatusCode::BAD_REQUEST,
+        Json(json!({"error": "invalid_pagination", "details": error.to_string()})),
+    )
+}
+
+/// `{"data": items, "pagination": info}`.
+pub fn envelope(items: Vec<Value>, info: &PageInfo) -> Value {
+    json!({"data": items, "pagination": info})
+}
+
+pub fn encode_cursor(offset: u32, sort: &str) -> String {
+    URL_SAFE_NO_PAD.encode(format!("{offset}|{sort}"))
+}
+
+fn decode_cursor(cursor: &str) -> Result<(u32, String), PaginationError> {
+    let bytes = URL_SAFE_NO_PAD
+        .decode(cursor.trim())
+        .map_err(|_| PaginationError::InvalidCursor)?;
+    let text = String::from_utf8(bytes).map_err(|_| PaginationError::InvalidCursor)?;
+    let (offset, sort) = text.split_once('|').ok_or(PaginationError::InvalidCursor)?;
+    let offset = offset
+        .parse::<u32>()
+        .map_err(|_| PaginationError::InvalidCursor)?;
+    Ok((offset, sort.to_string()))
+}
+
+/// Order of JSON values of one field: numbers, then strings (ignoring
+/// case), then booleans, nulls last.
+fn compare_values(a: &Value, b: &Value) -> Ordering {
+    fn rank(value: &Value) -> u8 {
+        match value {
+            Value::Number(_) => 0,
+            Value::String(_) => 1,
+            Value::Bool(_) => 2,
+            Value::Null => 4,
+            _ => 3,
+        }
+    }
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => a
+            .as_f64()
+            .partial_cmp(&b.as_f64())
+            .unwrap_or(Ordering::Equal),
+        (Value::String(a), Value::String(b)) => a
+            .to_lowercase()
+            .cmp(&b.to_lowercase())
+            .then_with(|| a.cmp(b)),
+        (Value::Bool(a), Value::Bool(b)) => a.cmp(b),
+        _ => rank(a).cmp(&rank(b)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    include!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/tests/server/pagination_tests.rs"));
+}
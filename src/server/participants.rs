@@ -0,0 +1,254 @@
+//! Normalization of the numbers sent to message and group routes.
+//!
+//! Clients send `+55 (11) 99999-0000`, `0055...`, local numbers without the
+//! country code or full JIDs. [`normalize_middleware`] rewrites the
+//! `number`, `chatId` and `participants` fields of those requests into
+//! canonical JIDs before the handler runs, adding `DEFAULT_COUNTRY_CODE` to
+//! numbers short enough to be local. Entries it cannot read are all listed
+//! in a single `400 invalid_participants`, so nothing reaches WhatsApp.
+
+use crate::server::jid::{self, JidError};
+use crate::server::{AppState, body};
+use axum::{
+    Json,
+    body::Body,
+    extract::{MatchedPath, State},
+    http::{HeaderMap, Method, Request, StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+use serde_json::{Value, json};
+use std::ops::RangeInclusive;
+use std::sync::Arc;
+use thiserror::Error;
+use warp_core_binary::jid::{DEFAULT_USER_SERVER, HIDDEN_USER_SERVER, Jid};
+
+pub use crate::config::ParticipantConfig;
+
+/// E.164 allows at most 15 digits; shorter than 8 is never a full number.
+const NUMBER_DIGITS: RangeInclusive<usize> = 8..=15;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum NumberError {
+    #[error(transparent)]
+    Jid(#[from] JidError),
+    #[error("unexpected characters in number {0:?}")]
+    Characters(String),
+    #[error("number has {0} digits, expected 8 to 15")]
+    Length(usize),
+    #[error("{0:?} is not a user")]
+    NotAUser(String),
+}
+
+/// What a field may hold.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    /// Any chat: users, groups, newsletters, broadcasts.
+    Chat,
+    /// A user, by phone number or LID.
+    User,
+}
+
+impl ParticipantConfig {
+    /// Canonical JID of a request value.
+    pub fn normalize(&self, raw: &str, target: Target) -> Result<Jid, NumberError> {
+        let raw = raw.trim();
+        if raw.contains('@') {
+            return self.normalize_jid(raw, target);
+        }
+        if raw.is_empty() {
+            return Err(JidError::Empty.into());
+        }
+        if !raw
+            .chars()
+            .all(|c| c.is_ascii_digit() || matches!(c, '+' | ' ' | '-' | '.' | '(' | ')'))
+        {
+            return Err(NumberError::Characters(raw.to_string()));
+        }
+
+        let digits: String = raw.chars().filter(char::is_ascii_digit).collect();
+        let digits = if raw.starts_with('+') {
+            digits
+        } else if let Some(international) = digits.strip_prefix("00") {
+            international.to_string()
+        } else {
+            // A leading 0 is the trunk prefix of local numbers.
+            let local = digits.trim_start_matches('0');
+            match &self.default_country_code {
+                Some(code) if local.len() <= self.local_max_digits => format!("{code}{local}"),
+                _ => digits,
+            }
+        };
+        if !NUMBER_DIGITS.contains(&digits.len()) {
+            return Err(NumberError::Length(digits.len()));
+        }
+        Ok(Jid::pn(digits))
+    }
+
+    fn normalize_jid(&self, raw: &str, target: Target) -> Result<Jid, NumberError> {
+        let jid = jid::parse(raw)?.to_non_ad();
+        match jid.server.as_str() {
+            DEFAULT_USER_SERVER => {
+                if !jid.user.chars().all(|c| c.is_ascii_digit()) {
+                    return Err(JidError::Invalid(raw.to_string()).into());
+                }
+                if !NUMBER_DIGITS.contains(&jid.user.len()) {
+                    return Err(NumberError::Length(jid.user.len()));
+                }
+                Ok(jid)
+            }
+            HIDDEN_USER_SERVER => Ok(jid),
+            _ if target == Target::Chat => Ok(jid),
+            _ => Err(NumberError::NotAUser(raw.to_string())),
+        }
+    }
+
+    /// Rewrites the number fields of a request body in place and returns
+    /// the entries that could not be normalized.
+    pub fn normalize_body(&self, body: &mut Value) -> Vec<InvalidEntry> {
+        let mut invalid = Vec::new();
+        let Some(fields) = body.as_object_mut() else {
+            return invalid;
+        };
+        for field in ["number", "chatId"] {
+            if let Some(value) = fields.get_mut(field) {
+                self.normalize_value(field.to_string(), value, Target::Chat, &mut invalid);
+            }
+        }
+        if let Some(Value::Array(participants)) = fields.get_mut("participants") {
+            for (i, value) in participants.iter_mut().enumerate() {
+                // Participants are plain ids or `{"id": ...}` objects.
+                match value.get_mut("id") {
+                    Some(id) => {
+                        let field = format!("participants[{i}].id");
+                        self.normalize_value(field, id, Target::User, &mut invalid);
+                    }
+                    None => {
+                        let field = format!("participants[{i}]");
+                        self.normalize_value(field, value, Target::User, &mut invalid);
+                    }
+                }
+            }
+        }
+        invalid
+    }
+
+    fn normalize_value(
+        &self,
+        field: String,
+        value: &mut Value,
+        target: Target,
+        invalid: &mut Vec<InvalidEntry>,
+    ) {
+        // Numbers sent as JSON numbers are read like strings.
+        let raw = match value {
+            Value::String(s) => s.clone(),
+            Value::Number(n) => n.to_string(),
+            _ => {
+                invalid.push(InvalidEntry {
+                    field,
+                    value: value.clone(),
+                    reason: "expected a string".to_string(),
+                });
+                return;
+            }
+        };
+        match self.normalize(&raw, target) {
+            Ok(jid) => *value = Value::String(jid.to_string()),
+            Err(e) => invalid.push(InvalidEntry {
+                field,
+                value: value.clone(),
+                reason: e.to_string(),
+            }),
+        }
+    }
+}
+
+/// An entry of the request that is not a valid number or JID.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct InvalidEntry {
+    /// `number`, `chatId`, `participants[i]` or `participants[i].id`.
+    pub field: String,
+    pub value: Value,
+    pub reason: String,
+}
+
+/// Whether the body of `route` carries numbers to normalize.
+pub fn normalizes(method: &Method, route: &str) -> bool {
+    if !matches!(*method, Method::POST | Method::PUT) {
+        return false;
+    }
+    route.starts_with("/message/")
+        || route.starts_with("/group/")
+        || route.starts_with("/:session/groups")
+        || route.starts_with("/send")
+        || route == "/forwardMessage"
+}
+
+/// Whether the request declares a JSON body, as axum's `Json` extractor
+/// expects; other bodies are passed through unread for the handler to reject.
+fn is_json(headers: &HeaderMap) -> bool {
+    let Some(mime) = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.split(';').next())
+    else {
+        return false;
+    };
+    let mime = mime.trim().to_ascii_lowercase();
+    mime == "application/json" || (mime.starts_with("application/") && mime.ends_with("+json"))
+}
+
+/// Normalizes the numbers of message and group requests; see the module
+/// docs.
+pub async fn normalize_middleware(
+    State(state): State<Arc<AppState>>,
+    req: Request<Body>,
+    next: Next,
+) -> Response {
+    let applies = req
+        .extensions()
+        .get::<MatchedPath>()
+        .is_some_and(|route| normalizes(req.method(), route.as_str()))
+        && is_json(req.headers());
+    if !applies {
+        return next.run(req).await;
+    }
+
+    let (mut parts, body) = req.into_parts();
+    let bytes = match body::buffer(body, state.body_limit).await {
+        Ok(bytes) => bytes,
+        Err(response) => return response,
+    };
+    // Bodies that are not JSON objects are left for the handler to reject.
+    let mut json_body = match serde_json::from_slice::<Value>(&bytes) {
+        Ok(value @ Value::Object(_)) => value,
+        _ => {
+            return next
+                .run(Request::from_parts(parts, Body::from(bytes)))
+                .await;
+        }
+    };
+
+    let invalid = state.participants.normalize_body(&mut json_body);
+    if !invalid.is_empty() {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({"error": "invalid_participants", "invalid": invalid})),
+        )
+            .into_response();
+    }
+
+    parts.headers.remove(header::CONTENT_LENGTH);
+    next.run(Request::from_parts(
+        parts,
+        Body::from(json_body.to_string()),
+    ))
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    include!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/tests/server/participants_tests.rs"));
+}
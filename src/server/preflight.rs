@@ -0,0 +1,456 @@
+//! Startup validation of the environment.
+//!
+//! Most settings quietly fall back to their defaults when a value cannot be
+//! read, so a typo in `PORT` or a webhook URL only shows up once something
+//! misbehaves. [`PreflightReport`] reads the same variables up front and
+//! lists every value that is malformed, ignored or missing a variable it
+//! depends on. Errors keep the server from starting; `--check-config` prints
+//! the report and exits.
+
+use crate::config::{
+    self, DatabaseConfig, DatabaseProvider, Env, MAX_QR_IMAGE_SIZE, MIN_QR_IMAGE_SIZE,
+};
+use crate::error::AppError;
+use crate::server::health;
+use crate::server::logging::LogFormat;
+use crate::server::runtime_config::is_origin_pattern;
+use crate::server::tls::{TlsMode, read_pem_pair};
+use crate::server::ws::LagPolicy;
+use crate::version::VersionConfigError;
+use serde::Serialize;
+use std::fmt;
+use std::path::Path;
+
+/// Settings read as non-negative integers; unreadable values are ignored.
+const COUNTS: [&str; 29] = [
+    "CHATWARP_SESSION_TTL_SECONDS",
+    "RATE_LIMIT_PER_MINUTE",
+    "QR_IMAGE_SIZE",
+    "QR_CACHE_SECONDS",
+    "MAX_INSTANCES",
+    "MAX_INSTANCES_PER_WORKSPACE",
+    "MAX_MESSAGES_PER_DAY",
+    "MAX_MEDIA_SIZE_MB",
+    "WEBHOOK_BASE64_MAX_MB",
+    "LOG_FILE_MAX_FILES",
+    "INSTANCE_LOG_BUFFER",
+    "RUNNER_MAX_RESTARTS",
+    "RUNNER_RESTART_BACKOFF_MS",
+    "HTTP_MAX_ATTEMPTS",
+    "HTTP_RETRY_BACKOFF_MS",
+    "HTTP_BREAKER_THRESHOLD",
+    "HTTP_BREAKER_COOLDOWN_SECONDS",
+    "STATIC_CACHE_MB",
+    "MEDIA_THUMBNAIL_SIZE",
+    "WHATSAPP_NUMBERS_CACHE_SECONDS",
+    "PROFILE_PICTURE_CACHE_SECONDS",
+    "LOCAL_NUMBER_MAX_DIGITS",
+    "HANDSHAKE_JITTER_MS",
+    "AUTH_SAVE_DEBOUNCE_MS",
+    "TLS_RELOAD_SECS",
+    "REQUEST_TIMEOUT_SECS",
+    "REQUEST_TIMEOUT_MESSAGES_SECS",
+    "REQUEST_TIMEOUT_CONNECT_SECS",
+    "REQUEST_TIMEOUT_QUERIES_SECS",
+];
+
+/// Settings that must be above zero; `0` is ignored like any bad value.
+const POSITIVE: [&str; 21] = [
+    "HEALTH_TIMEOUT_MS",
+    "HEALTH_SLOW_MS",
+    "LOG_FILE_MAX_MB",
+    "HTTP_TIMEOUT_MS",
+    "MEDIA_UPLOAD_MAX_MB",
+    "MEDIA_UPLOAD_TTL_MINUTES",
+    "WS_BUFFER_SIZE",
+    "WS_PING_INTERVAL_SECS",
+    "WS_PONG_TIMEOUT_SECS",
+    "SSE_HISTORY_SIZE",
+    "SSE_HEARTBEAT_SECS",
+    "EVENT_HISTORY_SIZE",
+    "OUTBOX_BATCH_SIZE",
+    "OUTBOX_POLL_MS",
+    "OUTBOX_LEASE_SECS",
+    "OUTBOX_RETENTION_HOURS",
+    "HANDSHAKE_MAX_CONCURRENT",
+    "INSTANCE_LEASE_SECS",
+    "CACHE_LOCAL_TTL",
+    "CACHE_LOCAL_MAX_ENTRIES",
+    "CACHE_REDIS_TTL",
+];
+
+/// Flags that only `true` or `1` turn on.
+const FLAGS: [&str; 15] = [
+    "WEBHOOK_GLOBAL_ENABLED",
+    "WEBHOOK_GLOBAL_WEBHOOK_BY_EVENTS",
+    "WEBHOOK_GLOBAL_WEBHOOK_BASE64",
+    "MAINTENANCE_MODE",
+    "EVOLUTION_COMPAT",
+    "NATS_ENABLED",
+    "NATS_JETSTREAM",
+    "NATS_GLOBAL_ENABLED",
+    "EVENT_HISTORY_PERSIST",
+    "AUTH_TRUST_FORWARDED_FOR",
+    "TLS_ACME_ENABLED",
+    "TLS_ACME_STAGING",
+    "STANDBY_MODE",
+    "INSTANCE_LEASES",
+    "CACHE_REDIS_ENABLED",
+];
+
+/// Settings holding an http(s) URL.
+const HTTP_URLS: [&str; 4] = [
+    "SERVER_URL",
+    "WEBHOOK_GLOBAL_URL",
+    "META_GRAPH_URL",
+    "HTTP_PROXY_URL",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// The value is ignored and the default used.
+    Warning,
+    /// The server refuses to start.
+    Error,
+}
+
+/// A problem with one variable.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Finding {
+    pub severity: Severity,
+    pub variable: String,
+    pub message: String,
+}
+
+/// Outcome of the environment checks.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct PreflightReport {
+    pub findings: Vec<Finding>,
+}
+
+impl PreflightReport {
+    /// Checks the settings read from `env`.
+    pub fn check(env: &Env) -> Self {
+        let mut report = Self::default();
+        let value = |name: &str| env.text(name);
+
+        if let Some(port) = value("PORT")
+            && port.parse::<u16>().ok().filter(|p| *p > 0).is_none()
+        {
+            report.error(
+                "PORT",
+                format!("expected a port between 1 and 65535, got {port:?}"),
+            );
+        }
+
+        match DatabaseConfig::read(env) {
+            Ok(config)
+                if config.secrets.is_some() && config.provider != DatabaseProvider::Postgresql =>
+            {
+                report.error(
+                    "SECRETS_MASTER_KEY",
+                    "secrets are only encrypted by the PostgreSQL storage",
+                );
+            }
+            Ok(config)
+                if config.provider != DatabaseProvider::Postgresql
+                    && (env.flag("STANDBY_MODE") || env.flag("INSTANCE_LEASES")) =>
+            {
+                let name = if env.flag("STANDBY_MODE") {
+                    "STANDBY_MODE"
+                } else {
+                    "INSTANCE_LEASES"
+                };
+                report.error(
+                    name,
+                    "instance leases are only kept by the PostgreSQL storage",
+                );
+            }
+            Ok(_) => {}
+            Err(AppError::MissingEnv(name)) => report.error(name, "required"),
+            Err(AppError::InvalidEnv { name, reason }) => report.error(name, reason),
+            Err(other) => report.error("DATABASE_URL", other.to_string()),
+        }
+
+        for name in HTTP_URLS {
+            if let Some(url) = value(name)
+                && !is_http_url(&url)
+            {
+                report.error(name, format!("expected an http(s) URL, got {url:?}"));
+            }
+        }
+        if env.flag("WEBHOOK_GLOBAL_ENABLED") && value("WEBHOOK_GLOBAL_URL").is_none() {
+            report.error(
+                "WEBHOOK_GLOBAL_URL",
+                "required when WEBHOOK_GLOBAL_ENABLED is true",
+            );
+        }
+        if value("WEBHOOK_GLOBAL_HEADERS").is_some_and(|raw| {
+            !serde_json::from_str::<serde_json::Value>(&raw).is_ok_and(|v| v.is_object())
+        }) {
+            report.warning(
+                "WEBHOOK_GLOBAL_HEADERS",
+                "not a JSON object; no headers are sent",
+            );
+        }
+
+        if env.flag("NATS_ENABLED") {
+            if !cfg!(feature = "nats") {
+                report.warning("NATS_ENABLED", "this build has no `nats` feature; ignored");
+            }
+            if let Some(url) = value("NATS_URL")
+                && !["nats://", "tls://", "ws://", "wss://"]
+                    .iter()
+                    .any(|scheme| url.starts_with(scheme))
+            {
+                report.error(
+                    "NATS_URL",
+                    format!("expected a nats:// or tls:// URL, got {url:?}"),
+                );
+            }
+        }
+        if env.flag("CACHE_REDIS_ENABLED") {
+            if !cfg!(feature = "redis") {
+                report.warning(
+                    "CACHE_REDIS_ENABLED",
+                    "this build has no `redis` feature; only the local cache is used",
+                );
+            }
+            if let Some(uri) = value("CACHE_REDIS_URI")
+                && !["redis://", "rediss://", "unix://"]
+                    .iter()
+                    .any(|scheme| uri.starts_with(scheme))
+            {
+                report.error(
+                    "CACHE_REDIS_URI",
+                    format!("expected a redis:// or rediss:// URL, got {uri:?}"),
+                );
+            }
+        }
+        if value("META_APP_SECRET").is_some() && value("META_VERIFY_TOKEN").is_none() {
+            report.warning(
+                "META_VERIFY_TOKEN",
+                "not set, so the /webhook/meta verification always fails",
+            );
+        }
+        if value("META_VERIFY_TOKEN").is_some() && value("META_APP_SECRET").is_none() {
+            report.warning(
+                "META_APP_SECRET",
+                "not set, so POST /webhook/meta answers 503 and Cloud API instances receive nothing",
+            );
+        }
+
+        report.check_numbers(&value);
+        for name in FLAGS {
+            if let Some(raw) = value(name)
+                && !matches!(
+                    raw.to_ascii_lowercase().as_str(),
+                    "true" | "1" | "false" | "0"
+                )
+            {
+                report.warning(
+                    name,
+                    format!("expected true or false, got {raw:?}; read as false"),
+                );
+            }
+        }
+        report.check_choices(env, &value);
+        report.check_paths(&value);
+        report.check_tls(env);
+        report
+    }
+
+    fn check_numbers(&mut self, value: &impl Fn(&str) -> Option<String>) {
+        for name in COUNTS.into_iter().chain(POSITIVE) {
+            let Some(raw) = value(name) else {
+                continue;
+            };
+            match raw.parse::<u64>() {
+                Ok(0) if POSITIVE.contains(&name) => {
+                    self.warning(name, "must be above 0; the default is used");
+                }
+                Ok(_) => {}
+                Err(_) => self.warning(
+                    name,
+                    format!("expected a non-negative integer, got {raw:?}; the default is used"),
+                ),
+            }
+        }
+        if let Some(size) = value("QR_IMAGE_SIZE").and_then(|v| v.parse::<u32>().ok())
+            && !(MIN_QR_IMAGE_SIZE..=MAX_QR_IMAGE_SIZE).contains(&size)
+        {
+            self.warning(
+                "QR_IMAGE_SIZE",
+                format!(
+                    "{size} is clamped to {}..={}",
+                    MIN_QR_IMAGE_SIZE, MAX_QR_IMAGE_SIZE
+                ),
+            );
+        }
+    }
+
+    fn check_choices(&mut self, env: &Env, value: &impl Fn(&str) -> Option<String>) {
+        if let Some(raw) = value("LOG_FORMAT")
+            && LogFormat::parse(&raw).is_none()
+        {
+            self.warning(
+                "LOG_FORMAT",
+                format!("expected json, pretty or compact, got {raw:?}; compact is used"),
+            );
+        }
+        if let Some(raw) = value("LOG_LEVEL")
+            && let Err(e) = tracing_subscriber::EnvFilter::try_new(&raw)
+        {
+            self.warning("LOG_LEVEL", format!("invalid filter {raw:?}: {e}"));
+        }
+        if let Some(raw) = value("WS_LAG_POLICY")
+            && LagPolicy::parse(&raw).is_none()
+        {
+            self.warning(
+                "WS_LAG_POLICY",
+                format!("expected drop_oldest or disconnect, got {raw:?}; drop_oldest is used"),
+            );
+        }
+        if let Err(e) = config::wa_version(env) {
+            let variable = match &e {
+                VersionConfigError::InvalidVersion { name, .. } => *name,
+                VersionConfigError::InvalidSource(_) => "WA_VERSION_SOURCE",
+            };
+            self.warning(variable, format!("{e}; ignored"));
+        }
+        if let Some(raw) = value("HEALTH_CRITICAL") {
+            for name in raw.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+                if ![health::DATABASE, health::NATS, health::WA_VERSION]
+                    .contains(&name.to_ascii_lowercase().as_str())
+                {
+                    self.warning("HEALTH_CRITICAL", format!("unknown dependency {name:?}"));
+                }
+            }
+        }
+        for variable in ["CORS_ORIGINS", "MANAGER_CORS_ORIGINS", "WS_CORS_ORIGINS"] {
+            let Some(raw) = value(variable) else {
+                continue;
+            };
+            for origin in raw.split(',').map(str::trim).filter(|o| !o.is_empty()) {
+                if !is_origin_pattern(origin) {
+                    self.warning(
+                        variable,
+                        format!("{origin:?} is not an http(s) origin and never matches"),
+                    );
+                }
+            }
+        }
+        if let Some(raw) = value("DEFAULT_COUNTRY_CODE")
+            && !raw
+                .trim_start_matches('+')
+                .chars()
+                .all(|c| c.is_ascii_digit())
+        {
+            self.warning(
+                "DEFAULT_COUNTRY_CODE",
+                format!("expected digits, got {raw:?}; ignored"),
+            );
+        }
+    }
+
+    fn check_paths(&mut self, value: &impl Fn(&str) -> Option<String>) {
+        // A bare name is looked up in PATH when ffmpeg runs.
+        if let Some(ffmpeg) = value("FFMPEG_PATH")
+            && ffmpeg.contains(std::path::MAIN_SEPARATOR)
+            && !Path::new(&ffmpeg).is_file()
+        {
+            self.warning(
+                "FFMPEG_PATH",
+                format!("{ffmpeg} does not exist; audio conversion fails"),
+            );
+        }
+        if let Some(file) = value("LOG_FILE")
+            && Path::new(&file).is_dir()
+        {
+            self.error("LOG_FILE", format!("{file} is a directory"));
+        }
+        if let Some(dir) = value("MEDIA_UPLOAD_DIR")
+            && Path::new(&dir).exists()
+            && !Path::new(&dir).is_dir()
+        {
+            self.error("MEDIA_UPLOAD_DIR", format!("{dir} is not a directory"));
+        }
+    }
+
+    fn check_tls(&mut self, env: &Env) {
+        let checked = config::tls(env).and_then(|mode| {
+            mode.ensure_supported()?;
+            match mode {
+                TlsMode::Files { cert, key, .. } => read_pem_pair(&cert, &key).map(drop),
+                _ => Ok(()),
+            }
+        });
+        if let Err(e) = checked {
+            self.error(e.variable(), e.to_string());
+        }
+    }
+
+    fn error(&mut self, variable: &str, message: impl Into<String>) {
+        self.push(Severity::Error, variable, message.into());
+    }
+
+    fn warning(&mut self, variable: &str, message: impl Into<String>) {
+        self.push(Severity::Warning, variable, message.into());
+    }
+
+    fn push(&mut self, severity: Severity, variable: &str, message: String) {
+        self.findings.push(Finding {
+            severity,
+            variable: variable.to_string(),
+            message,
+        });
+    }
+
+    /// Whether the server must not start.
+    pub fn has_errors(&self) -> bool {
+        self.findings.iter().any(|f| f.severity == Severity::Error)
+    }
+}
+
+/// One line per finding, errors first, then a summary line.
+impl fmt::Display for PreflightReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut findings: Vec<&Finding> = self.findings.iter().collect();
+        findings.sort_by_key(|finding| std::cmp::Reverse(finding.severity));
+        let width = findings.iter().map(|f| f.variable.len()).max().unwrap_or(0);
+        for finding in &findings {
+            let severity = match finding.severity {
+                Severity::Error => "error",
+                Severity::Warning => "warning",
+            };
+            writeln!(
+                f,
+                "{severity:<7}  {:<width$}  {}",
+                finding.variable, finding.message
+            )?;
+        }
+        let errors = findings
+            .iter()
+            .filter(|finding| finding.severity == Severity::Error)
+            .count();
+        write!(
+            f,
+            "configuration check: {errors} error(s), {} warning(s)",
+            findings.len() - errors
+        )
+    }
+}
+
+fn is_http_url(value: &str) -> bool {
+    value
+        .strip_prefix("http://")
+        .or_else(|| value.strip_prefix("https://"))
+        .and_then(|rest| rest.split(['/', '?', '#']).next())
+        .is_some_and(|authority| !authority.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    include!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/tests/server/preflight_tests.rs"));
+}
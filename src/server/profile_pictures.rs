@@ -0,0 +1,279 @@
+//! `/chat/fetchProfilePictureUrl`: profile picture URLs, cached in Postgres.
+//!
+//! URLs come from a `w:profile:picture` IQ, for the preview thumbnail or the
+//! full image. They are signed CDN links that stop working at the unix time
+//! carried (in hex) by their `oe` parameter, so each answer is kept in
+//! `api_profile_pictures` per instance, JID and size until shortly before
+//! then, or for `PROFILE_PICTURE_CACHE_SECONDS` when the URL has no expiry.
+//! "No picture" answers are cached for `PROFILE_PICTURE_CACHE_SECONDS` too.
+//!
+//! Answers are also kept in the hot [`cache`](super::cache), in memory
+//! and Redis, never past their expiry.
+//!
+//! Once connected, an instance looks up its own picture so the runtime can
+//! report `profilePicUrl`.
+
+use crate::api_store::ApiBind;
+use crate::client::Client;
+use crate::server::AppState;
+use crate::server::cache::CacheSpace;
+use crate::server::jid::{self, JidError};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::time::Duration;
+use thiserror::Error;
+use tracing::debug;
+use warp_core_binary::jid::Jid;
+
+pub use crate::config::ProfilePictureConfig;
+
+/// Cached URLs are dropped this long before the CDN expires them.
+const EXPIRY_MARGIN: Duration = Duration::from_secs(300);
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ProfilePictureError {
+    #[error("number is required")]
+    MissingNumber,
+    #[error(transparent)]
+    InvalidNumber(#[from] JidError),
+    #[error("invalid type {0:?}: expected preview or image")]
+    InvalidType(String),
+}
+
+/// Which picture to fetch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PictureSize {
+    /// 96x96 thumbnail.
+    Preview,
+    /// Full resolution.
+    Image,
+}
+
+impl PictureSize {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "preview" | "thumbnail" => Some(Self::Preview),
+            "image" | "full" => Some(Self::Image),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Preview => "preview",
+            Self::Image => "image",
+        }
+    }
+}
+
+/// Body of `/chat/fetchProfilePictureUrl`: `{"number", "type"?}`, the full
+/// image when `type` is omitted.
+pub fn request_from_body(body: &Value) -> Result<(Jid, PictureSize), ProfilePictureError> {
+    let number = body
+        .get("number")
+        .and_then(Value::as_str)
+        .filter(|raw| !raw.trim().is_empty())
+        .ok_or(ProfilePictureError::MissingNumber)?;
+    let size = match body.get("type").and_then(Value::as_str) {
+        None => PictureSize::Image,
+        Some(raw) => PictureSize::parse(raw)
+            .ok_or_else(|| ProfilePictureError::InvalidType(raw.to_string()))?,
+    };
+    Ok((jid::parse(number)?.to_non_ad(), size))
+}
+
+/// Answer of `/chat/fetchProfilePictureUrl`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProfilePictureUrl {
+    pub wuid: String,
+    /// `None` when the contact has no picture or hides it from us.
+    pub profile_picture_url: Option<String>,
+    pub picture_id: Option<String>,
+    #[serde(rename = "type")]
+    pub size: PictureSize,
+    /// When the cached answer stops being served.
+    pub expires_at: Option<DateTime<Utc>>,
+    pub cached: bool,
+}
+
+/// Expiry signed into a WhatsApp CDN URL (`oe`, hex unix seconds).
+pub fn url_expiry(url: &str) -> Option<DateTime<Utc>> {
+    let (_, query) = url.split_once('?')?;
+    let oe = query.split('&').find_map(|pair| pair.strip_prefix("oe="))?;
+    let secs = i64::from_str_radix(oe, 16).ok()?;
+    DateTime::from_timestamp(secs, 0)
+}
+
+/// Until when an answer fetched at `now` may be served; `None` when it must
+/// not be cached.
+pub fn cache_until(url: Option<&str>, now: DateTime<Utc>, ttl: Duration) -> Option<DateTime<Utc>> {
+    if ttl.is_zero() {
+        return None;
+    }
+    let until = match url.and_then(url_expiry) {
+        Some(expiry) => expiry - chrono::Duration::from_std(EXPIRY_MARGIN).ok()?,
+        None => now + chrono::Duration::from_std(ttl).ok()?,
+    };
+    (until > now).then_some(until)
+}
+
+/// The picture of `jid`, from the cache or a fresh IQ.
+pub async fn fetch(
+    state: &AppState,
+    client: &Client,
+    session: &str,
+    jid: &Jid,
+    size: PictureSize,
+) -> anyhow::Result<ProfilePictureUrl> {
+    let ttl = state.profile_pictures.ttl;
+    let cache_key = format!("{jid}:{}", size.as_str());
+    if !ttl.is_zero() {
+        if let Some(hot) = state
+            .cache
+            .get::<ProfilePictureUrl>(CacheSpace::ProfilePictures, session, &cache_key)
+            .await
+            .filter(|hot| hot.expires_at.is_some_and(|at| at > Utc::now()))
+        {
+            return Ok(hot);
+        }
+        // A cache that cannot be read (no Postgres) only costs an IQ.
+        match cached(state, session, jid, size).await {
+            Ok(Some(cached)) => {
+                keep_hot(state, session, &cache_key, &cached).await;
+                return Ok(cached);
+            }
+            Ok(None) => {}
+            Err(e) => debug!(session, error = %e, "Cache de fotos de perfil indisponível"),
+        }
+    }
+
+    let picture = client
+        .contacts()
+        .get_profile_picture(jid, size == PictureSize::Preview)
+        .await?;
+    let url = picture.as_ref().map(|p| p.url.clone());
+    let picture_id = picture.map(|p| p.id).filter(|id| !id.is_empty());
+    let expires_at = cache_until(url.as_deref(), Utc::now(), ttl);
+
+    if let Some(expires_at) = expires_at
+        && let Err(e) = state
+            .api_store
+            .execute(
+                "INSERT INTO api_profile_pictures (session, jid, size, url, picture_id, expires_at) \
+                 VALUES ($1, $2, $3, $4, $5, $6::timestamptz) \
+                 ON CONFLICT (session, jid, size) DO UPDATE \
+                    SET url = EXCLUDED.url, picture_id = EXCLUDED.picture_id, \
+                        expires_at = EXCLUDED.expires_at, fetched_at = now()",
+                vec![
+                    ApiBind::Text(session.to_string()),
+                    ApiBind::Text(jid.to_string()),
+                    ApiBind::Text(size.as_str().to_string()),
+                    ApiBind::NullableText(url.clone()),
+                    ApiBind::NullableText(picture_id.clone()),
+                    ApiBind::Text(expires_at.to_rfc3339()),
+                ],
+            )
+            .await
+    {
+        debug!(session, error = %e, "Falha ao salvar foto de perfil no cache");
+    }
+
+    let picture = ProfilePictureUrl {
+        wuid: jid.to_string(),
+        profile_picture_url: url,
+        picture_id,
+        size,
+        expires_at,
+        cached: false,
+    };
+    let hot = ProfilePictureUrl {
+        cached: true,
+        ..picture.clone()
+    };
+    keep_hot(state, session, &cache_key, &hot).await;
+    Ok(picture)
+}
+
+/// Puts `picture` in the hot cache until it expires.
+async fn keep_hot(state: &AppState, session: &str, key: &str, picture: &ProfilePictureUrl) {
+    let Some(remaining) = picture
+        .expires_at
+        .and_then(|at| (at - Utc::now()).to_std().ok())
+    else {
+        return;
+    };
+    state
+        .cache
+        .insert(
+            CacheSpace::ProfilePictures,
+            session,
+            key,
+            picture,
+            Some(remaining),
+        )
+        .await;
+}
+
+async fn cached(
+    state: &AppState,
+    session: &str,
+    jid: &Jid,
+    size: PictureSize,
+) -> anyhow::Result<Option<ProfilePictureUrl>> {
+    let rows = state
+        .api_store
+        .query_json(
+            "SELECT jsonb_build_object('url', url, 'pictureId', picture_id, \
+                'expiresAt', expires_at) as value \
+             FROM api_profile_pictures \
+             WHERE session = $1 AND jid = $2 AND size = $3 AND expires_at > now()",
+            vec![
+                ApiBind::Text(session.to_string()),
+                ApiBind::Text(jid.to_string()),
+                ApiBind::Text(size.as_str().to_string()),
+            ],
+        )
+        .await?;
+    Ok(rows.first().map(|row| ProfilePictureUrl {
+        wuid: jid.to_string(),
+        profile_picture_url: row["url"].as_str().map(str::to_string),
+        picture_id: row["pictureId"].as_str().map(str::to_string),
+        size,
+        expires_at: row["expiresAt"]
+            .as_str()
+            .and_then(|at| DateTime::parse_from_rfc3339(at).ok())
+            .map(|at| at.with_timezone(&Utc)),
+        cached: true,
+    }))
+}
+
+/// Looks up the picture of the account behind `instance_name` and keeps its
+/// URL in the instance runtime.
+pub async fn refresh_owner(state: &AppState, instance_name: &str) -> anyhow::Result<()> {
+    let Some(client) = state.clients.get(instance_name).map(|c| c.value().clone()) else {
+        return Ok(());
+    };
+    let Some(owner) = client.get_pn().await else {
+        return Ok(());
+    };
+    let picture = fetch(
+        state,
+        &client,
+        instance_name,
+        &owner.to_non_ad(),
+        PictureSize::Image,
+    )
+    .await?;
+    if let Some(mut runtime) = state.sessions_runtime.get_mut(instance_name) {
+        runtime.profile_pic_url = picture.profile_picture_url;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    include!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/tests/server/profile_pictures_tests.rs"));
+}
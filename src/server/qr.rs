@@ -0,0 +1,180 @@
+use crate::config::{DEFAULT_QR_IMAGE_SIZE, MAX_QR_IMAGE_SIZE, MIN_QR_IMAGE_SIZE};
+use image::Luma;
+use qrcode::QrCode;
+use qrcode::render::{svg, unicode};
+use thiserror::Error;
+
+/// Output variants supported by [`render_qr`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QrFormat {
+    /// PNG image bytes.
+    Png,
+    /// Standalone SVG document.
+    Svg,
+    /// Unicode half-block rendering, two modules per character row.
+    Terminal,
+    /// Compact ASCII rendering (`#` for dark modules).
+    Ascii,
+    /// The raw pairing string, without any rendering.
+    Raw,
+}
+
+impl QrFormat {
+    /// Parses the `format` query value used by the QR handlers.
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "png" | "image" => Some(Self::Png),
+            "svg" => Some(Self::Svg),
+            "terminal" | "unicode" | "utf8" => Some(Self::Terminal),
+            "ascii" | "compact" => Some(Self::Ascii),
+            "raw" | "text" => Some(Self::Raw),
+            _ => None,
+        }
+    }
+
+    /// Content type of the rendered output.
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::Png => "image/png",
+            Self::Svg => "image/svg+xml",
+            Self::Terminal | Self::Ascii | Self::Raw => "text/plain; charset=utf-8",
+        }
+    }
+
+    /// Next format to try when rendering in this one fails.
+    fn fallback(self) -> Option<Self> {
+        match self {
+            Self::Png => Some(Self::Svg),
+            Self::Svg | Self::Terminal | Self::Ascii => Some(Self::Raw),
+            Self::Raw => None,
+        }
+    }
+}
+
+/// Options accepted by [`render_qr`].
+#[derive(Debug, Clone, Copy)]
+pub struct QrRenderOptions {
+    pub format: QrFormat,
+    /// Target edge in pixels for PNG/SVG; ignored by the text formats.
+    pub size: u32,
+    /// Whether to keep the 4-module quiet zone around the code.
+    pub quiet_zone: bool,
+}
+
+impl Default for QrRenderOptions {
+    fn default() -> Self {
+        Self {
+            format: QrFormat::Png,
+            size: DEFAULT_QR_IMAGE_SIZE,
+            quiet_zone: true,
+        }
+    }
+}
+
+/// A rendered QR code together with the format that actually produced it.
+#[derive(Debug, Clone)]
+pub struct RenderedQr {
+    pub format: QrFormat,
+    pub body: Vec<u8>,
+}
+
+#[derive(Debug, Error)]
+pub enum QrRenderError {
+    #[error("qr encode failed: {0}")]
+    Encode(#[from] qrcode::types::QrError),
+    #[error("png encode failed: {0}")]
+    Png(#[from] image::ImageError),
+}
+
+/// Renders `code` in the requested format.
+///
+/// Sizes are clamped to [`MIN_QR_IMAGE_SIZE`]..=[`MAX_QR_IMAGE_SIZE`] so callers
+/// cannot request unbounded images.
+pub fn render_qr(code: &str, options: QrRenderOptions) -> Result<RenderedQr, QrRenderError> {
+    if options.format == QrFormat::Raw {
+        return Ok(RenderedQr {
+            format: QrFormat::Raw,
+            body: code.as_bytes().to_vec(),
+        });
+    }
+
+    let qr = QrCode::new(code.as_bytes())?;
+    let size = options.size.clamp(MIN_QR_IMAGE_SIZE, MAX_QR_IMAGE_SIZE);
+
+    let body = match options.format {
+        QrFormat::Png => {
+            let img = qr
+                .render::<Luma<u8>>()
+                .quiet_zone(options.quiet_zone)
+                .max_dimensions(size, size)
+                .build();
+            let mut buffer = std::io::Cursor::new(Vec::new());
+            img.write_to(&mut buffer, image::ImageFormat::Png)?;
+            buffer.into_inner()
+        }
+        QrFormat::Svg => qr
+            .render::<svg::Color<'_>>()
+            .quiet_zone(options.quiet_zone)
+            .max_dimensions(size, size)
+            .build()
+            .into_bytes(),
+        QrFormat::Terminal => qr
+            .render::<unicode::Dense1x2>()
+            .dark_color(unicode::Dense1x2::Light)
+            .light_color(unicode::Dense1x2::Dark)
+            .quiet_zone(options.quiet_zone)
+            .build()
+            .into_bytes(),
+        QrFormat::Ascii => qr
+            .render::<char>()
+            .dark_color('#')
+            .light_color(' ')
+            .quiet_zone(options.quiet_zone)
+            .build()
+            .into_bytes(),
+        QrFormat::Raw => code.as_bytes().to_vec(),
+    };
+
+    Ok(RenderedQr {
+        format: options.format,
+        body,
+    })
+}
+
+/// Splits an image file name such as `sales.png` into the instance name and
+/// its image format (`png` or `svg`).
+pub fn image_file(file: &str) -> Option<(&str, QrFormat)> {
+    let (name, extension) = file.rsplit_once('.')?;
+    let format = match extension.to_ascii_lowercase().as_str() {
+        "png" => QrFormat::Png,
+        "svg" => QrFormat::Svg,
+        _ => return None,
+    };
+    (!name.is_empty()).then_some((name, format))
+}
+
+/// Renders `code`, walking the fallback chain (PNG → SVG → raw) on failure.
+///
+/// Never fails: the raw pairing string is always a valid last resort.
+pub fn render_qr_with_fallback(code: &str, options: QrRenderOptions) -> RenderedQr {
+    let mut current = Some(options.format);
+    while let Some(format) = current {
+        match render_qr(code, QrRenderOptions { format, ..options }) {
+            Ok(rendered) => return rendered,
+            Err(err) => {
+                log::warn!("QR render as {:?} failed, falling back: {}", format, err);
+                current = format.fallback();
+            }
+        }
+    }
+
+    RenderedQr {
+        format: QrFormat::Raw,
+        body: code.as_bytes().to_vec(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    include!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/tests/server/qr_tests.rs"));
+}
@@ -40,6 +40,9 @@ pub trait Queue<J: QueueJob + Send + Sync> {
     async fn claim_batch(&self, limit: i64) -> anyhow::Result<Vec<J>>;
 }
 
+/// Tentativas de entrega antes de um webhook ir para a dead-letter.
+pub const MAX_WEBHOOK_ATTEMPTS: i32 = 5;
+
 /// Job específico da fila de webhooks (`webhook_outbox`).
 #[derive(Debug, Clone)]
 pub struct WebhookJob {
@@ -82,8 +85,9 @@ impl WebhookQueue {
     }
 
     /// Marca um webhook para nova tentativa, aplicando backoff incremental.
+    /// Ao esgotar as tentativas, o evento vai para `event_deadletters`.
     pub async fn mark_retry(&self, id: Uuid, attempts: i32, error: String) -> anyhow::Result<()> {
-        let (status, delay_seconds) = if attempts >= 5 {
+        let (status, delay_seconds) = if attempts >= MAX_WEBHOOK_ATTEMPTS {
             ("failed", 600)
         } else {
             ("pending", backoff_seconds(attempts))
@@ -92,10 +96,15 @@ impl WebhookQueue {
         self.state
             .api_store
             .execute(
-                "UPDATE webhook_outbox \
-                 SET status = $2, attempts = $3, last_error = $4, \
-                     next_attempt_at = now() + ($5 || ' seconds')::interval \
-                 WHERE id = $1",
+                "WITH updated AS ( \
+                    UPDATE webhook_outbox \
+                    SET status = $2, attempts = $3, last_error = $4, \
+                        next_attempt_at = now() + ($5 || ' seconds')::interval \
+                    WHERE id = $1 \
+                    RETURNING id, session, event, payload, status \
+                 ) \
+                 INSERT INTO event_deadletters (outbox_id, session, event, payload, reason, attempts) \
+                 SELECT id, session, event, payload, $4, $3 FROM updated WHERE status = 'failed'",
                 vec![
                     ApiBind::Uuid(id),
                     ApiBind::Text(status.to_string()),
@@ -0,0 +1,246 @@
+//! Operator quotas: instances per deployment and per workspace, messages per
+//! instance per day and media size per message.
+//!
+//! Limits live in the runtime config (`MAX_*` env vars, editable through
+//! `/manager/config`); 0 disables a limit. Usage is counted from
+//! `api_sessions` and the outgoing rows of `api_messages`, so it survives
+//! restarts and is shared by every replica.
+
+use crate::api_store::ApiBind;
+use crate::server::AppState;
+use crate::server::runtime_config::RuntimeConfig;
+use axum::{Json, http::StatusCode};
+use serde_json::{Value, json};
+use thiserror::Error;
+
+pub const BYTES_PER_MB: u64 = 1024 * 1024;
+
+/// Start of the current UTC day, as used by the daily message quota.
+const TODAY_UTC: &str = "date_trunc('day', now(), 'UTC')";
+
+#[derive(Debug, Error)]
+pub enum QuotaError {
+    #[error("deployment allows at most {limit} instances")]
+    Instances { limit: u32 },
+    #[error("workspace allows at most {limit} instances")]
+    WorkspaceInstances { limit: u32 },
+    #[error("instance may send at most {limit} messages per day")]
+    MessagesPerDay { limit: u32 },
+    #[error("media is {size} bytes, instance may send at most {limit} bytes")]
+    MediaSize { size: u64, limit: u64 },
+    #[error(transparent)]
+    Store(#[from] anyhow::Error),
+}
+
+impl QuotaError {
+    /// Name of the exceeded quota, as reported to API clients.
+    pub fn quota(&self) -> Option<&'static str> {
+        match self {
+            Self::Instances { .. } => Some("instances"),
+            Self::WorkspaceInstances { .. } => Some("workspace_instances"),
+            Self::MessagesPerDay { .. } => Some("messages_per_day"),
+            Self::MediaSize { .. } => Some("media_size"),
+            Self::Store(_) => None,
+        }
+    }
+
+    /// `403 quota_exceeded`, or `500 db_error` when usage could not be read.
+    pub fn response(&self) -> (StatusCode, Json<Value>) {
+        let limit = match self {
+            Self::Instances { limit }
+            | Self::WorkspaceInstances { limit }
+            | Self::MessagesPerDay { limit } => u64::from(*limit),
+            Self::MediaSize { limit, .. } => *limit,
+            Self::Store(e) => {
+                return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(json!({"error": "db_error", "details": e.to_string()})),
+                );
+            }
+        };
+        (
+            StatusCode::FORBIDDEN,
+            Json(json!({
+                "error": "quota_exceeded",
+                "quota": self.quota(),
+                "limit": limit,
+                "details": self.to_string(),
+            })),
+        )
+    }
+}
+
+/// Whether `used` units leave room for one more under `limit` (0 = unlimited).
+pub fn within(limit: u32, used: u64) -> bool {
+    limit == 0 || used < u64::from(limit)
+}
+
+/// Media size limit in bytes, `None` when unlimited.
+pub fn media_limit(config: &RuntimeConfig) -> Option<u64> {
+    (config.max_media_size_mb > 0).then(|| u64::from(config.max_media_size_mb) * BYTES_PER_MB)
+}
+
+/// Rejects media larger than the configured limit.
+pub fn check_media_size(config: &RuntimeConfig, size: u64) -> Result<(), QuotaError> {
+    match media_limit(config) {
+        Some(limit) if size > limit => Err(QuotaError::MediaSize { size, limit }),
+        _ => Ok(()),
+    }
+}
+
+/// Decoded size of inline base64 media (data URLs included), without decoding it.
+pub fn base64_size(input: &str) -> u64 {
+    let data = match input.strip_prefix("data:") {
+        Some(rest) => rest.split_once(',').map_or(rest, |(_, data)| data),
+        None => input,
+    };
+    let chars = data
+        .bytes()
+        .filter(|b| !b.is_ascii_whitespace() && *b != b'=')
+        .count();
+    (chars * 3 / 4) as u64
+}
+
+/// Checks the instance quotas before `session` is created. Updating an
+/// existing instance never counts against them.
+pub async fn check_new_instance(
+    state: &AppState,
+    session: &str,
+    workspace_id: Option<&str>,
+) -> Result<(), QuotaError> {
+    let config = state.runtime_config();
+    if config.max_instances == 0 && config.max_instances_per_workspace == 0 {
+        return Ok(());
+    }
+    let rows = state
+        .api_store
+        .query_json(
+            "SELECT jsonb_build_object( \
+                'exists', bool_or(session = $1), \
+                'total', COUNT(*), \
+                'workspace', COUNT(*) FILTER (WHERE workspace_id = $2::uuid) \
+            ) as value FROM api_sessions",
+            vec![
+                ApiBind::Text(session.to_string()),
+                ApiBind::NullableText(workspace_id.map(str::to_string)),
+            ],
+        )
+        .await?;
+    let usage = rows.into_iter().next().unwrap_or(Value::Null);
+    if usage["exists"].as_bool().unwrap_or(false) {
+        return Ok(());
+    }
+
+    if !within(config.max_instances, usage["total"].as_u64().unwrap_or(0)) {
+        return Err(QuotaError::Instances {
+            limit: config.max_instances,
+        });
+    }
+    if workspace_id.is_some()
+        && !within(
+            config.max_instances_per_workspace,
+            usage["workspace"].as_u64().unwrap_or(0),
+        )
+    {
+        return Err(QuotaError::WorkspaceInstances {
+            limit: config.max_instances_per_workspace,
+        });
+    }
+    Ok(())
+}
+
+/// Checks the daily message quota of `session` before one more is queued.
+pub async fn check_message(state: &AppState, session: &str) -> Result<(), QuotaError> {
+    let limit = state.runtime_config().max_messages_per_day;
+    if limit == 0 {
+        return Ok(());
+    }
+    let sent = messages_today(state, session).await?;
+    if within(limit, sent) {
+        Ok(())
+    } else {
+        Err(QuotaError::MessagesPerDay { limit })
+    }
+}
+
+async fn messages_today(state: &AppState, session: &str) -> anyhow::Result<u64> {
+    let rows = state
+        .api_store
+        .query_json(
+            &format!(
+                "SELECT to_jsonb(COUNT(*)) as value FROM api_messages \
+                 WHERE session = $1 AND from_me AND created_at >= {TODAY_UTC}"
+            ),
+            vec![ApiBind::Text(session.to_string())],
+        )
+        .await?;
+    Ok(rows.first().and_then(Value::as_u64).unwrap_or(0))
+}
+
+/// Limits plus current usage: instances overall and per workspace, and
+/// messages sent today by each instance.
+pub async fn usage(state: &AppState) -> anyhow::Result<Value> {
+    let config = state.runtime_config();
+    let instances = state
+        .api_store
+        .query_json(
+            &format!(
+                "SELECT jsonb_build_object( \
+                    'instance', s.session, \
+                    'workspaceId', s.workspace_id, \
+                    'messagesToday', ( \
+                        SELECT COUNT(*) FROM api_messages m \
+                        WHERE m.session = s.session AND m.from_me AND m.created_at >= {TODAY_UTC} \
+                    ) \
+                ) as value FROM api_sessions s ORDER BY s.session"
+            ),
+            vec![],
+        )
+        .await?;
+
+    let mut workspaces = serde_json::Map::new();
+    for instance in &instances {
+        if let Some(id) = instance["workspaceId"].as_str() {
+            let count = workspaces.get(id).and_then(Value::as_u64).unwrap_or(0);
+            workspaces.insert(id.to_string(), json!(count + 1));
+        }
+    }
+
+    Ok(json!({
+        "limits": {
+            "maxInstances": config.max_instances,
+            "maxInstancesPerWorkspace": config.max_instances_per_workspace,
+            "maxMessagesPerDay": config.max_messages_per_day,
+            "maxMediaSizeMb": config.max_media_size_mb,
+        },
+        "instances": instances.len(),
+        "workspaces": workspaces,
+        "perInstance": instances,
+    }))
+}
+
+/// Subset of [`usage`] published on the unauthenticated `/metrics`: limits,
+/// instance count and messages sent today per instance (no workspace ids).
+pub fn metrics(usage: &Value) -> Value {
+    let messages_today: serde_json::Map<String, Value> = usage["perInstance"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|instance| {
+            Some((
+                instance["instance"].as_str()?.to_string(),
+                instance["messagesToday"].clone(),
+            ))
+        })
+        .collect();
+    json!({
+        "limits": usage["limits"],
+        "instances": usage["instances"],
+        "messages_today": messages_today,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    include!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/tests/server/quotas_tests.rs"));
+}
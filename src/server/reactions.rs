@@ -0,0 +1,229 @@
+//! Reactions to messages, kept per target message.
+//!
+//! Every reaction that reaches an instance is stored in
+//! `api_message_reactions`, keyed by the WhatsApp id of the message it
+//! targets and by who reacted: a new emoji from the same reactor replaces
+//! the previous one, and an empty one (how WhatsApp removes a reaction)
+//! deletes it. Reactions older than the stored one are ignored, so
+//! out-of-order delivery cannot resurrect a removed reaction.
+//! `/chat/findMessages` returns them aggregated by emoji.
+
+use crate::api_store::ApiBind;
+use crate::client::Client;
+use crate::server::AppState;
+use crate::types::events::{Event, EventHandler};
+use crate::types::message::MessageInfo;
+use chrono::{DateTime, Utc};
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use waproto::whatsapp as wa;
+use warp_core::proto_helpers::MessageExt;
+
+/// A reaction set or removed by one reactor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReactionUpdate {
+    pub chat_id: String,
+    /// WhatsApp id of the message reacted to.
+    pub message_id: String,
+    pub reactor: String,
+    /// `None` when the reaction was removed.
+    pub emoji: Option<String>,
+    pub from_me: bool,
+    pub at: DateTime<Utc>,
+}
+
+impl ReactionUpdate {
+    /// The reaction carried by `message`, if it is one.
+    pub fn from_message(message: &wa::Message, info: &MessageInfo) -> Option<Self> {
+        let reaction = message.get_base_message().reaction_message.as_ref()?;
+        let message_id = reaction
+            .key
+            .as_ref()
+            .and_then(|key| key.id.clone())
+            .filter(|id| !id.is_empty())?;
+        Some(Self {
+            chat_id: info.source.chat.to_string(),
+            message_id,
+            reactor: info.source.sender.to_non_ad().to_string(),
+            emoji: reaction.text.clone().filter(|text| !text.is_empty()),
+            from_me: info.source.is_from_me,
+            at: reaction
+                .sender_timestamp_ms
+                .and_then(DateTime::from_timestamp_millis)
+                .unwrap_or(info.timestamp),
+        })
+    }
+
+    /// Maps a client event; `None` for anything but a reaction.
+    pub fn from_event(event: &Event) -> Option<Self> {
+        match event {
+            Event::Message(message, info) => Self::from_message(message, info),
+            _ => None,
+        }
+    }
+}
+
+struct ReactionEventForwarder {
+    tx: mpsc::UnboundedSender<ReactionUpdate>,
+}
+
+impl EventHandler for ReactionEventForwarder {
+    fn handle_event(&self, event: &Event) {
+        if let Some(update) = ReactionUpdate::from_event(event) {
+            let _ = self.tx.send(update);
+        }
+    }
+}
+
+/// Subscribes `instance_name` to the reactions received by `client`.
+pub fn attach(state: Arc<AppState>, instance_name: String, client: &Client) {
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    client
+        .core
+        .event_bus
+        .add_handler(Arc::new(ReactionEventForwarder { tx }));
+
+    tokio::spawn(async move {
+        while let Some(update) = rx.recv().await {
+            if let Err(e) = record(&state, &instance_name, &update).await {
+                log::warn!("Failed to record reaction for {}: {}", instance_name, e);
+            }
+        }
+    });
+}
+
+/// Stores or removes the reaction of `update.reactor`, unless a newer one
+/// is already stored.
+pub async fn record(
+    state: &AppState,
+    session: &str,
+    update: &ReactionUpdate,
+) -> anyhow::Result<()> {
+    let at = ApiBind::Text(update.at.to_rfc3339());
+    match &update.emoji {
+        Some(emoji) => {
+            state
+                .api_store
+                .execute(
+                    "INSERT INTO api_message_reactions \
+                        (session, chat_id, message_id, reactor, emoji, from_me, reacted_at) \
+                     VALUES ($1, $2, $3, $4, $5, $6, $7::timestamptz) \
+                     ON CONFLICT (session, message_id, reactor) DO UPDATE \
+                        SET emoji = EXCLUDED.emoji, reacted_at = EXCLUDED.reacted_at \
+                        WHERE api_message_reactions.reacted_at <= EXCLUDED.reacted_at",
+                    vec![
+                        ApiBind::Text(session.to_string()),
+                        ApiBind::Text(update.chat_id.clone()),
+                        ApiBind::Text(update.message_id.clone()),
+                        ApiBind::Text(update.reactor.clone()),
+                        ApiBind::Text(emoji.clone()),
+                        ApiBind::Bool(update.from_me),
+                        at,
+                    ],
+                )
+                .await?;
+        }
+        None => {
+            state
+                .api_store
+                .execute(
+                    "DELETE FROM api_message_reactions \
+                     WHERE session = $1 AND message_id = $2 AND reactor = $3 \
+                       AND reacted_at <= $4::timestamptz",
+                    vec![
+                        ApiBind::Text(session.to_string()),
+                        ApiBind::Text(update.message_id.clone()),
+                        ApiBind::Text(update.reactor.clone()),
+                        at,
+                    ],
+                )
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+/// Groups reaction rows (`emoji`, `reactor`, `fromMe`, oldest first) into
+/// `[{emoji, count, fromMe, reactors}]`, most used emoji first and ties in
+/// the order they first appeared.
+pub fn aggregate(rows: &[Value]) -> Vec<Value> {
+    let mut groups: Vec<(&str, Vec<&str>, bool)> = Vec::new();
+    for row in rows {
+        let Some(emoji) = row["emoji"].as_str() else {
+            continue;
+        };
+        let reactor = row["reactor"].as_str().unwrap_or_default();
+        let from_me = row["fromMe"].as_bool().unwrap_or(false);
+        match groups.iter_mut().find(|(e, _, _)| *e == emoji) {
+            Some((_, reactors, mine)) => {
+                reactors.push(reactor);
+                *mine |= from_me;
+            }
+            None => groups.push((emoji, vec![reactor], from_me)),
+        }
+    }
+    // Stable, so equal counts keep their first appearance order.
+    groups.sort_by(|a, b| b.1.len().cmp(&a.1.len()));
+    groups
+        .into_iter()
+        .map(|(emoji, reactors, from_me)| {
+            json!({
+                "emoji": emoji,
+                "count": reactors.len(),
+                "fromMe": from_me,
+                "reactors": reactors,
+            })
+        })
+        .collect()
+}
+
+/// Sets `reactions` on each of `messages`, matched by `key.id`.
+pub async fn attach_to_messages(
+    state: &AppState,
+    session: &str,
+    messages: &mut [Value],
+) -> anyhow::Result<()> {
+    let ids: Vec<&str> = messages
+        .iter()
+        .filter_map(|m| m["key"]["id"].as_str())
+        .collect();
+    let mut by_message: HashMap<String, Vec<Value>> = HashMap::new();
+    if !ids.is_empty() {
+        let rows = state
+            .api_store
+            .query_json(
+                "SELECT jsonb_build_object('messageId', message_id, 'emoji', emoji, \
+                    'reactor', reactor, 'fromMe', from_me) as value \
+                 FROM api_message_reactions \
+                 WHERE session = $1 AND message_id IN (SELECT jsonb_array_elements_text($2::jsonb)) \
+                 ORDER BY reacted_at",
+                vec![ApiBind::Text(session.to_string()), ApiBind::Json(json!(ids))],
+            )
+            .await?;
+        for row in rows {
+            let row = row.get("value").cloned().unwrap_or(row);
+            if let Some(id) = row["messageId"].as_str() {
+                by_message.entry(id.to_string()).or_default().push(row);
+            }
+        }
+    }
+    for message in messages.iter_mut() {
+        let rows = message["key"]["id"]
+            .as_str()
+            .and_then(|id| by_message.get(id))
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        let reactions = json!(aggregate(rows));
+        if let Some(message) = message.as_object_mut() {
+            message.insert("reactions".to_string(), reactions);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    include!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/tests/server/reactions_tests.rs"));
+}
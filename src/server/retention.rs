@@ -0,0 +1,449 @@
+//! Retention of stored messages, webhook logs and media.
+//!
+//! `RETENTION_*_DAYS` set how long the deployment keeps each kind of data;
+//! an instance may override any of them in `api_sessions.retention`
+//! (`PUT /instance/retention/:name`), `0` keeping that kind forever. Every
+//! `RETENTION_INTERVAL_MINUTES` the pruner deletes what is older, in
+//! batches of `RETENTION_BATCH_SIZE` rows so no statement holds locks for
+//! long, along with disappearing messages past their `expires_at`. Rows and
+//! bytes reclaimed are published in `/metrics` and `/metrics/prometheus`.
+//!
+//! Messages still waiting in the send queue are never pruned.
+
+use crate::api_store::ApiBind;
+use crate::config::MAX_RETENTION_DAYS;
+use crate::server::AppState;
+use crate::server::cache::CacheSpace;
+use crate::server::message_counters::Metric;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::{Value, json};
+use std::fmt::Write;
+use std::sync::{Arc, Mutex, PoisonError};
+use thiserror::Error;
+
+pub use crate::config::{RetentionConfig, RetentionPolicy};
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum RetentionError {
+    #[error("body must be an object with messagesDays, webhookLogsDays and/or mediaDays")]
+    InvalidBody,
+    #[error("{0} must be at most {MAX_RETENTION_DAYS}")]
+    TooLong(&'static str),
+}
+
+impl RetentionPolicy {
+    pub fn from_body(body: &Value) -> Result<Self, RetentionError> {
+        let policy: Self =
+            serde_json::from_value(body.clone()).map_err(|_| RetentionError::InvalidBody)?;
+        for kind in RetentionKind::ALL {
+            if policy
+                .get(kind)
+                .is_some_and(|days| days > MAX_RETENTION_DAYS)
+            {
+                return Err(RetentionError::TooLong(kind.field()));
+            }
+        }
+        Ok(policy)
+    }
+
+    pub fn get(&self, kind: RetentionKind) -> Option<u32> {
+        match kind {
+            RetentionKind::Messages => self.messages_days,
+            RetentionKind::WebhookLogs => self.webhook_logs_days,
+            RetentionKind::Media => self.media_days,
+        }
+    }
+
+    /// This policy with the unset kinds taken from `defaults`.
+    pub fn or(self, defaults: Self) -> Self {
+        Self {
+            messages_days: self.messages_days.or(defaults.messages_days),
+            webhook_logs_days: self.webhook_logs_days.or(defaults.webhook_logs_days),
+            media_days: self.media_days.or(defaults.media_days),
+        }
+    }
+
+    /// Days after which `kind` is pruned, `None` when it is kept forever.
+    pub fn days(&self, kind: RetentionKind) -> Option<u32> {
+        self.get(kind).filter(|days| *days > 0)
+    }
+}
+
+/// What the pruner deletes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RetentionKind {
+    /// `api_messages` rows other than stored media.
+    Messages,
+    /// `webhook_deliveries` and delivered or given up `webhook_outbox` rows.
+    WebhookLogs,
+    /// Media messages kept for `/chat/getBase64FromMediaMessage`.
+    Media,
+}
+
+/// `message_type` of the rows written by
+/// [`store_media_message`](crate::server::media::store_media_message).
+const MEDIA_TYPES: &str = "('image', 'video', 'audio', 'document', 'sticker')";
+
+impl RetentionKind {
+    pub const ALL: [Self; 3] = [Self::Messages, Self::WebhookLogs, Self::Media];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Messages => "messages",
+            Self::WebhookLogs => "webhook_logs",
+            Self::Media => "media",
+        }
+    }
+
+    fn field(self) -> &'static str {
+        match self {
+            Self::Messages => "messagesDays",
+            Self::WebhookLogs => "webhookLogsDays",
+            Self::Media => "mediaDays",
+        }
+    }
+
+    /// Tables rows are pruned from, as `(table, filter)`. Filters bind the
+    /// instance as `$1` and the days to keep as `$2`, `0` matching only
+    /// expired disappearing messages.
+    fn targets(self) -> Vec<(&'static str, String)> {
+        let aged = "($2 > 0 AND created_at < now() - make_interval(days => $2))";
+        match self {
+            Self::Messages => vec![(
+                "api_messages",
+                format!(
+                    "session = $1 AND status IS DISTINCT FROM 'queued' \
+                     AND message_type NOT IN {MEDIA_TYPES} AND ({aged} OR expires_at < now())"
+                ),
+            )],
+            Self::Media => vec![(
+                "api_messages",
+                format!(
+                    "session = $1 AND status IS DISTINCT FROM 'queued' \
+                     AND message_type IN {MEDIA_TYPES} AND ({aged} OR expires_at < now())"
+                ),
+            )],
+            Self::WebhookLogs => vec![
+                (
+                    "webhook_deliveries",
+                    format!("session IS NOT DISTINCT FROM $1 AND {aged}"),
+                ),
+                (
+                    "webhook_outbox",
+                    format!(
+                        "session IS NOT DISTINCT FROM $1 AND status IN ('sent', 'failed') AND {aged}"
+                    ),
+                ),
+            ],
+        }
+    }
+}
+
+/// Deletes one batch of `table` rows matching `filter`, reporting how many
+/// rows and bytes went away.
+fn prune_statement(table: &str, filter: &str) -> String {
+    format!(
+        "WITH doomed AS (SELECT id FROM {table} WHERE {filter} LIMIT $3), \
+         deleted AS ( \
+            DELETE FROM {table} t USING doomed WHERE t.id = doomed.id \
+            RETURNING pg_column_size(t.*) AS bytes \
+         ) \
+         SELECT jsonb_build_object('rows', count(*), 'bytes', COALESCE(sum(bytes), 0)) as value \
+         FROM deleted"
+    )
+}
+
+/// Rows and bytes deleted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct Reclaimed {
+    pub rows: u64,
+    pub bytes: u64,
+}
+
+impl Reclaimed {
+    fn from_row(row: &Value) -> Self {
+        let value = row.get("value").unwrap_or(row);
+        Self {
+            rows: value["rows"].as_u64().unwrap_or(0),
+            bytes: value["bytes"].as_u64().unwrap_or(0),
+        }
+    }
+
+    fn add(&mut self, other: Self) {
+        self.rows += other.rows;
+        self.bytes += other.bytes;
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RetentionTotals {
+    pub runs: u64,
+    pub last_run_at: Option<DateTime<Utc>>,
+    pub messages: Reclaimed,
+    pub webhook_logs: Reclaimed,
+    pub media: Reclaimed,
+}
+
+impl RetentionTotals {
+    pub fn get(&self, kind: RetentionKind) -> Reclaimed {
+        match kind {
+            RetentionKind::Messages => self.messages,
+            RetentionKind::WebhookLogs => self.webhook_logs,
+            RetentionKind::Media => self.media,
+        }
+    }
+
+    fn get_mut(&mut self, kind: RetentionKind) -> &mut Reclaimed {
+        match kind {
+            RetentionKind::Messages => &mut self.messages,
+            RetentionKind::WebhookLogs => &mut self.webhook_logs,
+            RetentionKind::Media => &mut self.media,
+        }
+    }
+}
+
+/// What the pruner reclaimed since the server started.
+#[derive(Debug, Default)]
+pub struct RetentionMetrics {
+    totals: Mutex<RetentionTotals>,
+}
+
+impl RetentionMetrics {
+    pub fn record(&self, kind: RetentionKind, reclaimed: Reclaimed) {
+        self.totals
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get_mut(kind)
+            .add(reclaimed);
+    }
+
+    pub fn finish_run(&self, at: DateTime<Utc>) {
+        let mut totals = self.totals.lock().unwrap_or_else(PoisonError::into_inner);
+        totals.runs += 1;
+        totals.last_run_at = Some(at);
+    }
+
+    pub fn snapshot(&self) -> RetentionTotals {
+        *self.totals.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Prometheus text exposition of `totals`.
+pub fn render_prometheus(totals: &RetentionTotals) -> String {
+    let mut out = String::new();
+    let counters: [Metric<Reclaimed>; 2] = [
+        (
+            "chatwarp_retention_rows_deleted_total",
+            "Rows deleted by the retention pruner since the server started.",
+            |r| r.rows,
+        ),
+        (
+            "chatwarp_retention_bytes_reclaimed_total",
+            "Bytes of rows deleted by the retention pruner since the server started.",
+            |r| r.bytes,
+        ),
+    ];
+    for (name, help, value) in counters {
+        let _ = writeln!(out, "# HELP {name} {help}\n# TYPE {name} counter");
+        for kind in RetentionKind::ALL {
+            let _ = writeln!(
+                out,
+                "{name}{{kind=\"{}\"}} {}",
+                kind.as_str(),
+                value(&totals.get(kind))
+            );
+        }
+    }
+    if let Some(last_run_at) = totals.last_run_at {
+        let name = "chatwarp_retention_last_run_timestamp_seconds";
+        let _ = writeln!(
+            out,
+            "# HELP {name} When the retention pruner last finished.\n# TYPE {name} gauge\n{name} {}",
+            last_run_at.timestamp()
+        );
+    }
+    out
+}
+
+/// Retention override of `session`: `None` when the instance does not
+/// exist, a default policy when it has none.
+pub async fn fetch(state: &AppState, session: &str) -> anyhow::Result<Option<RetentionPolicy>> {
+    let rows = state
+        .api_store
+        .query_json(
+            "SELECT jsonb_build_object('retention', retention) as value \
+             FROM api_sessions WHERE session = $1",
+            vec![ApiBind::Text(session.to_string())],
+        )
+        .await?;
+    Ok(rows.first().map(|row| {
+        let value = row.get("value").unwrap_or(row);
+        serde_json::from_value(value["retention"].clone()).unwrap_or_default()
+    }))
+}
+
+/// Saves (or clears, with `None`) the override of `session`. Returns
+/// whether the instance exists.
+pub async fn set(
+    state: &AppState,
+    session: &str,
+    policy: Option<&RetentionPolicy>,
+) -> anyhow::Result<bool> {
+    let policy = policy.map(serde_json::to_value).transpose()?;
+    let updated = state
+        .api_store
+        .execute(
+            "UPDATE api_sessions SET retention = $2, updated_at = now() WHERE session = $1",
+            vec![
+                ApiBind::Text(session.to_string()),
+                ApiBind::NullableJson(policy),
+            ],
+        )
+        .await?;
+    state.cache.invalidate_space(CacheSpace::Instances).await;
+    Ok(updated > 0)
+}
+
+/// Effective policy of every instance.
+async fn policies(state: &AppState) -> anyhow::Result<Vec<(String, RetentionPolicy)>> {
+    let defaults = state.retention.defaults;
+    let rows = state
+        .api_store
+        .query_json(
+            "SELECT jsonb_build_object('session', session, 'retention', retention) as value \
+             FROM api_sessions",
+            vec![],
+        )
+        .await?;
+    Ok(rows
+        .iter()
+        .filter_map(|row| {
+            let value = row.get("value").unwrap_or(row);
+            let policy: RetentionPolicy =
+                serde_json::from_value(value["retention"].clone()).unwrap_or_default();
+            Some((value["session"].as_str()?.to_string(), policy.or(defaults)))
+        })
+        .collect())
+}
+
+/// Deletes the `kind` rows of `session` (`None`: rows of no instance)
+/// older than `days`, batch by batch.
+async fn prune(
+    state: &AppState,
+    session: Option<&str>,
+    kind: RetentionKind,
+    days: u32,
+) -> anyhow::Result<Reclaimed> {
+    let batch_size = state.retention.batch_size;
+    let mut reclaimed = Reclaimed::default();
+    for (table, filter) in kind.targets() {
+        let sql = prune_statement(table, &filter);
+        loop {
+            let rows = state
+                .api_store
+                .query_json(
+                    &sql,
+                    vec![
+                        ApiBind::NullableText(session.map(str::to_string)),
+                        ApiBind::Int(i32::try_from(days).unwrap_or(i32::MAX)),
+                        ApiBind::Int(batch_size),
+                    ],
+                )
+                .await?;
+            let batch = rows.first().map(Reclaimed::from_row).unwrap_or_default();
+            reclaimed.add(batch);
+            if batch.rows < batch_size as u64 {
+                break;
+            }
+        }
+    }
+    Ok(reclaimed)
+}
+
+/// One pass over every instance.
+pub async fn run(state: &AppState) -> anyhow::Result<RetentionTotals> {
+    let mut pass = RetentionTotals::default();
+    let mut targets: Vec<(Option<String>, RetentionPolicy)> = policies(state)
+        .await?
+        .into_iter()
+        .map(|(session, policy)| (Some(session), policy))
+        .collect();
+    // Webhooks of the global URL belong to no instance.
+    targets.push((None, state.retention.defaults));
+
+    for (session, policy) in targets {
+        let kinds: &[RetentionKind] = if session.is_some() {
+            &RetentionKind::ALL
+        } else {
+            &[RetentionKind::WebhookLogs]
+        };
+        for &kind in kinds {
+            let days = match (policy.days(kind), kind) {
+                (Some(days), _) => days,
+                (None, RetentionKind::WebhookLogs) => continue,
+                // Expired disappearing messages go even without a retention.
+                (None, _) => 0,
+            };
+            match prune(state, session.as_deref(), kind, days).await {
+                Ok(reclaimed) => {
+                    state.retention_metrics.record(kind, reclaimed);
+                    pass.get_mut(kind).add(reclaimed);
+                }
+                Err(e) => tracing::warn!(
+                    session = session.as_deref().unwrap_or("-"),
+                    kind = kind.as_str(),
+                    error = %e,
+                    "Falha ao aplicar retenção"
+                ),
+            }
+        }
+    }
+    let now = Utc::now();
+    state.retention_metrics.finish_run(now);
+    pass.runs = 1;
+    pass.last_run_at = Some(now);
+    Ok(pass)
+}
+
+/// Runs the pruner every `RETENTION_INTERVAL_MINUTES`.
+pub fn spawn_pruner(state: Arc<AppState>) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            match run(&state).await {
+                Ok(pass) => {
+                    let rows: u64 = RetentionKind::ALL.iter().map(|k| pass.get(*k).rows).sum();
+                    if rows > 0 {
+                        tracing::info!(
+                            messages = pass.messages.rows,
+                            webhook_logs = pass.webhook_logs.rows,
+                            media = pass.media.rows,
+                            bytes = RetentionKind::ALL
+                                .iter()
+                                .map(|k| pass.get(*k).bytes)
+                                .sum::<u64>(),
+                            "Retenção aplicada"
+                        );
+                    }
+                }
+                Err(e) => tracing::warn!(error = %e, "Falha ao carregar políticas de retenção"),
+            }
+            tokio::time::sleep(state.retention.interval).await;
+        }
+    })
+}
+
+/// Body of the retention routes.
+pub fn response(instance: &str, policy: RetentionPolicy, defaults: RetentionPolicy) -> Value {
+    json!({
+        "instance": instance,
+        "retention": policy,
+        "effective": policy.or(defaults),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    include!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/tests/server/retention_tests.rs"));
+}
@@ -1,24 +1,65 @@
 use crate::api_store::ApiBind;
+use crate::server::cache::CacheSpace;
+use crate::server::qr::{self, QrFormat, QrRenderOptions};
 use crate::server::webhooks;
 use crate::server::AppState;
 use axum::{
     Json,
-    extract::{Path, State},
-    http::StatusCode,
-    response::IntoResponse,
+    extract::{Path, Query, State},
+    http::{StatusCode, header},
+    response::{IntoResponse, Response},
 };
+use serde::Deserialize;
 use serde_json::json;
 use std::sync::Arc;
 use warp_core::pair_code::PairCodeUtils;
 
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QrQuery {
+    pub format: Option<String>,
+    pub size: Option<u32>,
+    pub quiet_zone: Option<bool>,
+}
+
+/// Current pairing string for `session`, preferring the API runtime over the
+/// bot instance state.
+async fn current_qr(state: &AppState, session: &str) -> Option<String> {
+    let runtime_qr = state
+        .sessions_runtime
+        .get(session)
+        .and_then(|entry| entry.qr_code.clone());
+    if runtime_qr.is_some() {
+        return runtime_qr;
+    }
+
+    let qr_code = state.instances.get(session).map(|entry| entry.qr_code.clone())?;
+    let guard = qr_code.read().await;
+    guard.clone()
+}
+
+/// Returns the session QR as JSON, or rendered when `format` is given
+/// (`png`, `svg`, `terminal`, `ascii`, `raw`).
 pub async fn get_qr(
     State(state): State<Arc<AppState>>,
     Path(session): Path<String>,
-) -> impl IntoResponse {
-    let qr = state
-        .sessions_runtime
-        .get(&session)
-        .and_then(|entry| entry.qr_code.clone());
+    Query(query): Query<QrQuery>,
+) -> Response {
+    let format = match query.format.as_deref() {
+        Some(value) => match QrFormat::parse(value) {
+            Some(format) => Some(format),
+            None => {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    Json(json!({"error": "invalid_format", "format": value})),
+                )
+                    .into_response();
+            }
+        },
+        None => None,
+    };
+
+    let qr = current_qr(&state, &session).await;
 
     if let Some(qr_code) = qr {
         let _ = state
@@ -37,13 +78,33 @@ pub async fn get_qr(
         )
         .await;
 
-        return (StatusCode::OK, Json(json!({"session": session, "qr": qr_code})));
+        let Some(format) = format else {
+            return (StatusCode::OK, Json(json!({"session": session, "qr": qr_code})))
+                .into_response();
+        };
+
+        let defaults = QrRenderOptions::default();
+        let rendered = qr::render_qr_with_fallback(
+            &qr_code,
+            QrRenderOptions {
+                format,
+                size: query.size.unwrap_or(defaults.size),
+                quiet_zone: query.quiet_zone.unwrap_or(defaults.quiet_zone),
+            },
+        );
+        return (
+            StatusCode::OK,
+            [(header::CONTENT_TYPE, rendered.format.content_type())],
+            rendered.body,
+        )
+            .into_response();
     }
 
     (
         StatusCode::NOT_FOUND,
         Json(json!({"error": "qr_not_available"})),
     )
+        .into_response()
 }
 
 pub async fn request_code(
@@ -59,6 +120,7 @@ pub async fn request_code(
             vec![ApiBind::Text(session.clone()), ApiBind::Text(code.clone())],
         )
         .await;
+    state.cache.invalidate_space(CacheSpace::Instances).await;
 
     state
         .sessions_runtime
@@ -1,5 +1,10 @@
 use crate::api_store::ApiBind;
+use crate::models::api_model::FindMessages;
 use crate::server::AppState;
+use crate::server::messages_worker;
+use crate::server::outbox;
+use crate::server::pagination::{self, ListSpec, PageRequest, PaginationError, invalid_pagination};
+use crate::server::quotas;
 use crate::server::routes::helpers::{chat_id_from_body, session_from_body};
 use crate::server::webhooks;
 use axum::{Json, extract::State, http::StatusCode, response::IntoResponse};
@@ -49,31 +54,103 @@ async fn list_messages(
     state: &AppState,
     session: &str,
     chat_id: Option<String>,
-    limit: i64,
-    offset: i64,
-) -> anyhow::Result<Vec<Value>> {
-    let sql = if chat_id.is_some() {
-        "SELECT row_to_json(api_messages)::jsonb as value \
-         FROM api_messages \
-         WHERE session = $1 AND chat_id = $2 \
-         ORDER BY created_at DESC \
-         LIMIT $3 OFFSET $4"
+    page: &PageRequest,
+) -> anyhow::Result<(Vec<Value>, u64)> {
+    let from = if chat_id.is_some() {
+        "FROM api_messages WHERE session = $1 AND chat_id = $2"
     } else {
-        "SELECT row_to_json(api_messages)::jsonb as value \
-         FROM api_messages \
-         WHERE session = $1 \
-         ORDER BY created_at DESC \
-         LIMIT $3 OFFSET $4"
+        "FROM api_messages WHERE session = $1"
     };
 
     let mut binds = vec![ApiBind::Text(session.to_string())];
     if let Some(chat_id) = chat_id {
         binds.push(ApiBind::Text(chat_id));
     }
-    binds.push(ApiBind::Int(limit as i32));
-    binds.push(ApiBind::Int(offset as i32));
 
-    Ok(state.api_store.query_json(sql, binds).await?)
+    page.fetch(state, "row_to_json(api_messages)::jsonb", from, binds)
+        .await
+}
+
+/// Paging of `GET /messages` and `/chat/findMessages`.
+pub const MESSAGES: ListSpec = ListSpec {
+    default_limit: 50,
+    max_limit: 500,
+    sort_fields: &[("createdAt", "created_at")],
+    default_sort: "-createdAt",
+    tiebreak: &["id"],
+};
+
+/// Filters of `/chat/findMessages`, read from its [`FindMessages`] body.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MessageFilter {
+    pub chat_id: Option<String>,
+    /// WhatsApp id of the message.
+    pub message_id: Option<String>,
+    pub page: PageRequest,
+}
+
+impl MessageFilter {
+    /// Out-of-range `limit` and `offset` are clamped rather than rejected,
+    /// as `/chat/findMessages` always accepted them.
+    pub fn from_request(body: &FindMessages) -> Result<Self, PaginationError> {
+        let key = &body.filter.key;
+        let text = |value: &Option<String>| {
+            value
+                .as_deref()
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(str::to_string)
+        };
+        let page = PageRequest::from_lookup(&MESSAGES, |name| match name {
+            "limit" => body
+                .limit
+                .map(|v| v.clamp(1, i64::from(MESSAGES.max_limit)).to_string()),
+            "offset" => body
+                .offset
+                .map(|v| v.clamp(0, i64::from(i32::MAX)).to_string()),
+            "page" => body.page.map(|v| v.to_string()),
+            "cursor" => body.cursor.clone(),
+            "sort" => body.sort.clone(),
+            _ => None,
+        })?;
+        Ok(Self {
+            chat_id: text(&key.remote_jid),
+            message_id: text(&key.id),
+            page,
+        })
+    }
+}
+
+/// Stored messages of `session` matching `filter`, newest first unless
+/// sorted otherwise, shaped as `{id, key: {remoteJid, fromMe, id},
+/// messageType, message, status, createdAt}`, with the number of matches.
+pub(crate) async fn find_messages(
+    state: &AppState,
+    session: &str,
+    filter: &MessageFilter,
+) -> anyhow::Result<(Vec<Value>, u64)> {
+    filter
+        .page
+        .fetch(
+            state,
+            "jsonb_build_object( \
+                'id', id, \
+                'key', jsonb_build_object('remoteJid', chat_id, 'fromMe', from_me, \
+                    'id', COALESCE(wa_message_id, payload->>'messageId')), \
+                'messageType', message_type, 'message', payload, \
+                'status', COALESCE(delivery_status, status), 'createdAt', created_at \
+             )",
+            "FROM api_messages \
+             WHERE session = $1 \
+               AND ($2::text IS NULL OR chat_id = $2) \
+               AND ($3::text IS NULL OR wa_message_id = $3 OR payload->>'messageId' = $3)",
+            vec![
+                ApiBind::Text(session.to_string()),
+                ApiBind::NullableText(filter.chat_id.clone()),
+                ApiBind::NullableText(filter.message_id.clone()),
+            ],
+        )
+        .await
 }
 
 pub async fn send_message(
@@ -141,7 +218,9 @@ pub async fn send_message(
         }
     }
 
-    let has_media = body.get("base64").is_some() || body.get("url").is_some();
+    let has_media = ["base64", "url", "mediaId"]
+        .iter()
+        .any(|key| body.get(*key).is_some());
     let text_value = body
         .get("text")
         .and_then(|v| v.as_str())
@@ -227,6 +306,9 @@ pub async fn send_buttons(
     State(state): State<Arc<AppState>>,
     Json(body): Json<Value>,
 ) -> impl IntoResponse {
+    if let Some(response) = check_interactive("buttons", &body) {
+        return response;
+    }
     send_message_type(state, body, "buttons", true).await
 }
 
@@ -234,9 +316,33 @@ pub async fn send_list(
     State(state): State<Arc<AppState>>,
     Json(body): Json<Value>,
 ) -> impl IntoResponse {
+    if let Some(response) = check_interactive("list", &body) {
+        return response;
+    }
     send_message_type(state, body, "list", true).await
 }
 
+/// Rejects `buttons` and `list` bodies the messages worker could not build,
+/// so they fail with a 400 instead of after being queued. Returns the 400
+/// to answer with.
+pub(crate) fn check_interactive(
+    message_type: &str,
+    body: &Value,
+) -> Option<axum::response::Response> {
+    let (valid, error) = match message_type {
+        "buttons" => (
+            messages_worker::build_buttons_message(body).is_some(),
+            "invalid_buttons",
+        ),
+        "list" => (
+            messages_worker::build_list_message(body).is_some(),
+            "invalid_list",
+        ),
+        _ => return None,
+    };
+    (!valid).then(|| (StatusCode::BAD_REQUEST, Json(json!({"error": error}))).into_response())
+}
+
 pub async fn send_poll(
     State(state): State<Arc<AppState>>,
     Json(body): Json<Value>,
@@ -272,12 +378,26 @@ pub async fn send_location(
     send_message_type(state, body, "location", false).await
 }
 
-async fn send_message_type(
+pub(crate) async fn send_message_type(
     state: Arc<AppState>,
     body: Value,
     message_type: &str,
     send_event: bool,
 ) -> axum::response::Response {
+    match queue_message(state, body, message_type, send_event).await {
+        Ok(message) => (StatusCode::OK, Json(message)).into_response(),
+        Err(response) => response,
+    }
+}
+
+/// Checks quotas, stores the message as `queued` and wakes the messages
+/// worker. Returns the stored row, or the error response to answer with.
+pub(crate) async fn queue_message(
+    state: Arc<AppState>,
+    body: Value,
+    message_type: &str,
+    send_event: bool,
+) -> Result<Value, axum::response::Response> {
     let session = session_from_body(&body);
     let chat_id = chat_id_from_body(&body);
 
@@ -288,6 +408,20 @@ async fn send_message_type(
         "Requisição para enviar mensagem de tipo específico recebida"
     );
 
+    let media_size = body
+        .get("base64")
+        .and_then(|v| v.as_str())
+        .map(quotas::base64_size);
+    if let Some(size) = media_size
+        && let Err(e) = quotas::check_media_size(&state.runtime_config(), size)
+    {
+        return Err(e.response().into_response());
+    }
+    if let Err(e) = quotas::check_message(&state, &session).await {
+        warn!(session = %session, error = %e, "Mensagem recusada por cota");
+        return Err(e.response().into_response());
+    }
+
     match insert_message(
         &state,
         &session,
@@ -336,7 +470,7 @@ async fn send_message_type(
                 }
             });
 
-            (StatusCode::OK, Json(message)).into_response()
+            Ok(message)
         }
         Err(err) => {
             error!(
@@ -344,11 +478,11 @@ async fn send_message_type(
                 error = %err,
                 "Falha ao inserir mensagem no banco de dados"
             );
-            (
+            Err((
                 StatusCode::INTERNAL_SERVER_ERROR,
                 Json(json!({"error": "db_error", "details": err.to_string()})),
             )
-                .into_response()
+                .into_response())
         }
     }
 }
@@ -371,13 +505,22 @@ pub async fn send_seen(
         );
     };
 
-    let result = state
-        .api_store
-        .execute(
-            "UPDATE api_messages SET status = 'seen' WHERE id = $1",
+    let event = outbox::prepare(
+        &state,
+        Some(&session),
+        "MESSAGES_UPDATE",
+        json!({"id": message_id}),
+    )
+    .await;
+    let result = outbox::commit(
+        &state,
+        vec![(
+            "UPDATE api_messages SET status = 'seen' WHERE id = $1".to_string(),
             vec![ApiBind::Uuid(message_id)],
-        )
-        .await;
+        )],
+        vec![event],
+    )
+    .await;
 
     if let Err(err) = result {
         return (
@@ -386,14 +529,6 @@ pub async fn send_seen(
         );
     }
 
-    webhooks::enqueue(
-        &state,
-        Some(&session),
-        "MESSAGES_UPDATE",
-        json!({"id": message_id}),
-    )
-    .await;
-
     (
         StatusCode::OK,
         Json(json!({"status": "seen", "id": message_id})),
@@ -459,17 +594,16 @@ pub async fn list_messages_handler(
         .cloned()
         .unwrap_or_else(|| "default".to_string());
     let chat_id = params.get("chatId").cloned();
-    let limit = params
-        .get("limit")
-        .and_then(|v| v.parse::<i64>().ok())
-        .unwrap_or(50);
-    let offset = params
-        .get("offset")
-        .and_then(|v| v.parse::<i64>().ok())
-        .unwrap_or(0);
-
-    match list_messages(&state, &session, chat_id, limit, offset).await {
-        Ok(rows) => (StatusCode::OK, Json(json!(rows))),
+    let page = match PageRequest::from_query(&MESSAGES, &params) {
+        Ok(page) => page,
+        Err(e) => return invalid_pagination(e),
+    };
+
+    match list_messages(&state, &session, chat_id, &page).await {
+        Ok((rows, total)) => {
+            let info = page.info(rows.len(), total);
+            (StatusCode::OK, Json(pagination::envelope(rows, &info)))
+        }
         Err(err) => (
             StatusCode::INTERNAL_SERVER_ERROR,
             Json(json!({"error": "db_error", "details": err.to_string()})),
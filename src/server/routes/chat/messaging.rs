@@ -1,29 +1,55 @@
 use crate::api_store::ApiBind;
+use crate::server::jid;
+use crate::server::outbox;
+use crate::server::pagination::{self, ListSpec, PageRequest, invalid_pagination};
 use crate::server::webhooks;
 use crate::server::AppState;
-use axum::{Json, extract::{Path, State}, http::StatusCode, response::IntoResponse};
-use serde_json::json;
+use crate::server::routes::chat::chat_manager;
+use axum::{Json, extract::{Path, Query, State}, http::StatusCode, response::IntoResponse};
+use serde_json::{Value, json};
+use std::collections::HashMap;
 use std::sync::Arc;
 use tracing::info;
 
+/// Paging of `GET /:session/chats`.
+pub const CHATS: ListSpec = ListSpec {
+    default_limit: 50,
+    max_limit: 500,
+    sort_fields: &[
+        ("lastMessageAt", "last_message_at"),
+        ("title", "title"),
+        ("unreadCount", "unread_count"),
+        ("id", "id"),
+    ],
+    default_sort: "-lastMessageAt",
+    tiebreak: &["id"],
+};
+
 pub async fn list_chats(
     State(state): State<Arc<AppState>>,
     Path(session): Path<String>,
+    Query(query): Query<HashMap<String, String>>,
 ) -> impl IntoResponse {
     info!(session = %session, "Listando conversas");
+    let page = match PageRequest::from_query(&CHATS, &query) {
+        Ok(page) => page,
+        Err(e) => return invalid_pagination(e),
+    };
     let session_name = session.clone();
-    let rows = state
-        .api_store
-        .query_json(
-            "SELECT row_to_json(api_chats)::jsonb as value FROM api_chats WHERE session = $1 ORDER BY last_message_at DESC",
+    let rows = page
+        .fetch(
+            &state,
+            "row_to_json(api_chats)::jsonb",
+            "FROM api_chats WHERE session = $1",
             vec![ApiBind::Text(session)],
         )
         .await;
 
     match rows {
-        Ok(rows) => {
+        Ok((rows, total)) => {
             webhooks::enqueue(&state, Some(&session_name), "CHATS_SET", json!({"count": rows.len()})).await;
-            (StatusCode::OK, Json(json!(rows)))
+            let info = page.info(rows.len(), total);
+            (StatusCode::OK, Json(pagination::envelope(rows, &info)))
         }
         Err(err) => (
             StatusCode::INTERNAL_SERVER_ERROR,
@@ -60,22 +86,45 @@ pub async fn overview(
     }
 }
 
+fn invalid_chat_id(err: jid::JidError) -> (StatusCode, Json<Value>) {
+    (
+        StatusCode::BAD_REQUEST,
+        Json(json!({"error": "invalid_chat_id", "details": err.to_string()})),
+    )
+}
+
+/// Messages of a chat, matching both its phone-number and LID forms.
 pub async fn messages(
     State(state): State<Arc<AppState>>,
     Path((session, chat_id)): Path<(String, String)>,
+    Query(query): Query<HashMap<String, String>>,
 ) -> impl IntoResponse {
-    let rows = state
-        .api_store
-        .query_json(
-            "SELECT row_to_json(api_messages)::jsonb as value \
-             FROM api_messages WHERE session = $1 AND chat_id = $2 \
-             ORDER BY created_at DESC",
-            vec![ApiBind::Text(session), ApiBind::Text(chat_id)],
+    let page = match PageRequest::from_query(&chat_manager::MESSAGES, &query) {
+        Ok(page) => page,
+        Err(e) => return invalid_pagination(e),
+    };
+    let (chat_id, alternate) = match jid::chat_aliases(&state, &session, &chat_id).await {
+        Ok(aliases) => aliases,
+        Err(err) => return invalid_chat_id(err),
+    };
+    let rows = page
+        .fetch(
+            &state,
+            "row_to_json(api_messages)::jsonb",
+            "FROM api_messages WHERE session = $1 AND (chat_id = $2 OR chat_id = $3)",
+            vec![
+                ApiBind::Text(session),
+                ApiBind::Text(chat_id),
+                ApiBind::NullableText(alternate),
+            ],
         )
         .await;
 
     match rows {
-        Ok(rows) => (StatusCode::OK, Json(json!(rows))),
+        Ok((rows, total)) => {
+            let info = page.info(rows.len(), total);
+            (StatusCode::OK, Json(pagination::envelope(rows, &info)))
+        }
         Err(err) => (
             StatusCode::INTERNAL_SERVER_ERROR,
             Json(json!({"error": "db_error", "details": err.to_string()})),
@@ -88,13 +137,32 @@ pub async fn read_messages(
     Path((session, chat_id)): Path<(String, String)>,
 ) -> impl IntoResponse {
     info!(session = %session, chat_id = %chat_id, "Marcando mensagens como lidas");
-    let result = state
-        .api_store
-        .execute(
-            "UPDATE api_messages SET status = 'read' WHERE session = $1 AND chat_id = $2",
-            vec![ApiBind::Text(session.clone()), ApiBind::Text(chat_id.clone())],
-        )
-        .await;
+    let (chat_id, alternate) = match jid::chat_aliases(&state, &session, &chat_id).await {
+        Ok(aliases) => aliases,
+        Err(err) => return invalid_chat_id(err),
+    };
+    let event = outbox::prepare(
+        &state,
+        Some(&session),
+        "MESSAGES_UPDATE",
+        json!({"chat_id": chat_id.clone()}),
+    )
+    .await;
+    let result = outbox::commit(
+        &state,
+        vec![(
+            "UPDATE api_messages SET status = 'read' \
+             WHERE session = $1 AND (chat_id = $2 OR chat_id = $3)"
+                .to_string(),
+            vec![
+                ApiBind::Text(session.clone()),
+                ApiBind::Text(chat_id.clone()),
+                ApiBind::NullableText(alternate),
+            ],
+        )],
+        vec![event],
+    )
+    .await;
 
     if let Err(err) = result {
         return (
@@ -103,13 +171,5 @@ pub async fn read_messages(
         );
     }
 
-    webhooks::enqueue(
-        &state,
-        Some(&session),
-        "MESSAGES_UPDATE",
-        json!({"chat_id": chat_id}),
-    )
-    .await;
-
     (StatusCode::OK, Json(json!({"status": "read"})))
 }
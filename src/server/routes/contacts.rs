@@ -1,20 +1,34 @@
 use crate::api_store::ApiBind;
 use crate::server::AppState;
+use crate::server::pagination::{self, ListSpec, PageRequest, invalid_pagination};
 use crate::server::webhooks;
 use axum::{Json, extract::{Query, State}, http::StatusCode, response::IntoResponse};
 use serde_json::json;
 use std::collections::HashMap;
 use std::sync::Arc;
 
+/// Paging of `GET /contacts` and `GET /contacts/all`.
+pub const CONTACTS: ListSpec = ListSpec {
+    default_limit: 100,
+    max_limit: 1000,
+    sort_fields: &[("name", "name"), ("id", "id"), ("updatedAt", "updated_at")],
+    default_sort: "name",
+    tiebreak: &["session", "id"],
+};
+
 pub async fn list_contacts(
     State(state): State<Arc<AppState>>,
     Query(params): Query<HashMap<String, String>>,
 ) -> impl IntoResponse {
+    let page = match PageRequest::from_query(&CONTACTS, &params) {
+        Ok(page) => page,
+        Err(e) => return invalid_pagination(e),
+    };
     let session = params.get("session").cloned();
-    let sql = if session.is_some() {
-        "SELECT row_to_json(api_contacts)::jsonb as value FROM api_contacts WHERE session = $1"
+    let from = if session.is_some() {
+        "FROM api_contacts WHERE session = $1"
     } else {
-        "SELECT row_to_json(api_contacts)::jsonb as value FROM api_contacts"
+        "FROM api_contacts"
     };
     let binds = if let Some(session) = session {
         vec![ApiBind::Text(session)]
@@ -22,8 +36,14 @@ pub async fn list_contacts(
         vec![]
     };
 
-    match state.api_store.query_json(sql, binds).await {
-        Ok(rows) => (StatusCode::OK, Json(json!(rows))),
+    match page
+        .fetch(&state, "row_to_json(api_contacts)::jsonb", from, binds)
+        .await
+    {
+        Ok((rows, total)) => {
+            let info = page.info(rows.len(), total);
+            (StatusCode::OK, Json(pagination::envelope(rows, &info)))
+        }
         Err(err) => (
             StatusCode::INTERNAL_SERVER_ERROR,
             Json(json!({"error": "db_error", "details": err.to_string()})),
@@ -33,15 +53,25 @@ pub async fn list_contacts(
 
 pub async fn list_contacts_all(
     State(state): State<Arc<AppState>>,
+    Query(params): Query<HashMap<String, String>>,
 ) -> impl IntoResponse {
-    match state
-        .api_store
-        .query_json("SELECT row_to_json(api_contacts)::jsonb as value FROM api_contacts", vec![])
+    let page = match PageRequest::from_query(&CONTACTS, &params) {
+        Ok(page) => page,
+        Err(e) => return invalid_pagination(e),
+    };
+    match page
+        .fetch(
+            &state,
+            "row_to_json(api_contacts)::jsonb",
+            "FROM api_contacts",
+            vec![],
+        )
         .await
     {
-        Ok(rows) => {
+        Ok((rows, total)) => {
             webhooks::enqueue(&state, None, "CONTACTS_SET", json!({"count": rows.len()})).await;
-            (StatusCode::OK, Json(json!(rows)))
+            let info = page.info(rows.len(), total);
+            (StatusCode::OK, Json(pagination::envelope(rows, &info)))
         }
         Err(err) => (
             StatusCode::INTERNAL_SERVER_ERROR,
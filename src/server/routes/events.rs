@@ -1,4 +1,5 @@
 use crate::api_store::ApiBind;
+use crate::server::pagination::{ListSpec, PageRequest, invalid_pagination};
 use crate::server::webhooks;
 use crate::server::AppState;
 use axum::{
@@ -10,52 +11,55 @@ use axum::{
 use serde_json::{Value, json};
 use std::sync::Arc;
 
+/// Paging of `GET /:session/events`.
+pub const EVENTS: ListSpec = ListSpec {
+    default_limit: 50,
+    max_limit: 500,
+    sort_fields: &[("createdAt", "created_at"), ("event", "event")],
+    default_sort: "-createdAt",
+    tiebreak: &["id"],
+};
+
 pub async fn get_events(
     State(state): State<Arc<AppState>>,
     Path(session): Path<String>,
     Query(params): Query<std::collections::HashMap<String, String>>,
 ) -> impl IntoResponse {
+    let page = match PageRequest::from_query(&EVENTS, &params) {
+        Ok(page) => page,
+        Err(e) => return invalid_pagination(e),
+    };
     let event_type = params.get("type").cloned();
-    let limit = params
-        .get("limit")
-        .and_then(|v| v.parse::<i32>().ok())
-        .unwrap_or(50);
-    let offset = params
-        .get("offset")
-        .and_then(|v| v.parse::<i32>().ok())
-        .unwrap_or(0);
 
-    let (sql, binds) = if let Some(t) = event_type {
+    let (from, binds) = if let Some(t) = event_type {
         (
-            "SELECT id, session, event, payload, created_at \
-             FROM api_events \
-             WHERE session = $1 AND event = $2 \
-             ORDER BY created_at DESC \
-             LIMIT $3 OFFSET $4",
-            vec![
-                ApiBind::Text(session),
-                ApiBind::Text(t),
-                ApiBind::Int(limit),
-                ApiBind::Int(offset),
-            ],
+            "FROM api_events WHERE session = $1 AND event = $2",
+            vec![ApiBind::Text(session), ApiBind::Text(t)],
         )
     } else {
         (
-            "SELECT id, session, event, payload, created_at \
-             FROM api_events \
-             WHERE session = $1 \
-             ORDER BY created_at DESC \
-             LIMIT $2 OFFSET $3",
-            vec![
-                ApiBind::Text(session),
-                ApiBind::Int(limit),
-                ApiBind::Int(offset),
-            ],
+            "FROM api_events WHERE session = $1",
+            vec![ApiBind::Text(session)],
         )
     };
 
-    match state.api_store.query_json(sql, binds).await {
-        Ok(rows) => (StatusCode::OK, Json(json!({ "events": rows }))),
+    match page
+        .fetch(
+            &state,
+            "jsonb_build_object('id', id, 'session', session, 'event', event, \
+                'payload', payload, 'created_at', created_at)",
+            from,
+            binds,
+        )
+        .await
+    {
+        Ok((rows, total)) => {
+            let info = page.info(rows.len(), total);
+            (
+                StatusCode::OK,
+                Json(json!({ "events": rows, "pagination": info })),
+            )
+        }
         Err(err) => (
             StatusCode::INTERNAL_SERVER_ERROR,
             Json(json!({"error": "db_error", "details": err.to_string()})),
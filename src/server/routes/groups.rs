@@ -1,8 +1,11 @@
 use crate::api_store::ApiBind;
+use crate::server::cache::CacheSpace;
+use crate::server::pagination::{self, ListSpec, PageRequest, invalid_pagination};
 use crate::server::webhooks;
 use crate::server::AppState;
-use axum::{Json, extract::{Path, State}, http::StatusCode, response::IntoResponse};
+use axum::{Json, extract::{Path, Query, State}, http::StatusCode, response::IntoResponse};
 use serde_json::{Value, json};
+use std::collections::HashMap;
 use std::sync::Arc;
 use uuid::Uuid;
 
@@ -52,10 +55,25 @@ pub async fn create_group(
     (StatusCode::OK, Json(json!({"id": id})))
 }
 
+/// Paging of `GET /:session/groups`, applied to the groups the account
+/// takes part in.
+pub const GROUPS: ListSpec = ListSpec {
+    default_limit: 100,
+    max_limit: 1000,
+    sort_fields: &[("groupName", "groupName"), ("jid", "jid")],
+    default_sort: "groupName",
+    tiebreak: &["jid"],
+};
+
 pub async fn list_groups(
     State(state): State<Arc<AppState>>,
     Path(session): Path<String>,
+    Query(query): Query<HashMap<String, String>>,
 ) -> impl IntoResponse {
+    let page = match PageRequest::from_query(&GROUPS, &query) {
+        Ok(page) => page,
+        Err(e) => return invalid_pagination(e),
+    };
     let Some(client_ref) = state.clients.get(&session) else {
         return (
             StatusCode::NOT_FOUND,
@@ -66,6 +84,16 @@ pub async fn list_groups(
     let client = client_ref.value().clone();
     drop(client_ref);
 
+    if let Some(list) = state
+        .cache
+        .get::<Vec<Value>>(CacheSpace::Groups, &session, "list")
+        .await
+    {
+        let (list, total) = page.apply(list);
+        let info = page.info(list.len(), total);
+        return (StatusCode::OK, Json(pagination::envelope(list, &info)));
+    }
+
     match client.groups().get_participating().await {
         Ok(groups_map) => {
             let list: Vec<Value> = groups_map
@@ -77,8 +105,14 @@ pub async fn list_groups(
                     })
                 })
                 .collect();
+            state
+                .cache
+                .insert(CacheSpace::Groups, &session, "list", &list, None)
+                .await;
 
-            (StatusCode::OK, Json(json!(list)))
+            let (list, total) = page.apply(list);
+            let info = page.info(list.len(), total);
+            (StatusCode::OK, Json(pagination::envelope(list, &info)))
         }
         Err(err) => {
             log::error!("Failed to fetch groups for session {}: {}", session, err);
@@ -1,39 +1,119 @@
 use crate::api_store::ApiBind;
 use crate::server::AppState;
-use axum::{Json, extract::{Path, State}, http::StatusCode, response::IntoResponse};
+use crate::server::api_keys::{IpRule, KeyRole};
+use crate::server::workspaces::hash_api_key;
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
 use serde_json::json;
-use sha2::{Digest, Sha256};
 use std::sync::Arc;
 use uuid::Uuid;
 
-fn hash_key(key: &str) -> String {
-    let mut hasher = Sha256::new();
-    hasher.update(key.as_bytes());
-    hex::encode(hasher.finalize())
-}
-
 pub async fn create_key(
     State(state): State<Arc<AppState>>,
     Json(body): Json<serde_json::Value>,
 ) -> impl IntoResponse {
-    let label = body.get("label").and_then(|v| v.as_str()).map(|s| s.to_string());
+    let label = body
+        .get("label")
+        .and_then(|v| v.as_str())
+        .map(|s| s.to_string());
+    // Keys bound to a workspace only reach that workspace's instances.
+    let workspace_id = match body.get("workspaceId").and_then(|v| v.as_str()) {
+        Some(raw) => match Uuid::parse_str(raw) {
+            Ok(id) => Some(id.to_string()),
+            Err(_) => {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    Json(json!({"error": "invalid_workspace_id"})),
+                );
+            }
+        },
+        None => None,
+    };
+    let role = match body.get("role").and_then(|v| v.as_str()) {
+        Some(raw) => match KeyRole::parse(raw) {
+            Some(role) => role,
+            None => {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    Json(json!({"error": "invalid_role", "allowed": ["admin", "write", "read"]})),
+                );
+            }
+        },
+        None => KeyRole::Write,
+    };
+    if role == KeyRole::Admin && workspace_id.is_some() {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({"error": "workspace_admin_key"})),
+        );
+    }
+    let expires_at = match body.get("expiresAt").and_then(|v| v.as_str()) {
+        Some(raw) => match chrono::DateTime::parse_from_rfc3339(raw) {
+            Ok(at) if at > chrono::Utc::now() => Some(at.to_rfc3339()),
+            _ => {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    Json(json!({"error": "invalid_expires_at"})),
+                );
+            }
+        },
+        None => None,
+    };
+    let allowed_ips = match body.get("allowedIps").filter(|v| !v.is_null()) {
+        Some(raw) => {
+            let ips = raw.as_array().map(|ips| {
+                ips.iter()
+                    .map(|ip| ip.as_str().filter(|ip| IpRule::parse(ip).is_some()))
+                    .collect::<Option<Vec<_>>>()
+            });
+            match ips.flatten() {
+                Some(ips) if !ips.is_empty() => Some(json!(ips)),
+                _ => {
+                    return (
+                        StatusCode::BAD_REQUEST,
+                        Json(json!({"error": "invalid_allowed_ips"})),
+                    );
+                }
+            }
+        }
+        None => None,
+    };
+    let id = Uuid::new_v4();
     let raw_key = Uuid::new_v4().to_string();
-    let key_hash = hash_key(&raw_key);
+    let key_hash = hash_api_key(&raw_key);
 
     let result = state
         .api_store
         .execute(
-            "INSERT INTO api_keys (id, label, key_hash, created_at) VALUES ($1, $2, $3, now())",
+            "INSERT INTO api_keys (id, label, key_hash, workspace_id, role, expires_at, allowed_ips, created_at) \
+             VALUES ($1, $2, $3, $4::uuid, $5, $6::timestamptz, $7, now())",
             vec![
-                ApiBind::Uuid(Uuid::parse_str(&raw_key).unwrap_or_else(|_| Uuid::new_v4())),
+                ApiBind::Uuid(id),
                 ApiBind::NullableText(label),
                 ApiBind::Text(key_hash),
+                ApiBind::NullableText(workspace_id),
+                ApiBind::Text(role.as_str().to_string()),
+                ApiBind::NullableText(expires_at.clone()),
+                ApiBind::NullableJson(allowed_ips.clone()),
             ],
         )
         .await;
 
     match result {
-        Ok(_) => (StatusCode::OK, Json(json!({"key": raw_key}))),
+        Ok(_) => (
+            StatusCode::OK,
+            Json(json!({
+                "id": id,
+                "key": raw_key,
+                "role": role,
+                "expiresAt": expires_at,
+                "allowedIps": allowed_ips,
+            })),
+        ),
         Err(err) => (
             StatusCode::INTERNAL_SERVER_ERROR,
             Json(json!({"error": "db_error", "details": err.to_string()})),
@@ -45,7 +125,7 @@ pub async fn list_keys(State(state): State<Arc<AppState>>) -> impl IntoResponse
     match state
         .api_store
         .query_json(
-            "SELECT row_to_json(api_keys)::jsonb as value FROM api_keys ORDER BY created_at DESC",
+            "SELECT row_to_json(api_keys)::jsonb - 'key_hash' as value FROM api_keys ORDER BY created_at DESC",
             vec![],
         )
         .await
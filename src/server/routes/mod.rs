@@ -10,10 +10,10 @@ use serde_json::json;
 
 mod apps;
 mod auth;
-mod chat;
+pub(crate) mod chat;
 mod calls;
 mod channels;
-mod contacts;
+pub(crate) mod contacts;
 mod events;
 mod groups;
 mod helpers;
@@ -23,8 +23,10 @@ mod media;
 mod observability;
 mod presence;
 mod profile;
-mod sessions;
+pub(crate) mod sessions;
 mod status;
+mod templates;
+mod workspaces;
 
 use std::sync::Arc;
 use crate::server::AppState;
@@ -137,6 +139,27 @@ pub fn router() -> Router<Arc<AppState>> {
         // Api Keys
         .route("/keys", post(keys::create_key).get(keys::list_keys))
         .route("/keys/:id", put(not_implemented).delete(keys::revoke_key))
+        // Workspaces
+        .route(
+            "/workspaces",
+            get(workspaces::list_workspaces).post(workspaces::create_workspace),
+        )
+        .route(
+            "/workspaces/:id/instances/:session",
+            put(workspaces::assign_instance),
+        )
+        // Templates
+        .route(
+            "/templates",
+            get(templates::list_templates).post(templates::create_template),
+        )
+        .route(
+            "/templates/:name",
+            get(templates::get_template)
+                .put(templates::update_template)
+                .delete(templates::delete_template),
+        )
+        .route("/templates/:name/render", post(templates::render_template))
         // Contacts
         .route("/contacts/all", get(contacts::list_contacts_all))
         .route("/contacts", get(contacts::list_contacts))
@@ -1,15 +1,23 @@
 use crate::api_store::ApiBind;
 use crate::server::{AppState, SessionRuntime};
-use crate::server::webhooks;
-use axum::{Json, extract::{Path, State}, http::StatusCode, response::IntoResponse};
+use crate::server::cache::CacheSpace;
+use crate::server::cloud_api;
+use crate::server::instance_deletion::{self, DeletionOptions};
+use crate::server::instance_fingerprint::InstanceFingerprint;
+use crate::server::instance_meta;
+use crate::server::outbox::{self, OutboxEvent};
+use crate::server::quotas;
+use crate::server::workspaces::Scope;
+use axum::{Json, extract::{Extension, Path, State}, http::StatusCode, response::IntoResponse};
 use serde_json::{Value, json};
 use std::sync::Arc;
 use tracing::{error, info};
 
 pub async fn create_session(
     State(state): State<Arc<AppState>>,
+    scope: Option<Extension<Scope>>,
     Json(body): Json<Value>,
-) -> impl IntoResponse {
+) -> (StatusCode, Json<Value>) {
     let session = body
         .get("session")
         .and_then(|v| v.as_str())
@@ -37,17 +45,126 @@ pub async fn create_session(
         .and_then(|v| v.as_bool())
         .unwrap_or(false);
     let webhook_headers = webhook.get("headers").cloned();
+    let webhook_secret = webhook
+        .get("secret")
+        .and_then(|v| v.as_str())
+        .filter(|s| !s.is_empty())
+        .map(|s| s.to_string());
     let webhook_events = webhook.get("events").cloned();
+    let nats = body.get("nats").cloned().unwrap_or(Value::Null);
+    let nats_enabled = nats.get("enabled").and_then(|v| v.as_bool()).unwrap_or(false);
+    let nats_events = nats.get("events").cloned();
     let phone_number = body
         .get("phone_number")
         .and_then(|v| v.as_str())
         .map(|s| s.to_string());
+    let workspace_id = scope
+        .and_then(|Extension(scope)| scope.workspace())
+        .map(|id| id.to_string());
+    let integration = body
+        .get("integration")
+        .and_then(|v| v.as_str())
+        .unwrap_or(cloud_api::DEFAULT_INTEGRATION)
+        .to_string();
+    // Cloud API instances: `number` is the Meta phone number id.
+    let cloud_field = |key: &str| {
+        body.get(key)
+            .and_then(|v| v.as_str())
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(str::to_string)
+    };
+    let (cloud_phone_number_id, cloud_business_id, cloud_access_token) =
+        match integration.as_str() {
+            cloud_api::DEFAULT_INTEGRATION => (None, None, None),
+            cloud_api::INTEGRATION => {
+                let (Some(number), Some(token)) = (cloud_field("number"), cloud_field("token"))
+                else {
+                    return (
+                        StatusCode::BAD_REQUEST,
+                        Json(json!({"error": "cloud_credentials_required", "fields": ["number", "token"]})),
+                    );
+                };
+                (Some(number), cloud_field("businessId"), Some(token))
+            }
+            _ => {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    Json(json!({"error": "invalid_integration", "integration": integration})),
+                );
+            }
+        };
 
-    let result = state
-        .api_store
-        .execute(
-            "INSERT INTO api_sessions (session, status, webhook_url, webhook_events, webhook_by_events, webhook_base64, webhook_headers, webhook_enabled, phone_number, created_at, updated_at) \
-             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, now(), now()) \
+    let tags = match instance_meta::tags_from_body(&body) {
+        Ok(tags) => tags,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": "invalid_request", "details": e.to_string()})),
+            );
+        }
+    };
+    let metadata = match instance_meta::metadata_from_body(&body) {
+        Ok(metadata) => metadata,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": "invalid_request", "details": e.to_string()})),
+            );
+        }
+    };
+
+    let fingerprint = match InstanceFingerprint::from_body(&body) {
+        Ok(fingerprint) => fingerprint,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": "invalid_request", "details": e.to_string()})),
+            );
+        }
+    };
+
+    if let Err(e) = quotas::check_new_instance(&state, &session, workspace_id.as_deref()).await {
+        return e.response();
+    }
+
+    let sealed = state
+        .seal_secret(webhook_secret)
+        .and_then(|secret| Ok((secret, state.seal_secret(cloud_access_token)?)));
+    let (webhook_secret, cloud_access_token) = match sealed {
+        Ok(sealed) => sealed,
+        Err(err) => {
+            error!(session = %session, error = %err, "Falha ao criptografar os segredos da sessão");
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": "encryption_error", "details": err.to_string()})),
+            );
+        }
+    };
+
+    // The event carries the tags and metadata being saved.
+    let mut meta = instance_meta::load(&state, &session)
+        .await
+        .unwrap_or_default();
+    if let Some(tags) = &tags {
+        meta.tags = tags.clone();
+    }
+    if let Some(metadata) = &metadata {
+        meta.metadata = metadata.clone();
+    }
+    let mut event = OutboxEvent::new(
+        Some(&session),
+        "CONNECTION_UPDATE",
+        json!({"status": "open"}),
+    );
+    meta.apply_to(&mut event.payload);
+
+    let result = outbox::commit(
+        &state,
+        vec![(
+            "INSERT INTO api_sessions (session, status, webhook_url, webhook_events, webhook_by_events, webhook_base64, webhook_headers, webhook_enabled, phone_number, workspace_id, webhook_secret, nats_enabled, nats_events, integration, cloud_phone_number_id, cloud_business_id, cloud_access_token, tags, metadata, browser, wa_version, created_at, updated_at) \
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10::uuid, $11, $12, $13, $14, $15, $16, $17, \
+                     COALESCE($18::jsonb, '[]'::jsonb), COALESCE($19::jsonb, '{}'::jsonb), $20, $21, now(), now()) \
              ON CONFLICT (session) DO UPDATE SET \
                 status = EXCLUDED.status, \
                 webhook_url = EXCLUDED.webhook_url, \
@@ -57,7 +174,19 @@ pub async fn create_session(
                 webhook_headers = EXCLUDED.webhook_headers, \
                 webhook_enabled = EXCLUDED.webhook_enabled, \
                 phone_number = EXCLUDED.phone_number, \
-                updated_at = now()",
+                webhook_secret = EXCLUDED.webhook_secret, \
+                nats_enabled = EXCLUDED.nats_enabled, \
+                nats_events = EXCLUDED.nats_events, \
+                integration = EXCLUDED.integration, \
+                cloud_phone_number_id = EXCLUDED.cloud_phone_number_id, \
+                cloud_business_id = EXCLUDED.cloud_business_id, \
+                cloud_access_token = EXCLUDED.cloud_access_token, \
+                tags = COALESCE($18::jsonb, api_sessions.tags), \
+                metadata = COALESCE($19::jsonb, api_sessions.metadata), \
+                browser = COALESCE($20, api_sessions.browser), \
+                wa_version = COALESCE($21, api_sessions.wa_version), \
+                updated_at = now()"
+                .to_string(),
             vec![
                 ApiBind::Text(session.clone()),
                 ApiBind::Text("open".to_string()),
@@ -68,9 +197,23 @@ pub async fn create_session(
                 ApiBind::Json(webhook_headers.unwrap_or_else(|| json!({}))),
                 ApiBind::Bool(webhook_enabled),
                 ApiBind::NullableText(phone_number),
+                ApiBind::NullableText(workspace_id),
+                ApiBind::NullableText(webhook_secret),
+                ApiBind::Bool(nats_enabled),
+                ApiBind::NullableJson(nats_events),
+                ApiBind::Text(integration),
+                ApiBind::NullableText(cloud_phone_number_id),
+                ApiBind::NullableText(cloud_business_id),
+                ApiBind::NullableText(cloud_access_token),
+                ApiBind::NullableJson(tags.map(|tags| json!(tags))),
+                ApiBind::NullableJson(metadata.map(Value::Object)),
+                ApiBind::NullableJson(fingerprint.browser_json()),
+                ApiBind::NullableText(fingerprint.wa_version_text()),
             ],
-        )
-        .await;
+        )],
+        vec![event],
+    )
+    .await;
 
     if let Err(err) = result {
         error!(session = %session, error = %err, "Falha ao salvar sessão no banco de dados");
@@ -81,24 +224,24 @@ pub async fn create_session(
     }
 
     info!(session = %session, "Sessão salva com sucesso no banco de dados");
+    state.webhook_config_cache.remove(&session);
+    state.instance_meta_cache.remove(&session);
+    state.cache.invalidate_space(CacheSpace::Instances).await;
 
     state
         .sessions_runtime
         .entry(session.clone())
         .or_insert_with(SessionRuntime::new);
 
-    webhooks::enqueue(
-        &state,
-        Some(&session),
-        "CONNECTION_UPDATE",
-        json!({"status": "open"}),
-    )
-    .await;
+    let client = state.clients.get(&session).map(|entry| entry.value().clone());
+    if let Some(client) = client {
+        fingerprint.apply(&client).await;
+    }
 
     let row = state
         .api_store
         .query_json(
-            "SELECT row_to_json(api_sessions)::jsonb as value FROM api_sessions WHERE session = $1",
+            "SELECT row_to_json(api_sessions)::jsonb - 'webhook_secret' - 'cloud_access_token' as value FROM api_sessions WHERE session = $1",
             vec![ApiBind::Text(session.clone())],
         )
         .await
@@ -111,12 +254,20 @@ pub async fn create_session(
     )
 }
 
-pub async fn list_sessions(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+pub async fn list_sessions(
+    State(state): State<Arc<AppState>>,
+    scope: Option<Extension<Scope>>,
+) -> impl IntoResponse {
+    let workspace_id = scope
+        .and_then(|Extension(scope)| scope.workspace())
+        .map(|id| id.to_string());
     let rows = state
         .api_store
         .query_json(
-            "SELECT row_to_json(api_sessions)::jsonb as value FROM api_sessions ORDER BY created_at DESC",
-            vec![],
+            "SELECT row_to_json(api_sessions)::jsonb - 'webhook_secret' - 'cloud_access_token' as value FROM api_sessions \
+             WHERE ($1::uuid IS NULL OR workspace_id = $1::uuid) \
+             ORDER BY created_at DESC",
+            vec![ApiBind::NullableText(workspace_id)],
         )
         .await;
 
@@ -136,7 +287,7 @@ pub async fn get_session(
     let row = state
         .api_store
         .query_json(
-            "SELECT row_to_json(api_sessions)::jsonb as value FROM api_sessions WHERE session = $1",
+            "SELECT row_to_json(api_sessions)::jsonb - 'webhook_secret' - 'cloud_access_token' as value FROM api_sessions WHERE session = $1",
             vec![ApiBind::Text(session.clone())],
         )
         .await;
@@ -150,6 +301,8 @@ pub async fn get_session(
                         "qr_code": entry.qr_code,
                         "pair_code": entry.pair_code,
                         "last_seen": entry.last_seen,
+                        "runner_restarts": entry.runner_restarts,
+                        "profile_pic_url": entry.profile_pic_url,
                     })
                 });
                 if let Some(runtime) = runtime {
@@ -177,13 +330,26 @@ pub async fn start_session(
     Path(session): Path<String>,
 ) -> impl IntoResponse {
     info!(session = %session, "Solicitação para iniciar sessão recebida");
-    let result = state
-        .api_store
-        .execute(
-            "UPDATE api_sessions SET status = $2, updated_at = now() WHERE session = $1",
-            vec![ApiBind::Text(session.clone()), ApiBind::Text("started".to_string())],
-        )
-        .await;
+    let event = outbox::prepare(
+        &state,
+        Some(&session),
+        "CONNECTION_UPDATE",
+        json!({"status": "started"}),
+    )
+    .await;
+    let result = outbox::commit(
+        &state,
+        vec![(
+            "UPDATE api_sessions SET status = $2, updated_at = now() WHERE session = $1"
+                .to_string(),
+            vec![
+                ApiBind::Text(session.clone()),
+                ApiBind::Text("started".to_string()),
+            ],
+        )],
+        vec![event],
+    )
+    .await;
 
     if let Err(err) = result {
         return (
@@ -196,14 +362,6 @@ pub async fn start_session(
         entry.connection_state = "started".to_string();
     }
 
-    webhooks::enqueue(
-        &state,
-        Some(&session),
-        "CONNECTION_UPDATE",
-        json!({"status": "started"}),
-    )
-    .await;
-
     (
         StatusCode::OK,
         Json(json!({"session": session, "status": "started"})),
@@ -215,13 +373,26 @@ pub async fn stop_session(
     Path(session): Path<String>,
 ) -> impl IntoResponse {
     info!(session = %session, "Solicitação para parar sessão recebida");
-    let result = state
-        .api_store
-        .execute(
-            "UPDATE api_sessions SET status = $2, updated_at = now() WHERE session = $1",
-            vec![ApiBind::Text(session.clone()), ApiBind::Text("stopped".to_string())],
-        )
-        .await;
+    let event = outbox::prepare(
+        &state,
+        Some(&session),
+        "CONNECTION_UPDATE",
+        json!({"status": "close"}),
+    )
+    .await;
+    let result = outbox::commit(
+        &state,
+        vec![(
+            "UPDATE api_sessions SET status = $2, updated_at = now() WHERE session = $1"
+                .to_string(),
+            vec![
+                ApiBind::Text(session.clone()),
+                ApiBind::Text("stopped".to_string()),
+            ],
+        )],
+        vec![event],
+    )
+    .await;
 
     if let Err(err) = result {
         return (
@@ -234,52 +405,32 @@ pub async fn stop_session(
         entry.connection_state = "stopped".to_string();
     }
 
-    webhooks::enqueue(
-        &state,
-        Some(&session),
-        "CONNECTION_UPDATE",
-        json!({"status": "close"}),
-    )
-    .await;
-
     (
         StatusCode::OK,
         Json(json!({"session": session, "status": "stopped"})),
     )
 }
 
+/// Deletes or disables the session in the steps chosen by the optional
+/// body, see [`instance_deletion`].
 pub async fn delete_session(
     State(state): State<Arc<AppState>>,
     Path(session): Path<String>,
+    body: Option<Json<Value>>,
 ) -> impl IntoResponse {
     info!(session = %session, "Solicitação para deletar sessão recebida");
-    let result = state
-        .api_store
-        .execute(
-            "DELETE FROM api_sessions WHERE session = $1",
-            vec![ApiBind::Text(session.clone())],
-        )
-        .await;
-
-    if let Err(err) = result {
-        return (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(json!({"error": "db_error", "details": err.to_string()})),
-        );
-    }
-
-    state.sessions_runtime.remove(&session);
-
-    webhooks::enqueue(
-        &state,
-        Some(&session),
-        "CONNECTION_UPDATE",
-        json!({"status": "close"}),
-    )
-    .await;
+    let options = match DeletionOptions::from_body(body.as_ref().map(|Json(body)| body)) {
+        Ok(options) => options,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": "invalid_deletion_options", "details": e.to_string()})),
+            );
+        }
+    };
 
-    (
-        StatusCode::OK,
-        Json(json!({"session": session, "status": "deleted"})),
-    )
+    let deletion = instance_deletion::run(&state, &session, options).await;
+    let mut body = deletion.to_json();
+    body["session"] = json!(session);
+    (deletion.status_code(), Json(body))
 }
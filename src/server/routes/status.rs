@@ -1,4 +1,5 @@
 use crate::api_store::ApiBind;
+use crate::server::events::{self, MessagesUpsert};
 use crate::server::webhooks;
 use crate::server::AppState;
 use axum::{Json, extract::{Path, State}, http::StatusCode, response::IntoResponse};
@@ -31,11 +32,17 @@ async fn create_status(
         );
     }
 
-    webhooks::enqueue(
+    events::emit(
         &state,
         Some(&session),
-        "MESSAGES_UPSERT",
-        json!({"status_type": status_type, "payload": payload}),
+        &MessagesUpsert {
+            messages: vec![json!({
+                "key": {"remoteJid": "status@broadcast", "fromMe": true},
+                "message": payload,
+                "statusType": status_type,
+            })],
+            kind: "notify",
+        },
     )
     .await;
 
@@ -0,0 +1,118 @@
+use crate::server::AppState;
+use crate::server::templates::{self, Template};
+use axum::{Json, extract::{Path, State}, http::StatusCode, response::IntoResponse};
+use serde_json::{Value, json};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+fn db_error(err: anyhow::Error) -> (StatusCode, Json<Value>) {
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(json!({"error": "db_error", "details": err.to_string()})),
+    )
+}
+
+fn invalid_template(err: templates::TemplateError) -> (StatusCode, Json<Value>) {
+    (
+        StatusCode::BAD_REQUEST,
+        Json(json!({"error": "invalid_template", "details": err.to_string()})),
+    )
+}
+
+fn not_found(name: &str) -> (StatusCode, Json<Value>) {
+    (
+        StatusCode::NOT_FOUND,
+        Json(json!({"error": "template_not_found", "name": name})),
+    )
+}
+
+pub async fn list_templates(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    match templates::list(&state).await {
+        Ok(rows) => (StatusCode::OK, Json(json!(rows))),
+        Err(err) => db_error(err),
+    }
+}
+
+pub async fn get_template(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+) -> impl IntoResponse {
+    match templates::get_json(&state, &name).await {
+        Ok(Some(row)) => (StatusCode::OK, Json(row)),
+        Ok(None) => not_found(&name),
+        Err(err) => db_error(err),
+    }
+}
+
+pub async fn create_template(
+    State(state): State<Arc<AppState>>,
+    Json(body): Json<Value>,
+) -> impl IntoResponse {
+    let template = match Template::from_body(None, &body) {
+        Ok(template) => template,
+        Err(err) => return invalid_template(err),
+    };
+    match templates::create(&state, &template).await {
+        Ok(true) => (StatusCode::CREATED, Json(json!(template))),
+        Ok(false) => (
+            StatusCode::CONFLICT,
+            Json(json!({"error": "template_exists", "name": template.name})),
+        ),
+        Err(err) => db_error(err),
+    }
+}
+
+pub async fn update_template(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+    Json(body): Json<Value>,
+) -> impl IntoResponse {
+    let template = match Template::from_body(Some(&name), &body) {
+        Ok(template) => template,
+        Err(err) => return invalid_template(err),
+    };
+    match templates::update(&state, &template).await {
+        Ok(true) => (StatusCode::OK, Json(json!(template))),
+        Ok(false) => not_found(&name),
+        Err(err) => db_error(err),
+    }
+}
+
+pub async fn delete_template(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+) -> impl IntoResponse {
+    match templates::delete(&state, &name).await {
+        Ok(true) => (StatusCode::OK, Json(json!({"name": name, "deleted": true}))),
+        Ok(false) => not_found(&name),
+        Err(err) => db_error(err),
+    }
+}
+
+/// Renders a stored template without sending it.
+/// Body: `{"variables": {...}}`.
+pub async fn render_template(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+    body: Option<Json<Value>>,
+) -> impl IntoResponse {
+    let values = match body.as_ref().and_then(|Json(b)| b.get("variables")) {
+        Some(variables) => match templates::values_from(variables) {
+            Ok(values) => values,
+            Err(err) => return invalid_template(err),
+        },
+        None => BTreeMap::new(),
+    };
+    let template = match templates::get(&state, &name).await {
+        Ok(Some(template)) => template,
+        Ok(None) => return not_found(&name),
+        Err(err) => return db_error(err),
+    };
+    match template.render(&values) {
+        Ok(rendered) => (StatusCode::OK, Json(json!(rendered))),
+        Err(err) => (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(json!({"error": "render_failed", "details": err.to_string()})),
+        ),
+    }
+}
@@ -0,0 +1,107 @@
+use crate::api_store::ApiBind;
+use crate::server::AppState;
+use crate::server::cache::CacheSpace;
+use axum::{Json, extract::{Path, State}, http::StatusCode, response::IntoResponse};
+use serde_json::{Value, json};
+use std::sync::Arc;
+use uuid::Uuid;
+
+pub async fn list_workspaces(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    match state
+        .api_store
+        .query_json(
+            "SELECT row_to_json(t)::jsonb as value FROM ( \
+                SELECT w.id, w.name, w.created_at, \
+                       (SELECT count(*) FROM api_sessions s WHERE s.workspace_id = w.id) AS instances \
+                FROM api_workspaces w ORDER BY w.created_at DESC \
+            ) t",
+            vec![],
+        )
+        .await
+    {
+        Ok(rows) => (StatusCode::OK, Json(json!(rows))),
+        Err(err) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": "db_error", "details": err.to_string()})),
+        ),
+    }
+}
+
+pub async fn create_workspace(
+    State(state): State<Arc<AppState>>,
+    Json(body): Json<Value>,
+) -> impl IntoResponse {
+    let Some(name) = body
+        .get("name")
+        .and_then(|v| v.as_str())
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+    else {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({"error": "name_required"})),
+        );
+    };
+
+    let id = Uuid::new_v4();
+    let result = state
+        .api_store
+        .execute(
+            "INSERT INTO api_workspaces (id, name, created_at) VALUES ($1, $2, now()) \
+             ON CONFLICT (name) DO NOTHING",
+            vec![ApiBind::Uuid(id), ApiBind::Text(name.to_string())],
+        )
+        .await;
+
+    match result {
+        Ok(0) => (
+            StatusCode::CONFLICT,
+            Json(json!({"error": "workspace_exists", "name": name})),
+        ),
+        Ok(_) => (StatusCode::CREATED, Json(json!({"id": id, "name": name}))),
+        Err(err) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": "db_error", "details": err.to_string()})),
+        ),
+    }
+}
+
+/// Moves an existing instance into the workspace.
+pub async fn assign_instance(
+    State(state): State<Arc<AppState>>,
+    Path((id, session)): Path<(String, String)>,
+) -> impl IntoResponse {
+    let Ok(id) = Uuid::parse_str(&id) else {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({"error": "invalid_id"})),
+        );
+    };
+
+    let result = state
+        .api_store
+        .execute(
+            "UPDATE api_sessions SET workspace_id = w.id, updated_at = now() \
+             FROM api_workspaces w WHERE w.id = $1 AND api_sessions.session = $2",
+            vec![ApiBind::Uuid(id), ApiBind::Text(session.clone())],
+        )
+        .await;
+    if matches!(result, Ok(updated) if updated > 0) {
+        state.cache.invalidate_space(CacheSpace::Instances).await;
+    }
+
+    match result {
+        Ok(0) => (
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "workspace_or_session_not_found"})),
+        ),
+        Ok(_) => (
+            StatusCode::OK,
+            Json(json!({"session": session, "workspaceId": id})),
+        ),
+        Err(err) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": "db_error", "details": err.to_string()})),
+        ),
+    }
+}
@@ -0,0 +1,368 @@
+//! Settings that can be changed at runtime through `/manager/config`.
+//!
+//! Defaults come from the environment at boot; overrides are persisted in
+//! `api_runtime_config` and re-applied on the next start.
+//!
+//! CORS has one origin list per [`CorsGroup`]: the public API, the
+//! `/manager` routes (locked to the `SERVER_URL` origin unless configured)
+//! and `/ws`. Entries are exact origins, `*`, or a wildcard subdomain such
+//! as `https://*.example.com`.
+
+use crate::api_store::ApiBind;
+use crate::config::{MAX_QR_IMAGE_SIZE, MIN_QR_IMAGE_SIZE};
+use crate::server::{AppState, logging, versioning};
+use serde_json::{Map, Value};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+pub use crate::config::{GlobalWebhook, RuntimeConfig};
+
+/// Applies a new `EnvFilter` directive to the running subscriber.
+pub type LogLevelReloader = Arc<dyn Fn(&str) -> Result<(), String> + Send + Sync>;
+
+const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Error)]
+pub enum RuntimeConfigError {
+    #[error("body must be a JSON object")]
+    NotAnObject,
+    #[error("unknown setting {0:?}")]
+    UnknownKey(String),
+    #[error("invalid value for {key}: {reason}")]
+    InvalidValue { key: &'static str, reason: String },
+    #[error("failed to apply log level: {0}")]
+    LogLevel(String),
+}
+
+impl RuntimeConfig {
+    /// Merges `patch` (camelCase keys, `webhook` merged field by field) into a
+    /// copy of `self`. Returns the new config and the top-level keys changed.
+    pub fn patched(&self, patch: &Value) -> Result<(Self, Vec<String>), RuntimeConfigError> {
+        let patch = patch.as_object().ok_or(RuntimeConfigError::NotAnObject)?;
+        let mut merged = match serde_json::to_value(self) {
+            Ok(Value::Object(map)) => map,
+            _ => Map::new(),
+        };
+
+        for (key, value) in patch {
+            match (key.as_str(), merged.get_mut(key)) {
+                ("webhook", Some(Value::Object(current))) => {
+                    let fields = value.as_object().ok_or(RuntimeConfigError::InvalidValue {
+                        key: "webhook",
+                        reason: "must be an object".to_string(),
+                    })?;
+                    for (field, field_value) in fields {
+                        if !current.contains_key(field) {
+                            return Err(RuntimeConfigError::UnknownKey(format!("webhook.{field}")));
+                        }
+                        current.insert(field.clone(), field_value.clone());
+                    }
+                }
+                (_, Some(current)) => *current = value.clone(),
+                (_, None) => return Err(RuntimeConfigError::UnknownKey(key.clone())),
+            }
+        }
+
+        let config: Self = serde_json::from_value(Value::Object(merged)).map_err(|e| {
+            RuntimeConfigError::InvalidValue {
+                key: "body",
+                reason: e.to_string(),
+            }
+        })?;
+        config.validate()?;
+        Ok((config, patch.keys().cloned().collect()))
+    }
+
+    /// Filter directive of `log_level` with the `log_targets` overrides.
+    pub fn log_filter(&self) -> String {
+        logging::filter_directive(&self.log_level, &self.log_targets)
+    }
+
+    fn validate(&self) -> Result<(), RuntimeConfigError> {
+        if let Err(e) = tracing_subscriber::EnvFilter::try_new(&self.log_level) {
+            return Err(RuntimeConfigError::InvalidValue {
+                key: "logLevel",
+                reason: e.to_string(),
+            });
+        }
+        if let Some(target) = self
+            .log_targets
+            .keys()
+            .find(|t| t.trim().is_empty() || t.contains([',', '=']))
+        {
+            return Err(RuntimeConfigError::InvalidValue {
+                key: "logTargets",
+                reason: format!("invalid target {target:?}"),
+            });
+        }
+        if let Err(e) = tracing_subscriber::EnvFilter::try_new(self.log_filter()) {
+            return Err(RuntimeConfigError::InvalidValue {
+                key: "logTargets",
+                reason: e.to_string(),
+            });
+        }
+        let origin_lists = [
+            ("corsOrigins", Some(&self.cors_origins)),
+            ("managerCorsOrigins", Some(&self.manager_cors_origins)),
+            ("wsCorsOrigins", self.ws_cors_origins.as_ref()),
+        ];
+        for (key, origins) in origin_lists {
+            if let Some(origin) = origins
+                .into_iter()
+                .flatten()
+                .find(|o| !is_origin_pattern(o))
+            {
+                return Err(RuntimeConfigError::InvalidValue {
+                    key,
+                    reason: format!("{origin:?} is not an http(s) origin"),
+                });
+            }
+        }
+        if !(MIN_QR_IMAGE_SIZE..=MAX_QR_IMAGE_SIZE).contains(&self.qr_image_size) {
+            return Err(RuntimeConfigError::InvalidValue {
+                key: "qrImageSize",
+                reason: format!(
+                    "must be between {} and {}",
+                    MIN_QR_IMAGE_SIZE, MAX_QR_IMAGE_SIZE
+                ),
+            });
+        }
+        if let Some(url) = self.webhook.url.as_deref().filter(|u| !is_http_url(u)) {
+            return Err(RuntimeConfigError::InvalidValue {
+                key: "webhook",
+                reason: format!("url {url:?} is not http(s)"),
+            });
+        }
+        Ok(())
+    }
+
+    /// Origins allowed on the routes of `group`.
+    pub fn origins(&self, group: CorsGroup) -> &[String] {
+        match group {
+            CorsGroup::Api => &self.cors_origins,
+            CorsGroup::Manager => &self.manager_cors_origins,
+            CorsGroup::Ws => self
+                .ws_cors_origins
+                .as_deref()
+                .unwrap_or(&self.cors_origins),
+        }
+    }
+
+    /// Whether `origin` may receive CORS headers on the routes of `group`.
+    pub fn allows_origin(&self, group: CorsGroup, origin: &str) -> bool {
+        self.origins(group)
+            .iter()
+            .any(|pattern| origin_matches(pattern, origin))
+    }
+}
+
+/// Routes sharing a CORS origin list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CorsGroup {
+    Api,
+    /// `/manager/...`.
+    Manager,
+    /// `/ws`.
+    Ws,
+}
+
+impl CorsGroup {
+    /// Group of a request path, with or without the `/api/v1` prefix.
+    pub fn for_path(path: &str) -> Self {
+        let path = path.strip_prefix(versioning::V1_PREFIX).unwrap_or(path);
+        if path == "/ws" {
+            Self::Ws
+        } else if path == "/manager" || path.starts_with("/manager/") {
+            Self::Manager
+        } else {
+            Self::Api
+        }
+    }
+}
+
+/// Whether `value` is a valid origin list entry: `*`, an http(s) origin,
+/// or one whose host starts with a `*.` wildcard label.
+pub fn is_origin_pattern(value: &str) -> bool {
+    if value == "*" {
+        return true;
+    }
+    let Some((_, host)) = value.split_once("://").filter(|_| is_http_url(value)) else {
+        return false;
+    };
+    let host = host.strip_prefix("*.").unwrap_or(host);
+    !host.is_empty() && !host.contains('*')
+}
+
+/// Whether `origin` matches `pattern`; `https://*.example.com` matches any
+/// subdomain of `example.com` on https, but not `example.com` itself.
+pub fn origin_matches(pattern: &str, origin: &str) -> bool {
+    if pattern == "*" || pattern == origin {
+        return true;
+    }
+    let Some((scheme, suffix)) = pattern.split_once("://*.") else {
+        return false;
+    };
+    let subdomain = origin
+        .strip_prefix(scheme)
+        .and_then(|rest| rest.strip_prefix("://"))
+        .and_then(|rest| rest.strip_suffix(suffix))
+        .and_then(|rest| rest.strip_suffix('.'));
+    subdomain.is_some_and(|sub| {
+        !sub.is_empty()
+            && sub.split('.').all(|label| {
+                !label.is_empty() && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+            })
+    })
+}
+
+fn is_http_url(value: &str) -> bool {
+    value.starts_with("http://") || value.starts_with("https://")
+}
+
+/// Fixed one-minute window shared by every API request.
+#[derive(Debug)]
+pub struct RateLimiter {
+    window: Mutex<(Instant, u32)>,
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self {
+            window: Mutex::new((Instant::now(), 0)),
+        }
+    }
+}
+
+impl RateLimiter {
+    /// Counts a request; returns `false` once `limit` is exceeded in the
+    /// current window. A limit of 0 always allows.
+    pub fn check(&self, limit: u32) -> bool {
+        self.check_at(limit, Instant::now())
+    }
+
+    fn check_at(&self, limit: u32, now: Instant) -> bool {
+        if limit == 0 {
+            return true;
+        }
+        let mut window = self.window.lock().unwrap_or_else(PoisonError::into_inner);
+        if now.duration_since(window.0) >= RATE_LIMIT_WINDOW {
+            *window = (now, 0);
+        }
+        if window.1 >= limit {
+            return false;
+        }
+        window.1 += 1;
+        true
+    }
+}
+
+/// Applies `patch` to the running server and persists the changed keys.
+/// Returns the new config and whether the overrides were persisted.
+pub async fn update(
+    state: &AppState,
+    patch: &Value,
+) -> Result<(RuntimeConfig, bool), RuntimeConfigError> {
+    let current = state.runtime_config();
+    let (next, changed) = current.patched(patch)?;
+
+    if next.log_filter() != current.log_filter() {
+        apply_log_level(state, &next.log_filter())?;
+    }
+    *state
+        .runtime_config
+        .write()
+        .unwrap_or_else(PoisonError::into_inner) = next.clone();
+
+    let persisted = match persist(state, &next, &changed).await {
+        Ok(()) => true,
+        Err(e) => {
+            log::warn!("Runtime config applied but not persisted: {}", e);
+            false
+        }
+    };
+    Ok((next, persisted))
+}
+
+/// Re-applies overrides saved by previous `PATCH /manager/config` calls.
+pub async fn restore_overrides(state: &AppState) {
+    let rows = match state
+        .api_store
+        .query_json(
+            "SELECT jsonb_build_object('key', key, 'value', value) as value FROM api_runtime_config",
+            vec![],
+        )
+        .await
+    {
+        Ok(rows) => rows,
+        Err(e) => {
+            log::debug!("No persisted runtime config: {}", e);
+            return;
+        }
+    };
+
+    let patch: Map<String, Value> = rows
+        .into_iter()
+        .filter_map(|row| {
+            Some((
+                row.get("key")?.as_str()?.to_string(),
+                row.get("value")?.clone(),
+            ))
+        })
+        .collect();
+    if patch.is_empty() {
+        return;
+    }
+
+    let current = state.runtime_config();
+    match current.patched(&Value::Object(patch)) {
+        Ok((next, _)) => {
+            if next.log_filter() != current.log_filter()
+                && let Err(e) = apply_log_level(state, &next.log_filter())
+            {
+                log::warn!("Persisted log level not applied: {}", e);
+            }
+            *state
+                .runtime_config
+                .write()
+                .unwrap_or_else(PoisonError::into_inner) = next;
+            log::info!("Runtime config overrides restored");
+        }
+        Err(e) => log::warn!("Ignoring persisted runtime config: {}", e),
+    }
+}
+
+fn apply_log_level(state: &AppState, level: &str) -> Result<(), RuntimeConfigError> {
+    match &state.log_level_reloader {
+        Some(reload) => reload(level).map_err(RuntimeConfigError::LogLevel),
+        None => Err(RuntimeConfigError::LogLevel(
+            "log level reload not available".to_string(),
+        )),
+    }
+}
+
+async fn persist(state: &AppState, config: &RuntimeConfig, keys: &[String]) -> anyhow::Result<()> {
+    let values = match serde_json::to_value(config)? {
+        Value::Object(map) => map,
+        _ => return Ok(()),
+    };
+    for key in keys {
+        let Some(value) = values.get(key) else {
+            continue;
+        };
+        state
+            .api_store
+            .execute(
+                "INSERT INTO api_runtime_config (key, value, updated_at) VALUES ($1, $2, now()) \
+                 ON CONFLICT (key) DO UPDATE SET value = EXCLUDED.value, updated_at = now()",
+                vec![ApiBind::Text(key.clone()), ApiBind::Json(value.clone())],
+            )
+            .await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    include!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/tests/server/runtime_config_tests.rs"));
+}
@@ -0,0 +1,335 @@
+//! Per-instance bridge from the client event bus to the API runtime state.
+//!
+//! The event bus dispatches synchronously, so the handler only maps events
+//! and forwards them over a channel; a task per instance applies them in
+//! order (instance state, `SessionRuntime`, CONNECTION_UPDATE/QRCODE_UPDATED).
+//! Each update carries the [`Reason`] reported in CONNECTION_UPDATE.
+
+use crate::client::Client;
+use crate::server::connection::{
+    ConnectionState, LastError, Reason, Transition, update_connection_state,
+};
+use crate::server::events::{self, LogoutInstance, QrcodeUpdated};
+use crate::server::{
+    AppState, SessionRuntime, auto_rules, message_counters, message_status, messages_worker,
+    profile_pictures, reactions,
+};
+use crate::types::events::{Event, EventHandler, StreamErrorReason};
+use serde_json::json;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+/// Event subset that changes the runtime state of an instance.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RuntimeUpdate {
+    Qr { code: String, timeout_secs: u64 },
+    PairCode { code: String },
+    Connected,
+    /// WhatsApp logged the device out, while connected or when refusing
+    /// the login (`on_connect`).
+    LoggedOut {
+        on_connect: bool,
+    },
+    Disconnected,
+    /// Another client took over the session.
+    Replaced,
+    TemporaryBan,
+    /// `<stream:error>` from the server, kept as the instance `lastError`.
+    StreamError(LastError),
+}
+
+impl RuntimeUpdate {
+    /// Maps a client event; `None` for events that do not affect the runtime.
+    pub fn from_event(event: &Event) -> Option<Self> {
+        match event {
+            Event::PairingQrCode { code, timeout } => Some(Self::Qr {
+                code: code.clone(),
+                timeout_secs: timeout.as_secs(),
+            }),
+            Event::PairingCode { code, .. } => Some(Self::PairCode { code: code.clone() }),
+            Event::Connected(_) => Some(Self::Connected),
+            Event::LoggedOut(logged_out) => Some(Self::LoggedOut {
+                on_connect: logged_out.on_connect,
+            }),
+            Event::Disconnected(_) => Some(Self::Disconnected),
+            Event::StreamReplaced(_) => Some(Self::Replaced),
+            Event::TemporaryBan(_) => Some(Self::TemporaryBan),
+            Event::StreamError(error) => Some(Self::StreamError(LastError::from_stream_error(
+                error,
+                chrono::Utc::now(),
+            ))),
+            _ => None,
+        }
+    }
+}
+
+struct RuntimeEventForwarder {
+    tx: mpsc::UnboundedSender<RuntimeUpdate>,
+}
+
+impl EventHandler for RuntimeEventForwarder {
+    fn handle_event(&self, event: &Event) {
+        if let Some(update) = RuntimeUpdate::from_event(event) {
+            // Only fails once the instance task is gone, i.e. during shutdown.
+            let _ = self.tx.send(update);
+        }
+    }
+}
+
+/// Subscribes `instance_name` to `client` events, message acks, receipts,
+/// reactions and the auto rules included. Call before the client connects so
+/// the first QR code is not missed.
+pub fn attach(state: Arc<AppState>, instance_name: String, client: &Client) {
+    auto_rules::attach(state.clone(), instance_name.clone(), client);
+    message_status::attach(state.clone(), instance_name.clone(), client);
+    reactions::attach(state.clone(), instance_name.clone(), client);
+    message_counters::attach(&state, instance_name.clone(), client);
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    client
+        .core
+        .event_bus
+        .add_handler(Arc::new(RuntimeEventForwarder { tx }));
+
+    tokio::spawn(async move {
+        while let Some(update) = rx.recv().await {
+            apply(&state, &instance_name, update).await;
+        }
+        log::debug!("Runtime event stream for {} closed", instance_name);
+    });
+}
+
+/// Applies one update to the instance and session runtime state.
+pub async fn apply(state: &Arc<AppState>, instance_name: &str, update: RuntimeUpdate) {
+    match update {
+        RuntimeUpdate::Qr { code, timeout_secs } => {
+            log::info!("Pairing QR code received for {} (valid {}s)", instance_name, timeout_secs);
+            if let Some(instance) = state.instances.get(instance_name) {
+                *instance.qr_code.write().await = Some(code.clone());
+                *instance.qr_count.write().await += 1;
+            }
+            with_runtime(state, instance_name, |runtime| runtime.qr_code = Some(code.clone()));
+            update_runtime_state(
+                state,
+                instance_name,
+                ConnectionState::QrPending,
+                Reason::QrIssued,
+                json!({}),
+            )
+            .await;
+            events::emit(
+                state,
+                Some(instance_name),
+                &QrcodeUpdated {
+                    qrcode: code,
+                    timeout: timeout_secs,
+                },
+            )
+            .await;
+        }
+        RuntimeUpdate::PairCode { code } => {
+            with_runtime(state, instance_name, |runtime| runtime.pair_code = Some(code));
+            update_runtime_state(
+                state,
+                instance_name,
+                ConnectionState::PairingPending,
+                Reason::PairCodeIssued,
+                json!({}),
+            )
+            .await;
+        }
+        RuntimeUpdate::Connected => {
+            log::info!("Instance {} connected", instance_name);
+            if let Some(instance) = state.instances.get(instance_name) {
+                *instance.qr_code.write().await = None;
+            }
+            with_runtime(state, instance_name, |runtime| {
+                runtime.qr_code = None;
+                runtime.pair_code = None;
+            });
+            update_runtime_state(
+                state,
+                instance_name,
+                ConnectionState::Connected,
+                Reason::Opened,
+                json!({}),
+            )
+            .await;
+            // Pre-warm E2E sessions for recent DM chats in the background.
+            // This eliminates the ~20-30s first-message latency for known contacts.
+            tokio::spawn(messages_worker::warm_sessions(
+                state.clone(),
+                instance_name.to_string(),
+            ));
+            let picture_state = state.clone();
+            let picture_instance = instance_name.to_string();
+            tokio::spawn(async move {
+                if let Err(e) =
+                    profile_pictures::refresh_owner(&picture_state, &picture_instance).await
+                {
+                    log::debug!("Profile picture of {} not fetched: {}", picture_instance, e);
+                }
+            });
+        }
+        RuntimeUpdate::LoggedOut { on_connect } => {
+            log::error!("Instance {} was logged out", instance_name);
+            let logout = clear_credentials(state, instance_name, on_connect).await;
+            with_runtime(state, instance_name, |runtime| {
+                runtime.qr_code = None;
+                runtime.pair_code = None;
+                runtime.profile_pic_url = None;
+            });
+            update_runtime_state(
+                state,
+                instance_name,
+                ConnectionState::LoggedOut,
+                Reason::LoggedOut,
+                json!({}),
+            )
+            .await;
+            events::emit(state, Some(instance_name), &logout).await;
+        }
+        RuntimeUpdate::Disconnected => {
+            update_runtime_state(
+                state,
+                instance_name,
+                ConnectionState::Disconnected,
+                Reason::ConnectionLost,
+                json!({}),
+            )
+            .await;
+        }
+        RuntimeUpdate::Replaced => {
+            log::warn!("Instance {} was replaced by another connection", instance_name);
+            update_runtime_state(
+                state,
+                instance_name,
+                ConnectionState::Disconnected,
+                Reason::ConnectionReplaced,
+                json!({}),
+            )
+            .await;
+        }
+        RuntimeUpdate::TemporaryBan => {
+            log::error!("Instance {} is temporarily banned", instance_name);
+            update_runtime_state(
+                state,
+                instance_name,
+                ConnectionState::Disconnected,
+                Reason::Forbidden,
+                json!({}),
+            )
+            .await;
+        }
+        RuntimeUpdate::StreamError(error) => {
+            // A 515 ends every pairing; it is not worth reporting.
+            if error.reason == StreamErrorReason::RestartRequired {
+                return;
+            }
+            log::warn!(
+                "Instance {} got stream error {} ({})",
+                instance_name,
+                error.code,
+                error.reason
+            );
+            let reason = stream_error_reason(error.reason);
+            let extra = json!({"lastError": error});
+            let status = state
+                .instances
+                .get(instance_name)
+                .map(|instance| instance.connection_state.clone());
+            if let Some(status) = status {
+                status.write().await.record_error(error);
+            }
+            // Replaced and logged out arrive as their own events.
+            if let Some(reason) = reason {
+                update_runtime_state(
+                    state,
+                    instance_name,
+                    ConnectionState::Disconnected,
+                    reason,
+                    extra,
+                )
+                .await;
+            }
+        }
+    }
+}
+
+/// Resets the device of a logged out instance so that reconnecting shows a
+/// QR code instead of retrying the revoked credentials forever.
+async fn clear_credentials(
+    state: &AppState,
+    instance_name: &str,
+    on_connect: bool,
+) -> LogoutInstance {
+    let mut logout = LogoutInstance {
+        wuid: None,
+        on_connect,
+        credentials_cleared: false,
+        at: chrono::Utc::now(),
+    };
+    let Some(client) = state.clients.get(instance_name).map(|c| c.value().clone()) else {
+        return logout;
+    };
+    match client.clear_credentials().await {
+        Ok(pn) => {
+            log::info!(
+                "Cleared the credentials of logged out instance {}",
+                instance_name
+            );
+            logout.wuid = pn.map(|jid| jid.to_non_ad().to_string());
+            logout.credentials_cleared = true;
+        }
+        Err(e) => log::warn!(
+            "Failed to clear the credentials of logged out instance {}: {}",
+            instance_name,
+            e
+        ),
+    }
+    logout
+}
+
+/// CONNECTION_UPDATE reason of a stream error that leaves the instance
+/// reconnecting; `None` for the ones followed by a dedicated event.
+pub fn stream_error_reason(reason: StreamErrorReason) -> Option<Reason> {
+    match reason {
+        StreamErrorReason::RateOverlimit => Some(Reason::RateOverlimit),
+        StreamErrorReason::ServiceUnavailable => Some(Reason::ServiceUnavailable),
+        StreamErrorReason::Unknown => Some(Reason::ConnectionLost),
+        StreamErrorReason::RestartRequired
+        | StreamErrorReason::ReplacedByOtherDevice
+        | StreamErrorReason::LoggedOut => None,
+    }
+}
+
+/// Moves the instance to `next` and mirrors it into its `SessionRuntime`.
+pub(crate) async fn update_runtime_state(
+    state: &AppState,
+    instance_name: &str,
+    next: ConnectionState,
+    reason: Reason,
+    extra: serde_json::Value,
+) {
+    let current = match update_connection_state(state, instance_name, next, reason, extra).await {
+        Transition::Changed { current, .. }
+        | Transition::Unchanged(current)
+        | Transition::Invalid { current, .. } => current,
+    };
+    with_runtime(state, instance_name, |runtime| {
+        runtime.connection_state = current.as_str().to_string();
+    });
+}
+
+fn with_runtime(state: &AppState, instance_name: &str, update: impl FnOnce(&mut SessionRuntime)) {
+    let mut runtime = state
+        .sessions_runtime
+        .entry(instance_name.to_string())
+        .or_insert_with(SessionRuntime::new);
+    update(&mut runtime);
+    runtime.last_seen = Some(chrono::Utc::now());
+}
+
+#[cfg(test)]
+mod tests {
+    include!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/tests/server/session_events_tests.rs"));
+}
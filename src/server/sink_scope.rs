@@ -0,0 +1,32 @@
+//! Instances the global event sinks deliver for.
+//!
+//! The global sinks (the global webhook, `/ws` and NATS with
+//! `NATS_GLOBAL_ENABLED`) take the events of every instance. They can be
+//! narrowed with `GLOBAL_SINK_INSTANCES` (only these instances) and
+//! `GLOBAL_SINK_EXCLUDE_INSTANCES` (every instance but these), both comma
+//! separated; an instance in both lists is excluded. Events not tied to an
+//! instance always pass. Per-instance sinks (the instance webhook and its
+//! NATS subjects) are not affected: an instance can be left out of the
+//! global sinks and still deliver to its own.
+
+pub use crate::config::InstanceScope;
+
+impl InstanceScope {
+    /// Whether the global sinks deliver the events of `instance` (empty for
+    /// events not tied to one).
+    pub fn includes(&self, instance: &str) -> bool {
+        if instance.is_empty() {
+            return true;
+        }
+        !self.deny.contains(instance)
+            && self
+                .allow
+                .as_ref()
+                .is_none_or(|allow| allow.contains(instance))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    include!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/tests/server/sink_scope_tests.rs"));
+}
@@ -0,0 +1,271 @@
+//! Server-sent events stream, for consumers whose proxies do not keep
+//! websockets open.
+//!
+//! `GET /events/sse` streams every event and `GET /events/sse/:instance` the
+//! events of one instance, each as an SSE message whose `event` is the event
+//! name, `data` the envelope and `id` a sequence number. The last
+//! `SSE_HISTORY_SIZE` events are kept in memory so a client reconnecting
+//! with `Last-Event-ID` gets what it missed; if the gap is older than the
+//! history it first receives `SSE_LAGGED` with the number of skipped events.
+//! Heartbeat comments keep idle connections open. Envelopes are serialized
+//! when first sent, so history nobody replays costs no JSON.
+
+use crate::server::AppState;
+use crate::server::event_bus::{BusEvent, SinkCounts, SinkStats};
+use crate::server::workspaces::Scope;
+use axum::{
+    Json,
+    extract::{Extension, Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    response::{
+        IntoResponse, Response,
+        sse::{Event, KeepAlive, Sse},
+    },
+};
+use futures_util::stream::{self, StreamExt};
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::{HashSet, VecDeque};
+use std::convert::Infallible;
+use std::sync::{Arc, Mutex, PoisonError};
+use tokio::sync::broadcast::{self, error::RecvError};
+
+pub use crate::config::SseConfig;
+
+/// Sent before the replay when events were lost, and to clients that fall
+/// too far behind the live stream.
+pub const LAGGED_EVENT: &str = "SSE_LAGGED";
+
+/// An event as kept in the history.
+#[derive(Debug, Clone)]
+pub struct StreamedEvent {
+    pub id: u64,
+    pub envelope: Arc<BusEvent>,
+}
+
+/// What a reconnecting client gets before the live stream.
+#[derive(Debug, Clone)]
+pub struct Replay {
+    pub events: Vec<Arc<StreamedEvent>>,
+    /// Events after `Last-Event-ID` that already left the history.
+    pub skipped: u64,
+    /// Last id assigned when the replay was taken; live events up to it
+    /// are skipped.
+    pub up_to: u64,
+}
+
+#[derive(Default)]
+struct History {
+    last_id: u64,
+    events: VecDeque<Arc<StreamedEvent>>,
+}
+
+/// History and fan-out of the SSE stream.
+pub struct SseHub {
+    config: SseConfig,
+    history: Mutex<History>,
+    tx: broadcast::Sender<Arc<StreamedEvent>>,
+    stats: Arc<SinkStats>,
+}
+
+impl SseHub {
+    pub fn new(config: SseConfig) -> Self {
+        let (tx, _) = broadcast::channel(config.history.max(1));
+        Self {
+            config,
+            history: Mutex::default(),
+            tx,
+            stats: Arc::default(),
+        }
+    }
+
+    pub fn config(&self) -> &SseConfig {
+        &self.config
+    }
+
+    /// Events recorded and skipped by lagging clients.
+    pub fn stats(&self) -> SinkCounts {
+        self.stats.snapshot()
+    }
+
+    /// Records an event and hands it to the connected clients.
+    pub fn publish(&self, envelope: &Arc<BusEvent>) {
+        let mut history = self.history.lock().unwrap_or_else(PoisonError::into_inner);
+        history.last_id += 1;
+        let event = Arc::new(StreamedEvent {
+            id: history.last_id,
+            envelope: envelope.clone(),
+        });
+        self.stats.record_queued();
+        if history.events.len() >= self.config.history {
+            history.events.pop_front();
+        }
+        history.events.push_back(event.clone());
+        // Sent under the lock so subscribers see ids in order.
+        let _ = self.tx.send(event);
+    }
+
+    /// Subscribes to new events and takes the events after `last_event_id`
+    /// from the history. Without a `last_event_id` nothing is replayed; an
+    /// id ahead of the history (ids restart with the process) replays
+    /// nothing either.
+    pub fn subscribe(
+        &self,
+        last_event_id: Option<u64>,
+    ) -> (broadcast::Receiver<Arc<StreamedEvent>>, Replay) {
+        let history = self.history.lock().unwrap_or_else(PoisonError::into_inner);
+        let rx = self.tx.subscribe();
+        let mut replay = Replay {
+            events: Vec::new(),
+            skipped: 0,
+            up_to: history.last_id,
+        };
+        if let Some(after) = last_event_id.filter(|id| *id < history.last_id) {
+            let oldest = history.events.front().map_or(history.last_id + 1, |e| e.id);
+            replay.skipped = oldest.saturating_sub(after + 1);
+            replay.events = history
+                .events
+                .iter()
+                .filter(|event| event.id > after)
+                .cloned()
+                .collect();
+        }
+        (rx, replay)
+    }
+}
+
+/// Which events a client asked for.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EventFilter {
+    pub instance: Option<String>,
+    /// Upper-cased event names; `None` means every event.
+    pub events: Option<HashSet<String>>,
+}
+
+impl EventFilter {
+    /// `events` is a comma separated list, case-insensitive.
+    pub fn new(instance: Option<String>, events: Option<&str>) -> Self {
+        let events = events
+            .map(|raw| {
+                raw.split(',')
+                    .map(|name| name.trim().to_ascii_uppercase())
+                    .filter(|name| !name.is_empty())
+                    .collect::<HashSet<_>>()
+            })
+            .filter(|names| !names.is_empty());
+        Self { instance, events }
+    }
+
+    pub fn matches(&self, event: &BusEvent) -> bool {
+        self.instance
+            .as_ref()
+            .is_none_or(|instance| *instance == event.instance)
+            && self
+                .events
+                .as_ref()
+                .is_none_or(|names| names.contains(&event.event))
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SseQuery {
+    /// Comma separated event names.
+    events: Option<String>,
+    /// Same as the `Last-Event-ID` header, for clients that cannot set it.
+    last_event_id: Option<u64>,
+}
+
+fn message(event: &StreamedEvent) -> Result<Event, Infallible> {
+    Ok(Event::default()
+        .id(event.id.to_string())
+        .event(&event.envelope.event)
+        .data(event.envelope.json()))
+}
+
+fn lagged(skipped: u64) -> Result<Event, Infallible> {
+    Ok(Event::default()
+        .event(LAGGED_EVENT)
+        .data(json!({"event": LAGGED_EVENT, "data": {"skipped": skipped}}).to_string()))
+}
+
+fn last_event_id(headers: &HeaderMap, query: &SseQuery) -> Option<u64> {
+    headers
+        .get("last-event-id")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse().ok())
+        .or(query.last_event_id)
+}
+
+fn stream_events(state: &AppState, filter: EventFilter, last_event_id: Option<u64>) -> Response {
+    let hub = &state.sse;
+    let (rx, replay) = hub.subscribe(last_event_id);
+
+    let head = (replay.skipped > 0).then(|| lagged(replay.skipped));
+    let backlog = replay
+        .events
+        .into_iter()
+        .filter(|event| filter.matches(&event.envelope))
+        .map(|event| message(&event));
+    let replayed = stream::iter(head.into_iter().chain(backlog).collect::<Vec<_>>());
+
+    let up_to = replay.up_to;
+    let cursor = (rx, filter, hub.stats.clone());
+    let live = stream::unfold(cursor, move |(mut rx, filter, stats)| async move {
+        loop {
+            match rx.recv().await {
+                Ok(event) if event.id > up_to && filter.matches(&event.envelope) => {
+                    return Some((message(&event), (rx, filter, stats)));
+                }
+                Ok(_) => continue,
+                Err(RecvError::Lagged(skipped)) => {
+                    stats.record_dropped(skipped);
+                    return Some((lagged(skipped), (rx, filter, stats)));
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    });
+
+    Sse::new(replayed.chain(live))
+        .keep_alive(
+            KeepAlive::new()
+                .interval(hub.config().heartbeat)
+                .text("heartbeat"),
+        )
+        .into_response()
+}
+
+/// `GET /events/sse`: events of every instance. Admin only.
+pub async fn sse_handler(
+    State(state): State<Arc<AppState>>,
+    scope: Option<Extension<Scope>>,
+    headers: HeaderMap,
+    Query(query): Query<SseQuery>,
+) -> Response {
+    if let Some(Extension(Scope::Workspace(_))) = scope {
+        return (
+            StatusCode::FORBIDDEN,
+            Json(json!({"error": "workspace_forbidden", "route": "/events/sse"})),
+        )
+            .into_response();
+    }
+    let filter = EventFilter::new(None, query.events.as_deref());
+    stream_events(&state, filter, last_event_id(&headers, &query))
+}
+
+/// `GET /events/sse/:instance`: events of one instance.
+pub async fn instance_sse_handler(
+    Path(instance): Path<String>,
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(query): Query<SseQuery>,
+) -> Response {
+    let filter = EventFilter::new(Some(instance), query.events.as_deref());
+    stream_events(&state, filter, last_event_id(&headers, &query))
+}
+
+#[cfg(test)]
+mod tests {
+    include!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/tests/server/sse_tests.rs"));
+}
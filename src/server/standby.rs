@@ -0,0 +1,441 @@
+//! Warm standby: instance leases, promotion and fencing.
+//!
+//! With `INSTANCE_LEASES` on, a node only runs the WhatsApp connection of
+//! its instance while it holds the instance's row in `instance_leases`,
+//! renewed every third of `INSTANCE_LEASE_SECS`. A node started with
+//! `STANDBY_MODE` serves the HTTP API from the shared database (instances,
+//! message history) but builds no client until it is promoted with
+//! `POST /manager/promote`. Promotion takes the lease, bumping its `epoch`;
+//! with `force` it also takes a lease still held by another node and then
+//! waits for that lease to run out, so the old primary has stopped before
+//! the new one connects.
+//!
+//! A primary that finds its lease taken, or cannot renew it before it
+//! expires, fences itself: the connection is closed and held, the client
+//! leaves [`AppState::clients`] (so nothing is sent through it) and the
+//! instance reports `reason: "fenced"`. A fenced node keeps serving reads;
+//! it takes the instance back only after a restart.
+
+use crate::api_store::ApiBind;
+use crate::server::AppState;
+use crate::server::connection::{ConnectionState, Reason};
+use crate::server::session_events::update_runtime_state;
+use serde::Serialize;
+use serde_json::{Value, json};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use tokio::sync::watch;
+use tracing::{error, info, warn};
+
+pub use crate::config::StandbyConfig;
+
+/// What this node does with its instance.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// Holds the lease and runs the connection.
+    Primary,
+    /// Serves the API and waits for a promotion.
+    Standby,
+    /// Took the lease and waits for the previous holder's to run out.
+    Promoting,
+    /// Lost the lease; the connection stays closed until a restart.
+    Fenced,
+}
+
+impl Role {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Primary => "primary",
+            Self::Standby => "standby",
+            Self::Promoting => "promoting",
+            Self::Fenced => "fenced",
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum StandbyError {
+    #[error("instance leases are off (INSTANCE_LEASES / STANDBY_MODE)")]
+    Disabled,
+    #[error("this node is {}, not standby", .0.as_str())]
+    NotStandby(Role),
+    #[error("the lease is held by {holder} for another {}ms", .remaining.as_millis())]
+    Held { holder: String, remaining: Duration },
+    #[error(transparent)]
+    Store(#[from] anyhow::Error),
+}
+
+impl StandbyConfig {
+    fn lease_secs(&self) -> i32 {
+        i32::try_from(self.lease_ttl.as_secs()).unwrap_or(i32::MAX)
+    }
+}
+
+/// Lease state of this node, shared through [`AppState`].
+#[derive(Debug)]
+pub struct Standby {
+    pub config: StandbyConfig,
+    instance: String,
+    role: watch::Sender<Role>,
+    epoch: AtomicI32,
+}
+
+impl Standby {
+    /// Starts as [`Role::Standby`] with `STANDBY_MODE`, else as primary.
+    pub fn new(config: StandbyConfig, instance: impl Into<String>) -> Self {
+        let role = if config.standby {
+            Role::Standby
+        } else {
+            Role::Primary
+        };
+        Self {
+            config,
+            instance: instance.into(),
+            role: watch::Sender::new(role),
+            epoch: AtomicI32::new(0),
+        }
+    }
+
+    pub fn role(&self) -> Role {
+        *self.role.borrow()
+    }
+
+    /// Whether this node may run the connection and send messages.
+    pub fn is_primary(&self) -> bool {
+        self.role() == Role::Primary
+    }
+
+    /// Instance the lease is held for.
+    pub fn instance(&self) -> &str {
+        &self.instance
+    }
+
+    /// Epoch of the lease last taken, 0 before any.
+    pub fn epoch(&self) -> i32 {
+        self.epoch.load(Ordering::Acquire)
+    }
+
+    /// Moves from standby to promoting; fails with the current role when
+    /// this node is not standby (e.g. a promotion already running).
+    pub fn begin_promotion(&self) -> Result<(), Role> {
+        let mut current = Role::Standby;
+        self.role.send_if_modified(|role| {
+            current = *role;
+            if *role == Role::Standby {
+                *role = Role::Promoting;
+                true
+            } else {
+                false
+            }
+        });
+        match current {
+            Role::Standby => Ok(()),
+            role => Err(role),
+        }
+    }
+
+    /// Sets `to` if the role is still `from`.
+    fn transition(&self, from: Role, to: Role) -> bool {
+        self.role.send_if_modified(|role| {
+            if *role == from {
+                *role = to;
+                true
+            } else {
+                false
+            }
+        })
+    }
+
+    /// Body of `standby` in `/manager/status`.
+    pub fn status(&self) -> StandbyStatus {
+        StandbyStatus {
+            role: self.role(),
+            leases: self.config.leases,
+            node_id: self.config.node_id.clone(),
+            instance: self.instance.clone(),
+            epoch: self.epoch(),
+        }
+    }
+}
+
+/// Role and lease of this node, reported by `/manager/status`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StandbyStatus {
+    pub role: Role,
+    pub leases: bool,
+    pub node_id: String,
+    pub instance: String,
+    pub epoch: i32,
+}
+
+/// Outcome of taking the lease.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Acquisition {
+    /// Taken with a new epoch; the connection may start after `wait`,
+    /// when the lease taken over from another node has run out.
+    Taken { epoch: i32, wait: Duration },
+    /// Another node holds a live lease.
+    Held { holder: String, remaining: Duration },
+}
+
+impl Acquisition {
+    /// Reads the row of [`acquire`]: `epoch` is null when the lease was
+    /// not taken; `holder` and `remainingMs` describe the previous lease.
+    fn from_row(node_id: &str, row: &Value) -> Option<Self> {
+        let holder = row["holder"].as_str();
+        let remaining = Duration::from_millis(row["remainingMs"].as_u64().unwrap_or(0));
+        match row["epoch"]
+            .as_i64()
+            .and_then(|epoch| i32::try_from(epoch).ok())
+        {
+            Some(epoch) => Some(Self::Taken {
+                epoch,
+                wait: match holder {
+                    Some(holder) if holder != node_id => remaining,
+                    _ => Duration::ZERO,
+                },
+            }),
+            None => Some(Self::Held {
+                holder: holder?.to_string(),
+                remaining,
+            }),
+        }
+    }
+}
+
+/// Whether a primary whose last renewal was `since_renewal` ago must stop:
+/// the next attempt would come after the lease expired.
+pub fn must_fence(since_renewal: Duration, ttl: Duration) -> bool {
+    since_renewal + renew_interval(ttl) >= ttl
+}
+
+fn renew_interval(ttl: Duration) -> Duration {
+    ttl / 3
+}
+
+/// Takes the lease of this node's instance when it is free, expired or
+/// already ours, or with `force` in any case.
+pub async fn acquire(state: &AppState, force: bool) -> anyhow::Result<Acquisition> {
+    let standby = &state.standby;
+    let rows = state
+        .api_store
+        .query_json(
+            "WITH previous AS ( \
+                SELECT holder, \
+                       GREATEST(0, EXTRACT(EPOCH FROM (expires_at - now())) * 1000)::bigint AS remaining_ms \
+                FROM instance_leases WHERE instance = $1 FOR UPDATE \
+            ), taken AS ( \
+                INSERT INTO instance_leases (instance, holder, epoch, expires_at) \
+                VALUES ($1, $2, 1, now() + ($3 || ' seconds')::interval) \
+                ON CONFLICT (instance) DO UPDATE SET holder = EXCLUDED.holder, \
+                    epoch = instance_leases.epoch + 1, expires_at = EXCLUDED.expires_at, \
+                    updated_at = now() \
+                WHERE instance_leases.holder = $2 OR instance_leases.expires_at <= now() \
+                    OR $4::boolean \
+                RETURNING epoch \
+            ) \
+            SELECT jsonb_build_object( \
+                'epoch', (SELECT epoch FROM taken), \
+                'holder', (SELECT holder FROM previous), \
+                'remainingMs', (SELECT remaining_ms FROM previous)) as value",
+            vec![
+                ApiBind::Text(standby.instance.clone()),
+                ApiBind::Text(standby.config.node_id.clone()),
+                ApiBind::Int(standby.config.lease_secs()),
+                ApiBind::Bool(force),
+            ],
+        )
+        .await?;
+    rows.first()
+        .and_then(|row| Acquisition::from_row(&standby.config.node_id, row))
+        .ok_or_else(|| anyhow::anyhow!("lease query returned no row"))
+}
+
+/// Extends the lease; `false` when another node took it.
+async fn renew(state: &AppState, epoch: i32) -> anyhow::Result<bool> {
+    let standby = &state.standby;
+    let updated = state
+        .api_store
+        .execute(
+            "UPDATE instance_leases SET expires_at = now() + ($4 || ' seconds')::interval, \
+             updated_at = now() WHERE instance = $1 AND holder = $2 AND epoch = $3",
+            vec![
+                ApiBind::Text(standby.instance.clone()),
+                ApiBind::Text(standby.config.node_id.clone()),
+                ApiBind::Int(epoch),
+                ApiBind::Int(standby.config.lease_secs()),
+            ],
+        )
+        .await?;
+    Ok(updated > 0)
+}
+
+/// Waits until this node may run its instance: right away without leases,
+/// once the lease is taken for a primary, or after a promotion for a
+/// standby. A primary that finds the lease held by a live node becomes
+/// standby.
+pub async fn wait_for_lease(state: &Arc<AppState>) {
+    let standby = &state.standby;
+    if !standby.config.leases {
+        return;
+    }
+    let retry = renew_interval(standby.config.lease_ttl);
+    while standby.role() == Role::Primary {
+        match acquire(state, false).await {
+            Ok(Acquisition::Taken { epoch, .. }) => {
+                standby.epoch.store(epoch, Ordering::Release);
+                spawn_renewer(state.clone(), epoch);
+                info!(instance = %standby.instance, node = %standby.config.node_id, epoch, "Lease da instância adquirida");
+                return;
+            }
+            Ok(Acquisition::Held { holder, remaining }) => {
+                warn!(
+                    instance = %standby.instance,
+                    holder = %holder,
+                    remaining_ms = remaining.as_millis() as u64,
+                    "Lease da instância com outro nó; aguardando promoção"
+                );
+                standby.transition(Role::Primary, Role::Standby);
+            }
+            Err(e) => {
+                warn!(instance = %standby.instance, error = %e, "Falha ao adquirir lease da instância");
+                tokio::time::sleep(retry).await;
+            }
+        }
+    }
+    info!(instance = %standby.instance, node = %standby.config.node_id, "Nó em standby; aguardando promoção");
+    let mut role = standby.role.subscribe();
+    let _ = role.wait_for(|role| *role == Role::Primary).await;
+}
+
+/// Takes the lease for a standby node. Returns the epoch and how long
+/// until the node turns primary.
+pub async fn promote(state: &Arc<AppState>, force: bool) -> Result<(i32, Duration), StandbyError> {
+    let standby = &state.standby;
+    if !standby.config.leases {
+        return Err(StandbyError::Disabled);
+    }
+    standby
+        .begin_promotion()
+        .map_err(StandbyError::NotStandby)?;
+    let acquisition = match acquire(state, force).await {
+        Ok(acquisition) => acquisition,
+        Err(e) => {
+            standby.transition(Role::Promoting, Role::Standby);
+            return Err(e.into());
+        }
+    };
+    let (epoch, wait) = match acquisition {
+        Acquisition::Taken { epoch, wait } => (epoch, wait),
+        Acquisition::Held { holder, remaining } => {
+            standby.transition(Role::Promoting, Role::Standby);
+            return Err(StandbyError::Held { holder, remaining });
+        }
+    };
+    standby.epoch.store(epoch, Ordering::Release);
+    spawn_renewer(state.clone(), epoch);
+    info!(
+        instance = %standby.instance,
+        node = %standby.config.node_id,
+        epoch,
+        wait_ms = wait.as_millis() as u64,
+        force,
+        "Nó promovido; conexão inicia quando a lease anterior expirar"
+    );
+    let state = state.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(wait).await;
+        state.standby.transition(Role::Promoting, Role::Primary);
+    });
+    Ok((epoch, wait))
+}
+
+/// Renews the lease until it is lost or this node stops being primary.
+fn spawn_renewer(state: Arc<AppState>, epoch: i32) {
+    tokio::spawn(async move {
+        let ttl = state.standby.config.lease_ttl;
+        let mut renewed_at = Instant::now();
+        loop {
+            tokio::time::sleep(renew_interval(ttl)).await;
+            if !matches!(state.standby.role(), Role::Primary | Role::Promoting)
+                || state.standby.epoch() != epoch
+            {
+                return;
+            }
+            match renew(&state, epoch).await {
+                Ok(true) => renewed_at = Instant::now(),
+                Ok(false) => {
+                    fence(&state, "lease taken by another node").await;
+                    return;
+                }
+                Err(e) => {
+                    warn!(instance = %state.standby.instance, error = %e, "Falha ao renovar lease da instância");
+                    if must_fence(renewed_at.elapsed(), ttl) {
+                        fence(&state, "lease could not be renewed").await;
+                        return;
+                    }
+                }
+            }
+        }
+    });
+}
+
+/// Stops this node from running its instance: the connection is closed
+/// and held, and the client is no longer used for sends.
+pub async fn fence(state: &AppState, reason: &str) {
+    let standby = &state.standby;
+    standby.role.send_replace(Role::Fenced);
+    if let Some((_, client)) = state.clients.remove(&standby.instance) {
+        client.hold_connection().await;
+        if let Err(e) = client.persistence_manager().flush().await {
+            warn!(instance = %standby.instance, error = %e, "Falha ao gravar estado de autenticação ao isolar o nó");
+        }
+    }
+    update_runtime_state(
+        state,
+        &standby.instance,
+        ConnectionState::Disconnected,
+        Reason::Fenced,
+        json!({ "fenced": reason }),
+    )
+    .await;
+    error!(
+        instance = %standby.instance,
+        node = %standby.config.node_id,
+        reason,
+        "Lease perdida; conexão parada até o processo reiniciar"
+    );
+}
+
+/// Lets the lease run out now, on shutdown, so a standby can be promoted
+/// without waiting for it.
+pub async fn release(state: &AppState) {
+    let standby = &state.standby;
+    if !standby.config.leases || !standby.transition(Role::Primary, Role::Standby) {
+        return;
+    }
+    if let Err(e) = state
+        .api_store
+        .execute(
+            "UPDATE instance_leases SET expires_at = now(), updated_at = now() \
+             WHERE instance = $1 AND holder = $2 AND epoch = $3",
+            vec![
+                ApiBind::Text(standby.instance.clone()),
+                ApiBind::Text(standby.config.node_id.clone()),
+                ApiBind::Int(standby.epoch()),
+            ],
+        )
+        .await
+    {
+        warn!(instance = %standby.instance, error = %e, "Falha ao liberar lease da instância");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    include!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/tests/server/standby_tests.rs"));
+}
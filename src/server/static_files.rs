@@ -0,0 +1,270 @@
+//! Conditional and ranged responses for files served from disk.
+//!
+//! [`serve_file`] answers `If-None-Match`/`If-Modified-Since` with `304`,
+//! single `Range: bytes=` requests with `206` (and `If-Range`), and streams
+//! everything else in chunks. Small files are kept in an LRU cache, keyed
+//! by path and checked against the ETag, so hot assets skip the disk.
+
+use crate::config::DEFAULT_STATIC_CACHE_MB;
+use axum::{
+    body::Body,
+    http::{HeaderMap, HeaderValue, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use moka::future::Cache;
+use moka::policy::EvictionPolicy;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+/// Files up to this size are cached.
+const MAX_CACHED_FILE: u64 = 256 * 1024;
+const READ_CHUNK: usize = 64 * 1024;
+
+#[derive(Debug, Clone)]
+struct CachedFile {
+    etag: String,
+    body: Bytes,
+}
+
+/// LRU cache of small files, bounded by total size.
+#[derive(Clone)]
+pub struct FileCache {
+    entries: Cache<PathBuf, CachedFile>,
+}
+
+impl Default for FileCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_STATIC_CACHE_MB * 1024 * 1024)
+    }
+}
+
+impl FileCache {
+    /// Cache holding up to `capacity_bytes` of file contents; 0 disables it.
+    pub fn new(capacity_bytes: u64) -> Self {
+        Self {
+            entries: Cache::builder()
+                .eviction_policy(EvictionPolicy::lru())
+                .weigher(|_, file: &CachedFile| u32::try_from(file.body.len()).unwrap_or(u32::MAX))
+                .max_capacity(capacity_bytes)
+                .build(),
+        }
+    }
+}
+
+/// Validator of a file version, from its size and modification time.
+pub fn etag(len: u64, modified: SystemTime) -> String {
+    let nanos = modified
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos());
+    format!("\"{len:x}-{nanos:x}\"")
+}
+
+/// `Last-Modified` value (`Sun, 06 Nov 1994 08:49:37 GMT`).
+pub fn http_date(at: SystemTime) -> String {
+    DateTime::<Utc>::from(at)
+        .format("%a, %d %b %Y %H:%M:%S GMT")
+        .to_string()
+}
+
+/// Whether the client copy is current: `If-None-Match` wins over
+/// `If-Modified-Since`, as in RFC 9110.
+pub fn is_not_modified(headers: &HeaderMap, etag: &str, modified: SystemTime) -> bool {
+    if let Some(tags) = header_str(headers, header::IF_NONE_MATCH) {
+        return tags
+            .split(',')
+            .map(|tag| tag.trim().trim_start_matches("W/"))
+            .any(|tag| tag == "*" || tag == etag);
+    }
+    header_str(headers, header::IF_MODIFIED_SINCE)
+        .and_then(|since| DateTime::parse_from_rfc2822(since).ok())
+        .is_some_and(|since| DateTime::<Utc>::from(modified).timestamp() <= since.timestamp())
+}
+
+/// A `Range` outside the file, answered with `416`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Unsatisfiable;
+
+/// Byte range asked by a `Range` header for a file of `len` bytes.
+/// `Ok(None)` serves the whole file (no header, or one this server does
+/// not handle, like multiple ranges).
+pub fn parse_range(value: &str, len: u64) -> Result<Option<Range<u64>>, Unsatisfiable> {
+    let Some(spec) = value.trim().strip_prefix("bytes=") else {
+        return Ok(None);
+    };
+    if spec.contains(',') {
+        return Ok(None);
+    }
+    let Some((start, end)) = spec.trim().split_once('-') else {
+        return Ok(None);
+    };
+    let range = match (start.trim(), end.trim()) {
+        ("", suffix) => {
+            let suffix: u64 = suffix.parse().map_err(|_| Unsatisfiable)?;
+            if suffix == 0 {
+                return Err(Unsatisfiable);
+            }
+            len.saturating_sub(suffix)..len
+        }
+        (start, "") => start.parse().map_err(|_| Unsatisfiable)?..len,
+        (start, end) => {
+            let start: u64 = start.parse().map_err(|_| Unsatisfiable)?;
+            let end: u64 = end.parse().map_err(|_| Unsatisfiable)?;
+            if end < start {
+                return Err(Unsatisfiable);
+            }
+            start..end.saturating_add(1).min(len)
+        }
+    };
+    if range.start >= len {
+        return Err(Unsatisfiable);
+    }
+    Ok(Some(range))
+}
+
+fn set(headers: &mut HeaderMap, name: header::HeaderName, value: &str) {
+    if let Ok(value) = HeaderValue::from_str(value) {
+        headers.insert(name, value);
+    }
+}
+
+fn header_str(headers: &HeaderMap, name: header::HeaderName) -> Option<&str> {
+    headers.get(name).and_then(|v| v.to_str().ok())
+}
+
+/// Serves `path` as `content_type`, honoring the conditional and range
+/// headers of the request.
+pub async fn serve_file(
+    cache: &FileCache,
+    path: &Path,
+    content_type: &str,
+    cache_control: &str,
+    request: &HeaderMap,
+) -> Response {
+    let metadata = match tokio::fs::metadata(path).await {
+        Ok(metadata) if metadata.is_file() => metadata,
+        _ => return StatusCode::NOT_FOUND.into_response(),
+    };
+    let len = metadata.len();
+    let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+    let etag = etag(len, modified);
+
+    let mut headers = HeaderMap::new();
+    set(&mut headers, header::ETAG, &etag);
+    set(&mut headers, header::LAST_MODIFIED, &http_date(modified));
+    set(&mut headers, header::CACHE_CONTROL, cache_control);
+    set(&mut headers, header::ACCEPT_RANGES, "bytes");
+
+    if is_not_modified(request, &etag, modified) {
+        return (StatusCode::NOT_MODIFIED, headers).into_response();
+    }
+
+    // A stale `If-Range` gets the whole (new) file.
+    let range_applies = header_str(request, header::IF_RANGE).is_none_or(|tag| tag == etag);
+    let range = match header_str(request, header::RANGE).filter(|_| range_applies) {
+        Some(value) => match parse_range(value, len) {
+            Ok(range) => range,
+            Err(Unsatisfiable) => {
+                set(
+                    &mut headers,
+                    header::CONTENT_RANGE,
+                    &format!("bytes */{len}"),
+                );
+                return (StatusCode::RANGE_NOT_SATISFIABLE, headers).into_response();
+            }
+        },
+        None => None,
+    };
+    set(&mut headers, header::CONTENT_TYPE, content_type);
+
+    if len <= MAX_CACHED_FILE {
+        let body = match cached_body(cache, path, &etag).await {
+            Ok(body) => body,
+            Err(_) => return StatusCode::NOT_FOUND.into_response(),
+        };
+        return match range {
+            Some(range) => {
+                set(
+                    &mut headers,
+                    header::CONTENT_RANGE,
+                    &content_range(&range, len),
+                );
+                let slice = body.slice(range.start as usize..range.end as usize);
+                (StatusCode::PARTIAL_CONTENT, headers, slice).into_response()
+            }
+            None => (StatusCode::OK, headers, body).into_response(),
+        };
+    }
+
+    let (status, range) = match range {
+        Some(range) => {
+            set(
+                &mut headers,
+                header::CONTENT_RANGE,
+                &content_range(&range, len),
+            );
+            (StatusCode::PARTIAL_CONTENT, range)
+        }
+        None => (StatusCode::OK, 0..len),
+    };
+    set(
+        &mut headers,
+        header::CONTENT_LENGTH,
+        &(range.end - range.start).to_string(),
+    );
+    match stream_range(path, range).await {
+        Ok(body) => (status, headers, body).into_response(),
+        Err(_) => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+fn content_range(range: &Range<u64>, len: u64) -> String {
+    format!("bytes {}-{}/{}", range.start, range.end - 1, len)
+}
+
+async fn cached_body(cache: &FileCache, path: &Path, etag: &str) -> std::io::Result<Bytes> {
+    if let Some(file) = cache.entries.get(path).await
+        && file.etag == etag
+    {
+        return Ok(file.body);
+    }
+    let body = Bytes::from(tokio::fs::read(path).await?);
+    cache
+        .entries
+        .insert(
+            path.to_path_buf(),
+            CachedFile {
+                etag: etag.to_string(),
+                body: body.clone(),
+            },
+        )
+        .await;
+    Ok(body)
+}
+
+/// Body reading `range` of `path` one chunk at a time.
+async fn stream_range(path: &Path, range: Range<u64>) -> std::io::Result<Body> {
+    let mut file = tokio::fs::File::open(path).await?;
+    file.seek(std::io::SeekFrom::Start(range.start)).await?;
+    let reader = file.take(range.end - range.start);
+    let chunks = futures_util::stream::unfold(reader, |mut reader| async move {
+        let mut buf = vec![0u8; READ_CHUNK];
+        match reader.read(&mut buf).await {
+            Ok(0) => None,
+            Ok(n) => {
+                buf.truncate(n);
+                Some((Ok(Bytes::from(buf)), reader))
+            }
+            Err(e) => Some((Err(e), reader)),
+        }
+    });
+    Ok(Body::from_stream(chunks))
+}
+
+#[cfg(test)]
+mod tests {
+    include!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/tests/server/static_files_tests.rs"));
+}
@@ -0,0 +1,250 @@
+//! Deployment summary behind `GET /manager/status` and the startup banner.
+//!
+//! Reports what a deployment actually runs with, so it can be checked
+//! without reading its environment: build version, commit and compiled
+//! features, database provider, protocol and runner settings, event sinks,
+//! dependency health and instances by connection state. Secrets and URLs
+//! are left out.
+
+use crate::server::connection::ConnectionState;
+use crate::server::health::{self, HealthReport};
+use crate::server::runtime_config::RuntimeConfig;
+use crate::server::standby::StandbyStatus;
+use crate::server::{AppState, cloud_api};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use tracing::info;
+
+/// Cargo features that change what a deployment can do.
+const FEATURES: &[(&str, bool)] = &[
+    ("sqlite-storage", cfg!(feature = "sqlite-storage")),
+    ("postgres-storage", cfg!(feature = "postgres-storage")),
+    ("tokio-transport", cfg!(feature = "tokio-transport")),
+    ("ureq-client", cfg!(feature = "ureq-client")),
+    ("signal", cfg!(feature = "signal")),
+    ("nats", cfg!(feature = "nats")),
+    ("graphql", cfg!(feature = "graphql")),
+    ("sentry", cfg!(feature = "sentry")),
+    ("tls", cfg!(feature = "tls")),
+    ("acme", cfg!(feature = "acme")),
+    ("chaos", cfg!(feature = "chaos")),
+    (
+        "danger-skip-tls-verify",
+        cfg!(feature = "danger-skip-tls-verify"),
+    ),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BuildInfo {
+    pub version: &'static str,
+    /// Commit the binary was built from (`CHATWARP_GIT_COMMIT` at build
+    /// time).
+    pub commit: Option<&'static str>,
+    pub features: Vec<&'static str>,
+}
+
+impl BuildInfo {
+    pub fn current() -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION"),
+            commit: option_env!("CHATWARP_GIT_COMMIT").filter(|commit| !commit.is_empty()),
+            features: FEATURES
+                .iter()
+                .filter(|(_, enabled)| *enabled)
+                .map(|(name, _)| *name)
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DatabaseStatus {
+    pub provider: &'static str,
+    /// Whether auth keys and instance tokens are sealed (`SECRETS_MASTER_KEY`).
+    pub secrets_encrypted: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProtocolStatus {
+    /// Integration of instances created without `integration`.
+    pub default_integration: &'static str,
+    /// Whether `/webhook/meta` can verify and receive Cloud API events.
+    pub cloud_api_webhook: bool,
+    pub graph_version: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RunnerStatus {
+    pub supervised: bool,
+    pub max_restarts: u32,
+    pub restart_backoff_ms: u64,
+    pub handshake_max_concurrent: usize,
+    pub handshake_jitter_ms: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SinkStatus {
+    pub name: &'static str,
+    pub enabled: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InstanceCounts {
+    pub total: usize,
+    /// Instances per connection state; states without instances are left
+    /// out.
+    pub by_state: BTreeMap<&'static str, usize>,
+}
+
+impl InstanceCounts {
+    pub fn from_states(states: impl IntoIterator<Item = ConnectionState>) -> Self {
+        let mut counts = Self::default();
+        for state in states {
+            counts.total += 1;
+            *counts.by_state.entry(state.as_str()).or_default() += 1;
+        }
+        counts
+    }
+}
+
+/// Body of `GET /manager/status`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RuntimeStatus {
+    pub build: BuildInfo,
+    pub started_at: DateTime<Utc>,
+    pub uptime_seconds: i64,
+    pub maintenance_mode: bool,
+    pub database: DatabaseStatus,
+    pub protocol: ProtocolStatus,
+    pub runner: RunnerStatus,
+    pub sinks: Vec<SinkStatus>,
+    pub dependencies: HealthReport,
+    pub instances: InstanceCounts,
+    /// Role of this node and its instance lease.
+    pub standby: StandbyStatus,
+}
+
+/// Collects the status, running the health checks.
+pub async fn collect(state: &AppState) -> RuntimeStatus {
+    let runtime = state.runtime_config();
+    let policy = state.restart_policy;
+    let handshake = state.handshake_gate.config();
+
+    let instances: Vec<_> = state
+        .instances
+        .iter()
+        .map(|entry| entry.value().connection_state.clone())
+        .collect();
+    let mut states = Vec::with_capacity(instances.len());
+    for connection in instances {
+        states.push(connection.read().await.state());
+    }
+
+    #[cfg(feature = "nats")]
+    let nats = state.nats.is_some();
+    #[cfg(not(feature = "nats"))]
+    let nats = false;
+
+    RuntimeStatus {
+        build: BuildInfo::current(),
+        started_at: state.started_at,
+        uptime_seconds: (Utc::now() - state.started_at).num_seconds().max(0),
+        maintenance_mode: runtime.maintenance_mode,
+        database: DatabaseStatus {
+            provider: state.database_provider.as_str(),
+            secrets_encrypted: state.secrets.is_some(),
+        },
+        protocol: ProtocolStatus {
+            default_integration: cloud_api::DEFAULT_INTEGRATION,
+            cloud_api_webhook: state.meta.verify_token.is_some(),
+            graph_version: state.meta.graph_version.clone(),
+        },
+        runner: RunnerStatus {
+            supervised: true,
+            max_restarts: policy.max_restarts,
+            restart_backoff_ms: policy.initial_backoff.as_millis() as u64,
+            handshake_max_concurrent: handshake.max_concurrent,
+            handshake_jitter_ms: handshake.jitter.as_millis() as u64,
+        },
+        sinks: sinks(&runtime, nats),
+        dependencies: health::check(state).await,
+        instances: InstanceCounts::from_states(states),
+        standby: state.standby.status(),
+    }
+}
+
+/// Event sinks of this deployment. Per-instance webhooks, `/ws` and
+/// `/events/sse` are always on; the global webhook and NATS depend on the
+/// configuration.
+pub fn sinks(runtime: &RuntimeConfig, nats: bool) -> Vec<SinkStatus> {
+    vec![
+        SinkStatus {
+            name: "webhook",
+            enabled: true,
+        },
+        SinkStatus {
+            name: "webhook_global",
+            enabled: runtime.webhook.enabled && runtime.webhook.url.is_some(),
+        },
+        SinkStatus {
+            name: "ws",
+            enabled: true,
+        },
+        SinkStatus {
+            name: "sse",
+            enabled: true,
+        },
+        SinkStatus {
+            name: "nats",
+            enabled: nats,
+        },
+    ]
+}
+
+/// One structured line with the status, logged once the server is set up.
+pub fn log_banner(status: &RuntimeStatus) {
+    let enabled = |sinks: &[SinkStatus]| {
+        sinks
+            .iter()
+            .filter(|sink| sink.enabled)
+            .map(|sink| sink.name)
+            .collect::<Vec<_>>()
+            .join(",")
+    };
+    let dependencies = status
+        .dependencies
+        .dependencies
+        .iter()
+        .map(|dependency| format!("{}={:?}", dependency.name, dependency.status).to_lowercase())
+        .collect::<Vec<_>>()
+        .join(",");
+    info!(
+        version = status.build.version,
+        commit = status.build.commit.unwrap_or("unknown"),
+        features = %status.build.features.join(","),
+        database = status.database.provider,
+        secrets_encrypted = status.database.secrets_encrypted,
+        integration = status.protocol.default_integration,
+        cloud_api_webhook = status.protocol.cloud_api_webhook,
+        runner_max_restarts = status.runner.max_restarts,
+        handshake_max_concurrent = status.runner.handshake_max_concurrent,
+        sinks = %enabled(&status.sinks),
+        dependencies = %dependencies,
+        health = ?status.dependencies.status,
+        instances = status.instances.total,
+        maintenance = status.maintenance_mode,
+        role = status.standby.role.as_str(),
+        "ChatWarp iniciado"
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    include!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/tests/server/status_tests.rs"));
+}
@@ -0,0 +1,168 @@
+//! Supervision of instance runner tasks.
+//!
+//! A panic inside `Client::run` used to end the spawned task and leave the
+//! instance dead until the process restarted. [`supervise`] watches the
+//! runner's `JoinHandle`, logs the panic, restarts it with exponential
+//! backoff and, once the restart budget is spent, marks the instance
+//! `errored`. A runner that stayed up for `stable_after` gets its budget back.
+
+use crate::server::connection::{ConnectionState, Reason};
+use crate::server::session_events::update_runtime_state;
+use crate::server::{AppState, SessionRuntime};
+use serde_json::json;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::task::JoinError;
+use tracing::Instrument;
+
+pub use crate::config::RestartPolicy;
+
+/// What to do after a runner panicked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Step {
+    /// Restart after the delay; the value is the new restart count.
+    Restart { restarts: u32, delay: Duration },
+    GiveUp,
+}
+
+impl RestartPolicy {
+    /// Delay before restart number `restart` (1-based): doubles each time,
+    /// capped at `max_backoff`.
+    pub fn backoff(&self, restart: u32) -> Duration {
+        let factor = 2u32.saturating_pow(restart.saturating_sub(1));
+        self.initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff)
+    }
+
+    /// Decides the next step after a panic, given the restarts so far and how
+    /// long the crashed run lasted.
+    pub fn next_step(&self, restarts: u32, ran_for: Duration) -> Step {
+        let restarts = if ran_for >= self.stable_after { 0 } else { restarts };
+        if restarts >= self.max_restarts {
+            return Step::GiveUp;
+        }
+        let restarts = restarts + 1;
+        Step::Restart {
+            restarts,
+            delay: self.backoff(restarts),
+        }
+    }
+}
+
+/// Runs `start(attempt)` in its own task until it returns normally,
+/// restarting it after panics as `policy` allows. `attempt` is 0 for the
+/// first run, so the factory can reset leftover state on restarts.
+pub async fn supervise<F, Fut>(
+    state: Arc<AppState>,
+    instance_name: String,
+    policy: RestartPolicy,
+    mut start: F,
+) where
+    F: FnMut(u32) -> Fut,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let mut restarts = 0;
+    loop {
+        update_runtime_state(
+            &state,
+            &instance_name,
+            ConnectionState::Connecting,
+            Reason::Started,
+            json!({ "restarts": restarts }),
+        )
+        .await;
+        let started_at = Instant::now();
+        let span = tracing::info_span!("instance", instance = %instance_name);
+        let runner = start(restarts).instrument(span);
+        #[cfg(feature = "sentry")]
+        let runner = state.sentry_hubs.bind(&instance_name, runner);
+        let result = tokio::spawn(runner).await;
+        let error = match result {
+            Ok(()) => {
+                tracing::info!(instance = %instance_name, "Runner da instância encerrado");
+                return;
+            }
+            Err(e) if e.is_cancelled() => {
+                tracing::info!(instance = %instance_name, "Runner da instância cancelado");
+                return;
+            }
+            Err(e) => e,
+        };
+
+        let panic = panic_message(error);
+        tracing::error!(
+            instance = %instance_name,
+            restarts,
+            panic = %panic,
+            "Runner da instância entrou em pânico"
+        );
+
+        let step = policy.next_step(restarts, started_at.elapsed());
+        #[cfg(feature = "sentry")]
+        state.sentry_hubs.runner_failed(
+            &instance_name,
+            &panic,
+            restarts,
+            step == Step::GiveUp,
+        );
+        match step {
+            Step::Restart {
+                restarts: next,
+                delay,
+            } => {
+                restarts = next;
+                set_restarts(&state, &instance_name, restarts);
+                tracing::warn!(
+                    instance = %instance_name,
+                    restarts,
+                    delay_ms = delay.as_millis() as u64,
+                    "Reiniciando runner da instância"
+                );
+                tokio::time::sleep(delay).await;
+            }
+            Step::GiveUp => {
+                tracing::error!(
+                    instance = %instance_name,
+                    restarts,
+                    "Limite de reinícios atingido; instância marcada como errored"
+                );
+                update_runtime_state(
+                    &state,
+                    &instance_name,
+                    ConnectionState::Errored,
+                    Reason::RunnerCrashed,
+                    json!({ "restarts": restarts }),
+                )
+                .await;
+                return;
+            }
+        }
+    }
+}
+
+fn set_restarts(state: &AppState, instance_name: &str, restarts: u32) {
+    state
+        .sessions_runtime
+        .entry(instance_name.to_string())
+        .or_insert_with(SessionRuntime::new)
+        .runner_restarts = restarts;
+}
+
+/// Panic payload as text, when it is a string.
+fn panic_message(error: JoinError) -> String {
+    match error.try_into_panic() {
+        Ok(payload) => payload
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "non-string panic payload".to_string()),
+        Err(e) => e.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    include!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/tests/server/supervisor_tests.rs"));
+}
@@ -0,0 +1,348 @@
+//! Named message templates.
+//!
+//! Templates live in `message_templates`. `title`, `content`, `footer` and
+//! button texts/URLs may hold `{{variable}}` placeholders; `variables` keeps
+//! default values used when a send does not provide one. Rendered templates
+//! go through the regular message queue: as a `template` message when they
+//! have buttons, as plain `text` otherwise.
+
+use crate::api_store::ApiBind;
+use crate::server::AppState;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::collections::{BTreeMap, BTreeSet};
+use thiserror::Error;
+
+const MAX_NAME_LEN: usize = 64;
+/// WhatsApp renders at most three buttons on template and button messages.
+pub(crate) const MAX_BUTTONS: usize = 3;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum TemplateError {
+    #[error("body must be a JSON object")]
+    NotAnObject,
+    #[error("name must be 1-64 characters of letters, digits, '.', '_' or '-'")]
+    InvalidName,
+    #[error("content is required")]
+    ContentRequired,
+    #[error("variables must be an object of scalar values")]
+    InvalidVariables,
+    #[error("invalid button {index}: {reason}")]
+    InvalidButton { index: usize, reason: String },
+    #[error("at most {MAX_BUTTONS} buttons are allowed")]
+    TooManyButtons,
+    #[error("invalid template: {0}")]
+    Invalid(String),
+    #[error("missing variables: {}", .0.join(", "))]
+    MissingVariables(Vec<String>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ButtonKind {
+    Reply,
+    Url,
+    Call,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TemplateButton {
+    #[serde(rename = "type")]
+    pub kind: ButtonKind,
+    pub text: String,
+    /// Reply id reported back in the button response (`reply` buttons).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub phone: Option<String>,
+}
+
+impl TemplateButton {
+    fn validate(&self, index: usize) -> Result<(), TemplateError> {
+        let invalid = |reason: &str| TemplateError::InvalidButton {
+            index,
+            reason: reason.to_string(),
+        };
+        if self.text.trim().is_empty() {
+            return Err(invalid("text is required"));
+        }
+        match self.kind {
+            ButtonKind::Url if self.url.as_deref().is_none_or(|u| u.trim().is_empty()) => {
+                Err(invalid("url is required"))
+            }
+            ButtonKind::Call if self.phone.as_deref().is_none_or(|p| p.trim().is_empty()) => {
+                Err(invalid("phone is required"))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Template {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    pub content: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub footer: Option<String>,
+    #[serde(default)]
+    pub variables: BTreeMap<String, String>,
+    #[serde(default)]
+    pub buttons: Vec<TemplateButton>,
+}
+
+/// Template with every placeholder filled in.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Rendered {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    pub text: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub footer: Option<String>,
+    pub buttons: Vec<TemplateButton>,
+}
+
+impl Rendered {
+    /// Message type and queue payload for the messages worker.
+    pub fn into_payload(self, session: &str, chat_id: &str) -> (&'static str, Value) {
+        let message_type = if self.buttons.is_empty() { "text" } else { "template" };
+        let mut payload = json!(self);
+        payload["session"] = json!(session);
+        payload["chatId"] = json!(chat_id);
+        (message_type, payload)
+    }
+}
+
+impl Template {
+    /// Validates a create/update body. `name` overrides the body's `name`
+    /// (used by `PUT /templates/:name`).
+    pub fn from_body(name: Option<&str>, body: &Value) -> Result<Self, TemplateError> {
+        let mut body = body.clone();
+        let object = body.as_object_mut().ok_or(TemplateError::NotAnObject)?;
+        if let Some(name) = name {
+            object.insert("name".to_string(), json!(name));
+        }
+        if let Some(variables) = object.get("variables").filter(|v| !v.is_null()) {
+            let variables = values_from(variables)?;
+            object.insert("variables".to_string(), json!(variables));
+        }
+        if !object.get("content").is_some_and(Value::is_string) {
+            return Err(TemplateError::ContentRequired);
+        }
+
+        let template: Template =
+            serde_json::from_value(body).map_err(|e| TemplateError::Invalid(e.to_string()))?;
+        template.validate()?;
+        Ok(template)
+    }
+
+    fn validate(&self) -> Result<(), TemplateError> {
+        if !valid_name(&self.name) {
+            return Err(TemplateError::InvalidName);
+        }
+        if self.content.trim().is_empty() {
+            return Err(TemplateError::ContentRequired);
+        }
+        if self.buttons.len() > MAX_BUTTONS {
+            return Err(TemplateError::TooManyButtons);
+        }
+        self.buttons
+            .iter()
+            .enumerate()
+            .try_for_each(|(index, button)| button.validate(index))
+    }
+
+    /// Every placeholder used anywhere in the template.
+    pub fn placeholders(&self) -> BTreeSet<String> {
+        let mut texts = vec![Some(self.content.as_str()), self.title.as_deref(), self.footer.as_deref()];
+        for button in &self.buttons {
+            texts.extend([Some(button.text.as_str()), button.url.as_deref()]);
+        }
+        texts.into_iter().flatten().flat_map(placeholders_in).collect()
+    }
+
+    /// Fills placeholders from `values`, falling back to the template's
+    /// defaults. Fails listing every placeholder left without a value.
+    pub fn render(&self, values: &BTreeMap<String, String>) -> Result<Rendered, TemplateError> {
+        let missing: Vec<String> = self
+            .placeholders()
+            .into_iter()
+            .filter(|name| !values.contains_key(name) && !self.variables.contains_key(name))
+            .collect();
+        if !missing.is_empty() {
+            return Err(TemplateError::MissingVariables(missing));
+        }
+
+        let lookup = |name: &str| values.get(name).or_else(|| self.variables.get(name)).cloned();
+        let fill = |text: &str| interpolate(text, &lookup);
+        Ok(Rendered {
+            title: self.title.as_deref().map(fill),
+            text: fill(&self.content),
+            footer: self.footer.as_deref().map(fill),
+            buttons: self
+                .buttons
+                .iter()
+                .map(|button| TemplateButton {
+                    text: fill(&button.text),
+                    url: button.url.as_deref().map(fill),
+                    ..button.clone()
+                })
+                .collect(),
+        })
+    }
+}
+
+fn valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= MAX_NAME_LEN
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'))
+}
+
+/// Variable values from a JSON object; numbers and booleans are stringified.
+pub fn values_from(value: &Value) -> Result<BTreeMap<String, String>, TemplateError> {
+    let object = value.as_object().ok_or(TemplateError::InvalidVariables)?;
+    object
+        .iter()
+        .map(|(key, value)| {
+            let value = match value {
+                Value::String(s) => s.clone(),
+                Value::Number(n) => n.to_string(),
+                Value::Bool(b) => b.to_string(),
+                _ => return Err(TemplateError::InvalidVariables),
+            };
+            Ok((key.clone(), value))
+        })
+        .collect()
+}
+
+/// Names inside `{{ }}` in order of appearance. Unterminated braces are
+/// plain text.
+pub fn placeholders_in(text: &str) -> Vec<String> {
+    let mut names = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        let after = &rest[start + 2..];
+        let Some(end) = after.find("}}") else {
+            break;
+        };
+        let name = after[..end].trim();
+        if !name.is_empty() {
+            names.push(name.to_string());
+        }
+        rest = &after[end + 2..];
+    }
+    names
+}
+
+fn interpolate(text: &str, lookup: &impl Fn(&str) -> Option<String>) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        let after = &rest[start + 2..];
+        let Some(end) = after.find("}}") else {
+            break;
+        };
+        out.push_str(&rest[..start]);
+        match lookup(after[..end].trim()) {
+            Some(value) => out.push_str(&value),
+            None => out.push_str(&rest[start..start + end + 4]),
+        }
+        rest = &after[end + 2..];
+    }
+    out.push_str(rest);
+    out
+}
+
+fn template_binds(template: &Template) -> Vec<ApiBind> {
+    vec![
+        ApiBind::Text(template.name.clone()),
+        ApiBind::NullableText(template.title.clone()),
+        ApiBind::Text(template.content.clone()),
+        ApiBind::NullableText(template.footer.clone()),
+        ApiBind::Json(json!(template.variables)),
+        ApiBind::Json(json!(template.buttons)),
+    ]
+}
+
+const SELECT_TEMPLATE: &str = "SELECT jsonb_build_object( \
+        'name', name, 'title', title, 'content', content, 'footer', footer, \
+        'variables', variables, 'buttons', buttons, \
+        'createdAt', created_at, 'updatedAt', updated_at) as value \
+     FROM message_templates";
+
+/// All templates ordered by name, as stored.
+pub async fn list(state: &AppState) -> anyhow::Result<Vec<Value>> {
+    state
+        .api_store
+        .query_json(&format!("{SELECT_TEMPLATE} ORDER BY name"), vec![])
+        .await
+}
+
+/// Stored template as JSON, including timestamps.
+pub async fn get_json(state: &AppState, name: &str) -> anyhow::Result<Option<Value>> {
+    let rows = state
+        .api_store
+        .query_json(
+            &format!("{SELECT_TEMPLATE} WHERE name = $1"),
+            vec![ApiBind::Text(name.to_string())],
+        )
+        .await?;
+    Ok(rows.into_iter().next())
+}
+
+/// Stored template, `None` when no template has that name.
+pub async fn get(state: &AppState, name: &str) -> anyhow::Result<Option<Template>> {
+    Ok(get_json(state, name)
+        .await?
+        .and_then(|row| serde_json::from_value(row).ok()))
+}
+
+/// Inserts a new template; `false` when the name is taken.
+pub async fn create(state: &AppState, template: &Template) -> anyhow::Result<bool> {
+    let rows = state
+        .api_store
+        .execute(
+            "INSERT INTO message_templates (name, title, content, footer, variables, buttons) \
+             VALUES ($1, $2, $3, $4, $5, $6) ON CONFLICT (name) DO NOTHING",
+            template_binds(template),
+        )
+        .await?;
+    Ok(rows > 0)
+}
+
+/// Replaces an existing template; `false` when it does not exist.
+pub async fn update(state: &AppState, template: &Template) -> anyhow::Result<bool> {
+    let rows = state
+        .api_store
+        .execute(
+            "UPDATE message_templates \
+             SET title = $2, content = $3, footer = $4, variables = $5, buttons = $6, \
+                 updated_at = now() \
+             WHERE name = $1",
+            template_binds(template),
+        )
+        .await?;
+    Ok(rows > 0)
+}
+
+/// Deletes a template; `false` when it does not exist.
+pub async fn delete(state: &AppState, name: &str) -> anyhow::Result<bool> {
+    let rows = state
+        .api_store
+        .execute(
+            "DELETE FROM message_templates WHERE name = $1",
+            vec![ApiBind::Text(name.to_string())],
+        )
+        .await?;
+    Ok(rows > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    include!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/tests/server/templates_tests.rs"));
+}
@@ -0,0 +1,112 @@
+//! JPEG thumbnails (`jpegThumbnail`) of outgoing images and videos.
+//!
+//! WhatsApp draws the thumbnail while the media downloads; without one the
+//! bubble stays blank. Images are scaled with the `image` crate and videos
+//! take their first frame through ffmpeg (`FFMPEG_PATH`). When a thumbnail
+//! cannot be made, e.g. ffmpeg is missing or the format is unknown, the
+//! message goes out without it.
+
+use crate::server::audio::{self, AudioError};
+use image::codecs::jpeg::JpegEncoder;
+use std::path::PathBuf;
+use thiserror::Error;
+
+pub use crate::config::ThumbnailConfig;
+
+const THUMBNAIL_QUALITY: u8 = 75;
+/// Writes the first video frame as a PNG.
+const FRAME_ARGS: &[&str] = &["-an", "-frames:v", "1", "-c:v", "png", "-f", "image2"];
+
+#[derive(Debug, Error)]
+pub enum ThumbnailError {
+    #[error("thumbnail failed: {0}")]
+    Image(#[from] image::ImageError),
+    #[error("video frame failed: {0}")]
+    Video(#[from] AudioError),
+    #[error("thumbnail io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("thumbnail task failed: {0}")]
+    Join(#[from] tokio::task::JoinError),
+}
+
+impl ThumbnailError {
+    /// Whether ffmpeg could not be started at all, which is expected on
+    /// hosts without it.
+    pub fn is_unavailable(&self) -> bool {
+        matches!(
+            self,
+            Self::Video(AudioError::Io(err)) if err.kind() == std::io::ErrorKind::NotFound
+        )
+    }
+}
+
+/// JPEG thumbnail together with its dimensions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Thumbnail {
+    pub jpeg: Vec<u8>,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Where the media to thumbnail is read from.
+#[derive(Debug, Clone)]
+pub enum Source {
+    Bytes(Vec<u8>),
+    /// A spooled `/media/upload` file.
+    File(PathBuf),
+}
+
+/// Scales `bytes` down so the longest edge is at most `max_size` and
+/// encodes it as JPEG. Smaller images keep their size.
+pub fn jpeg_thumbnail(bytes: &[u8], max_size: u32) -> Result<Thumbnail, image::ImageError> {
+    let img = image::load_from_memory(bytes)?;
+    let thumb = if img.width() <= max_size && img.height() <= max_size {
+        img.to_rgb8()
+    } else {
+        img.thumbnail(max_size, max_size).to_rgb8()
+    };
+    let mut jpeg = Vec::new();
+    JpegEncoder::new_with_quality(&mut jpeg, THUMBNAIL_QUALITY).encode_image(&thumb)?;
+    Ok(Thumbnail {
+        jpeg,
+        width: thumb.width(),
+        height: thumb.height(),
+    })
+}
+
+/// Thumbnail of an image.
+pub async fn image_thumbnail(source: Source, max_size: u32) -> Result<Thumbnail, ThumbnailError> {
+    tokio::task::spawn_blocking(move || {
+        let bytes = match source {
+            Source::Bytes(bytes) => bytes,
+            Source::File(path) => std::fs::read(path)?,
+        };
+        Ok(jpeg_thumbnail(&bytes, max_size)?)
+    })
+    .await?
+}
+
+/// Thumbnail of the first frame of a video.
+pub async fn video_thumbnail(
+    source: Source,
+    ffmpeg: &str,
+    max_size: u32,
+) -> Result<Thumbnail, ThumbnailError> {
+    let ffmpeg = ffmpeg.to_string();
+    tokio::task::spawn_blocking(move || {
+        let frame = match source {
+            Source::Bytes(bytes) => {
+                let file = audio::write_source(&bytes)?;
+                audio::ffmpeg_file(&ffmpeg, file.path(), FRAME_ARGS)?
+            }
+            Source::File(path) => audio::ffmpeg_file(&ffmpeg, &path, FRAME_ARGS)?,
+        };
+        Ok(jpeg_thumbnail(&frame, max_size)?)
+    })
+    .await?
+}
+
+#[cfg(test)]
+mod tests {
+    include!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/tests/server/thumbnails_tests.rs"));
+}
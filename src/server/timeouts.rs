@@ -0,0 +1,141 @@
+//! Time budgets of HTTP requests.
+//!
+//! Each route falls in a [`RouteGroup`] with its own budget: sends wait on
+//! the queue and WhatsApp, connects on the handshake and queries should be
+//! quick. A request that outlives its budget is answered with `504`
+//! `{"error": "request_timeout"}` and its handler is dropped, instead of
+//! holding the client until a slow sidecar or WhatsApp call returns.
+//! Long-lived routes (`/ws`, SSE, uploads and downloads) have no budget.
+
+use crate::server::AppState;
+use axum::{
+    Json,
+    extract::State,
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
+
+pub use crate::config::TimeoutConfig;
+
+/// Streamed in either direction, so they may legitimately take long.
+const UNBOUNDED_PREFIXES: &[&str] = &["/ws", "/events/sse", "/instance/logs/", "/media/upload/"];
+const MESSAGES_PREFIXES: &[&str] = &[
+    "/message/",
+    "/send",
+    "/reply",
+    "/forwardMessage",
+    "/reaction",
+    "/chat/getBase64FromMediaMessage/",
+    "/integration/inbound/",
+];
+const CONNECT_PREFIXES: &[&str] = &["/instance/connect/", "/instance/create"];
+const QUERIES_PREFIXES: &[&str] = &[
+    "/chat/",
+    "/group/",
+    "/business/",
+    "/contacts",
+    "/checkNumberStatus",
+];
+/// Second segment of the `/:session/...` routes.
+const SESSION_CONNECT: &[&str] = &["auth"];
+const SESSION_QUERIES: &[&str] = &[
+    "chats", "contacts", "groups", "channels", "labels", "lids", "presence", "profile",
+];
+
+/// Routes sharing a budget.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RouteGroup {
+    Messages,
+    Connect,
+    Queries,
+    Other,
+}
+
+impl RouteGroup {
+    /// Group of `path`, or `None` for the routes without a budget.
+    pub fn for_path(path: &str) -> Option<Self> {
+        let starts = |prefixes: &[&str]| prefixes.iter().any(|p| path.starts_with(p));
+        if starts(UNBOUNDED_PREFIXES) || path.ends_with("/download") {
+            return None;
+        }
+        if starts(MESSAGES_PREFIXES) {
+            return Some(Self::Messages);
+        }
+        if starts(CONNECT_PREFIXES) || path.ends_with("/start") {
+            return Some(Self::Connect);
+        }
+        if starts(QUERIES_PREFIXES) {
+            return Some(Self::Queries);
+        }
+        match path.trim_start_matches('/').split('/').nth(1) {
+            Some(segment) if SESSION_CONNECT.contains(&segment) => Some(Self::Connect),
+            Some(segment) if SESSION_QUERIES.contains(&segment) => Some(Self::Queries),
+            _ => Some(Self::Other),
+        }
+    }
+}
+
+impl TimeoutConfig {
+    pub fn budget(&self, group: RouteGroup) -> Option<Duration> {
+        match group {
+            RouteGroup::Messages => self.messages,
+            RouteGroup::Connect => self.connect,
+            RouteGroup::Queries => self.queries,
+            RouteGroup::Other => self.other,
+        }
+    }
+}
+
+/// Answers `504` when the route's budget runs out before the handler.
+pub async fn timeout_middleware(
+    State(state): State<Arc<AppState>>,
+    req: axum::http::Request<axum::body::Body>,
+    next: Next,
+) -> Response {
+    let Some((group, budget)) = RouteGroup::for_path(req.uri().path())
+        .and_then(|group| Some((group, state.timeouts.budget(group)?)))
+    else {
+        return next.run(req).await;
+    };
+
+    let method = req.method().clone();
+    let path = req.uri().path().to_string();
+    match tokio::time::timeout(budget, next.run(req)).await {
+        Ok(response) => response,
+        Err(_) => {
+            tracing::warn!(
+                method = %method,
+                path = %path,
+                group = ?group,
+                timeout_ms = budget.as_millis() as u64,
+                "Requisição excedeu o tempo limite da rota"
+            );
+            timeout_response(group, budget)
+        }
+    }
+}
+
+fn timeout_response(group: RouteGroup, budget: Duration) -> Response {
+    (
+        StatusCode::GATEWAY_TIMEOUT,
+        Json(json!({
+            "error": "request_timeout",
+            "details": {
+                "group": group,
+                "timeoutMs": budget.as_millis() as u64,
+            },
+        })),
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    include!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/tests/server/timeouts_tests.rs"));
+}
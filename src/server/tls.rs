@@ -0,0 +1,229 @@
+//! HTTPS for the API.
+//!
+//! With `TLS_CERT_PATH` and `TLS_KEY_PATH` the server speaks HTTPS with those
+//! PEM files and checks their modification time every `TLS_RELOAD_SECS`: once
+//! either changes (a certbot renewal, say) the new pair is loaded without
+//! dropping open connections. A pair that does not parse is logged and the
+//! current certificate kept until the files change again. Polling, rather
+//! than filesystem events, also notices the symlinks certbot swaps in `live/`.
+//!
+//! With `TLS_ACME_ENABLED` the certificate is requested and renewed from
+//! Let's Encrypt (TLS-ALPN-01, answered on the HTTPS port itself) for
+//! `TLS_ACME_DOMAINS`, by default the host of `SERVER_URL`.
+//!
+//! PEM files need the `tls` feature and ACME the `acme` feature.
+
+use axum::Router;
+use std::io;
+use std::net::SocketAddr;
+use std::path::Path;
+#[cfg(feature = "tls")]
+use std::path::PathBuf;
+#[cfg(feature = "tls")]
+use std::time::Duration;
+
+pub use crate::config::{AcmeSettings, TlsError, TlsMode};
+
+impl TlsError {
+    /// Environment variable the error is about.
+    pub fn variable(&self) -> &'static str {
+        match self {
+            Self::Missing { variable, .. }
+            | Self::Unreadable { variable, .. }
+            | Self::NotPem { variable, .. }
+            | Self::FeatureDisabled { variable, .. } => variable,
+            Self::AcmeWithFiles => "TLS_ACME_ENABLED",
+            Self::NoAcmeDomain => "TLS_ACME_DOMAINS",
+        }
+    }
+}
+
+impl TlsMode {
+    /// Fails when this build cannot serve the mode.
+    pub fn ensure_supported(&self) -> Result<(), TlsError> {
+        match self {
+            Self::Files { .. } if !cfg!(feature = "tls") => Err(TlsError::FeatureDisabled {
+                variable: "TLS_CERT_PATH",
+                feature: "tls",
+            }),
+            Self::Acme(_) if !cfg!(feature = "acme") => Err(TlsError::FeatureDisabled {
+                variable: "TLS_ACME_ENABLED",
+                feature: "acme",
+            }),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Whether `pem` has a block whose label ends with `label`
+/// (`CERTIFICATE`, or `PRIVATE KEY` for PKCS#8, RSA and EC keys).
+pub fn has_pem_block(pem: &[u8], label: &str) -> bool {
+    String::from_utf8_lossy(pem).lines().any(|line| {
+        line.trim()
+            .strip_prefix("-----BEGIN ")
+            .and_then(|rest| rest.strip_suffix("-----"))
+            .is_some_and(|found| found.ends_with(label))
+    })
+}
+
+/// Reads the certificate chain and key, checking that each holds what it
+/// should.
+pub fn read_pem_pair(cert: &Path, key: &Path) -> Result<(Vec<u8>, Vec<u8>), TlsError> {
+    let read = |variable: &'static str, path: &Path, expected: &'static str| {
+        let bytes = std::fs::read(path).map_err(|e| TlsError::Unreadable {
+            variable,
+            path: path.display().to_string(),
+            reason: e.to_string(),
+        })?;
+        if has_pem_block(&bytes, expected) {
+            Ok(bytes)
+        } else {
+            Err(TlsError::NotPem {
+                variable,
+                path: path.display().to_string(),
+                expected,
+            })
+        }
+    };
+    Ok((
+        read("TLS_CERT_PATH", cert, "CERTIFICATE")?,
+        read("TLS_KEY_PATH", key, "PRIVATE KEY")?,
+    ))
+}
+
+/// Serves `app` on `addr`, over HTTPS unless `mode` is [`TlsMode::Off`].
+pub async fn serve(addr: SocketAddr, app: Router, mode: TlsMode) -> io::Result<()> {
+    mode.ensure_supported().map_err(io::Error::other)?;
+    let service = app.into_make_service_with_connect_info::<SocketAddr>();
+    match mode {
+        TlsMode::Off => {
+            let listener = tokio::net::TcpListener::bind(addr).await?;
+            axum::serve(listener, service).await
+        }
+        #[cfg(feature = "tls")]
+        TlsMode::Files {
+            cert,
+            key,
+            reload_interval,
+        } => {
+            install_crypto_provider();
+            let (cert_pem, key_pem) = read_pem_pair(&cert, &key).map_err(io::Error::other)?;
+            let config = axum_server::tls_rustls::RustlsConfig::from_pem(cert_pem, key_pem).await?;
+            if !reload_interval.is_zero() {
+                tokio::spawn(watch_files(config.clone(), cert, key, reload_interval));
+            }
+            axum_server::bind_rustls(addr, config).serve(service).await
+        }
+        #[cfg(feature = "acme")]
+        TlsMode::Acme(settings) => serve_acme(addr, service, settings).await,
+        #[allow(unreachable_patterns)]
+        _ => Err(io::Error::other("TLS mode not compiled in")),
+    }
+}
+
+#[cfg(feature = "tls")]
+fn install_crypto_provider() {
+    // Fails when the transport already installed it, which is fine.
+    let _ = rustls::crypto::ring::default_provider().install_default();
+}
+
+#[cfg(feature = "tls")]
+async fn modified(cert: &Path, key: &Path) -> [Option<std::time::SystemTime>; 2] {
+    let mut stamps = [None, None];
+    for (stamp, path) in stamps.iter_mut().zip([cert, key]) {
+        *stamp = tokio::fs::metadata(path)
+            .await
+            .and_then(|m| m.modified())
+            .ok();
+    }
+    stamps
+}
+
+/// Reloads `config` whenever the certificate or key file changes.
+#[cfg(feature = "tls")]
+async fn watch_files(
+    config: axum_server::tls_rustls::RustlsConfig,
+    cert: PathBuf,
+    key: PathBuf,
+    interval: Duration,
+) {
+    let mut loaded = modified(&cert, &key).await;
+    let mut ticker = tokio::time::interval(interval);
+    ticker.tick().await;
+    loop {
+        ticker.tick().await;
+        let current = modified(&cert, &key).await;
+        if current == loaded {
+            continue;
+        }
+        let pair = {
+            let (cert, key) = (cert.clone(), key.clone());
+            tokio::task::spawn_blocking(move || read_pem_pair(&cert, &key)).await
+        };
+        let result = match pair {
+            Ok(Ok((cert_pem, key_pem))) => config
+                .reload_from_pem(cert_pem, key_pem)
+                .await
+                .map_err(|e| e.to_string()),
+            Ok(Err(e)) => Err(e.to_string()),
+            Err(e) => Err(e.to_string()),
+        };
+        // A failed pair (half-written by the renewal, say) is retried on
+        // the next tick.
+        match result {
+            Ok(()) => {
+                loaded = current;
+                tracing::info!(cert = %cert.display(), "Certificado TLS recarregado");
+            }
+            Err(e) => {
+                tracing::warn!(
+                    cert = %cert.display(),
+                    error = %e,
+                    "Certificado TLS alterado, mas inválido; mantendo o atual"
+                );
+            }
+        }
+    }
+}
+
+#[cfg(feature = "acme")]
+async fn serve_acme(
+    addr: SocketAddr,
+    service: axum::extract::connect_info::IntoMakeServiceWithConnectInfo<Router, SocketAddr>,
+    settings: AcmeSettings,
+) -> io::Result<()> {
+    use futures_util::StreamExt;
+    use rustls_acme::{AcmeConfig, caches::DirCache};
+
+    install_crypto_provider();
+    let mut acme = AcmeConfig::new(settings.domains.clone())
+        .cache(DirCache::new(settings.cache_dir.clone()))
+        .directory_lets_encrypt(!settings.staging);
+    if let Some(contact) = &settings.contact {
+        acme = acme.contact_push(format!("mailto:{contact}"));
+    }
+    let mut state = acme.state();
+    let acceptor = state.axum_acceptor(state.default_rustls_config());
+    tokio::spawn(async move {
+        while let Some(event) = state.next().await {
+            match event {
+                Ok(event) => tracing::info!(event = ?event, "ACME"),
+                Err(e) => tracing::warn!(error = ?e, "Falha ao obter certificado ACME"),
+            }
+        }
+    });
+    tracing::info!(
+        domains = ?settings.domains,
+        staging = settings.staging,
+        "Certificados via ACME"
+    );
+    axum_server::bind(addr)
+        .acceptor(acceptor)
+        .serve(service)
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    include!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/tests/server/tls_tests.rs"));
+}
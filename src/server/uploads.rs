@@ -0,0 +1,192 @@
+//! Media streamed to disk ahead of a send.
+//!
+//! `POST /media/upload/:instance` writes the raw request body chunk by chunk
+//! into `MEDIA_UPLOAD_DIR`, so a large video never sits in memory (nor in the
+//! queue as base64). The returned `mediaId` replaces `url`/`base64` in the
+//! send endpoints, and the messages worker encrypts the file straight from
+//! disk. Uploads are removed `MEDIA_UPLOAD_TTL_MINUTES` after they finish.
+
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use futures_util::{Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use thiserror::Error;
+use tokio::io::{AsyncWriteExt, BufWriter};
+use uuid::Uuid;
+
+pub use crate::config::UploadConfig;
+
+const WRITE_BUFFER: usize = 64 * 1024;
+const SWEEP_EVERY: Duration = Duration::from_secs(5 * 60);
+
+#[derive(Debug, Error)]
+pub enum UploadError {
+    #[error("upload is larger than {limit} bytes")]
+    TooLarge { limit: u64 },
+    #[error("upload interrupted: {0}")]
+    Body(String),
+    #[error("upload not found or expired")]
+    NotFound,
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+impl UploadConfig {
+    /// Largest accepted upload: `MEDIA_UPLOAD_MAX_MB`, or the media quota
+    /// when that is lower.
+    pub fn limit(&self, media_limit: Option<u64>) -> u64 {
+        media_limit.map_or(self.max_bytes, |quota| quota.min(self.max_bytes))
+    }
+
+    fn data_path(&self, id: Uuid) -> PathBuf {
+        self.dir.join(id.to_string())
+    }
+
+    fn meta_path(&self, id: Uuid) -> PathBuf {
+        self.dir.join(format!("{id}.json"))
+    }
+}
+
+/// Finished upload, kept next to its data as `<mediaId>.json`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UploadedMedia {
+    pub media_id: Uuid,
+    pub session: String,
+    pub mimetype: Option<String>,
+    pub file_name: Option<String>,
+    pub size: u64,
+    pub sha256: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Writes `body` to a new upload of `session`, failing as soon as it passes
+/// `limit` bytes. A failed upload leaves nothing behind.
+pub async fn spool<S, E>(
+    config: &UploadConfig,
+    session: &str,
+    mimetype: Option<String>,
+    file_name: Option<String>,
+    body: S,
+    limit: u64,
+) -> Result<UploadedMedia, UploadError>
+where
+    S: Stream<Item = Result<Bytes, E>>,
+    E: Display,
+{
+    tokio::fs::create_dir_all(&config.dir).await?;
+    let id = Uuid::new_v4();
+    let path = config.data_path(id);
+    let written = write_body(&path, body, limit).await;
+    let (size, sha256) = match written {
+        Ok(written) => written,
+        Err(e) => {
+            let _ = tokio::fs::remove_file(&path).await;
+            return Err(e);
+        }
+    };
+
+    let upload = UploadedMedia {
+        media_id: id,
+        session: session.to_string(),
+        mimetype,
+        file_name,
+        size,
+        sha256,
+        created_at: Utc::now(),
+    };
+    let meta = serde_json::to_vec(&upload).map_err(std::io::Error::other)?;
+    if let Err(e) = tokio::fs::write(config.meta_path(id), meta).await {
+        let _ = tokio::fs::remove_file(&path).await;
+        return Err(e.into());
+    }
+    Ok(upload)
+}
+
+async fn write_body<S, E>(path: &Path, body: S, limit: u64) -> Result<(u64, String), UploadError>
+where
+    S: Stream<Item = Result<Bytes, E>>,
+    E: Display,
+{
+    let mut file = BufWriter::with_capacity(WRITE_BUFFER, tokio::fs::File::create(path).await?);
+    let mut hasher = Sha256::new();
+    let mut size = 0u64;
+    let mut body = std::pin::pin!(body);
+    while let Some(chunk) = body.next().await {
+        let chunk = chunk.map_err(|e| UploadError::Body(e.to_string()))?;
+        size += chunk.len() as u64;
+        if size > limit {
+            return Err(UploadError::TooLarge { limit });
+        }
+        hasher.update(&chunk);
+        file.write_all(&chunk).await?;
+    }
+    file.flush().await?;
+    Ok((size, hex::encode(hasher.finalize())))
+}
+
+/// Upload `media_id` of `session` and the path of its data.
+pub async fn find(
+    config: &UploadConfig,
+    session: &str,
+    media_id: &str,
+) -> Result<(UploadedMedia, PathBuf), UploadError> {
+    let id = Uuid::parse_str(media_id.trim()).map_err(|_| UploadError::NotFound)?;
+    let meta = match tokio::fs::read(config.meta_path(id)).await {
+        Ok(meta) => meta,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Err(UploadError::NotFound),
+        Err(e) => return Err(e.into()),
+    };
+    let upload: UploadedMedia = serde_json::from_slice(&meta).map_err(|_| UploadError::NotFound)?;
+    let path = config.data_path(id);
+    if upload.session != session || !tokio::fs::try_exists(&path).await? {
+        return Err(UploadError::NotFound);
+    }
+    Ok((upload, path))
+}
+
+/// Removes uploads older than the TTL every few minutes.
+pub fn spawn_sweeper(config: UploadConfig) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            match sweep(&config, SystemTime::now()).await {
+                Ok(0) => {}
+                Ok(removed) => tracing::debug!(removed, "Uploads de mídia expirados removidos"),
+                Err(e) => tracing::warn!(error = %e, "Falha ao limpar uploads de mídia"),
+            }
+            tokio::time::sleep(SWEEP_EVERY).await;
+        }
+    })
+}
+
+/// Deletes the files of `config.dir` last written before `now - ttl`.
+/// Returns how many were removed.
+pub async fn sweep(config: &UploadConfig, now: SystemTime) -> std::io::Result<usize> {
+    let mut entries = match tokio::fs::read_dir(&config.dir).await {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e),
+    };
+    let mut removed = 0;
+    while let Some(entry) = entries.next_entry().await? {
+        let metadata = entry.metadata().await?;
+        let expired = metadata
+            .modified()
+            .ok()
+            .and_then(|modified| now.duration_since(modified).ok())
+            .is_some_and(|age| age > config.ttl);
+        if metadata.is_file() && expired && tokio::fs::remove_file(entry.path()).await.is_ok() {
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    include!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/tests/server/uploads_tests.rs"));
+}
@@ -0,0 +1,147 @@
+//! Versioned mounting of the HTTP API.
+//!
+//! The API lives under `/api/v1`. Requests there are rewritten to the
+//! unprefixed route before routing, so the route table, the audit log and
+//! the workspace guard see one set of paths. The legacy root paths keep
+//! working while `LEGACY_ROUTES` is on (the default), answering with
+//! `Deprecation` and a `Link` to their `/api/v1` successor; with it off they
+//! answer 404. Health, auth, docs and the Meta webhook stay at the root.
+
+use axum::{
+    Json,
+    body::Body,
+    extract::{Request, State},
+    http::{HeaderValue, StatusCode, Uri},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde_json::{Map, Value, json};
+
+pub use crate::config::ApiMount;
+
+/// Prefix of the current API version.
+pub const V1_PREFIX: &str = "/api/v1";
+
+/// Paths served at the root only, outside of any version.
+const UNVERSIONED: &[&str] = &[
+    "/",
+    "/auth/login",
+    "/auth/logout",
+    "/healthz",
+    "/healthz/deep",
+    "/readyz",
+    "/metrics",
+    "/metrics/prometheus",
+    "/openapi.json",
+    "/docs/openapi.json",
+    "/swagger",
+    "/docs/swagger",
+    "/webhook/meta",
+];
+
+pub fn is_unversioned(path: &str) -> bool {
+    UNVERSIONED.contains(&path)
+}
+
+/// How a request path maps onto the route table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Mounted {
+    /// `/api/v1/...`, routed as the unprefixed path.
+    V1(String),
+    /// Root-level system route.
+    Unversioned,
+    /// Pre-versioning path of a versioned route.
+    Legacy,
+}
+
+pub fn classify(path: &str) -> Mounted {
+    match path.strip_prefix(V1_PREFIX) {
+        Some("") => Mounted::V1("/".to_string()),
+        Some(rest) if rest.starts_with('/') => Mounted::V1(rest.to_string()),
+        _ if is_unversioned(path) => Mounted::Unversioned,
+        _ => Mounted::Legacy,
+    }
+}
+
+/// Rewrites `/api/v1` requests to their route and marks or refuses legacy
+/// ones. Runs before routing.
+pub async fn mount_middleware(
+    State(mount): State<ApiMount>,
+    mut req: Request<Body>,
+    next: Next,
+) -> Response {
+    let path = req.uri().path().to_string();
+    match classify(&path) {
+        Mounted::V1(route) => {
+            if let Some(uri) = with_path(req.uri(), &route) {
+                *req.uri_mut() = uri;
+            }
+            next.run(req).await
+        }
+        Mounted::Unversioned => next.run(req).await,
+        Mounted::Legacy if !mount.legacy_routes => (
+            StatusCode::NOT_FOUND,
+            Json(json!({
+                "error": "legacy_route_disabled",
+                "successor": format!("{V1_PREFIX}{path}"),
+            })),
+        )
+            .into_response(),
+        Mounted::Legacy => {
+            let mut response = next.run(req).await;
+            let headers = response.headers_mut();
+            headers.insert("deprecation", HeaderValue::from_static("true"));
+            if let Ok(link) =
+                HeaderValue::from_str(&format!("<{V1_PREFIX}{path}>; rel=\"successor-version\""))
+            {
+                headers.insert("link", link);
+            }
+            response
+        }
+    }
+}
+
+fn with_path(uri: &Uri, path: &str) -> Option<Uri> {
+    let path_and_query = match uri.query() {
+        Some(query) => format!("{path}?{query}"),
+        None => path.to_string(),
+    };
+    let mut parts = uri.clone().into_parts();
+    parts.path_and_query = Some(path_and_query.parse().ok()?);
+    Uri::from_parts(parts).ok()
+}
+
+/// The OpenAPI document as mounted: versioned routes under `/api/v1`, plus
+/// their legacy paths flagged `deprecated` while those are served.
+pub fn mount_openapi(mut document: Value, mount: ApiMount) -> Value {
+    let Some(paths) = document.get_mut("paths").and_then(Value::as_object_mut) else {
+        return document;
+    };
+    let mut mounted = Map::new();
+    for (path, item) in std::mem::take(paths) {
+        if is_unversioned(&path) {
+            mounted.insert(path, item);
+            continue;
+        }
+        mounted.insert(format!("{V1_PREFIX}{path}"), item.clone());
+        if mount.legacy_routes {
+            mounted.insert(path, deprecated(item));
+        }
+    }
+    *paths = mounted;
+    document
+}
+
+fn deprecated(mut item: Value) -> Value {
+    if let Some(operations) = item.as_object_mut() {
+        for operation in operations.values_mut().filter_map(Value::as_object_mut) {
+            operation.insert("deprecated".to_string(), Value::Bool(true));
+        }
+    }
+    item
+}
+
+#[cfg(test)]
+mod tests {
+    include!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/tests/server/versioning_tests.rs"));
+}
@@ -1,46 +1,55 @@
 use crate::api_store::ApiBind;
 use crate::models::webhook_model::WebhookConfig;
+use crate::server::http_client::SharedHttpClient;
+use crate::server::event_filters;
+use crate::server::outbox;
 use crate::server::queue::{Queue, WebhookJob, WebhookQueue};
 use crate::server::AppState;
-use chatwarp_api_ureq_http_client::UreqHttpClient;
 use chrono::Utc;
+use hmac::{Hmac, Mac};
 use serde_json::{json, Value};
+use sha2::Sha256;
 use std::collections::HashMap;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tokio::time::sleep;
 use tracing::{debug, error, warn};
 use uuid::Uuid;
 use warp_core::net::{HttpClient, HttpRequest};
 
+/// Emits an event through the outbox. Without it (no database, or the
+/// insert failed) the event is published directly and its webhook queued
+/// best effort, as before the outbox existed.
 pub async fn enqueue(state: &AppState, session: Option<&str>, event: &str, data: Value) {
     debug!(session = ?session, event = %event, "Enfileirando webhook para processamento");
-    let payload = json!({
-        "event": event,
-        "instance": session.unwrap_or(""),
-        "data": data
-    });
-
-    // Mantém compatibilidade com o fluxo atual de inserção.
-    let _ = state
-        .api_store
-        .execute(
-            "INSERT INTO webhook_outbox (session, event, payload) VALUES ($1, $2, $3)",
-            vec![
-                ApiBind::NullableText(session.map(|s| s.to_string())),
-                ApiBind::Text(event.to_string()),
-                ApiBind::Json(payload),
-            ],
-        )
-        .await;
+    let outbox_event = outbox::prepare(state, session, event, data).await;
+    if let Err(err) = outbox::record(state, &outbox_event).await {
+        debug!(event = %event, error = %err, "Outbox indisponível, publicando diretamente");
+        outbox::publish(state, &outbox_event);
+        outbox::invalidate(state, &outbox_event).await;
+        let _ = state
+            .api_store
+            .execute(
+                "INSERT INTO webhook_outbox (id, session, event, payload) VALUES ($1, $2, $3, $4)",
+                vec![
+                    ApiBind::Uuid(outbox_event.id),
+                    ApiBind::NullableText(outbox_event.session),
+                    ApiBind::Text(outbox_event.event),
+                    ApiBind::Json(outbox_event.payload),
+                ],
+            )
+            .await;
+    }
 }
 
 pub fn spawn_worker(state: Arc<AppState>) -> tokio::task::JoinHandle<()> {
     tokio::spawn(async move {
-        let client = UreqHttpClient::new();
         let queue = WebhookQueue::new(state.clone());
         loop {
-            if let Err(err) = process_outbox(&state, &queue, &client).await {
+            // Deliveries stay in the outbox while maintenance mode is on.
+            if !state.runtime_config().maintenance_mode
+                && let Err(err) = process_outbox(&state, &queue, &state.http).await
+            {
                 log::warn!("webhook worker error: {err}");
             }
             sleep(Duration::from_secs(5)).await;
@@ -51,7 +60,7 @@ pub fn spawn_worker(state: Arc<AppState>) -> tokio::task::JoinHandle<()> {
 async fn process_outbox(
     state: &AppState,
     queue: &WebhookQueue,
-    client: &UreqHttpClient,
+    client: &SharedHttpClient,
 ) -> anyhow::Result<()> {
     let jobs = queue.claim_batch(25).await?;
 
@@ -74,7 +83,7 @@ async fn process_outbox(
             }
         }
 
-        if let Some(cfg) = load_global_webhook(state, &event).await {
+        if let Some(cfg) = load_global_webhook(state, session.as_deref(), &event).await {
             targets.push(cfg);
         }
 
@@ -98,30 +107,58 @@ async fn process_outbox(
             };
 
             let enriched = enrich_payload(&payload, &url, target.base64);
-            let mut req = HttpRequest::post(&url)
-                .with_header("Content-Type", "application/json")
-                .with_body(serde_json::to_vec(&enriched)?);
+            let body = serde_json::to_vec(&enriched)?;
+            let mut req = HttpRequest::post(&url).with_header("Content-Type", "application/json");
 
             for (k, v) in target.headers.iter() {
                 req = req.with_header(k, v);
             }
+            // Applied after the custom headers so they cannot override it.
+            if let Some(secret) = target.secret.as_deref() {
+                req = req.with_header(
+                    SIGNATURE_HEADER,
+                    signature_header(secret, Utc::now().timestamp(), &body)?,
+                );
+            }
+            let req = req.with_body(body);
 
             debug!(url = %url, event = %event, "Enviando requisição de webhook");
-            match client.execute(req).await {
+            let started = Instant::now();
+            let result = client.execute(req).await;
+            let mut delivery = Delivery {
+                outbox_id: id,
+                session: session.clone(),
+                event: event.clone(),
+                url: url.clone(),
+                attempt: attempts + 1,
+                status_code: None,
+                latency_ms: started.elapsed().as_millis().min(i32::MAX as u128) as i32,
+                response_snippet: None,
+                error: None,
+            };
+            match result {
                 Ok(resp) if (200..300).contains(&resp.status_code) => {
                     debug!(url = %url, event = %event, status = %resp.status_code, "Webhook enviado com sucesso");
+                    delivery.status_code = Some(resp.status_code);
+                    delivery.response_snippet = Some(response_snippet(&resp.body));
                 }
                 Ok(resp) => {
                     all_ok = false;
                     warn!(url = %url, event = %event, status = %resp.status_code, "Falha no envio do webhook (status não-2xx)");
                     last_error = Some(format!("http {}", resp.status_code));
+                    delivery.status_code = Some(resp.status_code);
+                    delivery.response_snippet = Some(response_snippet(&resp.body));
                 }
                 Err(err) => {
                     all_ok = false;
                     error!(url = %url, event = %event, error = %err, "Erro ao enviar webhook");
                     last_error = Some(err.to_string());
+                    delivery.error = Some(err.to_string());
                 }
             }
+            if let Err(err) = record_delivery(state, delivery).await {
+                debug!(error = %err, "Registro de entrega do webhook não salvo");
+            }
         }
 
         if all_ok {
@@ -136,6 +173,138 @@ async fn process_outbox(
     Ok(())
 }
 
+/// Bytes of the receiver's response body kept in `webhook_deliveries`.
+const RESPONSE_SNIPPET_BYTES: usize = 512;
+pub const DEFAULT_DELIVERIES_LIMIT: i32 = 50;
+pub const MAX_DELIVERIES_LIMIT: i32 = 500;
+
+/// One HTTP attempt to deliver an outbox entry to one target.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Delivery {
+    pub outbox_id: Uuid,
+    pub session: Option<String>,
+    pub event: String,
+    pub url: String,
+    pub attempt: i32,
+    pub status_code: Option<u16>,
+    pub latency_ms: i32,
+    pub response_snippet: Option<String>,
+    pub error: Option<String>,
+}
+
+/// First bytes of a response body as text, cut on a char boundary.
+pub fn response_snippet(body: &[u8]) -> String {
+    let text = String::from_utf8_lossy(&body[..body.len().min(RESPONSE_SNIPPET_BYTES)]);
+    text.trim_end_matches('\u{FFFD}').to_string()
+}
+
+async fn record_delivery(state: &AppState, delivery: Delivery) -> anyhow::Result<()> {
+    state
+        .api_store
+        .execute(
+            "INSERT INTO webhook_deliveries \
+                (outbox_id, session, event, url, attempt, status_code, latency_ms, response_snippet, error) \
+             VALUES ($1, $2, $3, $4, $5, $6::int, $7, $8, $9)",
+            vec![
+                ApiBind::Uuid(delivery.outbox_id),
+                ApiBind::NullableText(delivery.session),
+                ApiBind::Text(delivery.event),
+                ApiBind::Text(delivery.url),
+                ApiBind::Int(delivery.attempt),
+                ApiBind::NullableText(delivery.status_code.map(|c| c.to_string())),
+                ApiBind::Int(delivery.latency_ms),
+                ApiBind::NullableText(delivery.response_snippet),
+                ApiBind::NullableText(delivery.error),
+            ],
+        )
+        .await?;
+    Ok(())
+}
+
+/// Delivery attempts of an instance, newest first. `failed_only` keeps
+/// attempts without a 2xx response.
+pub async fn list_deliveries(
+    state: &AppState,
+    session: &str,
+    event: Option<String>,
+    failed_only: bool,
+    limit: i32,
+) -> anyhow::Result<Vec<Value>> {
+    state
+        .api_store
+        .query_json(
+            "SELECT row_to_json(t)::jsonb as value FROM ( \
+                SELECT id, outbox_id, event, url, attempt, status_code, latency_ms, \
+                       response_snippet, error, created_at \
+                FROM webhook_deliveries \
+                WHERE session = $1 \
+                  AND ($2::text IS NULL OR event = $2) \
+                  AND (NOT $3 OR status_code IS NULL OR status_code NOT BETWEEN 200 AND 299) \
+                ORDER BY created_at DESC \
+                LIMIT $4 \
+            ) t",
+            vec![
+                ApiBind::Text(session.to_string()),
+                ApiBind::NullableText(event),
+                ApiBind::Bool(failed_only),
+                ApiBind::Int(limit),
+            ],
+        )
+        .await
+}
+
+/// Queues the payload of a past delivery again as a new outbox entry, so it
+/// goes to the instance's current targets without replaying the WA event.
+/// `workspace` limits the lookup to that workspace's instances. Returns the
+/// new outbox id, or `None` when the delivery does not exist.
+pub async fn redeliver(
+    state: &AppState,
+    delivery_id: Uuid,
+    workspace: Option<Uuid>,
+) -> anyhow::Result<Option<Uuid>> {
+    let rows = state
+        .api_store
+        .query_json(
+            "WITH inserted AS ( \
+                INSERT INTO webhook_outbox (session, event, payload) \
+                SELECT o.session, o.event, o.payload \
+                FROM webhook_deliveries d JOIN webhook_outbox o ON o.id = d.outbox_id \
+                WHERE d.id = $1 \
+                  AND ($2::uuid IS NULL OR o.session IN \
+                       (SELECT session FROM api_sessions WHERE workspace_id = $2::uuid)) \
+                RETURNING id \
+            ) SELECT to_jsonb(id) as value FROM inserted",
+            vec![
+                ApiBind::Uuid(delivery_id),
+                ApiBind::NullableText(workspace.map(|id| id.to_string())),
+            ],
+        )
+        .await?;
+    Ok(rows
+        .first()
+        .and_then(Value::as_str)
+        .and_then(|id| Uuid::parse_str(id).ok()))
+}
+
+/// Header carrying `t=<unix seconds>,v1=<hex HMAC-SHA256>`.
+pub const SIGNATURE_HEADER: &str = "X-Chatwarp-Signature";
+
+/// HMAC-SHA256 of `"{timestamp}.{body}"` keyed with the webhook secret.
+/// Receivers recompute it over the raw body and reject stale timestamps.
+pub fn sign_payload(secret: &str, timestamp: i64, body: &[u8]) -> anyhow::Result<String> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+        .map_err(|e| anyhow::anyhow!("invalid webhook secret: {e}"))?;
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    Ok(hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Value of [`SIGNATURE_HEADER`].
+pub fn signature_header(secret: &str, timestamp: i64, body: &[u8]) -> anyhow::Result<String> {
+    Ok(format!("t={},v1={}", timestamp, sign_payload(secret, timestamp, body)?))
+}
+
 fn enrich_payload(payload: &Value, destination: &str, base64_enabled: bool) -> Value {
     let mut obj = payload.as_object().cloned().unwrap_or_default();
     if !base64_enabled {
@@ -218,12 +387,8 @@ fn event_path(event: &str) -> String {
     event.to_lowercase().replace('_', "-")
 }
 
-fn event_allowed(events: &Option<Vec<String>>, event: &str) -> bool {
-    match events {
-        None => true,
-        Some(list) if list.is_empty() => true,
-        Some(list) => list.iter().any(|e| e == event),
-    }
+pub(crate) fn event_allowed(events: &Option<Vec<String>>, event: &str) -> bool {
+    event_filters::allows(events.as_deref(), event)
 }
 
 pub async fn load_instance_webhook(
@@ -245,7 +410,7 @@ pub async fn load_instance_webhook(
         .query_json(
             "SELECT row_to_json(t)::jsonb as value FROM ( \
                 SELECT webhook_enabled, webhook_url, webhook_by_events, webhook_base64, \
-                       webhook_headers, webhook_events \
+                       webhook_headers, webhook_events, webhook_secret \
                 FROM api_sessions WHERE session = $1 \
             ) t",
             vec![ApiBind::Text(session.to_string())],
@@ -297,6 +462,13 @@ pub async fn load_instance_webhook(
                 .collect::<Vec<_>>()
         });
 
+    let secret = row
+        .get("webhook_secret")
+        .and_then(|v| v.as_str())
+        .filter(|s| !s.is_empty())
+        .map(|s| state.open_secret(s))
+        .transpose()?;
+
     if url.is_empty() {
         state.webhook_config_cache.insert(
             session.to_string(),
@@ -312,6 +484,7 @@ pub async fn load_instance_webhook(
         base64,
         headers,
         events,
+        secret,
     };
 
     state.webhook_config_cache.insert(
@@ -322,12 +495,15 @@ pub async fn load_instance_webhook(
     Ok(Some(config))
 }
 
-async fn load_global_webhook(state: &AppState, event: &str) -> Option<WebhookConfig> {
-    let enabled = std::env::var("WEBHOOK_GLOBAL_ENABLED")
-        .ok()
-        .map(|v| v == "true" || v == "1")
-        .unwrap_or(false);
-    if !enabled {
+/// Global webhook target of an event, unless it is off or the instance is
+/// outside `GLOBAL_SINK_INSTANCES`/`GLOBAL_SINK_EXCLUDE_INSTANCES`.
+async fn load_global_webhook(
+    state: &AppState,
+    session: Option<&str>,
+    event: &str,
+) -> Option<WebhookConfig> {
+    let global = state.runtime_config().webhook;
+    if !global.enabled || !state.global_sinks.includes(session.unwrap_or_default()) {
         return None;
     }
 
@@ -336,22 +512,18 @@ async fn load_global_webhook(state: &AppState, event: &str) -> Option<WebhookCon
         return None;
     }
 
-    let url = std::env::var("WEBHOOK_GLOBAL_URL").ok()?;
-    let by_events = std::env::var("WEBHOOK_GLOBAL_WEBHOOK_BY_EVENTS")
-        .ok()
-        .map(|v| v == "true" || v == "1")
-        .unwrap_or(false);
-    let base64 = std::env::var("WEBHOOK_GLOBAL_WEBHOOK_BASE64")
-        .ok()
-        .map(|v| v == "true" || v == "1")
-        .unwrap_or(false);
-
     Some(WebhookConfig {
         enabled: true,
-        url,
-        by_events,
-        base64,
-        headers: HashMap::new(),
+        url: global.url?,
+        by_events: global.by_events,
+        base64: global.base64,
+        headers: global.headers.into_iter().collect(),
         events: None,
+        secret: global.secret.filter(|s| !s.is_empty()),
     })
 }
+
+#[cfg(test)]
+mod tests {
+    include!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/tests/server/webhooks_tests.rs"));
+}
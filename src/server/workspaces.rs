@@ -0,0 +1,200 @@
+//! Multi-tenant workspaces.
+//!
+//! `CHATWARP_PASSWORD` stays the admin key. Keys created with a
+//! `workspaceId` authenticate as that workspace: they only see instances
+//! whose `api_sessions.workspace_id` matches and cannot reach admin routes.
+
+use crate::api_store::ApiBind;
+use crate::server::audit::instance_from_path;
+use crate::server::{AppState, body};
+use axum::{
+    Json,
+    body::Body,
+    extract::{MatchedPath, State},
+    http::{Method, Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde_json::{Value, json};
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Routes a workspace key may never call.
+const ADMIN_ONLY_PREFIXES: &[&str] = &[
+    "/manager",
+    "/keys",
+    "/workspaces",
+    "/settings",
+    "/templates",
+    "/apps",
+    "/server",
+    "/contacts/all",
+    "/ws",
+    "/chaos",
+];
+
+/// Who the caller authenticated as; stored as a request extension by the
+/// auth middleware. Requests without it (auth disabled) act as admin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scope {
+    Admin,
+    Workspace(Uuid),
+}
+
+impl Scope {
+    /// Workspace the caller is limited to, `None` for admin.
+    pub fn workspace(&self) -> Option<Uuid> {
+        match self {
+            Self::Admin => None,
+            Self::Workspace(id) => Some(*id),
+        }
+    }
+}
+
+/// Hex SHA-256 stored in `api_keys.key_hash`.
+pub fn hash_api_key(key: &str) -> String {
+    hex::encode(Sha256::digest(key.as_bytes()))
+}
+
+/// Whether `path` is reserved for the admin key.
+pub fn is_admin_only(path: &str) -> bool {
+    ADMIN_ONLY_PREFIXES.iter().any(|prefix| {
+        path.strip_prefix(prefix)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+    })
+}
+
+/// Routes that do not act on one instance and are safe for any workspace.
+/// `GET /sessions`, `GET /instance/fetchInstances`, `/graphql` and
+/// redelivery are scoped by their handlers; export downloads by their
+/// signature.
+fn is_instance_free(method: &Method, path: &str) -> bool {
+    matches!(path, "/ping" | "/health" | "/auth/logout" | "/graphql")
+        || (*method == Method::GET && matches!(path, "/sessions" | "/instance/fetchInstances"))
+        || (*method == Method::POST && path.starts_with("/webhook/redeliver/"))
+        || (*method == Method::GET && crate::server::exports::is_download_path(path))
+}
+
+/// Instance a request acts on: the route parameter, then the `session`
+/// query parameter, then the `session` field of a JSON body.
+pub fn instance_from_request(
+    route: &str,
+    path: &str,
+    query: Option<&str>,
+    body: Option<&Value>,
+) -> Option<String> {
+    instance_from_path(route, path)
+        .or_else(|| {
+            query?
+                .split('&')
+                .filter_map(|pair| pair.split_once('='))
+                .find(|(key, _)| *key == "session")
+                .map(|(_, value)| value.to_string())
+        })
+        .or_else(|| body?.get("session")?.as_str().map(str::to_string))
+        .filter(|value| !value.is_empty())
+}
+
+/// Owner of `instance`: `None` when the instance is not registered,
+/// `Some(None)` when it has no workspace.
+pub async fn instance_owner(
+    state: &AppState,
+    instance: &str,
+) -> anyhow::Result<Option<Option<Uuid>>> {
+    let rows = state
+        .api_store
+        .query_json(
+            "SELECT jsonb_build_object('workspace_id', workspace_id) as value \
+             FROM api_sessions WHERE session = $1",
+            vec![ApiBind::Text(instance.to_string())],
+        )
+        .await?;
+    Ok(rows.first().map(|row| {
+        row["workspace_id"]
+            .as_str()
+            .and_then(|id| Uuid::parse_str(id).ok())
+    }))
+}
+
+/// Confines workspace keys to their own instances. Unregistered or foreign
+/// instances answer 404 so other tenants' instance names do not leak.
+pub async fn scope_guard(
+    State(state): State<Arc<AppState>>,
+    req: Request<Body>,
+    next: Next,
+) -> Response {
+    let Some(Scope::Workspace(workspace)) = req.extensions().get::<Scope>().copied() else {
+        return next.run(req).await;
+    };
+
+    let path = req.uri().path().to_string();
+    if is_admin_only(&path) {
+        return (
+            StatusCode::FORBIDDEN,
+            Json(json!({"error": "workspace_forbidden", "route": path})),
+        )
+            .into_response();
+    }
+    if is_instance_free(req.method(), &path) {
+        return next.run(req).await;
+    }
+
+    let route = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|m| m.as_str().to_string())
+        .unwrap_or_else(|| path.clone());
+    let query = req.uri().query().map(str::to_string);
+    let creating = *req.method() == Method::POST && path == "/sessions";
+
+    // The body is only read when neither the path nor the query names the
+    // instance.
+    let (parts, body) = req.into_parts();
+    let (instance, body) = match instance_from_request(&route, &path, query.as_deref(), None) {
+        Some(instance) => (Some(instance), body),
+        None => {
+            let bytes = match body::buffer(body, state.body_limit).await {
+                Ok(bytes) => bytes,
+                Err(response) => return response,
+            };
+            let json_body = serde_json::from_slice::<Value>(&bytes).ok();
+            let instance =
+                instance_from_request(&route, &path, query.as_deref(), json_body.as_ref());
+            (instance, Body::from(bytes))
+        }
+    };
+    let Some(instance) = instance else {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({"error": "session_required"})),
+        )
+            .into_response();
+    };
+
+    let allowed = match instance_owner(&state, &instance).await {
+        Ok(Some(owner)) => owner == Some(workspace),
+        Ok(None) => creating,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": "db_error", "details": e.to_string()})),
+            )
+                .into_response();
+        }
+    };
+    if !allowed {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "instance_not_found"})),
+        )
+            .into_response();
+    }
+
+    next.run(Request::from_parts(parts, body)).await
+}
+
+#[cfg(test)]
+mod tests {
+    include!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/tests/server/workspaces_tests.rs"));
+}
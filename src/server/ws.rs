@@ -0,0 +1,268 @@
+//! `/ws` event stream.
+//!
+//! Every event dispatched from the [`outbox`](super::outbox) is also
+//! published here as the same JSON envelope the webhooks receive. Each
+//! client reads from its own broadcast cursor, so the channel capacity is
+//! the per-connection buffer: a client that falls further behind is a slow
+//! consumer and `WS_LAG_POLICY` decides whether it skips ahead or is
+//! disconnected.
+//!
+//! `/ws` is a global sink: `GLOBAL_SINK_INSTANCES` and
+//! `GLOBAL_SINK_EXCLUDE_INSTANCES` narrow the instances it streams (see
+//! [`sink_scope`](super::sink_scope)). Events of an instance with a `/ws`
+//! event filter (`PUT /instance/events/:name`) are only sent when the
+//! filter takes them; the filters are cached for [`FILTER_CACHE_TTL`].
+//!
+//! Browsers do not apply CORS to websocket handshakes, so once
+//! `WS_CORS_ORIGINS` is set an upgrade carrying an `Origin` outside it is
+//! refused with 403.
+
+use crate::server::AppState;
+use crate::server::event_bus::{BusEvent, SinkCounts, SinkStats};
+use crate::server::event_filters;
+use crate::server::runtime_config::{CorsGroup, RuntimeConfig};
+use axum::{
+    Json,
+    extract::{
+        State,
+        ws::{CloseFrame, Message, WebSocket, WebSocketUpgrade},
+    },
+    http::{HeaderMap, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use dashmap::DashMap;
+use serde_json::json;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::{debug, warn};
+
+pub use crate::config::{LagPolicy, WsConfig};
+
+/// Close code sent to slow consumers under `LagPolicy::Disconnect` (policy violation).
+const CLOSE_SLOW_CONSUMER: u16 = 1008;
+/// Close code sent when the server stops publishing (going away).
+const CLOSE_GOING_AWAY: u16 = 1001;
+/// How long an instance's `/ws` event filter is reused before reloading.
+pub const FILTER_CACHE_TTL: Duration = Duration::from_secs(30);
+
+impl LagPolicy {
+    /// Parses a `WS_LAG_POLICY` value.
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().replace('-', "_").as_str() {
+            "drop_oldest" | "drop" => Some(Self::DropOldest),
+            "disconnect" | "close" => Some(Self::Disconnect),
+            _ => None,
+        }
+    }
+}
+
+/// Fan-out of API events to websocket clients.
+pub struct EventHub {
+    tx: broadcast::Sender<Arc<BusEvent>>,
+    clients: AtomicUsize,
+    config: WsConfig,
+    stats: SinkStats,
+    filters: DashMap<String, (Option<Vec<String>>, Instant)>,
+}
+
+impl EventHub {
+    pub fn new(config: WsConfig) -> Self {
+        let (tx, _) = broadcast::channel(config.buffer);
+        Self {
+            tx,
+            clients: AtomicUsize::new(0),
+            config,
+            stats: SinkStats::default(),
+            filters: DashMap::new(),
+        }
+    }
+
+    /// Publishes an event; a no-op when nobody is listening.
+    pub fn publish(&self, event: &Arc<BusEvent>) {
+        if self.tx.receiver_count() > 0 && self.tx.send(event.clone()).is_ok() {
+            self.stats.record_queued();
+        }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Arc<BusEvent>> {
+        self.tx.subscribe()
+    }
+
+    /// Events published to clients and skipped by lagging ones.
+    pub fn stats(&self) -> SinkCounts {
+        self.stats.snapshot()
+    }
+
+    /// Websocket clients currently connected, for `/metrics`.
+    pub fn connected_clients(&self) -> usize {
+        self.clients.load(Ordering::Relaxed)
+    }
+
+    pub fn config(&self) -> &WsConfig {
+        &self.config
+    }
+
+    /// Drops the cached `/ws` event filter of `instance`, after it changed.
+    pub fn forget_filter(&self, instance: &str) {
+        self.filters.remove(instance);
+    }
+
+    fn track_client(&self) -> ClientGuard<'_> {
+        self.clients.fetch_add(1, Ordering::Relaxed);
+        ClientGuard(&self.clients)
+    }
+}
+
+struct ClientGuard<'a>(&'a AtomicUsize);
+
+impl Drop for ClientGuard<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// `GET /ws`: upgrades and streams every API event as a JSON text frame.
+pub async fn ws_handler(
+    ws: WebSocketUpgrade,
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Response {
+    let origin = headers.get(header::ORIGIN).and_then(|v| v.to_str().ok());
+    if !origin_allowed(&state.runtime_config(), origin) {
+        return (
+            StatusCode::FORBIDDEN,
+            Json(json!({"error": "origin_not_allowed", "route": "/ws"})),
+        )
+            .into_response();
+    }
+    ws.on_upgrade(move |socket| serve(socket, state))
+}
+
+/// Whether an upgrade from `origin` is accepted: always without
+/// `WS_CORS_ORIGINS` or an `Origin` header (non-browser clients).
+pub fn origin_allowed(config: &RuntimeConfig, origin: Option<&str>) -> bool {
+    match (origin, &config.ws_cors_origins) {
+        (Some(origin), Some(_)) => config.allows_origin(CorsGroup::Ws, origin),
+        _ => true,
+    }
+}
+
+async fn serve(mut socket: WebSocket, state: Arc<AppState>) {
+    let hub = &state.event_hub;
+    let config = hub.config().clone();
+    let mut events = hub.subscribe();
+    let _client = hub.track_client();
+
+    let mut ping = tokio::time::interval(config.ping_interval);
+    // The first tick fires immediately; the client just proved it is alive.
+    ping.tick().await;
+    let mut last_pong = Instant::now();
+
+    let close = loop {
+        tokio::select! {
+            incoming = socket.recv() => match incoming {
+                Some(Ok(Message::Pong(_))) => last_pong = Instant::now(),
+                Some(Ok(Message::Close(_))) | None => break None,
+                // Pings are answered by the protocol layer; client text is ignored.
+                Some(Ok(_)) => {}
+                Some(Err(e)) => {
+                    debug!(error = %e, "Websocket client read failed");
+                    break None;
+                }
+            },
+            event = events.recv() => match event {
+                Ok(event) => {
+                    if !delivers(&state, &event).await {
+                        continue;
+                    }
+                    if !send(&mut socket, Message::Text(event.json().to_string()), config.pong_timeout).await {
+                        break None;
+                    }
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    hub.stats.record_dropped(skipped);
+                    match config.lag_policy {
+                        LagPolicy::DropOldest => {
+                            warn!(skipped, "Websocket client lagging, dropped oldest events");
+                            let notice = json!({"event": "WS_LAGGED", "data": {"skipped": skipped}});
+                            if !send(&mut socket, Message::Text(notice.to_string()), config.pong_timeout).await {
+                                break None;
+                            }
+                        }
+                        LagPolicy::Disconnect => {
+                            warn!(skipped, "Disconnecting lagging websocket client");
+                            break Some((CLOSE_SLOW_CONSUMER, "slow consumer"));
+                        }
+                    }
+                }
+                Err(RecvError::Closed) => break Some((CLOSE_GOING_AWAY, "server shutting down")),
+            },
+            _ = ping.tick() => {
+                if last_pong.elapsed() > config.ping_interval + config.pong_timeout {
+                    debug!("Websocket client missed pong, closing");
+                    break None;
+                }
+                if !send(&mut socket, Message::Ping(Vec::new()), config.pong_timeout).await {
+                    break None;
+                }
+            }
+        }
+    };
+
+    if let Some((code, reason)) = close {
+        let frame = CloseFrame {
+            code,
+            reason: reason.into(),
+        };
+        let _ = send(&mut socket, Message::Close(Some(frame)), config.pong_timeout).await;
+    }
+}
+
+/// Whether the event's instance is in the global sink scope and its `/ws`
+/// event filter takes the event.
+async fn delivers(state: &AppState, event: &BusEvent) -> bool {
+    if event.instance.is_empty() {
+        return true;
+    }
+    if !state.global_sinks.includes(&event.instance) {
+        return false;
+    }
+    let hub = &state.event_hub;
+    let cached = hub
+        .filters
+        .get(&event.instance)
+        .filter(|entry| entry.1.elapsed() < FILTER_CACHE_TTL)
+        .map(|entry| entry.0.clone());
+    let filter = match cached {
+        Some(filter) => filter,
+        None => {
+            let filter = event_filters::websocket(state, &event.instance).await;
+            hub.filters
+                .insert(event.instance.clone(), (filter.clone(), Instant::now()));
+            filter
+        }
+    };
+    event_filters::allows(filter.as_deref(), &event.event)
+}
+
+/// Sends one frame; `false` when the client is gone or did not accept it in time.
+async fn send(socket: &mut WebSocket, message: Message, timeout: Duration) -> bool {
+    match tokio::time::timeout(timeout, socket.send(message)).await {
+        Ok(Ok(())) => true,
+        Ok(Err(e)) => {
+            debug!(error = %e, "Websocket send failed");
+            false
+        }
+        Err(_) => {
+            warn!("Websocket send timed out, closing slow client");
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    include!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/tests/server/ws_tests.rs"));
+}
@@ -1,16 +1,20 @@
 use crate::socket::error::{EncryptSendError, Result, SocketError};
 use crate::transport::Transport;
+use bytes::BytesMut;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicU32, Ordering};
 use tokio::sync::{mpsc, oneshot};
 use tokio::task::JoinHandle;
 use warp_core::aes_gcm::{
-    Aes256Gcm,
+    Aes256Gcm, Tag,
     aead::{Aead, AeadInPlace},
 };
+use warp_core::framing;
 use warp_core::handshake::utils::generate_iv;
 
 const INLINE_ENCRYPT_THRESHOLD: usize = 16 * 1024;
+/// AES-GCM tag appended to every frame.
+const TAG_SIZE: usize = 16;
 
 /// Result type for send operations, returning both buffers for reuse.
 type SendResult = std::result::Result<(Vec<u8>, Vec<u8>), EncryptSendError>;
@@ -89,6 +93,10 @@ impl NoiseSocket {
     }
 
     /// Process a single send job: encrypt and send the message.
+    ///
+    /// The plaintext is copied once, into `out_buf` after the frame length
+    /// prefix, and encrypted there; the tag is appended and the prefix
+    /// filled last, so no intermediate ciphertext buffer is allocated.
     async fn process_send_job(
         transport: &Arc<dyn Transport>,
         write_key: &Arc<Aes256Gcm>,
@@ -99,70 +107,37 @@ impl NoiseSocket {
         let counter = *write_counter;
         *write_counter = write_counter.wrapping_add(1);
 
-        // For small messages, encrypt in-place in out_buf to avoid allocation
-        if plaintext_buf.len() <= INLINE_ENCRYPT_THRESHOLD {
-            // Copy plaintext to out_buf and encrypt in-place
-            out_buf.clear();
-            out_buf.extend_from_slice(&plaintext_buf);
-            plaintext_buf.clear();
-
-            let iv = generate_iv(counter);
-            if let Err(e) = write_key.encrypt_in_place(iv.as_ref().into(), b"", &mut out_buf) {
-                return Err(EncryptSendError::crypto(
-                    anyhow::anyhow!(e.to_string()),
-                    plaintext_buf,
-                    out_buf,
-                ));
-            }
+        framing::begin_frame(&mut out_buf);
+        out_buf.reserve(plaintext_buf.len() + TAG_SIZE);
+        out_buf.extend_from_slice(&plaintext_buf);
+        plaintext_buf.clear();
 
-            // Frame the ciphertext - we need a temporary copy since encode_frame_into
-            // clears the output buffer
-            let ciphertext_len = out_buf.len();
-            plaintext_buf.extend_from_slice(&out_buf);
-            out_buf.clear();
-            if let Err(e) = warp_core::framing::encode_frame_into(
-                &plaintext_buf[..ciphertext_len],
-                None,
-                &mut out_buf,
-            ) {
-                plaintext_buf.clear();
-                return Err(EncryptSendError::framing(e, plaintext_buf, out_buf));
-            }
-            plaintext_buf.clear();
+        let sealed = if out_buf.len() - framing::FRAME_LENGTH_SIZE <= INLINE_ENCRYPT_THRESHOLD {
+            seal_in_place(write_key, counter, &mut out_buf)
         } else {
-            // Offload larger messages to a blocking thread
+            // Offload larger messages to a blocking thread; the buffer
+            // travels with the job and comes back with the ciphertext.
             let write_key = write_key.clone();
-
-            let plaintext_arc = Arc::new(plaintext_buf);
-            let plaintext_arc_for_task = plaintext_arc.clone();
-
-            let spawn_result = tokio::task::spawn_blocking(move || {
-                let iv = generate_iv(counter);
-                write_key.encrypt(iv.as_ref().into(), &plaintext_arc_for_task[..])
-            })
-            .await;
-
-            plaintext_buf = Arc::try_unwrap(plaintext_arc).unwrap_or_else(|arc| (*arc).clone());
-
-            let ciphertext = match spawn_result {
-                Ok(Ok(c)) => c,
-                Ok(Err(e)) => {
-                    return Err(EncryptSendError::crypto(
-                        anyhow::anyhow!(e.to_string()),
-                        plaintext_buf,
-                        out_buf,
-                    ));
+            let job = tokio::task::spawn_blocking(move || {
+                let result = seal_in_place(&write_key, counter, &mut out_buf);
+                (result, out_buf)
+            });
+            match job.await {
+                Ok((result, buf)) => {
+                    out_buf = buf;
+                    result
                 }
                 Err(join_err) => {
-                    return Err(EncryptSendError::join(join_err, plaintext_buf, out_buf));
+                    return Err(EncryptSendError::join(join_err, plaintext_buf, Vec::new()));
                 }
-            };
-
-            plaintext_buf.clear();
-            out_buf.clear();
-            if let Err(e) = warp_core::framing::encode_frame_into(&ciphertext, None, &mut out_buf) {
-                return Err(EncryptSendError::framing(e, plaintext_buf, out_buf));
             }
+        };
+        if let Err(e) = sealed {
+            return Err(EncryptSendError::crypto(e, plaintext_buf, out_buf));
+        }
+
+        if let Err(e) = framing::finish_frame(&mut out_buf) {
+            return Err(EncryptSendError::framing(e, plaintext_buf, out_buf));
         }
 
         if let Err(e) = transport.send(&out_buf).await {
@@ -202,6 +177,23 @@ impl NoiseSocket {
         }
     }
 
+    /// Decrypts `frame` in place and drops its tag, without allocating.
+    pub fn decrypt_frame_in_place(&self, frame: &mut BytesMut) -> Result<()> {
+        let counter = self.read_counter.fetch_add(1, Ordering::SeqCst);
+        let Some(payload_len) = frame.len().checked_sub(TAG_SIZE) else {
+            return Err(SocketError::Crypto(
+                "frame shorter than the tag".to_string(),
+            ));
+        };
+        let iv = generate_iv(counter);
+        let (payload, tag) = frame.split_at_mut(payload_len);
+        self.read_key
+            .decrypt_in_place_detached(iv.as_ref().into(), b"", payload, Tag::from_slice(tag))
+            .map_err(|e| SocketError::Crypto(e.to_string()))?;
+        frame.truncate(payload_len);
+        Ok(())
+    }
+
     pub fn decrypt_frame(&self, ciphertext: &[u8]) -> Result<Vec<u8>> {
         let counter = self.read_counter.fetch_add(1, Ordering::SeqCst);
         let iv = generate_iv(counter);
@@ -211,6 +203,21 @@ impl NoiseSocket {
     }
 }
 
+/// Encrypts the payload of a frame started with [`framing::begin_frame`] in
+/// place and appends the tag.
+fn seal_in_place(key: &Aes256Gcm, counter: u32, frame: &mut Vec<u8>) -> anyhow::Result<()> {
+    let iv = generate_iv(counter);
+    let tag = key
+        .encrypt_in_place_detached(
+            iv.as_ref().into(),
+            b"",
+            &mut frame[framing::FRAME_LENGTH_SIZE..],
+        )
+        .map_err(|e| anyhow::anyhow!(e.to_string()))?;
+    frame.extend_from_slice(&tag);
+    Ok(())
+}
+
 impl Drop for NoiseSocket {
     fn drop(&mut self) {
         // Abort the sender task to prevent resource leaks if it's stuck
@@ -4,13 +4,14 @@ pub mod persistence_manager;
 pub mod signal;
 pub mod signal_adapter;
 pub mod traits;
+pub mod write_behind;
 
 // Re-export from the storage crates when the features are enabled
 #[cfg(feature = "sqlite-storage")]
 pub use chatwarp_api_sqlite_storage::SqliteStore;
 
 #[cfg(feature = "postgres-storage")]
-pub use chatwarp_api_postgres_storage::PostgresStore;
+pub use chatwarp_api_postgres_storage::{PoolOptions, PoolStats, PostgresStore};
 
 pub use crate::store::traits::*;
 use std::ops::{Deref, DerefMut};
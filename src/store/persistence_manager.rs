@@ -1,17 +1,23 @@
 use super::error::{StoreError, db_err};
 use crate::store::Device;
 use crate::store::traits::Backend;
+use crate::store::write_behind::WriteBehindBackend;
 use log::{debug, error};
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
 use tokio::sync::{Notify, RwLock};
 use tokio::time::{Duration, sleep};
+use warp_core_binary::jid::Jid;
 
 pub struct PersistenceManager {
     device: Arc<RwLock<Device>>,
     backend: Arc<dyn Backend>,
+    /// The device (credentials) changed since the last save.
     dirty: Arc<AtomicBool>,
     save_notify: Arc<Notify>,
+    /// Buffered Signal key writes, when saves are debounced.
+    keys: Option<Arc<WriteBehindBackend>>,
+    debounce: Duration,
 }
 
 impl PersistenceManager {
@@ -20,6 +26,29 @@ impl PersistenceManager {
     /// Note: The backend should already be configured with the correct device_id
     /// (via SqliteStore::new_for_device for multi-account scenarios).
     pub async fn new(backend: Arc<dyn Backend>) -> Result<Self, StoreError> {
+        Self::with_debounce(backend, Duration::ZERO).await
+    }
+
+    /// Like [`new`](Self::new), but coalesces writes: once something
+    /// changes, the background saver waits `debounce` before saving the
+    /// device, and the Signal key writes are buffered in a
+    /// [`WriteBehindBackend`] until then. Zero writes everything through.
+    pub async fn with_debounce(
+        backend: Arc<dyn Backend>,
+        debounce: Duration,
+    ) -> Result<Self, StoreError> {
+        let save_notify = Arc::new(Notify::new());
+        let keys = (!debounce.is_zero()).then(|| {
+            Arc::new(WriteBehindBackend::new(
+                backend.clone(),
+                save_notify.clone(),
+            ))
+        });
+        let backend: Arc<dyn Backend> = match &keys {
+            Some(keys) => keys.clone(),
+            None => backend,
+        };
+
         debug!("PersistenceManager: Ensuring device row exists.");
         // Ensure a device row exists for this backend's device_id; create it if not.
         let exists = backend.exists().await.map_err(db_err)?;
@@ -49,7 +78,9 @@ impl PersistenceManager {
             device: Arc::new(RwLock::new(device)),
             backend,
             dirty: Arc::new(AtomicBool::new(false)),
-            save_notify: Arc::new(Notify::new()),
+            save_notify,
+            keys,
+            debounce,
         })
     }
 
@@ -78,6 +109,24 @@ impl PersistenceManager {
         result
     }
 
+    /// Replaces the device with an unpaired one and saves it right away, so
+    /// no later start logs in with revoked credentials. Returns the phone
+    /// number the device was paired with.
+    pub async fn reset_device(&self) -> Result<Option<Jid>, StoreError> {
+        let (previous_pn, serializable_device) = {
+            let mut device_guard = self.device.write().await;
+            let previous_pn = device_guard.pn.clone();
+            device_guard.core = device_guard.core.unpaired();
+            (previous_pn, device_guard.to_serializable())
+        };
+        self.dirty.store(false, Ordering::Release);
+        self.backend
+            .save(&serializable_device)
+            .await
+            .map_err(db_err)?;
+        Ok(previous_pn)
+    }
+
     async fn save_to_disk(&self) -> Result<(), StoreError> {
         if self.dirty.swap(false, Ordering::AcqRel) {
             debug!("Device state is dirty, saving to disk.");
@@ -85,21 +134,39 @@ impl PersistenceManager {
             let serializable_device = device_guard.to_serializable();
             drop(device_guard);
 
-            self.backend
-                .save(&serializable_device)
-                .await
-                .map_err(db_err)?;
+            if let Err(e) = self.backend.save(&serializable_device).await {
+                self.dirty.store(true, Ordering::Release);
+                return Err(db_err(e));
+            }
             debug!("Device state saved successfully.");
         }
+        if let Some(keys) = &self.keys {
+            keys.flush().await?;
+        }
         Ok(())
     }
 
+    /// Whether the device or buffered keys still have to be saved.
+    pub fn is_dirty(&self) -> bool {
+        self.dirty.load(Ordering::Acquire) || self.keys.as_ref().is_some_and(|keys| keys.is_dirty())
+    }
+
+    /// Saves the device and the buffered key writes now, without waiting
+    /// for the debounce window. Called on disconnect and shutdown.
+    pub async fn flush(&self) -> Result<(), StoreError> {
+        self.save_to_disk().await
+    }
+
     pub fn run_background_saver(self: Arc<Self>, interval: Duration) {
         tokio::spawn(async move {
             loop {
                 tokio::select! {
                     _ = self.save_notify.notified() => {
                         debug!("Save notification received.");
+                        // Writes arriving in the window go out in the same save.
+                        if !self.debounce.is_zero() {
+                            sleep(self.debounce).await;
+                        }
                     }
                     _ = sleep(interval) => {}
                 }
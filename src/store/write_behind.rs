@@ -0,0 +1,471 @@
+//! Write-behind buffer for the Signal keys.
+//!
+//! Sessions, identities and sender keys change on almost every message and
+//! during session churn, and writing each one through costs a database
+//! round trip. [`WriteBehindBackend`] keeps the latest value of each key in
+//! memory (deletes as tombstones), answers reads from there first and
+//! writes them all at once on [`flush`](WriteBehindBackend::flush). The
+//! [`PersistenceManager`](super::persistence_manager::PersistenceManager)
+//! saver flushes it after its debounce window, with the device, and on
+//! disconnect. Everything else goes straight to the wrapped backend.
+
+use crate::store::traits::{
+    AppStateSyncKey, AppSyncStore, Backend, DeviceListRecord, DeviceStore, LidPnMappingEntry,
+    ProtocolStore, SignalStore,
+};
+use async_trait::async_trait;
+use log::debug;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use tokio::sync::Notify;
+use warp_core::appstate::hash::HashState;
+use warp_core::appstate::processor::AppStateMutationMAC;
+use warp_core::store::error::Result;
+
+/// Latest value per address; `None` is a pending delete.
+type Pending<V> = HashMap<String, Option<V>>;
+
+#[derive(Debug, Default)]
+struct KeyWrites {
+    sessions: Pending<Vec<u8>>,
+    identities: Pending<[u8; 32]>,
+    sender_keys: Pending<Vec<u8>>,
+}
+
+impl KeyWrites {
+    fn is_empty(&self) -> bool {
+        self.sessions.is_empty() && self.identities.is_empty() && self.sender_keys.is_empty()
+    }
+
+    fn len(&self) -> usize {
+        self.sessions.len() + self.identities.len() + self.sender_keys.len()
+    }
+
+    /// Puts back the writes of a failed flush, unless a newer one replaced
+    /// them meanwhile.
+    fn restore(&mut self, failed: &KeyWrites) {
+        fn merge<V: Clone>(into: &mut Pending<V>, from: &Pending<V>) {
+            for (address, value) in from {
+                into.entry(address.clone()).or_insert_with(|| value.clone());
+            }
+        }
+        merge(&mut self.sessions, &failed.sessions);
+        merge(&mut self.identities, &failed.identities);
+        merge(&mut self.sender_keys, &failed.sender_keys);
+    }
+}
+
+#[derive(Debug, Default)]
+struct Buffers {
+    /// Writes not handed to a flush yet.
+    pending: KeyWrites,
+    /// Writes of the flush in progress, still served to reads until stored.
+    flushing: Arc<KeyWrites>,
+}
+
+impl Buffers {
+    fn lookup<V: Clone>(
+        &self,
+        section: impl Fn(&KeyWrites) -> &Pending<V>,
+        address: &str,
+    ) -> Option<Option<V>> {
+        section(&self.pending)
+            .get(address)
+            .or_else(|| section(&self.flushing).get(address))
+            .cloned()
+    }
+}
+
+/// Backend wrapper buffering the Signal key writes until [`flush`](Self::flush).
+pub struct WriteBehindBackend {
+    inner: Arc<dyn Backend>,
+    buffers: Mutex<Buffers>,
+    /// One flush at a time, so a slow one is never overtaken by an older
+    /// value.
+    flush_lock: tokio::sync::Mutex<()>,
+    /// Woken on every buffered write (the saver of the persistence manager).
+    notify: Arc<Notify>,
+}
+
+impl WriteBehindBackend {
+    pub fn new(inner: Arc<dyn Backend>, notify: Arc<Notify>) -> Self {
+        Self {
+            inner,
+            buffers: Mutex::new(Buffers::default()),
+            flush_lock: tokio::sync::Mutex::new(()),
+            notify,
+        }
+    }
+
+    fn buffers(&self) -> MutexGuard<'_, Buffers> {
+        self.buffers.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Whether there are key writes waiting for a flush.
+    pub fn is_dirty(&self) -> bool {
+        !self.buffers().pending.is_empty()
+    }
+
+    fn buffer<V>(
+        &self,
+        section: impl FnOnce(&mut KeyWrites) -> &mut Pending<V>,
+        entries: impl IntoIterator<Item = (String, Option<V>)>,
+    ) {
+        section(&mut self.buffers().pending).extend(entries);
+        self.notify.notify_one();
+    }
+
+    /// Writes the buffered keys to the wrapped backend. On failure they
+    /// stay buffered for the next flush.
+    pub async fn flush(&self) -> Result<()> {
+        let _flushing = self.flush_lock.lock().await;
+        let writes = {
+            let mut buffers = self.buffers();
+            if buffers.pending.is_empty() {
+                return Ok(());
+            }
+            let writes = Arc::new(std::mem::take(&mut buffers.pending));
+            buffers.flushing = writes.clone();
+            writes
+        };
+
+        let result = self.write(&writes).await;
+        let mut buffers = self.buffers();
+        buffers.flushing = Arc::default();
+        match result {
+            Ok(()) => {
+                debug!("Flushed {} buffered key writes", writes.len());
+                Ok(())
+            }
+            Err(e) => {
+                buffers.pending.restore(&writes);
+                Err(e)
+            }
+        }
+    }
+
+    async fn write(&self, writes: &KeyWrites) -> Result<()> {
+        let sessions: Vec<(&str, &[u8])> = writes
+            .sessions
+            .iter()
+            .filter_map(|(address, session)| Some((address.as_str(), session.as_deref()?)))
+            .collect();
+        if !sessions.is_empty() {
+            self.inner.put_sessions_batch(&sessions).await?;
+        }
+        let identities: Vec<(&str, [u8; 32])> = writes
+            .identities
+            .iter()
+            .filter_map(|(address, key)| Some((address.as_str(), (*key)?)))
+            .collect();
+        if !identities.is_empty() {
+            self.inner.put_identities_batch(&identities).await?;
+        }
+        for (address, record) in &writes.sender_keys {
+            match record {
+                Some(record) => self.inner.put_sender_key(address, record).await?,
+                None => self.inner.delete_sender_key(address).await?,
+            }
+        }
+        for (address, _) in writes.sessions.iter().filter(|(_, s)| s.is_none()) {
+            self.inner.delete_session(address).await?;
+        }
+        for (address, _) in writes.identities.iter().filter(|(_, k)| k.is_none()) {
+            self.inner.delete_identity(address).await?;
+        }
+        Ok(())
+    }
+
+    /// Buffered values for `addresses` and the addresses left to read from
+    /// the wrapped backend.
+    fn split_batch<'a, V: Clone>(
+        &self,
+        section: impl Fn(&KeyWrites) -> &Pending<V>,
+        addresses: &[&'a str],
+    ) -> (Vec<(String, V)>, Vec<&'a str>) {
+        let buffers = self.buffers();
+        let mut found = Vec::new();
+        let mut missing = Vec::new();
+        for address in addresses {
+            match buffers.lookup(&section, address) {
+                Some(Some(value)) => found.push((address.to_string(), value)),
+                Some(None) => {}
+                None => missing.push(*address),
+            }
+        }
+        (found, missing)
+    }
+}
+
+#[async_trait]
+impl SignalStore for WriteBehindBackend {
+    async fn put_identity(&self, address: &str, key: [u8; 32]) -> Result<()> {
+        self.buffer(|w| &mut w.identities, [(address.to_string(), Some(key))]);
+        Ok(())
+    }
+
+    async fn load_identity(&self, address: &str) -> Result<Option<Vec<u8>>> {
+        let buffered = self.buffers().lookup(|w| &w.identities, address);
+        match buffered {
+            Some(key) => Ok(key.map(|key| key.to_vec())),
+            None => self.inner.load_identity(address).await,
+        }
+    }
+
+    async fn delete_identity(&self, address: &str) -> Result<()> {
+        self.buffer(|w| &mut w.identities, [(address.to_string(), None)]);
+        Ok(())
+    }
+
+    async fn get_session(&self, address: &str) -> Result<Option<Vec<u8>>> {
+        let buffered = self.buffers().lookup(|w| &w.sessions, address);
+        match buffered {
+            Some(session) => Ok(session),
+            None => self.inner.get_session(address).await,
+        }
+    }
+
+    async fn put_session(&self, address: &str, session: &[u8]) -> Result<()> {
+        self.buffer(
+            |w| &mut w.sessions,
+            [(address.to_string(), Some(session.to_vec()))],
+        );
+        Ok(())
+    }
+
+    async fn delete_session(&self, address: &str) -> Result<()> {
+        self.buffer(|w| &mut w.sessions, [(address.to_string(), None)]);
+        Ok(())
+    }
+
+    async fn has_session(&self, address: &str) -> Result<bool> {
+        let buffered = self.buffers().lookup(|w| &w.sessions, address);
+        match buffered {
+            Some(session) => Ok(session.is_some()),
+            None => self.inner.has_session(address).await,
+        }
+    }
+
+    async fn get_sessions_batch(&self, addresses: &[&str]) -> Result<Vec<(String, Vec<u8>)>> {
+        let (mut found, missing) = self.split_batch(|w| &w.sessions, addresses);
+        if !missing.is_empty() {
+            found.extend(self.inner.get_sessions_batch(&missing).await?);
+        }
+        Ok(found)
+    }
+
+    async fn put_sessions_batch(&self, entries: &[(&str, &[u8])]) -> Result<()> {
+        self.buffer(
+            |w| &mut w.sessions,
+            entries
+                .iter()
+                .map(|(address, session)| (address.to_string(), Some(session.to_vec()))),
+        );
+        Ok(())
+    }
+
+    async fn load_identities_batch(&self, addresses: &[&str]) -> Result<Vec<(String, Vec<u8>)>> {
+        let (found, missing) = self.split_batch(|w| &w.identities, addresses);
+        let mut found: Vec<(String, Vec<u8>)> = found
+            .into_iter()
+            .map(|(address, key)| (address, key.to_vec()))
+            .collect();
+        if !missing.is_empty() {
+            found.extend(self.inner.load_identities_batch(&missing).await?);
+        }
+        Ok(found)
+    }
+
+    async fn put_identities_batch(&self, entries: &[(&str, [u8; 32])]) -> Result<()> {
+        self.buffer(
+            |w| &mut w.identities,
+            entries
+                .iter()
+                .map(|(address, key)| (address.to_string(), Some(*key))),
+        );
+        Ok(())
+    }
+
+    async fn store_prekey(&self, id: u32, record: &[u8], uploaded: bool) -> Result<()> {
+        self.inner.store_prekey(id, record, uploaded).await
+    }
+
+    async fn load_prekey(&self, id: u32) -> Result<Option<Vec<u8>>> {
+        self.inner.load_prekey(id).await
+    }
+
+    async fn remove_prekey(&self, id: u32) -> Result<()> {
+        self.inner.remove_prekey(id).await
+    }
+
+    async fn store_signed_prekey(&self, id: u32, record: &[u8]) -> Result<()> {
+        self.inner.store_signed_prekey(id, record).await
+    }
+
+    async fn load_signed_prekey(&self, id: u32) -> Result<Option<Vec<u8>>> {
+        self.inner.load_signed_prekey(id).await
+    }
+
+    async fn load_all_signed_prekeys(&self) -> Result<Vec<(u32, Vec<u8>)>> {
+        self.inner.load_all_signed_prekeys().await
+    }
+
+    async fn remove_signed_prekey(&self, id: u32) -> Result<()> {
+        self.inner.remove_signed_prekey(id).await
+    }
+
+    async fn put_sender_key(&self, address: &str, record: &[u8]) -> Result<()> {
+        self.buffer(
+            |w| &mut w.sender_keys,
+            [(address.to_string(), Some(record.to_vec()))],
+        );
+        Ok(())
+    }
+
+    async fn get_sender_key(&self, address: &str) -> Result<Option<Vec<u8>>> {
+        let buffered = self.buffers().lookup(|w| &w.sender_keys, address);
+        match buffered {
+            Some(record) => Ok(record),
+            None => self.inner.get_sender_key(address).await,
+        }
+    }
+
+    async fn delete_sender_key(&self, address: &str) -> Result<()> {
+        self.buffer(|w| &mut w.sender_keys, [(address.to_string(), None)]);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl AppSyncStore for WriteBehindBackend {
+    async fn get_sync_key(&self, key_id: &[u8]) -> Result<Option<AppStateSyncKey>> {
+        self.inner.get_sync_key(key_id).await
+    }
+
+    async fn set_sync_key(&self, key_id: &[u8], key: AppStateSyncKey) -> Result<()> {
+        self.inner.set_sync_key(key_id, key).await
+    }
+
+    async fn get_latest_sync_key_id(&self) -> Result<Option<Vec<u8>>> {
+        self.inner.get_latest_sync_key_id().await
+    }
+
+    async fn get_version(&self, name: &str) -> Result<HashState> {
+        self.inner.get_version(name).await
+    }
+
+    async fn set_version(&self, name: &str, state: HashState) -> Result<()> {
+        self.inner.set_version(name, state).await
+    }
+
+    async fn put_mutation_macs(
+        &self,
+        name: &str,
+        version: u64,
+        mutations: &[AppStateMutationMAC],
+    ) -> Result<()> {
+        self.inner.put_mutation_macs(name, version, mutations).await
+    }
+
+    async fn get_mutation_mac(&self, name: &str, index_mac: &[u8]) -> Result<Option<Vec<u8>>> {
+        self.inner.get_mutation_mac(name, index_mac).await
+    }
+
+    async fn delete_mutation_macs(&self, name: &str, index_macs: &[Vec<u8>]) -> Result<()> {
+        self.inner.delete_mutation_macs(name, index_macs).await
+    }
+}
+
+#[async_trait]
+impl ProtocolStore for WriteBehindBackend {
+    async fn get_skdm_recipients(&self, group_jid: &str) -> Result<Vec<String>> {
+        self.inner.get_skdm_recipients(group_jid).await
+    }
+
+    async fn add_skdm_recipients(&self, group_jid: &str, device_jids: &[String]) -> Result<()> {
+        self.inner.add_skdm_recipients(group_jid, device_jids).await
+    }
+
+    async fn clear_skdm_recipients(&self, group_jid: &str) -> Result<()> {
+        self.inner.clear_skdm_recipients(group_jid).await
+    }
+
+    async fn get_lid_mapping(&self, lid: &str) -> Result<Option<LidPnMappingEntry>> {
+        self.inner.get_lid_mapping(lid).await
+    }
+
+    async fn get_pn_mapping(&self, phone: &str) -> Result<Option<LidPnMappingEntry>> {
+        self.inner.get_pn_mapping(phone).await
+    }
+
+    async fn put_lid_mapping(&self, entry: &LidPnMappingEntry) -> Result<()> {
+        self.inner.put_lid_mapping(entry).await
+    }
+
+    async fn get_all_lid_mappings(&self) -> Result<Vec<LidPnMappingEntry>> {
+        self.inner.get_all_lid_mappings().await
+    }
+
+    async fn save_base_key(&self, address: &str, message_id: &str, base_key: &[u8]) -> Result<()> {
+        self.inner
+            .save_base_key(address, message_id, base_key)
+            .await
+    }
+
+    async fn has_same_base_key(
+        &self,
+        address: &str,
+        message_id: &str,
+        current_base_key: &[u8],
+    ) -> Result<bool> {
+        self.inner
+            .has_same_base_key(address, message_id, current_base_key)
+            .await
+    }
+
+    async fn delete_base_key(&self, address: &str, message_id: &str) -> Result<()> {
+        self.inner.delete_base_key(address, message_id).await
+    }
+
+    async fn update_device_list(&self, record: DeviceListRecord) -> Result<()> {
+        self.inner.update_device_list(record).await
+    }
+
+    async fn get_devices(&self, user: &str) -> Result<Option<DeviceListRecord>> {
+        self.inner.get_devices(user).await
+    }
+
+    async fn mark_forget_sender_key(&self, group_jid: &str, participant: &str) -> Result<()> {
+        self.inner
+            .mark_forget_sender_key(group_jid, participant)
+            .await
+    }
+
+    async fn consume_forget_marks(&self, group_jid: &str) -> Result<Vec<String>> {
+        self.inner.consume_forget_marks(group_jid).await
+    }
+}
+
+#[async_trait]
+impl DeviceStore for WriteBehindBackend {
+    async fn save(&self, device: &warp_core::store::Device) -> Result<()> {
+        self.inner.save(device).await
+    }
+
+    async fn load(&self) -> Result<Option<warp_core::store::Device>> {
+        self.inner.load().await
+    }
+
+    async fn exists(&self) -> Result<bool> {
+        self.inner.exists().await
+    }
+
+    async fn create(&self) -> Result<i32> {
+        self.inner.create().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    include!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/tests/store/write_behind_tests.rs"));
+}
@@ -85,6 +85,15 @@
             self.keys.lock().await.insert(key_id.to_vec(), key);
             Ok(())
         }
+        async fn get_latest_sync_key_id(&self) -> StoreResult<Option<Vec<u8>>> {
+            Ok(self
+                .keys
+                .lock()
+                .await
+                .iter()
+                .max_by_key(|(_, key)| key.timestamp)
+                .map(|(id, _)| id.clone()))
+        }
         async fn get_version(&self, name: &str) -> StoreResult<HashState> {
             Ok(self
                 .versions
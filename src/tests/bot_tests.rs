@@ -178,7 +178,10 @@
         let client = bot.client();
 
         // Check that the override version is stored in the client
-        assert_eq!(client.override_version, Some((2, 3000, 123456789)));
+        assert_eq!(
+            client.version_manager.config().pinned,
+            Some((2, 3000, 123456789))
+        );
     }
 
     #[tokio::test]
@@ -277,3 +280,38 @@
             Some(warp_core::store::Device::default_os().to_string())
         );
     }
+
+    #[tokio::test]
+    async fn test_bot_builder_with_browser() {
+        let backend = create_test_sqlite_backend().await;
+        let bot = Bot::builder()
+            .with_backend(backend)
+            .with_transport_factory(TokioWebSocketTransportFactory::new())
+            .with_http_client(MockHttpClient)
+            .with_pair_code(PairCodeOptions {
+                phone_number: "15551234567".to_string(),
+                ..Default::default()
+            })
+            .with_browser(BrowserProfile {
+                name: "Safari".to_string(),
+                os: "Mac OS".to_string(),
+                version: "14.4.1".to_string(),
+            })
+            .build()
+            .await
+            .expect("Failed to build bot with a browser profile");
+
+        let device = bot
+            .client()
+            .persistence_manager()
+            .get_device_snapshot()
+            .await;
+        assert_eq!(device.device_props.os.as_deref(), Some("Mac OS"));
+        assert_eq!(
+            device.device_props.platform_type,
+            Some(wa::device_props::PlatformType::Safari as i32)
+        );
+
+        let options = bot.pair_code_options.expect("pair code options are kept");
+        assert_eq!(options.platform_display, "Safari (Mac OS)");
+    }
@@ -0,0 +1,116 @@
+    use super::*;
+    use warp_core_binary::jid::Jid;
+
+    fn parse(args: &[&str]) -> Command {
+        Cli::try_parse_from(std::iter::once("chatwarp-api").chain(args.iter().copied()))
+            .map(Cli::into_command)
+            .unwrap_or_else(|e| panic!("{args:?}: {e}"))
+    }
+
+    #[test]
+    fn no_subcommand_serves() {
+        assert_eq!(parse(&[]), Command::Serve(ServeArgs::default()));
+        assert_eq!(
+            parse(&["-p", "15551234567", "--code", "MYCODE12"]),
+            Command::Serve(ServeArgs {
+                phone: Some("15551234567".to_string()),
+                code: Some("MYCODE12".to_string()),
+            })
+        );
+        assert_eq!(
+            parse(&["serve", "--phone=15551234567"]),
+            Command::Serve(ServeArgs {
+                phone: Some("15551234567".to_string()),
+                code: None,
+            })
+        );
+    }
+
+    #[test]
+    fn legacy_flags() {
+        assert_eq!(parse(&["--migrate-only"]), Command::Migrate);
+        let cli = Cli::try_parse_from(["chatwarp-api", "--check-config"]).unwrap();
+        assert!(cli.check_config);
+    }
+
+    #[test]
+    fn code_requires_phone() {
+        assert!(Cli::try_parse_from(["chatwarp-api", "--code", "MYCODE12"]).is_err());
+    }
+
+    #[test]
+    fn ops_subcommands() {
+        assert_eq!(parse(&["migrate"]), Command::Migrate);
+        assert_eq!(
+            parse(&[
+                "instance",
+                "create",
+                "sales",
+                "--webhook-url",
+                "https://example.com/hook"
+            ]),
+            Command::Instance(InstanceCommand::Create(InstanceCreateArgs {
+                name: "sales".to_string(),
+                webhook_url: Some("https://example.com/hook".to_string()),
+                phone_number: None,
+                workspace: None,
+            }))
+        );
+        assert_eq!(
+            parse(&["instance", "delete", "sales"]),
+            Command::Instance(InstanceCommand::Delete {
+                name: "sales".to_string()
+            })
+        );
+        assert_eq!(
+            parse(&["auth", "import", "auth.json", "--force"]),
+            Command::Auth(AuthCommand::Import {
+                input: PathBuf::from("auth.json"),
+                force: true,
+            })
+        );
+        assert_eq!(
+            parse(&["secrets", "encrypt"]),
+            Command::Secrets(SecretsCommand::Encrypt)
+        );
+        assert_eq!(
+            parse(&["send-test", "5511999990000", "-t", "hi"]),
+            Command::SendTest(SendTestArgs {
+                to: "5511999990000".to_string(),
+                text: "hi".to_string(),
+                timeout_secs: 60,
+            })
+        );
+    }
+
+    #[test]
+    fn subcommands_reject_serve_flags() {
+        assert!(Cli::try_parse_from(["chatwarp-api", "-p", "15551234567", "migrate"]).is_err());
+        assert!(
+            Cli::try_parse_from(["chatwarp-api", "instance", "list", "--workspace", "nope"]).is_err()
+        );
+    }
+
+    #[test]
+    fn device_round_trips() {
+        let mut device = Device::new();
+        device.pn = Some(Jid::pn("5511999990000"));
+        device.push_name = "ops".to_string();
+
+        let imported = import_device(&export_device(&device).unwrap()).unwrap();
+        assert_eq!(imported.pn, device.pn);
+        assert_eq!(imported.push_name, "ops");
+        assert_eq!(imported.registration_id, device.registration_id);
+        assert_eq!(imported.adv_secret_key, device.adv_secret_key);
+        assert_eq!(
+            imported.signed_pre_key_signature,
+            device.signed_pre_key_signature
+        );
+    }
+
+    #[test]
+    fn unpaired_device_is_not_imported() {
+        let exported = export_device(&Device::new()).unwrap();
+        assert!(matches!(import_device(&exported), Err(CliError::NotPaired)));
+        assert!(matches!(import_device("{}"), Err(CliError::Json(_))));
+    }
@@ -0,0 +1,76 @@
+    use super::*;
+
+    #[test]
+    fn keeps_only_the_most_recent_attempts() {
+        let diagnostics = ConnectionDiagnostics::new();
+        for _ in 0..(MAX_CONNECTION_ATTEMPTS + 5) {
+            diagnostics.begin_attempt();
+        }
+
+        let recent = diagnostics.recent(usize::MAX);
+        assert_eq!(recent.len(), MAX_CONNECTION_ATTEMPTS);
+        assert_eq!(recent[0].id, (MAX_CONNECTION_ATTEMPTS + 5) as u64);
+    }
+
+    #[test]
+    fn phase_never_moves_backwards() {
+        let diagnostics = ConnectionDiagnostics::new();
+        diagnostics.begin_attempt();
+        diagnostics.set_phase(HandshakePhase::ClientFinish);
+        diagnostics.set_phase(HandshakePhase::ClientHello);
+
+        assert_eq!(diagnostics.recent(1)[0].phase, HandshakePhase::ClientFinish);
+    }
+
+    #[test]
+    fn failed_attempt_stays_failed_when_closed() {
+        let diagnostics = ConnectionDiagnostics::new();
+        diagnostics.begin_attempt();
+        diagnostics.set_wa_version("2.3000.1".to_string());
+        diagnostics.mark_failed("timeout".to_string());
+        diagnostics.mark_closed();
+        diagnostics.record_backoff(4);
+
+        let attempt = &diagnostics.recent(1)[0];
+        assert_eq!(attempt.outcome, AttemptOutcome::Failed);
+        assert_eq!(attempt.error.as_deref(), Some("timeout"));
+        assert_eq!(attempt.wa_version.as_deref(), Some("2.3000.1"));
+        assert_eq!(attempt.backoff_secs, Some(4));
+        assert!(attempt.ended_at.is_some());
+    }
+
+    #[test]
+    fn updates_without_attempts_are_ignored() {
+        let diagnostics = ConnectionDiagnostics::new();
+        diagnostics.mark_connected();
+        assert!(diagnostics.recent(10).is_empty());
+    }
+
+    #[test]
+    fn retry_stats_snapshot_counts_each_outcome() {
+        let stats = RetryStats::new();
+        stats.record_receipt_sent();
+        stats.record_receipt_sent();
+        stats.record_receipt_failure();
+        stats.record_exhausted();
+        stats.record_retry_received();
+        stats.record_retry_ignored();
+        stats.record_retry_received();
+        stats.record_message_resent();
+
+        assert_eq!(
+            stats.snapshot(),
+            RetryStatsSnapshot {
+                receipts_sent: 2,
+                receipt_failures: 1,
+                exhausted: 1,
+                retries_received: 2,
+                retries_ignored: 1,
+                messages_resent: 1,
+            }
+        );
+        assert_eq!(
+            serde_json::to_value(stats.snapshot()).expect("snapshot should serialize")["messagesResent"],
+            1
+        );
+    }
@@ -0,0 +1,46 @@
+    use super::*;
+
+    #[test]
+    fn corrupts_only_the_next_chunk() {
+        let faults = FaultInjector::default();
+        let data = Bytes::from_static(&[1, 2, 3]);
+        assert_eq!(faults.corrupt(data.clone()), data);
+
+        faults.corrupt_next_frame();
+        assert!(faults.state().corrupt_next_frame);
+        // Empty reads do not consume the fault.
+        assert!(faults.corrupt(Bytes::new()).is_empty());
+        assert_eq!(faults.corrupt(data.clone()).as_ref(), &[1, 2, 0xfc]);
+        assert_eq!(faults.corrupt(data.clone()), data);
+        assert!(!faults.state().corrupt_next_frame);
+    }
+
+    #[test]
+    fn handshake_failure_fires_once_at_its_phase() {
+        let faults = FaultInjector::default();
+        faults.fail_handshake_at(Some(HandshakePhase::ServerHello));
+        assert!(!faults.take_handshake_failure(HandshakePhase::ClientHello));
+        assert!(faults.take_handshake_failure(HandshakePhase::ServerHello));
+        assert!(!faults.take_handshake_failure(HandshakePhase::ServerHello));
+        assert_eq!(faults.state().fail_handshake_at, None);
+    }
+
+    #[test]
+    fn send_delay_until_cleared() {
+        let faults = FaultInjector::default();
+        assert_eq!(faults.send_delay(), None);
+        faults.set_send_delay(Duration::from_millis(250));
+        faults.fail_handshake_at(Some(HandshakePhase::PostFinish));
+        assert_eq!(faults.send_delay(), Some(Duration::from_millis(250)));
+        assert_eq!(faults.send_delay(), Some(Duration::from_millis(250)));
+
+        faults.clear();
+        assert_eq!(
+            faults.state(),
+            FaultState {
+                corrupt_next_frame: false,
+                send_delay_ms: 0,
+                fail_handshake_at: None,
+            }
+        );
+    }
@@ -0,0 +1,58 @@
+    use super::*;
+
+    fn no_jitter(max_concurrent: usize) -> HandshakeGate {
+        HandshakeGate::new(HandshakeConfig {
+            max_concurrent,
+            jitter: Duration::ZERO,
+        })
+    }
+
+    #[test]
+    fn jitter_stays_in_bounds() {
+        let gate = HandshakeGate::new(HandshakeConfig {
+            max_concurrent: 1,
+            jitter: Duration::from_millis(50),
+        });
+        for _ in 0..100 {
+            assert!(gate.jitter_delay() <= Duration::from_millis(50));
+        }
+        assert_eq!(no_jitter(1).jitter_delay(), Duration::ZERO);
+    }
+
+    #[test]
+    fn zero_concurrency_still_lets_one_through() {
+        assert_eq!(no_jitter(0).config().max_concurrent, 1);
+    }
+
+    #[tokio::test]
+    async fn attempts_beyond_the_limit_wait() {
+        let gate = Arc::new(no_jitter(2));
+        let first = gate.enter().await;
+        let _second = gate.enter().await;
+        assert_eq!(gate.stats().in_flight, 2);
+
+        let queued = tokio::spawn({
+            let gate = gate.clone();
+            async move {
+                let _permit = gate.enter().await;
+            }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!queued.is_finished());
+        assert_eq!(
+            gate.stats(),
+            HandshakeGateStats {
+                max_concurrent: 2,
+                in_flight: 2,
+                waiting: 1,
+            }
+        );
+
+        drop(first);
+        tokio::time::timeout(Duration::from_secs(1), queued)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(gate.stats().waiting, 0);
+        assert_eq!(gate.stats().in_flight, 1);
+    }
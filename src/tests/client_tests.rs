@@ -872,3 +872,44 @@
 
         info!("✅ test_immediate_session_does_not_wait_for_offline_sync passed");
     }
+
+    #[tokio::test]
+    async fn test_clear_credentials_unpairs_and_persists_device() {
+        let client = crate::test_utils::create_test_client_with_name("clear_credentials").await;
+        let pn: Jid = "559980000001:3@s.whatsapp.net"
+            .parse()
+            .expect("test JID should be valid");
+        client
+            .persistence_manager
+            .process_command(DeviceCommand::SetId(Some(pn.clone())))
+            .await;
+        client
+            .persistence_manager
+            .process_command(DeviceCommand::SetPushName("Sales".to_string()))
+            .await;
+        let before = client.persistence_manager.get_device_snapshot().await;
+
+        let cleared = client
+            .clear_credentials()
+            .await
+            .expect("clearing credentials should succeed");
+        assert_eq!(cleared, Some(pn));
+
+        let after = client.persistence_manager.get_device_snapshot().await;
+        assert!(after.pn.is_none());
+        assert!(after.push_name.is_empty());
+        assert_ne!(
+            after.identity_key.public_key.public_key_bytes(),
+            before.identity_key.public_key.public_key_bytes()
+        );
+        assert_eq!(after.app_version_tertiary, before.app_version_tertiary);
+
+        let stored = client
+            .persistence_manager
+            .backend()
+            .load()
+            .await
+            .expect("device should load")
+            .expect("device should exist");
+        assert!(stored.pn.is_none());
+    }
@@ -0,0 +1,779 @@
+    use super::*;
+
+    fn env<'a>(vars: &'a [(&'a str, &'a str)]) -> Env<'a> {
+        Env::from_lookup(|name| {
+            vars.iter()
+                .find(|(key, _)| *key == name)
+                .map(|(_, value)| value.to_string())
+        })
+    }
+
+    fn config(provider: Option<&str>, url: Option<&str>) -> Result<DatabaseConfig, AppError> {
+        DatabaseConfig::from_values(provider.map(String::from), url.map(String::from))
+    }
+
+    #[test]
+    fn provider_is_inferred_from_url() {
+        assert_eq!(config(None, None).unwrap().provider, DatabaseProvider::Sqlite);
+        assert_eq!(
+            config(None, Some("postgres://u:p@db/chatwarp")).unwrap().provider,
+            DatabaseProvider::Postgresql
+        );
+        assert_eq!(
+            config(None, Some("file:whatsapp.db")).unwrap().provider,
+            DatabaseProvider::Sqlite
+        );
+        assert!(matches!(
+            config(None, Some("redis://db/0")),
+            Err(AppError::InvalidEnv { name: "DATABASE_URL", .. })
+        ));
+        assert_eq!(config(None, Some("whatsapp.db")).unwrap().provider, DatabaseProvider::Sqlite);
+    }
+
+    #[test]
+    fn mysql_is_refused_by_name() {
+        for (provider, url, variable) in [
+            (Some("mysql"), None, "DATABASE_PROVIDER"),
+            (Some(" MariaDB "), None, "DATABASE_PROVIDER"),
+            (None, Some("mysql://u:p@db/chatwarp"), "DATABASE_URL"),
+            (Some("postgresql"), Some("mysql://u:p@db/chatwarp"), "DATABASE_URL"),
+        ] {
+            match config(provider, url) {
+                Err(AppError::InvalidEnv { name, reason }) => {
+                    assert_eq!(name, variable);
+                    assert!(reason.starts_with("MySQL is not supported"), "{reason}");
+                }
+                other => panic!("{provider:?} {url:?} gave {other:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn explicit_provider_must_match_url() {
+        assert_eq!(
+            config(Some("postgresql"), Some("postgresql://db/x")).unwrap().provider,
+            DatabaseProvider::Postgresql
+        );
+        assert!(matches!(
+            config(Some("sqlite"), Some("postgres://db/x")),
+            Err(AppError::InvalidEnv { name: "DATABASE_URL", .. })
+        ));
+        assert!(matches!(
+            config(Some("postgresql"), Some("whatsapp.db")),
+            Err(AppError::InvalidEnv { name: "DATABASE_URL", .. })
+        ));
+        assert!(matches!(
+            config(Some("postgresql"), None),
+            Err(AppError::MissingEnv("DATABASE_URL"))
+        ));
+        assert!(matches!(
+            config(Some("oracle"), None),
+            Err(AppError::InvalidEnv { name: "DATABASE_PROVIDER", .. })
+        ));
+    }
+
+    #[test]
+    fn migrations_run_by_default() {
+        assert!(config(None, None).unwrap().run_migrations);
+        assert_eq!(parse_flag("FALSE"), Some(false));
+        assert_eq!(parse_flag(" yes "), Some(true));
+        assert_eq!(parse_flag("later"), None);
+    }
+
+    #[test]
+    fn pool_config_reads_and_validates_env() {
+        let pool = PoolConfig::read(&env(&[
+            ("DATABASE_POOL_MAX", "20"),
+            ("DATABASE_POOL_MIN", "2"),
+            ("DATABASE_POOL_ACQUIRE_TIMEOUT", "5"),
+        ]))
+        .unwrap();
+        assert_eq!(pool.max_size, Some(20));
+        assert_eq!(pool.min_idle, Some(2));
+        assert_eq!(pool.acquire_timeout, Some(std::time::Duration::from_secs(5)));
+        assert_eq!(PoolConfig::read(&env(&[])).unwrap(), PoolConfig::default());
+
+        let invalid = |name, value| PoolConfig::read(&env(&[(name, value)])).is_err();
+        assert!(invalid("DATABASE_POOL_MAX", "0"));
+        assert!(invalid("DATABASE_POOL_MAX", "many"));
+        assert!(invalid("DATABASE_POOL_ACQUIRE_TIMEOUT", "0"));
+        assert!(
+            PoolConfig::read(&env(&[
+                ("DATABASE_POOL_MAX", "2"),
+                ("DATABASE_POOL_MIN", "3"),
+            ]))
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn secrets_keyring_reads_env() {
+        const KEY: &str = "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=";
+        let (current, previous) = (format!("k2:{KEY}"), format!("k1:{KEY}"));
+        let ring = keyring(&env(&[
+            ("SECRETS_MASTER_KEY", &current),
+            ("SECRETS_PREVIOUS_KEYS", &previous),
+        ]))
+        .unwrap()
+        .unwrap();
+        assert_eq!(ring.current_id(), "k2");
+        assert_eq!(keyring(&env(&[])).unwrap(), None);
+
+        let error = |name, value| match keyring(&env(&[(name, value)])) {
+            Err(AppError::InvalidEnv { name, .. }) => Some(name),
+            _ => None,
+        };
+        assert_eq!(error("SECRETS_MASTER_KEY", "k1:short"), Some("SECRETS_MASTER_KEY"));
+        assert_eq!(error("SECRETS_PREVIOUS_KEYS", KEY), Some("SECRETS_PREVIOUS_KEYS"));
+        assert_eq!(
+            error("SECRETS_MASTER_KEY_FILE", "/nonexistent/master.key"),
+            Some("SECRETS_MASTER_KEY_FILE")
+        );
+    }
+
+    #[test]
+    fn runtime_settings_read_env() {
+        assert_eq!(runtime(&env(&[])), RuntimeConfig::default());
+        let config = runtime(&env(&[
+            ("CORS_ORIGINS", "https://app.example.com, http://localhost:3000"),
+            ("RATE_LIMIT_PER_MINUTE", "120"),
+            ("WEBHOOK_GLOBAL_ENABLED", "true"),
+            ("WEBHOOK_GLOBAL_URL", "https://hooks.example.com"),
+            ("MAX_MESSAGES_PER_DAY", "1000"),
+            ("LOG_TARGETS", "warp_core=warn, bad, =debug"),
+            ("SERVER_URL", "https://api.example.com:8443/base/"),
+        ]));
+        assert_eq!(config.log_level, "info");
+        assert_eq!(config.log_filter(), "info,warp_core=warn");
+        assert_eq!(
+            config.cors_origins,
+            vec!["https://app.example.com", "http://localhost:3000"]
+        );
+        assert_eq!(config.manager_cors_origins, vec!["https://api.example.com:8443"]);
+        assert_eq!(config.ws_cors_origins, None);
+        assert_eq!(config.rate_limit_per_minute, 120);
+        assert!(config.webhook.enabled);
+        assert_eq!(config.webhook.url.as_deref(), Some("https://hooks.example.com"));
+        assert!(!config.webhook.base64);
+        assert_eq!(config.qr_cache_seconds, 5);
+        assert_eq!(config.max_messages_per_day, 1000);
+        assert_eq!(config.max_instances, 0);
+        assert!(!config.maintenance_mode);
+        assert!(!config.evolution_compat);
+
+        let config = runtime(&env(&[
+            ("RUST_LOG", "debug"),
+            ("LOG_TARGETS", "warp_core=trace, =info,sqlx"),
+            ("CORS_ORIGINS", " https://a.example.com, ,https://b.example.com"),
+            ("SERVER_URL", "https://api.example.com:8443/base"),
+            ("QR_IMAGE_SIZE", "1"),
+            ("MAX_INSTANCES", "3"),
+            ("MAINTENANCE_MODE", "1"),
+            ("WEBHOOK_GLOBAL_URL", " "),
+        ]));
+        assert_eq!(config.log_level, "debug");
+        assert_eq!(
+            config.log_targets.into_iter().collect::<Vec<_>>(),
+            [("warp_core".to_string(), "trace".to_string())]
+        );
+        assert_eq!(
+            config.cors_origins,
+            ["https://a.example.com", "https://b.example.com"]
+        );
+        assert_eq!(config.manager_cors_origins, ["https://api.example.com:8443"]);
+        assert_eq!(config.qr_image_size, MIN_QR_IMAGE_SIZE);
+        assert_eq!(config.max_instances, 3);
+        assert!(config.maintenance_mode);
+        assert_eq!(config.webhook.url, None);
+
+        let config = runtime(&env(&[
+            ("SERVER_URL", "https://api.example.com"),
+            ("MANAGER_CORS_ORIGINS", "https://admin.example.com"),
+        ]));
+        assert_eq!(config.manager_cors_origins, vec!["https://admin.example.com"]);
+        // Set but blank turns the manager origins off.
+        let config = runtime(&env(&[
+            ("SERVER_URL", "https://api.example.com"),
+            ("MANAGER_CORS_ORIGINS", " "),
+        ]));
+        assert!(config.manager_cors_origins.is_empty());
+    }
+
+    #[test]
+    fn origins_and_hosts_of_urls() {
+        assert_eq!(
+            url_origin("https://api.example.com/manager?x=1").as_deref(),
+            Some("https://api.example.com")
+        );
+        assert_eq!(url_origin("ftp://files.example.com"), None);
+        assert_eq!(
+            url_host("https://user@API.example.com:8443/").as_deref(),
+            Some("api.example.com")
+        );
+        assert_eq!(url_host("api.example.com"), None);
+    }
+
+    #[test]
+    fn profile_picture_ttl_reads_env() {
+        let config = ServerConfig::read(&env(&[("PROFILE_PICTURE_CACHE_SECONDS", "0")])).unwrap();
+        assert_eq!(config.profile_pictures.ttl, Duration::ZERO);
+        let config = ServerConfig::read(&env(&[])).unwrap();
+        assert_eq!(config.profile_pictures, ProfilePictureConfig::default());
+    }
+
+    #[test]
+    fn server_settings_read_env() {
+        let config = ServerConfig::read(&env(&[
+            ("PORT", "9000"),
+            ("CHATWARP_PASSWORD", " secret "),
+            ("AUTH_TRUST_FORWARDED_FOR", "TRUE"),
+            ("AUTH_SAVE_DEBOUNCE_MS", "0"),
+            ("WHATSAPP_NUMBERS_CACHE_SECONDS", "60"),
+            ("STATIC_CACHE_MB", "2"),
+        ]))
+        .unwrap();
+        assert_eq!(config.port, 9000);
+        assert_eq!(config.api_password.as_deref(), Some(" secret "));
+        assert!(config.trust_forwarded_for);
+        assert_eq!(config.save_debounce, Duration::ZERO);
+        assert_eq!(config.numbers_cache_ttl, Duration::from_secs(60));
+        assert_eq!(config.static_cache_bytes, 2 * 1024 * 1024);
+
+        let config = ServerConfig::read(&env(&[("CHATWARP_PASSWORD", "")])).unwrap();
+        assert_eq!(config.port, DEFAULT_PORT);
+        assert_eq!(config.api_password, None);
+        assert_eq!(config.session_ttl_seconds, DEFAULT_SESSION_TTL_SECONDS);
+        assert_eq!(config.save_debounce, DEFAULT_SAVE_DEBOUNCE);
+        assert_eq!(config.restart_policy, RestartPolicy::default());
+
+        assert!(matches!(
+            ServerConfig::read(&env(&[("TLS_KEY_PATH", "/tls/key.pem")])),
+            Err(AppError::InvalidEnv {
+                name: "TLS_CERT_PATH",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn plain_http_without_tls_settings() {
+        assert_eq!(tls(&env(&[])), Ok(TlsMode::Off));
+        assert_eq!(tls(&env(&[("TLS_CERT_PATH", " ")])), Ok(TlsMode::Off));
+    }
+
+    #[test]
+    fn tls_files_need_both_paths() {
+        assert_eq!(
+            tls(&env(&[
+                ("TLS_CERT_PATH", "/tls/cert.pem"),
+                ("TLS_KEY_PATH", "/tls/key.pem"),
+                ("TLS_RELOAD_SECS", "0"),
+            ])),
+            Ok(TlsMode::Files {
+                cert: "/tls/cert.pem".into(),
+                key: "/tls/key.pem".into(),
+                reload_interval: Duration::ZERO,
+            })
+        );
+        let err = tls(&env(&[("TLS_KEY_PATH", "/tls/key.pem")])).unwrap_err();
+        assert_eq!(err.variable(), "TLS_CERT_PATH");
+        let Ok(TlsMode::Files {
+            reload_interval, ..
+        }) = tls(&env(&[("TLS_CERT_PATH", "c"), ("TLS_KEY_PATH", "k")]))
+        else {
+            panic!("expected PEM files");
+        };
+        assert_eq!(reload_interval, DEFAULT_TLS_RELOAD_INTERVAL);
+    }
+
+    #[test]
+    fn acme_defaults_to_the_server_url_host() {
+        let Ok(TlsMode::Acme(acme)) = tls(&env(&[
+            ("TLS_ACME_ENABLED", "true"),
+            ("SERVER_URL", "https://API.example.com:8443/base"),
+        ])) else {
+            panic!("expected ACME");
+        };
+        assert_eq!(acme.domains, vec!["api.example.com"]);
+        assert_eq!(acme.cache_dir, PathBuf::from("./acme-cache"));
+        assert!(!acme.staging);
+
+        let Ok(TlsMode::Acme(acme)) = tls(&env(&[
+            ("TLS_ACME_ENABLED", "1"),
+            ("TLS_ACME_DOMAINS", "a.example.com, b.example.com"),
+            ("TLS_ACME_EMAIL", "ops@example.com"),
+            ("TLS_ACME_STAGING", "true"),
+        ])) else {
+            panic!("expected ACME");
+        };
+        assert_eq!(acme.domains, vec!["a.example.com", "b.example.com"]);
+        assert_eq!(acme.contact.as_deref(), Some("ops@example.com"));
+        assert!(acme.staging);
+    }
+
+    #[test]
+    fn acme_rejects_files_and_missing_domains() {
+        assert_eq!(
+            tls(&env(&[("TLS_ACME_ENABLED", "true"), ("TLS_CERT_PATH", "c")])),
+            Err(TlsError::AcmeWithFiles)
+        );
+        assert_eq!(
+            tls(&env(&[("TLS_ACME_ENABLED", "true")])),
+            Err(TlsError::NoAcmeDomain)
+        );
+        assert_eq!(
+            tls(&env(&[
+                ("TLS_ACME_ENABLED", "true"),
+                ("TLS_ACME_DOMAINS", " , "),
+                ("SERVER_URL", "https://api.example.com"),
+            ])),
+            Err(TlsError::NoAcmeDomain)
+        );
+    }
+
+    #[cfg(feature = "sentry")]
+    #[test]
+    fn sentry_needs_a_valid_dsn() {
+        assert_eq!(sentry(&env(&[])), None);
+        assert_eq!(sentry(&env(&[("SENTRY_DSN", "not a dsn")])), None);
+
+        let dsn = "https://public@sentry.example.com/42";
+        let defaults = sentry(&env(&[("SENTRY_DSN", dsn)])).unwrap();
+        assert_eq!(defaults.sample_rate, 1.0);
+        assert_eq!(defaults.throttle, DEFAULT_SENTRY_THROTTLE);
+        assert!(defaults.ignore.is_empty());
+
+        let tuned = sentry(&env(&[
+            ("SENTRY_DSN", dsn),
+            ("SENTRY_ENVIRONMENT", "staging"),
+            ("SENTRY_SAMPLE_RATE", "0.25"),
+            ("SENTRY_IGNORE", "Stream error, ,keepalive timeout"),
+            ("SENTRY_THROTTLE_SECS", "0"),
+        ]))
+        .unwrap();
+        assert_eq!(tuned.environment.as_deref(), Some("staging"));
+        assert_eq!(tuned.sample_rate, 0.25);
+        assert_eq!(tuned.ignore, ["Stream error", "keepalive timeout"]);
+        assert_eq!(tuned.throttle, Duration::ZERO);
+
+        let clamped = sentry(&env(&[("SENTRY_DSN", dsn), ("SENTRY_SAMPLE_RATE", "3")])).unwrap();
+        assert_eq!(clamped.sample_rate, 1.0);
+    }
+
+    #[test]
+    fn ws_config_ignores_invalid_values() {
+        let config = ws(&env(&[
+            ("WS_BUFFER_SIZE", "32"),
+            ("WS_LAG_POLICY", "disconnect"),
+            ("WS_PING_INTERVAL_SECS", "0"),
+            ("WS_PONG_TIMEOUT_SECS", "abc"),
+        ]));
+        let defaults = WsConfig::default();
+
+        assert_eq!(config.buffer, 32);
+        assert_eq!(config.lag_policy, LagPolicy::Disconnect);
+        assert_eq!(config.ping_interval, defaults.ping_interval);
+        assert_eq!(config.pong_timeout, defaults.pong_timeout);
+    }
+
+    #[test]
+    fn country_code_reads_env() {
+        let config = participants(&env(&[
+            ("DEFAULT_COUNTRY_CODE", " +55 "),
+            ("LOCAL_NUMBER_MAX_DIGITS", "10"),
+        ]));
+        assert_eq!(config.default_country_code.as_deref(), Some("55"));
+        assert_eq!(config.local_max_digits, 10);
+
+        let config = participants(&env(&[("DEFAULT_COUNTRY_CODE", "br")]));
+        assert_eq!(config, ParticipantConfig::default());
+    }
+
+    #[test]
+    fn logging_reads_file_settings() {
+        let config = logging(&env(&[
+            ("LOG_FORMAT", "json"),
+            ("LOG_FILE", "/var/log/chatwarp/api.log"),
+            ("LOG_FILE_MAX_MB", "10"),
+            ("LOG_FILE_MAX_FILES", "0"),
+        ]));
+        assert_eq!(config.format, LogFormat::Json);
+        let file = config.file.unwrap();
+        assert_eq!(file.max_bytes, 10 * 1024 * 1024);
+        assert_eq!(file.max_files, 1);
+
+        assert_eq!(logging(&env(&[])), LoggingConfig::default());
+    }
+
+    #[test]
+    fn startup_settings_read_env() {
+        let startup = StartupConfig::read(&env(&[("INSTANCE_LOG_BUFFER", "0")]));
+        assert_eq!(startup.instance_log_buffer, 0);
+        let startup = StartupConfig::read(&env(&[]));
+        assert_eq!(startup.instance_log_buffer, DEFAULT_INSTANCE_LOG_BUFFER);
+        assert_eq!(startup.runtime, RuntimeConfig::default());
+    }
+
+    #[cfg(feature = "nats")]
+    #[test]
+    fn nats_requires_enabled_flag() {
+        assert_eq!(nats(&env(&[])), None);
+
+        let config = nats(&env(&[
+            ("NATS_ENABLED", "true"),
+            ("NATS_JETSTREAM", "1"),
+            ("NATS_SUBJECT_PREFIX", " events "),
+        ]))
+        .unwrap();
+        assert_eq!(config.url, "nats://127.0.0.1:4222");
+        assert_eq!(config.subject_prefix, "events");
+        assert!(config.jetstream);
+        assert_eq!(config.stream, "CHATWARP");
+        assert!(!config.global);
+    }
+
+    #[cfg(feature = "nats")]
+    #[test]
+    fn nats_global_reads_its_prefix_and_scope() {
+        let config = nats(&env(&[
+            ("NATS_ENABLED", "true"),
+            ("NATS_GLOBAL_ENABLED", "true"),
+            ("NATS_SUBJECT_PREFIX", "events"),
+            ("GLOBAL_SINK_EXCLUDE_INSTANCES", "test"),
+        ]))
+        .unwrap();
+        assert!(config.global);
+        assert_eq!(config.global_subject_prefix, "events-global");
+        assert!(!config.scope.includes("test"));
+        assert!(config.scope.includes("sales"));
+
+        let config = nats(&env(&[
+            ("NATS_ENABLED", "true"),
+            ("NATS_GLOBAL_SUBJECT_PREFIX", "all"),
+        ]))
+        .unwrap();
+        assert_eq!(config.global_subject_prefix, "all");
+    }
+
+    #[test]
+    fn global_sink_lists_read_env() {
+        assert_eq!(global_sinks(&env(&[])), InstanceScope::default());
+        let scope = global_sinks(&env(&[
+            ("GLOBAL_SINK_INSTANCES", " sales, support ,"),
+            ("GLOBAL_SINK_EXCLUDE_INSTANCES", "test"),
+        ]));
+        assert_eq!(
+            scope.allow,
+            Some(["sales", "support"].map(String::from).into())
+        );
+        assert_eq!(scope.deny, ["test"].map(String::from).into());
+
+        let scope = global_sinks(&env(&[("GLOBAL_SINK_INSTANCES", " , ")]));
+        assert_eq!(scope.allow, None);
+    }
+
+    #[test]
+    fn inbound_dedup_reads_env() {
+        let config = inbound_dedup(&env(&[
+            ("INBOUND_DEDUP_ENABLED", "false"),
+            ("INBOUND_DEDUP_TTL_SECONDS", "600"),
+            ("INBOUND_DEDUP_CACHE_SIZE", "0"),
+        ]));
+        assert!(!config.enabled);
+        assert_eq!(config.ttl, Duration::from_secs(600));
+        assert_eq!(config.cache_size, DedupConfig::default().cache_size);
+        assert_eq!(inbound_dedup(&env(&[])), DedupConfig::default());
+        assert!(DedupConfig::default().enabled);
+    }
+
+    #[test]
+    fn sse_reads_env() {
+        assert_eq!(sse(&env(&[])), SseConfig::default());
+        let config = sse(&env(&[
+            ("SSE_HISTORY_SIZE", "50"),
+            ("SSE_HEARTBEAT_SECS", "0"),
+        ]));
+        assert_eq!(config.history, 50);
+        assert_eq!(config.heartbeat, SseConfig::default().heartbeat);
+    }
+
+    #[test]
+    fn timeouts_read_budgets_and_zero_turns_them_off() {
+        let config = timeouts(&env(&[
+            ("REQUEST_TIMEOUT_MESSAGES_SECS", " 45 "),
+            ("REQUEST_TIMEOUT_QUERIES_SECS", "0"),
+            ("REQUEST_TIMEOUT_SECS", "soon"),
+        ]));
+        let defaults = TimeoutConfig::default();
+        assert_eq!(config.messages, Some(Duration::from_secs(45)));
+        assert_eq!(config.connect, defaults.connect);
+        assert_eq!(config.queries, None);
+        assert_eq!(config.other, defaults.other);
+    }
+
+    #[test]
+    fn uploads_read_env() {
+        let config = uploads(&env(&[
+            ("MEDIA_UPLOAD_DIR", "/data/uploads"),
+            ("MEDIA_UPLOAD_MAX_MB", "2"),
+            ("MEDIA_UPLOAD_TTL_MINUTES", "0"),
+        ]));
+        assert_eq!(config.dir, PathBuf::from("/data/uploads"));
+        assert_eq!(config.max_bytes, 2 * 1024 * 1024);
+        assert_eq!(config.ttl, UploadConfig::default().ttl);
+    }
+
+    #[test]
+    fn event_history_reads_env() {
+        let config = event_history(&env(&[
+            ("EVENT_HISTORY_SIZE", "50"),
+            ("EVENT_HISTORY_PERSIST", "TRUE"),
+        ]));
+        assert_eq!(config.size, 50);
+        assert!(config.persist);
+
+        let config = event_history(&env(&[("EVENT_HISTORY_SIZE", "0")]));
+        assert_eq!(config, EventHistoryConfig::default());
+    }
+
+    #[test]
+    fn exports_read_env() {
+        let config = exports(&env(&[
+            ("EXPORT_DIR", "/data/exports"),
+            ("EXPORT_TTL_HOURS", "2"),
+            ("EXPORT_URL_TTL_MINUTES", "0"),
+            ("EXPORT_SIGNING_SECRET", "s3cret"),
+            ("SERVER_URL", "https://api.example.com/"),
+        ]));
+        assert_eq!(config.dir, PathBuf::from("/data/exports"));
+        assert_eq!(config.ttl, Duration::from_secs(2 * 3600));
+        assert_eq!(config.url_ttl, ExportConfig::default().url_ttl);
+        assert_eq!(config.secret, b"s3cret");
+        assert_eq!(config.public_url.as_deref(), Some("https://api.example.com"));
+
+        let config = exports(&env(&[]));
+        assert_eq!(config.public_url, None);
+        assert_ne!(config.secret, exports(&env(&[])).secret);
+    }
+
+    #[test]
+    fn cache_reads_env() {
+        let config = cache(&env(&[
+            ("CACHE_LOCAL_ENABLED", "false"),
+            ("CACHE_LOCAL_TTL", "120"),
+            ("CACHE_LOCAL_MAX_ENTRIES", "0"),
+            ("CACHE_REDIS_ENABLED", "true"),
+            ("CACHE_REDIS_URI", " redis://cache:6379/2 "),
+            ("CACHE_REDIS_TTL", "600"),
+        ]));
+        assert!(!config.local_enabled);
+        assert_eq!(config.local_ttl, Duration::from_secs(120));
+        assert_eq!(
+            config.local_max_entries,
+            CacheConfig::default().local_max_entries
+        );
+        assert_eq!(
+            config.redis,
+            Some(RedisCacheConfig {
+                uri: "redis://cache:6379/2".to_string(),
+                prefix: "chatwarp".to_string(),
+                ttl: Duration::from_secs(600),
+            })
+        );
+        assert_eq!(cache(&env(&[])), CacheConfig::default());
+        assert!(CacheConfig::default().redis.is_none());
+    }
+
+    #[test]
+    fn legacy_routes_flag() {
+        let legacy = |value| {
+            ServerConfig::read(&env(&[("LEGACY_ROUTES", value)]))
+                .unwrap()
+                .api_mount
+                .legacy_routes
+        };
+        assert!(legacy(""));
+        for off in ["false", "0", "OFF"] {
+            assert!(!legacy(off), "{off}");
+        }
+        assert!(legacy("true"));
+    }
+
+    #[test]
+    fn meta_reads_env_with_defaults() {
+        let config = meta(&env(&[
+            ("META_VERIFY_TOKEN", "secret-token"),
+            ("META_GRAPH_URL", "http://graph.local/"),
+            ("META_APP_SECRET", "  "),
+        ]));
+        assert_eq!(config.verify_token.as_deref(), Some("secret-token"));
+        assert_eq!(config.app_secret, None);
+        assert_eq!(config.graph_url, "http://graph.local");
+        assert_eq!(config.graph_version, MetaConfig::default().graph_version);
+    }
+
+    #[test]
+    fn outbox_reads_env() {
+        assert_eq!(outbox(&env(&[])), OutboxConfig::default());
+        let config = outbox(&env(&[
+            ("OUTBOX_BATCH_SIZE", "25"),
+            ("OUTBOX_POLL_MS", " 250 "),
+            ("OUTBOX_LEASE_SECS", "0"),
+            ("OUTBOX_RETENTION_HOURS", "abc"),
+        ]));
+        assert_eq!(config.batch_size, 25);
+        assert_eq!(config.poll, Duration::from_millis(250));
+        assert_eq!(config.lease_secs, OutboxConfig::default().lease_secs);
+        assert_eq!(
+            config.retention_hours,
+            OutboxConfig::default().retention_hours
+        );
+    }
+
+    #[test]
+    fn retention_reads_env() {
+        let config = retention(&env(&[
+            ("RETENTION_MESSAGES_DAYS", "90"),
+            ("RETENTION_WEBHOOK_LOGS_DAYS", "0"),
+            ("RETENTION_MEDIA_DAYS", "99999"),
+            ("RETENTION_INTERVAL_MINUTES", "0"),
+            ("RETENTION_BATCH_SIZE", "250"),
+        ]));
+        assert_eq!(
+            config.defaults,
+            RetentionPolicy {
+                messages_days: Some(90),
+                webhook_logs_days: Some(0),
+                media_days: Some(MAX_RETENTION_DAYS),
+            }
+        );
+        assert_eq!(config.interval, RetentionConfig::default().interval);
+        assert_eq!(config.batch_size, 250);
+        assert_eq!(
+            RetentionConfig::default().defaults,
+            RetentionPolicy::default()
+        );
+    }
+
+    #[test]
+    fn health_reads_env() {
+        assert_eq!(health(&env(&[])), HealthConfig::default());
+        let config = health(&env(&[
+            ("HEALTH_CRITICAL", "Database, nats,"),
+            ("HEALTH_TIMEOUT_MS", "500"),
+            ("HEALTH_SLOW_MS", "0"),
+        ]));
+        assert_eq!(config.critical, vec!["database", "nats"]);
+        assert_eq!(config.timeout, Duration::from_millis(500));
+        assert_eq!(config.slow, HealthConfig::default().slow);
+        assert!(health(&env(&[("HEALTH_CRITICAL", "")])).critical.is_empty());
+    }
+
+    #[test]
+    fn wa_version_reads_pin_fallbacks_and_source() {
+        let config = wa_version(&env(&[
+            ("WA_VERSION_PIN", "2.3000.10"),
+            ("WA_VERSION_FALLBACKS", "2.3000.9, 2.3000.8,"),
+            ("WA_VERSION_SOURCE", "static"),
+        ]))
+        .unwrap();
+
+        assert_eq!(config.pinned, Some((2, 3000, 10)));
+        assert_eq!(config.fallbacks, vec![(2, 3000, 9), (2, 3000, 8)]);
+        assert_eq!(config.source, VersionSource::Static);
+        assert_eq!(wa_version(&env(&[])).unwrap(), VersionConfig::default());
+    }
+
+    #[test]
+    fn wa_version_rejects_invalid_values() {
+        let err = wa_version(&env(&[("WA_VERSION_FALLBACKS", "2.3000.9,latest")])).unwrap_err();
+        assert_eq!(
+            err,
+            VersionConfigError::InvalidVersion {
+                name: "WA_VERSION_FALLBACKS",
+                value: "latest".to_string(),
+            }
+        );
+
+        let err = wa_version(&env(&[("WA_VERSION_SOURCE", "cdn")])).unwrap_err();
+        assert_eq!(err, VersionConfigError::InvalidSource("cdn".to_string()));
+    }
+
+    #[test]
+    fn handshake_defaults_and_overrides() {
+        assert_eq!(handshake(&env(&[])), HandshakeConfig::default());
+        assert_eq!(
+            handshake(&env(&[
+                ("HANDSHAKE_MAX_CONCURRENT", "3"),
+                ("HANDSHAKE_JITTER_MS", "0")
+            ])),
+            HandshakeConfig {
+                max_concurrent: 3,
+                jitter: Duration::ZERO,
+            }
+        );
+        assert_eq!(
+            handshake(&env(&[
+                ("HANDSHAKE_MAX_CONCURRENT", "0"),
+                ("HANDSHAKE_JITTER_MS", "soon")
+            ])),
+            HandshakeConfig::default()
+        );
+    }
+
+    #[test]
+    fn standby_defaults_to_a_primary_without_leases() {
+        let config = standby(&env(&[]));
+        assert!(!config.standby);
+        assert!(!config.leases);
+        assert!(config.node_id.starts_with("node-"));
+        assert_eq!(
+            config.lease_ttl,
+            Duration::from_secs(DEFAULT_INSTANCE_LEASE_SECS)
+        );
+    }
+
+    #[test]
+    fn standby_mode_implies_leases() {
+        let config = standby(&env(&[
+            ("STANDBY_MODE", "true"),
+            ("HOSTNAME", "chatwarp-1"),
+            ("INSTANCE_LEASE_SECS", "12"),
+        ]));
+        assert!(config.standby);
+        assert!(config.leases);
+        assert_eq!(config.node_id, "chatwarp-1");
+        assert_eq!(config.lease_ttl, Duration::from_secs(12));
+
+        let config = standby(&env(&[
+            ("INSTANCE_LEASES", "1"),
+            ("NODE_ID", " a "),
+            ("HOSTNAME", "chatwarp-1"),
+            ("INSTANCE_LEASE_SECS", "0"),
+        ]));
+        assert!(!config.standby);
+        assert!(config.leases);
+        assert_eq!(config.node_id, "a");
+        assert_eq!(
+            config.lease_ttl,
+            Duration::from_secs(DEFAULT_INSTANCE_LEASE_SECS)
+        );
+    }
+
+    #[test]
+    fn thumbnails_read_size_and_ffmpeg() {
+        assert_eq!(thumbnails(&env(&[])), ThumbnailConfig::default());
+        assert_eq!(thumbnails(&env(&[("MEDIA_THUMBNAIL_SIZE", "0")])).max_size, None);
+        assert_eq!(
+            thumbnails(&env(&[("MEDIA_THUMBNAIL_SIZE", " 120 ")])).max_size,
+            Some(120)
+        );
+        assert_eq!(
+            thumbnails(&env(&[("MEDIA_THUMBNAIL_SIZE", "big")])).max_size,
+            ThumbnailConfig::default().max_size
+        );
+        assert_eq!(
+            thumbnails(&env(&[("FFMPEG_PATH", "/opt/ffmpeg/bin/ffmpeg")])).ffmpeg,
+            "/opt/ffmpeg/bin/ffmpeg"
+        );
+    }
@@ -0,0 +1,150 @@
+    use super::*;
+
+    fn product(id: &str, price: &str) -> Node {
+        NodeBuilder::new("product")
+            .children([
+                text_node("id", id),
+                text_node("name", format!("Produto {id}")),
+                text_node("price", price),
+                text_node("currency", "BRL"),
+                NodeBuilder::new("media")
+                    .children([NodeBuilder::new("image")
+                        .children([text_node("original_image_url", "https://img/1.jpg")])
+                        .build()])
+                    .build(),
+            ])
+            .build()
+    }
+
+    #[test]
+    fn parses_catalog_page() {
+        let response = NodeBuilder::new("iq")
+            .children([NodeBuilder::new("product_catalog")
+                .children([
+                    product("1", "19990"),
+                    product("2", "abc"),
+                    NodeBuilder::new("product").build(),
+                    NodeBuilder::new("paging")
+                        .children([text_node("after", "cursor-2")])
+                        .build(),
+                ])
+                .build()])
+            .build();
+        let page = parse_catalog(&response);
+
+        assert_eq!(page.products.len(), 2);
+        assert_eq!(page.products[0].price_amount1000, Some(19990));
+        assert_eq!(page.products[0].image_urls, vec!["https://img/1.jpg"]);
+        assert_eq!(page.products[1].price_amount1000, None);
+        assert_eq!(page.next_cursor.as_deref(), Some("cursor-2"));
+    }
+
+    #[test]
+    fn parses_collections() {
+        let response = NodeBuilder::new("iq")
+            .children([NodeBuilder::new("collections")
+                .children([NodeBuilder::new("collection")
+                    .children([text_node("id", "c1"), text_node("name", "Verão"), product("1", "1000")])
+                    .build()])
+                .build()])
+            .build();
+        let collections = parse_collections(&response);
+
+        assert_eq!(collections.len(), 1);
+        assert_eq!(collections[0].name.as_deref(), Some("Verão"));
+        assert_eq!(collections[0].products[0].id, "1");
+        assert!(parse_collections(&NodeBuilder::new("iq").build()).is_empty());
+    }
+
+    #[test]
+    fn parses_business_profile() {
+        let response = NodeBuilder::new("iq")
+            .children([NodeBuilder::new("business_profile")
+                .children([NodeBuilder::new("profile")
+                    .attr("jid", "5511999990000@s.whatsapp.net")
+                    .children([
+                        text_node("description", "Padaria"),
+                        text_node("address", "Rua A, 1"),
+                        text_node("website", "https://a.example"),
+                        text_node("website", "https://b.example"),
+                        NodeBuilder::new("categories")
+                            .children([NodeBuilder::new("category")
+                                .attr("id", "133436743388217")
+                                .string_content("Bakery")
+                                .build()])
+                            .build(),
+                        NodeBuilder::new("business_hours")
+                            .attr("timezone", "America/Sao_Paulo")
+                            .children([
+                                NodeBuilder::new("business_hours_config")
+                                    .attr("day_of_week", "mon")
+                                    .attr("mode", "specific_hours")
+                                    .attr("open_time", "480")
+                                    .attr("close_time", "1080")
+                                    .build(),
+                                NodeBuilder::new("business_hours_config")
+                                    .attr("day_of_week", "sun")
+                                    .attr("mode", "open_24h")
+                                    .build(),
+                            ])
+                            .build(),
+                    ])
+                    .build()])
+                .build()])
+            .build();
+        let profile = parse_profile(&response).unwrap();
+
+        assert_eq!(profile.jid, "5511999990000@s.whatsapp.net");
+        assert_eq!(profile.description.as_deref(), Some("Padaria"));
+        assert_eq!(profile.email, None);
+        assert_eq!(
+            profile.websites,
+            vec!["https://a.example", "https://b.example"]
+        );
+        assert_eq!(profile.categories[0].name.as_deref(), Some("Bakery"));
+        let hours = profile.business_hours.unwrap();
+        assert_eq!(hours.timezone.as_deref(), Some("America/Sao_Paulo"));
+        assert_eq!(hours.days[0].open_time, Some(480));
+        assert_eq!(hours.days[1].close_time, None);
+        assert!(parse_profile(&NodeBuilder::new("iq").build()).is_none());
+    }
+
+    #[test]
+    fn profile_update_only_carries_changed_fields() {
+        let node = build_profile_update(&BusinessProfileUpdate {
+            description: Some(String::new()),
+            websites: Some(vec!["https://a.example".to_string()]),
+            business_hours: Some(BusinessHours {
+                timezone: Some("UTC".to_string()),
+                days: vec![
+                    BusinessHoursDay {
+                        day: "mon".to_string(),
+                        mode: "specific_hours".to_string(),
+                        open_time: Some(480),
+                        close_time: Some(1080),
+                    },
+                    BusinessHoursDay {
+                        day: "tue".to_string(),
+                        mode: "open_24h".to_string(),
+                        open_time: Some(1),
+                        close_time: None,
+                    },
+                ],
+            }),
+            ..Default::default()
+        });
+
+        assert_eq!(node.attrs().optional_string("mutation_type"), Some("delta"));
+        assert!(node.get_optional_child("description").is_some());
+        assert!(node.get_optional_child("address").is_none());
+        assert_eq!(
+            child_text(&node, "website").as_deref(),
+            Some("https://a.example")
+        );
+        let days = node
+            .get_optional_child("business_hours")
+            .unwrap()
+            .get_children_by_tag("business_hours_config");
+        assert_eq!(days[0].attrs().optional_string("open_time"), Some("480"));
+        assert_eq!(days[1].attrs().optional_string("open_time"), None);
+    }
@@ -44,6 +44,7 @@
         let result = IsOnWhatsAppResult {
             jid,
             is_registered: true,
+            query: Some("+1234567890".to_string()),
         };
 
         assert!(result.is_registered);
@@ -0,0 +1,33 @@
+    use super::*;
+
+    fn device(jid: &str) -> Jid {
+        jid.parse().expect("test JID should be valid")
+    }
+
+    #[test]
+    fn marks_primary_and_current_device() {
+        let devices = [
+            device("5511999990000:12@s.whatsapp.net"),
+            device("5511999990000@s.whatsapp.net"),
+            device("5511999990000:3@s.whatsapp.net"),
+        ];
+        let linked = linked_devices(&devices, 3);
+
+        assert_eq!(
+            linked.iter().map(|d| d.device_id).collect::<Vec<_>>(),
+            vec![0, 3, 12]
+        );
+        assert!(linked[0].primary && !linked[0].current);
+        assert!(!linked[1].primary && linked[1].current);
+        assert!(!linked[2].primary && !linked[2].current);
+        assert_eq!(linked[2].jid, "5511999990000:12@s.whatsapp.net");
+    }
+
+    #[test]
+    fn drops_repeated_devices() {
+        let devices = [
+            device("5511999990000:7@s.whatsapp.net"),
+            device("5511999990000:7@s.whatsapp.net"),
+        ];
+        assert_eq!(linked_devices(&devices, 1).len(), 1);
+    }
@@ -24,3 +24,164 @@
         assert_eq!(metadata.participants.len(), 1);
         assert!(metadata.participants[0].is_admin);
     }
+
+    #[test]
+    fn test_invite_code_from_link_or_code() {
+        assert_eq!(invite_code("AbCdEf123"), "AbCdEf123");
+        assert_eq!(invite_code(" AbCdEf123 "), "AbCdEf123");
+        assert_eq!(
+            invite_code("https://chat.whatsapp.com/AbCdEf123"),
+            "AbCdEf123"
+        );
+        assert_eq!(
+            invite_code("chat.whatsapp.com/AbCdEf123?mode=r"),
+            "AbCdEf123"
+        );
+        assert_eq!(invite_code("https://chat.whatsapp.com/"), "");
+    }
+
+    #[test]
+    fn test_parse_invite_info() {
+        let group = NodeBuilder::new("group")
+            .attr("id", "120363000000000000")
+            .attr("subject", "Test Group")
+            .attr("s_o", "1111@s.whatsapp.net")
+            .attr("s_t", "1700000000")
+            .attr("creator", "2222@s.whatsapp.net")
+            .attr("creation", "1690000000")
+            .attr("size", "42")
+            .children([
+                NodeBuilder::new("description")
+                    .children([NodeBuilder::new("body").string_content("Rules").build()])
+                    .build(),
+                NodeBuilder::new("participant")
+                    .attr("jid", "2222@s.whatsapp.net")
+                    .build(),
+                NodeBuilder::new("announcement").build(),
+            ])
+            .build();
+
+        let info = parse_invite_info(&group);
+
+        assert_eq!(info.id, "120363000000000000@g.us");
+        assert_eq!(info.subject, "Test Group");
+        assert_eq!(info.subject_owner.as_deref(), Some("1111@s.whatsapp.net"));
+        assert_eq!(info.subject_time, Some(1700000000));
+        assert_eq!(info.creator.as_deref(), Some("2222@s.whatsapp.net"));
+        assert_eq!(info.creation, Some(1690000000));
+        assert_eq!(info.size, 42);
+        assert_eq!(info.description.as_deref(), Some("Rules"));
+        assert_eq!(info.participants, vec!["2222@s.whatsapp.net".to_string()]);
+        assert!(info.announce);
+        assert!(!info.restrict);
+    }
+
+    #[test]
+    fn test_parse_invite_info_size_defaults_to_participants() {
+        let group = NodeBuilder::new("group")
+            .attr("id", "120363000000000000")
+            .children([
+                NodeBuilder::new("participant")
+                    .attr("jid", "1111@s.whatsapp.net")
+                    .build(),
+                NodeBuilder::new("locked").build(),
+            ])
+            .build();
+
+        let info = parse_invite_info(&group);
+
+        assert_eq!(info.size, 1);
+        assert!(info.restrict);
+        assert_eq!(info.description, None);
+    }
+
+    #[test]
+    fn test_parse_join_response() {
+        let joined = NodeBuilder::new("iq")
+            .children([NodeBuilder::new("group")
+                .attr("jid", "120363000000000000@g.us")
+                .build()])
+            .build();
+        let pending = NodeBuilder::new("iq")
+            .children([NodeBuilder::new("membership_approval_request")
+                .attr("jid", "120363000000000000@g.us")
+                .build()])
+            .build();
+
+        let joined = parse_join_response(&joined).expect("join response should parse");
+        assert_eq!(joined.jid.to_string(), "120363000000000000@g.us");
+        assert!(!joined.pending_approval);
+
+        let pending = parse_join_response(&pending).expect("approval response should parse");
+        assert!(pending.pending_approval);
+
+        assert!(parse_join_response(&NodeBuilder::new("iq").build()).is_err());
+    }
+
+    #[test]
+    fn test_group_setting_from_action() {
+        assert_eq!(
+            GroupSetting::from_action("announcement"),
+            Some(GroupSetting::Announce(true))
+        );
+        assert_eq!(
+            GroupSetting::from_action("not_announcement"),
+            Some(GroupSetting::Announce(false))
+        );
+        assert_eq!(
+            GroupSetting::from_action("locked"),
+            Some(GroupSetting::Locked(true))
+        );
+        assert_eq!(
+            GroupSetting::from_action("unlocked"),
+            Some(GroupSetting::Locked(false))
+        );
+        assert_eq!(GroupSetting::from_action("ephemeral"), None);
+    }
+
+    #[test]
+    fn test_group_setting_nodes() {
+        assert_eq!(GroupSetting::Announce(true).node().tag, "announcement");
+        assert_eq!(GroupSetting::Announce(false).node().tag, "not_announcement");
+        assert_eq!(GroupSetting::Locked(true).node().tag, "locked");
+        assert_eq!(GroupSetting::Locked(false).node().tag, "unlocked");
+        assert_eq!(GroupSetting::Ephemeral(0).node().tag, "not_ephemeral");
+
+        let ephemeral = GroupSetting::Ephemeral(86_400).node();
+        assert_eq!(ephemeral.tag, "ephemeral");
+        assert_eq!(
+            ephemeral.attrs().optional_string("expiration"),
+            Some("86400")
+        );
+
+        let add_mode = GroupSetting::MemberAddMode(MemberAddMode::AdminAdd).node();
+        assert_eq!(add_mode.tag, "member_add_mode");
+        assert!(matches!(add_mode.content, Some(NodeContent::String(ref s)) if s == "admin_add"));
+    }
+
+    #[test]
+    fn test_member_add_mode_parse() {
+        assert_eq!(
+            MemberAddMode::parse("admin_add"),
+            Some(MemberAddMode::AdminAdd)
+        );
+        assert_eq!(
+            MemberAddMode::parse("all_member_add"),
+            Some(MemberAddMode::AllMemberAdd)
+        );
+        assert_eq!(MemberAddMode::parse("everyone"), None);
+    }
+
+    #[test]
+    fn test_parse_group_jid() {
+        assert_eq!(
+            parse_group_jid("120363000000000000").map(|jid| jid.to_string()),
+            Some("120363000000000000@g.us".to_string())
+        );
+        assert_eq!(
+            parse_group_jid(" 120363000000000000@g.us ").map(|jid| jid.to_string()),
+            Some("120363000000000000@g.us".to_string())
+        );
+        assert_eq!(parse_group_jid("5511999999999@s.whatsapp.net"), None);
+        assert_eq!(parse_group_jid(""), None);
+    }
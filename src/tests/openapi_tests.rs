@@ -0,0 +1,158 @@
+    use super::*;
+    use std::collections::{BTreeMap, BTreeSet};
+
+    const METHODS: &[&str] = &["get", "post", "put", "patch", "delete"];
+    const ROUTER: &str = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/server/mod.rs"));
+    const ROUTES: &str = include_str!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/src/server/routes/mod.rs"
+    ));
+
+    /// `(path, method)` pairs registered with `.route(...)` in `source`,
+    /// with `:param` segments written as OpenAPI `{param}`.
+    fn registered_routes(source: &str) -> BTreeSet<(String, String)> {
+        let mut routes = BTreeSet::new();
+        let mut rest = source;
+        while let Some(start) = rest.find(".route(") {
+            let call = &rest[start + ".route(".len()..];
+            let mut depth = 1;
+            let end = call
+                .char_indices()
+                .find(|&(_, c)| {
+                    match c {
+                        '(' => depth += 1,
+                        ')' => depth -= 1,
+                        _ => {}
+                    }
+                    depth == 0
+                })
+                .map_or(call.len(), |(i, _)| i);
+            let call = &call[..end];
+            rest = &rest[start + ".route(".len() + end..];
+
+            let Some(path) = call.split('"').nth(1) else {
+                continue;
+            };
+            let path = path
+                .split('/')
+                .map(|segment| match segment.strip_prefix(':') {
+                    Some(param) => format!("{{{param}}}"),
+                    None => segment.to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join("/");
+            for method in METHODS {
+                let needle = format!("{method}(");
+                let called = call.match_indices(&needle).any(|(i, _)| {
+                    call[..i]
+                        .chars()
+                        .next_back()
+                        .is_none_or(|c| !(c.is_alphanumeric() || c == '_' || c == ':'))
+                });
+                if called {
+                    routes.insert((path.clone(), method.to_string()));
+                }
+            }
+        }
+        routes
+    }
+
+    fn documented_routes(document: &Value) -> BTreeMap<String, BTreeSet<String>> {
+        document["paths"]
+            .as_object()
+            .unwrap()
+            .iter()
+            .map(|(path, item)| {
+                let methods = item
+                    .as_object()
+                    .unwrap()
+                    .keys()
+                    .filter(|key| METHODS.contains(&key.as_str()))
+                    .cloned()
+                    .collect();
+                (path.clone(), methods)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn parses_route_registrations() {
+        let source = r#"
+            .route("/sessions/:id", get(handlers::get).delete(handlers::delete))
+            .route("/upload", post(handlers::upload).layer(DefaultBodyLimit::disable()))
+        "#;
+        let routes = registered_routes(source);
+        assert_eq!(
+            routes.into_iter().collect::<Vec<_>>(),
+            vec![
+                ("/sessions/{id}".to_string(), "delete".to_string()),
+                ("/sessions/{id}".to_string(), "get".to_string()),
+                ("/upload".to_string(), "post".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn every_route_is_documented() {
+        let source = [ROUTER, ROUTES].concat();
+        let documented = documented_routes(&openapi_document());
+        let missing: Vec<_> = registered_routes(&source)
+            .into_iter()
+            .filter(|(path, method)| !documented.get(path).is_some_and(|m| m.contains(method)))
+            .collect();
+        assert!(
+            missing.is_empty(),
+            "routes missing from openapi.json: {missing:?}"
+        );
+    }
+
+    #[test]
+    fn every_documented_route_exists() {
+        let source = [ROUTER, ROUTES].concat();
+        let registered = registered_routes(&source);
+        let stale: Vec<_> = documented_routes(&openapi_document())
+            .into_iter()
+            .flat_map(|(path, methods)| methods.into_iter().map(move |m| (path.clone(), m)))
+            .filter(|route| !registered.contains(route))
+            .collect();
+        assert!(
+            stale.is_empty(),
+            "openapi.json documents unknown routes: {stale:?}"
+        );
+    }
+
+    #[test]
+    fn errors_and_auth_are_described() {
+        let document = openapi_document();
+        assert_eq!(
+            document["components"]["schemas"]["ErrorResponse"]["required"],
+            serde_json::json!(["error"])
+        );
+        let schemes = document["components"]["securitySchemes"]
+            .as_object()
+            .unwrap();
+        for scheme in document["security"].as_array().unwrap() {
+            for name in scheme.as_object().unwrap().keys() {
+                assert!(schemes.contains_key(name), "{name}");
+            }
+        }
+        assert_eq!(
+            document["paths"]["/healthz"]["get"]["security"],
+            serde_json::json!([])
+        );
+        assert_eq!(
+            document["paths"]["/auth/login"]["post"]["security"],
+            serde_json::json!([])
+        );
+
+        let mut ids = BTreeSet::new();
+        for item in document["paths"].as_object().unwrap().values() {
+            for (method, operation) in item.as_object().unwrap() {
+                if METHODS.contains(&method.as_str())
+                    && let Some(id) = operation["operationId"].as_str()
+                {
+                    assert!(ids.insert(id.to_string()), "duplicated operationId {id}");
+                }
+            }
+        }
+    }
@@ -0,0 +1,28 @@
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn path_segments_are_escaped() {
+        assert_eq!(segment("loja"), "loja");
+        assert_eq!(segment("a/b?c#d%e"), "a%2Fb%3Fc%23d%25e");
+    }
+
+    #[test]
+    fn api_errors_carry_the_error_field() {
+        let error = SdkError::from_response(
+            StatusCode::NOT_FOUND,
+            json!({"error": "instance_not_found"}),
+        );
+        assert_eq!(error.api_error(), Some("instance_not_found"));
+        assert_eq!(error.to_string(), "404 Not Found: instance_not_found");
+
+        let error = SdkError::from_response(StatusCode::BAD_GATEWAY, Value::Null);
+        assert_eq!(error.api_error(), Some("Bad Gateway"));
+    }
+
+    #[test]
+    fn base_url_drops_the_trailing_slash() {
+        let api = ApiClient::new("http://localhost:8080/api/v1/").with_token("secret");
+        assert_eq!(api.base_url, "http://localhost:8080/api/v1");
+        assert_eq!(api.token.as_deref(), Some("secret"));
+    }
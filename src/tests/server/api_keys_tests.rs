@@ -0,0 +1,196 @@
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn parses_roles() {
+        assert_eq!(KeyRole::parse("Admin"), Some(KeyRole::Admin));
+        assert_eq!(KeyRole::parse("write"), Some(KeyRole::Write));
+        assert_eq!(KeyRole::parse("read-only"), Some(KeyRole::Read));
+        assert_eq!(KeyRole::parse("owner"), None);
+        assert!(KeyRole::Read < KeyRole::Write && KeyRole::Write < KeyRole::Admin);
+    }
+
+    #[test]
+    fn maps_requests_to_roles() {
+        assert_eq!(
+            KeyRole::required_for(&Method::GET, "/sessions"),
+            KeyRole::Read
+        );
+        assert_eq!(
+            KeyRole::required_for(&Method::HEAD, "/sessions"),
+            KeyRole::Read
+        );
+        assert_eq!(
+            KeyRole::required_for(&Method::POST, "/sendMessage"),
+            KeyRole::Write
+        );
+        assert_eq!(
+            KeyRole::required_for(&Method::DELETE, "/sessions/sales"),
+            KeyRole::Admin
+        );
+        assert_eq!(
+            KeyRole::required_for(&Method::DELETE, "/instance/maintenance/sales"),
+            KeyRole::Write
+        );
+        assert_eq!(
+            KeyRole::required_for(&Method::POST, "/graphql"),
+            KeyRole::Read
+        );
+        assert_eq!(KeyRole::required_for(&Method::GET, "/keys"), KeyRole::Admin);
+        assert_eq!(
+            KeyRole::required_for(&Method::PATCH, "/manager/config"),
+            KeyRole::Admin
+        );
+    }
+
+    #[test]
+    fn gets_that_act_are_not_reads() {
+        assert_eq!(
+            KeyRole::required_for(&Method::GET, "/instance/delete/sales"),
+            KeyRole::Admin
+        );
+        assert_eq!(
+            KeyRole::required_for(&Method::GET, "/instance/connect/sales"),
+            KeyRole::Write
+        );
+        assert_eq!(
+            KeyRole::required_for(&Method::GET, "/group/acceptInviteCode/sales"),
+            KeyRole::Write
+        );
+        assert_eq!(
+            KeyRole::required_for(&Method::GET, "/instance/connectionState/sales"),
+            KeyRole::Read
+        );
+        assert!(changes_state(&Method::GET, "/instance/delete/sales"));
+        assert!(changes_state(&Method::HEAD, "/instance/connect/sales"));
+        assert!(!changes_state(&Method::GET, "/group/inviteInfo/sales"));
+    }
+
+    #[test]
+    fn read_keys_get_403_on_gets_that_act() {
+        let key = ResolvedKey {
+            id: Uuid::new_v4(),
+            workspace: None,
+            role: KeyRole::Read,
+            allowed_ips: Vec::new(),
+        };
+        for path in [
+            "/instance/delete/sales",
+            "/group/acceptInviteCode/sales",
+            "/instance/connect/sales",
+        ] {
+            let response = key.forbidden(&Method::GET, path).unwrap();
+            assert_eq!(response.status(), StatusCode::FORBIDDEN, "{path}");
+        }
+        assert!(key.forbidden(&Method::GET, "/instance/fetchInstances").is_none());
+
+        let write = ResolvedKey {
+            role: KeyRole::Write,
+            ..key.clone()
+        };
+        assert!(write.forbidden(&Method::GET, "/instance/connect/sales").is_none());
+        assert!(write.forbidden(&Method::GET, "/instance/delete/sales").is_some());
+        assert!(write.forbidden(&Method::DELETE, "/instance/delete/sales").is_some());
+
+        let workspace = ResolvedKey {
+            workspace: Some(Uuid::new_v4()),
+            ..write
+        };
+        assert!(workspace.forbidden(&Method::DELETE, "/instance/delete/sales").is_none());
+        let reader = ResolvedKey {
+            role: KeyRole::Read,
+            ..workspace
+        };
+        assert!(reader.forbidden(&Method::GET, "/instance/delete/sales").is_some());
+    }
+
+    #[test]
+    fn matches_addresses_and_ranges() {
+        let ip = |s: &str| s.parse::<IpAddr>().unwrap();
+
+        let single = IpRule::parse("10.0.0.7").unwrap();
+        assert!(single.matches(ip("10.0.0.7")));
+        assert!(!single.matches(ip("10.0.0.8")));
+
+        let range = IpRule::parse("192.168.0.0/16").unwrap();
+        assert!(range.matches(ip("192.168.42.1")));
+        assert!(!range.matches(ip("192.169.0.1")));
+        // IPv4 peers of a dual-stack listener arrive mapped.
+        assert!(range.matches(ip("::ffff:192.168.1.1")));
+
+        let v6 = IpRule::parse("2001:db8::/32").unwrap();
+        assert!(v6.matches(ip("2001:db8:1::1")));
+        assert!(!v6.matches(ip("2001:db9::1")));
+        assert!(!v6.matches(ip("10.0.0.7")));
+
+        assert!(IpRule::parse("0.0.0.0/0").unwrap().matches(ip("8.8.8.8")));
+        assert_eq!(IpRule::parse("10.0.0.0/33"), None);
+        assert_eq!(IpRule::parse("example.com"), None);
+    }
+
+    #[test]
+    fn workspace_keys_are_never_admin() {
+        let workspace = Uuid::new_v4();
+        let key = ResolvedKey::from_row(&json!({"value": {
+            "id": Uuid::new_v4().to_string(),
+            "workspace_id": workspace.to_string(),
+            "role": "admin",
+            "allowed_ips": null,
+        }}))
+        .unwrap();
+        assert_eq!(key.workspace, Some(workspace));
+        assert_eq!(key.role, KeyRole::Write);
+
+        let global = ResolvedKey::from_row(&json!({
+            "id": Uuid::new_v4().to_string(),
+            "workspace_id": null,
+            "role": "admin",
+            "allowed_ips": ["10.0.0.0/8", "bogus"],
+        }))
+        .unwrap();
+        assert_eq!(global.role, KeyRole::Admin);
+        assert_eq!(global.allowed_ips.len(), 1);
+
+        let legacy = json!({"id": Uuid::new_v4().to_string(), "role": null});
+        assert_eq!(ResolvedKey::from_row(&legacy), None);
+    }
+
+    #[test]
+    fn allowlist_needs_a_known_address() {
+        let key = ResolvedKey {
+            id: Uuid::new_v4(),
+            workspace: None,
+            role: KeyRole::Read,
+            allowed_ips: vec![IpRule::parse("10.0.0.0/8").unwrap()],
+        };
+        assert!(key.allows_ip(Some("10.1.2.3".parse().unwrap())));
+        assert!(!key.allows_ip(Some("11.1.2.3".parse().unwrap())));
+        assert!(!key.allows_ip(None));
+
+        let open = ResolvedKey {
+            allowed_ips: Vec::new(),
+            ..key
+        };
+        assert!(open.allows_ip(None));
+    }
+
+    #[test]
+    fn forwarded_for_is_only_trusted_when_enabled() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-forwarded-for", "203.0.113.9, 10.0.0.1".parse().unwrap());
+        let peer = Some(SocketAddr::from(([10, 0, 0, 1], 4000)));
+
+        assert_eq!(
+            client_ip(&headers, peer, false),
+            Some("10.0.0.1".parse().unwrap())
+        );
+        assert_eq!(
+            client_ip(&headers, peer, true),
+            Some("203.0.113.9".parse().unwrap())
+        );
+        assert_eq!(
+            client_ip(&HeaderMap::new(), peer, true),
+            Some("10.0.0.1".parse().unwrap())
+        );
+        assert_eq!(client_ip(&HeaderMap::new(), None, false), None);
+    }
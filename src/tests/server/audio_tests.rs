@@ -0,0 +1,66 @@
+    use super::*;
+
+    #[tokio::test]
+    async fn missing_binary_is_reported_as_io_error() {
+        let result = run_ffmpeg(
+            "/nonexistent/ffmpeg-binary".to_string(),
+            Bytes::from_static(&[0u8; 16]),
+            &["-f", "mp3"],
+        )
+        .await;
+
+        assert!(matches!(result, Err(AudioError::Io(_))));
+    }
+
+    #[tokio::test]
+    async fn failing_binary_reports_exit_status() {
+        let result = run_ffmpeg(
+            "false".to_string(),
+            Bytes::from_static(&[0u8; 16]),
+            &["-f", "mp3"],
+        )
+        .await;
+
+        assert!(matches!(result, Err(AudioError::Ffmpeg { .. })));
+    }
+
+    #[test]
+    fn waveform_is_normalised_to_loudest_bucket() {
+        let mut samples = vec![0i16; 6400];
+        for sample in samples.iter_mut().skip(3200) {
+            *sample = 1000;
+        }
+        samples[6399] = -2000;
+
+        let waveform = waveform_from_pcm(&samples);
+        assert_eq!(waveform.len(), WAVEFORM_SAMPLES);
+        assert_eq!(waveform[0], 0);
+        assert_eq!(waveform.iter().copied().max(), Some(100));
+        assert!(waveform[40] > 90);
+    }
+
+    #[test]
+    fn waveform_of_silence_is_flat() {
+        assert_eq!(waveform_from_pcm(&[]), vec![0; WAVEFORM_SAMPLES]);
+        assert_eq!(waveform_from_pcm(&[0; 100]), vec![0; WAVEFORM_SAMPLES]);
+    }
+
+    #[test]
+    fn short_inputs_pad_waveform() {
+        let waveform = waveform_from_pcm(&[100, 200, 300]);
+        assert_eq!(waveform.len(), WAVEFORM_SAMPLES);
+        assert_eq!(&waveform[..3], &[33, 66, 100]);
+        assert!(waveform[3..].iter().all(|v| *v == 0));
+    }
+
+    #[test]
+    fn duration_rounds_up() {
+        assert_eq!(duration_seconds(8000, 8000), 1);
+        assert_eq!(duration_seconds(8001, 8000), 2);
+        assert_eq!(duration_seconds(0, 8000), 0);
+    }
+
+    #[test]
+    fn pcm_bytes_are_little_endian() {
+        assert_eq!(pcm_samples(&[0x01, 0x00, 0xff, 0xff, 0x7f]), vec![1, -1]);
+    }
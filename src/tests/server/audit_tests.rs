@@ -0,0 +1,57 @@
+    use super::*;
+    use axum::http::{HeaderMap, Method};
+
+    #[test]
+    fn instance_is_taken_from_named_route_params() {
+        assert_eq!(
+            instance_from_path("/:session/messages/send", "/default/messages/send"),
+            Some("default".to_string())
+        );
+        assert_eq!(
+            instance_from_path("/message/:operation/:instance_name", "/message/sendText/sales"),
+            Some("sales".to_string())
+        );
+        assert_eq!(
+            instance_from_path("/instance/qrcode/:file", "/instance/qrcode/sales.svg"),
+            Some("sales".to_string())
+        );
+        assert_eq!(instance_from_path("/instance/qrcode/:file", "/instance/qrcode/sales.gif"), None);
+        assert_eq!(instance_from_path("/manager/config", "/manager/config"), None);
+    }
+
+    #[test]
+    fn gets_that_act_are_audited() {
+        assert!(changes_state(&Method::GET, "/instance/delete/sales"));
+        assert!(changes_state(&Method::GET, "/instance/connect/sales"));
+        assert!(changes_state(&Method::GET, "/group/acceptInviteCode/sales"));
+        assert!(changes_state(&Method::POST, "/message/sendText/sales"));
+        assert!(!changes_state(&Method::GET, "/instance/fetchInstances"));
+        assert!(changes_state(&Method::HEAD, "/instance/delete/sales"));
+    }
+
+    #[test]
+    fn actor_fingerprints_keys_without_storing_them() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(actor(&headers), "anonymous");
+
+        headers.insert(header::COOKIE, "theme=dark; chatwarp_auth=abc".parse().unwrap());
+        assert_eq!(actor(&headers), "session");
+
+        headers.insert(header::AUTHORIZATION, "Bearer secret".parse().unwrap());
+        let bearer = actor(&headers);
+        assert!(bearer.starts_with("key:"));
+        assert_eq!(bearer.len(), "key:".len() + 8);
+        assert!(!bearer.contains("secret"));
+
+        let mut headers = HeaderMap::new();
+        headers.insert("x-chatwarp-password", "secret".parse().unwrap());
+        assert_eq!(actor(&headers), bearer);
+    }
+
+    #[test]
+    fn request_hash_is_sha256_hex() {
+        assert_eq!(
+            request_hash(b""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
@@ -0,0 +1,221 @@
+    use super::*;
+    use crate::types::message::MessageSource;
+    use chrono::TimeZone;
+    use waproto::whatsapp as wa;
+    use warp_core_binary::jid::Jid;
+
+    fn inbound(group: bool, text: Option<&str>) -> InboundMessage {
+        InboundMessage {
+            info: MessageInfo {
+                id: "3EB0A".to_string(),
+                source: MessageSource {
+                    chat: if group {
+                        Jid::group("120363000000000001")
+                    } else {
+                        Jid::pn("5511999990000")
+                    },
+                    sender: Jid::pn_device("5511999990000", 3),
+                    sender_alt: Some(Jid::lid("123456789")),
+                    is_group: group,
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: text.map(str::to_string),
+        }
+    }
+
+    fn rule(value: Value) -> AutoRule {
+        serde_json::from_value(value).unwrap()
+    }
+
+    /// 2026-04-06 is a Monday.
+    fn monday(hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 4, 6, hour, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn wildcards_match_any_run() {
+        assert!(wildcard_match("5511*", "5511999990000"));
+        assert!(wildcard_match("*@lid", "123456789@LID"));
+        assert!(wildcard_match("55*99*0000", "5511999990000"));
+        assert!(wildcard_match("*", ""));
+        assert!(!wildcard_match("5521*", "5511999990000"));
+        assert!(!wildcard_match("5511", "5511999990000"));
+    }
+
+    #[test]
+    fn parses_rules_with_defaults() {
+        let rules = rules_from_body(&json!({"rules": [{
+            "id": "price",
+            "conditions": {"contains": "preço", "chat": "direct"},
+            "actions": [
+                {"type": "markRead"},
+                {"type": "reply", "text": "Tabela em anexo"},
+                {"type": "label", "labelId": "leads"}
+            ]
+        }]}))
+        .unwrap();
+        let price = &rules[0];
+        assert!(price.enabled);
+        assert!(!price.stop);
+        assert_eq!(price.conditions.contains, vec!["preço".to_string()]);
+        assert_eq!(price.conditions.chat, ChatKind::Direct);
+        assert_eq!(
+            price.actions[2],
+            RuleAction::Label {
+                label_id: "leads".to_string()
+            }
+        );
+        assert_eq!(
+            serde_json::to_value(&price.actions[0]).unwrap(),
+            json!({"type": "markRead"})
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_rules() {
+        let body = |rule: Value| json!({"rules": [rule]});
+        assert!(matches!(
+            rules_from_body(&json!({})),
+            Err(AutoRuleError::InvalidBody(_))
+        ));
+        assert!(matches!(
+            rules_from_body(&body(json!({"id": "a", "actions": [{"type": "wave"}]}))),
+            Err(AutoRuleError::InvalidBody(_))
+        ));
+        assert_eq!(
+            rules_from_body(&body(
+                json!({"id": "a b", "actions": [{"type": "markRead"}]})
+            )),
+            Err(AutoRuleError::InvalidId("a b".to_string()))
+        );
+        assert_eq!(
+            rules_from_body(&body(json!({"id": "a", "actions": []}))),
+            Err(AutoRuleError::NoActions("a".to_string()))
+        );
+        assert!(matches!(
+            rules_from_body(&body(json!({
+                "id": "a",
+                "actions": [{"type": "webhook", "url": "ftp://x"}]
+            }))),
+            Err(AutoRuleError::InvalidAction { .. })
+        ));
+        assert!(matches!(
+            rules_from_body(&body(json!({
+                "id": "a",
+                "conditions": {"businessHours": {"start": "9:00", "end": "18:00"}},
+                "actions": [{"type": "markRead"}]
+            }))),
+            Err(AutoRuleError::InvalidCondition { .. })
+        ));
+        let same = json!({"id": "a", "actions": [{"type": "markRead"}]});
+        assert_eq!(
+            rules_from_body(&json!({"rules": [same.clone(), same]})),
+            Err(AutoRuleError::DuplicateId("a".to_string()))
+        );
+    }
+
+    #[test]
+    fn conditions_must_all_hold() {
+        let price = rule(json!({
+            "id": "price",
+            "conditions": {"sender": "5511*", "contains": ["preço", "price"], "chat": "direct"},
+            "actions": [{"type": "markRead"}]
+        }));
+        let now = monday(12, 0);
+        assert!(price.matches(&inbound(false, Some("Qual o PREÇO?")), now));
+        assert!(!price.matches(&inbound(false, Some("oi")), now));
+        assert!(!price.matches(&inbound(false, None), now));
+        assert!(!price.matches(&inbound(true, Some("price")), now));
+
+        let by_lid = rule(json!({
+            "id": "lid",
+            "conditions": {"sender": "123456789@lid"},
+            "actions": [{"type": "markRead"}]
+        }));
+        assert!(by_lid.matches(&inbound(true, None), now));
+
+        let disabled = rule(json!({"id": "off", "enabled": false, "actions": [{"type": "markRead"}]}));
+        assert!(!disabled.matches(&inbound(false, None), now));
+    }
+
+    #[test]
+    fn business_hours_use_the_offset_and_cross_midnight() {
+        let hours = |value: Value| serde_json::from_value::<BusinessHours>(value).unwrap();
+        let office = hours(json!({
+            "days": [1, 2, 3, 4, 5], "start": "09:00", "end": "18:00", "utcOffsetMinutes": -180
+        }));
+        assert!(office.matches(monday(12, 0)));
+        assert!(office.matches(monday(20, 59)));
+        assert!(!office.matches(monday(21, 0)));
+        assert!(!office.matches(monday(11, 59)));
+        // Sunday in São Paulo.
+        assert!(!office.matches(Utc.with_ymd_and_hms(2026, 4, 5, 15, 0, 0).unwrap()));
+
+        let away = hours(json!({"start": "09:00", "end": "18:00", "outside": true}));
+        assert!(away.matches(monday(8, 0)));
+        assert!(!away.matches(monday(9, 0)));
+
+        let night = hours(json!({"start": "22:00", "end": "06:00"}));
+        assert!(night.matches(monday(23, 30)));
+        assert!(night.matches(monday(5, 59)));
+        assert!(!night.matches(monday(6, 0)));
+    }
+
+    #[test]
+    fn stop_ends_the_check() {
+        let rules = rules_from_body(&json!({"rules": [
+            {"id": "groups", "conditions": {"chat": "group"}, "actions": [{"type": "markRead"}]},
+            {"id": "read", "actions": [{"type": "markRead"}], "stop": true},
+            {"id": "reply", "actions": [{"type": "reply", "text": "oi"}]}
+        ]}))
+        .unwrap();
+        let ids = |message: &InboundMessage| -> Vec<String> {
+            matching(&rules, message, monday(12, 0))
+                .iter()
+                .map(|rule| rule.id.clone())
+                .collect()
+        };
+        assert_eq!(ids(&inbound(false, None)), vec!["read"]);
+        assert_eq!(ids(&inbound(true, None)), vec!["groups", "read"]);
+    }
+
+    #[test]
+    fn only_messages_from_others_are_checked() {
+        let text = wa::Message {
+            conversation: Some("oi".to_string()),
+            ..Default::default()
+        };
+        let received = inbound(false, None).info;
+        let message =
+            InboundMessage::from_event(&Event::Message(Box::new(text.clone()), received.clone()))
+                .unwrap();
+        assert_eq!(message.text.as_deref(), Some("oi"));
+
+        let mut own = received.clone();
+        own.source.is_from_me = true;
+        assert!(InboundMessage::from_event(&Event::Message(Box::new(text), own)).is_none());
+
+        let reaction = wa::Message {
+            reaction_message: Some(wa::message::ReactionMessage::default()),
+            ..Default::default()
+        };
+        assert!(InboundMessage::from_event(&Event::Message(Box::new(reaction), received)).is_none());
+    }
+
+    #[test]
+    fn webhook_payload_carries_the_message() {
+        let payload = inbound(true, Some("oi")).webhook_payload("sales", "leads");
+        assert_eq!(payload["event"], "AUTO_RULE_MATCHED");
+        assert_eq!(payload["rule"], "leads");
+        assert_eq!(
+            payload["data"]["key"]["remoteJid"],
+            "120363000000000001@g.us"
+        );
+        assert_eq!(
+            payload["data"]["key"]["participant"],
+            "5511999990000@s.whatsapp.net"
+        );
+        assert_eq!(payload["data"]["text"], "oi");
+    }
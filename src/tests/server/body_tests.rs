@@ -0,0 +1,13 @@
+    use super::*;
+
+    #[tokio::test]
+    async fn bodies_within_the_limit_are_buffered() {
+        let bytes = buffer(Body::from("{\"number\":\"1\"}"), 64).await.unwrap();
+        assert_eq!(&bytes[..], b"{\"number\":\"1\"}");
+    }
+
+    #[tokio::test]
+    async fn bodies_over_the_limit_answer_413() {
+        let response = buffer(Body::from(vec![b'x'; 65]), 64).await.unwrap_err();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
@@ -0,0 +1,117 @@
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn events_drop_what_they_change() {
+        assert_eq!(
+            stale_on("GROUP_PARTICIPANTS_UPDATE"),
+            [Stale::Instance(CacheSpace::Groups)]
+        );
+        assert_eq!(
+            stale_on("CONNECTION_UPDATE"),
+            [Stale::Space(CacheSpace::Instances)]
+        );
+        assert!(stale_on("LOGOUT_INSTANCE").contains(&Stale::Instance(CacheSpace::ProfilePictures)));
+        assert!(stale_on("MESSAGES_UPSERT").is_empty());
+    }
+
+    #[tokio::test]
+    async fn entries_are_dropped_per_scope_and_space() {
+        let cache = HotCache::default();
+        let groups = vec![json!({"id": "120363000000000001@g.us"})];
+        cache
+            .insert(CacheSpace::Groups, "main", "summary", &groups, None)
+            .await;
+        cache
+            .insert(CacheSpace::Groups, "sales", "summary", &groups, None)
+            .await;
+        cache
+            .insert(
+                CacheSpace::Instances,
+                "all",
+                "{}",
+                &(vec![json!({})], 1u64),
+                None,
+            )
+            .await;
+        assert_eq!(
+            cache
+                .get::<Vec<Value>>(CacheSpace::Groups, "main", "summary")
+                .await,
+            Some(groups.clone())
+        );
+
+        cache.on_event(Some("main"), "GROUPS_UPSERT").await;
+        assert!(
+            cache
+                .get::<Vec<Value>>(CacheSpace::Groups, "main", "summary")
+                .await
+                .is_none()
+        );
+        assert!(
+            cache
+                .get::<Vec<Value>>(CacheSpace::Groups, "sales", "summary")
+                .await
+                .is_some()
+        );
+
+        cache.invalidate_space(CacheSpace::Instances).await;
+        assert!(
+            cache
+                .get::<(Vec<Value>, u64)>(CacheSpace::Instances, "all", "{}")
+                .await
+                .is_none()
+        );
+    }
+
+    #[tokio::test]
+    async fn disabled_local_tier_stores_nothing() {
+        let cache = HotCache::new(CacheConfig {
+            local_enabled: false,
+            ..Default::default()
+        });
+        cache
+            .insert(CacheSpace::Groups, "main", "list", &json!([]), None)
+            .await;
+        assert!(
+            cache
+                .get::<Value>(CacheSpace::Groups, "main", "list")
+                .await
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn query_key_ignores_parameter_order() {
+        let a: HashMap<String, String> = [("limit", "10"), ("tags", "vip")]
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        let b: HashMap<String, String> = [("tags", "vip"), ("limit", "10")]
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        assert_eq!(query_key(&a), query_key(&b));
+        assert_eq!(query_key(&HashMap::new()), "{}");
+    }
+
+    #[test]
+    fn redis_keys_are_namespaced() {
+        assert_eq!(
+            redis_key(
+                "chatwarp",
+                CacheSpace::ProfilePictures,
+                "main",
+                "a@s.whatsapp.net:image"
+            ),
+            "chatwarp:profile_pictures:main:a@s.whatsapp.net:image"
+        );
+        assert_eq!(
+            redis_index("chatwarp", CacheSpace::Groups, Some("main")),
+            "chatwarp:index:groups:main"
+        );
+        assert_eq!(
+            redis_index("chatwarp", CacheSpace::Groups, None),
+            "chatwarp:index:groups"
+        );
+    }
@@ -0,0 +1,105 @@
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn chat_comes_from_chat_or_number() {
+        let jid = chat_from_body(&json!({"chat": "5511999990000@c.us"})).unwrap();
+        assert_eq!(jid.to_string(), "5511999990000@s.whatsapp.net");
+        let jid = chat_from_body(&json!({"number": "+55 11 99999-0000"})).unwrap();
+        assert_eq!(jid.to_string(), "5511999990000@s.whatsapp.net");
+        assert_eq!(
+            chat_from_body(&json!({})),
+            Err(ChatSettingError::MissingChat)
+        );
+        assert!(matches!(
+            chat_from_body(&json!({"chat": "abc"})),
+            Err(ChatSettingError::InvalidChat(_))
+        ));
+    }
+
+    #[test]
+    fn archive_reads_flag_and_last_message() {
+        let body = json!({"archive": true, "lastMessage": {"messageTimestamp": "1700000000"}});
+        assert_eq!(
+            ChatAction::archive_from_body(&body),
+            Ok(ChatAction::Archive {
+                archive: true,
+                last_message_timestamp: Some(1_700_000_000),
+            })
+        );
+        assert_eq!(
+            ChatAction::archive_from_body(&json!({})),
+            Err(ChatSettingError::MissingFlag("archive"))
+        );
+    }
+
+    #[test]
+    fn mute_duration_becomes_end_timestamp() {
+        let now = 1_700_000_000_000;
+        assert_eq!(
+            ChatAction::mute_from_body(&json!({"mute": true, "duration": 3600}), now),
+            Ok(ChatAction::Mute(Some(now + 3_600_000)))
+        );
+        assert_eq!(
+            ChatAction::mute_from_body(&json!({"mute": true}), now),
+            Ok(ChatAction::Mute(Some(-1)))
+        );
+        assert_eq!(
+            ChatAction::mute_from_body(&json!({"mute": false, "duration": 3600}), now),
+            Ok(ChatAction::Mute(None))
+        );
+        assert_eq!(
+            ChatAction::mute_from_body(&json!({"mute": true, "duration": 0}), now),
+            Err(ChatSettingError::InvalidDuration)
+        );
+    }
+
+    #[test]
+    fn events_describe_the_change() {
+        let chat = "5511999990000@s.whatsapp.net";
+        let archive = ChatAction::Archive {
+            archive: true,
+            last_message_timestamp: None,
+        };
+        assert_eq!(
+            archive.event(chat),
+            json!({"id": chat, "archived": true, "pinned": false})
+        );
+        assert_eq!(
+            ChatAction::Pin(true).event(chat),
+            json!({"id": chat, "pinned": true})
+        );
+        assert_eq!(
+            ChatAction::Mute(None).event(chat),
+            json!({"id": chat, "muted": false, "muteEndTime": null})
+        );
+    }
+
+    #[test]
+    fn ephemeral_accepts_whatsapp_timers_on_direct_chats() {
+        let now = 1_700_000_000;
+        let body = json!({"number": "5511999990000", "expiration": 86400});
+        let action = ChatAction::ephemeral_from_body(&body, now).unwrap();
+        assert_eq!(
+            action,
+            ChatAction::Ephemeral {
+                expiration: 86_400,
+                timestamp: now,
+            }
+        );
+        assert_eq!(
+            action.event("5511999990000@s.whatsapp.net"),
+            json!({"id": "5511999990000@s.whatsapp.net", "ephemeralExpiration": 86400})
+        );
+        assert_eq!(
+            ChatAction::ephemeral_from_body(&json!({"chat": "5511999990000", "expiration": 3600}), now),
+            Err(ChatSettingError::InvalidExpiration)
+        );
+        assert_eq!(
+            ChatAction::ephemeral_from_body(
+                &json!({"chat": "120363000000000000@g.us", "expiration": 0}),
+                now
+            ),
+            Err(ChatSettingError::GroupChat)
+        );
+    }
@@ -0,0 +1,283 @@
+    use super::*;
+
+    #[test]
+    fn challenge_requires_matching_token() {
+        let config = MetaConfig {
+            verify_token: Some("t".to_string()),
+            ..MetaConfig::default()
+        };
+        let query = |token: &str| -> HashMap<String, String> {
+            [("hub.mode", "subscribe"), ("hub.verify_token", token), ("hub.challenge", "42")]
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect()
+        };
+        assert_eq!(verify_challenge(&config, &query("t")), Some("42".to_string()));
+        assert_eq!(verify_challenge(&config, &query("x")), None);
+        assert_eq!(verify_challenge(&MetaConfig::default(), &query("t")), None);
+    }
+
+    #[test]
+    fn checks_hub_signature() {
+        let body = br#"{"object":"whatsapp_business_account"}"#;
+        let mut mac = Hmac::<Sha256>::new_from_slice(b"app-secret").unwrap();
+        mac.update(body);
+        let header = format!("sha256={}", hex::encode(mac.finalize().into_bytes()));
+
+        assert!(verify_signature("app-secret", body, Some(&header)));
+        assert!(!verify_signature("other", body, Some(&header)));
+        assert!(!verify_signature("app-secret", body, None));
+    }
+
+    #[test]
+    fn webhooks_need_a_configured_secret_and_a_valid_signature() {
+        let body = br#"{"object":"whatsapp_business_account"}"#;
+        let mut mac = Hmac::<Sha256>::new_from_slice(b"app-secret").unwrap();
+        mac.update(body);
+        let mut signed = HeaderMap::new();
+        signed.insert(
+            SIGNATURE_HEADER,
+            format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+                .parse()
+                .unwrap(),
+        );
+
+        let status = |config: &MetaConfig, headers: &HeaderMap| {
+            signature_rejection(config, headers, body).map(|response| response.status())
+        };
+        let unset = MetaConfig::default();
+        assert_eq!(status(&unset, &signed), Some(StatusCode::SERVICE_UNAVAILABLE));
+        assert_eq!(status(&unset, &HeaderMap::new()), Some(StatusCode::SERVICE_UNAVAILABLE));
+
+        let config = MetaConfig {
+            app_secret: Some("app-secret".to_string()),
+            ..MetaConfig::default()
+        };
+        assert_eq!(status(&config, &signed), None);
+        assert_eq!(status(&config, &HeaderMap::new()), Some(StatusCode::UNAUTHORIZED));
+    }
+
+    #[test]
+    fn builds_graph_bodies() {
+        let text = outbound_message(
+            "text",
+            &json!({"chatId": "5511999990000@s.whatsapp.net", "text": "oi", "quoted": {"messageId": "wamid.1"}}),
+        )
+        .unwrap();
+        assert_eq!(text["to"], "5511999990000");
+        assert_eq!(text["text"]["body"], "oi");
+        assert_eq!(text["context"]["message_id"], "wamid.1");
+
+        let image = outbound_message(
+            "image",
+            &json!({"chatId": "1@s.whatsapp.net", "url": "https://x/y.jpg", "caption": "c"}),
+        )
+        .unwrap();
+        assert_eq!(image["image"], json!({"link": "https://x/y.jpg", "caption": "c"}));
+        assert!(outbound_message("image", &json!({"chatId": "1@s.whatsapp.net", "base64": "AAA"})).is_none());
+
+        let template = outbound_message(
+            "template",
+            &json!({
+                "chatId": "1@s.whatsapp.net",
+                "text": "Pedido pronto",
+                "footer": "Loja",
+                "buttons": [
+                    {"type": "reply", "text": "Ok", "id": "ok"},
+                    {"type": "url", "text": "Site", "url": "https://x"}
+                ]
+            }),
+        )
+        .unwrap();
+        assert_eq!(template["type"], "interactive");
+        assert_eq!(template["interactive"]["body"]["text"], "Pedido pronto\nSite: https://x");
+        assert_eq!(template["interactive"]["action"]["buttons"][0]["reply"]["id"], "ok");
+    }
+
+    #[test]
+    fn builds_interactive_lists() {
+        let list = outbound_message(
+            "list",
+            &json!({
+                "chatId": "1@s.whatsapp.net",
+                "text": "Escolha um horário",
+                "buttonText": "Horários",
+                "sections": [
+                    {"title": "Manhã", "rows": [{"title": "9h"}, {"title": "10h", "id": "h10"}]},
+                    {"rows": [{"title": "14h", "description": "Tarde"}]}
+                ]
+            }),
+        )
+        .unwrap();
+        let interactive = &list["interactive"];
+        assert_eq!(interactive["type"], "list");
+        assert_eq!(interactive["action"]["button"], "Horários");
+        let sections = &interactive["action"]["sections"];
+        assert_eq!(sections[0]["title"], "Manhã");
+        assert_eq!(sections[0]["rows"][0]["id"], "row-1");
+        assert_eq!(sections[0]["rows"][1]["id"], "h10");
+        assert_eq!(sections[1]["rows"][0]["id"], "row-3");
+        assert_eq!(sections[1]["rows"][0]["description"], "Tarde");
+
+        assert!(
+            outbound_message(
+                "list",
+                &json!({"chatId": "1@s.whatsapp.net", "text": "x", "sections": []}),
+            )
+            .is_none()
+        );
+    }
+
+    #[test]
+    fn normalizes_messages_and_statuses() {
+        let notification = json!({
+            "object": "whatsapp_business_account",
+            "entry": [{"changes": [{"value": {
+                "metadata": {"phone_number_id": "PN1"},
+                "contacts": [{"wa_id": "5511", "profile": {"name": "Ana"}}],
+                "messages": [{"from": "5511", "id": "wamid.2", "type": "text", "text": {"body": "olá"}}],
+                "statuses": [{"id": "wamid.1", "recipient_id": "5522", "status": "read"}]
+            }}]}]
+        });
+        let events = normalize(&notification);
+
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].phone_number_id, "PN1");
+        assert_eq!(events[0].event, "MESSAGES_UPSERT");
+        let item = &events[0].data["messages"][0];
+        assert_eq!(item["key"]["remoteJid"], "5511@s.whatsapp.net");
+        assert_eq!(item["key"]["senderName"], "Ana");
+        assert_eq!(item["message"], json!({"messageType": "conversation", "text": "olá"}));
+        assert_eq!(events[1].event, "MESSAGES_UPDATE");
+        assert_eq!(events[1].data["status"], "READ");
+    }
+
+    #[test]
+    fn template_message_builds_graph_body() {
+        let body = template_message(&json!({
+            "number": "+55 11 99999-0000",
+            "name": "order_update",
+            "language": {"code": "pt_BR"},
+            "components": [
+                {"type": "body", "parameters": [{"type": "text", "text": "Ana"}]},
+                {"type": "button", "sub_type": "url", "index": "0",
+                 "parameters": [{"type": "text", "text": "abc"}]}
+            ]
+        }))
+        .unwrap();
+        assert_eq!(body["type"], "template");
+        assert_eq!(body["to"], "5511999990000");
+        assert_eq!(body["template"]["name"], "order_update");
+        assert_eq!(body["template"]["language"], json!({"code": "pt_BR"}));
+        assert_eq!(body["template"]["components"].as_array().unwrap().len(), 2);
+
+        let plain =
+            template_message(&json!({"number": "5511", "name": "hello", "language": "en_US"})).unwrap();
+        assert_eq!(plain["template"]["language"]["code"], "en_US");
+        assert!(plain["template"].get("components").is_none());
+    }
+
+    #[test]
+    fn template_message_rejects_incomplete_requests() {
+        let base = json!({"number": "5511", "name": "hello", "language": "en"});
+        let with = |key: &str, value: Value| {
+            let mut payload = base.clone();
+            payload[key] = value;
+            template_message(&payload)
+        };
+        assert_eq!(
+            with("number", json!("")),
+            Err(TemplateRequestError::MissingNumber)
+        );
+        assert_eq!(
+            with("name", Value::Null),
+            Err(TemplateRequestError::MissingName)
+        );
+        assert_eq!(
+            with("language", json!({})),
+            Err(TemplateRequestError::MissingLanguage)
+        );
+        assert_eq!(
+            with("components", json!({})),
+            Err(TemplateRequestError::InvalidComponents)
+        );
+        assert_eq!(
+            with("components", json!([{"type": "footer"}])),
+            Err(TemplateRequestError::InvalidComponentType(
+                "footer".to_string()
+            ))
+        );
+        assert_eq!(
+            with("components", json!([{"type": "button", "sub_type": "url"}])),
+            Err(TemplateRequestError::InvalidButton)
+        );
+    }
+
+    #[test]
+    fn header_media_is_uploaded_and_replaced_by_id() {
+        let header = |image: Value| {
+            template_message(&json!({
+                "number": "5511", "name": "promo", "language": "pt_BR",
+                "components": [{"type": "header", "parameters": [{"type": "image", "image": image}]}]
+            }))
+            .unwrap()
+        };
+
+        let mut message = header(json!({"base64": "data:image/png;base64,AAAA"}));
+        assert_eq!(
+            header_media(&message),
+            Some(HeaderMedia::Base64 {
+                kind: "image".to_string(),
+                data: "data:image/png;base64,AAAA".to_string(),
+                mimetype: None,
+                filename: None,
+            })
+        );
+        set_header_media_id(&mut message, "MEDIA1");
+        assert_eq!(
+            message["template"]["components"][0]["parameters"][0]["image"],
+            json!({"id": "MEDIA1"})
+        );
+        assert_eq!(header_media(&message), None);
+
+        assert_eq!(
+            header_media(&header(json!({"mediaId": "u1"}))),
+            Some(HeaderMedia::Upload {
+                kind: "image".to_string(),
+                media_id: "u1".to_string(),
+            })
+        );
+        assert_eq!(
+            header_media(&header(json!({"link": "https://x/a.png"}))),
+            None
+        );
+    }
+
+    #[test]
+    fn graph_errors_map_to_envelopes() {
+        let error = |status: u16, code: i64| {
+            GraphError::from_response(
+                status,
+                &json!({"error": {"message": "boom", "code": code, "fbtrace_id": "T1"}}),
+            )
+        };
+
+        let missing = error(404, 132001);
+        assert_eq!(missing.code, Some(132001));
+        assert_eq!(missing.fbtrace_id.as_deref(), Some("T1"));
+        let (status, Json(body)) = missing.response();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"], "graph_invalid_request");
+        assert_eq!(body["graph"]["code"], 132001);
+
+        assert_eq!(
+            error(400, 131056).response().0,
+            StatusCode::TOO_MANY_REQUESTS
+        );
+        assert_eq!(error(401, 190).response().1["error"], "graph_auth_failed");
+        assert_eq!(error(500, 1).response().1["error"], "graph_api_error");
+
+        let wrapped: TemplateSendError = anyhow::Error::from(error(400, 100)).into();
+        assert!(matches!(wrapped, TemplateSendError::Graph(_)));
+        assert_eq!(wrapped.response().0, StatusCode::BAD_REQUEST);
+    }
@@ -0,0 +1,140 @@
+    use super::*;
+    use warp_core_binary::builder::NodeBuilder;
+
+    fn status_in(state: ConnectionState) -> ConnectionStatus {
+        let mut status = ConnectionStatus::default();
+        if state != ConnectionState::Disconnected {
+            status.transition(ConnectionState::Connecting, Reason::Started, Utc::now());
+            status.transition(state, Reason::Opened, Utc::now());
+        }
+        assert_eq!(status.state(), state);
+        status
+    }
+
+    #[test]
+    fn repeated_state_is_unchanged() {
+        let mut status = status_in(ConnectionState::Connected);
+        assert_eq!(
+            status.transition(ConnectionState::Connected, Reason::Opened, Utc::now()),
+            Transition::Unchanged(ConnectionState::Connected)
+        );
+        assert_eq!(status.history().count(), 2);
+    }
+
+    #[test]
+    fn valid_transition_reports_previous_state() {
+        let mut status = status_in(ConnectionState::QrPending);
+        let at = Utc::now();
+        assert_eq!(
+            status.transition(ConnectionState::Connected, Reason::Opened, at),
+            Transition::Changed {
+                previous: ConnectionState::QrPending,
+                current: ConnectionState::Connected,
+            }
+        );
+        assert_eq!(status.state(), ConnectionState::Connected);
+        assert_eq!(status.since(), at);
+        assert_eq!(
+            status.history().last(),
+            Some(&TransitionRecord {
+                from: ConnectionState::QrPending,
+                to: ConnectionState::Connected,
+                reason: Reason::Opened,
+                at,
+            })
+        );
+    }
+
+    #[test]
+    fn invalid_transition_keeps_current_state() {
+        let mut status = status_in(ConnectionState::Connected);
+        assert_eq!(
+            status.transition(ConnectionState::QrPending, Reason::QrIssued, Utc::now()),
+            Transition::Invalid {
+                current: ConnectionState::Connected,
+                requested: ConnectionState::QrPending,
+            }
+        );
+        assert_eq!(status.state(), ConnectionState::Connected);
+        assert_eq!(status.history().count(), 2);
+    }
+
+    #[test]
+    fn history_keeps_the_latest_transitions() {
+        let mut status = ConnectionStatus::default();
+        for _ in 0..HISTORY_LEN {
+            status.transition(ConnectionState::Connecting, Reason::Started, Utc::now());
+            status.transition(ConnectionState::Disconnected, Reason::ConnectionLost, Utc::now());
+        }
+        assert_eq!(status.history().count(), HISTORY_LEN);
+        assert_eq!(
+            status.history().last().map(|r| r.to),
+            Some(ConnectionState::Disconnected)
+        );
+    }
+
+    #[test]
+    fn errored_only_leads_back_to_disconnected_or_connecting() {
+        for state in [
+            ConnectionState::Disconnected,
+            ConnectionState::Connecting,
+            ConnectionState::QrPending,
+            ConnectionState::Connected,
+        ] {
+            assert!(state.can_transition_to(ConnectionState::Errored));
+        }
+        assert!(ConnectionState::Errored.can_transition_to(ConnectionState::Disconnected));
+        assert!(ConnectionState::Errored.can_transition_to(ConnectionState::Connecting));
+        assert!(!ConnectionState::Errored.can_transition_to(ConnectionState::Connected));
+        assert_eq!(ConnectionState::Errored.evolution_state(), "close");
+    }
+
+    #[test]
+    fn update_payload_carries_evolution_status_reason() {
+        let at = Utc::now();
+        let payload = update_payload(
+            ConnectionState::Connected,
+            ConnectionState::LoggedOut,
+            Reason::LoggedOut,
+            at,
+            serde_json::json!({"restarts": 1}),
+        );
+        assert_eq!(payload["state"], "close");
+        assert_eq!(payload["previousState"], "open");
+        assert_eq!(payload["connectionState"], "logged_out");
+        assert_eq!(payload["reason"], "loggedOut");
+        assert_eq!(payload["statusReason"], 401);
+        assert_eq!(payload["restarts"], 1);
+    }
+
+    #[test]
+    fn serializes_as_snake_case() {
+        assert_eq!(
+            serde_json::to_value(ConnectionState::QrPending).unwrap(),
+            serde_json::json!("qr_pending")
+        );
+        assert_eq!(ConnectionState::Disconnected.evolution_state(), "close");
+        assert_eq!(ConnectionState::PairingPending.evolution_state(), "connecting");
+    }
+
+    #[test]
+    fn last_error_is_reported_in_status_json() {
+        let mut status = status_in(ConnectionState::Connected);
+        assert!(status.to_json()["lastError"].is_null());
+
+        let node = NodeBuilder::new("stream:error")
+            .attr("code", "401")
+            .children([NodeBuilder::new("conflict")
+                .attr("type", "device_removed")
+                .build()])
+            .build();
+        let at = Utc::now();
+        status.record_error(LastError::from_stream_error(&StreamError::from_node(&node), at));
+
+        let json = status.to_json();
+        assert_eq!(json["lastError"]["reason"], "logged_out");
+        assert_eq!(json["lastError"]["code"], "401:device_removed");
+        assert_eq!(json["lastError"]["reconnecting"], false);
+        assert_eq!(json["lastError"]["requiresPairing"], true);
+        assert_eq!(status.last_error().map(|e| e.at), Some(at));
+    }
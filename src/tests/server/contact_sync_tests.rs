@@ -0,0 +1,109 @@
+    use super::*;
+
+    fn info(jid: &str, lid: Option<&str>, registered: bool) -> ContactInfo {
+        ContactInfo {
+            jid: jid.parse().unwrap(),
+            lid: lid.map(|lid| lid.parse().unwrap()),
+            is_registered: registered,
+            is_business: false,
+            status: None,
+            picture_id: None,
+        }
+    }
+
+    #[test]
+    fn csv_uses_the_phone_header() {
+        let csv = "name;Telefone;city\nAna;+55 (11) 99999-0000;SP\n\nBia;14155550100;NY\n";
+        assert_eq!(
+            numbers_from_csv(csv),
+            vec!["5511999990000".to_string(), "14155550100".to_string()]
+        );
+        assert_eq!(
+            numbers_from_csv("\"name\",\"phone\"\n\"Ana\",\"5511999990000\"\n"),
+            vec!["5511999990000".to_string()]
+        );
+    }
+
+    #[test]
+    fn csv_without_header_uses_the_first_number_column() {
+        let csv = "Ana,5511999990000\nBia,14155550100\nCai,\n";
+        assert_eq!(
+            numbers_from_csv(csv),
+            vec!["5511999990000".to_string(), "14155550100".to_string()]
+        );
+        assert!(numbers_from_csv("").is_empty());
+        assert!(numbers_from_csv("name,city\n").is_empty());
+    }
+
+    #[test]
+    fn numbers_and_csv_are_merged_without_duplicates() {
+        let body = json!({"numbers": ["+55 11 99999-0000", "5511999990000@s.whatsapp.net"]});
+        let csv = vec!["phone\n14155550100\n5511999990000\n".to_string()];
+        assert_eq!(
+            collect_numbers(&body, &csv).unwrap(),
+            vec!["5511999990000".to_string(), "14155550100".to_string()]
+        );
+        assert_eq!(
+            collect_numbers(&json!({}), &["phone\n14155550100".to_string()]).unwrap(),
+            vec!["14155550100".to_string()]
+        );
+    }
+
+    #[test]
+    fn rejects_empty_and_invalid_requests() {
+        assert!(matches!(
+            collect_numbers(&json!({}), &[]),
+            Err(ContactSyncError::Missing)
+        ));
+        assert!(matches!(
+            collect_numbers(&json!({"numbers": "5511999990000"}), &[]),
+            Err(ContactSyncError::NotStrings)
+        ));
+        assert!(matches!(
+            collect_numbers(&json!({"numbers": ["abc"]}), &[]),
+            Err(ContactSyncError::Invalid(_))
+        ));
+        let many: Vec<String> = (0..=MAX_CONTACTS).map(|n| n.to_string()).collect();
+        assert!(matches!(
+            collect_numbers(&json!({"numbers": many}), &[]),
+            Err(ContactSyncError::TooMany)
+        ));
+    }
+
+    #[test]
+    fn matches_answers_and_devices_to_numbers() {
+        let numbers = vec!["5511999990000".to_string(), "14155550100".to_string()];
+        let mut business = info(
+            "5511999990000@s.whatsapp.net",
+            Some("100000012345678@lid"),
+            true,
+        );
+        business.is_business = true;
+        business.status = Some("Open 9-18".to_string());
+        let devices: Vec<Jid> = [
+            "5511999990000:2@s.whatsapp.net",
+            "5511999990000@s.whatsapp.net",
+            "100000012345678:5@lid",
+            "14155550100:1@s.whatsapp.net",
+        ]
+        .iter()
+        .map(|jid| jid.parse().unwrap())
+        .collect();
+
+        let contacts = match_contacts(&numbers, &[business], &devices);
+        assert_eq!(
+            contacts[0],
+            SyncedContact {
+                number: "5511999990000".to_string(),
+                exists: true,
+                jid: "5511999990000@s.whatsapp.net".to_string(),
+                lid: Some("100000012345678@lid".to_string()),
+                is_business: true,
+                status: Some("Open 9-18".to_string()),
+                devices: vec![0, 2, 5],
+            }
+        );
+        assert!(!contacts[1].exists);
+        assert_eq!(contacts[1].jid, "14155550100@s.whatsapp.net");
+        assert!(contacts[1].devices.is_empty());
+    }
@@ -0,0 +1,25 @@
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn missing_ids_replay_everything() {
+        assert_eq!(ids_from_body(None), Ok(None));
+        assert_eq!(ids_from_body(Some(&json!({}))), Ok(None));
+        assert_eq!(ids_from_body(Some(&json!({"ids": null}))), Ok(None));
+    }
+
+    #[test]
+    fn parses_id_list() {
+        let id = Uuid::new_v4();
+        assert_eq!(
+            ids_from_body(Some(&json!({"ids": [id.to_string()]}))),
+            Ok(Some(vec![id]))
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_ids() {
+        assert!(ids_from_body(Some(&json!({"ids": "abc"}))).is_err());
+        assert!(ids_from_body(Some(&json!({"ids": ["not-a-uuid"]}))).is_err());
+        assert!(ids_from_body(Some(&json!({"ids": [1]}))).is_err());
+    }
@@ -0,0 +1,46 @@
+    use super::*;
+
+    #[tokio::test]
+    async fn only_the_first_copy_is_fresh() {
+        let dedup = InboundDedup::default();
+        let chat = "5511999990000@s.whatsapp.net";
+        assert!(dedup.claim_local("main", chat, "3EB0A").await);
+        assert!(!dedup.claim_local("main", chat, "3EB0A").await);
+        assert!(dedup.claim_local("main", chat, "3EB0B").await);
+        assert!(dedup.claim_local("sales", chat, "3EB0A").await);
+        assert!(
+            dedup
+                .claim_local("main", "120363000000000001@g.us", "3EB0A")
+                .await
+        );
+    }
+
+    #[tokio::test]
+    async fn keys_expire_after_the_ttl() {
+        let dedup = InboundDedup::new(DedupConfig {
+            ttl: Duration::from_millis(50),
+            ..Default::default()
+        });
+        assert!(dedup.claim_local("main", "a@s.whatsapp.net", "1").await);
+        tokio::time::sleep(Duration::from_millis(120)).await;
+        assert!(dedup.claim_local("main", "a@s.whatsapp.net", "1").await);
+    }
+
+    #[test]
+    fn hits_are_counted_per_instance() {
+        let dedup = InboundDedup::default();
+        dedup.record_hit("sales");
+        dedup.record_hit("main");
+        dedup.record_hit("sales");
+        assert_eq!(
+            dedup.hits(),
+            vec![("main".to_string(), 1), ("sales".to_string(), 2)]
+        );
+        assert_eq!(
+            render_prometheus(&dedup.hits()),
+            "# HELP chatwarp_inbound_duplicates_total Redelivered inbound messages dropped before reaching the sinks.\n\
+             # TYPE chatwarp_inbound_duplicates_total counter\n\
+             chatwarp_inbound_duplicates_total{instance=\"main\"} 1\n\
+             chatwarp_inbound_duplicates_total{instance=\"sales\"} 2\n"
+        );
+    }
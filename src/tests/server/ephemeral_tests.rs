@@ -0,0 +1,82 @@
+    use super::*;
+    use serde_json::json;
+
+    const TIMER: EphemeralTimer = EphemeralTimer {
+        expiration: 604_800,
+        setting_timestamp: Some(1_700_000_000),
+    };
+
+    #[test]
+    fn timer_reads_active_rows_only() {
+        let row = json!({"value": {"expiration": 86400, "settingAt": 1700000000}});
+        assert_eq!(
+            timer_from_row(&row),
+            Some(EphemeralTimer {
+                expiration: 86_400,
+                setting_timestamp: Some(1_700_000_000),
+            })
+        );
+        assert_eq!(
+            timer_from_row(&json!({"expiration": 0, "settingAt": null})),
+            None
+        );
+    }
+
+    #[test]
+    fn conversation_becomes_extended_text_with_timer() {
+        let mut msg = wa::Message {
+            conversation: Some("hi".to_string()),
+            ..Default::default()
+        };
+        apply(&mut msg, TIMER);
+        assert_eq!(msg.conversation, None);
+        let text = msg.extended_text_message.as_ref().unwrap();
+        assert_eq!(text.text.as_deref(), Some("hi"));
+        let context_info = text.context_info.as_ref().unwrap();
+        assert_eq!(context_info.expiration, Some(604_800));
+        assert_eq!(
+            context_info.ephemeral_setting_timestamp,
+            Some(1_700_000_000)
+        );
+        assert_eq!(expiration(&msg), Some(604_800));
+    }
+
+    #[test]
+    fn timer_keeps_reply_context() {
+        let mut msg = wa::Message {
+            image_message: Some(Box::new(wa::message::ImageMessage {
+                context_info: Some(Box::new(wa::ContextInfo {
+                    stanza_id: Some("ABC".to_string()),
+                    ..Default::default()
+                })),
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+        apply(&mut msg, TIMER);
+        let context_info = msg.image_message.unwrap().context_info.unwrap();
+        assert_eq!(context_info.stanza_id.as_deref(), Some("ABC"));
+        assert_eq!(context_info.expiration, Some(604_800));
+    }
+
+    #[test]
+    fn expiration_looks_through_ephemeral_wrapper() {
+        let inner = wa::Message {
+            video_message: Some(Box::new(wa::message::VideoMessage {
+                context_info: Some(Box::new(wa::ContextInfo {
+                    expiration: Some(86_400),
+                    ..Default::default()
+                })),
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+        let wrapped = wa::Message {
+            ephemeral_message: Some(Box::new(wa::message::FutureProofMessage {
+                message: Some(Box::new(inner)),
+            })),
+            ..Default::default()
+        };
+        assert_eq!(expiration(&wrapped), Some(86_400));
+        assert_eq!(expiration(&wa::Message::default()), None);
+    }
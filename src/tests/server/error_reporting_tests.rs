@@ -0,0 +1,54 @@
+    use super::*;
+
+    #[test]
+    fn filter_drops_ignored_messages() {
+        let filter = EventFilter::new(vec!["Stream error".to_string()], Duration::ZERO);
+        let now = Instant::now();
+        assert!(!filter.keep("sales", "Stream error 515", now));
+        assert!(filter.keep("sales", "Failed to decrypt", now));
+        assert!(filter.keep("sales", "Failed to decrypt", now));
+    }
+
+    #[test]
+    fn filter_throttles_repeats_per_instance() {
+        let filter = EventFilter::new(Vec::new(), Duration::from_secs(60));
+        let now = Instant::now();
+        assert!(filter.keep("sales", "connect failed", now));
+        assert!(!filter.keep("sales", "connect failed", now + Duration::from_secs(30)));
+        assert!(filter.keep("support", "connect failed", now + Duration::from_secs(30)));
+        assert!(filter.keep("sales", "other error", now + Duration::from_secs(30)));
+        assert!(filter.keep("sales", "connect failed", now + Duration::from_secs(61)));
+    }
+
+    #[test]
+    fn filter_reads_the_event_message() {
+        let filter = EventFilter::new(vec!["noisy".to_string()], Duration::ZERO);
+        let event = Event {
+            message: Some("noisy reconnect".to_string()),
+            ..Default::default()
+        };
+        assert!(filter.check(event).is_none());
+        let event = Event {
+            message: Some("quiet".to_string()),
+            ..Default::default()
+        };
+        assert!(filter.check(event).is_some());
+    }
+
+    #[test]
+    fn each_instance_keeps_its_own_hub() {
+        let hubs = InstanceHubs::default();
+        assert!(Arc::ptr_eq(&hubs.hub("sales"), &hubs.hub("sales")));
+        assert!(!Arc::ptr_eq(&hubs.hub("sales"), &hubs.hub("support")));
+    }
+
+    #[test]
+    fn removed_instances_get_a_fresh_hub() {
+        let hubs = InstanceHubs::default();
+        let stale = hubs.hub("sales");
+        let support = hubs.hub("support");
+        hubs.remove("sales");
+        assert!(!Arc::ptr_eq(&hubs.hub("sales"), &stale));
+        assert!(Arc::ptr_eq(&hubs.hub("support"), &support));
+        hubs.remove("unknown");
+    }
@@ -0,0 +1,58 @@
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn serializes_once_on_demand() {
+        let event = BusEvent::new(json!({"event": "CALL", "instance": "sales", "data": {}}));
+        assert_eq!(event.event, "CALL");
+        assert_eq!(event.instance, "sales");
+        assert!(!event.is_serialized());
+
+        let first = event.json();
+        assert!(event.is_serialized());
+        assert!(Arc::ptr_eq(&first, &event.json()));
+        assert_eq!(
+            serde_json::from_str::<Value>(&first).unwrap(),
+            event.payload
+        );
+    }
+
+    #[test]
+    fn global_events_have_no_instance() {
+        let event = BusEvent::new(json!({"event": "APPLICATION_STARTUP", "instance": ""}));
+        assert_eq!(event.instance, "");
+        assert_eq!(BusEvent::new(json!({})).event, "");
+    }
+
+    #[test]
+    fn stats_count_queued_and_dropped() {
+        let stats = SinkStats::default();
+        stats.record_queued();
+        stats.record_queued();
+        stats.record_dropped(3);
+        assert_eq!(
+            stats.snapshot(),
+            SinkCounts {
+                queued: 2,
+                dropped: 3
+            }
+        );
+    }
+
+    #[test]
+    fn renders_prometheus_counters_per_sink() {
+        let text = render_prometheus(&[
+            (
+                "ws",
+                SinkCounts {
+                    queued: 10,
+                    dropped: 2,
+                },
+            ),
+            ("sse", SinkCounts::default()),
+        ]);
+        assert!(text.contains("# TYPE chatwarp_sink_events_dropped_total counter\n"));
+        assert!(text.contains("chatwarp_sink_events_queued_total{sink=\"ws\"} 10\n"));
+        assert!(text.contains("chatwarp_sink_events_dropped_total{sink=\"ws\"} 2\n"));
+        assert!(text.contains("chatwarp_sink_events_dropped_total{sink=\"sse\"} 0\n"));
+    }
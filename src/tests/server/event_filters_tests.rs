@@ -0,0 +1,114 @@
+    use super::*;
+
+    fn list(entries: &[&str]) -> Vec<String> {
+        entries.iter().map(|entry| entry.to_string()).collect()
+    }
+
+    #[test]
+    fn missing_and_empty_filters_take_every_event() {
+        assert!(allows(None, "MESSAGES_UPSERT"));
+        assert!(allows(Some(&[]), "MESSAGES_UPSERT"));
+        assert!(!allows(Some(&list(&["QRCODE_UPDATED"])), "MESSAGES_UPSERT"));
+    }
+
+    #[test]
+    fn wildcards_match_event_prefixes() {
+        let filter = list(&["MESSAGES_*", "CONNECTION_UPDATE"]);
+        assert!(allows(Some(&filter), "MESSAGES_UPSERT"));
+        assert!(allows(Some(&filter), "MESSAGES_UPDATE"));
+        assert!(allows(Some(&filter), "CONNECTION_UPDATE"));
+        assert!(!allows(Some(&filter), "CHATS_UPDATE"));
+        assert!(!allows(Some(&filter), "SEND_MESSAGE"));
+        assert!(allows(Some(&list(&["*"])), "CHATS_UPDATE"));
+    }
+
+    #[test]
+    fn evolution_style_names_match() {
+        assert!(entry_matches("messages.upsert", "MESSAGES_UPSERT"));
+        assert!(entry_matches("messages.*", "MESSAGES_DELETE"));
+        assert!(entry_matches(" qrcode_updated ", "QRCODE_UPDATED"));
+        assert!(!entry_matches("messages.upsert", "MESSAGES_UPDATE"));
+    }
+
+    #[test]
+    fn apply_replaces_only_the_given_lists() {
+        let mut filters = EventFilters {
+            webhook: Some(list(&["QRCODE_UPDATED"])),
+            websocket: None,
+            nats: Some(list(&["CONNECTION_UPDATE"])),
+        };
+        filters
+            .apply(&json!({"websocket": ["messages.*", "MESSAGES_*", "chats.update"], "nats": null}))
+            .unwrap();
+        assert_eq!(
+            filters,
+            EventFilters {
+                webhook: Some(list(&["QRCODE_UPDATED"])),
+                websocket: Some(list(&["MESSAGES_*", "CHATS_UPDATE"])),
+                nats: None,
+            }
+        );
+
+        filters.apply(&json!({"webhook": []})).unwrap();
+        assert_eq!(filters.webhook, None);
+    }
+
+    #[test]
+    fn apply_rejects_bad_bodies() {
+        let mut filters = EventFilters::default();
+        assert_eq!(
+            filters.apply(&json!([])),
+            Err(EventFilterError::InvalidBody)
+        );
+        assert_eq!(
+            filters.apply(&json!({})),
+            Err(EventFilterError::InvalidBody)
+        );
+        assert_eq!(
+            filters.apply(&json!({"rabbitmq": ["*"]})),
+            Err(EventFilterError::InvalidBody)
+        );
+        assert_eq!(
+            filters.apply(&json!({"webhook": "MESSAGES_UPSERT"})),
+            Err(EventFilterError::NotAList("webhook"))
+        );
+        assert_eq!(
+            filters.apply(&json!({"nats": [1]})),
+            Err(EventFilterError::NotAList("nats"))
+        );
+        assert_eq!(
+            filters.apply(&json!({"websocket": ["MESSAGES_*_UPSERT"]})),
+            Err(EventFilterError::InvalidEvent(
+                "MESSAGES_*_UPSERT".to_string()
+            ))
+        );
+        assert_eq!(
+            filters.apply(&json!({"websocket": [""]})),
+            Err(EventFilterError::InvalidEvent(String::new()))
+        );
+        let too_many: Vec<String> = (0..=MAX_ENTRIES).map(|i| format!("EVENT_{i}")).collect();
+        assert_eq!(
+            filters.apply(&json!({"webhook": too_many})),
+            Err(EventFilterError::NotAList("webhook"))
+        );
+        assert_eq!(filters, EventFilters::default());
+    }
+
+    #[test]
+    fn rows_and_responses_use_the_sink_names() {
+        let filters = EventFilters::from_row(&json!({
+            "webhook": ["MESSAGES_*"],
+            "websocket": null,
+            "nats": []
+        }));
+        assert_eq!(filters.webhook, Some(list(&["MESSAGES_*"])));
+        assert_eq!(filters.websocket, None);
+        assert_eq!(filters.nats, Some(vec![]));
+        assert_eq!(
+            response("loja", &filters),
+            json!({
+                "instance": "loja",
+                "events": {"webhook": ["MESSAGES_*"], "websocket": null, "nats": []}
+            })
+        );
+    }
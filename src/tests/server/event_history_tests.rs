@@ -0,0 +1,126 @@
+    use super::*;
+    use chrono::Duration;
+
+    fn envelope(instance: &str, event_id: Uuid) -> Value {
+        json!({
+            "event": "MESSAGES_UPSERT",
+            "instance": instance,
+            "schemaVersion": 1,
+            "eventId": event_id.to_string(),
+            "data": {}
+        })
+    }
+
+    fn history(size: usize) -> EventHistory {
+        EventHistory::new(EventHistoryConfig {
+            size,
+            persist: false,
+        })
+    }
+
+    fn ids(page: &HistoryPage) -> Vec<String> {
+        page.events
+            .iter()
+            .map(|e| e["eventId"].as_str().unwrap_or_default().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn parses_since() {
+        let id = Uuid::new_v4();
+        assert_eq!(Since::parse(&id.to_string()), Some(Since::EventId(id)));
+
+        let at = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        assert_eq!(
+            Since::parse("2023-11-14T22:13:20Z"),
+            Some(Since::Timestamp(at))
+        );
+        assert_eq!(Since::parse("1700000000"), Some(Since::Timestamp(at)));
+        assert_eq!(Since::parse("1700000000000"), Some(Since::Timestamp(at)));
+        assert_eq!(Since::parse("yesterday"), None);
+    }
+
+    #[test]
+    fn returns_events_after_an_event_id() {
+        let history = history(10);
+        let events: Vec<Uuid> = (0..4).map(|_| Uuid::new_v4()).collect();
+        for id in &events {
+            history.record(&envelope("sales", *id));
+        }
+        history.record(&envelope("support", Uuid::new_v4()));
+
+        let page = history.page("sales", Some(Since::EventId(events[1])), 10);
+        assert_eq!(ids(&page), [events[2].to_string(), events[3].to_string()]);
+        assert!(page.complete);
+        assert!(!page.has_more);
+        assert_eq!(page.source, "memory");
+
+        let page = history.page("sales", Some(Since::EventId(events[0])), 2);
+        assert_eq!(ids(&page), [events[1].to_string(), events[2].to_string()]);
+        assert!(page.has_more);
+    }
+
+    #[test]
+    fn unknown_event_id_returns_everything_kept() {
+        let history = history(10);
+        history.record(&envelope("sales", Uuid::new_v4()));
+
+        let page = history.page("sales", Some(Since::EventId(Uuid::new_v4())), 10);
+        assert_eq!(page.events.len(), 1);
+        assert!(!page.complete);
+    }
+
+    #[test]
+    fn evicted_events_make_the_page_incomplete() {
+        let history = history(2);
+        let start = Utc::now();
+        let events: Vec<Uuid> = (0..4).map(|_| Uuid::new_v4()).collect();
+        for (i, id) in events.iter().enumerate() {
+            history.record_at(&envelope("sales", *id), start + Duration::seconds(i as i64));
+        }
+
+        // The first two events left the buffer.
+        let page = history.page("sales", Some(Since::EventId(events[0])), 10);
+        assert!(!page.complete);
+        let page = history.page("sales", Some(Since::Timestamp(start)), 10);
+        assert!(!page.complete);
+        assert_eq!(ids(&page), [events[2].to_string(), events[3].to_string()]);
+
+        let page = history.page(
+            "sales",
+            Some(Since::Timestamp(start + Duration::seconds(2))),
+            10,
+        );
+        assert!(page.complete);
+        assert_eq!(ids(&page), [events[3].to_string()]);
+    }
+
+    #[test]
+    fn timestamps_before_the_start_are_not_covered() {
+        let history = history(10);
+        history.record(&envelope("sales", Uuid::new_v4()));
+
+        let before = history.page(
+            "sales",
+            Some(Since::Timestamp(history.started - Duration::hours(1))),
+            10,
+        );
+        assert_eq!(before.events.len(), 1);
+        assert!(!before.complete);
+
+        // Nothing happened since: complete, and empty.
+        let idle = history.page("support", Some(Since::Timestamp(Utc::now())), 10);
+        assert!(idle.events.is_empty());
+        assert!(idle.complete);
+
+        let everything = history.page("sales", None, 10);
+        assert_eq!(everything.events.len(), 1);
+        assert!(!everything.complete);
+    }
+
+    #[test]
+    fn ignores_events_without_instance() {
+        let history = history(10);
+        history.record(&json!({"event": "APPLICATION_STARTUP", "instance": "", "data": {}}));
+        assert!(history.buffers.lock().unwrap().is_empty());
+    }
@@ -0,0 +1,129 @@
+    use super::*;
+
+    #[test]
+    fn envelope_carries_schema_version() {
+        let payload = envelope("QRCODE_UPDATED", Some("sales"), json!({"qrcode": "2@abc"}));
+        assert_eq!(payload["event"], "QRCODE_UPDATED");
+        assert_eq!(payload["instance"], "sales");
+        assert_eq!(payload["schemaVersion"], SCHEMA_VERSION);
+        assert_eq!(payload["data"]["qrcode"], "2@abc");
+        assert_eq!(envelope("CONTACTS_SET", None, json!({}))["instance"], "");
+    }
+
+    #[test]
+    fn chats_update_omits_unchanged_fields() {
+        let update = ChatsUpdate {
+            id: "123@s.whatsapp.net".to_string(),
+            pinned: Some(true),
+            ..Default::default()
+        };
+        assert_eq!(
+            update.to_data(),
+            json!({"id": "123@s.whatsapp.net", "pinned": true})
+        );
+
+        let unmuted = ChatsUpdate {
+            id: "123@s.whatsapp.net".to_string(),
+            muted: Some(false),
+            mute_end_time: Some(None),
+            ..Default::default()
+        };
+        assert_eq!(unmuted.to_data()["muteEndTime"], Value::Null);
+        assert!(unmuted.to_data().get("muteEndTime").is_some());
+    }
+
+    #[test]
+    fn connection_update_flattens_extra_fields() {
+        let mut extra = Map::new();
+        extra.insert("restarts".to_string(), json!(2));
+        let update = ConnectionUpdate {
+            action: "update",
+            state: "connecting",
+            previous_state: "close",
+            connection_state: ConnectionState::Connecting,
+            reason: Reason::Started,
+            status_reason: Reason::Started.status_code(),
+            at: Utc::now(),
+            extra,
+        };
+        let data = update.to_data();
+        assert_eq!(data["connectionState"], "connecting");
+        assert_eq!(data["restarts"], 2);
+        assert!(data.get("extra").is_none());
+    }
+
+    #[test]
+    fn schema_document_lists_typed_events() {
+        let schema = schema_document();
+        assert_eq!(schema["schemaVersion"], SCHEMA_VERSION);
+        assert_eq!(schema["properties"]["schemaVersion"]["const"], SCHEMA_VERSION);
+        for event in [
+            QrcodeUpdated::EVENT,
+            ConnectionUpdate::EVENT,
+            LogoutInstance::EVENT,
+            MessagesUpsert::EVENT,
+            ChatsUpdate::EVENT,
+            ContactsSyncProgress::EVENT,
+            InstanceDeleteProgress::EVENT,
+        ] {
+            assert!(schema["$defs"][event].is_object(), "{event} missing");
+        }
+        assert_eq!(schema["allOf"].as_array().map(Vec::len), Some(7));
+    }
+
+    #[test]
+    fn schemas_require_every_serialized_field() {
+        let qr = QrcodeUpdated {
+            qrcode: "2@abc".to_string(),
+            timeout: 60,
+        }
+        .to_data();
+        let schema = QrcodeUpdated::schema();
+        for field in schema["required"].as_array().into_iter().flatten() {
+            let field = field.as_str().unwrap_or_default();
+            assert!(qr.get(field).is_some(), "{field} not serialized");
+        }
+
+        let upsert = MessagesUpsert {
+            messages: vec![json!({"key": {}, "message": {}})],
+            kind: "notify",
+        }
+        .to_data();
+        assert_eq!(upsert["type"], "notify");
+        assert_eq!(upsert["messages"].as_array().map(Vec::len), Some(1));
+
+        let logout = LogoutInstance {
+            wuid: None,
+            on_connect: true,
+            credentials_cleared: true,
+            at: Utc::now(),
+        }
+        .to_data();
+        for field in LogoutInstance::schema()["required"]
+            .as_array()
+            .into_iter()
+            .flatten()
+        {
+            let field = field.as_str().unwrap_or_default();
+            assert!(logout.get(field).is_some(), "{field} not serialized");
+        }
+
+        let progress = ContactsSyncProgress {
+            sync_id: "0b0e1c52-3a55-4c43-9d3e-2f4ab3a1c6de".to_string(),
+            processed: 50,
+            total: 120,
+            found: 41,
+            done: false,
+            error: None,
+        }
+        .to_data();
+        for field in ContactsSyncProgress::schema()["required"]
+            .as_array()
+            .into_iter()
+            .flatten()
+        {
+            let field = field.as_str().unwrap_or_default();
+            assert!(progress.get(field).is_some(), "{field} not serialized");
+        }
+        assert!(progress.get("error").is_none());
+    }
@@ -0,0 +1,281 @@
+    use super::*;
+    use crate::features::GroupParticipant;
+    use crate::types::message::AddressingMode;
+
+    /// Checks `value` against an Evolution v2 schema: every key of the
+    /// schema is present with the given type (`"string?"` also takes
+    /// `null`), objects recurse and `[schema]` checks each element.
+    fn conforms(value: &Value, schema: &Value, path: &str) -> Result<(), String> {
+        match schema {
+            Value::String(kind) => {
+                let (kind, nullable) = match kind.strip_suffix('?') {
+                    Some(kind) => (kind, true),
+                    None => (kind.as_str(), false),
+                };
+                let matches = match value {
+                    Value::Null => nullable || kind == "null",
+                    Value::String(_) => kind == "string",
+                    Value::Number(_) => kind == "number",
+                    Value::Bool(_) => kind == "boolean",
+                    Value::Object(_) => kind == "object",
+                    Value::Array(_) => kind == "array",
+                };
+                matches
+                    .then_some(())
+                    .ok_or_else(|| format!("{path}: expected {kind}, got {value}"))
+            }
+            Value::Object(fields) => {
+                let object = value
+                    .as_object()
+                    .ok_or_else(|| format!("{path}: expected object, got {value}"))?;
+                for (key, field) in fields {
+                    let path = format!("{path}.{key}");
+                    let value = object.get(key).ok_or_else(|| format!("{path}: missing"))?;
+                    conforms(value, field, &path)?;
+                }
+                Ok(())
+            }
+            Value::Array(items) => {
+                let array = value
+                    .as_array()
+                    .ok_or_else(|| format!("{path}: expected array, got {value}"))?;
+                array
+                    .iter()
+                    .enumerate()
+                    .try_for_each(|(i, item)| conforms(item, &items[0], &format!("{path}[{i}]")))
+            }
+            _ => Err(format!("{path}: bad schema {schema}")),
+        }
+    }
+
+    fn assert_conforms(value: &Value, schema: &Value) {
+        if let Err(e) = conforms(value, schema, "$") {
+            panic!("{e}\n{value:#}");
+        }
+    }
+
+    fn instance_schema() -> Value {
+        json!({
+            "id": "string",
+            "name": "string",
+            "connectionStatus": "string",
+            "ownerJid": "string?",
+            "profileName": "string?",
+            "profilePicUrl": "string?",
+            "integration": "string",
+            "number": "string?",
+            "businessId": "string?",
+            "token": "string?",
+            "clientName": "string",
+            "disconnectionReasonCode": "number?",
+            "disconnectionObject": "string?",
+            "disconnectionAt": "string?",
+            "createdAt": "string",
+            "updatedAt": "string",
+            "Chatwoot": "object?",
+            "Proxy": "object?",
+            "Rabbitmq": "object?",
+            "Sqs": "object?",
+            "Websocket": "object?",
+            "Setting": "object?",
+            "_count": {"Message": "number", "Contact": "number", "Chat": "number"}
+        })
+    }
+
+    fn qrcode_schema() -> Value {
+        json!({
+            "pairingCode": "string?",
+            "code": "string?",
+            "base64": "string?",
+            "count": "number"
+        })
+    }
+
+    fn session_row() -> Value {
+        json!({
+            "session": "loja",
+            "status": "open",
+            "integration": "WHATSAPP-BAILEYS",
+            "phone_number": "5511999990000",
+            "cloud_phone_number_id": null,
+            "cloud_business_id": null,
+            "webhook_url": "https://hooks.example/wa",
+            "webhook_headers": {},
+            "webhook_by_events": false,
+            "webhook_base64": false,
+            "created_at": "2026-04-15T10:00:00.123456+00:00",
+            "updated_at": "2026-04-15T10:05:00.123456+00:00",
+            "_count": {"Message": 12, "Contact": 3, "Chat": 2}
+        })
+    }
+
+    #[test]
+    fn fetch_instances_entry_matches_evolution_schema() {
+        let entry = instance(&session_row(), ConnectionState::Connected, None);
+        assert_conforms(&entry, &instance_schema());
+        assert_eq!(entry["connectionStatus"], "open");
+        assert_eq!(entry["ownerJid"], "5511999990000@s.whatsapp.net");
+        assert_eq!(entry["number"], "5511999990000");
+        assert_eq!(entry["_count"]["Message"], 12);
+
+        let mut bare = session_row();
+        bare["phone_number"] = Value::Null;
+        let entry = instance(
+            &bare,
+            ConnectionState::QrPending,
+            Some("https://pps.example/p.jpg"),
+        );
+        assert_conforms(&entry, &instance_schema());
+        assert_eq!(entry["connectionStatus"], "connecting");
+        assert_eq!(entry["ownerJid"], Value::Null);
+        assert_eq!(entry["profilePicUrl"], "https://pps.example/p.jpg");
+    }
+
+    #[test]
+    fn create_response_matches_evolution_schema() {
+        let created = created(&session_row(), qrcode(None, None, 0));
+        assert_conforms(
+            &created,
+            &json!({
+                "instance": {
+                    "instanceName": "string",
+                    "instanceId": "string",
+                    "integration": "string",
+                    "webhookWaBusiness": "string?",
+                    "accessTokenWaBusiness": "string",
+                    "status": "string"
+                },
+                "hash": "string?",
+                "webhook": {
+                    "webhookUrl": "string",
+                    "webhookHeaders": "object",
+                    "webhookByEvents": "boolean",
+                    "webhookBase64": "boolean"
+                },
+                "websocket": "object",
+                "rabbitmq": "object",
+                "sqs": "object",
+                "settings": {
+                    "rejectCall": "boolean",
+                    "msgCall": "string",
+                    "groupsIgnore": "boolean",
+                    "alwaysOnline": "boolean",
+                    "readMessages": "boolean",
+                    "readStatus": "boolean",
+                    "syncFullHistory": "boolean",
+                    "wavoipToken": "string"
+                },
+                "qrcode": qrcode_schema()
+            }),
+        );
+        assert_eq!(created["instance"]["status"], "created");
+    }
+
+    #[test]
+    fn connect_responses_match_evolution_schemas() {
+        let pending = qrcode(Some("2@AbC,def,ghi"), Some("WZYE-H1YY"), 2);
+        assert_conforms(&pending, &qrcode_schema());
+        assert!(
+            pending["base64"]
+                .as_str()
+                .unwrap()
+                .starts_with("data:image/png;base64,")
+        );
+        assert_eq!(pending["count"], 2);
+
+        let open = connection_state("loja", ConnectionState::Connected);
+        assert_conforms(
+            &open,
+            &json!({"instance": {"instanceName": "string", "state": "string"}}),
+        );
+        assert_eq!(open["instance"]["state"], "open");
+        assert_eq!(
+            connection_state("loja", ConnectionState::LoggedOut)["instance"]["state"],
+            "close"
+        );
+    }
+
+    #[test]
+    fn sent_message_matches_evolution_schema() {
+        let row = json!({
+            "id": "0f8c6b9e-4f0e-4c57-9a55-3b7d0b2a9c11",
+            "session": "loja",
+            "chat_id": "5511999990000@s.whatsapp.net",
+            "message_type": "text",
+            "status": "queued",
+            "created_at": "2026-04-15T10:00:00.5+00:00"
+        });
+        let sent = sent_message(&row, &json!({"text": "oi"}));
+        assert_conforms(
+            &sent,
+            &json!({
+                "key": {"remoteJid": "string", "fromMe": "boolean", "id": "string"},
+                "pushName": "string",
+                "status": "string",
+                "message": "object",
+                "contextInfo": "object?",
+                "messageType": "string",
+                "messageTimestamp": "number",
+                "instanceId": "string",
+                "source": "string"
+            }),
+        );
+        assert_eq!(sent["message"], json!({"conversation": "oi"}));
+        assert_eq!(sent["messageType"], "conversation");
+        assert_eq!(sent["messageTimestamp"], 1_776_247_200);
+
+        let mut voice = row.clone();
+        voice["message_type"] = json!("voice");
+        let sent = sent_message(&voice, &json!({"url": "https://cdn.example/a.ogg"}));
+        assert_eq!(sent["messageType"], "audioMessage");
+        assert_eq!(sent["message"], json!({}));
+    }
+
+    #[test]
+    fn group_matches_evolution_schema() {
+        let metadata = GroupMetadata {
+            id: "120363025246125486@g.us".parse().unwrap(),
+            subject: "Equipe".to_string(),
+            participants: vec![
+                GroupParticipant {
+                    jid: "5511999990000@s.whatsapp.net".parse().unwrap(),
+                    phone_number: None,
+                    is_admin: true,
+                },
+                GroupParticipant {
+                    jid: "5511888880000@s.whatsapp.net".parse().unwrap(),
+                    phone_number: None,
+                    is_admin: false,
+                },
+            ],
+            addressing_mode: AddressingMode::Pn,
+        };
+        let schema = json!({
+            "id": "string",
+            "subject": "string",
+            "subjectOwner": "string?",
+            "subjectTime": "number?",
+            "pictureUrl": "string?",
+            "size": "number",
+            "creation": "number?",
+            "owner": "string?",
+            "desc": "string?",
+            "descId": "string?",
+            "restrict": "boolean?",
+            "announce": "boolean?"
+        });
+
+        let summary = group(&metadata, false);
+        assert_conforms(&summary, &schema);
+        assert_eq!(summary["size"], 2);
+        assert!(summary.get("participants").is_none());
+
+        let full = group(&metadata, true);
+        assert_conforms(&full, &schema);
+        assert_conforms(
+            &full["participants"],
+            &json!([{"id": "string", "admin": "string?"}]),
+        );
+        assert_eq!(full["participants"][0]["admin"], "admin");
+        assert_eq!(full["participants"][1]["admin"], Value::Null);
+    }
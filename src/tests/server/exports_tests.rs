@@ -0,0 +1,110 @@
+    use super::*;
+    use chrono::TimeZone;
+
+    fn config(secret: &str) -> ExportConfig {
+        ExportConfig {
+            secret: secret.as_bytes().to_vec(),
+            public_url: Some("https://api.example.com".to_string()),
+            ..ExportConfig::default()
+        }
+    }
+
+    #[test]
+    fn parses_filters_and_format() {
+        let request = ExportRequest::from_body(&json!({
+            "remoteJid": "5511999990000",
+            "from": "2026-04-01T00:00:00-03:00",
+            "to": 1775617200,
+            "format": "CSV"
+        }))
+        .unwrap();
+        assert_eq!(request.format, ExportFormat::Csv);
+        assert_eq!(
+            request.remote_jid.as_deref(),
+            Some("5511999990000@s.whatsapp.net")
+        );
+        assert_eq!(
+            request.from,
+            Some(Utc.with_ymd_and_hms(2026, 4, 1, 3, 0, 0).unwrap())
+        );
+        assert_eq!(request.to, DateTime::from_timestamp(1775617200, 0));
+
+        let by_date = ExportRequest::from_body(&json!({"from": "2026-04-01T00:00:00Z"})).unwrap();
+        assert_eq!(by_date.format, ExportFormat::Json);
+        assert_eq!(by_date.remote_jid, None);
+    }
+
+    #[test]
+    fn rejects_invalid_requests() {
+        assert_eq!(
+            ExportRequest::from_body(&json!({})),
+            Err(ExportError::MissingFilter)
+        );
+        assert_eq!(
+            ExportRequest::from_body(&json!({"remoteJid": "5511999990000", "format": "xml"})),
+            Err(ExportError::InvalidFormat)
+        );
+        assert_eq!(
+            ExportRequest::from_body(&json!({"from": "yesterday"})),
+            Err(ExportError::InvalidDate("from"))
+        );
+        assert_eq!(
+            ExportRequest::from_body(&json!({"from": 200, "to": 100})),
+            Err(ExportError::InvalidRange)
+        );
+        assert!(matches!(
+            ExportRequest::from_body(&json!({"remoteJid": "abc"})),
+            Err(ExportError::InvalidChat(_))
+        ));
+    }
+
+    #[test]
+    fn csv_rows_escape_cells() {
+        let row = csv_row(&json!({
+            "id": "9b2f",
+            "waMessageId": "3EB0A",
+            "chatId": "5511999990000@s.whatsapp.net",
+            "fromMe": true,
+            "messageType": "conversation",
+            "status": "sent",
+            "deliveryStatus": null,
+            "createdAt": "2026-04-01T12:00:00+00:00",
+            "payload": {"text": "oi, \"tudo\" bem?"}
+        }));
+        assert_eq!(
+            row,
+            "9b2f,3EB0A,5511999990000@s.whatsapp.net,true,conversation,sent,,\
+             2026-04-01T12:00:00+00:00,\"oi, \"\"tudo\"\" bem?\",\
+             \"{\"\"text\"\":\"\"oi, \\\"\"tudo\\\"\" bem?\"\"}\""
+        );
+        assert_eq!(csv_row(&json!({})), ",,,,,,,,,");
+    }
+
+    #[test]
+    fn download_urls_are_signed_and_expire() {
+        let signer = config("s3cret");
+        let id = Uuid::new_v4();
+        let now = Utc.with_ymd_and_hms(2026, 4, 1, 12, 0, 0).unwrap();
+        let (url, expires) = signer.download_url(id, now).unwrap();
+        assert_eq!(expires, now + chrono::Duration::minutes(60));
+        assert!(url.starts_with(&format!(
+            "https://api.example.com/api/v1/jobs/{id}/download?expires={}&signature=",
+            expires.timestamp()
+        )));
+
+        let signature = url.rsplit_once("signature=").unwrap().1;
+        assert!(signer.verify(id, expires.timestamp(), signature, now));
+        assert!(!signer.verify(id, expires.timestamp(), signature, expires));
+        assert!(!signer.verify(id, expires.timestamp() + 1, signature, now));
+        assert!(!signer.verify(Uuid::new_v4(), expires.timestamp(), signature, now));
+        assert!(!signer.verify(id, expires.timestamp(), "zz", now));
+        assert!(!config("other").verify(id, expires.timestamp(), signature, now));
+    }
+
+    #[test]
+    fn only_download_paths_skip_auth() {
+        assert!(is_download_path("/jobs/9b2f/download"));
+        assert!(!is_download_path("/jobs/9b2f"));
+        assert!(!is_download_path("/jobs//download"));
+        assert!(!is_download_path("/jobs/a/b/download"));
+    }
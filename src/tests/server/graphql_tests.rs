@@ -0,0 +1,71 @@
+    use super::*;
+
+    #[test]
+    fn schema_is_read_only() {
+        let sdl = schema().sdl();
+        for field in [
+            "instances(",
+            "instance(name: String!): Instance",
+            "connectionStatus: String!",
+            "counts: Counts!",
+            "lastMessage: Message",
+            "contacts(",
+        ] {
+            assert!(sdl.contains(field), "{field} missing from the schema");
+        }
+        assert!(!sdl.contains("type Mutation"));
+        assert!(!sdl.contains("type Subscription"));
+    }
+
+    #[test]
+    fn list_arguments_follow_the_rest_rules() {
+        let page = page_request(&CHATS, None, None, None).unwrap();
+        assert_eq!((page.limit, page.offset), (CHATS.default_limit, 0));
+        assert_eq!(page.sort_param(), CHATS.default_sort);
+
+        let page = page_request(&MESSAGES, Some(10_000), Some(20), Some("createdAt".into())).unwrap();
+        assert_eq!((page.limit, page.offset), (MESSAGES.max_limit, 20));
+        assert_eq!(page.sort_param(), "createdAt");
+
+        assert!(page_request(&CHATS, Some(0), None, None).is_err());
+        assert!(page_request(&CHATS, None, Some(-1), None).is_err());
+        assert!(page_request(&CONTACTS, None, None, Some("phone".into())).is_err());
+    }
+
+    #[tokio::test]
+    async fn rejects_queries_that_are_too_deep() {
+        let response = schema()
+            .execute("{ __schema { types { fields { type { ofType { ofType { name } } } } } } }")
+            .await;
+        assert_eq!(response.errors[0].message, "Query is nested too deep.");
+    }
+
+    #[test]
+    fn rows_decode_into_objects() {
+        let chats: Vec<Chat> = decode(vec![json!({
+            "session": "sales",
+            "id": "5511999990000@s.whatsapp.net",
+            "title": "Ana",
+            "last_message_at": "2026-04-01T10:00:00+00:00",
+            "unread_count": 2,
+            "archived": false,
+            "pinned": true,
+            "mute_end_at": null,
+        })])
+        .unwrap();
+        assert_eq!(chats[0].instance, "sales");
+        assert_eq!(chats[0].unread_count, 2);
+        assert!(chats[0].pinned);
+
+        let messages: Vec<Message> = decode(vec![json!({
+            "id": "00000000-0000-0000-0000-000000000000",
+            "session": "sales",
+            "chat_id": "5511999990000@s.whatsapp.net",
+            "payload": {"text": "oi"},
+        })])
+        .unwrap();
+        assert!(!messages[0].from_me);
+        assert_eq!(messages[0].payload.0["text"], "oi");
+
+        assert!(decode::<Contact>(vec![json!({"session": "sales"})]).is_err());
+    }
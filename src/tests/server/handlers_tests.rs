@@ -0,0 +1,317 @@
+    use super::*;
+
+    #[test]
+    fn number_to_jid_strips_formatting() {
+        assert_eq!(number_to_jid("+55 (11) 99999-0000"), "5511999990000@s.whatsapp.net");
+    }
+
+    #[test]
+    fn number_to_jid_keeps_explicit_jids() {
+        assert_eq!(number_to_jid("123456@g.us"), "123456@g.us");
+    }
+
+    #[test]
+    fn version_config_from_json_parses_all_fields() {
+        let config = version_config_from_json(&json!({
+            "pin": null,
+            "fallbacks": ["2.3000.2", "2.3000.1"],
+            "source": "static"
+        }))
+        .unwrap();
+
+        assert_eq!(config.pinned, None);
+        assert_eq!(config.fallbacks, vec![(2, 3000, 2), (2, 3000, 1)]);
+        assert_eq!(config.source, version::VersionSource::Static);
+    }
+
+    #[test]
+    fn version_config_from_json_rejects_bad_versions() {
+        assert!(version_config_from_json(&json!({"pin": "2.3000"})).is_err());
+        assert!(version_config_from_json(&json!({"fallbacks": "2.3000.1"})).is_err());
+        assert!(version_config_from_json(&json!({"source": "cdn"})).is_err());
+    }
+
+    #[test]
+    fn companion_to_remove_keeps_the_phone_and_this_device() {
+        let own: Jid = "5511999990000:4@s.whatsapp.net".parse().unwrap();
+        assert_eq!(
+            companion_to_remove(&own, " 12 ").unwrap().to_string(),
+            "5511999990000:12@s.whatsapp.net"
+        );
+        assert_eq!(companion_to_remove(&own, "0"), Err("primary_device"));
+        assert_eq!(companion_to_remove(&own, "4"), Err("current_device"));
+        assert_eq!(companion_to_remove(&own, "web"), Err("invalid_device"));
+    }
+
+    #[test]
+    fn business_profile_update_is_validated() {
+        let update = business_profile_update_from_json(&json!({
+            "description": "Padaria",
+            "websites": ["https://a.example"],
+            "businessHours": {
+                "timezone": "America/Sao_Paulo",
+                "days": [
+                    {"day": "mon", "mode": "specific_hours", "openTime": 480, "closeTime": 1080},
+                    {"day": "sun", "mode": "open_24h"}
+                ]
+            }
+        }))
+        .unwrap();
+        assert_eq!(update.description.as_deref(), Some("Padaria"));
+        assert_eq!(update.business_hours.unwrap().days[0].close_time, Some(1080));
+
+        for invalid in [
+            json!({}),
+            json!({"email": "nope"}),
+            json!({"websites": ["a", "b", "c"]}),
+            json!({"businessHours": {"days": [{"day": "monday", "mode": "open_24h"}]}}),
+            json!({"businessHours": {"days": [{"day": "mon", "mode": "closed"}]}}),
+            json!({"businessHours": {"days": [{"day": "mon", "mode": "specific_hours", "openTime": 600, "closeTime": 540}]}}),
+            json!({"websites": "https://a.example"}),
+        ] {
+            assert!(business_profile_update_from_json(&invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn catalog_link_uses_the_phone_number() {
+        assert_eq!(catalog_link("5511999990000@s.whatsapp.net"), "https://wa.me/c/5511999990000");
+        assert_eq!(catalog_link("5511999990000:12@s.whatsapp.net"), "https://wa.me/c/5511999990000");
+    }
+
+    #[test]
+    fn find_messages_filter_reads_evolution_body() {
+        let filter = |body: Value| {
+            chat_manager::MessageFilter::from_request(&serde_json::from_value(body).unwrap())
+        };
+        let filter_all = filter(json!({
+            "where": {"key": {"remoteJid": "5511999990000@s.whatsapp.net", "id": " 3EB0A "}},
+            "limit": 10_000,
+            "offset": -5
+        }))
+        .unwrap();
+        assert_eq!(filter_all.chat_id.as_deref(), Some("5511999990000@s.whatsapp.net"));
+        assert_eq!(filter_all.message_id.as_deref(), Some("3EB0A"));
+        assert_eq!(filter_all.page.limit, chat_manager::MESSAGES.max_limit);
+        assert_eq!(filter_all.page.offset, 0);
+
+        let by_id = filter(json!({"where": {"key": {"id": ""}}})).unwrap();
+        assert_eq!(by_id.message_id, None);
+        assert_eq!(by_id.page.limit, chat_manager::MESSAGES.default_limit);
+        assert_eq!(by_id.page.order_by(), "ORDER BY created_at DESC NULLS LAST, id");
+
+        let clamped = filter(json!({"limit": 0, "offset": "x"})).unwrap();
+        assert_eq!(clamped.page.limit, 1);
+        assert_eq!(clamped.page.offset, 0);
+
+        let second = filter(json!({"limit": 20, "page": 2})).unwrap();
+        assert_eq!(second.page.offset, 20);
+    }
+
+    #[test]
+    fn evolution_buttons_and_lists_map_to_queued_bodies() {
+        let body = buttons_body(
+            "loja",
+            "5511999990000@s.whatsapp.net",
+            &json!({
+                "number": "5511999990000",
+                "title": "Pedido 42",
+                "description": "Confirma?",
+                "footer": "Loja",
+                "buttons": [
+                    {"type": "reply", "displayText": "Sim", "id": "sim"},
+                    {"type": "call", "displayText": "Ligar", "phoneNumber": "+5511999990000"}
+                ]
+            }),
+        );
+        assert_eq!(body["session"], "loja");
+        assert_eq!(body["text"], "Confirma?");
+        assert_eq!(body["buttons"][0]["text"], "Sim");
+        assert_eq!(body["buttons"][1]["phone"], "+5511999990000");
+        assert!(crate::server::messages_worker::build_buttons_message(&body).is_some());
+
+        let body = list_body(
+            "loja",
+            "5511999990000@s.whatsapp.net",
+            &json!({
+                "number": "5511999990000",
+                "description": "Escolha",
+                "buttonText": "Opções",
+                "footerText": "Loja",
+                "sections": [{"title": "Tudo", "rows": [{"title": "A", "rowId": "a"}]}]
+            }),
+        );
+        assert_eq!(body["footer"], "Loja");
+        assert_eq!(body["sections"][0]["rows"][0]["id"], "a");
+        assert!(crate::server::messages_worker::build_list_message(&body).is_some());
+    }
+
+    #[test]
+    fn evolution_create_body_maps_to_session_body() {
+        let create = |body: Value| create_body(&serde_json::from_value(body).unwrap());
+        let body = create(json!({
+            "instanceName": "loja",
+            "number": "5511999990000",
+            "qrcode": true,
+            "webhook": {"url": "https://hooks.example/wa", "byEvents": true, "events": ["MESSAGES_UPSERT"]}
+        }))
+        .unwrap();
+        assert_eq!(body["session"], "loja");
+        assert_eq!(body["integration"], cloud_api::DEFAULT_INTEGRATION);
+        assert_eq!(body["phone_number"], "5511999990000");
+        assert_eq!(
+            body["webhook"],
+            json!({
+                "url": "https://hooks.example/wa",
+                "webhookByEvents": true,
+                "webhookBase64": false,
+                "events": ["MESSAGES_UPSERT"]
+            })
+        );
+
+        let cloud = create(json!({
+            "instanceName": "meta",
+            "integration": cloud_api::INTEGRATION,
+            "number": "1234567890",
+            "token": "EAAG",
+            "businessId": "998877"
+        }))
+        .unwrap();
+        assert_eq!(cloud["number"], "1234567890");
+        assert_eq!(cloud["token"], "EAAG");
+        assert!(cloud.get("phone_number").is_none());
+
+        let named = create(json!({"name": "loja", "webhook": {"byEvents": true}})).unwrap();
+        assert_eq!(named["session"], "loja");
+        assert!(named.get("webhook").is_none());
+
+        assert!(create(json!({"instanceName": "  "})).is_none());
+        assert!(create(json!({})).is_none());
+    }
+
+    #[test]
+    fn send_text_needs_a_number_and_a_text() {
+        let send = |body: Value| send_text_body("loja", &serde_json::from_value(body).unwrap());
+        let body = send(json!({
+            "number": "5511999990000",
+            "text": "oi",
+            "quoted": {"key": {"id": "3EB0A"}}
+        }))
+        .unwrap();
+        assert_eq!(body["session"], "loja");
+        assert_eq!(body["chatId"], "5511999990000@s.whatsapp.net");
+        assert_eq!(body["quoted"]["key"]["id"], "3EB0A");
+        assert!(body.get("linkPreview").is_none());
+
+        assert_eq!(send(json!({"text": "oi"})), Err("number_required"));
+        assert_eq!(send(json!({"number": " ", "text": "oi"})), Err("number_required"));
+        assert_eq!(send(json!({"number": "5511999990000"})), Err("text_required"));
+    }
+
+    #[test]
+    fn api_model_bodies_round_trip_through_the_handlers() {
+        use crate::models::api_model::{
+            CreateInstance, FindMessages, FoundMessages, InstanceWebhook, MessageKeyFilter,
+            MessageWhere, SendText, WhatsappNumbers,
+        };
+        use serde::de::DeserializeOwned;
+
+        // What the client sends is what the handler's extractor reads back.
+        fn sent<T: serde::Serialize + DeserializeOwned>(body: &T) -> T {
+            serde_json::from_value(serde_json::to_value(body).unwrap()).unwrap()
+        }
+
+        let create = CreateInstance {
+            instance_name: "loja".to_string(),
+            number: Some("5511999990000".to_string()),
+            webhook: Some(InstanceWebhook {
+                url: "https://hooks.example/wa".to_string(),
+                by_events: true,
+                events: Some(vec!["MESSAGES_UPSERT".to_string()]),
+                ..Default::default()
+            }),
+            wa_version: Some("2.3000.1015901307".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(sent(&create), create);
+        let body = create_body(&sent(&create)).unwrap();
+        assert_eq!(body["session"], "loja");
+        assert_eq!(body["phone_number"], "5511999990000");
+        assert_eq!(body["webhook"]["webhookByEvents"], true);
+        assert_eq!(body["webhook"]["events"], json!(["MESSAGES_UPSERT"]));
+        assert_eq!(body["waVersion"], "2.3000.1015901307");
+
+        let send = SendText {
+            number: "5511999990000".to_string(),
+            text: "oi".to_string(),
+            link_preview: Some(false),
+            quoted: None,
+        };
+        assert_eq!(sent(&send), send);
+        let body = send_text_body("loja", &sent(&send)).unwrap();
+        assert_eq!(body["text"], "oi");
+        assert_eq!(body["linkPreview"], false);
+
+        let find = FindMessages {
+            filter: MessageWhere {
+                key: MessageKeyFilter {
+                    remote_jid: Some("5511999990000@s.whatsapp.net".to_string()),
+                    id: Some("3EB0ABC".to_string()),
+                },
+            },
+            limit: Some(10),
+            ..Default::default()
+        };
+        assert_eq!(sent(&find), find);
+        let filter = chat_manager::MessageFilter::from_request(&sent(&find)).unwrap();
+        assert_eq!(
+            filter.chat_id.as_deref(),
+            Some("5511999990000@s.whatsapp.net")
+        );
+        assert_eq!(filter.message_id.as_deref(), Some("3EB0ABC"));
+        assert_eq!(filter.page.limit, 10);
+
+        // A row as `chat_manager::find_messages` selects it, with its
+        // reactions attached.
+        let row = json!({
+            "id": "6f1c9a52-2f5e-4b8e-9d1a-0c3e5b7a9d11",
+            "key": {"remoteJid": "5511999990000@s.whatsapp.net", "fromMe": true, "id": "3EB0ABC"},
+            "messageType": "text",
+            "message": {"text": "oi"},
+            "status": "sent",
+            "createdAt": "2024-05-01T12:00:00.000000+00:00",
+            "reactions": [{"emoji": "👍", "count": 1}]
+        });
+        let answer = found_messages("loja".to_string(), vec![row], &filter, 1);
+        let parsed: FoundMessages = serde_json::from_value(answer.clone()).unwrap();
+        assert_eq!(parsed.count, 1);
+        assert_eq!(parsed.messages[0].key.id.as_deref(), Some("3EB0ABC"));
+        assert_eq!(serde_json::to_value(&parsed).unwrap(), answer);
+
+        let numbers = WhatsappNumbers {
+            numbers: vec!["5511999990000".to_string()],
+        };
+        assert_eq!(sent(&numbers), numbers);
+        assert_eq!(numbers::numbers_from_body(&sent(&numbers)).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn connection_state_answer_matches_the_api_model() {
+        use crate::models::api_model::InstanceConnection;
+        use crate::server::connection::{ConnectionStatus, Reason};
+
+        let mut status = ConnectionStatus::default();
+        status.transition(
+            ConnectionState::Connecting,
+            Reason::Started,
+            chrono::Utc::now(),
+        );
+        let mut answer = status.to_json();
+        answer["instance"] = json!("loja");
+
+        let parsed: InstanceConnection = serde_json::from_value(answer).unwrap();
+        assert_eq!(parsed.instance, "loja");
+        assert_eq!(parsed.state, ConnectionState::Connecting);
+        assert_eq!(parsed.transitions.len(), 1);
+        assert_eq!(parsed.transitions[0].reason, Reason::Started);
+    }
@@ -0,0 +1,107 @@
+    use super::*;
+    use crate::api_store::{ApiBind, NoopApiStore};
+
+    struct SlowStore(Duration);
+
+    #[async_trait::async_trait]
+    impl ApiStore for SlowStore {
+        async fn query_json(&self, _sql: &str, _binds: Vec<ApiBind>) -> anyhow::Result<Vec<Value>> {
+            tokio::time::sleep(self.0).await;
+            Ok(vec![])
+        }
+
+        async fn execute(&self, _sql: &str, _binds: Vec<ApiBind>) -> anyhow::Result<usize> {
+            Err(anyhow::anyhow!("connection refused"))
+        }
+    }
+
+    fn dependency(name: &'static str, status: DependencyStatus, critical: bool) -> DependencyHealth {
+        DependencyHealth {
+            critical,
+            ..DependencyHealth::new(name, status)
+        }
+    }
+
+    #[test]
+    fn rolls_up_by_criticality() {
+        assert_eq!(rollup(&[]), HealthStatus::Healthy);
+        assert_eq!(
+            rollup(&[
+                dependency(DATABASE, DependencyStatus::Ok, true),
+                dependency(WA_VERSION, DependencyStatus::Ok, false),
+            ]),
+            HealthStatus::Healthy
+        );
+        assert_eq!(
+            rollup(&[
+                dependency(DATABASE, DependencyStatus::Degraded, true),
+                dependency(WA_VERSION, DependencyStatus::Ok, false),
+            ]),
+            HealthStatus::Degraded
+        );
+        assert_eq!(
+            rollup(&[
+                dependency(DATABASE, DependencyStatus::Ok, true),
+                dependency(NATS, DependencyStatus::Down, false),
+            ]),
+            HealthStatus::Degraded
+        );
+        assert_eq!(
+            rollup(&[
+                dependency(DATABASE, DependencyStatus::Down, true),
+                dependency(NATS, DependencyStatus::Ok, false),
+            ]),
+            HealthStatus::Unhealthy
+        );
+    }
+
+    #[test]
+    fn report_marks_critical_dependencies() {
+        let config = HealthConfig {
+            critical: vec![NATS.to_string()],
+            ..HealthConfig::default()
+        };
+        let report = HealthReport::new(
+            &config,
+            vec![
+                dependency(DATABASE, DependencyStatus::Down, true),
+                dependency(NATS, DependencyStatus::Ok, false),
+            ],
+        );
+        assert!(!report.dependency(DATABASE).unwrap().critical);
+        assert!(report.dependency(NATS).unwrap().critical);
+        assert_eq!(report.status, HealthStatus::Degraded);
+        assert!(report.ready());
+
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["status"], "degraded");
+        assert_eq!(json["dependencies"][0]["status"], "down");
+        assert_eq!(json["dependencies"][1].get("latencyMs"), None);
+    }
+
+    #[tokio::test]
+    async fn database_check_reports_latency_and_timeouts() {
+        let config = HealthConfig {
+            timeout: Duration::from_millis(200),
+            slow: Duration::from_millis(50),
+            ..HealthConfig::default()
+        };
+
+        let ok = check_database(&NoopApiStore, &config).await;
+        assert_eq!(ok.status, DependencyStatus::Ok);
+        assert!(ok.latency_ms.is_some());
+
+        let slow = check_database(&SlowStore(Duration::from_millis(100)), &config).await;
+        assert_eq!(slow.status, DependencyStatus::Degraded);
+
+        let hung = check_database(&SlowStore(Duration::from_secs(5)), &config).await;
+        assert_eq!(hung.status, DependencyStatus::Down);
+        assert_eq!(hung.error.as_deref(), Some("timeout"));
+    }
+
+    #[test]
+    fn version_check_without_fetches_is_ok() {
+        let check = check_wa_version(std::iter::empty());
+        assert_eq!(check.status, DependencyStatus::Ok);
+        assert_eq!(check.latency_ms, None);
+    }
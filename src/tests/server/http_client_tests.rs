@@ -0,0 +1,127 @@
+    use super::*;
+    use std::sync::Mutex;
+
+    /// Answers with the queued results in order, then 200.
+    struct Scripted {
+        results: Mutex<Vec<Result<u16, &'static str>>>,
+        calls: Mutex<u32>,
+    }
+
+    impl Scripted {
+        fn new(mut results: Vec<Result<u16, &'static str>>) -> Arc<Self> {
+            results.reverse();
+            Arc::new(Self {
+                results: Mutex::new(results),
+                calls: Mutex::new(0),
+            })
+        }
+
+        fn calls(&self) -> u32 {
+            *self.calls.lock().unwrap()
+        }
+    }
+
+    #[async_trait]
+    impl HttpClient for Scripted {
+        async fn execute(&self, _request: HttpRequest) -> anyhow::Result<HttpResponse> {
+            *self.calls.lock().unwrap() += 1;
+            match self.results.lock().unwrap().pop().unwrap_or(Ok(200)) {
+                Ok(status_code) => Ok(HttpResponse {
+                    status_code,
+                    body: Vec::new(),
+                }),
+                Err(e) => Err(anyhow::anyhow!(e)),
+            }
+        }
+    }
+
+    fn fast_config() -> HttpClientConfig {
+        HttpClientConfig {
+            initial_backoff: Duration::ZERO,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn destination_keeps_scheme_host_and_port() {
+        assert_eq!(
+            destination("https://Hooks.Example.com:8443/a/b?x=1"),
+            "https://hooks.example.com:8443"
+        );
+        assert_eq!(destination("http://user:pw@proxy.local/x"), "http://proxy.local");
+        assert_eq!(destination("https://graph.facebook.com"), "https://graph.facebook.com");
+    }
+
+    #[test]
+    fn retries_only_transient_statuses() {
+        assert!(retryable_status(429));
+        assert!(retryable_status(503));
+        assert!(!retryable_status(501));
+        assert!(!retryable_status(404));
+        assert!(!retryable_status(200));
+    }
+
+    #[test]
+    fn backoff_doubles_with_jitter_and_cap() {
+        let config = HttpClientConfig::default();
+        assert_eq!(config.backoff(1, 1.0), Duration::from_millis(200));
+        assert_eq!(config.backoff(1, 0.0), Duration::from_millis(100));
+        assert_eq!(config.backoff(3, 1.0), Duration::from_millis(800));
+        assert_eq!(config.backoff(30, 1.0), config.max_backoff);
+    }
+
+    #[tokio::test]
+    async fn retries_until_success() {
+        let inner = Scripted::new(vec![Err("reset"), Ok(502), Ok(200)]);
+        let client = SharedHttpClient::new(inner.clone(), fast_config());
+        let response = client
+            .execute(HttpRequest::get("https://hooks.example.com/x"))
+            .await
+            .unwrap();
+        assert_eq!(response.status_code, 200);
+        assert_eq!(inner.calls(), 3);
+    }
+
+    #[tokio::test]
+    async fn client_errors_are_returned_without_retry() {
+        let inner = Scripted::new(vec![Ok(404)]);
+        let client = SharedHttpClient::new(inner.clone(), fast_config());
+        let response = client
+            .execute(HttpRequest::get("https://hooks.example.com/x"))
+            .await
+            .unwrap();
+        assert_eq!(response.status_code, 404);
+        assert_eq!(inner.calls(), 1);
+    }
+
+    #[tokio::test]
+    async fn opens_circuit_after_consecutive_failures() {
+        let inner = Scripted::new(vec![Ok(500); 4]);
+        let config = HttpClientConfig {
+            max_attempts: 2,
+            breaker_threshold: 2,
+            ..fast_config()
+        };
+        let client = SharedHttpClient::new(inner.clone(), config);
+        let url = "https://down.example.com/hook";
+
+        for _ in 0..2 {
+            let response = client.execute(HttpRequest::get(url)).await.unwrap();
+            assert_eq!(response.status_code, 500);
+        }
+        assert!(client.is_open("https://down.example.com"));
+        assert_eq!(client.open_circuits(), vec!["https://down.example.com"]);
+
+        let err = client.execute(HttpRequest::get(url)).await.unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<HttpClientError>(),
+            Some(&HttpClientError::CircuitOpen("https://down.example.com".to_string()))
+        );
+        assert_eq!(inner.calls(), 4);
+
+        // Other destinations are unaffected.
+        client
+            .execute(HttpRequest::get("https://up.example.com/hook"))
+            .await
+            .unwrap();
+    }
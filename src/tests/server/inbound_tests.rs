@@ -0,0 +1,129 @@
+    use super::*;
+
+    fn message(body: Value) -> InboundMessage {
+        InboundMessage::from_body(body.to_string().as_bytes()).expect("valid body")
+    }
+
+    #[test]
+    fn text_goes_as_text_message() {
+        let (body, message_type) = message(json!({"to": "5511999990000", "text": " hello "}))
+            .queue_body("main", "5511999990000@s.whatsapp.net")
+            .expect("queue body");
+        assert_eq!(message_type, "text");
+        assert_eq!(
+            body,
+            json!({"session": "main", "chatId": "5511999990000@s.whatsapp.net", "text": "hello"})
+        );
+    }
+
+    #[test]
+    fn media_type_is_guessed() {
+        let queued = |body: Value| {
+            message(body)
+                .queue_body("main", "a@s.whatsapp.net")
+                .expect("queue body")
+        };
+        let (body, message_type) = queued(json!({
+            "to": "1",
+            "text": "look",
+            "mediaUrl": "https://cdn.example.com/a.JPG?size=big",
+        }));
+        assert_eq!(message_type, "image");
+        assert_eq!(body["url"], "https://cdn.example.com/a.JPG?size=big");
+        assert_eq!(body["caption"], "look");
+
+        let (body, message_type) = queued(json!({
+            "to": "1",
+            "mediaUrl": "https://cdn.example.com/download",
+            "mimetype": "video/mp4",
+        }));
+        assert_eq!(message_type, "video");
+        assert_eq!(body["mimetype"], "video/mp4");
+
+        let (body, message_type) = queued(json!({
+            "to": "1",
+            "mediaUrl": "https://cdn.example.com/report",
+            "fileName": "report.pdf",
+        }));
+        assert_eq!(message_type, "file");
+        assert_eq!(body["filename"], "report.pdf");
+
+        let (_, message_type) = queued(json!({
+            "to": "1",
+            "mediaUrl": "https://cdn.example.com/a.jpg",
+            "mediaType": "Document",
+        }));
+        assert_eq!(message_type, "file");
+    }
+
+    #[test]
+    fn invalid_messages_are_rejected() {
+        assert_eq!(
+            InboundMessage::from_body(b"[]"),
+            Err(InboundError::InvalidBody)
+        );
+        assert_eq!(
+            InboundMessage::from_body(br#"{"text": "hi"}"#),
+            Err(InboundError::MissingTo)
+        );
+        let queue = |body: Value| message(body).queue_body("main", "a@s.whatsapp.net");
+        assert_eq!(
+            queue(json!({"to": "1", "text": "  "})),
+            Err(InboundError::MissingContent)
+        );
+        assert_eq!(
+            queue(json!({"to": "1", "mediaUrl": "file:///etc/passwd"})),
+            Err(InboundError::InvalidMediaUrl(
+                "file:///etc/passwd".to_string()
+            ))
+        );
+        assert_eq!(
+            queue(json!({"to": "1", "mediaUrl": "https://a/b", "mediaType": "gif"})),
+            Err(InboundError::InvalidMediaType("gif".to_string()))
+        );
+        assert_eq!(
+            queue(json!({"to": "1", "text": "hi", "mediaUrl": "https://a/b.ogg"})),
+            Err(InboundError::AudioWithText)
+        );
+    }
+
+    #[test]
+    fn secrets() {
+        let secret = generate_secret();
+        assert_eq!(secret.len(), 64);
+        assert_ne!(secret, generate_secret());
+        assert!(secret_matches(&secret, &hash_api_key(&secret)));
+        assert!(!secret_matches("other", &hash_api_key(&secret)));
+        assert!(!secret_matches(&secret, ""));
+
+        assert_eq!(
+            secret_from_body(Some(&json!({"secret": "short"}))),
+            Err(InboundError::SecretTooShort)
+        );
+        assert_eq!(
+            secret_from_body(Some(&json!({"secret": " zapier-main-0001 "}))),
+            Ok("zapier-main-0001".to_string())
+        );
+        assert_eq!(secret_from_body(None).map(|s| s.len()), Ok(64));
+    }
+
+    #[test]
+    fn secret_is_read_from_header_bearer_or_query() {
+        let mut headers = HeaderMap::new();
+        let mut query = HashMap::new();
+        assert_eq!(request_secret(&headers, &query), None);
+        query.insert("secret".to_string(), "from-query".to_string());
+        assert_eq!(request_secret(&headers, &query), Some("from-query"));
+        headers.insert(header::AUTHORIZATION, "Bearer from-bearer".parse().unwrap());
+        assert_eq!(request_secret(&headers, &query), Some("from-bearer"));
+        headers.insert(SECRET_HEADER, "from-header".parse().unwrap());
+        assert_eq!(request_secret(&headers, &query), Some("from-header"));
+    }
+
+    #[test]
+    fn receiver_paths() {
+        assert!(is_receiver_path("/integration/inbound/main"));
+        assert!(!is_receiver_path("/integration/inbound/"));
+        assert!(!is_receiver_path("/integration/inbound/main/extra"));
+        assert!(!is_receiver_path("/instance/inbound/main"));
+    }
@@ -0,0 +1,142 @@
+    use super::*;
+    use crate::server::events::EventPayload;
+
+    #[test]
+    fn modes_are_presets() {
+        assert_eq!(
+            DeletionOptions::from_body(None),
+            Ok(DeletionOptions::KEEP_AUTH)
+        );
+        assert_eq!(
+            DeletionOptions::from_body(Some(&json!({"mode": "logout-first"}))),
+            Ok(DeletionOptions::LOGOUT_FIRST)
+        );
+        assert_eq!(
+            DeletionOptions::from_mode("purge-data"),
+            Ok(DeletionOptions::PURGE_DATA)
+        );
+        assert_eq!(
+            DeletionOptions::from_body(Some(&json!({"soft": true}))),
+            Ok(DeletionOptions::DISABLE)
+        );
+        assert_eq!(
+            DeletionOptions::from_mode("wipe"),
+            Err(DeletionError::UnknownMode("wipe".to_string()))
+        );
+    }
+
+    #[test]
+    fn flags_override_the_mode() {
+        let options = DeletionOptions::from_body(Some(&json!({
+            "mode": "keep-auth",
+            "logout": true
+        })))
+        .unwrap();
+        assert!(options.logout && !options.purge_auth && options.purge_data);
+
+        let options =
+            DeletionOptions::from_body(Some(&json!({"soft": true, "purgeData": true}))).unwrap();
+        assert!(options.soft && options.purge_data && !options.purge_auth);
+    }
+
+    #[test]
+    fn rejects_invalid_options() {
+        assert_eq!(
+            DeletionOptions::from_body(Some(&json!({"purgeData": false}))),
+            Err(DeletionError::DataRequired)
+        );
+        assert_eq!(
+            DeletionOptions::from_body(Some(&json!({"mode": "disable", "soft": false}))),
+            Err(DeletionError::DataRequired)
+        );
+        assert_eq!(
+            DeletionOptions::from_body(Some(&json!({"purge": true}))),
+            Err(DeletionError::InvalidBody)
+        );
+        assert_eq!(
+            DeletionOptions::from_body(Some(&json!({"logout": "yes"}))),
+            Err(DeletionError::InvalidBody)
+        );
+    }
+
+    #[test]
+    fn steps_run_in_a_fixed_order() {
+        assert_eq!(
+            DeletionOptions::LOGOUT_FIRST.steps(),
+            vec![
+                DeletionStep::Logout,
+                DeletionStep::Stop,
+                DeletionStep::PurgeAuth,
+                DeletionStep::PurgeData,
+                DeletionStep::Delete,
+            ]
+        );
+        assert_eq!(
+            DeletionOptions::KEEP_AUTH.steps(),
+            vec![
+                DeletionStep::Stop,
+                DeletionStep::PurgeData,
+                DeletionStep::Delete
+            ]
+        );
+        assert_eq!(
+            DeletionOptions::DISABLE.steps(),
+            vec![DeletionStep::Stop, DeletionStep::Disable]
+        );
+    }
+
+    #[test]
+    fn failed_deletions_report_the_step() {
+        let mut deletion = Deletion {
+            id: Uuid::nil(),
+            instance: "sales".to_string(),
+            options: DeletionOptions::LOGOUT_FIRST,
+            steps: vec![StepReport::failed(DeletionStep::Logout, "not authorized")],
+        };
+        let body = deletion.to_json();
+        assert_eq!(deletion.status_code(), StatusCode::BAD_GATEWAY);
+        assert_eq!(body["status"], "failed");
+        assert_eq!(body["error"], "logout_failed");
+        assert_eq!(body["details"], "not authorized");
+        assert_eq!(body["steps"][0]["step"], "logout");
+        assert_eq!(body["options"]["purgeAuth"], true);
+
+        deletion.steps = vec![
+            StepReport::skipped(DeletionStep::Logout),
+            StepReport::done(DeletionStep::Stop, None),
+            StepReport::done(DeletionStep::PurgeData, Some(42)),
+            StepReport::done(DeletionStep::Delete, None),
+        ];
+        let body = deletion.to_json();
+        assert_eq!(deletion.status_code(), StatusCode::OK);
+        assert_eq!(body["status"], "deleted");
+        assert!(body.get("error").is_none());
+        assert_eq!(body["steps"][0]["status"], "skipped");
+        assert_eq!(body["steps"][2]["rows"], 42);
+    }
+
+    #[test]
+    fn progress_matches_its_schema() {
+        let progress = InstanceDeleteProgress {
+            deletion_id: Uuid::nil().to_string(),
+            step: DeletionStep::PurgeAuth,
+            status: StepStatus::Done,
+            completed: 3,
+            total: 5,
+            done: false,
+            rows: Some(120),
+            error: None,
+        }
+        .to_data();
+        assert_eq!(progress["step"], "purgeAuth");
+        assert_eq!(progress["status"], "done");
+        assert!(progress.get("error").is_none());
+        for field in InstanceDeleteProgress::schema()["required"]
+            .as_array()
+            .into_iter()
+            .flatten()
+        {
+            let field = field.as_str().unwrap_or_default();
+            assert!(progress.get(field).is_some(), "{field} not serialized");
+        }
+    }
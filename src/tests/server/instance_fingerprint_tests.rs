@@ -0,0 +1,77 @@
+    use super::*;
+
+    #[test]
+    fn reads_browser_and_version_from_the_body() {
+        assert_eq!(
+            InstanceFingerprint::from_body(&json!({"session": "sales"})),
+            Ok(InstanceFingerprint::default())
+        );
+
+        let fingerprint = InstanceFingerprint::from_body(&json!({
+            "browser": {"name": " Firefox ", "os": "Windows", "version": "10.0.22631"},
+            "waVersion": "2.3000.1015901307",
+        }))
+        .unwrap();
+        assert_eq!(
+            fingerprint.browser,
+            Some(BrowserProfile {
+                name: "Firefox".to_string(),
+                os: "Windows".to_string(),
+                version: "10.0.22631".to_string(),
+            })
+        );
+        assert_eq!(fingerprint.wa_version, Some((2, 3000, 1015901307)));
+        assert_eq!(
+            fingerprint.browser_json(),
+            Some(json!({"name": "Firefox", "os": "Windows", "version": "10.0.22631"}))
+        );
+        assert_eq!(
+            fingerprint.wa_version_text().as_deref(),
+            Some("2.3000.1015901307")
+        );
+
+        let without_version =
+            InstanceFingerprint::from_body(&json!({"browser": {"name": "Chrome", "os": "Linux"}}))
+                .unwrap();
+        assert_eq!(without_version.browser.unwrap().version, DEFAULT_OS_VERSION);
+    }
+
+    #[test]
+    fn rejects_invalid_fingerprints() {
+        assert_eq!(
+            InstanceFingerprint::from_body(&json!({"browser": "Chrome"})),
+            Err(FingerprintError::BrowserNotObject)
+        );
+        assert_eq!(
+            InstanceFingerprint::from_body(&json!({"browser": {"name": "Chrome"}})),
+            Err(FingerprintError::InvalidField("os"))
+        );
+        assert_eq!(
+            InstanceFingerprint::from_body(
+                &json!({"browser": {"name": "x".repeat(65), "os": "Linux"}})
+            ),
+            Err(FingerprintError::InvalidField("name"))
+        );
+        assert_eq!(
+            InstanceFingerprint::from_body(&json!({"waVersion": "2.3000"})),
+            Err(FingerprintError::InvalidVersion("2.3000".to_string()))
+        );
+        assert_eq!(
+            InstanceFingerprint::from_body(&json!({"waVersion": 2})),
+            Err(FingerprintError::InvalidVersion("2".to_string()))
+        );
+    }
+
+    #[test]
+    fn reads_the_stored_columns() {
+        let stored = InstanceFingerprint::from_row(&json!({
+            "browser": {"name": "Safari", "os": "Mac OS", "version": "14.4"},
+            "wa_version": "2.3000.1",
+        }));
+        assert_eq!(stored.browser.unwrap().name, "Safari");
+        assert_eq!(stored.wa_version, Some((2, 3000, 1)));
+        assert_eq!(
+            InstanceFingerprint::from_row(&json!({"browser": null, "wa_version": null})),
+            InstanceFingerprint::default()
+        );
+    }
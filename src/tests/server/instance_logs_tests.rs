@@ -0,0 +1,93 @@
+    use super::*;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[derive(Debug)]
+    enum Phase {
+        ServerHello,
+    }
+
+    fn capture(logs: &InstanceLogs, f: impl FnOnce()) {
+        let subscriber = tracing_subscriber::registry().with(logs.layer());
+        tracing::subscriber::with_default(subscriber, f);
+    }
+
+    #[test]
+    fn tags_events_by_field_and_span() {
+        let logs = InstanceLogs::new(10);
+        capture(&logs, || {
+            tracing::info!(instance = %"sales", attempt = 2, "Conectando");
+            tracing::warn!(session = "support", "Sessão sem webhook");
+            tracing::info!("Sem instância");
+            let span = tracing::info_span!("instance", instance = %"sales");
+            let _guard = span.enter();
+            tracing::error!(phase = ?Phase::ServerHello, "Handshake falhou");
+        });
+
+        let (sales, last_seq) = logs.recent("sales", 10, Level::TRACE);
+        assert_eq!(last_seq, 3);
+        assert_eq!(sales.len(), 2);
+        assert_eq!(sales[0].message, "Conectando");
+        assert_eq!(sales[0].fields["attempt"], 2);
+        assert_eq!(sales[1].level, Level::ERROR);
+        assert_eq!(sales[1].fields["phase"], "ServerHello");
+
+        let (support, _) = logs.recent("support", 10, Level::TRACE);
+        assert_eq!(support.len(), 1);
+        assert_eq!(support[0].instance, "support");
+    }
+
+    #[test]
+    fn keeps_the_newest_entries_per_instance() {
+        let logs = InstanceLogs::new(3);
+        for n in 0..5 {
+            logs.push(
+                "sales",
+                Level::INFO,
+                "test",
+                format!("linha {n}"),
+                Map::new(),
+            );
+        }
+        logs.push(
+            "sales",
+            Level::DEBUG,
+            "test",
+            "detalhe".to_string(),
+            Map::new(),
+        );
+
+        let (entries, _) = logs.recent("sales", 10, Level::TRACE);
+        let messages: Vec<_> = entries.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(messages, ["linha 3", "linha 4", "detalhe"]);
+
+        let (entries, last_seq) = logs.recent("sales", 1, Level::INFO);
+        assert_eq!(entries[0].message, "linha 4");
+        assert_eq!(last_seq, 6);
+        assert!(logs.recent("other", 10, Level::TRACE).0.is_empty());
+    }
+
+    #[test]
+    fn disabled_buffer_captures_nothing() {
+        let logs = InstanceLogs::new(0);
+        capture(&logs, || tracing::info!(instance = "sales", "Conectando"));
+        assert_eq!(logs.recent("sales", 10, Level::TRACE), (vec![], 0));
+    }
+
+    #[tokio::test]
+    async fn live_subscribers_receive_new_entries() {
+        let logs = InstanceLogs::new(5);
+        let mut live = logs.subscribe();
+        logs.push(
+            "sales",
+            Level::WARN,
+            "test",
+            "Reconectando".to_string(),
+            Map::new(),
+        );
+        let entry = live.recv().await.unwrap();
+        assert_eq!(entry.seq, 1);
+        let json = serde_json::to_value(&*entry).unwrap();
+        assert_eq!(json["level"], "WARN");
+        assert_eq!(json["instance"], "sales");
+        assert!(json.get("fields").is_none());
+    }
@@ -0,0 +1,82 @@
+    use super::*;
+
+    #[test]
+    fn tags_are_normalized_and_deduplicated() {
+        let body = json!({"tags": [" Prod ", "eu", "prod"]});
+        assert_eq!(
+            tags_from_body(&body),
+            Ok(Some(vec!["prod".to_string(), "eu".to_string()]))
+        );
+        assert_eq!(tags_from_body(&json!({})), Ok(None));
+        assert_eq!(tags_from_body(&json!({"tags": null})), Ok(None));
+        assert_eq!(tags_from_body(&json!({"tags": []})), Ok(Some(vec![])));
+    }
+
+    #[test]
+    fn rejects_invalid_tags() {
+        assert_eq!(
+            tags_from_body(&json!({"tags": "prod"})),
+            Err(InstanceMetaError::TagsNotArray)
+        );
+        assert_eq!(
+            tags_from_body(&json!({"tags": ["a,b"]})),
+            Err(InstanceMetaError::InvalidTag("a,b".to_string()))
+        );
+        assert_eq!(
+            tags_from_body(&json!({"tags": ["  "]})),
+            Err(InstanceMetaError::InvalidTag("  ".to_string()))
+        );
+        let many: Vec<String> = (0..=MAX_TAGS).map(|i| format!("t{i}")).collect();
+        assert_eq!(
+            tags_from_body(&json!({"tags": many})),
+            Err(InstanceMetaError::TooManyTags)
+        );
+    }
+
+    #[test]
+    fn metadata_values_must_be_strings() {
+        let body = json!({"metadata": {"region": "eu", "team": "sales"}});
+        let metadata = metadata_from_body(&body).unwrap().unwrap();
+        assert_eq!(metadata["region"], "eu");
+        assert_eq!(
+            metadata_from_body(&json!({"metadata": {"count": 3}})),
+            Err(InstanceMetaError::MetadataNotObject)
+        );
+        assert_eq!(
+            metadata_from_body(&json!({"metadata": {"": "x"}})),
+            Err(InstanceMetaError::InvalidKey(String::new()))
+        );
+        assert_eq!(metadata_from_body(&json!({"metadata": null})), Ok(None));
+    }
+
+    #[test]
+    fn filter_reads_tags_and_metadata_params() {
+        let params: HashMap<String, String> = [
+            ("tag", "Prod, eu,,prod"),
+            ("metadata.region", "eu"),
+            ("metadata.", "ignored"),
+            ("instanceName", "sales"),
+            ("limit", "10"),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+        let filter = InstanceFilter::from_query(&params);
+        assert_eq!(filter.name.as_deref(), Some("sales"));
+        assert_eq!(filter.tags, vec!["prod", "eu"]);
+        assert_eq!(filter.metadata.len(), 1);
+        assert_eq!(filter.metadata["region"], "eu");
+        assert_eq!(InstanceFilter::from_query(&HashMap::new()), InstanceFilter::default());
+    }
+
+    #[test]
+    fn envelope_gets_tags_only_when_present() {
+        let mut envelope = json!({"event": "CONNECTION_UPDATE", "data": {}});
+        InstanceMeta::default().apply_to(&mut envelope);
+        assert!(envelope.get("tags").is_none());
+
+        let meta = InstanceMeta::from_row(&json!({"tags": ["prod"], "metadata": {"region": "eu"}}));
+        meta.apply_to(&mut envelope);
+        assert_eq!(envelope["tags"], json!(["prod"]));
+        assert_eq!(envelope["metadata"], json!({"region": "eu"}));
+    }
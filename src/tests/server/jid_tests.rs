@@ -0,0 +1,30 @@
+    use super::*;
+
+    #[test]
+    fn parses_numbers_and_legacy_ids() {
+        assert_eq!(parse("+55 (11) 99999-0000"), Ok(Jid::pn("5511999990000")));
+        assert_eq!(parse("5511999990000@c.us"), Ok(Jid::pn("5511999990000")));
+        assert_eq!(parse("100000012345678@lid"), Ok(Jid::lid("100000012345678")));
+        assert_eq!(
+            normalize("120363000000000000@g.us").as_deref(),
+            Ok("120363000000000000@g.us")
+        );
+    }
+
+    #[test]
+    fn rejects_empty_and_malformed_values() {
+        assert_eq!(parse("  "), Err(JidError::Empty));
+        assert!(matches!(parse("abc"), Err(JidError::Invalid(_))));
+        assert!(matches!(parse("@s.whatsapp.net"), Err(JidError::Invalid(_))));
+    }
+
+    #[test]
+    fn strips_device_suffixes() {
+        assert_eq!(
+            normalize("5511999990000:12@s.whatsapp.net").as_deref(),
+            Ok("5511999990000@s.whatsapp.net")
+        );
+        assert_eq!(device("5511999990000:12@s.whatsapp.net"), Some(12));
+        assert_eq!(user("100000012345678:3@lid").as_deref(), Some("100000012345678"));
+        assert_eq!(device("5511999990000"), Some(0));
+    }
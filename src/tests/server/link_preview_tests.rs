@@ -0,0 +1,62 @@
+    use super::*;
+
+    #[test]
+    fn first_url_trims_punctuation() {
+        assert_eq!(
+            first_url("veja isso: https://example.com/a?b=1)."),
+            Some("https://example.com/a?b=1")
+        );
+        assert_eq!(first_url("sem link aqui"), None);
+        assert_eq!(first_url("https://"), None);
+    }
+
+    #[test]
+    fn parse_preview_reads_open_graph() {
+        let html = r#"<html><head>
+            <title>Fallback</title>
+            <meta property="og:title" content="Tom &amp; Jerry">
+            <META name="description" content="plain description"/>
+            <meta content='OG description' property='og:description' />
+            <meta property="og:image" content="/img/cover.png">
+        </head></html>"#;
+
+        let preview = parse_preview("https://example.com/news/item", html);
+        assert_eq!(preview.title.as_deref(), Some("Tom & Jerry"));
+        assert_eq!(preview.description.as_deref(), Some("OG description"));
+        assert_eq!(
+            preview.image_url.as_deref(),
+            Some("https://example.com/img/cover.png")
+        );
+    }
+
+    #[test]
+    fn parse_preview_falls_back_to_title_and_description() {
+        let html = "<head><title> Plain page </title><meta name=description content=short></head>";
+
+        let preview = parse_preview("https://example.com", html);
+        assert_eq!(preview.title.as_deref(), Some("Plain page"));
+        assert_eq!(preview.description.as_deref(), Some("short"));
+        assert_eq!(preview.image_url, None);
+    }
+
+    #[test]
+    fn resolve_url_handles_relative_forms() {
+        let base = "https://example.com/a/b.html";
+        assert_eq!(resolve_url(base, "//cdn.example.com/x.jpg"), "https://cdn.example.com/x.jpg");
+        assert_eq!(resolve_url(base, "/x.jpg"), "https://example.com/x.jpg");
+        assert_eq!(resolve_url(base, "x.jpg"), "https://example.com/a/x.jpg");
+        assert_eq!(resolve_url("https://example.com", "x.jpg"), "https://example.com/x.jpg");
+    }
+
+    #[test]
+    fn make_thumbnail_outputs_small_jpeg() {
+        let img = image::RgbaImage::from_pixel(800, 400, image::Rgba([200, 10, 10, 255]));
+        let mut png = std::io::Cursor::new(Vec::new());
+        img.write_to(&mut png, image::ImageFormat::Png).unwrap();
+
+        let thumb = make_thumbnail(png.get_ref()).unwrap();
+        assert_eq!(&thumb.jpeg[..2], &[0xFF, 0xD8]);
+        assert_eq!((thumb.width, thumb.height), (192, 96));
+        let decoded = image::load_from_memory(&thumb.jpeg).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (192, 96));
+    }
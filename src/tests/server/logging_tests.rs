@@ -0,0 +1,44 @@
+    use super::*;
+
+    #[test]
+    fn parses_formats() {
+        assert_eq!(LogFormat::parse("JSON"), Some(LogFormat::Json));
+        assert_eq!(LogFormat::parse(" pretty "), Some(LogFormat::Pretty));
+        assert_eq!(LogFormat::parse("compact"), Some(LogFormat::Compact));
+        assert_eq!(LogFormat::parse("xml"), None);
+    }
+
+    #[test]
+    fn directive_appends_target_levels() {
+        let targets: BTreeMap<String, String> =
+            [("warp_core", "warn"), ("chatwarp_api::server", "debug")]
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect();
+        assert_eq!(
+            filter_directive("info", &targets),
+            "info,chatwarp_api::server=debug,warp_core=warn"
+        );
+        assert_eq!(filter_directive("info", &BTreeMap::new()), "info");
+    }
+
+    #[test]
+    fn rotates_by_size_and_keeps_max_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = LogFileConfig {
+            path: dir.path().join("logs/api.log"),
+            max_bytes: 10,
+            max_files: 2,
+        };
+        let file = RotatingFile::open(&config).unwrap();
+        for line in ["first-line\n", "second-line\n", "third-line\n", "fourth-line\n"] {
+            (&file).write_all(line.as_bytes()).unwrap();
+        }
+        (&file).flush().unwrap();
+
+        let read = |path: PathBuf| fs::read_to_string(path).unwrap();
+        assert_eq!(read(config.path.clone()), "fourth-line\n");
+        assert_eq!(read(rotated_path(&config.path, 1)), "third-line\n");
+        assert_eq!(read(rotated_path(&config.path, 2)), "second-line\n");
+        assert!(!rotated_path(&config.path, 3).exists());
+    }
@@ -0,0 +1,90 @@
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(day: u32, hour: u32, minute: u32) -> DateTime<Utc> {
+        // 2026-04-05 is a Sunday.
+        Utc.with_ymd_and_hms(2026, 4, day, hour, minute, 30).unwrap()
+    }
+
+    #[test]
+    fn parses_lists_ranges_and_steps() {
+        let schedule = CronSchedule::parse("*/15 2-4 * * 1,3").unwrap();
+        assert!(schedule.matches(at(6, 2, 45)));
+        assert!(schedule.matches(at(8, 4, 0)));
+        assert!(!schedule.matches(at(6, 2, 40)));
+        assert!(!schedule.matches(at(7, 3, 0)));
+        assert!(!schedule.matches(at(6, 5, 0)));
+    }
+
+    #[test]
+    fn sunday_is_zero_or_seven() {
+        assert!(CronSchedule::parse("0 3 * * 7").unwrap().matches(at(5, 3, 0)));
+        assert!(CronSchedule::parse("0 3 * * 0").unwrap().matches(at(5, 3, 0)));
+    }
+
+    #[test]
+    fn restricted_day_and_weekday_match_either() {
+        let schedule = CronSchedule::parse("0 0 1 * 0").unwrap();
+        assert!(schedule.matches(at(1, 0, 0)));
+        assert!(schedule.matches(at(5, 0, 0)));
+        assert!(!schedule.matches(at(6, 0, 0)));
+    }
+
+    #[test]
+    fn rejects_invalid_cron() {
+        for expr in [
+            "",
+            "0 3 * *",
+            "60 * * * *",
+            "* 24 * * *",
+            "5-1 * * * *",
+            "*/0 * * * *",
+            "a * * * *",
+        ] {
+            assert_eq!(
+                CronSchedule::parse(expr),
+                Err(MaintenanceError::InvalidCron(expr.to_string())),
+                "{expr}"
+            );
+        }
+    }
+
+    #[test]
+    fn window_is_open_for_its_duration() {
+        let window = MaintenanceWindow {
+            cron: "0 3 * * *".to_string(),
+            duration_minutes: 10,
+        };
+        let schedule = window.schedule().unwrap();
+        let start = Utc.with_ymd_and_hms(2026, 4, 6, 3, 0, 0).unwrap();
+        assert_eq!(window.opened_at(&schedule, at(6, 3, 0)), Some(start));
+        assert_eq!(window.opened_at(&schedule, at(6, 3, 9)), Some(start));
+        assert_eq!(window.opened_at(&schedule, at(6, 3, 10)), None);
+        assert_eq!(window.opened_at(&schedule, at(6, 2, 59)), None);
+
+        let restart = MaintenanceWindow {
+            duration_minutes: 0,
+            ..window
+        };
+        assert_eq!(restart.opened_at(&schedule, at(6, 3, 0)), Some(start));
+        assert_eq!(restart.opened_at(&schedule, at(6, 3, 1)), None);
+    }
+
+    #[test]
+    fn window_from_body_is_validated() {
+        let window = MaintenanceWindow::from_body(&json!({"cron": "30 4 * * *", "durationMinutes": 5}))
+            .unwrap();
+        assert_eq!(window.duration_minutes, 5);
+        assert_eq!(
+            MaintenanceWindow::from_body(&json!({"cron": "30 4 * * *"})).unwrap().duration_minutes,
+            0
+        );
+        assert_eq!(
+            MaintenanceWindow::from_body(&json!({"durationMinutes": 5})),
+            Err(MaintenanceError::InvalidBody)
+        );
+        assert_eq!(
+            MaintenanceWindow::from_body(&json!({"cron": "0 3 * * *", "durationMinutes": 1441})),
+            Err(MaintenanceError::DurationTooLong)
+        );
+    }
@@ -0,0 +1,91 @@
+    use super::*;
+
+    #[test]
+    fn find_media_unwraps_ephemeral_audio() {
+        let message = wa::Message {
+            ephemeral_message: Some(Box::new(wa::message::FutureProofMessage {
+                message: Some(Box::new(wa::Message {
+                    audio_message: Some(Box::new(wa::message::AudioMessage {
+                        direct_path: Some("/v/t62/audio".to_string()),
+                        media_key: Some(vec![1; 32]),
+                        ..Default::default()
+                    })),
+                    ..Default::default()
+                })),
+            })),
+            ..Default::default()
+        };
+
+        let (downloadable, info) = find_media(&message).expect("audio media");
+        assert_eq!(downloadable.direct_path(), Some("/v/t62/audio"));
+        assert_eq!(info.media_type, "audio");
+        assert_eq!(info.mimetype, "audio/ogg; codecs=opus");
+    }
+
+    #[test]
+    fn find_media_keeps_document_name_and_caption() {
+        let message = wa::Message {
+            document_message: Some(Box::new(wa::message::DocumentMessage {
+                mimetype: Some("application/pdf".to_string()),
+                file_name: Some("invoice.pdf".to_string()),
+                caption: Some("march".to_string()),
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+
+        let (_, info) = find_media(&message).expect("document media");
+        assert_eq!(
+            info,
+            MediaInfo {
+                media_type: "document",
+                mimetype: "application/pdf".to_string(),
+                file_name: Some("invoice.pdf".to_string()),
+                caption: Some("march".to_string()),
+            }
+        );
+    }
+
+    #[test]
+    fn find_media_ignores_text_messages() {
+        let message = wa::Message {
+            conversation: Some("hello".to_string()),
+            ..Default::default()
+        };
+
+        assert!(find_media(&message).is_none());
+    }
+
+    #[test]
+    fn downloaded_media_json_contains_base64() {
+        let media = DownloadedMedia {
+            info: MediaInfo {
+                media_type: "image",
+                mimetype: "image/jpeg".to_string(),
+                file_name: None,
+                caption: None,
+            },
+            bytes: b"abc".to_vec(),
+        };
+
+        let value = media.to_json();
+        assert_eq!(value["base64"], "YWJj");
+        assert_eq!(value["size"], 3);
+        assert_eq!(value["mimetype"], "image/jpeg");
+    }
+
+    #[test]
+    fn webhook_base64_skips_media_over_the_limit() {
+        let mut config = RuntimeConfig::default();
+        assert_eq!(
+            webhook_base64_limit(&config),
+            Some(5 * quotas::BYTES_PER_MB)
+        );
+        config.webhook_base64_max_mb = 0;
+        assert_eq!(webhook_base64_limit(&config), None);
+
+        assert!(within_limit(Some(10), Some(10)));
+        assert!(!within_limit(Some(11), Some(10)));
+        assert!(within_limit(None, Some(10)));
+        assert!(within_limit(Some(u64::MAX), None));
+    }
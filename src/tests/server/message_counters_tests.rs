@@ -0,0 +1,80 @@
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn counts_each_outcome_per_instance() {
+        let counters = MessageCounters::default();
+        counters.record("sales", Outcome::Sent);
+        counters.record("sales", Outcome::Sent);
+        counters.record("sales", Outcome::Failed);
+        counters.record("support", Outcome::Received);
+
+        assert_eq!(
+            counters.get("sales"),
+            MessageCounts {
+                sent: 2,
+                received: 0,
+                failed: 1,
+            }
+        );
+        assert_eq!(counters.get("unknown"), MessageCounts::default());
+        let names: Vec<_> = counters
+            .snapshot()
+            .into_iter()
+            .map(|(instance, _)| instance)
+            .collect();
+        assert_eq!(names, ["sales", "support"]);
+    }
+
+    #[test]
+    fn merges_counters_into_stored_totals() {
+        let mut count = json!({"Message": 10, "Contact": 4, "Chat": 3});
+        merge_into_count(
+            &mut count,
+            MessageCounts {
+                sent: 5,
+                received: 7,
+                failed: 1,
+            },
+        );
+        assert_eq!(
+            count,
+            json!({"Message": 10, "Contact": 4, "Chat": 3, "sent": 5, "received": 7, "failed": 1})
+        );
+
+        let mut missing = Value::Null;
+        merge_into_count(&mut missing, MessageCounts::default());
+        assert_eq!(missing, json!({"sent": 0, "received": 0, "failed": 0}));
+    }
+
+    #[test]
+    fn renders_prometheus_counters_with_instance_labels() {
+        let counts = vec![(
+            "sales \"eu\"".to_string(),
+            MessageCounts {
+                sent: 3,
+                received: 8,
+                failed: 1,
+            },
+        )];
+        let stored = vec![StoredTotals {
+            instance: "sales \"eu\"".to_string(),
+            contacts: 12,
+            chats: 4,
+        }];
+        let text = render_prometheus(&counts, &stored);
+
+        assert!(text.contains("# TYPE chatwarp_messages_sent_total counter\n"));
+        assert!(text.contains("chatwarp_messages_sent_total{instance=\"sales \\\"eu\\\"\"} 3\n"));
+        assert!(text.contains("chatwarp_messages_received_total{instance=\"sales \\\"eu\\\"\"} 8\n"));
+        assert!(text.contains("chatwarp_messages_failed_total{instance=\"sales \\\"eu\\\"\"} 1\n"));
+        assert!(text.contains("# TYPE chatwarp_contacts gauge\n"));
+        assert!(text.contains("chatwarp_chats{instance=\"sales \\\"eu\\\"\"} 4\n"));
+    }
+
+    #[test]
+    fn omits_stored_gauges_without_a_store() {
+        let text = render_prometheus(&[], &[]);
+        assert!(text.contains("# TYPE chatwarp_messages_failed_total counter"));
+        assert!(!text.contains("chatwarp_contacts"));
+    }
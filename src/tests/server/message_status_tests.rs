@@ -0,0 +1,72 @@
+    use super::*;
+    use crate::types::events::{MessageAck, Receipt};
+    use crate::types::message::MessageSource;
+
+    fn receipt(kind: ReceiptType, from_me: bool) -> Event {
+        Event::Receipt(Receipt {
+            source: MessageSource {
+                is_from_me: from_me,
+                ..Default::default()
+            },
+            message_ids: vec!["3EB0A".to_string(), "3EB0B".to_string()],
+            timestamp: chrono::Utc::now(),
+            r#type: kind,
+            message_sender: Default::default(),
+        })
+    }
+
+    #[test]
+    fn statuses_only_move_forward() {
+        use MessageStatus::*;
+        assert!(Pending.can_advance_to(Server));
+        assert!(Pending.can_advance_to(Read));
+        assert!(Server.can_advance_to(Delivered));
+        assert!(!Read.can_advance_to(Delivered));
+        assert!(!Delivered.can_advance_to(Delivered));
+        assert!(Server.can_advance_to(Failed));
+        assert!(!Delivered.can_advance_to(Failed));
+        assert!(!Failed.can_advance_to(Played));
+        assert_eq!(Delivered.reachable_from(), vec!["pending", "server"]);
+        assert_eq!(Failed.reachable_from(), vec!["pending", "server"]);
+    }
+
+    #[test]
+    fn maps_receipts_of_the_recipient() {
+        let update = StatusUpdate::from_event(&receipt(ReceiptType::Read, false)).unwrap();
+        assert_eq!(update.status, MessageStatus::Read);
+        assert_eq!(update.message_ids, vec!["3EB0A", "3EB0B"]);
+
+        assert_eq!(StatusUpdate::from_event(&receipt(ReceiptType::Read, true)), None);
+        assert_eq!(StatusUpdate::from_event(&receipt(ReceiptType::Retry, false)), None);
+        assert_eq!(StatusUpdate::from_event(&receipt(ReceiptType::ReadSelf, false)), None);
+        assert_eq!(
+            StatusUpdate::from_event(&receipt(ReceiptType::Played, false)).map(|u| u.status),
+            Some(MessageStatus::Played)
+        );
+    }
+
+    #[test]
+    fn server_ack_errors_fail_the_message() {
+        let ack = |error: Option<&str>| {
+            Event::MessageAck(MessageAck {
+                id: "3EB0A".to_string(),
+                chat: None,
+                timestamp: chrono::Utc::now(),
+                error: error.map(str::to_string),
+            })
+        };
+        let update = StatusUpdate::from_event(&ack(None)).unwrap();
+        assert_eq!(update.status, MessageStatus::Server);
+        assert_eq!(update.error, None);
+
+        let update = StatusUpdate::from_event(&ack(Some("479"))).unwrap();
+        assert_eq!(update.status, MessageStatus::Failed);
+        assert_eq!(update.error.as_deref(), Some("479"));
+    }
+
+    #[test]
+    fn evolution_names() {
+        assert_eq!(MessageStatus::Server.evolution_status(), "SERVER_ACK");
+        assert_eq!(MessageStatus::Delivered.evolution_status(), "DELIVERY_ACK");
+        assert_eq!(serde_json::to_value(MessageStatus::Played).unwrap(), "played");
+    }
@@ -0,0 +1,164 @@
+    use super::*;
+
+    #[test]
+    fn connection_errors_are_transient() {
+        assert!(SendFailure::Timeout.is_transient());
+        assert!(SendFailure::Error(ClientError::NotConnected.into()).is_transient());
+        assert!(
+            SendFailure::Error(anyhow::Error::from(ClientError::NotConnected).context("sending"))
+                .is_transient()
+        );
+        assert!(SendFailure::Error(SocketError::SocketClosed.into()).is_transient());
+    }
+
+    #[test]
+    fn other_errors_are_not_retried() {
+        assert!(!SendFailure::Error(ClientError::NotLoggedIn.into()).is_transient());
+        assert!(!SendFailure::Error(anyhow::anyhow!("invalid jid")).is_transient());
+    }
+
+    #[test]
+    fn retry_delay_doubles_and_caps() {
+        assert_eq!(retry_delay(1), Duration::from_secs(1));
+        assert_eq!(retry_delay(2), Duration::from_secs(2));
+        assert_eq!(retry_delay(3), Duration::from_secs(4));
+        assert_eq!(retry_delay(50), Duration::from_secs(32));
+    }
+
+    #[test]
+    fn crypto_socket_errors_are_not_retried() {
+        let err = ClientError::Socket(SocketError::Crypto("bad key".to_string()));
+        assert!(!SendFailure::Error(err.into()).is_transient());
+    }
+
+    #[test]
+    fn template_payload_builds_hydrated_buttons() {
+        let payload = serde_json::json!({
+            "text": "Olá Ana",
+            "footer": "Loja",
+            "buttons": [
+                {"type": "reply", "text": "Sim"},
+                {"type": "url", "text": "Site", "url": "https://example.com"},
+                {"type": "unknown", "text": "x"}
+            ]
+        });
+        let msg = build_template_message(&payload).unwrap();
+        let template = msg.template_message.unwrap().hydrated_template.unwrap();
+
+        assert_eq!(template.hydrated_content_text.as_deref(), Some("Olá Ana"));
+        assert_eq!(template.hydrated_footer_text.as_deref(), Some("Loja"));
+        assert_eq!(template.hydrated_buttons.len(), 2);
+        assert!(matches!(
+            &template.hydrated_buttons[0].hydrated_button,
+            Some(wa::hydrated_template_button::HydratedButton::QuickReplyButton(b))
+                if b.id.as_deref() == Some("btn-1")
+        ));
+        assert!(build_template_message(&serde_json::json!({"text": " "})).is_none());
+    }
+
+    #[test]
+    fn buttons_payload_builds_view_once_native_flow() {
+        use wa::message::interactive_message::InteractiveMessage;
+
+        let payload = serde_json::json!({
+            "text": "Confirma o pedido?",
+            "title": "Pedido 42",
+            "footer": "Loja",
+            "buttons": [
+                {"type": "reply", "text": "Sim"},
+                {"type": "url", "text": "Site", "url": "https://example.com"},
+                {"type": "call", "text": "Ligar", "phone": "+5511999990000"}
+            ]
+        });
+        let msg = build_buttons_message(&payload).unwrap();
+        let inner = msg.view_once_message.unwrap().message.unwrap();
+        assert_eq!(
+            inner
+                .message_context_info
+                .unwrap()
+                .device_list_metadata_version,
+            Some(2)
+        );
+        let interactive = inner.interactive_message.unwrap();
+        assert_eq!(
+            interactive.body.unwrap().text.as_deref(),
+            Some("Confirma o pedido?")
+        );
+        assert_eq!(
+            interactive.header.unwrap().title.as_deref(),
+            Some("Pedido 42")
+        );
+        let Some(InteractiveMessage::NativeFlowMessage(flow)) = interactive.interactive_message else {
+            panic!("expected a native flow message");
+        };
+        let names: Vec<_> = flow
+            .buttons
+            .iter()
+            .filter_map(|b| b.name.as_deref())
+            .collect();
+        assert_eq!(names, ["quick_reply", "cta_url", "cta_call"]);
+        let reply: serde_json::Value =
+            serde_json::from_str(flow.buttons[0].button_params_json.as_deref().unwrap()).unwrap();
+        assert_eq!(reply["id"], "btn-1");
+        assert_eq!(reply["display_text"], "Sim");
+    }
+
+    #[test]
+    fn buttons_payload_rejects_invalid_buttons() {
+        let button = serde_json::json!({"type": "reply", "text": "Sim"});
+        for payload in [
+            serde_json::json!({"text": "Oi"}),
+            serde_json::json!({"text": "Oi", "buttons": []}),
+            serde_json::json!({"text": "Oi", "buttons": [button, button, button, button]}),
+            serde_json::json!({"text": "Oi", "buttons": [{"type": "url", "text": "Site"}]}),
+            serde_json::json!({"text": "Oi", "buttons": [{"type": "other", "text": "x"}]}),
+            serde_json::json!({"text": " ", "buttons": [button]}),
+        ] {
+            assert!(build_buttons_message(&payload).is_none(), "{payload}");
+        }
+    }
+
+    #[test]
+    fn list_payload_builds_single_select_list() {
+        use wa::message::list_message::ListType;
+
+        let payload = serde_json::json!({
+            "text": "Escolha um horário",
+            "buttonText": "Horários",
+            "footer": "Clínica",
+            "sections": [
+                {"title": "Manhã", "rows": [{"title": "9h"}, {"title": "10h", "id": "h10"}]},
+                {"title": "Tarde", "rows": [{"title": "14h", "description": "Com fila"}]}
+            ]
+        });
+        let msg = build_list_message(&payload).unwrap();
+        let list = msg
+            .view_once_message
+            .unwrap()
+            .message
+            .unwrap()
+            .list_message
+            .unwrap();
+        assert_eq!(list.list_type, Some(ListType::SingleSelect as i32));
+        assert_eq!(list.button_text.as_deref(), Some("Horários"));
+        assert_eq!(list.footer_text.as_deref(), Some("Clínica"));
+        let ids: Vec<_> = list
+            .sections
+            .iter()
+            .flat_map(|section| &section.rows)
+            .filter_map(|row| row.row_id.as_deref())
+            .collect();
+        assert_eq!(ids, ["row-1", "h10", "row-3"]);
+
+        assert!(
+            build_list_message(&serde_json::json!({"text": "x", "buttonText": "y", "sections": []}))
+                .is_none()
+        );
+        assert!(
+            build_list_message(&serde_json::json!({
+                "text": "x",
+                "sections": [{"rows": [{"title": "a"}]}]
+            }))
+            .is_none()
+        );
+    }
@@ -0,0 +1,48 @@
+    use super::*;
+    use crate::server::sink_scope::InstanceScope;
+
+    #[test]
+    fn subject_uses_instance_and_event_tokens() {
+        assert_eq!(
+            subject("chatwarp", Some("sales"), "MESSAGES_UPSERT"),
+            "chatwarp.sales.MESSAGES_UPSERT"
+        );
+        assert_eq!(
+            subject("chatwarp", None, "APPLICATION_STARTUP"),
+            "chatwarp.global.APPLICATION_STARTUP"
+        );
+    }
+
+    #[test]
+    fn subject_tokens_cannot_add_levels_or_wildcards() {
+        assert_eq!(subject("cw", Some("a.b *>"), "X"), "cw.a_b___.X");
+    }
+
+    #[test]
+    fn global_subject_puts_the_event_first() {
+        assert_eq!(
+            global_subject("chatwarp-global", Some("sales"), "MESSAGES_UPSERT"),
+            "chatwarp-global.MESSAGES_UPSERT.sales"
+        );
+        assert_eq!(
+            global_subject("chatwarp-global", None, "APPLICATION_STARTUP"),
+            "chatwarp-global.APPLICATION_STARTUP.global"
+        );
+        assert_eq!(global_subject("g", Some("a.b"), "X>"), "g.X_.a_b");
+    }
+
+    #[test]
+    fn stream_covers_the_global_subjects_when_on() {
+        let mut config = NatsConfig {
+            url: "nats://127.0.0.1:4222".to_string(),
+            subject_prefix: "events".to_string(),
+            jetstream: true,
+            stream: "CHATWARP".to_string(),
+            global: true,
+            global_subject_prefix: "events-global".to_string(),
+            scope: InstanceScope::default(),
+        };
+        assert_eq!(stream_subjects(&config), ["events.>", "events-global.>"]);
+        config.global = false;
+        assert_eq!(stream_subjects(&config), ["events.>"]);
+    }
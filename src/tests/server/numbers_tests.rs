@@ -0,0 +1,88 @@
+    use super::*;
+    use serde_json::json;
+
+    fn result(jid: &str, registered: bool, query: Option<&str>) -> IsOnWhatsAppResult {
+        IsOnWhatsAppResult {
+            jid: jid.parse().unwrap(),
+            is_registered: registered,
+            query: query.map(str::to_string),
+        }
+    }
+
+    fn body(numbers: serde_json::Value) -> WhatsappNumbers {
+        serde_json::from_value(numbers).unwrap()
+    }
+
+    #[test]
+    fn numbers_are_digits_without_duplicates() {
+        let body = body(json!({"numbers": ["+55 (11) 99999-0000", "5511999990000@s.whatsapp.net", "14155550100"]}));
+        assert_eq!(
+            numbers_from_body(&body),
+            Ok(vec!["5511999990000".to_string(), "14155550100".to_string()])
+        );
+    }
+
+    #[test]
+    fn rejects_missing_and_invalid_numbers() {
+        assert_eq!(numbers_from_body(&body(json!({}))), Err(NumbersError::Missing));
+        assert_eq!(numbers_from_body(&body(json!({"numbers": []}))), Err(NumbersError::Missing));
+        assert!(serde_json::from_value::<WhatsappNumbers>(json!({"numbers": [1]})).is_err());
+        assert!(matches!(
+            numbers_from_body(&body(json!({"numbers": ["abc"]}))),
+            Err(NumbersError::Invalid(_))
+        ));
+        let many: Vec<String> = (0..=MAX_NUMBERS).map(|n| n.to_string()).collect();
+        assert_eq!(numbers_from_body(&body(json!({"numbers": many}))), Err(NumbersError::TooMany));
+    }
+
+    #[test]
+    fn matches_results_by_query_then_jid() {
+        let numbers = vec![
+            "5511999990000".to_string(),
+            "14155550100".to_string(),
+            "447700900000".to_string(),
+        ];
+        let results = vec![
+            // Brazilian numbers may come back without the ninth digit.
+            result("551199990000@s.whatsapp.net", true, Some("+5511999990000")),
+            result("14155550100@s.whatsapp.net", false, None),
+        ];
+        assert_eq!(
+            match_results(&numbers, &results),
+            vec![
+                NumberStatus {
+                    exists: true,
+                    jid: "551199990000@s.whatsapp.net".to_string(),
+                    number: "5511999990000".to_string(),
+                },
+                NumberStatus {
+                    exists: false,
+                    jid: "14155550100@s.whatsapp.net".to_string(),
+                    number: "14155550100".to_string(),
+                },
+                NumberStatus {
+                    exists: false,
+                    jid: "447700900000@s.whatsapp.net".to_string(),
+                    number: "447700900000".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn cache_expires_and_can_be_disabled() {
+        let status = NumberStatus {
+            exists: true,
+            jid: "14155550100@s.whatsapp.net".to_string(),
+            number: "14155550100".to_string(),
+        };
+
+        let cache = NumberCache::default();
+        cache.insert("sales", &status);
+        assert_eq!(cache.get("sales", "14155550100"), Some(status.clone()));
+        assert_eq!(cache.get("support", "14155550100"), None);
+
+        let disabled = NumberCache::new(Duration::ZERO);
+        disabled.insert("sales", &status);
+        assert_eq!(disabled.get("sales", "14155550100"), None);
+    }
@@ -0,0 +1,56 @@
+    use super::*;
+
+    #[test]
+    fn event_carries_its_id_in_the_envelope() {
+        let event = OutboxEvent::new(
+            Some("sales"),
+            "CONNECTION_UPDATE",
+            serde_json::json!({"status": "open"}),
+        );
+        assert_eq!(event.session.as_deref(), Some("sales"));
+        assert_eq!(event.payload["event"], "CONNECTION_UPDATE");
+        assert_eq!(event.payload["instance"], "sales");
+        assert_eq!(event.payload["data"]["status"], "open");
+        assert_eq!(event.payload["eventId"], event.id.to_string());
+        assert_ne!(
+            OutboxEvent::new(None, "CONTACTS_SET", Value::Null).id,
+            event.id
+        );
+    }
+
+    #[test]
+    fn insert_statement_binds_the_event() {
+        let event = OutboxEvent::new(None, "APPLICATION_STARTUP", serde_json::json!({}));
+        let (sql, binds) = event.insert_statement();
+        assert!(sql.starts_with("INSERT INTO event_outbox"));
+        assert_eq!(binds.len(), 4);
+        assert!(matches!(binds[0], ApiBind::Uuid(id) if id == event.id));
+        assert!(matches!(&binds[1], ApiBind::NullableText(None)));
+        assert!(matches!(&binds[2], ApiBind::Text(name) if name == "APPLICATION_STARTUP"));
+    }
+
+    #[test]
+    fn parses_claimed_rows() {
+        let event = OutboxEvent::new(
+            Some("sales"),
+            "MESSAGES_UPDATE",
+            serde_json::json!({"id": 1}),
+        );
+        let row = serde_json::json!({"value": {
+            "id": event.id.to_string(),
+            "seq": 7,
+            "session": "sales",
+            "event": "MESSAGES_UPDATE",
+            "payload": event.payload.clone(),
+        }});
+        assert_eq!(OutboxEvent::from_row(&row), Some(event));
+
+        assert_eq!(
+            OutboxEvent::from_row(&serde_json::json!({"value": {"id": "nope", "event": "X"}})),
+            None
+        );
+        assert_eq!(
+            OutboxEvent::from_row(&serde_json::json!({"value": {"id": Uuid::new_v4().to_string()}})),
+            None
+        );
+    }
@@ -0,0 +1,150 @@
+    use super::*;
+
+    const SPEC: ListSpec = ListSpec {
+        default_limit: 20,
+        max_limit: 100,
+        sort_fields: &[
+            ("lastMessageAt", "last_message_at"),
+            ("title", "title"),
+            ("id", "id"),
+        ],
+        default_sort: "-lastMessageAt",
+        tiebreak: &["id"],
+    };
+
+    fn query(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn defaults_and_limits() {
+        let page = PageRequest::from_query(&SPEC, &HashMap::new()).unwrap();
+        assert_eq!((page.limit, page.offset), (20, 0));
+        assert_eq!(page.sort_param(), "-lastMessageAt");
+        assert_eq!(
+            page.order_by(),
+            "ORDER BY last_message_at DESC NULLS LAST, id"
+        );
+
+        let page = PageRequest::from_query(&SPEC, &query(&[("limit", "500"), ("page", "3")])).unwrap();
+        assert_eq!((page.limit, page.offset), (100, 200));
+
+        let page = PageRequest::from_body(&SPEC, &json!({"limit": 10, "offset": "15"})).unwrap();
+        assert_eq!((page.limit, page.offset), (10, 15));
+    }
+
+    #[test]
+    fn rejects_invalid_parameters() {
+        assert_eq!(
+            PageRequest::from_query(&SPEC, &query(&[("limit", "0")])),
+            Err(PaginationError::InvalidNumber("limit"))
+        );
+        assert_eq!(
+            PageRequest::from_query(&SPEC, &query(&[("page", "0")])),
+            Err(PaginationError::InvalidNumber("page"))
+        );
+        assert_eq!(
+            PageRequest::from_query(&SPEC, &query(&[("offset", "-1")])),
+            Err(PaginationError::InvalidNumber("offset"))
+        );
+        assert!(matches!(
+            PageRequest::from_query(&SPEC, &query(&[("sort", "title,-secret")])),
+            Err(PaginationError::UnknownSort { field, .. }) if field == "secret"
+        ));
+        assert_eq!(
+            PageRequest::from_query(&SPEC, &query(&[("cursor", "not a cursor")])),
+            Err(PaginationError::InvalidCursor)
+        );
+    }
+
+    #[test]
+    fn sort_keeps_the_first_mention_and_adds_the_tiebreak() {
+        let page = PageRequest::from_query(&SPEC, &query(&[("sort", "title, -title ,+lastMessageAt")]))
+            .unwrap();
+        assert_eq!(page.sort_param(), "title,lastMessageAt");
+        assert_eq!(
+            page.order_by(),
+            "ORDER BY title ASC NULLS LAST, last_message_at ASC NULLS LAST, id"
+        );
+
+        let page = PageRequest::from_query(&SPEC, &query(&[("sort", "-id")])).unwrap();
+        assert_eq!(page.order_by(), "ORDER BY id DESC NULLS LAST");
+    }
+
+    #[test]
+    fn cursor_continues_with_the_same_sort() {
+        let first =
+            PageRequest::from_query(&SPEC, &query(&[("limit", "10"), ("sort", "title")])).unwrap();
+        let info = first.info(10, 25);
+        assert_eq!(info.total, Some(25));
+        assert_eq!(info.page, Some(1));
+        assert!(info.has_more);
+        let cursor = info.next_cursor.clone().unwrap();
+
+        let second =
+            PageRequest::from_query(&SPEC, &query(&[("limit", "10"), ("cursor", &cursor)])).unwrap();
+        assert_eq!(second.offset, 10);
+        assert_eq!(second.sort_param(), "title");
+
+        assert_eq!(
+            PageRequest::from_query(&SPEC, &query(&[("cursor", &cursor), ("sort", "-title")])),
+            Err(PaginationError::CursorSortMismatch("title".to_string()))
+        );
+
+        let last = second.info(5, 15);
+        assert!(!last.has_more);
+        assert_eq!(last.next_cursor, None);
+        assert_eq!(
+            serde_json::to_value(&last).unwrap(),
+            json!({
+                "limit": 10, "offset": 10, "page": 2, "total": 15,
+                "hasMore": false, "nextCursor": null, "sort": "title"
+            })
+        );
+    }
+
+    fn jids(page: &[Value]) -> Vec<&str> {
+        page.iter()
+            .map(|item| item["jid"].as_str().unwrap_or_default())
+            .collect()
+    }
+
+    #[test]
+    fn pages_in_memory_lists() {
+        let items = vec![
+            json!({"jid": "3@g.us", "groupName": "beta"}),
+            json!({"jid": "1@g.us", "groupName": null}),
+            json!({"jid": "2@g.us", "groupName": "Alpha"}),
+            json!({"jid": "4@g.us", "groupName": "beta"}),
+        ];
+        let spec = ListSpec {
+            sort_fields: &[("groupName", "groupName"), ("jid", "jid")],
+            default_sort: "groupName",
+            tiebreak: &["jid"],
+            ..SPEC
+        };
+
+        let page = PageRequest::from_query(&spec, &query(&[("limit", "3")])).unwrap();
+        let (items_page, total) = page.apply(items.clone());
+        assert_eq!(total, 4);
+        assert_eq!(jids(&items_page), vec!["2@g.us", "3@g.us", "4@g.us"]);
+
+        let page =
+            PageRequest::from_query(&spec, &query(&[("sort", "-groupName"), ("offset", "1")])).unwrap();
+        let (items_page, _) = page.apply(items);
+        assert_eq!(jids(&items_page), vec!["4@g.us", "2@g.us", "1@g.us"]);
+    }
+
+    #[test]
+    fn keyset_pages_only_point_forward_when_there_is_more() {
+        let info = PageInfo::keyset(100, false, Some("evt".to_string()));
+        assert_eq!(info.next_cursor, None);
+        let value = serde_json::to_value(PageInfo::keyset(100, true, Some("evt".to_string()))).unwrap();
+        assert_eq!(
+            value,
+            json!({"limit": 100, "hasMore": true, "nextCursor": "evt"})
+        );
+    }
@@ -0,0 +1,154 @@
+    use super::*;
+
+    fn brazil() -> ParticipantConfig {
+        ParticipantConfig {
+            default_country_code: Some("55".to_string()),
+            ..ParticipantConfig::default()
+        }
+    }
+
+    fn normalized(config: &ParticipantConfig, raw: &str) -> Result<String, NumberError> {
+        config
+            .normalize(raw, Target::Chat)
+            .map(|jid| jid.to_string())
+    }
+
+    #[test]
+    fn normalizes_number_formats() {
+        let config = brazil();
+        for raw in [
+            "+55 (11) 99999-0000",
+            "5511999990000",
+            "005511999990000",
+            "11 99999-0000",
+            "011999990000",
+            "5511999990000@c.us",
+            "5511999990000:3@s.whatsapp.net",
+        ] {
+            assert_eq!(
+                normalized(&config, raw).as_deref(),
+                Ok("5511999990000@s.whatsapp.net"),
+                "{raw}"
+            );
+        }
+    }
+
+    #[test]
+    fn keeps_local_numbers_without_a_default_country_code() {
+        let config = ParticipantConfig::default();
+        assert_eq!(
+            normalized(&config, "11999990000").as_deref(),
+            Ok("11999990000@s.whatsapp.net")
+        );
+        assert_eq!(
+            normalized(&config, "+1 415 555 0100").as_deref(),
+            Ok("14155550100@s.whatsapp.net")
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_numbers() {
+        let config = brazil();
+        assert_eq!(
+            normalized(&config, " "),
+            Err(NumberError::Jid(JidError::Empty))
+        );
+        assert_eq!(
+            normalized(&config, "55119abc"),
+            Err(NumberError::Characters("55119abc".to_string()))
+        );
+        assert_eq!(normalized(&config, "+123"), Err(NumberError::Length(3)));
+        assert_eq!(
+            normalized(&config, "+1234567890123456"),
+            Err(NumberError::Length(16))
+        );
+        assert!(matches!(
+            normalized(&config, "abc@s.whatsapp.net"),
+            Err(NumberError::Jid(JidError::Invalid(_)))
+        ));
+    }
+
+    #[test]
+    fn participants_must_be_users() {
+        let config = brazil();
+        assert_eq!(
+            normalized(&config, "120363000000000000@g.us").as_deref(),
+            Ok("120363000000000000@g.us")
+        );
+        assert_eq!(
+            config
+                .normalize("100000012345678@lid", Target::User)
+                .map(|jid| jid.to_string())
+                .as_deref(),
+            Ok("100000012345678@lid")
+        );
+        assert_eq!(
+            config.normalize("120363000000000000@g.us", Target::User),
+            Err(NumberError::NotAUser("120363000000000000@g.us".to_string()))
+        );
+    }
+
+    #[test]
+    fn rewrites_body_fields() {
+        let mut body = json!({
+            "number": "+55 11 99999-0000",
+            "text": "hi",
+            "participants": ["11 98888-0000", {"id": "5511977770000@c.us"}],
+        });
+        assert!(brazil().normalize_body(&mut body).is_empty());
+        assert_eq!(
+            body,
+            json!({
+                "number": "5511999990000@s.whatsapp.net",
+                "text": "hi",
+                "participants": [
+                    "5511988880000@s.whatsapp.net",
+                    {"id": "5511977770000@s.whatsapp.net"},
+                ],
+            })
+        );
+    }
+
+    #[test]
+    fn lists_every_invalid_entry() {
+        let mut body = json!({
+            "chatId": "abc",
+            "participants": ["5511999990000", "120363000000000000@g.us", true],
+        });
+        let invalid = brazil().normalize_body(&mut body);
+        let fields: Vec<_> = invalid.iter().map(|entry| entry.field.as_str()).collect();
+        assert_eq!(fields, ["chatId", "participants[1]", "participants[2]"]);
+        assert_eq!(invalid[0].value, json!("abc"));
+    }
+
+    #[test]
+    fn only_message_and_group_writes_are_normalized() {
+        assert!(normalizes(
+            &Method::POST,
+            "/message/:operation/:instance_name"
+        ));
+        assert!(normalizes(&Method::POST, "/group/create/:instance_name"));
+        assert!(normalizes(
+            &Method::POST,
+            "/:session/groups/:id/participants/add"
+        ));
+        assert!(normalizes(&Method::POST, "/sendMessage"));
+        assert!(!normalizes(
+            &Method::GET,
+            "/message/status/:instance_name/:message_id"
+        ));
+        assert!(!normalizes(
+            &Method::POST,
+            "/chat/whatsappNumbers/:instance_name"
+        ));
+    }
+
+    #[test]
+    fn only_json_bodies_are_read() {
+        let mut headers = HeaderMap::new();
+        assert!(!is_json(&headers));
+        headers.insert(header::CONTENT_TYPE, "application/json; charset=utf-8".parse().unwrap());
+        assert!(is_json(&headers));
+        headers.insert(header::CONTENT_TYPE, "multipart/form-data; boundary=x".parse().unwrap());
+        assert!(!is_json(&headers));
+    }
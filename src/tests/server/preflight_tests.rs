@@ -0,0 +1,184 @@
+    use super::*;
+    use std::collections::HashMap;
+
+    fn check(vars: &[(&str, &str)]) -> PreflightReport {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        PreflightReport::check(&Env::from_lookup(|name| vars.get(name).cloned()))
+    }
+
+    fn variables(report: &PreflightReport, severity: Severity) -> Vec<&str> {
+        report
+            .findings
+            .iter()
+            .filter(|f| f.severity == severity)
+            .map(|f| f.variable.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn defaults_pass() {
+        let report = check(&[]);
+        assert!(report.findings.is_empty(), "{report}");
+        assert!(!report.has_errors());
+    }
+
+    #[test]
+    fn malformed_port_and_urls_are_errors() {
+        let report = check(&[
+            ("PORT", "80a"),
+            ("SERVER_URL", "localhost:8080"),
+            ("WEBHOOK_GLOBAL_URL", "https://"),
+            ("HTTP_PROXY_URL", "http://proxy:3128"),
+        ]);
+        assert_eq!(
+            variables(&report, Severity::Error),
+            ["PORT", "SERVER_URL", "WEBHOOK_GLOBAL_URL"]
+        );
+        assert!(report.has_errors());
+    }
+
+    #[test]
+    fn enabled_webhook_requires_url() {
+        let report = check(&[("WEBHOOK_GLOBAL_ENABLED", "true")]);
+        assert_eq!(variables(&report, Severity::Error), ["WEBHOOK_GLOBAL_URL"]);
+    }
+
+    #[test]
+    fn master_key_needs_postgres() {
+        let key = "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=";
+        let report = check(&[("SECRETS_MASTER_KEY", key)]);
+        assert_eq!(variables(&report, Severity::Error), ["SECRETS_MASTER_KEY"]);
+        let report = check(&[
+            ("DATABASE_URL", "postgres://db/chatwarp"),
+            ("SECRETS_MASTER_KEY", "not base64"),
+        ]);
+        assert_eq!(variables(&report, Severity::Error), ["SECRETS_MASTER_KEY"]);
+        let report = check(&[
+            ("DATABASE_URL", "postgres://db/chatwarp"),
+            ("SECRETS_MASTER_KEY", key),
+        ]);
+        assert!(!report.has_errors(), "{report}");
+    }
+
+    #[test]
+    fn leases_need_postgres() {
+        let report = check(&[("STANDBY_MODE", "true"), ("INSTANCE_LEASES", "true")]);
+        assert_eq!(variables(&report, Severity::Error), ["STANDBY_MODE"]);
+        let report = check(&[("INSTANCE_LEASES", "1")]);
+        assert_eq!(variables(&report, Severity::Error), ["INSTANCE_LEASES"]);
+        let report = check(&[
+            ("DATABASE_URL", "postgres://db/chatwarp"),
+            ("STANDBY_MODE", "true"),
+            ("INSTANCE_LEASE_SECS", "15"),
+        ]);
+        assert!(report.findings.is_empty(), "{report}");
+    }
+
+    #[test]
+    fn tls_settings_are_checked() {
+        let report = check(&[("TLS_CERT_PATH", "/etc/ssl/cert.pem")]);
+        assert_eq!(variables(&report, Severity::Error), ["TLS_KEY_PATH"]);
+        let report = check(&[("TLS_ACME_ENABLED", "true"), ("SERVER_URL", "http://")]);
+        assert!(variables(&report, Severity::Error).contains(&"TLS_ACME_DOMAINS"));
+        let report = check(&[
+            ("TLS_CERT_PATH", "/nonexistent/cert.pem"),
+            ("TLS_KEY_PATH", "/nonexistent/key.pem"),
+        ]);
+        assert!(report.has_errors());
+    }
+
+    #[test]
+    fn database_errors_name_the_variable() {
+        let report = check(&[("DATABASE_PROVIDER", "postgresql")]);
+        assert_eq!(variables(&report, Severity::Error), ["DATABASE_URL"]);
+        let report = check(&[("DATABASE_PROVIDER", "mysql")]);
+        assert_eq!(variables(&report, Severity::Error), ["DATABASE_PROVIDER"]);
+        let report = check(&[("DATABASE_PROVIDER", "sqlite"), ("DATABASE_URL", "postgres://db/x")]);
+        assert_eq!(variables(&report, Severity::Error), ["DATABASE_URL"]);
+    }
+
+    #[test]
+    fn ignored_values_are_warnings() {
+        let report = check(&[
+            ("OUTBOX_POLL_MS", "0"),
+            ("RATE_LIMIT_PER_MINUTE", "-1"),
+            ("MAINTENANCE_MODE", "yes"),
+            ("WS_LAG_POLICY", "block"),
+            ("HEALTH_CRITICAL", "database,redis"),
+            ("WA_VERSION_SOURCE", "github"),
+            (
+                "MANAGER_CORS_ORIGINS",
+                "https://*.example.com, https://a.*.example.com",
+            ),
+        ]);
+        assert!(!report.has_errors(), "{report}");
+        assert_eq!(
+            variables(&report, Severity::Warning),
+            [
+                "RATE_LIMIT_PER_MINUTE",
+                "OUTBOX_POLL_MS",
+                "MAINTENANCE_MODE",
+                "WS_LAG_POLICY",
+                "WA_VERSION_SOURCE",
+                "HEALTH_CRITICAL",
+                "MANAGER_CORS_ORIGINS",
+            ]
+        );
+    }
+
+    #[test]
+    fn meta_webhook_needs_both_secrets() {
+        let report = check(&[("META_VERIFY_TOKEN", "t")]);
+        assert_eq!(variables(&report, Severity::Warning), ["META_APP_SECRET"]);
+        let report = check(&[("META_APP_SECRET", "s")]);
+        assert_eq!(variables(&report, Severity::Warning), ["META_VERIFY_TOKEN"]);
+        let report = check(&[("META_APP_SECRET", "s"), ("META_VERIFY_TOKEN", "t")]);
+        assert!(variables(&report, Severity::Warning).is_empty());
+    }
+
+    #[test]
+    fn paths_are_checked() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("upload");
+        std::fs::write(&file, b"").unwrap();
+        let report = check(&[
+            ("LOG_FILE", dir.path().to_str().unwrap()),
+            ("MEDIA_UPLOAD_DIR", file.to_str().unwrap()),
+            ("FFMPEG_PATH", dir.path().join("ffmpeg").to_str().unwrap()),
+        ]);
+        assert_eq!(
+            variables(&report, Severity::Error),
+            ["LOG_FILE", "MEDIA_UPLOAD_DIR"]
+        );
+        assert_eq!(variables(&report, Severity::Warning), ["FFMPEG_PATH"]);
+    }
+
+    #[test]
+    fn display_lists_errors_first() {
+        let report = check(&[("MAINTENANCE_MODE", "on"), ("PORT", "0")]);
+        let text = report.to_string();
+        let lines: Vec<&str> = text.lines().collect();
+        assert!(lines[0].starts_with("error"), "{text}");
+        assert!(lines[1].starts_with("warning"), "{text}");
+        assert_eq!(lines[2], "configuration check: 1 error(s), 1 warning(s)");
+    }
+
+    #[test]
+    fn cache_redis_uri_must_be_a_redis_url() {
+        let report = check(&[
+            ("CACHE_REDIS_ENABLED", "true"),
+            ("CACHE_REDIS_URI", "localhost:6379"),
+            ("CACHE_LOCAL_TTL", "0"),
+        ]);
+        assert_eq!(variables(&report, Severity::Error), ["CACHE_REDIS_URI"]);
+        assert!(variables(&report, Severity::Warning).contains(&"CACHE_LOCAL_TTL"));
+
+        let report = check(&[
+            ("CACHE_REDIS_ENABLED", "true"),
+            ("CACHE_REDIS_URI", "rediss://cache:6380/0"),
+        ]);
+        assert!(!report.has_errors(), "{report}");
+    }
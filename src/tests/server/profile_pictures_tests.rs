@@ -0,0 +1,88 @@
+    use super::*;
+    use serde_json::json;
+
+    const URL: &str = "https://pps.whatsapp.net/v/t61.24694-24/123_n.jpg?ccb=11-4&oh=01_Q5AaI&oe=6700A4B0&_nc_sid=5e03e0";
+
+    #[test]
+    fn reads_number_and_size() {
+        let (jid, size) = request_from_body(&json!({"number": "+55 11 99999-0000"})).unwrap();
+        assert_eq!(jid.to_string(), "5511999990000@s.whatsapp.net");
+        assert_eq!(size, PictureSize::Image);
+
+        let (jid, size) = request_from_body(&json!({
+            "number": "5511999990000:4@s.whatsapp.net",
+            "type": "Preview"
+        }))
+        .unwrap();
+        assert_eq!(jid.to_string(), "5511999990000@s.whatsapp.net");
+        assert_eq!(size, PictureSize::Preview);
+    }
+
+    #[test]
+    fn rejects_bad_requests() {
+        assert_eq!(
+            request_from_body(&json!({})).unwrap_err(),
+            ProfilePictureError::MissingNumber
+        );
+        assert_eq!(
+            request_from_body(&json!({"number": "5511999990000", "type": "huge"})).unwrap_err(),
+            ProfilePictureError::InvalidType("huge".to_string())
+        );
+        assert!(matches!(
+            request_from_body(&json!({"number": "abc"})),
+            Err(ProfilePictureError::InvalidNumber(_))
+        ));
+    }
+
+    #[test]
+    fn reads_expiry_from_cdn_url() {
+        assert_eq!(url_expiry(URL).map(|at| at.timestamp()), Some(0x6700A4B0));
+        assert_eq!(url_expiry("https://pps.whatsapp.net/v/pic.jpg"), None);
+        assert_eq!(url_expiry("https://pps.whatsapp.net/v/pic.jpg?oe=zz"), None);
+    }
+
+    #[test]
+    fn caches_until_shortly_before_the_url_expires() {
+        let ttl = Duration::from_secs(3600);
+        let now = DateTime::from_timestamp(0x6700A4B0 - 86_400, 0).unwrap();
+        assert_eq!(
+            cache_until(Some(URL), now, ttl).map(|at| at.timestamp()),
+            Some(0x6700A4B0 - 300)
+        );
+        // No expiry in the URL, or no picture at all: the configured TTL.
+        assert_eq!(
+            cache_until(Some("https://example.com/pic.jpg"), now, ttl),
+            Some(now + chrono::Duration::seconds(3600))
+        );
+        assert_eq!(
+            cache_until(None, now, ttl),
+            Some(now + chrono::Duration::seconds(3600))
+        );
+        // Already (nearly) expired links and a disabled cache are not stored.
+        let late = DateTime::from_timestamp(0x6700A4B0 - 60, 0).unwrap();
+        assert_eq!(cache_until(Some(URL), late, ttl), None);
+        assert_eq!(cache_until(Some(URL), now, Duration::ZERO), None);
+    }
+
+    #[test]
+    fn serializes_evolution_fields() {
+        let picture = ProfilePictureUrl {
+            wuid: "5511999990000@s.whatsapp.net".to_string(),
+            profile_picture_url: None,
+            picture_id: None,
+            size: PictureSize::Preview,
+            expires_at: None,
+            cached: true,
+        };
+        assert_eq!(
+            serde_json::to_value(&picture).unwrap(),
+            json!({
+                "wuid": "5511999990000@s.whatsapp.net",
+                "profilePictureUrl": null,
+                "pictureId": null,
+                "type": "preview",
+                "expiresAt": null,
+                "cached": true
+            })
+        );
+    }
@@ -0,0 +1,77 @@
+    use super::*;
+
+    const CODE: &str = "2@abcdefghijklmnopqrstuvwxyz,0123456789ABCDEF,ghijklmnop,qrstuvwxyz";
+
+    #[test]
+    fn test_parse_format_aliases() {
+        assert_eq!(QrFormat::parse("PNG"), Some(QrFormat::Png));
+        assert_eq!(QrFormat::parse("unicode"), Some(QrFormat::Terminal));
+        assert_eq!(QrFormat::parse("compact"), Some(QrFormat::Ascii));
+        assert_eq!(QrFormat::parse("text"), Some(QrFormat::Raw));
+        assert_eq!(QrFormat::parse("bmp"), None);
+    }
+
+    #[test]
+    fn test_render_png_has_signature() {
+        let rendered = render_qr(CODE, QrRenderOptions::default()).unwrap();
+        assert_eq!(rendered.format, QrFormat::Png);
+        assert_eq!(&rendered.body[..8], b"\x89PNG\r\n\x1a\n");
+    }
+
+    #[test]
+    fn test_render_svg_respects_size_clamp() {
+        let rendered = render_qr(
+            CODE,
+            QrRenderOptions {
+                format: QrFormat::Svg,
+                size: 10_000,
+                quiet_zone: true,
+            },
+        )
+        .unwrap();
+        let svg = String::from_utf8(rendered.body).unwrap();
+        assert!(svg.starts_with("<?xml"));
+        assert!(svg.len() < 200_000);
+    }
+
+    #[test]
+    fn test_render_text_formats() {
+        let terminal = render_qr(
+            CODE,
+            QrRenderOptions {
+                format: QrFormat::Terminal,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert!(String::from_utf8(terminal.body).unwrap().contains('\u{2588}'));
+
+        let ascii = render_qr(
+            CODE,
+            QrRenderOptions {
+                format: QrFormat::Ascii,
+                quiet_zone: false,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let ascii = String::from_utf8(ascii.body).unwrap();
+        assert!(ascii.chars().all(|c| c == '#' || c == ' ' || c == '\n'));
+    }
+
+    #[test]
+    fn test_fallback_to_raw_when_code_too_long() {
+        let huge = "x".repeat(8_000);
+        let rendered = render_qr_with_fallback(&huge, QrRenderOptions::default());
+        assert_eq!(rendered.format, QrFormat::Raw);
+        assert_eq!(rendered.body, huge.as_bytes());
+    }
+
+    #[test]
+    fn test_image_file_splits_name_and_format() {
+        assert_eq!(image_file("sales.png"), Some(("sales", QrFormat::Png)));
+        assert_eq!(image_file("my.shop.SVG"), Some(("my.shop", QrFormat::Svg)));
+        assert_eq!(image_file("sales.txt"), None);
+        assert_eq!(image_file("sales"), None);
+        assert_eq!(image_file(".png"), None);
+    }
@@ -0,0 +1,53 @@
+    use super::*;
+    use serde_json::json;
+
+    fn config(max_media_size_mb: u32) -> RuntimeConfig {
+        RuntimeConfig {
+            max_media_size_mb,
+            ..RuntimeConfig::default()
+        }
+    }
+
+    #[test]
+    fn zero_limit_is_unlimited() {
+        assert!(within(0, 1_000_000));
+        assert!(within(3, 2));
+        assert!(!within(3, 3));
+    }
+
+    #[test]
+    fn media_size_is_checked_in_mib() {
+        assert!(check_media_size(&config(0), u64::MAX).is_ok());
+        assert!(check_media_size(&config(1), 1024 * 1024).is_ok());
+        let err = check_media_size(&config(1), 1024 * 1024 + 1).unwrap_err();
+        assert_eq!(err.quota(), Some("media_size"));
+        let (status, Json(body)) = err.response();
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(body["error"], "quota_exceeded");
+        assert_eq!(body["limit"], 1024 * 1024);
+    }
+
+    #[test]
+    fn base64_size_ignores_padding_and_data_url_prefix() {
+        assert_eq!(base64_size("aGVsbG8="), 5);
+        assert_eq!(base64_size("data:image/png;base64,aGVsbG8gd29ybGQ="), 11);
+        assert_eq!(base64_size("aGVs\nbG8="), 5);
+    }
+
+    #[test]
+    fn metrics_omit_workspaces() {
+        let usage = json!({
+            "limits": {"maxMessagesPerDay": 100},
+            "instances": 1,
+            "workspaces": {"3f1c": 1},
+            "perInstance": [{"instance": "vendas", "workspaceId": "3f1c", "messagesToday": 7}],
+        });
+        assert_eq!(
+            metrics(&usage),
+            json!({
+                "limits": {"maxMessagesPerDay": 100},
+                "instances": 1,
+                "messages_today": {"vendas": 7},
+            })
+        );
+    }
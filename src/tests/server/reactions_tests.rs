@@ -0,0 +1,106 @@
+    use super::*;
+    use crate::types::message::MessageSource;
+
+    fn info(from_me: bool) -> MessageInfo {
+        MessageInfo {
+            source: MessageSource {
+                chat: "5511999990000@s.whatsapp.net".parse().unwrap(),
+                sender: "5511888880000:3@s.whatsapp.net".parse().unwrap(),
+                is_from_me: from_me,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    fn reaction(text: &str, timestamp_ms: Option<i64>) -> wa::Message {
+        wa::Message {
+            reaction_message: Some(wa::message::ReactionMessage {
+                key: Some(wa::MessageKey {
+                    id: Some("3EB0A".to_string()),
+                    ..Default::default()
+                }),
+                text: Some(text.to_string()),
+                sender_timestamp_ms: timestamp_ms,
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn row(emoji: &str, reactor: &str, from_me: bool) -> Value {
+        json!({"emoji": emoji, "reactor": reactor, "fromMe": from_me})
+    }
+
+    #[test]
+    fn reads_reactions_keyed_by_target_message() {
+        let update =
+            ReactionUpdate::from_message(&reaction("👍", Some(1_700_000_000_000)), &info(false))
+                .unwrap();
+        assert_eq!(update.message_id, "3EB0A");
+        assert_eq!(update.chat_id, "5511999990000@s.whatsapp.net");
+        assert_eq!(update.reactor, "5511888880000@s.whatsapp.net");
+        assert_eq!(update.emoji.as_deref(), Some("👍"));
+        assert!(!update.from_me);
+        assert_eq!(update.at.timestamp_millis(), 1_700_000_000_000);
+    }
+
+    #[test]
+    fn empty_text_removes_the_reaction() {
+        let update = ReactionUpdate::from_message(&reaction("", None), &info(true)).unwrap();
+        assert_eq!(update.emoji, None);
+        assert!(update.from_me);
+        assert_eq!(update.at, info(true).timestamp);
+    }
+
+    #[test]
+    fn ignores_other_messages_and_reactions_without_target() {
+        assert_eq!(
+            ReactionUpdate::from_message(&wa::Message::default(), &info(false)),
+            None
+        );
+        let mut untargeted = reaction("👍", None);
+        if let Some(reaction) = untargeted.reaction_message.as_mut() {
+            reaction.key = None;
+        }
+        assert_eq!(
+            ReactionUpdate::from_message(&untargeted, &info(false)),
+            None
+        );
+    }
+
+    #[test]
+    fn looks_through_ephemeral_wrapper() {
+        let wrapped = wa::Message {
+            ephemeral_message: Some(Box::new(wa::message::FutureProofMessage {
+                message: Some(Box::new(reaction("❤️", None))),
+            })),
+            ..Default::default()
+        };
+        let update = ReactionUpdate::from_message(&wrapped, &info(false)).unwrap();
+        assert_eq!(update.emoji.as_deref(), Some("❤️"));
+    }
+
+    #[test]
+    fn aggregates_by_emoji_most_used_first() {
+        let rows = vec![
+            row("❤️", "a@s.whatsapp.net", false),
+            row("👍", "b@s.whatsapp.net", false),
+            row("👍", "me@s.whatsapp.net", true),
+            row("😂", "c@s.whatsapp.net", false),
+        ];
+        assert_eq!(
+            aggregate(&rows),
+            vec![
+                json!({
+                    "emoji": "👍",
+                    "count": 2,
+                    "fromMe": true,
+                    "reactors": ["b@s.whatsapp.net", "me@s.whatsapp.net"],
+                }),
+                json!({"emoji": "❤️", "count": 1, "fromMe": false, "reactors": ["a@s.whatsapp.net"]}),
+                json!({"emoji": "😂", "count": 1, "fromMe": false, "reactors": ["c@s.whatsapp.net"]}),
+            ]
+        );
+        assert_eq!(aggregate(&[]), Vec::<Value>::new());
+    }
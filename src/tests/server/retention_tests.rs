@@ -0,0 +1,117 @@
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn instance_overrides_fall_back_to_the_deployment() {
+        let defaults = RetentionPolicy {
+            messages_days: Some(90),
+            webhook_logs_days: Some(7),
+            media_days: None,
+        };
+        let instance = RetentionPolicy::from_body(&json!({"messagesDays": 0, "mediaDays": 30}))
+            .unwrap()
+            .or(defaults);
+        assert_eq!(instance.days(RetentionKind::Messages), None);
+        assert_eq!(instance.days(RetentionKind::WebhookLogs), Some(7));
+        assert_eq!(instance.days(RetentionKind::Media), Some(30));
+        assert_eq!(
+            RetentionPolicy::default()
+                .or(defaults)
+                .days(RetentionKind::Media),
+            None
+        );
+
+        let body = response("main", RetentionPolicy::default(), defaults);
+        assert_eq!(body["retention"]["messagesDays"], Value::Null);
+        assert_eq!(body["effective"]["messagesDays"], 90);
+    }
+
+    #[test]
+    fn rejects_invalid_policies() {
+        assert_eq!(
+            RetentionPolicy::from_body(&json!({"days": 3})),
+            Err(RetentionError::InvalidBody)
+        );
+        assert_eq!(
+            RetentionPolicy::from_body(&json!({"mediaDays": -1})),
+            Err(RetentionError::InvalidBody)
+        );
+        assert_eq!(
+            RetentionPolicy::from_body(&json!({"webhookLogsDays": MAX_RETENTION_DAYS + 1})),
+            Err(RetentionError::TooLong("webhookLogsDays"))
+        );
+        assert_eq!(
+            RetentionPolicy::from_body(&json!({})),
+            Ok(RetentionPolicy::default())
+        );
+    }
+
+    #[test]
+    fn queued_messages_are_never_targeted() {
+        for kind in [RetentionKind::Messages, RetentionKind::Media] {
+            let targets = kind.targets();
+            assert_eq!(targets.len(), 1);
+            assert_eq!(targets[0].0, "api_messages");
+            assert!(targets[0].1.contains("status IS DISTINCT FROM 'queued'"));
+            assert!(targets[0].1.contains("expires_at < now()"));
+        }
+        let tables: Vec<_> = RetentionKind::WebhookLogs
+            .targets()
+            .into_iter()
+            .map(|(table, _)| table)
+            .collect();
+        assert_eq!(tables, vec!["webhook_deliveries", "webhook_outbox"]);
+    }
+
+    #[test]
+    fn metrics_add_up_per_kind() {
+        let metrics = RetentionMetrics::default();
+        metrics.record(
+            RetentionKind::Media,
+            Reclaimed {
+                rows: 2,
+                bytes: 900,
+            },
+        );
+        metrics.record(
+            RetentionKind::Media,
+            Reclaimed {
+                rows: 1,
+                bytes: 100,
+            },
+        );
+        let at = Utc.with_ymd_and_hms(2026, 4, 12, 3, 0, 0).unwrap();
+        metrics.finish_run(at);
+        let totals = metrics.snapshot();
+        assert_eq!(totals.runs, 1);
+        assert_eq!(
+            totals.media,
+            Reclaimed {
+                rows: 3,
+                bytes: 1000
+            }
+        );
+        assert_eq!(totals.messages, Reclaimed::default());
+
+        let text = render_prometheus(&totals);
+        assert!(text.contains("chatwarp_retention_rows_deleted_total{kind=\"media\"} 3\n"));
+        assert!(text.contains("chatwarp_retention_bytes_reclaimed_total{kind=\"media\"} 1000\n"));
+        assert!(text.contains("chatwarp_retention_rows_deleted_total{kind=\"webhook_logs\"} 0\n"));
+        assert!(text.contains(&format!(
+            "chatwarp_retention_last_run_timestamp_seconds {}\n",
+            at.timestamp()
+        )));
+        assert!(!render_prometheus(&RetentionTotals::default()).contains("last_run_timestamp"));
+    }
+
+    #[test]
+    fn reclaimed_reads_the_statement_row() {
+        assert_eq!(
+            Reclaimed::from_row(&json!({"value": {"rows": 4, "bytes": 512}})),
+            Reclaimed {
+                rows: 4,
+                bytes: 512
+            }
+        );
+        assert_eq!(Reclaimed::from_row(&json!({})), Reclaimed::default());
+    }
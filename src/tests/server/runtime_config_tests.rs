@@ -0,0 +1,157 @@
+    use super::*;
+    use serde_json::json;
+
+    fn config() -> RuntimeConfig {
+        RuntimeConfig {
+            log_targets: [("warp_core".to_string(), "warn".to_string())].into(),
+            cors_origins: vec![
+                "https://app.example.com".to_string(),
+                "http://localhost:3000".to_string(),
+            ],
+            manager_cors_origins: vec!["https://api.example.com:8443".to_string()],
+            rate_limit_per_minute: 120,
+            webhook: GlobalWebhook {
+                enabled: true,
+                url: Some("https://hooks.example.com".to_string()),
+                ..GlobalWebhook::default()
+            },
+            max_messages_per_day: 1000,
+            ..RuntimeConfig::default()
+        }
+    }
+
+    #[test]
+    fn patch_merges_webhook_fields() {
+        let (next, changed) = config()
+            .patched(&json!({"webhook": {"base64": true}, "rateLimitPerMinute": 0}))
+            .unwrap();
+
+        assert!(next.webhook.base64);
+        assert_eq!(next.webhook.url.as_deref(), Some("https://hooks.example.com"));
+        assert_eq!(next.rate_limit_per_minute, 0);
+        assert_eq!(changed, vec!["rateLimitPerMinute", "webhook"]);
+    }
+
+    #[test]
+    fn patch_toggles_maintenance_mode() {
+        let (next, changed) = config()
+            .patched(&json!({"maintenanceMode": true}))
+            .unwrap();
+        assert!(next.maintenance_mode);
+        assert_eq!(changed, vec!["maintenanceMode"]);
+        assert!(config().patched(&json!({"maintenanceMode": "yes"})).is_err());
+    }
+
+    #[test]
+    fn patch_replaces_log_targets() {
+        let (next, _) = config()
+            .patched(&json!({"logTargets": {"chatwarp_api::server": "debug"}}))
+            .unwrap();
+        assert_eq!(next.log_filter(), "info,chatwarp_api::server=debug");
+        assert!(matches!(
+            config().patched(&json!({"logTargets": {"a,b": "debug"}})),
+            Err(RuntimeConfigError::InvalidValue { key: "logTargets", .. })
+        ));
+    }
+
+    #[test]
+    fn patch_rejects_unknown_and_invalid_values() {
+        let config = config();
+        assert!(matches!(
+            config.patched(&json!({"databaseUrl": "x"})),
+            Err(RuntimeConfigError::UnknownKey(_))
+        ));
+        assert!(matches!(
+            config.patched(&json!({"webhook": {"token": "x"}})),
+            Err(RuntimeConfigError::UnknownKey(_))
+        ));
+        assert!(matches!(
+            config.patched(&json!({"corsOrigins": ["ftp://x"]})),
+            Err(RuntimeConfigError::InvalidValue { key: "corsOrigins", .. })
+        ));
+        assert!(matches!(
+            config.patched(&json!({"managerCorsOrigins": ["https://*.*.example.com"]})),
+            Err(RuntimeConfigError::InvalidValue { key: "managerCorsOrigins", .. })
+        ));
+        assert!(matches!(
+            config.patched(&json!({"wsCorsOrigins": ["example.com"]})),
+            Err(RuntimeConfigError::InvalidValue { key: "wsCorsOrigins", .. })
+        ));
+        assert!(matches!(
+            config.patched(&json!({"logLevel": "info,[=="})),
+            Err(RuntimeConfigError::InvalidValue { key: "logLevel", .. })
+        ));
+        assert!(matches!(
+            config.patched(&json!({"rateLimitPerMinute": -1})),
+            Err(RuntimeConfigError::InvalidValue { .. })
+        ));
+        assert!(matches!(
+            config.patched(&json!({"qrImageSize": 10})),
+            Err(RuntimeConfigError::InvalidValue { key: "qrImageSize", .. })
+        ));
+        assert!(matches!(config.patched(&json!([])), Err(RuntimeConfigError::NotAnObject)));
+    }
+
+    #[test]
+    fn allows_origin_supports_wildcard() {
+        let mut config = config();
+        assert!(config.allows_origin(CorsGroup::Api, "http://localhost:3000"));
+        assert!(!config.allows_origin(CorsGroup::Api, "https://evil.example.com"));
+        config.cors_origins = vec!["*".to_string()];
+        assert!(config.allows_origin(CorsGroup::Api, "https://evil.example.com"));
+    }
+
+    #[test]
+    fn wildcard_subdomains_match_only_subdomains() {
+        let pattern = "https://*.example.com";
+        assert!(origin_matches(pattern, "https://app.example.com"));
+        assert!(origin_matches(pattern, "https://a.b.example.com"));
+        assert!(!origin_matches(pattern, "https://example.com"));
+        assert!(!origin_matches(pattern, "http://app.example.com"));
+        assert!(!origin_matches(pattern, "https://app.example.com:8443"));
+        assert!(!origin_matches(pattern, "https://evilexample.com"));
+        assert!(!origin_matches(pattern, "https://app.example.com.evil.io"));
+        assert!(origin_matches("https://*.example.com:8443", "https://app.example.com:8443"));
+
+        assert!(is_origin_pattern(pattern));
+        assert!(is_origin_pattern("*"));
+        assert!(!is_origin_pattern("https://app.*.example.com"));
+        assert!(!is_origin_pattern("*.example.com"));
+        assert!(!is_origin_pattern("https://"));
+    }
+
+    #[test]
+    fn route_groups_have_their_own_origins() {
+        let mut config = config();
+        assert!(!config.allows_origin(CorsGroup::Manager, "https://app.example.com"));
+        assert!(config.allows_origin(CorsGroup::Manager, "https://api.example.com:8443"));
+        // `/ws` follows the API until it has a list of its own.
+        assert!(config.allows_origin(CorsGroup::Ws, "https://app.example.com"));
+        config.ws_cors_origins = Some(vec!["https://*.chat.example.com".to_string()]);
+        assert!(!config.allows_origin(CorsGroup::Ws, "https://app.example.com"));
+        assert!(config.allows_origin(CorsGroup::Ws, "https://eu.chat.example.com"));
+
+        let (next, _) = config.patched(&json!({"wsCorsOrigins": null})).unwrap();
+        assert_eq!(next.origins(CorsGroup::Ws), next.cors_origins.as_slice());
+    }
+
+    #[test]
+    fn paths_map_to_cors_groups() {
+        assert_eq!(CorsGroup::for_path("/manager/config"), CorsGroup::Manager);
+        assert_eq!(CorsGroup::for_path("/api/v1/manager/audit"), CorsGroup::Manager);
+        assert_eq!(CorsGroup::for_path("/ws"), CorsGroup::Ws);
+        assert_eq!(CorsGroup::for_path("/api/v1/ws"), CorsGroup::Ws);
+        assert_eq!(CorsGroup::for_path("/managers"), CorsGroup::Api);
+        assert_eq!(CorsGroup::for_path("/message/sendText/x"), CorsGroup::Api);
+    }
+
+    #[test]
+    fn rate_limiter_resets_each_window() {
+        let limiter = RateLimiter::default();
+        let start = Instant::now();
+        assert!(limiter.check_at(2, start));
+        assert!(limiter.check_at(2, start));
+        assert!(!limiter.check_at(2, start));
+        assert!(limiter.check_at(0, start));
+        assert!(limiter.check_at(2, start + RATE_LIMIT_WINDOW));
+    }
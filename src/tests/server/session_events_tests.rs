@@ -0,0 +1,102 @@
+    use super::*;
+    use crate::types::events::{
+        ConnectFailureReason, Connected, Disconnected, LoggedOut, StreamError, StreamReplaced,
+    };
+    use std::time::Duration;
+    use warp_core_binary::builder::NodeBuilder;
+
+    #[test]
+    fn maps_pairing_qr_code_with_timeout() {
+        let event = Event::PairingQrCode {
+            code: "2@abc".to_string(),
+            timeout: Duration::from_secs(60),
+        };
+        assert_eq!(
+            RuntimeUpdate::from_event(&event),
+            Some(RuntimeUpdate::Qr {
+                code: "2@abc".to_string(),
+                timeout_secs: 60,
+            })
+        );
+    }
+
+    #[test]
+    fn maps_pair_code() {
+        let event = Event::PairingCode {
+            code: "ABCD-EFGH".to_string(),
+            timeout: Duration::from_secs(180),
+        };
+        assert_eq!(
+            RuntimeUpdate::from_event(&event),
+            Some(RuntimeUpdate::PairCode {
+                code: "ABCD-EFGH".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn maps_connection_lifecycle_events() {
+        assert_eq!(
+            RuntimeUpdate::from_event(&Event::Connected(Connected)),
+            Some(RuntimeUpdate::Connected)
+        );
+        assert_eq!(
+            RuntimeUpdate::from_event(&Event::Disconnected(Disconnected)),
+            Some(RuntimeUpdate::Disconnected)
+        );
+        let logged_out = Event::LoggedOut(LoggedOut {
+            on_connect: false,
+            reason: ConnectFailureReason::LoggedOut,
+        });
+        assert_eq!(
+            RuntimeUpdate::from_event(&logged_out),
+            Some(RuntimeUpdate::LoggedOut { on_connect: false })
+        );
+    }
+
+    #[test]
+    fn maps_stream_replaced() {
+        assert_eq!(
+            RuntimeUpdate::from_event(&Event::StreamReplaced(StreamReplaced)),
+            Some(RuntimeUpdate::Replaced)
+        );
+    }
+
+    #[test]
+    fn maps_stream_error_to_last_error() {
+        let node = NodeBuilder::new("stream:error").attr("code", "429").build();
+        let Some(RuntimeUpdate::StreamError(error)) =
+            RuntimeUpdate::from_event(&Event::StreamError(StreamError::from_node(&node)))
+        else {
+            panic!("stream error not mapped");
+        };
+        assert_eq!(error.reason, StreamErrorReason::RateOverlimit);
+        assert_eq!(error.code, "429");
+        assert!(error.reconnecting);
+        assert!(!error.requires_pairing);
+    }
+
+    #[test]
+    fn only_reconnecting_stream_errors_change_the_state_themselves() {
+        assert_eq!(
+            stream_error_reason(StreamErrorReason::RateOverlimit),
+            Some(Reason::RateOverlimit)
+        );
+        assert_eq!(
+            stream_error_reason(StreamErrorReason::ServiceUnavailable),
+            Some(Reason::ServiceUnavailable)
+        );
+        assert_eq!(
+            stream_error_reason(StreamErrorReason::Unknown),
+            Some(Reason::ConnectionLost)
+        );
+        assert_eq!(stream_error_reason(StreamErrorReason::LoggedOut), None);
+        assert_eq!(
+            stream_error_reason(StreamErrorReason::ReplacedByOtherDevice),
+            None
+        );
+        assert_eq!(
+            stream_error_reason(StreamErrorReason::RestartRequired),
+            None
+        );
+    }
@@ -0,0 +1,36 @@
+    use super::*;
+
+    fn scope_of(allow: Option<&[&str]>, deny: &[&str]) -> InstanceScope {
+        InstanceScope {
+            allow: allow.map(|allow| allow.iter().map(|i| i.to_string()).collect()),
+            deny: deny.iter().map(|i| i.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn takes_every_instance_by_default() {
+        let scope = scope_of(None, &[]);
+        assert_eq!(scope, InstanceScope::default());
+        assert!(scope.includes("sales"));
+        assert!(scope.includes(""));
+    }
+
+    #[test]
+    fn allowlist_keeps_only_listed_instances() {
+        let scope = scope_of(Some(&["sales", "support"]), &[]);
+        assert!(scope.includes("sales"));
+        assert!(scope.includes("support"));
+        assert!(!scope.includes("test"));
+        assert!(scope.includes(""));
+    }
+
+    #[test]
+    fn denylist_wins_over_allowlist() {
+        let scope = scope_of(Some(&["sales", "support"]), &["support"]);
+        assert!(scope.includes("sales"));
+        assert!(!scope.includes("support"));
+
+        let scope = scope_of(None, &["test"]);
+        assert!(!scope.includes("test"));
+        assert!(scope.includes("sales"));
+    }
@@ -0,0 +1,97 @@
+    use super::*;
+
+    fn envelope(instance: &str, event: &str) -> Arc<BusEvent> {
+        BusEvent::new(json!({"event": event, "instance": instance, "schemaVersion": 1, "data": {}}))
+    }
+
+    fn hub(history: usize) -> SseHub {
+        SseHub::new(SseConfig {
+            history,
+            ..SseConfig::default()
+        })
+    }
+
+    #[test]
+    fn replays_events_after_the_last_id() {
+        let hub = hub(10);
+        for event in ["QRCODE_UPDATED", "CONNECTION_UPDATE", "MESSAGES_UPSERT"] {
+            hub.publish(&envelope("sales", event));
+        }
+
+        let (_, fresh) = hub.subscribe(None);
+        assert!(fresh.events.is_empty());
+        assert_eq!(fresh.up_to, 3);
+
+        let (_, replay) = hub.subscribe(Some(1));
+        let ids: Vec<_> = replay.events.iter().map(|e| e.id).collect();
+        assert_eq!(ids, [2, 3]);
+        assert_eq!(replay.skipped, 0);
+        assert_eq!(replay.events[0].envelope.event, "CONNECTION_UPDATE");
+        assert_eq!(replay.events[0].envelope.instance, "sales");
+        // Nothing was sent yet, so nothing was serialized.
+        assert!(!replay.events[0].envelope.is_serialized());
+        assert_eq!(hub.stats().queued, 3);
+
+        // Ids from before a restart are ahead of the history.
+        let (_, ahead) = hub.subscribe(Some(42));
+        assert!(ahead.events.is_empty());
+        assert_eq!(ahead.skipped, 0);
+    }
+
+    #[test]
+    fn reports_events_that_left_the_history() {
+        let hub = hub(2);
+        for _ in 0..5 {
+            hub.publish(&envelope("sales", "MESSAGES_UPSERT"));
+        }
+        let (_, replay) = hub.subscribe(Some(1));
+        let ids: Vec<_> = replay.events.iter().map(|e| e.id).collect();
+        assert_eq!(ids, [4, 5]);
+        assert_eq!(replay.skipped, 2);
+
+        let (_, caught_up) = hub.subscribe(Some(5));
+        assert!(caught_up.events.is_empty());
+        assert_eq!(caught_up.skipped, 0);
+    }
+
+    #[tokio::test]
+    async fn live_events_follow_the_replay() {
+        let hub = hub(10);
+        hub.publish(&envelope("sales", "QRCODE_UPDATED"));
+        let (mut rx, replay) = hub.subscribe(Some(0));
+        assert_eq!(replay.events.len(), 1);
+        hub.publish(&envelope("support", "CONNECTION_UPDATE"));
+        let event = rx.recv().await.unwrap();
+        assert_eq!(event.id, 2);
+        assert!(event.id > replay.up_to);
+        assert_eq!(event.envelope.payload["instance"], "support");
+    }
+
+    #[test]
+    fn filters_by_instance_and_event() {
+        let all = EventFilter::new(None, Some(" , "));
+        assert_eq!(all, EventFilter::default());
+        assert!(all.matches(&envelope("sales", "CALL")));
+
+        let filter = EventFilter::new(
+            Some("sales".to_string()),
+            Some("messages_upsert, CONNECTION_UPDATE"),
+        );
+        assert!(filter.matches(&envelope("sales", "MESSAGES_UPSERT")));
+        assert!(filter.matches(&envelope("sales", "CONNECTION_UPDATE")));
+        assert!(!filter.matches(&envelope("sales", "CALL")));
+        assert!(!filter.matches(&envelope("support", "MESSAGES_UPSERT")));
+    }
+
+    #[test]
+    fn reads_last_event_id_from_header_or_query() {
+        let mut headers = HeaderMap::new();
+        let query = SseQuery {
+            events: None,
+            last_event_id: Some(7),
+        };
+        assert_eq!(last_event_id(&headers, &query), Some(7));
+        headers.insert("last-event-id", "12".parse().unwrap());
+        assert_eq!(last_event_id(&headers, &query), Some(12));
+        assert_eq!(last_event_id(&HeaderMap::new(), &SseQuery::default()), None);
+    }
@@ -0,0 +1,94 @@
+    use super::*;
+
+    fn config(standby: bool, leases: bool) -> StandbyConfig {
+        StandbyConfig {
+            standby,
+            leases,
+            node_id: "a".to_string(),
+            lease_ttl: Duration::from_secs(crate::config::DEFAULT_INSTANCE_LEASE_SECS),
+        }
+    }
+
+    #[test]
+    fn promotion_starts_only_from_standby() {
+        let standby = Standby::new(config(true, true), "default");
+        assert_eq!(standby.role(), Role::Standby);
+        assert!(!standby.is_primary());
+        assert_eq!(standby.begin_promotion(), Ok(()));
+        assert_eq!(standby.role(), Role::Promoting);
+        assert_eq!(standby.begin_promotion(), Err(Role::Promoting));
+        assert!(!standby.transition(Role::Standby, Role::Primary));
+        assert!(standby.transition(Role::Promoting, Role::Primary));
+        assert!(standby.is_primary());
+
+        let primary = Standby::new(config(false, true), "default");
+        assert_eq!(primary.begin_promotion(), Err(Role::Primary));
+        assert_eq!(
+            primary.status(),
+            StandbyStatus {
+                role: Role::Primary,
+                leases: true,
+                node_id: "a".to_string(),
+                instance: "default".to_string(),
+                epoch: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn acquisition_waits_out_a_lease_taken_from_another_node() {
+        assert_eq!(
+            Acquisition::from_row(
+                "a",
+                &json!({"epoch": 1, "holder": null, "remainingMs": null})
+            ),
+            Some(Acquisition::Taken {
+                epoch: 1,
+                wait: Duration::ZERO
+            })
+        );
+        assert_eq!(
+            Acquisition::from_row(
+                "a",
+                &json!({"epoch": 4, "holder": "a", "remainingMs": 9000})
+            ),
+            Some(Acquisition::Taken {
+                epoch: 4,
+                wait: Duration::ZERO
+            })
+        );
+        assert_eq!(
+            Acquisition::from_row(
+                "a",
+                &json!({"epoch": 5, "holder": "b", "remainingMs": 9000})
+            ),
+            Some(Acquisition::Taken {
+                epoch: 5,
+                wait: Duration::from_secs(9)
+            })
+        );
+        assert_eq!(
+            Acquisition::from_row(
+                "a",
+                &json!({"epoch": null, "holder": "b", "remainingMs": 250})
+            ),
+            Some(Acquisition::Held {
+                holder: "b".to_string(),
+                remaining: Duration::from_millis(250)
+            })
+        );
+        assert_eq!(
+            Acquisition::from_row("a", &json!({"epoch": null, "holder": null})),
+            None
+        );
+    }
+
+    #[test]
+    fn primaries_fence_before_the_lease_expires() {
+        let ttl = Duration::from_secs(30);
+        assert!(!must_fence(Duration::ZERO, ttl));
+        assert!(!must_fence(Duration::from_secs(10), ttl));
+        assert!(!must_fence(Duration::from_secs(19), ttl));
+        assert!(must_fence(Duration::from_secs(20), ttl));
+        assert!(must_fence(Duration::from_secs(45), ttl));
+    }
@@ -0,0 +1,105 @@
+    use super::*;
+    use std::time::Duration;
+
+    fn request(pairs: &[(header::HeaderName, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.insert(name.clone(), HeaderValue::from_str(value).unwrap());
+        }
+        headers
+    }
+
+    async fn body_of(response: Response) -> Vec<u8> {
+        axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap()
+            .to_vec()
+    }
+
+    #[test]
+    fn parses_single_byte_ranges() {
+        assert_eq!(parse_range("bytes=0-99", 1000), Ok(Some(0..100)));
+        assert_eq!(parse_range("bytes=900-", 1000), Ok(Some(900..1000)));
+        assert_eq!(parse_range("bytes=-100", 1000), Ok(Some(900..1000)));
+        assert_eq!(parse_range("bytes=-5000", 1000), Ok(Some(0..1000)));
+        assert_eq!(parse_range("bytes=990-2000", 1000), Ok(Some(990..1000)));
+        assert_eq!(parse_range("bytes=0-1,5-9", 1000), Ok(None));
+        assert_eq!(parse_range("items=0-1", 1000), Ok(None));
+        assert_eq!(parse_range("bytes=1000-", 1000), Err(Unsatisfiable));
+        assert_eq!(parse_range("bytes=9-5", 1000), Err(Unsatisfiable));
+        assert_eq!(parse_range("bytes=-0", 1000), Err(Unsatisfiable));
+    }
+
+    #[test]
+    fn conditional_headers() {
+        let modified = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let tag = etag(10, modified);
+        assert!(is_not_modified(&request(&[(header::IF_NONE_MATCH, &tag)]), &tag, modified));
+        assert!(is_not_modified(&request(&[(header::IF_NONE_MATCH, "\"x\", *")]), &tag, modified));
+        assert!(!is_not_modified(&request(&[(header::IF_NONE_MATCH, "\"x\"")]), &tag, modified));
+
+        let date = http_date(modified);
+        assert_eq!(date, "Tue, 14 Nov 2023 22:13:20 GMT");
+        assert!(is_not_modified(&request(&[(header::IF_MODIFIED_SINCE, &date)]), &tag, modified));
+        let later = modified + Duration::from_secs(1);
+        assert!(!is_not_modified(&request(&[(header::IF_MODIFIED_SINCE, &date)]), &tag, later));
+        // If-None-Match decides when both are sent.
+        assert!(!is_not_modified(
+            &request(&[(header::IF_NONE_MATCH, "\"x\""), (header::IF_MODIFIED_SINCE, &date)]),
+            &tag,
+            modified
+        ));
+    }
+
+    #[tokio::test]
+    async fn serves_ranges_and_revalidates() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("clip.bin");
+        let data: Vec<u8> = (0..300_000u32).map(|i| (i % 256) as u8).collect();
+        std::fs::write(&path, &data).unwrap();
+        let cache = FileCache::default();
+
+        let full = serve_file(&cache, &path, "video/mp4", "no-cache", &HeaderMap::new()).await;
+        assert_eq!(full.status(), StatusCode::OK);
+        let tag = full.headers()[header::ETAG].to_str().unwrap().to_string();
+        assert_eq!(body_of(full).await, data);
+
+        let ranged = request(&[(header::RANGE, "bytes=100000-100009")]);
+        let partial = serve_file(&cache, &path, "video/mp4", "no-cache", &ranged).await;
+        assert_eq!(partial.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(partial.headers()[header::CONTENT_RANGE], "bytes 100000-100009/300000");
+        assert_eq!(body_of(partial).await, data[100_000..100_010]);
+
+        let stale = request(&[(header::RANGE, "bytes=0-9"), (header::IF_RANGE, "\"old\"")]);
+        let whole = serve_file(&cache, &path, "video/mp4", "no-cache", &stale).await;
+        assert_eq!(whole.status(), StatusCode::OK);
+
+        let out_of_bounds = request(&[(header::RANGE, "bytes=400000-")]);
+        let refused = serve_file(&cache, &path, "video/mp4", "no-cache", &out_of_bounds).await;
+        assert_eq!(refused.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(refused.headers()[header::CONTENT_RANGE], "bytes */300000");
+
+        let revalidate = request(&[(header::IF_NONE_MATCH, &tag)]);
+        let cached = serve_file(&cache, &path, "video/mp4", "no-cache", &revalidate).await;
+        assert_eq!(cached.status(), StatusCode::NOT_MODIFIED);
+    }
+
+    #[tokio::test]
+    async fn small_files_are_cached_until_they_change() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app.js");
+        std::fs::write(&path, "one").unwrap();
+        let cache = FileCache::default();
+
+        let first = serve_file(&cache, &path, "text/javascript", "no-cache", &HeaderMap::new()).await;
+        assert_eq!(body_of(first).await, b"one");
+        assert!(cache.entries.get(&path).await.is_some());
+
+        std::fs::write(&path, "two!").unwrap();
+        let second = serve_file(&cache, &path, "text/javascript", "no-cache", &HeaderMap::new()).await;
+        assert_eq!(body_of(second).await, b"two!");
+
+        let missing = dir.path().join("missing.js");
+        let response = serve_file(&cache, &missing, "text/javascript", "no-cache", &HeaderMap::new()).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
@@ -0,0 +1,46 @@
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn build_info_lists_compiled_features() {
+        let build = BuildInfo::current();
+        assert_eq!(build.version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(
+            build.features.contains(&"sqlite-storage"),
+            cfg!(feature = "sqlite-storage")
+        );
+        assert_eq!(build.features.contains(&"nats"), cfg!(feature = "nats"));
+    }
+
+    #[test]
+    fn counts_instances_by_state() {
+        let counts = InstanceCounts::from_states([
+            ConnectionState::Connected,
+            ConnectionState::QrPending,
+            ConnectionState::Connected,
+            ConnectionState::Errored,
+        ]);
+        assert_eq!(
+            serde_json::to_value(&counts).unwrap(),
+            json!({"total": 4, "byState": {"connected": 2, "errored": 1, "qr_pending": 1}})
+        );
+        assert_eq!(InstanceCounts::from_states([]).total, 0);
+    }
+
+    #[test]
+    fn global_webhook_needs_a_url() {
+        let enabled = |sinks: Vec<SinkStatus>, name: &str| {
+            sinks
+                .into_iter()
+                .find(|sink| sink.name == name)
+                .is_some_and(|sink| sink.enabled)
+        };
+        let mut runtime = RuntimeConfig::default();
+        assert!(!enabled(sinks(&runtime, false), "webhook_global"));
+        runtime.webhook.enabled = true;
+        assert!(!enabled(sinks(&runtime, false), "webhook_global"));
+        runtime.webhook.url = Some("https://hooks.example.com".to_string());
+        assert!(enabled(sinks(&runtime, false), "webhook_global"));
+        assert!(enabled(sinks(&runtime, true), "nats"));
+        assert!(enabled(sinks(&runtime, false), "ws"));
+    }
@@ -0,0 +1,61 @@
+    use super::*;
+
+    fn policy() -> RestartPolicy {
+        RestartPolicy {
+            max_restarts: 3,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(5),
+            stable_after: Duration::from_secs(600),
+        }
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap() {
+        let policy = policy();
+        assert_eq!(policy.backoff(1), Duration::from_secs(1));
+        assert_eq!(policy.backoff(2), Duration::from_secs(2));
+        assert_eq!(policy.backoff(3), Duration::from_secs(4));
+        assert_eq!(policy.backoff(4), Duration::from_secs(5));
+        assert_eq!(policy.backoff(40), Duration::from_secs(5));
+    }
+
+    #[test]
+    fn gives_up_after_max_restarts() {
+        let policy = policy();
+        let quick = Duration::from_secs(1);
+        assert_eq!(
+            policy.next_step(0, quick),
+            Step::Restart {
+                restarts: 1,
+                delay: Duration::from_secs(1)
+            }
+        );
+        assert_eq!(
+            policy.next_step(2, quick),
+            Step::Restart {
+                restarts: 3,
+                delay: Duration::from_secs(4)
+            }
+        );
+        assert_eq!(policy.next_step(3, quick), Step::GiveUp);
+    }
+
+    #[test]
+    fn stable_run_resets_the_budget() {
+        let policy = policy();
+        assert_eq!(
+            policy.next_step(3, Duration::from_secs(600)),
+            Step::Restart {
+                restarts: 1,
+                delay: Duration::from_secs(1)
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn panic_message_is_extracted() {
+        let error = tokio::spawn(async { panic!("runner exploded") })
+            .await
+            .unwrap_err();
+        assert_eq!(panic_message(error), "runner exploded");
+    }
@@ -0,0 +1,91 @@
+    use super::*;
+
+    fn template() -> Template {
+        Template::from_body(
+            None,
+            &json!({
+                "name": "order.ready",
+                "title": "Pedido {{order}}",
+                "content": "Olá {{ name }}, seu pedido {{order}} está pronto.",
+                "footer": "{{store}}",
+                "variables": {"store": "Loja Centro"},
+                "buttons": [
+                    {"type": "reply", "text": "Obrigado", "id": "thanks"},
+                    {"type": "url", "text": "Rastrear", "url": "https://example.com/{{order}}"}
+                ]
+            }),
+        )
+        .unwrap()
+    }
+
+    fn values(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn extracts_placeholders_in_order() {
+        assert_eq!(placeholders_in("{{a}} x {{ b }} {{}} {{c"), vec!["a", "b"]);
+        assert_eq!(
+            template().placeholders().into_iter().collect::<Vec<_>>(),
+            vec!["name", "order", "store"]
+        );
+    }
+
+    #[test]
+    fn renders_with_values_and_defaults() {
+        let rendered = template()
+            .render(&values(&[("name", "Ana"), ("order", "42")]))
+            .unwrap();
+
+        assert_eq!(rendered.title.as_deref(), Some("Pedido 42"));
+        assert_eq!(rendered.text, "Olá Ana, seu pedido 42 está pronto.");
+        assert_eq!(rendered.footer.as_deref(), Some("Loja Centro"));
+        assert_eq!(rendered.buttons[1].url.as_deref(), Some("https://example.com/42"));
+    }
+
+    #[test]
+    fn reports_every_missing_variable() {
+        assert_eq!(
+            template().render(&BTreeMap::new()),
+            Err(TemplateError::MissingVariables(vec!["name".to_string(), "order".to_string()]))
+        );
+    }
+
+    #[test]
+    fn payload_type_depends_on_buttons() {
+        let rendered = template()
+            .render(&values(&[("name", "Ana"), ("order", "42")]))
+            .unwrap();
+        let (message_type, payload) = rendered.clone().into_payload("sales", "1@s.whatsapp.net");
+        assert_eq!(message_type, "template");
+        assert_eq!(payload["session"], "sales");
+        assert_eq!(payload["chatId"], "1@s.whatsapp.net");
+
+        let plain = Rendered { buttons: vec![], ..rendered };
+        assert_eq!(plain.into_payload("sales", "1@s.whatsapp.net").0, "text");
+    }
+
+    #[test]
+    fn validates_bodies() {
+        assert_eq!(
+            Template::from_body(None, &json!({"name": "bad name", "content": "x"})),
+            Err(TemplateError::InvalidName)
+        );
+        assert_eq!(
+            Template::from_body(Some("a"), &json!({"content": " "})),
+            Err(TemplateError::ContentRequired)
+        );
+        assert!(matches!(
+            Template::from_body(
+                Some("a"),
+                &json!({"content": "x", "buttons": [{"type": "url", "text": "Site"}]})
+            ),
+            Err(TemplateError::InvalidButton { index: 0, .. })
+        ));
+        assert_eq!(
+            Template::from_body(Some("a"), &json!({"content": "x", "variables": {"n": 1}}))
+                .unwrap()
+                .variables,
+            values(&[("n", "1")])
+        );
+    }
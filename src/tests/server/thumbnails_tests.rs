@@ -0,0 +1,39 @@
+    use super::*;
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let img = image::RgbaImage::from_pixel(width, height, image::Rgba([10, 120, 200, 255]));
+        let mut png = std::io::Cursor::new(Vec::new());
+        img.write_to(&mut png, image::ImageFormat::Png).unwrap();
+        png.into_inner()
+    }
+
+    #[test]
+    fn jpeg_thumbnail_keeps_the_aspect_ratio() {
+        let thumb = jpeg_thumbnail(&png(300, 600), 72).unwrap();
+        assert_eq!(&thumb.jpeg[..2], &[0xFF, 0xD8]);
+        assert_eq!((thumb.width, thumb.height), (36, 72));
+        assert!(jpeg_thumbnail(b"not an image", 72).is_err());
+    }
+
+    #[tokio::test]
+    async fn image_thumbnail_reads_spooled_files() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), png(64, 32)).unwrap();
+        let thumb = image_thumbnail(Source::File(file.path().to_path_buf()), 72)
+            .await
+            .unwrap();
+        assert_eq!((thumb.width, thumb.height), (64, 32));
+    }
+
+    #[tokio::test]
+    async fn missing_ffmpeg_is_reported_as_unavailable() {
+        let err = video_thumbnail(Source::Bytes(vec![0; 16]), "/nonexistent/ffmpeg", 72)
+            .await
+            .unwrap_err();
+        assert!(err.is_unavailable(), "{err}");
+
+        let err = image_thumbnail(Source::Bytes(vec![0; 16]), 72)
+            .await
+            .unwrap_err();
+        assert!(!err.is_unavailable());
+    }
@@ -0,0 +1,44 @@
+    use super::*;
+
+    #[test]
+    fn routes_fall_in_their_budget_group() {
+        let cases = [
+            ("/message/sendText/loja", Some(RouteGroup::Messages)),
+            ("/sendText", Some(RouteGroup::Messages)),
+            (
+                "/chat/getBase64FromMediaMessage/loja",
+                Some(RouteGroup::Messages),
+            ),
+            ("/instance/connect/loja", Some(RouteGroup::Connect)),
+            ("/instance/create", Some(RouteGroup::Connect)),
+            ("/sessions/loja/start", Some(RouteGroup::Connect)),
+            ("/loja/auth/qr", Some(RouteGroup::Connect)),
+            ("/chat/findMessages/loja", Some(RouteGroup::Queries)),
+            ("/group/fetchAllGroups/loja", Some(RouteGroup::Queries)),
+            ("/loja/chats/overview", Some(RouteGroup::Queries)),
+            ("/manager/status", Some(RouteGroup::Other)),
+            ("/instance/fetchInstances", Some(RouteGroup::Other)),
+            ("/ws", None),
+            ("/events/sse/loja", None),
+            ("/instance/logs/loja", None),
+            ("/media/upload/loja", None),
+            ("/jobs/42/download", None),
+        ];
+        for (path, group) in cases {
+            assert_eq!(RouteGroup::for_path(path), group, "{path}");
+        }
+    }
+
+    #[tokio::test]
+    async fn timeouts_answer_504_with_the_budget() {
+        let response = timeout_response(RouteGroup::Queries, Duration::from_secs(10));
+        assert_eq!(response.status(), StatusCode::GATEWAY_TIMEOUT);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            body,
+            json!({"error": "request_timeout", "details": {"group": "queries", "timeoutMs": 10000}})
+        );
+    }
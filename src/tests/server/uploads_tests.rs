@@ -0,0 +1,59 @@
+    use super::*;
+
+    fn config(dir: &Path) -> UploadConfig {
+        UploadConfig {
+            dir: dir.to_path_buf(),
+            ..Default::default()
+        }
+    }
+
+    fn body(chunks: &[&'static [u8]]) -> impl Stream<Item = Result<Bytes, std::io::Error>> {
+        let chunks: Vec<_> = chunks.iter().map(|c| Ok(Bytes::from_static(c))).collect();
+        futures_util::stream::iter(chunks)
+    }
+
+    #[tokio::test]
+    async fn spools_chunks_and_finds_them_by_session() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = config(dir.path());
+        let upload = spool(
+            &config,
+            "main",
+            Some("video/mp4".to_string()),
+            None,
+            body(&[b"hello ", b"world"]),
+            1024,
+        )
+        .await
+        .unwrap();
+        assert_eq!(upload.size, 11);
+        assert_eq!(upload.sha256, hex::encode(Sha256::digest(b"hello world")));
+
+        let id = upload.media_id.to_string();
+        let (found, path) = find(&config, "main", &id).await.unwrap();
+        assert_eq!(found, upload);
+        assert_eq!(std::fs::read(path).unwrap(), b"hello world");
+
+        assert!(matches!(find(&config, "other", &id).await, Err(UploadError::NotFound)));
+        assert!(matches!(find(&config, "main", "../etc/passwd").await, Err(UploadError::NotFound)));
+    }
+
+    #[tokio::test]
+    async fn oversized_upload_is_discarded() {
+        let dir = tempfile::tempdir().unwrap();
+        let result = spool(&config(dir.path()), "main", None, None, body(&[b"12345", b"67890"]), 8).await;
+        assert!(matches!(result, Err(UploadError::TooLarge { limit: 8 })));
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+
+    #[tokio::test]
+    async fn sweep_removes_expired_uploads() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = config(dir.path());
+        spool(&config, "main", None, None, body(&[b"data"]), 1024).await.unwrap();
+
+        assert_eq!(sweep(&config, SystemTime::now()).await.unwrap(), 0);
+        let later = SystemTime::now() + config.ttl + Duration::from_secs(1);
+        assert_eq!(sweep(&config, later).await.unwrap(), 2);
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
@@ -0,0 +1,48 @@
+    use super::*;
+
+    #[test]
+    fn classifies_paths() {
+        assert_eq!(classify("/api/v1/sendMessage"), Mounted::V1("/sendMessage".to_string()));
+        assert_eq!(
+            classify("/api/v1/instance/connect/main"),
+            Mounted::V1("/instance/connect/main".to_string())
+        );
+        assert_eq!(classify("/api/v1"), Mounted::V1("/".to_string()));
+        assert_eq!(classify("/api/v10/sendMessage"), Mounted::Legacy);
+        assert_eq!(classify("/healthz"), Mounted::Unversioned);
+        assert_eq!(classify("/webhook/meta"), Mounted::Unversioned);
+        assert_eq!(classify("/sendMessage"), Mounted::Legacy);
+    }
+
+    #[test]
+    fn rewrites_keep_the_query() {
+        let uri: Uri = "/api/v1/messages?session=main".parse().unwrap();
+        let Mounted::V1(route) = classify(uri.path()) else {
+            panic!("versioned path");
+        };
+        assert_eq!(with_path(&uri, &route).unwrap(), "/messages?session=main");
+    }
+
+    #[test]
+    fn openapi_follows_the_mounting() {
+        let document = json!({
+            "paths": {
+                "/healthz": {"get": {"responses": {}}},
+                "/sessions": {"get": {"responses": {}}, "parameters": []},
+            }
+        });
+
+        let both = mount_openapi(document.clone(), ApiMount::default());
+        let paths = both["paths"].as_object().unwrap();
+        assert_eq!(paths.len(), 3);
+        assert_eq!(paths["/healthz"]["get"].get("deprecated"), None);
+        assert_eq!(paths["/api/v1/sessions"]["get"].get("deprecated"), None);
+        assert_eq!(paths["/sessions"]["get"]["deprecated"], true);
+        assert_eq!(paths["/sessions"]["parameters"], json!([]));
+
+        let v1_only = mount_openapi(document, ApiMount { legacy_routes: false });
+        let paths = v1_only["paths"].as_object().unwrap();
+        assert!(paths.contains_key("/api/v1/sessions"));
+        assert!(!paths.contains_key("/sessions"));
+        assert!(paths.contains_key("/healthz"));
+    }
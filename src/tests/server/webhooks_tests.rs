@@ -0,0 +1,38 @@
+    use super::*;
+
+    #[test]
+    fn signs_timestamp_and_body() {
+        let body = br#"{"event":"TEST"}"#;
+        assert_eq!(
+            sign_payload("topsecret", 1_700_000_000, body).unwrap(),
+            "9f730b412bb97bd671507a4f9292045702eb9a2d4b56769739ddd1853beb9801"
+        );
+        assert_eq!(
+            signature_header("topsecret", 1_700_000_000, body).unwrap(),
+            "t=1700000000,v1=9f730b412bb97bd671507a4f9292045702eb9a2d4b56769739ddd1853beb9801"
+        );
+    }
+
+    #[test]
+    fn signature_changes_with_timestamp_and_secret() {
+        let body = b"{}";
+        let base = sign_payload("a", 1, body).unwrap();
+        assert_ne!(base, sign_payload("a", 2, body).unwrap());
+        assert_ne!(base, sign_payload("b", 1, body).unwrap());
+    }
+
+    #[test]
+    fn event_filter_accepts_empty_lists() {
+        assert!(event_allowed(&None, "MESSAGES_UPSERT"));
+        assert!(event_allowed(&Some(vec![]), "MESSAGES_UPSERT"));
+        assert!(!event_allowed(&Some(vec!["QRCODE_UPDATED".to_string()]), "MESSAGES_UPSERT"));
+    }
+
+    #[test]
+    fn response_snippet_is_bounded_and_utf8_safe() {
+        let long = "é".repeat(400);
+        let snippet = response_snippet(long.as_bytes());
+        assert!(snippet.len() <= RESPONSE_SNIPPET_BYTES);
+        assert!(snippet.chars().all(|c| c == 'é'));
+        assert_eq!(response_snippet(b"ok"), "ok");
+    }
@@ -0,0 +1,50 @@
+    use super::*;
+
+    #[test]
+    fn admin_prefixes_match_whole_segments() {
+        assert!(is_admin_only("/manager/config"));
+        assert!(is_admin_only("/keys"));
+        assert!(is_admin_only("/workspaces/abc/instances/sales"));
+        assert!(is_admin_only("/ws"));
+        assert!(!is_admin_only("/keysmith/profile"));
+        assert!(!is_admin_only("/sessions"));
+        assert!(!is_admin_only("/contacts"));
+    }
+
+    #[test]
+    fn instance_prefers_route_then_query_then_body() {
+        let body = json!({"session": "from-body"});
+        assert_eq!(
+            instance_from_request("/:session/groups", "/sales/groups", Some("session=other"), Some(&body)),
+            Some("sales".to_string())
+        );
+        assert_eq!(
+            instance_from_request("/messages", "/messages", Some("chatId=1&session=support"), Some(&body)),
+            Some("support".to_string())
+        );
+        assert_eq!(
+            instance_from_request("/sendMessage", "/sendMessage", None, Some(&body)),
+            Some("from-body".to_string())
+        );
+        assert_eq!(
+            instance_from_request("/contacts", "/contacts", Some("session="), None),
+            None
+        );
+    }
+
+    #[test]
+    fn only_self_scoped_routes_are_instance_free() {
+        assert!(is_instance_free(&Method::GET, "/sessions"));
+        assert!(!is_instance_free(&Method::POST, "/sessions"));
+        assert!(!is_instance_free(&Method::GET, "/contacts"));
+        assert!(is_instance_free(&Method::GET, "/instance/fetchInstances"));
+        assert!(is_instance_free(&Method::POST, "/webhook/redeliver/abc"));
+        assert!(is_instance_free(&Method::POST, "/graphql"));
+    }
+
+    #[test]
+    fn scope_exposes_workspace() {
+        let id = Uuid::new_v4();
+        assert_eq!(Scope::Workspace(id).workspace(), Some(id));
+        assert_eq!(Scope::Admin.workspace(), None);
+    }
@@ -0,0 +1,64 @@
+    use super::*;
+
+    #[test]
+    fn parses_lag_policies() {
+        assert_eq!(LagPolicy::parse("drop_oldest"), Some(LagPolicy::DropOldest));
+        assert_eq!(LagPolicy::parse("Drop-Oldest"), Some(LagPolicy::DropOldest));
+        assert_eq!(LagPolicy::parse("disconnect"), Some(LagPolicy::Disconnect));
+        assert_eq!(LagPolicy::parse("block"), None);
+    }
+
+    #[test]
+    fn origin_is_checked_once_ws_origins_are_set() {
+        let mut config = RuntimeConfig {
+            cors_origins: vec!["https://app.example.com".to_string()],
+            ..RuntimeConfig::default()
+        };
+        assert!(origin_allowed(&config, Some("https://evil.example.com")));
+        config.ws_cors_origins = Some(vec!["https://*.example.com".to_string()]);
+        assert!(origin_allowed(&config, Some("https://app.example.com")));
+        assert!(origin_allowed(&config, None));
+        assert!(!origin_allowed(&config, Some("https://evil.io")));
+    }
+
+    #[tokio::test]
+    async fn slow_subscriber_lags_past_the_buffer() {
+        let hub = EventHub::new(WsConfig {
+            buffer: 2,
+            ..WsConfig::default()
+        });
+        let mut rx = hub.subscribe();
+        for i in 0..5 {
+            hub.publish(&BusEvent::new(json!({"event": "TEST", "data": i})));
+        }
+
+        assert!(matches!(rx.recv().await, Err(RecvError::Lagged(3))));
+        let next = rx.recv().await.unwrap();
+        assert_eq!(next.payload["data"], 3);
+        assert_eq!(hub.stats().queued, 5);
+    }
+
+    #[test]
+    fn publishing_without_clients_skips_the_event() {
+        let hub = EventHub::new(WsConfig::default());
+        let event = BusEvent::new(json!({"event": "TEST"}));
+        hub.publish(&event);
+        assert_eq!(hub.stats(), SinkCounts::default());
+
+        let _rx = hub.subscribe();
+        hub.publish(&event);
+        assert_eq!(hub.stats().queued, 1);
+        // Clients serialize when they send.
+        assert!(!event.is_serialized());
+    }
+
+    #[test]
+    fn client_gauge_follows_guards() {
+        let hub = EventHub::new(WsConfig::default());
+        {
+            let _a = hub.track_client();
+            let _b = hub.track_client();
+            assert_eq!(hub.connected_clients(), 2);
+        }
+        assert_eq!(hub.connected_clients(), 0);
+    }
@@ -0,0 +1,130 @@
+    use super::*;
+    use crate::store::SqliteStore;
+    use crate::store::persistence_manager::PersistenceManager;
+    use std::time::Duration;
+
+    /// A file database: each pooled `:memory:` connection would get its own
+    /// empty schema.
+    async fn sqlite() -> (tempfile::TempDir, Arc<dyn Backend>) {
+        let dir = tempfile::tempdir().expect("temp dir");
+        let path = dir.path().join("auth.db");
+        let store = SqliteStore::new(&path.to_string_lossy())
+            .await
+            .expect("test backend should initialize");
+        (dir, Arc::new(store))
+    }
+
+    #[tokio::test]
+    async fn buffers_writes_until_flush() {
+        let (_dir, inner) = sqlite().await;
+        let notify = Arc::new(Notify::new());
+        let store = WriteBehindBackend::new(inner.clone(), notify);
+
+        store.put_session("alice.0", b"v1").await.unwrap();
+        store.put_session("alice.0", b"v2").await.unwrap();
+        store.put_identity("alice.0", [7; 32]).await.unwrap();
+        store.put_sender_key("group\nalice.0", b"sk").await.unwrap();
+
+        assert!(store.is_dirty());
+        assert_eq!(
+            store.get_session("alice.0").await.unwrap(),
+            Some(b"v2".to_vec())
+        );
+        assert_eq!(
+            store.load_identity("alice.0").await.unwrap(),
+            Some(vec![7; 32])
+        );
+        assert_eq!(inner.get_session("alice.0").await.unwrap(), None);
+
+        store.flush().await.unwrap();
+        assert!(!store.is_dirty());
+        assert_eq!(
+            inner.get_session("alice.0").await.unwrap(),
+            Some(b"v2".to_vec())
+        );
+        assert_eq!(
+            inner.load_identity("alice.0").await.unwrap(),
+            Some(vec![7; 32])
+        );
+        assert_eq!(
+            inner.get_sender_key("group\nalice.0").await.unwrap(),
+            Some(b"sk".to_vec())
+        );
+    }
+
+    #[tokio::test]
+    async fn deletes_hide_stored_keys_until_flushed() {
+        let (_dir, inner) = sqlite().await;
+        inner.put_session("bob.0", b"stored").await.unwrap();
+        let store = WriteBehindBackend::new(inner.clone(), Arc::new(Notify::new()));
+
+        store.delete_session("bob.0").await.unwrap();
+        assert_eq!(store.get_session("bob.0").await.unwrap(), None);
+        assert!(!store.has_session("bob.0").await.unwrap());
+        assert!(inner.has_session("bob.0").await.unwrap());
+
+        store.flush().await.unwrap();
+        assert!(!inner.has_session("bob.0").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn batch_reads_merge_buffered_and_stored_sessions() {
+        let (_dir, inner) = sqlite().await;
+        inner.put_session("a.0", b"a").await.unwrap();
+        inner.put_session("b.0", b"stale").await.unwrap();
+        let store = WriteBehindBackend::new(inner, Arc::new(Notify::new()));
+        store
+            .put_sessions_batch(&[("b.0", b"fresh".as_slice()), ("c.0", b"c".as_slice())])
+            .await
+            .unwrap();
+        store.delete_session("a.0").await.unwrap();
+
+        let mut sessions = store
+            .get_sessions_batch(&["a.0", "b.0", "c.0", "d.0"])
+            .await
+            .unwrap();
+        sessions.sort();
+        assert_eq!(
+            sessions,
+            vec![
+                ("b.0".to_string(), b"fresh".to_vec()),
+                ("c.0".to_string(), b"c".to_vec()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn persistence_manager_flushes_debounced_keys() {
+        let (_dir, inner) = sqlite().await;
+        let pm = PersistenceManager::with_debounce(inner.clone(), Duration::from_secs(60))
+            .await
+            .expect("persistence manager should initialize");
+
+        pm.backend().put_session("carol.0", b"s").await.unwrap();
+        pm.modify_device(|device| device.push_name = "Carol".to_string())
+            .await;
+        assert!(pm.is_dirty());
+        assert_eq!(inner.get_session("carol.0").await.unwrap(), None);
+
+        pm.flush().await.unwrap();
+        assert!(!pm.is_dirty());
+        assert_eq!(
+            inner.get_session("carol.0").await.unwrap(),
+            Some(b"s".to_vec())
+        );
+        assert_eq!(inner.load().await.unwrap().unwrap().push_name, "Carol");
+    }
+
+    #[tokio::test]
+    async fn zero_debounce_writes_through() {
+        let (_dir, inner) = sqlite().await;
+        let pm = PersistenceManager::new(inner.clone())
+            .await
+            .expect("persistence manager should initialize");
+
+        pm.backend().put_session("dave.0", b"s").await.unwrap();
+        assert_eq!(
+            inner.get_session("dave.0").await.unwrap(),
+            Some(b"s".to_vec())
+        );
+    }
@@ -34,3 +34,52 @@ self.__swData=JSON.parse(/*BTDS*/"{\"dynamic_data\":{\"dynamic_modules\":{\"cr:3
 
         assert_eq!(parse_sw_js(s), Some((2, 3000, 1026131876)));
     }
+
+    #[test]
+    fn parse_version_requires_three_parts() {
+        assert_eq!(parse_version("2.3000.1015901307"), Some((2, 3000, 1015901307)));
+        assert_eq!(parse_version(" 2.3000.1 "), Some((2, 3000, 1)));
+        assert_eq!(parse_version("2.3000"), None);
+        assert_eq!(parse_version("2.3000.1.4"), None);
+        assert_eq!(parse_version("2.x.1"), None);
+        assert_eq!(format_version((2, 3000, 1)), "2.3000.1");
+    }
+
+    #[test]
+    fn rejection_allows_one_refetch_then_walks_fallbacks() {
+        let manager = WaVersionManager::new(VersionConfig {
+            fallbacks: vec![(2, 3000, 2)],
+            ..Default::default()
+        });
+
+        assert!(manager.mark_rejected((2, 3000, 5)));
+        assert!(manager.mark_rejected((2, 3000, 6)));
+        assert!(!manager.mark_rejected((2, 3000, 2)));
+
+        manager.set_config(VersionConfig::default());
+        assert!(manager.rejected().is_empty());
+    }
+
+    #[test]
+    fn pinned_version_is_never_retried() {
+        let manager = WaVersionManager::new(VersionConfig {
+            pinned: Some((2, 3000, 1)),
+            fallbacks: vec![(2, 3000, 2)],
+            ..Default::default()
+        });
+        assert!(!manager.mark_rejected((2, 3000, 1)));
+    }
+
+    #[test]
+    fn only_fetched_versions_restart_the_staleness_clock() {
+        use warp_core::store::{Device, apply_command_to_device};
+
+        let mut device = Device::new();
+        device.app_version_last_fetched_ms = 1;
+        apply_command_to_device(&mut device, DeviceCommand::UseAppVersion((2, 3000, 2)));
+        assert_eq!(device.app_version_tertiary, 2);
+        assert_eq!(device.app_version_last_fetched_ms, 1);
+
+        apply_command_to_device(&mut device, DeviceCommand::SetAppVersion((2, 3000, 3)));
+        assert!(!is_stale(device.app_version_last_fetched_ms));
+    }
@@ -1,6 +1,7 @@
 use anyhow::{Result, anyhow};
 use base64::Engine;
 use serde::Deserialize;
+use std::path::Path;
 use warp_core::download::MediaType;
 
 use crate::client::Client;
@@ -24,19 +25,66 @@ struct RawUploadResponse {
 
 impl Client {
     pub async fn upload(&self, data: Vec<u8>, media_type: MediaType) -> Result<UploadResponse> {
-        let enc = tokio::task::spawn_blocking({
-            let data = data.clone();
-            move || warp_core::upload::encrypt_media(&data, media_type)
+        let file_length = data.len() as u64;
+        let enc = tokio::task::spawn_blocking(move || {
+            warp_core::upload::encrypt_media(&data, media_type)
         })
         .await??;
 
+        let raw = self
+            .post_encrypted(enc.data_to_upload, &enc.file_enc_sha256, media_type)
+            .await?;
+        Ok(UploadResponse {
+            url: raw.url,
+            direct_path: raw.direct_path,
+            media_key: enc.media_key.to_vec(),
+            file_enc_sha256: enc.file_enc_sha256.to_vec(),
+            file_sha256: enc.file_sha256.to_vec(),
+            file_length,
+        })
+    }
+
+    /// Uploads the file at `path`, encrypting it in chunks straight from
+    /// disk. Only the encrypted body is held in memory, since the HTTP
+    /// client sends whole bodies.
+    pub async fn upload_file(&self, path: &Path, media_type: MediaType) -> Result<UploadResponse> {
+        let path = path.to_path_buf();
+        let (info, body) = tokio::task::spawn_blocking(move || {
+            let file = std::fs::File::open(&path)?;
+            // Ciphertext is at most a padding block plus the MAC longer.
+            let capacity = usize::try_from(file.metadata()?.len()).unwrap_or(0) + 32;
+            let mut body = Vec::with_capacity(capacity);
+            let info = warp_core::upload::encrypt_media_stream(file, &mut body, media_type)?;
+            Ok::<_, anyhow::Error>((info, body))
+        })
+        .await??;
+
+        let raw = self
+            .post_encrypted(body, &info.file_enc_sha256, media_type)
+            .await?;
+        Ok(UploadResponse {
+            url: raw.url,
+            direct_path: raw.direct_path,
+            media_key: info.media_key.to_vec(),
+            file_enc_sha256: info.file_enc_sha256.to_vec(),
+            file_sha256: info.file_sha256.to_vec(),
+            file_length: info.file_length,
+        })
+    }
+
+    async fn post_encrypted(
+        &self,
+        body: Vec<u8>,
+        file_enc_sha256: &[u8; 32],
+        media_type: MediaType,
+    ) -> Result<RawUploadResponse> {
         let media_conn = self.refresh_media_conn(false).await?;
         let host = media_conn
             .hosts
             .first()
             .ok_or_else(|| anyhow!("No media hosts"))?;
 
-        let token = base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(enc.file_enc_sha256);
+        let token = base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(file_enc_sha256);
         let mms_type = media_type.mms_type();
         let scheme = "https";
         let url = format!(
@@ -47,7 +95,7 @@ impl Client {
         let request = HttpRequest::post(url)
             .with_header("Content-Type", "application/octet-stream")
             .with_header("Origin", "https://web.whatsapp.com")
-            .with_body(enc.data_to_upload);
+            .with_body(body);
 
         let response = self.http_client.execute(request).await?;
 
@@ -69,16 +117,6 @@ impl Client {
             ));
         }
 
-        let raw: RawUploadResponse = serde_json::from_slice(&response.body)?;
-
-        let result = UploadResponse {
-            url: raw.url,
-            direct_path: raw.direct_path,
-            media_key: enc.media_key.to_vec(),
-            file_enc_sha256: enc.file_enc_sha256.to_vec(),
-            file_sha256: enc.file_sha256.to_vec(),
-            file_length: data.len() as u64,
-        };
-        Ok(result)
+        Ok(serde_json::from_slice(&response.body)?)
     }
 }
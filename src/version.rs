@@ -2,8 +2,11 @@ use crate::http::{HttpClient, HttpRequest};
 use crate::store::commands::DeviceCommand;
 use crate::store::persistence_manager::PersistenceManager;
 use anyhow::{Result, anyhow};
-use log::info;
-use std::sync::Arc;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use thiserror::Error;
 
 pub use warp_core::version::parse_sw_js;
 
@@ -21,6 +24,9 @@ pub async fn fetch_latest_app_version(
         .execute(request)
         .await
         .map_err(|e| anyhow!("HTTP request to {} failed: {}", SW_URL, e))?;
+    if response.status_code >= 300 {
+        return Err(anyhow!("{} returned status {}", SW_URL, response.status_code));
+    }
 
     let body_str = response
         .body_string()
@@ -30,51 +36,258 @@ pub async fn fetch_latest_app_version(
         .ok_or_else(|| anyhow!("Could not find 'client_revision' version in sw.js response"))
 }
 
-pub async fn resolve_and_update_version(
-    persistence_manager: &Arc<PersistenceManager>,
-    http_client: &Arc<dyn HttpClient>,
-    override_version: Option<(u32, u32, u32)>,
-) -> Result<()> {
-    if let Some((p, s, t)) = override_version {
-        info!("Using user-provided override version: {}.{}.{}", p, s, t);
-        persistence_manager
-            .process_command(DeviceCommand::SetAppVersion((p, s, t)))
-            .await;
-        return Ok(());
+/// A WhatsApp web version as `(primary, secondary, tertiary)`.
+pub type AppVersion = (u32, u32, u32);
+
+/// Parses `"2.3000.1015901307"` into an [`AppVersion`].
+pub fn parse_version(value: &str) -> Option<AppVersion> {
+    let mut parts = value.trim().split('.').map(|p| p.trim().parse::<u32>());
+    let version = (
+        parts.next()?.ok()?,
+        parts.next()?.ok()?,
+        parts.next()?.ok()?,
+    );
+    if parts.next().is_some() {
+        return None;
+    }
+    Some(version)
+}
+
+/// Formats an [`AppVersion`] as a dotted string.
+pub fn format_version((p, s, t): AppVersion) -> String {
+    format!("{}.{}.{}", p, s, t)
+}
+
+/// Where the WA web version comes from when it is not pinned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VersionSource {
+    /// Scrape `client_revision` from web.whatsapp.com/sw.js (default).
+    ServiceWorker,
+    /// Never fetch; use the fallback list (then the stored version).
+    Static,
+}
+
+impl VersionSource {
+    /// Parses the `WA_VERSION_SOURCE` value.
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "sw" | "sw.js" | "service_worker" | "wa-version" | "fetch" => Some(Self::ServiceWorker),
+            "static" => Some(Self::Static),
+            _ => None,
+        }
     }
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum VersionConfigError {
+    #[error("invalid version {value:?} in {name}")]
+    InvalidVersion { name: &'static str, value: String },
+    #[error("invalid WA_VERSION_SOURCE {0:?} (expected sw or static)")]
+    InvalidSource(String),
+}
 
-    let device = persistence_manager.get_device_snapshot().await;
-    let last_fetched_ms = device.app_version_last_fetched_ms;
+/// Version selection policy: an exact pin, an ordered fallback list and the
+/// fetch source.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VersionConfig {
+    pub pinned: Option<AppVersion>,
+    pub fallbacks: Vec<AppVersion>,
+    pub source: VersionSource,
+}
+
+impl Default for VersionConfig {
+    fn default() -> Self {
+        Self {
+            pinned: None,
+            fallbacks: Vec::new(),
+            source: VersionSource::ServiceWorker,
+        }
+    }
+}
+
+/// Result of the latest sw.js fetch, reported by the health checks.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FetchOutcome {
+    pub at: chrono::DateTime<chrono::Utc>,
+    pub latency_ms: u64,
+    /// Why the fetch failed; `None` when it succeeded.
+    pub error: Option<String>,
+}
+
+/// Picks the WA web version used for each connection attempt and remembers
+/// versions rejected by the server (ClientOutdated).
+#[derive(Debug)]
+pub struct WaVersionManager {
+    config: RwLock<VersionConfig>,
+    current: RwLock<Option<AppVersion>>,
+    rejected: RwLock<Vec<AppVersion>>,
+    last_fetch: RwLock<Option<FetchOutcome>>,
+    force_refresh: AtomicBool,
+}
+
+impl WaVersionManager {
+    /// Creates a manager with the given policy.
+    pub fn new(config: VersionConfig) -> Self {
+        Self {
+            config: RwLock::new(config),
+            current: RwLock::new(None),
+            rejected: RwLock::new(Vec::new()),
+            last_fetch: RwLock::new(None),
+            force_refresh: AtomicBool::new(false),
+        }
+    }
+
+    /// Current policy.
+    pub fn config(&self) -> VersionConfig {
+        read(&self.config).clone()
+    }
+
+    /// Replaces the policy at runtime; applies from the next connection attempt.
+    pub fn set_config(&self, config: VersionConfig) {
+        *write(&self.config) = config;
+        write(&self.rejected).clear();
+        self.force_refresh.store(true, Ordering::Relaxed);
+    }
 
-    let needs_fetch = if last_fetched_ms == 0 {
-        true
-    } else {
-        match chrono::DateTime::from_timestamp_millis(last_fetched_ms) {
-            Some(last_fetched_dt) => {
-                chrono::Utc::now().signed_duration_since(last_fetched_dt)
-                    > chrono::Duration::hours(24)
+    /// Version used by the latest connection attempt.
+    pub fn current(&self) -> Option<AppVersion> {
+        *read(&self.current)
+    }
+
+    /// Versions the server rejected since the policy was last changed.
+    pub fn rejected(&self) -> Vec<AppVersion> {
+        read(&self.rejected).clone()
+    }
+
+    /// Outcome of the latest sw.js fetch, if one was attempted.
+    pub fn last_fetch(&self) -> Option<FetchOutcome> {
+        read(&self.last_fetch).clone()
+    }
+
+    /// Records that the server rejected `version` as outdated. Returns whether
+    /// another candidate (a fresh fetch or an untried fallback) is available.
+    pub fn mark_rejected(&self, version: AppVersion) -> bool {
+        {
+            let mut rejected = write(&self.rejected);
+            if !rejected.contains(&version) {
+                rejected.push(version);
             }
-            None => true,
         }
-    };
-
-    if needs_fetch {
-        info!("WhatsApp version is stale or missing, fetching latest...");
-        let (p, s, t) = fetch_latest_app_version(http_client)
-            .await
-            .map_err(|e| anyhow!("Failed to fetch latest WhatsApp version: {}", e))?;
-        info!("Fetched latest version: {}.{}.{}", p, s, t);
-        persistence_manager
-            .process_command(DeviceCommand::SetAppVersion((p, s, t)))
-            .await;
-    } else {
-        info!(
-            "Using cached version: {}.{}.{}",
-            device.app_version_primary, device.app_version_secondary, device.app_version_tertiary
+        self.force_refresh.store(true, Ordering::Relaxed);
+
+        let config = self.config();
+        if config.pinned.is_some() {
+            return false;
+        }
+        let rejected = read(&self.rejected);
+        let untried_fallback = config.fallbacks.iter().any(|v| !rejected.contains(v));
+        // One refetch after a rejection, then walk the fallback list.
+        let can_refetch = config.source == VersionSource::ServiceWorker && rejected.len() == 1;
+        untried_fallback || can_refetch
+    }
+
+    /// Resolves the version for the next connection and stores it on the device.
+    ///
+    /// Order: pin → (sw.js fetch, unless cached and fresh) → fallbacks → stored version.
+    pub async fn resolve_and_update(
+        &self,
+        persistence_manager: &Arc<PersistenceManager>,
+        http_client: &Arc<dyn HttpClient>,
+    ) -> Result<AppVersion> {
+        let (version, fetched) = self.select(persistence_manager, http_client).await?;
+        info!("Using WhatsApp web version {}", format_version(version));
+        // Only a real sw.js fetch restarts the staleness clock.
+        let command = if fetched {
+            DeviceCommand::SetAppVersion(version)
+        } else {
+            DeviceCommand::UseAppVersion(version)
+        };
+        persistence_manager.process_command(command).await;
+        *write(&self.current) = Some(version);
+        Ok(version)
+    }
+
+    /// The version to use and whether it was just fetched from sw.js.
+    async fn select(
+        &self,
+        persistence_manager: &Arc<PersistenceManager>,
+        http_client: &Arc<dyn HttpClient>,
+    ) -> Result<(AppVersion, bool)> {
+        let config = self.config();
+        if let Some(pinned) = config.pinned {
+            return Ok((pinned, false));
+        }
+
+        let rejected = self.rejected();
+        let usable = |v: &AppVersion| !rejected.contains(v) && *v != (0, 0, 0);
+        let device = persistence_manager.get_device_snapshot().await;
+        let stored = (
+            device.app_version_primary,
+            device.app_version_secondary,
+            device.app_version_tertiary,
         );
+
+        if config.source == VersionSource::ServiceWorker {
+            let force = self.force_refresh.swap(false, Ordering::Relaxed);
+            if !force && !is_stale(device.app_version_last_fetched_ms) && usable(&stored) {
+                return Ok((stored, false));
+            }
+
+            info!("WhatsApp version is stale or missing, fetching latest...");
+            let started = std::time::Instant::now();
+            let fetched = fetch_latest_app_version(http_client).await;
+            *write(&self.last_fetch) = Some(FetchOutcome {
+                at: chrono::Utc::now(),
+                latency_ms: u64::try_from(started.elapsed().as_millis()).unwrap_or(u64::MAX),
+                error: fetched.as_ref().err().map(ToString::to_string),
+            });
+            match fetched {
+                Ok(fetched) if usable(&fetched) => return Ok((fetched, true)),
+                Ok(fetched) => {
+                    warn!(
+                        "Fetched version {} was rejected before",
+                        format_version(fetched)
+                    );
+                }
+                Err(e) => warn!("Failed to fetch latest WhatsApp version: {}", e),
+            }
+            // Whatever we fall back to, try fetching again next time.
+            self.force_refresh.store(true, Ordering::Relaxed);
+        }
+
+        if let Some(fallback) = config.fallbacks.iter().copied().find(|v| usable(v)) {
+            return Ok((fallback, false));
+        }
+        if usable(&stored) {
+            return Ok((stored, false));
+        }
+        Err(anyhow!(
+            "No usable WhatsApp web version (pin, fetch, fallbacks all unavailable)"
+        ))
     }
+}
+
+fn is_stale(last_fetched_ms: i64) -> bool {
+    if last_fetched_ms == 0 {
+        return true;
+    }
+    match chrono::DateTime::from_timestamp_millis(last_fetched_ms) {
+        Some(last_fetched_dt) => {
+            chrono::Utc::now().signed_duration_since(last_fetched_dt) > chrono::Duration::hours(24)
+        }
+        None => true,
+    }
+}
+
+fn read<T>(lock: &RwLock<T>) -> RwLockReadGuard<'_, T> {
+    lock.read().unwrap_or_else(PoisonError::into_inner)
+}
 
-    Ok(())
+fn write<T>(lock: &RwLock<T>) -> RwLockWriteGuard<'_, T> {
+    lock.write().unwrap_or_else(PoisonError::into_inner)
 }
 
 #[cfg(test)]
@@ -0,0 +1,5 @@
+CREATE TABLE IF NOT EXISTS api_runtime_config (
+    key TEXT PRIMARY KEY,
+    value JSONB NOT NULL,
+    updated_at TIMESTAMPTZ DEFAULT now()
+);
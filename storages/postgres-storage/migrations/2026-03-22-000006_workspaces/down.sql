@@ -0,0 +1,5 @@
+DROP INDEX IF EXISTS idx_api_sessions_workspace;
+DROP INDEX IF EXISTS idx_api_keys_key_hash;
+ALTER TABLE api_sessions DROP COLUMN IF EXISTS workspace_id;
+ALTER TABLE api_keys DROP COLUMN IF EXISTS workspace_id;
+DROP TABLE IF EXISTS api_workspaces;
@@ -0,0 +1,11 @@
+CREATE TABLE IF NOT EXISTS api_workspaces (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    name TEXT NOT NULL UNIQUE,
+    created_at TIMESTAMPTZ DEFAULT now()
+);
+
+ALTER TABLE api_keys ADD COLUMN IF NOT EXISTS workspace_id UUID REFERENCES api_workspaces(id) ON DELETE CASCADE;
+ALTER TABLE api_sessions ADD COLUMN IF NOT EXISTS workspace_id UUID REFERENCES api_workspaces(id) ON DELETE SET NULL;
+
+CREATE INDEX IF NOT EXISTS idx_api_keys_key_hash ON api_keys (key_hash);
+CREATE INDEX IF NOT EXISTS idx_api_sessions_workspace ON api_sessions (workspace_id);
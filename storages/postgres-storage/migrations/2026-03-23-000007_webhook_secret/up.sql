@@ -0,0 +1 @@
+ALTER TABLE api_sessions ADD COLUMN IF NOT EXISTS webhook_secret TEXT;
@@ -0,0 +1,2 @@
+ALTER TABLE api_sessions DROP COLUMN IF EXISTS nats_events;
+ALTER TABLE api_sessions DROP COLUMN IF EXISTS nats_enabled;
@@ -0,0 +1,2 @@
+ALTER TABLE api_sessions ADD COLUMN IF NOT EXISTS nats_enabled BOOLEAN DEFAULT false;
+ALTER TABLE api_sessions ADD COLUMN IF NOT EXISTS nats_events JSONB;
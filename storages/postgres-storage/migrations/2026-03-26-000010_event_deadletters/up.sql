@@ -0,0 +1,13 @@
+CREATE TABLE IF NOT EXISTS event_deadletters (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    outbox_id UUID,
+    session TEXT,
+    event TEXT NOT NULL,
+    payload JSONB NOT NULL,
+    reason TEXT,
+    attempts INT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    replayed_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_event_deadletters_session ON event_deadletters (session, created_at);
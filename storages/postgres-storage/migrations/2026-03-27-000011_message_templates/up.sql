@@ -0,0 +1,11 @@
+CREATE TABLE IF NOT EXISTS message_templates (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    name TEXT NOT NULL UNIQUE,
+    title TEXT,
+    content TEXT NOT NULL,
+    footer TEXT,
+    variables JSONB NOT NULL DEFAULT '{}'::jsonb,
+    buttons JSONB NOT NULL DEFAULT '[]'::jsonb,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
@@ -0,0 +1,5 @@
+DROP INDEX IF EXISTS idx_api_sessions_cloud_phone_number_id;
+ALTER TABLE api_sessions DROP COLUMN IF EXISTS cloud_access_token;
+ALTER TABLE api_sessions DROP COLUMN IF EXISTS cloud_business_id;
+ALTER TABLE api_sessions DROP COLUMN IF EXISTS cloud_phone_number_id;
+ALTER TABLE api_sessions DROP COLUMN IF EXISTS integration;
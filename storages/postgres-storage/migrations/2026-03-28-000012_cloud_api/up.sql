@@ -0,0 +1,7 @@
+ALTER TABLE api_sessions ADD COLUMN IF NOT EXISTS integration TEXT NOT NULL DEFAULT 'WHATSAPP-BAILEYS';
+ALTER TABLE api_sessions ADD COLUMN IF NOT EXISTS cloud_phone_number_id TEXT;
+ALTER TABLE api_sessions ADD COLUMN IF NOT EXISTS cloud_business_id TEXT;
+ALTER TABLE api_sessions ADD COLUMN IF NOT EXISTS cloud_access_token TEXT;
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_api_sessions_cloud_phone_number_id
+    ON api_sessions (cloud_phone_number_id) WHERE cloud_phone_number_id IS NOT NULL;
@@ -0,0 +1,4 @@
+ALTER TABLE api_chats DROP COLUMN IF EXISTS mute_end_at;
+ALTER TABLE api_chats DROP COLUMN IF EXISTS marked_unread;
+ALTER TABLE api_chats DROP COLUMN IF EXISTS pinned;
+ALTER TABLE api_chats DROP COLUMN IF EXISTS archived;
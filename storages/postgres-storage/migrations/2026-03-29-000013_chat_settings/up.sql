@@ -0,0 +1,5 @@
+ALTER TABLE api_chats ADD COLUMN IF NOT EXISTS archived BOOLEAN NOT NULL DEFAULT false;
+ALTER TABLE api_chats ADD COLUMN IF NOT EXISTS pinned BOOLEAN NOT NULL DEFAULT false;
+ALTER TABLE api_chats ADD COLUMN IF NOT EXISTS marked_unread BOOLEAN NOT NULL DEFAULT false;
+-- Milliseconds since the epoch, -1 muted forever, NULL not muted.
+ALTER TABLE api_chats ADD COLUMN IF NOT EXISTS mute_end_at BIGINT;
@@ -0,0 +1 @@
+DROP INDEX IF EXISTS idx_api_messages_outgoing_per_day;
@@ -0,0 +1,3 @@
+-- Counts messages sent per instance per day for MAX_MESSAGES_PER_DAY.
+CREATE INDEX IF NOT EXISTS idx_api_messages_outgoing_per_day
+    ON api_messages (session, created_at) WHERE from_me;
@@ -0,0 +1,4 @@
+DROP INDEX IF EXISTS idx_api_sessions_metadata;
+DROP INDEX IF EXISTS idx_api_sessions_tags;
+ALTER TABLE api_sessions DROP COLUMN IF EXISTS metadata;
+ALTER TABLE api_sessions DROP COLUMN IF EXISTS tags;
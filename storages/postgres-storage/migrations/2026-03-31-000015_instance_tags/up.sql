@@ -0,0 +1,5 @@
+ALTER TABLE api_sessions ADD COLUMN IF NOT EXISTS tags JSONB NOT NULL DEFAULT '[]'::jsonb;
+ALTER TABLE api_sessions ADD COLUMN IF NOT EXISTS metadata JSONB NOT NULL DEFAULT '{}'::jsonb;
+-- Containment filters of /instance/fetchInstances (?tag=, ?metadata.key=).
+CREATE INDEX IF NOT EXISTS idx_api_sessions_tags ON api_sessions USING GIN (tags);
+CREATE INDEX IF NOT EXISTS idx_api_sessions_metadata ON api_sessions USING GIN (metadata);
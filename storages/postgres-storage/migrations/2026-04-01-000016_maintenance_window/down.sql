@@ -0,0 +1 @@
+ALTER TABLE api_sessions DROP COLUMN IF EXISTS maintenance_window;
@@ -0,0 +1,2 @@
+-- Per-instance maintenance window: {"cron": "0 3 * * *", "durationMinutes": 10}.
+ALTER TABLE api_sessions ADD COLUMN IF NOT EXISTS maintenance_window JSONB;
@@ -0,0 +1,6 @@
+DROP INDEX IF EXISTS idx_api_messages_wa_message_id;
+ALTER TABLE api_messages DROP COLUMN IF EXISTS status_history;
+ALTER TABLE api_messages DROP COLUMN IF EXISTS status_updated_at;
+ALTER TABLE api_messages DROP COLUMN IF EXISTS delivery_error;
+ALTER TABLE api_messages DROP COLUMN IF EXISTS delivery_status;
+ALTER TABLE api_messages DROP COLUMN IF EXISTS wa_message_id;
@@ -0,0 +1,9 @@
+ALTER TABLE api_messages ADD COLUMN IF NOT EXISTS wa_message_id TEXT;
+-- pending, server, delivered, read, played or failed; NULL until sent.
+ALTER TABLE api_messages ADD COLUMN IF NOT EXISTS delivery_status TEXT;
+ALTER TABLE api_messages ADD COLUMN IF NOT EXISTS delivery_error TEXT;
+ALTER TABLE api_messages ADD COLUMN IF NOT EXISTS status_updated_at TIMESTAMPTZ;
+ALTER TABLE api_messages ADD COLUMN IF NOT EXISTS status_history JSONB NOT NULL DEFAULT '[]'::jsonb;
+-- Receipts and acks are matched by WhatsApp id within the session.
+CREATE INDEX IF NOT EXISTS idx_api_messages_wa_message_id
+    ON api_messages (session, wa_message_id) WHERE wa_message_id IS NOT NULL;
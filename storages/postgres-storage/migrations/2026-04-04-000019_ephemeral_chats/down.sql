@@ -0,0 +1,3 @@
+ALTER TABLE api_messages DROP COLUMN IF EXISTS expires_at;
+ALTER TABLE api_chats DROP COLUMN IF EXISTS ephemeral_setting_at;
+ALTER TABLE api_chats DROP COLUMN IF EXISTS ephemeral_expiration;
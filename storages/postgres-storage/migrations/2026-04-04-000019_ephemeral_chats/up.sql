@@ -0,0 +1,6 @@
+-- Disappearing messages timer of the chat in seconds, 0 when off.
+ALTER TABLE api_chats ADD COLUMN IF NOT EXISTS ephemeral_expiration INT NOT NULL DEFAULT 0;
+-- Unix seconds of the last timer change.
+ALTER TABLE api_chats ADD COLUMN IF NOT EXISTS ephemeral_setting_at BIGINT;
+-- When a disappearing message is gone from the chat; NULL for regular messages.
+ALTER TABLE api_messages ADD COLUMN IF NOT EXISTS expires_at TIMESTAMPTZ;
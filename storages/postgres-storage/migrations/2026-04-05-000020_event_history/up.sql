@@ -0,0 +1,2 @@
+-- Reads of /events/history/:instance once the in-memory history is exhausted.
+CREATE INDEX IF NOT EXISTS idx_event_outbox_session_seq ON event_outbox (session, seq) WHERE dispatched_at IS NOT NULL;
@@ -0,0 +1,7 @@
+-- admin, write or read. Global keys created before roles existed never
+-- authenticated, so they stay without a role and keep not authenticating.
+ALTER TABLE api_keys ADD COLUMN IF NOT EXISTS role TEXT CHECK (role IN ('admin', 'write', 'read'));
+UPDATE api_keys SET role = 'write' WHERE role IS NULL AND workspace_id IS NOT NULL;
+ALTER TABLE api_keys ADD COLUMN IF NOT EXISTS expires_at TIMESTAMPTZ;
+-- JSON array of addresses and CIDR ranges; NULL allows any address.
+ALTER TABLE api_keys ADD COLUMN IF NOT EXISTS allowed_ips JSONB;
@@ -0,0 +1,12 @@
+-- One reaction per reactor and message; removing a reaction deletes its row.
+CREATE TABLE IF NOT EXISTS api_message_reactions (
+    session TEXT NOT NULL,
+    chat_id TEXT NOT NULL,
+    -- WhatsApp id of the message reacted to.
+    message_id TEXT NOT NULL,
+    reactor TEXT NOT NULL,
+    emoji TEXT NOT NULL,
+    from_me BOOLEAN NOT NULL DEFAULT FALSE,
+    reacted_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (session, message_id, reactor)
+);
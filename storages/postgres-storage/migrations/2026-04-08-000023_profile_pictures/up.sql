@@ -0,0 +1,12 @@
+-- Answers of /chat/fetchProfilePictureUrl; url is NULL when there is no picture.
+CREATE TABLE IF NOT EXISTS api_profile_pictures (
+    session TEXT NOT NULL,
+    jid TEXT NOT NULL,
+    -- preview or image.
+    size TEXT NOT NULL,
+    url TEXT,
+    picture_id TEXT,
+    expires_at TIMESTAMPTZ NOT NULL,
+    fetched_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (session, jid, size)
+);
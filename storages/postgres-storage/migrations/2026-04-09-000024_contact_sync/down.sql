@@ -0,0 +1,5 @@
+ALTER TABLE api_contacts DROP COLUMN IF EXISTS synced_at;
+ALTER TABLE api_contacts DROP COLUMN IF EXISTS devices;
+ALTER TABLE api_contacts DROP COLUMN IF EXISTS status;
+ALTER TABLE api_contacts DROP COLUMN IF EXISTS is_business;
+ALTER TABLE api_contacts DROP COLUMN IF EXISTS lid;
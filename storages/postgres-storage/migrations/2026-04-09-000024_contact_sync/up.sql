@@ -0,0 +1,8 @@
+-- Filled by /chat/syncContacts from usync answers.
+ALTER TABLE api_contacts ADD COLUMN IF NOT EXISTS lid TEXT;
+ALTER TABLE api_contacts ADD COLUMN IF NOT EXISTS is_business BOOLEAN NOT NULL DEFAULT FALSE;
+-- About text, NULL when hidden or empty.
+ALTER TABLE api_contacts ADD COLUMN IF NOT EXISTS status TEXT;
+-- JSON array of device ids, 0 being the phone.
+ALTER TABLE api_contacts ADD COLUMN IF NOT EXISTS devices JSONB;
+ALTER TABLE api_contacts ADD COLUMN IF NOT EXISTS synced_at TIMESTAMPTZ;
@@ -0,0 +1,2 @@
+-- Per-instance rules run on incoming messages: [{"id", "conditions", "actions", ...}].
+ALTER TABLE api_sessions ADD COLUMN IF NOT EXISTS auto_rules JSONB NOT NULL DEFAULT '[]'::jsonb;
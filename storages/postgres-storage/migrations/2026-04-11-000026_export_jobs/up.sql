@@ -0,0 +1,16 @@
+-- Chat history exports of `/chat/export/:instance`, polled at `/jobs/:id`.
+CREATE TABLE IF NOT EXISTS api_export_jobs (
+    id UUID PRIMARY KEY,
+    session TEXT NOT NULL,
+    format TEXT NOT NULL,
+    filters JSONB NOT NULL DEFAULT '{}'::jsonb,
+    status TEXT NOT NULL DEFAULT 'queued',
+    message_count BIGINT,
+    size_bytes BIGINT,
+    error TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    finished_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS api_export_jobs_session_idx ON api_export_jobs (session, created_at);
+CREATE INDEX IF NOT EXISTS api_export_jobs_finished_idx ON api_export_jobs (finished_at) WHERE status = 'done';
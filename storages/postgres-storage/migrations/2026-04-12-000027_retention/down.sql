@@ -0,0 +1,4 @@
+DROP INDEX IF EXISTS idx_webhook_outbox_session_created;
+DROP INDEX IF EXISTS idx_api_messages_expires;
+DROP INDEX IF EXISTS idx_api_messages_session_created;
+ALTER TABLE api_sessions DROP COLUMN IF EXISTS retention;
@@ -0,0 +1,6 @@
+-- Per-instance override of RETENTION_*_DAYS: {"messagesDays", "webhookLogsDays", "mediaDays"}.
+ALTER TABLE api_sessions ADD COLUMN IF NOT EXISTS retention JSONB;
+-- Lookups of the retention pruner.
+CREATE INDEX IF NOT EXISTS idx_api_messages_session_created ON api_messages (session, created_at);
+CREATE INDEX IF NOT EXISTS idx_api_messages_expires ON api_messages (expires_at) WHERE expires_at IS NOT NULL;
+CREATE INDEX IF NOT EXISTS idx_webhook_outbox_session_created ON webhook_outbox (session, created_at);
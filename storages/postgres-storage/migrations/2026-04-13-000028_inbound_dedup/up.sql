@@ -0,0 +1,10 @@
+-- Keys of received messages, so WhatsApp redeliveries are dropped across restarts and replicas.
+CREATE TABLE IF NOT EXISTS api_inbound_dedup (
+    session TEXT NOT NULL,
+    remote_jid TEXT NOT NULL,
+    message_id TEXT NOT NULL,
+    seen_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (session, remote_jid, message_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_api_inbound_dedup_seen_at ON api_inbound_dedup (seen_at);
@@ -0,0 +1,2 @@
+ALTER TABLE api_sessions DROP COLUMN IF EXISTS wa_version;
+ALTER TABLE api_sessions DROP COLUMN IF EXISTS browser;
@@ -0,0 +1,4 @@
+-- Browser the instance presents itself as: {"name", "os", "version"}; NULL keeps the default.
+ALTER TABLE api_sessions ADD COLUMN IF NOT EXISTS browser JSONB;
+-- WA web version pinned for the instance, e.g. 2.3000.1015901307; NULL follows WA_VERSION_*.
+ALTER TABLE api_sessions ADD COLUMN IF NOT EXISTS wa_version TEXT;
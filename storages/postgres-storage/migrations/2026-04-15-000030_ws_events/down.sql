@@ -0,0 +1 @@
+ALTER TABLE api_sessions DROP COLUMN IF EXISTS ws_events;
@@ -0,0 +1,2 @@
+-- Events sent to /ws for the instance, e.g. ["MESSAGES_*"]; NULL sends every event.
+ALTER TABLE api_sessions ADD COLUMN IF NOT EXISTS ws_events JSONB;
@@ -0,0 +1,8 @@
+-- Which node runs each instance. A node starts the runners of an instance only while it holds its lease; `epoch` grows on every takeover, so a node that lost the lease cannot renew it.
+CREATE TABLE IF NOT EXISTS instance_leases (
+    instance TEXT PRIMARY KEY,
+    holder TEXT NOT NULL,
+    epoch INTEGER NOT NULL DEFAULT 1,
+    expires_at TIMESTAMPTZ NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
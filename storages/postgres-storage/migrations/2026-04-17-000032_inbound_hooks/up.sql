@@ -0,0 +1,7 @@
+-- Inbound webhook of each instance: low-code tools post messages to `/integration/inbound/:instance` with a secret whose SHA-256 is kept here.
+CREATE TABLE IF NOT EXISTS api_inbound_hooks (
+    session TEXT PRIMARY KEY REFERENCES api_sessions(session),
+    secret_hash TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    last_used_at TIMESTAMPTZ
+);
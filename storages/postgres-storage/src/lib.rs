@@ -3,8 +3,11 @@
 //! This crate provides a PostgreSQL-based storage implementation for the chatwarp-api library.
 //! It implements all the required storage traits from warp_core::store::traits.
 
+mod pool;
 mod postgres_store;
 mod schema;
 
+pub use pool::{PoolOptions, PoolStats};
 pub use postgres_store::PostgresStore;
 pub use postgres_store::BindValue;
+pub use postgres_store::ResealedColumn;
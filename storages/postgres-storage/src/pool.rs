@@ -0,0 +1,118 @@
+use diesel::r2d2::{HandleEvent, event::CheckoutEvent, event::TimeoutEvent};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Connection pool sizing for [`crate::PostgresStore`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolOptions {
+    pub max_size: u32,
+    /// Idle connections kept open; `None` keeps `max_size`.
+    pub min_idle: Option<u32>,
+    /// How long a query waits for a free connection before failing.
+    pub acquire_timeout: Duration,
+}
+
+impl Default for PoolOptions {
+    fn default() -> Self {
+        Self {
+            max_size: 5,
+            min_idle: None,
+            acquire_timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+/// Snapshot of pool usage since startup.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PoolStats {
+    pub max_size: u32,
+    pub connections: u32,
+    pub idle: u32,
+    pub in_use: u32,
+    pub checkouts: u64,
+    pub timeouts: u64,
+    pub wait_ms_avg: f64,
+    pub wait_ms_max: f64,
+}
+
+/// Checkout wait times, fed by r2d2 pool events.
+#[derive(Debug, Default)]
+pub(crate) struct PoolMetrics {
+    checkouts: AtomicU64,
+    timeouts: AtomicU64,
+    wait_micros_total: AtomicU64,
+    wait_micros_max: AtomicU64,
+}
+
+impl PoolMetrics {
+    pub(crate) fn record_checkout(&self, wait: Duration) {
+        let micros = u64::try_from(wait.as_micros()).unwrap_or(u64::MAX);
+        self.checkouts.fetch_add(1, Ordering::Relaxed);
+        self.wait_micros_total.fetch_add(micros, Ordering::Relaxed);
+        self.wait_micros_max.fetch_max(micros, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_timeout(&self) {
+        self.timeouts.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn stats(&self, max_size: u32, connections: u32, idle: u32) -> PoolStats {
+        let checkouts = self.checkouts.load(Ordering::Relaxed);
+        let total = self.wait_micros_total.load(Ordering::Relaxed);
+        PoolStats {
+            max_size,
+            connections,
+            idle,
+            in_use: connections.saturating_sub(idle),
+            checkouts,
+            timeouts: self.timeouts.load(Ordering::Relaxed),
+            wait_ms_avg: if checkouts == 0 {
+                0.0
+            } else {
+                total as f64 / checkouts as f64 / 1000.0
+            },
+            wait_ms_max: self.wait_micros_max.load(Ordering::Relaxed) as f64 / 1000.0,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct MetricsHandler(pub(crate) Arc<PoolMetrics>);
+
+impl HandleEvent for MetricsHandler {
+    fn handle_checkout(&self, event: CheckoutEvent) {
+        self.0.record_checkout(event.duration());
+    }
+
+    fn handle_timeout(&self, _event: TimeoutEvent) {
+        self.0.record_timeout();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stats_average_checkout_waits() {
+        let metrics = PoolMetrics::default();
+        metrics.record_checkout(Duration::from_millis(2));
+        metrics.record_checkout(Duration::from_millis(6));
+        metrics.record_timeout();
+
+        let stats = metrics.stats(5, 3, 1);
+        assert_eq!(stats.in_use, 2);
+        assert_eq!(stats.checkouts, 2);
+        assert_eq!(stats.timeouts, 1);
+        assert_eq!(stats.wait_ms_avg, 4.0);
+        assert_eq!(stats.wait_ms_max, 6.0);
+    }
+
+    #[test]
+    fn stats_without_checkouts_are_zero() {
+        let stats = PoolMetrics::default().stats(5, 0, 0);
+        assert_eq!(stats.wait_ms_avg, 0.0);
+        assert_eq!(stats.in_use, 0);
+    }
+}
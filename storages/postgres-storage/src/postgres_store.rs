@@ -1,14 +1,15 @@
+use crate::pool::{MetricsHandler, PoolMetrics, PoolOptions, PoolStats};
 use crate::schema::*;
 use async_trait::async_trait;
 use diesel::QueryableByName;
 use diesel::pg::Pg;
 use diesel::pg::PgConnection;
 use diesel::prelude::*;
-use diesel::query_builder::BoxedSqlQuery;
+use diesel::query_builder::{BoxedSqlQuery, SqlQuery};
 
 use diesel::r2d2::{ConnectionManager, Pool};
 use diesel::sql_query;
-use diesel::sql_types::{Bool, Int4, Jsonb, Nullable, Text, Uuid as SqlUuid};
+use diesel::sql_types::{Binary, Bool, Int4, Jsonb, Nullable, Text, Uuid as SqlUuid};
 use diesel_migrations::{EmbeddedMigrations, MigrationHarness, embed_migrations};
 use log::warn;
 use prost::Message;
@@ -21,6 +22,7 @@ use warp_core::appstate::processor::AppStateMutationMAC;
 use warp_core::libsignal::protocol::{KeyPair, PrivateKey, PublicKey};
 use warp_core::store::Device as CoreDevice;
 use warp_core::store::error::{Result, StoreError, db_err};
+use warp_core::store::secrets::{self, Keyring, SecretsError};
 use warp_core::store::traits::*;
 
 const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations");
@@ -50,7 +52,10 @@ type DeviceRow = (
 pub struct PostgresStore {
     pub(crate) pool: PgPool,
     pub(crate) db_semaphore: Arc<tokio::sync::Semaphore>,
+    pool_metrics: Arc<PoolMetrics>,
     device_id: i32,
+    /// Seals key material on write when set; see [`PostgresStore::with_keyring`].
+    keyring: Option<Keyring>,
 }
 
 #[derive(Debug, Clone)]
@@ -70,14 +75,83 @@ struct JsonRow {
     value: Value,
 }
 
+/// Binary columns sealed with the store keyring.
+const SECRET_COLUMNS: &[(&str, &str)] = &[
+    ("device", "noise_key"),
+    ("device", "identity_key"),
+    ("device", "signed_pre_key"),
+    ("device", "adv_secret_key"),
+    ("sessions", "record"),
+    ("sender_keys", "record"),
+    ("prekeys", "key"),
+    ("signed_prekeys", "record"),
+    ("app_state_keys", "key_data"),
+];
+
+/// Text columns of the HTTP API tables sealed with [`Keyring::seal_text`].
+const SECRET_TEXT_COLUMNS: &[(&str, &str)] = &[
+    ("api_sessions", "webhook_secret"),
+    ("api_sessions", "cloud_access_token"),
+];
+
+#[derive(QueryableByName)]
+struct SecretRow {
+    #[diesel(sql_type = Text)]
+    row_id: String,
+    #[diesel(sql_type = Binary)]
+    data: Vec<u8>,
+}
+
+/// Rows of one column rewritten by [`PostgresStore::reseal_secrets`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResealedColumn {
+    pub table: &'static str,
+    pub column: &'static str,
+    pub rows: usize,
+}
+
+fn prepare_schema(
+    conn: &mut PgConnection,
+    run_migrations: bool,
+) -> std::result::Result<(), StoreError> {
+    if run_migrations {
+        conn.run_pending_migrations(MIGRATIONS)
+            .map_err(|e| StoreError::Migration(e.to_string()))?;
+        return Ok(());
+    }
+
+    let pending = conn
+        .pending_migrations(MIGRATIONS)
+        .map_err(|e| StoreError::Migration(e.to_string()))?;
+    if !pending.is_empty() {
+        return Err(StoreError::Migration(format!(
+            "{} pending migration(s); start with DATABASE_RUN_MIGRATIONS=true or --migrate-only",
+            pending.len()
+        )));
+    }
+    Ok(())
+}
+
 impl PostgresStore {
     pub async fn new(database_url: &str) -> std::result::Result<Self, StoreError> {
-        let manager = ConnectionManager::<PgConnection>::new(database_url);
+        Self::connect(database_url, true, PoolOptions::default()).await
+    }
 
-        let pool_size = 5; // Postgres can handle more
+    /// Opens the store. With `run_migrations` false, pending migrations are
+    /// not applied and startup fails instead of running on an outdated schema.
+    pub async fn connect(
+        database_url: &str,
+        run_migrations: bool,
+        pool_options: PoolOptions,
+    ) -> std::result::Result<Self, StoreError> {
+        let manager = ConnectionManager::<PgConnection>::new(database_url);
+        let pool_metrics = Arc::new(PoolMetrics::default());
 
         let pool = Pool::builder()
-            .max_size(pool_size)
+            .max_size(pool_options.max_size)
+            .min_idle(pool_options.min_idle)
+            .connection_timeout(pool_options.acquire_timeout)
+            .event_handler(Box::new(MetricsHandler(pool_metrics.clone())))
             .build(manager)
             .map_err(|e| StoreError::Connection(e.to_string()))?;
 
@@ -87,21 +161,30 @@ impl PostgresStore {
                 .get()
                 .map_err(|e| StoreError::Connection(e.to_string()))?;
 
-            conn.run_pending_migrations(MIGRATIONS)
-                .map_err(|e| StoreError::Migration(e.to_string()))?;
-
-            Ok(())
+            prepare_schema(&mut conn, run_migrations)
         })
         .await
         .map_err(|e| StoreError::Database(e.to_string()))??;
 
         Ok(Self {
             pool,
-            db_semaphore: Arc::new(tokio::sync::Semaphore::new(10)), // More concurrent access
+            // Twice the pool size so queued work is already waiting on a connection.
+            db_semaphore: Arc::new(tokio::sync::Semaphore::new(
+                pool_options.max_size as usize * 2,
+            )),
+            pool_metrics,
             device_id: 1,
+            keyring: None,
         })
     }
 
+    /// Current pool usage and checkout wait times.
+    pub fn pool_stats(&self) -> PoolStats {
+        let state = self.pool.state();
+        self.pool_metrics
+            .stats(self.pool.max_size(), state.connections, state.idle_connections)
+    }
+
     pub async fn new_for_device(
         database_url: &str,
         device_id: i32,
@@ -115,6 +198,31 @@ impl PostgresStore {
         self.device_id
     }
 
+    /// Encrypts the device keys, Signal sessions, prekeys, sender keys and
+    /// app state sync keys with `keyring` from now on. Rows written before
+    /// stay readable; [`PostgresStore::reseal_secrets`] migrates them.
+    pub fn with_keyring(mut self, keyring: Keyring) -> Self {
+        self.keyring = Some(keyring);
+        self
+    }
+
+    fn seal(&self, data: &[u8]) -> Result<Vec<u8>> {
+        match &self.keyring {
+            Some(keyring) => Ok(keyring.seal(data)?),
+            None => Ok(data.to_vec()),
+        }
+    }
+
+    fn open(&self, data: Vec<u8>) -> Result<Vec<u8>> {
+        match &self.keyring {
+            Some(keyring) => Ok(keyring.open(&data)?),
+            None if secrets::is_sealed(&data) => Err(StoreError::Serialization(
+                "value is encrypted but no master key is configured".to_string(),
+            )),
+            None => Ok(data),
+        }
+    }
+
     async fn with_semaphore<F, T>(&self, f: F) -> Result<T>
     where
         F: FnOnce() -> Result<T> + Send + 'static,
@@ -165,9 +273,10 @@ impl PostgresStore {
         device_data: &CoreDevice,
     ) -> Result<()> {
         let pool = self.pool.clone();
-        let noise_key_data = self.serialize_keypair(&device_data.noise_key)?;
-        let identity_key_data = self.serialize_keypair(&device_data.identity_key)?;
-        let signed_pre_key_data = self.serialize_keypair(&device_data.signed_pre_key)?;
+        let noise_key_data = self.seal(&self.serialize_keypair(&device_data.noise_key)?)?;
+        let identity_key_data = self.seal(&self.serialize_keypair(&device_data.identity_key)?)?;
+        let signed_pre_key_data =
+            self.seal(&self.serialize_keypair(&device_data.signed_pre_key)?)?;
         let account_data = device_data
             .account
             .as_ref()
@@ -175,7 +284,7 @@ impl PostgresStore {
         let registration_id = device_data.registration_id as i32;
         let signed_pre_key_id = device_data.signed_pre_key_id as i32;
         let signed_pre_key_signature: Vec<u8> = device_data.signed_pre_key_signature.to_vec();
-        let adv_secret_key: Vec<u8> = device_data.adv_secret_key.to_vec();
+        let adv_secret_key: Vec<u8> = self.seal(&device_data.adv_secret_key)?;
         let push_name = device_data.push_name.clone();
         let app_version_primary = device_data.app_version_primary as i32;
         let app_version_secondary = device_data.app_version_secondary as i32;
@@ -253,32 +362,18 @@ impl PostgresStore {
         use crate::schema::device;
 
         let pool = self.pool.clone();
+        let new_device = warp_core::store::Device::new();
+        let noise_key_data = self.seal(&self.serialize_keypair(&new_device.noise_key)?)?;
+        let identity_key_data = self.seal(&self.serialize_keypair(&new_device.identity_key)?)?;
+        let signed_pre_key_data =
+            self.seal(&self.serialize_keypair(&new_device.signed_pre_key)?)?;
+        let adv_secret_key_data = self.seal(&new_device.adv_secret_key)?;
+
         tokio::task::spawn_blocking(move || -> Result<i32> {
             let mut conn = pool
                 .get()
                 .map_err(|e| StoreError::Connection(e.to_string()))?;
 
-            let new_device = warp_core::store::Device::new();
-
-            let noise_key_data = {
-                let mut bytes = Vec::with_capacity(64);
-                bytes.extend_from_slice(&new_device.noise_key.private_key.serialize());
-                bytes.extend_from_slice(new_device.noise_key.public_key.public_key_bytes());
-                bytes
-            };
-            let identity_key_data = {
-                let mut bytes = Vec::with_capacity(64);
-                bytes.extend_from_slice(&new_device.identity_key.private_key.serialize());
-                bytes.extend_from_slice(new_device.identity_key.public_key.public_key_bytes());
-                bytes
-            };
-            let signed_pre_key_data = {
-                let mut bytes = Vec::with_capacity(64);
-                bytes.extend_from_slice(&new_device.signed_pre_key.private_key.serialize());
-                bytes.extend_from_slice(new_device.signed_pre_key.public_key.public_key_bytes());
-                bytes
-            };
-
             let device_id: i32 = diesel::insert_into(device::table)
                 .values((
                     device::lid.eq(""),
@@ -289,7 +384,7 @@ impl PostgresStore {
                     device::signed_pre_key.eq(&signed_pre_key_data),
                     device::signed_pre_key_id.eq(new_device.signed_pre_key_id as i32),
                     device::signed_pre_key_signature.eq(&new_device.signed_pre_key_signature[..]),
-                    device::adv_secret_key.eq(&new_device.adv_secret_key[..]),
+                    device::adv_secret_key.eq(&adv_secret_key_data),
                     device::account.eq(None::<Vec<u8>>),
                     device::push_name.eq(&new_device.push_name),
                     device::app_version_primary.eq(new_device.app_version_primary as i32),
@@ -378,18 +473,19 @@ impl PostgresStore {
                 None
             };
 
-            let noise_key = self.deserialize_keypair(&noise_key_data)?;
-            let identity_key = self.deserialize_keypair(&identity_key_data)?;
-            let signed_pre_key = self.deserialize_keypair(&signed_pre_key_data)?;
+            let noise_key = self.deserialize_keypair(&self.open(noise_key_data)?)?;
+            let identity_key = self.deserialize_keypair(&self.open(identity_key_data)?)?;
+            let signed_pre_key = self.deserialize_keypair(&self.open(signed_pre_key_data)?)?;
 
             let signed_pre_key_signature: [u8; 64] =
                 signed_pre_key_signature_data.try_into().map_err(|_| {
                     StoreError::Serialization("Invalid signed_pre_key_signature length".to_string())
                 })?;
 
-            let adv_secret_key: [u8; 32] = adv_secret_key_data.try_into().map_err(|_| {
-                StoreError::Serialization("Invalid adv_secret_key length".to_string())
-            })?;
+            let adv_secret_key: [u8; 32] =
+                self.open(adv_secret_key_data)?.try_into().map_err(|_| {
+                    StoreError::Serialization("Invalid adv_secret_key length".to_string())
+                })?;
 
             let account = account_data
                 .map(|data| {
@@ -572,7 +668,7 @@ impl PostgresStore {
             })
             .await?;
 
-        Ok(result)
+        result.map(|record| self.open(record)).transpose()
     }
 
     pub async fn put_session_for_device(
@@ -584,7 +680,7 @@ impl PostgresStore {
         let pool = self.pool.clone();
         let db_semaphore = self.db_semaphore.clone();
         let address_owned = address.to_string();
-        let session_vec = session.to_vec();
+        let session_vec = self.seal(session)?;
 
         const MAX_RETRIES: u32 = 5;
 
@@ -682,7 +778,7 @@ impl PostgresStore {
     ) -> Result<()> {
         let pool = self.pool.clone();
         let address = address.to_string();
-        let record_vec = record.to_vec();
+        let record_vec = self.seal(record)?;
         tokio::task::spawn_blocking(move || -> Result<()> {
             let mut conn = pool
                 .get()
@@ -712,7 +808,7 @@ impl PostgresStore {
     ) -> Result<Option<Vec<u8>>> {
         let pool = self.pool.clone();
         let address = address.to_string();
-        tokio::task::spawn_blocking(move || -> Result<Option<Vec<u8>>> {
+        let res = tokio::task::spawn_blocking(move || -> Result<Option<Vec<u8>>> {
             let mut conn = pool
                 .get()
                 .map_err(|e| StoreError::Connection(e.to_string()))?;
@@ -726,7 +822,8 @@ impl PostgresStore {
             Ok(res)
         })
         .await
-        .map_err(|e| StoreError::Database(e.to_string()))?
+        .map_err(|e| StoreError::Database(e.to_string()))??;
+        res.map(|record| self.open(record)).transpose()
     }
 
     pub async fn delete_sender_key_for_device(&self, address: &str, device_id: i32) -> Result<()> {
@@ -775,6 +872,7 @@ impl PostgresStore {
             .map_err(|e| StoreError::Database(e.to_string()))??;
 
         if let Some(data) = res {
+            let data = self.open(data)?;
             let (key, _) = bincode::serde::decode_from_slice(&data, bincode::config::standard())
                 .map_err(|e| StoreError::Serialization(e.to_string()))?;
             Ok(Some(key))
@@ -783,6 +881,40 @@ impl PostgresStore {
         }
     }
 
+    /// Id of the newest (highest timestamp) app state sync key of a device.
+    pub async fn get_latest_app_state_sync_key_id_for_device(
+        &self,
+        device_id: i32,
+    ) -> Result<Option<Vec<u8>>> {
+        let pool = self.pool.clone();
+        let rows: Vec<(Vec<u8>, Vec<u8>)> =
+            tokio::task::spawn_blocking(move || -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+                let mut conn = pool
+                    .get()
+                    .map_err(|e| StoreError::Connection(e.to_string()))?;
+                let rows = app_state_keys::table
+                    .select((app_state_keys::key_id, app_state_keys::key_data))
+                    .filter(app_state_keys::device_id.eq(device_id))
+                    .load(&mut conn)
+                    .map_err(|e| StoreError::Database(e.to_string()))?;
+                Ok(rows)
+            })
+            .await
+            .map_err(|e| StoreError::Database(e.to_string()))??;
+
+        let mut latest: Option<(i64, Vec<u8>)> = None;
+        for (key_id, data) in rows {
+            let data = self.open(data)?;
+            let (key, _): (AppStateSyncKey, usize) =
+                bincode::serde::decode_from_slice(&data, bincode::config::standard())
+                    .map_err(|e| StoreError::Serialization(e.to_string()))?;
+            if latest.as_ref().is_none_or(|(timestamp, _)| key.timestamp > *timestamp) {
+                latest = Some((key.timestamp, key_id));
+            }
+        }
+        Ok(latest.map(|(_, key_id)| key_id))
+    }
+
     pub async fn set_app_state_sync_key_for_device(
         &self,
         key_id: &[u8],
@@ -793,6 +925,7 @@ impl PostgresStore {
         let key_id = key_id.to_vec();
         let data = bincode::serde::encode_to_vec(&key, bincode::config::standard())
             .map_err(|e| StoreError::Serialization(e.to_string()))?;
+        let data = self.seal(&data)?;
         tokio::task::spawn_blocking(move || -> Result<()> {
             let mut conn = pool
                 .get()
@@ -1002,20 +1135,7 @@ impl PostgresStore {
                 .get()
                 .map_err(|e| StoreError::Connection(e.to_string()))?;
 
-            let mut query: BoxedSqlQuery<'_, Pg, _> = sql_query(sql).into_boxed::<Pg>();
-            for bind in &binds {
-                query = match bind {
-                    BindValue::Text(v) => query.bind::<Text, _>(v.clone()),
-                    BindValue::NullableText(v) => query.bind::<Nullable<Text>, _>(v.clone()),
-                    BindValue::Bool(v) => query.bind::<Bool, _>(*v),
-                    BindValue::Int(v) => query.bind::<Int4, _>(*v),
-                    BindValue::Json(v) => query.bind::<Jsonb, _>(v.clone()),
-                    BindValue::NullableJson(v) => query.bind::<Nullable<Jsonb>, _>(v.clone()),
-                    BindValue::Uuid(v) => query.bind::<SqlUuid, _>(v),
-                };
-            }
-
-            let rows: Vec<JsonRow> = query.load(&mut conn).map_err(db_err)?;
+            let rows: Vec<JsonRow> = bound_query(sql, &binds).load(&mut conn).map_err(db_err)?;
             Ok(rows.into_iter().map(|row| row.value).collect())
         })
         .await
@@ -1035,26 +1155,145 @@ impl PostgresStore {
             let mut conn = pool
                 .get()
                 .map_err(|e| StoreError::Connection(e.to_string()))?;
-            let mut query: BoxedSqlQuery<'_, Pg, _> = sql_query(sql).into_boxed::<Pg>();
-            for bind in &binds {
-                query = match bind {
-                    BindValue::Text(v) => query.bind::<Text, _>(v.clone()),
-                    BindValue::NullableText(v) => query.bind::<Nullable<Text>, _>(v.clone()),
-                    BindValue::Bool(v) => query.bind::<Bool, _>(*v),
-                    BindValue::Int(v) => query.bind::<Int4, _>(*v),
-                    BindValue::Json(v) => query.bind::<Jsonb, _>(v.clone()),
-                    BindValue::NullableJson(v) => query.bind::<Nullable<Jsonb>, _>(v.clone()),
-                    BindValue::Uuid(v) => query.bind::<SqlUuid, _>(v),
-                };
-            }
+            bound_query(sql, &binds).execute(&mut conn).map_err(db_err)
+        })
+        .await
+        .map_err(|e| StoreError::Database(e.to_string()))?
+    }
+
+    /// Runs every statement in one transaction, returning the affected rows
+    /// of each. Nothing is applied if any of them fails.
+    pub async fn api_execute_batch(
+        &self,
+        statements: Vec<(String, Vec<BindValue>)>,
+    ) -> Result<Vec<usize>> {
+        let pool = self.pool.clone();
+        let _permit = self
+            .db_semaphore
+            .clone()
+            .acquire_owned()
+            .await
+            .map_err(|e| StoreError::Database(e.to_string()))?;
+        tokio::task::spawn_blocking(move || -> Result<Vec<usize>> {
+            let mut conn = pool
+                .get()
+                .map_err(|e| StoreError::Connection(e.to_string()))?;
+            conn.transaction::<_, diesel::result::Error, _>(|conn| {
+                statements
+                    .into_iter()
+                    .map(|(sql, binds)| bound_query(sql, &binds).execute(conn))
+                    .collect()
+            })
+            .map_err(db_err)
+        })
+        .await
+        .map_err(|e| StoreError::Database(e.to_string()))?
+    }
 
-            query.execute(&mut conn).map_err(db_err)
+    /// Seals every secret column under the current master key: plaintext
+    /// rows from before encryption was enabled and rows sealed with a
+    /// previous key after a rotation. Each column is rewritten in its own
+    /// transaction, and rows already under the current key are left alone,
+    /// so an interrupted run can simply be repeated.
+    pub async fn reseal_secrets(&self) -> Result<Vec<ResealedColumn>> {
+        let Some(keyring) = self.keyring.clone() else {
+            return Err(StoreError::Serialization(
+                "no master key is configured".to_string(),
+            ));
+        };
+        let pool = self.pool.clone();
+        tokio::task::spawn_blocking(move || -> Result<Vec<ResealedColumn>> {
+            let mut conn = pool
+                .get()
+                .map_err(|e| StoreError::Connection(e.to_string()))?;
+            let binary = SECRET_COLUMNS.iter().map(|&(t, c)| (t, c, false));
+            let text = SECRET_TEXT_COLUMNS.iter().map(|&(t, c)| (t, c, true));
+            binary
+                .chain(text)
+                .map(|(table, column, text)| {
+                    let rows = conn
+                        .transaction(|conn| reseal_column(conn, &keyring, table, column, text))
+                        .map_err(db_err)?;
+                    Ok(ResealedColumn {
+                        table,
+                        column,
+                        rows,
+                    })
+                })
+                .collect()
         })
         .await
         .map_err(|e| StoreError::Database(e.to_string()))?
     }
 }
 
+/// Reseals one column row by row. Text columns go through bytea so both
+/// kinds share the query; keyring errors abort the transaction as query
+/// builder errors.
+fn reseal_column(
+    conn: &mut PgConnection,
+    keyring: &Keyring,
+    table: &str,
+    column: &str,
+    text: bool,
+) -> QueryResult<usize> {
+    let abort = |e: SecretsError| diesel::result::Error::QueryBuilderError(Box::new(e));
+    let (read, write) = if text {
+        (
+            format!("convert_to({column}, 'UTF8')"),
+            "convert_from($1, 'UTF8')",
+        )
+    } else {
+        (column.to_string(), "$1")
+    };
+    let rows: Vec<SecretRow> = sql_query(format!(
+        "SELECT ctid::text AS row_id, {read} AS data FROM {table} \
+         WHERE {column} IS NOT NULL FOR UPDATE"
+    ))
+    .load(conn)?;
+
+    let mut resealed = 0;
+    for row in rows {
+        let data = if text {
+            let value = String::from_utf8(row.data).map_err(|_| abort(SecretsError::Malformed))?;
+            if value.is_empty() {
+                continue;
+            }
+            keyring
+                .reseal_text(&value)
+                .map_err(abort)?
+                .map(String::into_bytes)
+        } else {
+            keyring.reseal(&row.data).map_err(abort)?
+        };
+        if let Some(data) = data {
+            sql_query(format!(
+                "UPDATE {table} SET {column} = {write} WHERE ctid = $2::tid"
+            ))
+            .bind::<Binary, _>(data)
+            .bind::<Text, _>(row.row_id)
+            .execute(conn)?;
+            resealed += 1;
+        }
+    }
+    Ok(resealed)
+}
+fn bound_query(sql: String, binds: &[BindValue]) -> BoxedSqlQuery<'_, Pg, SqlQuery> {
+    let mut query = sql_query(sql).into_boxed::<Pg>();
+    for bind in binds {
+        query = match bind {
+            BindValue::Text(v) => query.bind::<Text, _>(v.clone()),
+            BindValue::NullableText(v) => query.bind::<Nullable<Text>, _>(v.clone()),
+            BindValue::Bool(v) => query.bind::<Bool, _>(*v),
+            BindValue::Int(v) => query.bind::<Int4, _>(*v),
+            BindValue::Json(v) => query.bind::<Jsonb, _>(v.clone()),
+            BindValue::NullableJson(v) => query.bind::<Nullable<Jsonb>, _>(v.clone()),
+            BindValue::Uuid(v) => query.bind::<SqlUuid, _>(v),
+        };
+    }
+    query
+}
+
 #[async_trait]
 impl SignalStore for PostgresStore {
     async fn put_identity(&self, address: &str, key: [u8; 32]) -> Result<()> {
@@ -1088,7 +1327,7 @@ impl SignalStore for PostgresStore {
     async fn store_prekey(&self, id: u32, record: &[u8], uploaded: bool) -> Result<()> {
         let pool = self.pool.clone();
         let device_id = self.device_id;
-        let record = record.to_vec();
+        let record = self.seal(record)?;
         tokio::task::spawn_blocking(move || -> Result<()> {
             let mut conn = pool
                 .get()
@@ -1115,7 +1354,7 @@ impl SignalStore for PostgresStore {
     async fn load_prekey(&self, id: u32) -> Result<Option<Vec<u8>>> {
         let pool = self.pool.clone();
         let device_id = self.device_id;
-        tokio::task::spawn_blocking(move || -> Result<Option<Vec<u8>>> {
+        let res = tokio::task::spawn_blocking(move || -> Result<Option<Vec<u8>>> {
             let mut conn = pool
                 .get()
                 .map_err(|e| StoreError::Connection(e.to_string()))?;
@@ -1129,7 +1368,8 @@ impl SignalStore for PostgresStore {
             Ok(res)
         })
         .await
-        .map_err(|e| StoreError::Database(e.to_string()))?
+        .map_err(|e| StoreError::Database(e.to_string()))??;
+        res.map(|record| self.open(record)).transpose()
     }
 
     async fn remove_prekey(&self, id: u32) -> Result<()> {
@@ -1156,7 +1396,7 @@ impl SignalStore for PostgresStore {
     async fn store_signed_prekey(&self, id: u32, record: &[u8]) -> Result<()> {
         let pool = self.pool.clone();
         let device_id = self.device_id;
-        let record = record.to_vec();
+        let record = self.seal(record)?;
         tokio::task::spawn_blocking(move || -> Result<()> {
             let mut conn = pool
                 .get()
@@ -1182,7 +1422,7 @@ impl SignalStore for PostgresStore {
     async fn load_signed_prekey(&self, id: u32) -> Result<Option<Vec<u8>>> {
         let pool = self.pool.clone();
         let device_id = self.device_id;
-        tokio::task::spawn_blocking(move || -> Result<Option<Vec<u8>>> {
+        let res = tokio::task::spawn_blocking(move || -> Result<Option<Vec<u8>>> {
             let mut conn = pool
                 .get()
                 .map_err(|e| StoreError::Connection(e.to_string()))?;
@@ -1196,13 +1436,14 @@ impl SignalStore for PostgresStore {
             Ok(res)
         })
         .await
-        .map_err(|e| StoreError::Database(e.to_string()))?
+        .map_err(|e| StoreError::Database(e.to_string()))??;
+        res.map(|record| self.open(record)).transpose()
     }
 
     async fn load_all_signed_prekeys(&self) -> Result<Vec<(u32, Vec<u8>)>> {
         let pool = self.pool.clone();
         let device_id = self.device_id;
-        tokio::task::spawn_blocking(move || -> Result<Vec<(u32, Vec<u8>)>> {
+        let results = tokio::task::spawn_blocking(move || -> Result<Vec<(i32, Vec<u8>)>> {
             let mut conn = pool
                 .get()
                 .map_err(|e| StoreError::Connection(e.to_string()))?;
@@ -1211,13 +1452,14 @@ impl SignalStore for PostgresStore {
                 .filter(signed_prekeys::device_id.eq(device_id))
                 .load(&mut conn)
                 .map_err(|e| StoreError::Database(e.to_string()))?;
-            Ok(results
-                .into_iter()
-                .map(|(id, record)| (id as u32, record))
-                .collect())
+            Ok(results)
         })
         .await
-        .map_err(|e| StoreError::Database(e.to_string()))?
+        .map_err(|e| StoreError::Database(e.to_string()))??;
+        results
+            .into_iter()
+            .map(|(id, record)| Ok((id as u32, self.open(record)?)))
+            .collect()
     }
 
     async fn remove_signed_prekey(&self, id: u32) -> Result<()> {
@@ -1263,19 +1505,24 @@ impl SignalStore for PostgresStore {
         let addrs: Vec<String> = addresses.iter().map(|a| a.to_string()).collect();
         let pool = self.pool.clone();
         let device_id = self.device_id;
-        self.with_semaphore(move || -> Result<Vec<(String, Vec<u8>)>> {
-            let mut conn = pool
-                .get()
-                .map_err(|e| StoreError::Connection(e.to_string()))?;
-            let results: Vec<(String, Vec<u8>)> = sessions::table
-                .select((sessions::address, sessions::record))
-                .filter(sessions::address.eq_any(&addrs))
-                .filter(sessions::device_id.eq(device_id))
-                .load(&mut conn)
-                .map_err(|e| StoreError::Database(e.to_string()))?;
-            Ok(results)
-        })
-        .await
+        let results = self
+            .with_semaphore(move || -> Result<Vec<(String, Vec<u8>)>> {
+                let mut conn = pool
+                    .get()
+                    .map_err(|e| StoreError::Connection(e.to_string()))?;
+                let results: Vec<(String, Vec<u8>)> = sessions::table
+                    .select((sessions::address, sessions::record))
+                    .filter(sessions::address.eq_any(&addrs))
+                    .filter(sessions::device_id.eq(device_id))
+                    .load(&mut conn)
+                    .map_err(|e| StoreError::Database(e.to_string()))?;
+                Ok(results)
+            })
+            .await?;
+        results
+            .into_iter()
+            .map(|(address, record)| Ok((address, self.open(record)?)))
+            .collect()
     }
 
     async fn put_sessions_batch(&self, entries: &[(&str, &[u8])]) -> Result<()> {
@@ -1284,8 +1531,8 @@ impl SignalStore for PostgresStore {
         }
         let owned: Vec<(String, Vec<u8>)> = entries
             .iter()
-            .map(|(a, d)| (a.to_string(), d.to_vec()))
-            .collect();
+            .map(|(a, d)| Ok((a.to_string(), self.seal(d)?)))
+            .collect::<Result<_>>()?;
         let pool = self.pool.clone();
         let db_semaphore = self.db_semaphore.clone();
         let device_id = self.device_id;
@@ -1473,6 +1720,11 @@ impl AppSyncStore for PostgresStore {
             .await
     }
 
+    async fn get_latest_sync_key_id(&self) -> Result<Option<Vec<u8>>> {
+        self.get_latest_app_state_sync_key_id_for_device(self.device_id)
+            .await
+    }
+
     async fn get_version(&self, name: &str) -> Result<HashState> {
         self.get_app_state_version_for_device(name, self.device_id)
             .await
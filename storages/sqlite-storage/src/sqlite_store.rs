@@ -76,8 +76,39 @@ impl diesel::r2d2::CustomizeConnection<SqliteConnection, diesel::r2d2::Error>
     }
 }
 
+fn prepare_schema(
+    conn: &mut SqliteConnection,
+    run_migrations: bool,
+) -> std::result::Result<(), StoreError> {
+    if run_migrations {
+        conn.run_pending_migrations(MIGRATIONS)
+            .map_err(|e| StoreError::Migration(e.to_string()))?;
+        return Ok(());
+    }
+
+    let pending = conn
+        .pending_migrations(MIGRATIONS)
+        .map_err(|e| StoreError::Migration(e.to_string()))?;
+    if !pending.is_empty() {
+        return Err(StoreError::Migration(format!(
+            "{} pending migration(s); start with DATABASE_RUN_MIGRATIONS=true or --migrate-only",
+            pending.len()
+        )));
+    }
+    Ok(())
+}
+
 impl SqliteStore {
     pub async fn new(database_url: &str) -> std::result::Result<Self, StoreError> {
+        Self::connect(database_url, true).await
+    }
+
+    /// Opens the store. With `run_migrations` false, pending migrations are
+    /// not applied and startup fails instead of running on an outdated schema.
+    pub async fn connect(
+        database_url: &str,
+        run_migrations: bool,
+    ) -> std::result::Result<Self, StoreError> {
         let manager = ConnectionManager::<SqliteConnection>::new(database_url);
 
         let pool_size = 2;
@@ -98,10 +129,7 @@ impl SqliteStore {
                 .execute(&mut conn)
                 .map_err(|e| StoreError::Database(e.to_string()))?;
 
-            conn.run_pending_migrations(MIGRATIONS)
-                .map_err(|e| StoreError::Migration(e.to_string()))?;
-
-            Ok(())
+            prepare_schema(&mut conn, run_migrations)
         })
         .await
         .map_err(|e| StoreError::Database(e.to_string()))??;
@@ -819,6 +847,39 @@ impl SqliteStore {
         }
     }
 
+    /// Id of the newest (highest timestamp) app state sync key of a device.
+    pub async fn get_latest_app_state_sync_key_id_for_device(
+        &self,
+        device_id: i32,
+    ) -> Result<Option<Vec<u8>>> {
+        let pool = self.pool.clone();
+        let rows: Vec<(Vec<u8>, Vec<u8>)> =
+            tokio::task::spawn_blocking(move || -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+                let mut conn = pool
+                    .get()
+                    .map_err(|e| StoreError::Connection(e.to_string()))?;
+                let rows = app_state_keys::table
+                    .select((app_state_keys::key_id, app_state_keys::key_data))
+                    .filter(app_state_keys::device_id.eq(device_id))
+                    .load(&mut conn)
+                    .map_err(|e| StoreError::Database(e.to_string()))?;
+                Ok(rows)
+            })
+            .await
+            .map_err(|e| StoreError::Database(e.to_string()))??;
+
+        let mut latest: Option<(i64, Vec<u8>)> = None;
+        for (key_id, data) in rows {
+            let (key, _): (AppStateSyncKey, usize) =
+                bincode::serde::decode_from_slice(&data, bincode::config::standard())
+                    .map_err(|e| StoreError::Serialization(e.to_string()))?;
+            if latest.as_ref().is_none_or(|(timestamp, _)| key.timestamp > *timestamp) {
+                latest = Some((key.timestamp, key_id));
+            }
+        }
+        Ok(latest.map(|(_, key_id)| key_id))
+    }
+
     pub async fn set_app_state_sync_key_for_device(
         &self,
         key_id: &[u8],
@@ -1337,6 +1398,11 @@ impl AppSyncStore for SqliteStore {
             .await
     }
 
+    async fn get_latest_sync_key_id(&self) -> Result<Option<Vec<u8>>> {
+        self.get_latest_app_state_sync_key_id_for_device(self.device_id)
+            .await
+    }
+
     async fn get_version(&self, name: &str) -> Result<HashState> {
         self.get_app_state_version_for_device(name, self.device_id)
             .await
@@ -147,7 +147,7 @@ impl Transport for TokioWebSocketTransport {
 
         debug!("--> Sending {} bytes", data.len());
         let t1 = std::time::Instant::now();
-        sink.send(Message::binary(data.to_vec()))
+        sink.send(Message::binary(Bytes::copy_from_slice(data)))
             .await
             .map_err(|e| anyhow::anyhow!("WebSocket send error: {}", e))?;
         log::debug!("⏱️ sink.send(): {:?}", t1.elapsed());
@@ -233,11 +233,12 @@ async fn read_pump(mut stream: WsStream, event_tx: async_channel::Sender<Transpo
         match stream.next().await {
             Some(Ok(msg)) => {
                 if msg.is_binary() {
-                    let data = msg.as_payload();
+                    // The payload already is a `Bytes`; hand it over without copying
+                    let data = Bytes::from(msg.into_payload());
                     debug!("<-- Received WebSocket data: {} bytes", data.len());
                     // Just forward the raw bytes - no framing logic
                     if event_tx
-                        .send(TransportEvent::DataReceived(Bytes::copy_from_slice(data)))
+                        .send(TransportEvent::DataReceived(data))
                         .await
                         .is_err()
                     {
@@ -0,0 +1,321 @@
+//! Outgoing app state patches - port of Go appstate/encode.go
+//!
+//! Builders return the mutations for a chat action; [`encode_patch`] encrypts
+//! them with the latest app state key and computes the snapshot/patch MACs
+//! for the collection's next version.
+
+use crate::AppStateError;
+use crate::hash::{HashState, generate_content_mac, generate_patch_mac};
+use crate::keys::ExpandedAppStateKeys;
+use crate::patch_decode::WAPatchName;
+use crate::processor::AppStateMutationMAC;
+use prost::Message;
+use warp_core_libsignal::crypto::{CryptographicMac, aes_256_cbc_encrypt_into};
+use waproto::whatsapp as wa;
+use waproto::whatsapp::sync_action_value as actions;
+
+pub const INDEX_ARCHIVE: &str = "archive";
+pub const INDEX_PIN: &str = "pin_v1";
+pub const INDEX_MUTE: &str = "mute";
+pub const INDEX_MARK_CHAT_AS_READ: &str = "markChatAsRead";
+
+/// One SET mutation before encryption.
+#[derive(Debug, Clone)]
+pub struct MutationInfo {
+    /// Index components, e.g. `["archive", "123@s.whatsapp.net"]`.
+    pub index: Vec<String>,
+    pub version: i32,
+    pub value: wa::SyncActionValue,
+}
+
+/// Mutations of a single collection, sent as one patch.
+#[derive(Debug, Clone)]
+pub struct PatchInfo {
+    pub name: WAPatchName,
+    pub mutations: Vec<MutationInfo>,
+}
+
+/// Encrypted patch plus the state the collection reaches once it is applied.
+#[derive(Debug, Clone)]
+pub struct EncodedPatch {
+    pub patch: wa::SyncdPatch,
+    pub state: HashState,
+    pub mutation_macs: Vec<AppStateMutationMAC>,
+}
+
+fn message_range(last_message_timestamp: Option<i64>) -> Option<actions::SyncActionMessageRange> {
+    Some(actions::SyncActionMessageRange {
+        last_message_timestamp,
+        ..Default::default()
+    })
+}
+
+fn pin_mutation(jid: &str, pinned: bool) -> MutationInfo {
+    MutationInfo {
+        index: vec![INDEX_PIN.to_string(), jid.to_string()],
+        version: 5,
+        value: wa::SyncActionValue {
+            pin_action: Some(actions::PinAction {
+                pinned: Some(pinned),
+            }),
+            ..Default::default()
+        },
+    }
+}
+
+/// Archives or unarchives a chat. Archiving also unpins it, like the phone does.
+/// `last_message_timestamp` is in seconds.
+pub fn build_archive(jid: &str, archive: bool, last_message_timestamp: Option<i64>) -> PatchInfo {
+    let mut mutations = vec![MutationInfo {
+        index: vec![INDEX_ARCHIVE.to_string(), jid.to_string()],
+        version: 3,
+        value: wa::SyncActionValue {
+            archive_chat_action: Some(actions::ArchiveChatAction {
+                archived: Some(archive),
+                message_range: message_range(last_message_timestamp),
+            }),
+            ..Default::default()
+        },
+    }];
+    if archive {
+        mutations.push(pin_mutation(jid, false));
+    }
+    PatchInfo {
+        name: WAPatchName::RegularLow,
+        mutations,
+    }
+}
+
+/// Pins or unpins a chat.
+pub fn build_pin(jid: &str, pin: bool) -> PatchInfo {
+    PatchInfo {
+        name: WAPatchName::RegularLow,
+        mutations: vec![pin_mutation(jid, pin)],
+    }
+}
+
+/// Mutes a chat until `mute_end_timestamp` (milliseconds, `-1` forever) or
+/// unmutes it when `None`.
+pub fn build_mute(jid: &str, mute_end_timestamp: Option<i64>) -> PatchInfo {
+    PatchInfo {
+        name: WAPatchName::RegularHigh,
+        mutations: vec![MutationInfo {
+            index: vec![INDEX_MUTE.to_string(), jid.to_string()],
+            version: 2,
+            value: wa::SyncActionValue {
+                mute_action: Some(actions::MuteAction {
+                    muted: Some(mute_end_timestamp.is_some()),
+                    mute_end_timestamp,
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+        }],
+    }
+}
+
+/// Marks a chat as read or unread. `last_message_timestamp` is in seconds.
+pub fn build_mark_chat_as_read(
+    jid: &str,
+    read: bool,
+    last_message_timestamp: Option<i64>,
+) -> PatchInfo {
+    PatchInfo {
+        name: WAPatchName::RegularLow,
+        mutations: vec![MutationInfo {
+            index: vec![INDEX_MARK_CHAT_AS_READ.to_string(), jid.to_string()],
+            version: 3,
+            value: wa::SyncActionValue {
+                mark_chat_as_read_action: Some(actions::MarkChatAsReadAction {
+                    read: Some(read),
+                    message_range: message_range(last_message_timestamp),
+                }),
+                ..Default::default()
+            },
+        }],
+    }
+}
+
+/// Index MAC of a mutation index, as stored alongside its value MAC.
+pub fn index_mac(
+    index: &[String],
+    keys: &ExpandedAppStateKeys,
+) -> Result<Vec<u8>, AppStateError> {
+    let index_json = serde_json::to_vec(index).map_err(|_| AppStateError::EncryptionFailed)?;
+    let mut mac = CryptographicMac::new("HmacSha256", &keys.index)
+        .map_err(|_| AppStateError::EncryptionFailed)?;
+    mac.update(&index_json);
+    Ok(mac.finalize())
+}
+
+/// Encrypts `info` as the next patch on top of `state`.
+///
+/// `next_iv` supplies a fresh random IV per mutation; `get_prev_value_mac`
+/// looks up the value MAC currently stored for an index MAC, so overwritten
+/// values are removed from the LTHash.
+pub fn encode_patch<I, F>(
+    info: &PatchInfo,
+    state: &HashState,
+    keys: &ExpandedAppStateKeys,
+    key_id: &[u8],
+    timestamp_ms: i64,
+    mut next_iv: I,
+    mut get_prev_value_mac: F,
+) -> Result<EncodedPatch, AppStateError>
+where
+    I: FnMut() -> [u8; 16],
+    F: FnMut(&[u8]) -> Result<Option<Vec<u8>>, AppStateError>,
+{
+    let name = info.name.as_str();
+    let mut mutations = Vec::with_capacity(info.mutations.len());
+    let mut mutation_macs = Vec::with_capacity(info.mutations.len());
+
+    for mutation in &info.mutations {
+        let index_json =
+            serde_json::to_vec(&mutation.index).map_err(|_| AppStateError::EncryptionFailed)?;
+        let mut value = mutation.value.clone();
+        value.timestamp = Some(timestamp_ms);
+        let content = wa::SyncActionData {
+            index: Some(index_json.clone()),
+            value: Some(value),
+            padding: Some(Vec::new()),
+            version: Some(mutation.version),
+        }
+        .encode_to_vec();
+
+        let iv = next_iv();
+        let mut blob = iv.to_vec();
+        aes_256_cbc_encrypt_into(&content, &keys.value_encryption, &iv, &mut blob)
+            .map_err(|_| AppStateError::EncryptionFailed)?;
+        let value_mac = generate_content_mac(
+            wa::syncd_mutation::SyncdOperation::Set,
+            &blob,
+            key_id,
+            &keys.value_mac,
+        );
+        blob.extend_from_slice(&value_mac);
+        let index_mac = index_mac(&mutation.index, keys)?;
+
+        mutation_macs.push(AppStateMutationMAC {
+            index_mac: index_mac.clone(),
+            value_mac,
+        });
+        mutations.push(wa::SyncdMutation {
+            operation: Some(wa::syncd_mutation::SyncdOperation::Set as i32),
+            record: Some(wa::SyncdRecord {
+                index: Some(wa::SyncdIndex {
+                    blob: Some(index_mac),
+                }),
+                value: Some(wa::SyncdValue { blob: Some(blob) }),
+                key_id: Some(wa::KeyId {
+                    id: Some(key_id.to_vec()),
+                }),
+            }),
+        });
+    }
+
+    // A value set earlier in this same patch wins over the stored one.
+    let mut prev_value_macs = Vec::with_capacity(mutation_macs.len());
+    for (i, mac) in mutation_macs.iter().enumerate() {
+        let earlier = mutation_macs[..i]
+            .iter()
+            .rev()
+            .find(|earlier| earlier.index_mac == mac.index_mac);
+        prev_value_macs.push(match earlier {
+            Some(earlier) => Some(earlier.value_mac.clone()),
+            None => get_prev_value_mac(&mac.index_mac)?,
+        });
+    }
+
+    let mut next_state = state.clone();
+    let (_, result) =
+        next_state.update_hash(&mutations, |_, i| Ok(prev_value_macs.get(i).cloned().flatten()));
+    result.map_err(|_| AppStateError::MismatchingLTHash)?;
+    next_state.version += 1;
+
+    let mut patch = wa::SyncdPatch {
+        snapshot_mac: Some(next_state.generate_snapshot_mac(name, &keys.snapshot_mac)),
+        key_id: Some(wa::KeyId {
+            id: Some(key_id.to_vec()),
+        }),
+        mutations,
+        ..Default::default()
+    };
+    patch.patch_mac = Some(generate_patch_mac(
+        &patch,
+        name,
+        &keys.patch_mac,
+        next_state.version,
+    ));
+
+    Ok(EncodedPatch {
+        patch,
+        state: next_state,
+        mutation_macs,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode::decode_record;
+    use crate::keys::expand_app_state_keys;
+    use crate::processor::validate_patch_macs;
+
+    #[test]
+    fn encoded_patch_decodes_and_validates() {
+        let keys = expand_app_state_keys(&[9u8; 32]);
+        let key_id = b"key-1".to_vec();
+        let info = build_archive("123@s.whatsapp.net", true, Some(1_700_000_000));
+
+        let encoded = encode_patch(
+            &info,
+            &HashState::default(),
+            &keys,
+            &key_id,
+            1_700_000_000_000,
+            || [7u8; 16],
+            |_| Ok(None),
+        )
+        .expect("patch encodes");
+
+        assert_eq!(encoded.state.version, 1);
+        assert_eq!(encoded.patch.mutations.len(), 2);
+        assert_eq!(encoded.mutation_macs.len(), 2);
+
+        let record = encoded.patch.mutations[0].record.as_ref().expect("record");
+        let mutation = decode_record(
+            wa::syncd_mutation::SyncdOperation::Set,
+            record,
+            &keys,
+            &key_id,
+            true,
+        )
+        .expect("record decodes");
+        assert_eq!(mutation.index, vec!["archive", "123@s.whatsapp.net"]);
+        let action = mutation.action_value.expect("value");
+        assert_eq!(action.timestamp, Some(1_700_000_000_000));
+        assert_eq!(
+            action.archive_chat_action.and_then(|a| a.archived),
+            Some(true)
+        );
+
+        let mut patch = encoded.patch.clone();
+        patch.version = Some(wa::SyncdVersion { version: Some(1) });
+        validate_patch_macs(&patch, &encoded.state, &keys, "regular_low", false)
+            .expect("MACs validate");
+    }
+
+    #[test]
+    fn mute_sets_end_timestamp() {
+        let info = build_mute("123@s.whatsapp.net", Some(-1));
+        assert_eq!(info.name, WAPatchName::RegularHigh);
+        let action = info.mutations[0].value.mute_action.expect("mute action");
+        assert_eq!(action.muted, Some(true));
+        assert_eq!(action.mute_end_timestamp, Some(-1));
+
+        let unmute = build_mute("123@s.whatsapp.net", None);
+        let action = unmute.mutations[0].value.mute_action.expect("mute action");
+        assert_eq!(action.muted, Some(false));
+    }
+}
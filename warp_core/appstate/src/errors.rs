@@ -20,6 +20,8 @@ pub enum AppStateError {
     ValueBlobTooShort,
     #[error("decryption failed")]
     DecryptionFailed,
+    #[error("encryption failed")]
+    EncryptionFailed,
     #[error("failed to decode protobuf")]
     DecodeFailed,
     #[error("missing index MAC in record")]
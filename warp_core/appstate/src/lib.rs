@@ -1,5 +1,6 @@
 #![feature(portable_simd)]
 pub mod decode;
+pub mod encode;
 pub mod errors;
 pub mod hash;
 pub mod keys;
@@ -8,6 +9,7 @@ pub mod patch_decode;
 pub mod processor;
 
 pub use decode::{Mutation, collect_key_ids_from_patch_list, decode_record};
+pub use encode::{EncodedPatch, MutationInfo, PatchInfo, encode_patch};
 pub use errors::*;
 pub use keys::{ExpandedAppStateKeys, expand_app_state_keys};
 pub use lthash::{LTHash, WAPATCH_INTEGRITY};
@@ -0,0 +1,196 @@
+//! Frame pipeline: the copying approach the client used before, next to
+//! the in-place framing and zero-copy decoding it uses now.
+
+use bytes::{Bytes, BytesMut};
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use std::hint::black_box;
+use warp_core::aes_gcm::aead::{Aead, AeadInPlace};
+use warp_core::aes_gcm::{Aes256Gcm, KeyInit, Tag};
+use warp_core::framing::{
+    FRAME_LENGTH_SIZE, FrameDecoder, begin_frame, encode_frame, encode_frame_into, finish_frame,
+};
+use warp_core::handshake::utils::generate_iv;
+
+const SIZES: [usize; 3] = [64, 1024, 16 * 1024];
+const TAG_SIZE: usize = 16;
+
+fn key() -> Aes256Gcm {
+    Aes256Gcm::new(&[7u8; 32].into())
+}
+
+fn payload(size: usize) -> Vec<u8> {
+    (0..size).map(|i| i as u8).collect()
+}
+
+/// A websocket message carrying `count` frames of `size` bytes.
+fn message(size: usize, count: usize) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut frame = Vec::new();
+    for _ in 0..count {
+        encode_frame_into(&payload(size), None, &mut frame).unwrap();
+        out.extend_from_slice(&frame);
+    }
+    out
+}
+
+fn bench_seal(c: &mut Criterion) {
+    let key = key();
+    let mut group = c.benchmark_group("seal_frame");
+    for size in SIZES {
+        let plaintext = payload(size);
+        group.throughput(Throughput::Bytes(size as u64));
+
+        // Copy into the output, encrypt, copy out again and frame the copy.
+        group.bench_with_input(
+            BenchmarkId::new("copying", size),
+            &plaintext,
+            |b, plaintext| {
+                let mut out = Vec::with_capacity(size + 64);
+                let mut scratch = Vec::with_capacity(size + 64);
+                b.iter(|| {
+                    out.clear();
+                    out.extend_from_slice(plaintext);
+                    key.encrypt_in_place(generate_iv(0).as_ref().into(), b"", &mut out)
+                        .unwrap();
+                    scratch.clear();
+                    scratch.extend_from_slice(&out);
+                    encode_frame_into(&scratch, None, &mut out).unwrap();
+                    black_box(&out);
+                });
+            },
+        );
+
+        group.bench_with_input(
+            BenchmarkId::new("in_place", size),
+            &plaintext,
+            |b, plaintext| {
+                let mut out = Vec::with_capacity(size + 64);
+                b.iter(|| {
+                    begin_frame(&mut out);
+                    out.extend_from_slice(plaintext);
+                    let tag = key
+                        .encrypt_in_place_detached(
+                            generate_iv(0).as_ref().into(),
+                            b"",
+                            &mut out[FRAME_LENGTH_SIZE..],
+                        )
+                        .unwrap();
+                    out.extend_from_slice(&tag);
+                    finish_frame(&mut out).unwrap();
+                    black_box(&out);
+                });
+            },
+        );
+    }
+    group.finish();
+}
+
+fn bench_encode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("encode_frame");
+    for size in SIZES {
+        let data = payload(size);
+        group.throughput(Throughput::Bytes(size as u64));
+
+        group.bench_with_input(BenchmarkId::new("allocating", size), &data, |b, data| {
+            b.iter(|| black_box(encode_frame(data, None).unwrap()));
+        });
+
+        group.bench_with_input(BenchmarkId::new("reused_buffer", size), &data, |b, data| {
+            let mut out = Vec::with_capacity(size + FRAME_LENGTH_SIZE);
+            b.iter(|| {
+                begin_frame(&mut out);
+                out.extend_from_slice(data);
+                finish_frame(&mut out).unwrap();
+                black_box(&out);
+            });
+        });
+    }
+    group.finish();
+}
+
+fn bench_decode(c: &mut Criterion) {
+    const FRAMES: usize = 8;
+    let mut group = c.benchmark_group("decode_frames");
+    for size in SIZES {
+        let data = message(size, FRAMES);
+        group.throughput(Throughput::Bytes(data.len() as u64));
+
+        group.bench_with_input(BenchmarkId::new("copying", size), &data, |b, data| {
+            b.iter_batched(
+                || Bytes::from(data.clone()),
+                |data| {
+                    let mut decoder = FrameDecoder::new();
+                    decoder.feed(&data);
+                    while let Some(frame) = decoder.decode_frame() {
+                        // The payload used to be copied out of every frame.
+                        black_box(frame.to_vec());
+                    }
+                },
+                criterion::BatchSize::SmallInput,
+            );
+        });
+
+        group.bench_with_input(BenchmarkId::new("zero_copy", size), &data, |b, data| {
+            b.iter_batched(
+                || Bytes::from(data.clone()),
+                |data| {
+                    let mut decoder = FrameDecoder::new();
+                    decoder.feed_bytes(data);
+                    while let Some(frame) = decoder.decode_frame_mut() {
+                        black_box(frame);
+                    }
+                },
+                criterion::BatchSize::SmallInput,
+            );
+        });
+    }
+    group.finish();
+}
+
+fn bench_open(c: &mut Criterion) {
+    let key = key();
+    let mut group = c.benchmark_group("open_frame");
+    for size in SIZES {
+        let sealed = key
+            .encrypt(generate_iv(0).as_ref().into(), payload(size).as_slice())
+            .unwrap();
+        group.throughput(Throughput::Bytes(size as u64));
+
+        group.bench_with_input(
+            BenchmarkId::new("allocating", size),
+            &sealed,
+            |b, sealed| {
+                b.iter(|| {
+                    black_box(
+                        key.decrypt(generate_iv(0).as_ref().into(), sealed.as_slice())
+                            .unwrap(),
+                    )
+                });
+            },
+        );
+
+        group.bench_with_input(BenchmarkId::new("in_place", size), &sealed, |b, sealed| {
+            b.iter_batched(
+                || BytesMut::from(sealed.as_slice()),
+                |mut frame| {
+                    let len = frame.len() - TAG_SIZE;
+                    let (data, tag) = frame.split_at_mut(len);
+                    key.decrypt_in_place_detached(
+                        generate_iv(0).as_ref().into(),
+                        b"",
+                        data,
+                        Tag::from_slice(tag),
+                    )
+                    .unwrap();
+                    frame.truncate(len);
+                    black_box(frame)
+                },
+                criterion::BatchSize::SmallInput,
+            );
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_encode, bench_seal, bench_decode, bench_open);
+criterion_main!(benches);
@@ -0,0 +1,19 @@
+//! Raw frames as they come off the noise socket: the flag byte, an optional
+//! zlib stream and the node itself.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use warp_core_binary::marshal::{marshal_ref, unmarshal_ref};
+use warp_core_binary::util::unpack;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(unpacked) = unpack(data) else {
+        return;
+    };
+    let Ok(node) = unmarshal_ref(&unpacked) else {
+        return;
+    };
+    // Whatever decodes must encode again.
+    let _ = marshal_ref(&node);
+});
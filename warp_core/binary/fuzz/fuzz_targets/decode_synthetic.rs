@@ -0,0 +1,47 @@
+//! Well-formed trees built from the fuzzer input, so the decoder is driven
+//! past the first byte; they must survive an encode/decode round-trip.
+
+#![no_main]
+
+use arbitrary::{Result, Unstructured};
+use libfuzzer_sys::fuzz_target;
+use warp_core_binary::marshal::{marshal, marshal_ref, unmarshal_ref};
+use warp_core_binary::node::{Attrs, Node, NodeContent};
+
+const MAX_DEPTH: usize = 8;
+
+fn node(u: &mut Unstructured<'_>, depth: usize) -> Result<Node> {
+    let tag: String = u.arbitrary()?;
+    let mut attrs = Attrs::new();
+    for _ in 0..u.int_in_range(0..=4)? {
+        attrs.insert(u.arbitrary()?, u.arbitrary()?);
+    }
+    let content = match u.int_in_range(0..=3)? {
+        0 => None,
+        1 => Some(NodeContent::Bytes(u.arbitrary()?)),
+        2 => Some(NodeContent::String(u.arbitrary()?)),
+        _ if depth < MAX_DEPTH => {
+            let mut children = Vec::new();
+            for _ in 0..u.int_in_range(1..=4)? {
+                children.push(node(u, depth + 1)?);
+            }
+            Some(NodeContent::Nodes(children))
+        }
+        _ => None,
+    };
+    Ok(Node::new(&tag, attrs, content))
+}
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(node) = node(&mut Unstructured::new(data), 1) else {
+        return;
+    };
+    // Trees the encoder refuses are not interesting here.
+    let Ok(encoded) = marshal(&node) else {
+        return;
+    };
+    let decoded = unmarshal_ref(&encoded[1..]).expect("encoded node must decode");
+    let reencoded = marshal_ref(&decoded).expect("decoded node must encode");
+    let again = unmarshal_ref(&reencoded[1..]).expect("re-encoded node must decode");
+    assert_eq!(again, decoded);
+});
@@ -5,14 +5,53 @@ use crate::token;
 use std::borrow::Cow;
 use std::simd::{Simd, prelude::*, u8x16};
 
+/// Bounds applied while decoding untrusted input. Exceeding one fails the
+/// decode with an explicit error instead of recursing or allocating on the
+/// strength of a length field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecodeLimits {
+    /// Maximum nesting of nodes, the root node being depth 1.
+    pub max_depth: usize,
+    /// Maximum number of children of a single node.
+    pub max_children: usize,
+    /// Maximum number of attributes of a single node.
+    pub max_attrs: usize,
+    /// Maximum length of a single binary or string value, and of a
+    /// decompressed payload in [`unpack`](crate::util::unpack).
+    pub max_payload_len: usize,
+}
+
+impl Default for DecodeLimits {
+    fn default() -> Self {
+        Self {
+            max_depth: 64,
+            max_children: 16 * 1024,
+            max_attrs: 256,
+            max_payload_len: 16 * 1024 * 1024,
+        }
+    }
+}
+
 pub(crate) struct Decoder<'a> {
     data: &'a [u8],
     position: usize,
+    limits: DecodeLimits,
+    depth: usize,
 }
 
 impl<'a> Decoder<'a> {
+    #[cfg(test)]
     pub(crate) fn new(data: &'a [u8]) -> Self {
-        Self { data, position: 0 }
+        Self::with_limits(data, DecodeLimits::default())
+    }
+
+    pub(crate) fn with_limits(data: &'a [u8], limits: DecodeLimits) -> Self {
+        Self {
+            data,
+            position: 0,
+            limits,
+            depth: 0,
+        }
     }
 
     pub(crate) fn is_finished(&self) -> bool {
@@ -69,6 +108,9 @@ impl<'a> Decoder<'a> {
     }
 
     fn read_bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        if len > self.limits.max_payload_len {
+            return Err(BinaryError::PayloadTooLarge(len));
+        }
         self.check_eos(len)?;
         let slice = &self.data[self.position..self.position + len];
         self.position += len;
@@ -325,7 +367,12 @@ impl<'a> Decoder<'a> {
     }
 
     fn read_attributes(&mut self, size: usize) -> Result<AttrsRef<'a>> {
-        let mut attrs = AttrsRef::with_capacity(size);
+        if size > self.limits.max_attrs {
+            return Err(BinaryError::TooManyAttrs(size));
+        }
+        // A key and a value take at least one byte each, so a size the
+        // remaining input cannot hold must not size the allocation.
+        let mut attrs = AttrsRef::with_capacity(size.min(self.bytes_left() / 2));
         for _ in 0..size {
             let key = self
                 .read_value_as_string()?
@@ -346,7 +393,11 @@ impl<'a> Decoder<'a> {
 
             token::LIST_8 | token::LIST_16 => {
                 let size = self.read_list_size(tag)?;
-                let mut nodes = NodeVec::with_capacity(size);
+                if size > self.limits.max_children {
+                    return Err(BinaryError::TooManyChildren(size));
+                }
+                // Every child takes at least two bytes.
+                let mut nodes = NodeVec::with_capacity(size.min(self.bytes_left() / 2));
                 for _ in 0..size {
                     nodes.push(self.read_node_ref()?);
                 }
@@ -382,6 +433,16 @@ impl<'a> Decoder<'a> {
     }
 
     pub(crate) fn read_node_ref(&mut self) -> Result<NodeRef<'a>> {
+        if self.depth >= self.limits.max_depth {
+            return Err(BinaryError::DepthLimitExceeded(self.limits.max_depth));
+        }
+        self.depth += 1;
+        let node = self.read_node_fields();
+        self.depth -= 1;
+        node
+    }
+
+    fn read_node_fields(&mut self) -> Result<NodeRef<'a>> {
         let tag = self.read_u8()?;
         let list_size = self.read_list_size(tag)?;
         if list_size == 0 {
@@ -713,6 +774,81 @@ mod tests {
         assert!(result.is_err());
     }
 
+    /// `levels` nested nodes, the innermost being a leaf.
+    fn nested(levels: usize) -> Node {
+        let mut current = Node::new("leaf", indexmap::IndexMap::new(), None);
+        for _ in 1..levels {
+            current = Node::new(
+                "level",
+                indexmap::IndexMap::new(),
+                Some(crate::node::NodeContent::Nodes(vec![current])),
+            );
+        }
+        current
+    }
+
+    #[test]
+    fn test_depth_limit() -> TestResult {
+        let limits = DecodeLimits {
+            max_depth: 8,
+            ..DecodeLimits::default()
+        };
+        let within = crate::marshal::marshal(&nested(8))?;
+        Decoder::with_limits(&within[1..], limits).read_node_ref()?;
+
+        let beyond = crate::marshal::marshal(&nested(9))?;
+        let result = Decoder::with_limits(&beyond[1..], limits).read_node_ref();
+        assert!(matches!(result, Err(BinaryError::DepthLimitExceeded(8))));
+        Ok(())
+    }
+
+    #[test]
+    fn test_unbounded_nesting_is_rejected() {
+        // Every level opens a node whose only content is another list, the
+        // shape that used to recurse until the stack overflowed.
+        let mut data = Vec::new();
+        for _ in 0..100_000 {
+            data.extend_from_slice(&[248, 2, token::BINARY_8, 1, b'x', 248, 1]);
+        }
+        let result = Decoder::new(&data).read_node_ref();
+        assert!(matches!(result, Err(BinaryError::DepthLimitExceeded(_))));
+    }
+
+    #[test]
+    fn test_children_limit() {
+        // LIST_16 claiming 65535 children, with no data behind it.
+        let data = vec![248, 2, token::BINARY_8, 1, b'x', 249, 0xFF, 0xFF];
+        let result = Decoder::new(&data).read_node_ref();
+        assert!(matches!(result, Err(BinaryError::TooManyChildren(65535))));
+    }
+
+    #[test]
+    fn test_attrs_limit() {
+        // 1 + 2 * 300 entries: a tag and 300 attributes.
+        let size: u16 = 601;
+        let mut data = vec![249];
+        data.extend_from_slice(&size.to_be_bytes());
+        data.extend_from_slice(&[token::BINARY_8, 1, b'x']);
+        let result = Decoder::new(&data).read_node_ref();
+        assert!(matches!(result, Err(BinaryError::TooManyAttrs(300))));
+    }
+
+    #[test]
+    fn test_payload_limit() {
+        let limits = DecodeLimits {
+            max_payload_len: 4,
+            ..DecodeLimits::default()
+        };
+        let data = vec![token::BINARY_8, 5, 1, 2, 3, 4, 5];
+        let result = Decoder::with_limits(&data, limits).read_content();
+        assert!(matches!(result, Err(BinaryError::PayloadTooLarge(5))));
+
+        // A huge BINARY_32 length fails on the limit, not on the data.
+        let data = vec![token::BINARY_32, 0xFF, 0xFF, 0xFF, 0xFF];
+        let result = Decoder::new(&data).read_content();
+        assert!(matches!(result, Err(BinaryError::PayloadTooLarge(_))));
+    }
+
     /// Test deeply nested nodes (recursion safety)
     #[test]
     fn test_nested_nodes() -> TestResult {
@@ -3,7 +3,7 @@ use std::io::Write;
 use core::simd::prelude::*;
 use core::simd::{Simd, u8x16};
 
-use crate::error::Result;
+use crate::error::{BinaryError, Result};
 use crate::jid;
 use crate::node::{Attrs, AttrsRef, Node, NodeContent, NodeContentRef, NodeRef};
 use crate::token;
@@ -260,9 +260,12 @@ impl<W: Write> Encoder<W> {
         } else if len < 256 {
             self.write_u8(248)?;
             self.write_u8(len as u8)?;
-        } else {
+        } else if len <= u16::MAX as usize {
             self.write_u8(249)?;
             self.write_u16_be(len as u16)?;
+        } else {
+            // The list size would silently wrap.
+            return Err(BinaryError::TooManyChildren(len));
         }
         Ok(())
     }
@@ -614,4 +617,18 @@ mod tests {
         }
         Ok(())
     }
+
+    #[test]
+    fn test_oversized_list_is_rejected() {
+        let children = vec![Node::new("item", indexmap::IndexMap::new(), None); 65_536];
+        let node = Node::new(
+            "list",
+            indexmap::IndexMap::new(),
+            Some(NodeContent::Nodes(children)),
+        );
+
+        let mut buffer = Vec::new();
+        let result = Encoder::new(Cursor::new(&mut buffer)).and_then(|mut e| e.write_node(&node));
+        assert!(matches!(result, Err(BinaryError::TooManyChildren(65_536))));
+    }
 }
@@ -16,6 +16,10 @@ pub enum BinaryError {
     EmptyData,
     LeftoverData(usize),
     AttrList(Vec<BinaryError>),
+    DepthLimitExceeded(usize),
+    TooManyChildren(usize),
+    TooManyAttrs(usize),
+    PayloadTooLarge(usize),
 }
 
 impl fmt::Display for BinaryError {
@@ -33,6 +37,14 @@ impl fmt::Display for BinaryError {
             BinaryError::EmptyData => write!(f, "Received empty data where payload was expected"),
             BinaryError::LeftoverData(n) => write!(f, "Leftover data after decoding: {n} bytes"),
             BinaryError::AttrList(list) => write!(f, "Multiple attribute parsing errors: {list:?}"),
+            BinaryError::DepthLimitExceeded(max) => {
+                write!(f, "Node nesting exceeds the limit of {max} levels")
+            }
+            BinaryError::TooManyChildren(n) => write!(f, "Node has too many children: {n}"),
+            BinaryError::TooManyAttrs(n) => write!(f, "Node has too many attributes: {n}"),
+            BinaryError::PayloadTooLarge(n) => {
+                write!(f, "Payload exceeds the size limit: {n} bytes")
+            }
         }
     }
 }
@@ -78,6 +90,10 @@ impl Clone for BinaryError {
             BinaryError::EmptyData => BinaryError::EmptyData,
             BinaryError::LeftoverData(n) => BinaryError::LeftoverData(*n),
             BinaryError::AttrList(list) => BinaryError::AttrList(list.clone()),
+            BinaryError::DepthLimitExceeded(n) => BinaryError::DepthLimitExceeded(*n),
+            BinaryError::TooManyChildren(n) => BinaryError::TooManyChildren(*n),
+            BinaryError::TooManyAttrs(n) => BinaryError::TooManyAttrs(*n),
+            BinaryError::PayloadTooLarge(n) => BinaryError::PayloadTooLarge(*n),
         }
     }
 }
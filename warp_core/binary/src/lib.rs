@@ -13,6 +13,7 @@ pub mod token;
 pub mod util;
 
 pub use attrs::{AttrParser, AttrParserRef};
+pub use decoder::DecodeLimits;
 pub use error::{BinaryError, Result};
 pub use marshal::{marshal, marshal_ref, marshal_ref_to, marshal_to};
 pub use node::{Node, NodeRef};
@@ -1,9 +1,18 @@
 use std::io::Write;
 
-use crate::{BinaryError, Node, NodeRef, Result, decoder::Decoder, encoder::Encoder};
+use crate::{
+    BinaryError, Node, NodeRef, Result,
+    decoder::{DecodeLimits, Decoder},
+    encoder::Encoder,
+};
 
 pub fn unmarshal_ref(data: &[u8]) -> Result<NodeRef<'_>> {
-    let mut decoder = Decoder::new(data);
+    unmarshal_ref_with_limits(data, DecodeLimits::default())
+}
+
+/// Like [`unmarshal_ref`], with explicit bounds on the decoded tree.
+pub fn unmarshal_ref_with_limits(data: &[u8], limits: DecodeLimits) -> Result<NodeRef<'_>> {
+    let mut decoder = Decoder::with_limits(data, limits);
     let node = decoder.read_node_ref()?;
 
     if decoder.is_finished() {
@@ -40,3 +49,55 @@ pub fn marshal_ref(node: &NodeRef<'_>) -> Result<Vec<u8>> {
     marshal_ref_to(node, &mut payload)?;
     Ok(payload)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node::NodeContent;
+    use crate::util::unpack;
+    use proptest::collection::{btree_map, vec};
+    use proptest::prelude::*;
+
+    /// Nodes the encoder round-trips exactly: string content decodes as
+    /// bytes and an empty child list as no content, so neither is generated.
+    fn node_strategy() -> impl Strategy<Value = Node> {
+        let leaf = (
+            "[a-z]{1,12}",
+            btree_map("[a-z]{1,12}", "[a-z][a-z0-9]{0,15}", 0..4),
+            proptest::option::of(vec(any::<u8>(), 0..64)),
+        )
+            .prop_map(|(tag, attrs, bytes)| {
+                Node::new(
+                    &tag,
+                    attrs.into_iter().collect(),
+                    bytes.map(NodeContent::Bytes),
+                )
+            });
+        leaf.prop_recursive(4, 32, 4, |inner| {
+            ("[a-z]{1,12}", vec(inner, 1..4)).prop_map(|(tag, children)| {
+                Node::new(&tag, Default::default(), Some(NodeContent::Nodes(children)))
+            })
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn prop_marshal_roundtrip(node in node_strategy()) {
+            let data = marshal(&node).unwrap();
+            let decoded = unmarshal_ref(&data[1..]).unwrap();
+            prop_assert_eq!(decoded.to_owned(), node.clone());
+
+            let mut reencoded = Vec::new();
+            marshal_ref_to(&decoded, &mut reencoded).unwrap();
+            prop_assert_eq!(reencoded, data);
+        }
+
+        #[test]
+        fn prop_arbitrary_input_never_panics(data in vec(any::<u8>(), 0..512)) {
+            let _ = unmarshal_ref(&data);
+            if let Ok(unpacked) = unpack(&data) {
+                let _ = unmarshal_ref(&unpacked);
+            }
+        }
+    }
+}
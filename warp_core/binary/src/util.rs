@@ -1,9 +1,16 @@
+use crate::decoder::DecodeLimits;
 use crate::error::{BinaryError, Result};
 use flate2::read::ZlibDecoder;
 use std::borrow::Cow;
 use std::io::Read;
 
 pub fn unpack(data: &[u8]) -> Result<Cow<'_, [u8]>> {
+    unpack_with_limit(data, DecodeLimits::default().max_payload_len)
+}
+
+/// Like [`unpack`], failing with [`BinaryError::PayloadTooLarge`] once the
+/// decompressed payload grows past `max_len` bytes.
+pub fn unpack_with_limit(data: &[u8], max_len: usize) -> Result<Cow<'_, [u8]>> {
     if data.is_empty() {
         return Err(BinaryError::EmptyData);
     }
@@ -11,16 +18,49 @@ pub fn unpack(data: &[u8]) -> Result<Cow<'_, [u8]>> {
     let data = &data[1..];
 
     if (data_type & 2) > 0 {
-        let mut decoder = ZlibDecoder::new(data);
+        let limit = u64::try_from(max_len).unwrap_or(u64::MAX).saturating_add(1);
+        let mut decoder = ZlibDecoder::new(data).take(limit);
         // Pre-allocate with estimated decompressed size (typically 4-8x compressed)
         // Min 256 bytes for small inputs, max 64KB to limit allocation for large inputs
-        let estimated_size = (data.len() * 4).clamp(256, 64 * 1024);
+        let estimated_size = (data.len() * 4).clamp(256, 64 * 1024).min(max_len);
         let mut decompressed = Vec::with_capacity(estimated_size);
         decoder
             .read_to_end(&mut decompressed)
             .map_err(|e| BinaryError::Zlib(e.to_string()))?;
+        if decompressed.len() > max_len {
+            return Err(BinaryError::PayloadTooLarge(decompressed.len()));
+        }
         Ok(Cow::Owned(decompressed))
     } else {
         Ok(Cow::Borrowed(data))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::Compression;
+    use flate2::write::ZlibEncoder;
+    use std::io::Write;
+
+    fn compressed(payload: &[u8]) -> Vec<u8> {
+        let mut encoder = ZlibEncoder::new(vec![2u8], Compression::best());
+        encoder.write_all(payload).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn test_unpack_within_limit() {
+        let data = compressed(&[7; 1024]);
+        assert_eq!(unpack_with_limit(&data, 1024).unwrap().as_ref(), &[7; 1024]);
+    }
+
+    #[test]
+    fn test_unpack_stops_at_limit() {
+        // A few KB inflating to 8 MB.
+        let data = compressed(&vec![0; 8 * 1024 * 1024]);
+        assert!(data.len() < 64 * 1024);
+        let result = unpack_with_limit(&data, 1024 * 1024);
+        assert!(matches!(result, Err(BinaryError::PayloadTooLarge(_))));
+    }
+}
@@ -4,6 +4,30 @@ use log::trace;
 pub const FRAME_LENGTH_SIZE: usize = 3;
 pub const FRAME_MAX_SIZE: usize = 2 << 23;
 
+/// Starts a frame in `out`: clears it and reserves the length prefix. The
+/// payload is then appended (or encrypted in place) after
+/// [`FRAME_LENGTH_SIZE`] bytes and [`finish_frame`] fills the prefix, so the
+/// payload is never copied a second time.
+pub fn begin_frame(out: &mut Vec<u8>) {
+    out.clear();
+    out.extend_from_slice(&[0; FRAME_LENGTH_SIZE]);
+}
+
+/// Writes the length prefix of a frame started with [`begin_frame`].
+pub fn finish_frame(frame: &mut [u8]) -> Result<(), anyhow::Error> {
+    let payload_len = frame.len().saturating_sub(FRAME_LENGTH_SIZE);
+    if frame.len() < FRAME_LENGTH_SIZE || payload_len >= FRAME_MAX_SIZE {
+        return Err(anyhow::anyhow!(
+            "Frame is too large (max: {}, got: {})",
+            FRAME_MAX_SIZE,
+            payload_len
+        ));
+    }
+    let len_bytes = u32::to_be_bytes(payload_len as u32);
+    frame[..FRAME_LENGTH_SIZE].copy_from_slice(&len_bytes[1..]);
+    Ok(())
+}
+
 /// Encodes a payload into a WhatsApp frame, writing directly into `out`.
 /// The `out` buffer is cleared before use, allowing buffer reuse.
 pub fn encode_frame_into(
@@ -50,6 +74,10 @@ pub fn encode_frame(payload: &[u8], header: Option<&[u8]>) -> Result<Vec<u8>, an
 }
 
 /// A frame decoder that buffers incoming data and extracts complete frames.
+///
+/// Frames are split off the internal buffer without copying. Once every
+/// frame split from an allocation is dropped, the buffer reclaims it
+/// instead of allocating again.
 pub struct FrameDecoder {
     buffer: BytesMut,
 }
@@ -65,7 +93,26 @@ impl FrameDecoder {
         self.buffer.extend_from_slice(data);
     }
 
+    /// Like [`feed`](Self::feed), but adopts `data` without copying when
+    /// nothing is buffered and nobody else holds it, which is the common case
+    /// of a websocket message carrying whole frames.
+    pub fn feed_bytes(&mut self, data: Bytes) {
+        if !self.buffer.is_empty() {
+            self.buffer.extend_from_slice(&data);
+            return;
+        }
+        match data.try_into_mut() {
+            Ok(data) => self.buffer = data,
+            Err(data) => self.buffer.extend_from_slice(&data),
+        }
+    }
+
     pub fn decode_frame(&mut self) -> Option<Bytes> {
+        self.decode_frame_mut().map(BytesMut::freeze)
+    }
+
+    /// Next complete frame, still writable so it can be decrypted in place.
+    pub fn decode_frame_mut(&mut self) -> Option<BytesMut> {
         if self.buffer.len() < FRAME_LENGTH_SIZE {
             return None;
         }
@@ -85,7 +132,7 @@ impl FrameDecoder {
 
         if self.buffer.len() >= FRAME_LENGTH_SIZE + frame_len {
             self.buffer.advance(FRAME_LENGTH_SIZE);
-            let frame_data = self.buffer.split_to(frame_len).freeze();
+            let frame_data = self.buffer.split_to(frame_len);
             trace!("<-- Decoded frame: {} bytes", frame_data.len());
             Some(frame_data)
         } else {
@@ -187,6 +234,60 @@ mod tests {
         assert_eq!(buffer.as_ptr(), original_ptr);
     }
 
+    #[test]
+    fn test_begin_and_finish_frame_match_encode_frame() {
+        let payload = vec![9u8; 300];
+        let mut buffer = Vec::new();
+        begin_frame(&mut buffer);
+        buffer.extend_from_slice(&payload);
+        finish_frame(&mut buffer).expect("frame operation should succeed");
+
+        assert_eq!(
+            buffer,
+            encode_frame(&payload, None).expect("frame operation should succeed")
+        );
+    }
+
+    #[test]
+    fn test_finish_frame_rejects_missing_prefix() {
+        assert!(finish_frame(&mut [0, 0]).is_err());
+    }
+
+    #[test]
+    fn test_feed_bytes_adopts_unshared_buffer() {
+        let mut decoder = FrameDecoder::new();
+        let data = Bytes::from(vec![0, 0, 2, 0xAA, 0xBB, 0, 0, 1]);
+        let ptr = data.as_ptr();
+        decoder.feed_bytes(data);
+
+        let frame = decoder
+            .decode_frame_mut()
+            .expect("frame operation should succeed");
+        assert_eq!(&frame[..], &[0xAA, 0xBB]);
+        assert_eq!(frame.as_ptr(), ptr.wrapping_add(FRAME_LENGTH_SIZE));
+
+        // The partial frame left behind is completed by a later message.
+        decoder.feed_bytes(Bytes::from_static(&[0xCC]));
+        let frame = decoder
+            .decode_frame()
+            .expect("frame operation should succeed");
+        assert_eq!(&frame[..], &[0xCC]);
+        assert!(decoder.decode_frame().is_none());
+    }
+
+    #[test]
+    fn test_feed_bytes_copies_shared_buffer() {
+        let mut decoder = FrameDecoder::new();
+        let data = Bytes::from(vec![0, 0, 1, 0xAA]);
+        decoder.feed_bytes(data.clone());
+
+        let frame = decoder
+            .decode_frame()
+            .expect("frame operation should succeed");
+        assert_eq!(&frame[..], &[0xAA]);
+        assert_eq!(&data[..], &[0, 0, 1, 0xAA]);
+    }
+
     #[test]
     fn test_encode_frame_into_with_header() {
         let mut buffer = Vec::new();
@@ -20,6 +20,7 @@
 //! - Bundle encryption: AES-256-GCM after HKDF key derivation
 
 use crate::libsignal::protocol::{KeyPair, PublicKey};
+use crate::store::BrowserProfile;
 use aes::cipher::{KeyIvInit, StreamCipher};
 use aes_gcm::Aes256Gcm;
 use aes_gcm::aead::{Aead, KeyInit};
@@ -85,6 +86,22 @@ impl PlatformId {
             Self::OtherWebClient => "9",
         }
     }
+
+    /// Platform of a browser name such as `Chrome` or `Firefox`, case
+    /// insensitive; unknown names are [`PlatformId::OtherWebClient`].
+    pub fn from_browser(name: &str) -> Self {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "chrome" => Self::Chrome,
+            "firefox" => Self::Firefox,
+            "ie" | "internet explorer" => Self::InternetExplorer,
+            "opera" => Self::Opera,
+            "safari" => Self::Safari,
+            "edge" => Self::Edge,
+            "desktop" | "electron" => Self::Electron,
+            "uwp" => Self::Uwp,
+            _ => Self::OtherWebClient,
+        }
+    }
 }
 
 /// Options for pair code authentication.
@@ -114,6 +131,15 @@ impl Default for PairCodeOptions {
     }
 }
 
+impl PairCodeOptions {
+    /// Shows the phone `browser` instead of the default "Chrome (Linux)".
+    pub fn with_browser(mut self, browser: &BrowserProfile) -> Self {
+        self.platform_id = PlatformId::from_browser(&browser.name);
+        self.platform_display = browser.display_name();
+        self
+    }
+}
+
 /// State machine for pair code authentication flow.
 #[derive(Default)]
 pub enum PairCodeState {
@@ -716,6 +742,19 @@ mod tests {
         assert_eq!(options.platform_display, "Chrome (Linux)");
     }
 
+    #[test]
+    fn test_pair_code_options_with_browser() {
+        let browser = BrowserProfile {
+            name: "firefox".to_string(),
+            os: "Windows".to_string(),
+            version: "10.0".to_string(),
+        };
+        let options = PairCodeOptions::default().with_browser(&browser);
+        assert_eq!(options.platform_id, PlatformId::Firefox);
+        assert_eq!(options.platform_display, "firefox (Windows)");
+        assert_eq!(PlatformId::from_browser("Netscape"), PlatformId::OtherWebClient);
+    }
+
     #[test]
     fn test_pair_code_options_with_custom_code() {
         let options = PairCodeOptions {
@@ -814,6 +814,38 @@ where
     encrypt_for_devices_unified(stores, resolver, &tasks, enc_extra_attrs).await
 }
 
+/// `<biz>` node interactive messages need to be rendered by the recipient:
+/// native flow buttons and single select lists. `None` for anything else.
+pub fn build_biz_node(message: &wa::Message) -> Option<Node> {
+    use crate::proto_helpers::MessageExt;
+    use wa::message::interactive_message::InteractiveMessage;
+
+    let message = message.get_base_message();
+    let child = if let Some(list) = &message.list_message {
+        let list_type = wa::message::list_message::ListType::try_from(list.list_type?).ok()?;
+        NodeBuilder::new("list")
+            .attrs([
+                ("v", "2".to_string()),
+                ("type", list_type.as_str_name().to_lowercase()),
+            ])
+            .build()
+    } else if let Some(InteractiveMessage::NativeFlowMessage(_)) = message
+        .interactive_message
+        .as_ref()
+        .and_then(|m| m.interactive_message.as_ref())
+    {
+        NodeBuilder::new("interactive")
+            .attrs([("type", "native_flow"), ("v", "1")])
+            .children([NodeBuilder::new("native_flow")
+                .attrs([("v", "9"), ("name", "mixed")])
+                .build()])
+            .build()
+    } else {
+        return None;
+    };
+    Some(NodeBuilder::new("biz").children([child]).build())
+}
+
 #[allow(clippy::too_many_arguments)]
 pub async fn prepare_dm_stanza<
     'a,
@@ -936,6 +968,10 @@ pub async fn prepare_dm_stanza<
         message_content_nodes.push(build_reporting_node(result));
     }
 
+    if let Some(biz) = build_biz_node(message) {
+        message_content_nodes.push(biz);
+    }
+
     let mut stanza_attrs = Attrs::new();
     stanza_attrs.insert("to".to_string(), to_jid.to_string());
     stanza_attrs.insert("id".to_string(), request_id);
@@ -1260,6 +1296,10 @@ pub async fn prepare_group_stanza<
         message_children.push(build_reporting_node(result));
     }
 
+    if let Some(biz) = build_biz_node(message) {
+        message_children.push(biz);
+    }
+
     // Add phash if we distributed keys in this message
     if let Some(devices) = &resolved_devices_for_phash {
         match MessageUtils::participant_list_hash(devices) {
@@ -1948,4 +1988,55 @@ mod tests {
 
         println!("✅ LID lookup correctly limited to s.whatsapp.net JIDs");
     }
+
+    #[test]
+    fn biz_node_marks_native_flow_and_lists() {
+        use wa::message::interactive_message::{InteractiveMessage, NativeFlowMessage};
+        use wa::message::{FutureProofMessage, ListMessage, list_message::ListType};
+
+        let buttons = wa::Message {
+            view_once_message: Some(Box::new(FutureProofMessage {
+                message: Some(Box::new(wa::Message {
+                    interactive_message: Some(Box::new(wa::message::InteractiveMessage {
+                        interactive_message: Some(InteractiveMessage::NativeFlowMessage(
+                            NativeFlowMessage::default(),
+                        )),
+                        ..Default::default()
+                    })),
+                    ..Default::default()
+                })),
+            })),
+            ..Default::default()
+        };
+        let biz = build_biz_node(&buttons).expect("native flow needs a biz node");
+        let interactive = biz
+            .get_optional_child("interactive")
+            .expect("interactive child");
+        assert_eq!(
+            interactive.attrs.get("type").map(String::as_str),
+            Some("native_flow")
+        );
+        assert!(interactive.get_optional_child("native_flow").is_some());
+
+        let list = wa::Message {
+            list_message: Some(Box::new(ListMessage {
+                list_type: Some(ListType::SingleSelect as i32),
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+        let biz = build_biz_node(&list).expect("lists need a biz node");
+        let node = biz.get_optional_child("list").expect("list child");
+        assert_eq!(
+            node.attrs.get("type").map(String::as_str),
+            Some("single_select")
+        );
+        assert_eq!(node.attrs.get("v").map(String::as_str), Some("2"));
+
+        let text = wa::Message {
+            conversation: Some("oi".to_string()),
+            ..Default::default()
+        };
+        assert!(build_biz_node(&text).is_none());
+    }
 }
@@ -8,7 +8,10 @@ pub enum DeviceCommand {
     SetLid(Option<Jid>),
     SetPushName(String),
     SetAccount(Option<wa::AdvSignedDeviceIdentity>),
+    /// Version just fetched from WhatsApp Web; stamps the fetch time.
     SetAppVersion((u32, u32, u32)),
+    /// Version from a pin, fallback or the store; keeps the fetch time.
+    UseAppVersion((u32, u32, u32)),
 }
 
 pub fn apply_command_to_device(device: &mut Device, command: DeviceCommand) {
@@ -31,5 +34,10 @@ pub fn apply_command_to_device(device: &mut Device, command: DeviceCommand) {
             device.app_version_tertiary = t;
             device.app_version_last_fetched_ms = chrono::Utc::now().timestamp_millis();
         }
+        DeviceCommand::UseAppVersion((p, s, t)) => {
+            device.app_version_primary = p;
+            device.app_version_secondary = s;
+            device.app_version_tertiary = t;
+        }
     }
 }
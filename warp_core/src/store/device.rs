@@ -30,7 +30,7 @@ pub mod key_pair_serde {
     where
         D: Deserializer<'de>,
     {
-        let bytes: &[u8] = serde::Deserialize::deserialize(deserializer)?;
+        let bytes = deserializer.deserialize_bytes(KeyBytes)?;
         if bytes.len() != 64 {
             return Err(serde::de::Error::invalid_length(bytes.len(), &"64"));
         }
@@ -40,6 +40,30 @@ pub mod key_pair_serde {
             .map_err(|e| serde::de::Error::custom(e.to_string()))?;
         Ok(KeyPair::new(public_key, private_key))
     }
+
+    /// Raw bytes from binary formats, or an array of numbers from JSON
+    /// (`auth export`).
+    struct KeyBytes;
+
+    impl<'de> serde::de::Visitor<'de> for KeyBytes {
+        type Value = Vec<u8>;
+
+        fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            f.write_str("64 key pair bytes")
+        }
+
+        fn visit_bytes<E: serde::de::Error>(self, bytes: &[u8]) -> Result<Vec<u8>, E> {
+            Ok(bytes.to_vec())
+        }
+
+        fn visit_seq<A: serde::de::SeqAccess<'de>>(self, mut seq: A) -> Result<Vec<u8>, A::Error> {
+            let mut bytes = Vec::with_capacity(64);
+            while let Some(byte) = seq.next_element()? {
+                bytes.push(byte);
+            }
+            Ok(bytes)
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -48,8 +72,61 @@ pub struct ProcessedMessageKey {
     pub id: String,
 }
 
+/// Browser a companion device presents itself as. WhatsApp shows it in the
+/// phone's linked devices list (e.g. "Chrome (Linux)").
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BrowserProfile {
+    /// Browser name: `Chrome`, `Firefox`, `Safari`, `Edge`, `Opera`, `IE`,
+    /// `Desktop` or `UWP`; other names are sent as an unknown platform.
+    pub name: String,
+    /// Operating system shown next to the browser, e.g. `Windows`.
+    pub os: String,
+    /// OS version, up to three dotted numbers, e.g. `10.0.22631`.
+    pub version: String,
+}
+
+impl BrowserProfile {
+    /// `DeviceProps` platform of [`Self::name`], case insensitive.
+    pub fn platform_type(&self) -> wa::device_props::PlatformType {
+        use wa::device_props::PlatformType;
+        match self.name.trim().to_ascii_lowercase().as_str() {
+            "chrome" => PlatformType::Chrome,
+            "firefox" => PlatformType::Firefox,
+            "ie" | "internet explorer" => PlatformType::Ie,
+            "opera" => PlatformType::Opera,
+            "safari" => PlatformType::Safari,
+            "edge" => PlatformType::Edge,
+            "desktop" | "electron" => PlatformType::Desktop,
+            "uwp" => PlatformType::Uwp,
+            _ => PlatformType::Unknown,
+        }
+    }
+
+    /// [`Self::version`] as an app version; missing or non-numeric parts
+    /// are 0.
+    pub fn os_version(&self) -> wa::device_props::AppVersion {
+        let mut parts = self
+            .version
+            .trim()
+            .split('.')
+            .map(|part| part.trim().parse::<u32>().unwrap_or(0));
+        wa::device_props::AppVersion {
+            primary: Some(parts.next().unwrap_or(0)),
+            secondary: Some(parts.next().unwrap_or(0)),
+            tertiary: Some(parts.next().unwrap_or(0)),
+            ..Default::default()
+        }
+    }
+
+    /// Name shown on the phone, e.g. `Chrome (Linux)`.
+    pub fn display_name(&self) -> String {
+        format!("{} ({})", self.name.trim(), self.os.trim())
+    }
+}
+
 fn build_base_client_payload(
     app_version: wa::client_payload::user_agent::AppVersion,
+    os_version: String,
 ) -> wa::ClientPayload {
     wa::ClientPayload {
         user_agent: Some(wa::client_payload::UserAgent {
@@ -58,10 +135,10 @@ fn build_base_client_payload(
             app_version: Some(app_version),
             mcc: Some("000".to_string()),
             mnc: Some("000".to_string()),
-            os_version: Some("0.1.0".to_string()),
+            os_version: Some(os_version.clone()),
             manufacturer: Some("".to_string()),
             device: Some("Desktop".to_string()),
-            os_build_number: Some("0.1.0".to_string()),
+            os_build_number: Some(os_version),
             locale_language_iso6391: Some("en".to_string()),
             locale_country_iso31661_alpha2: Some("en".to_string()),
             ..Default::default()
@@ -173,6 +250,20 @@ impl Device {
         }
     }
 
+    /// A fresh unpaired device (new keys, no account) that keeps the app
+    /// version and device props of `self`, for when WhatsApp revoked the
+    /// pairing.
+    pub fn unpaired(&self) -> Self {
+        Self {
+            app_version_primary: self.app_version_primary,
+            app_version_secondary: self.app_version_secondary,
+            app_version_tertiary: self.app_version_tertiary,
+            app_version_last_fetched_ms: self.app_version_last_fetched_ms,
+            device_props: self.device_props.clone(),
+            ..Self::new()
+        }
+    }
+
     /// Returns the default OS string used for device props
     pub fn default_os() -> &'static str {
         "rust"
@@ -205,6 +296,28 @@ impl Device {
         }
     }
 
+    /// Presents the device as `browser`: its OS, OS version and platform go
+    /// into the device props and the user agent of the next handshake.
+    pub fn set_browser(&mut self, browser: &BrowserProfile) {
+        self.device_props.os = Some(browser.os.trim().to_string());
+        self.device_props.version = Some(browser.os_version());
+        self.device_props.platform_type = Some(browser.platform_type() as i32);
+    }
+
+    /// OS version sent in the user agent, from the device props.
+    fn os_version(&self) -> String {
+        let version = self
+            .device_props
+            .version
+            .unwrap_or_else(Self::default_device_props_version);
+        format!(
+            "{}.{}.{}",
+            version.primary(),
+            version.secondary(),
+            version.tertiary()
+        )
+    }
+
     pub fn get_client_payload(&self) -> wa::ClientPayload {
         match &self.pn {
             Some(jid) => self.get_login_payload(jid),
@@ -219,7 +332,7 @@ impl Device {
             tertiary: Some(self.app_version_tertiary),
             ..Default::default()
         };
-        let mut payload = build_base_client_payload(app_version);
+        let mut payload = build_base_client_payload(app_version, self.os_version());
         payload.username = jid.user.parse::<u64>().ok();
         payload.device = Some(jid.device as u32);
         payload.passive = Some(true);
@@ -233,7 +346,7 @@ impl Device {
             tertiary: Some(self.app_version_tertiary),
             ..Default::default()
         };
-        let mut payload = build_base_client_payload(app_version);
+        let mut payload = build_base_client_payload(app_version, self.os_version());
 
         let device_props_bytes = self.device_props.encode_to_vec();
 
@@ -269,3 +382,54 @@ impl Device {
         payload
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user_agent(device: &Device) -> wa::client_payload::UserAgent {
+        device
+            .get_client_payload()
+            .user_agent
+            .expect("payload has a user agent")
+    }
+
+    #[test]
+    fn browser_profile_changes_device_props_and_user_agent() {
+        let mut device = Device::new();
+        assert_eq!(user_agent(&device).os_version.as_deref(), Some("0.1.0"));
+
+        device.set_browser(&BrowserProfile {
+            name: "Edge".to_string(),
+            os: "Windows".to_string(),
+            version: "10.0.22631".to_string(),
+        });
+        assert_eq!(device.device_props.os.as_deref(), Some("Windows"));
+        assert_eq!(
+            device.device_props.platform_type,
+            Some(wa::device_props::PlatformType::Edge as i32)
+        );
+        let agent = user_agent(&device);
+        assert_eq!(agent.os_version.as_deref(), Some("10.0.22631"));
+        assert_eq!(agent.os_build_number.as_deref(), Some("10.0.22631"));
+    }
+
+    #[test]
+    fn browser_version_tolerates_short_and_odd_values() {
+        let browser = BrowserProfile {
+            name: "Netscape".to_string(),
+            os: "Mac OS".to_string(),
+            version: "14.x".to_string(),
+        };
+        let version = browser.os_version();
+        assert_eq!(
+            (version.primary(), version.secondary(), version.tertiary()),
+            (14, 0, 0)
+        );
+        assert_eq!(
+            browser.platform_type(),
+            wa::device_props::PlatformType::Unknown
+        );
+        assert_eq!(browser.display_name(), "Netscape (Mac OS)");
+    }
+}
@@ -1,7 +1,8 @@
 pub mod commands;
 pub mod device;
 pub mod error;
+pub mod secrets;
 pub mod traits;
 
 pub use commands::*;
-pub use device::Device;
+pub use device::{BrowserProfile, Device};
@@ -0,0 +1,401 @@
+//! Envelope encryption for secrets kept by the storage backends.
+//!
+//! Every value is sealed with AES-256-GCM under its own random data key,
+//! and that data key is sealed under a master key named by an id stored in
+//! the envelope. Rotating the master key means adding the new key as current
+//! and keeping the old one as previous until every row has been resealed;
+//! values from before encryption was enabled carry no envelope and open as
+//! they are, so existing databases keep working until they are migrated.
+
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use base64::Engine as _;
+use base64::prelude::BASE64_STANDARD;
+use rand::Rng;
+use thiserror::Error;
+
+use super::error::StoreError;
+
+/// Leading bytes of every sealed value.
+const MAGIC: &[u8] = b"\0CWSEC";
+const VERSION: u8 = 1;
+/// Prefix of sealed values stored in text columns.
+const TEXT_PREFIX: &str = "enc:v1:";
+const NONCE_LEN: usize = 12;
+const KEY_LEN: usize = 32;
+const TAG_LEN: usize = 16;
+const WRAPPED_KEY_LEN: usize = NONCE_LEN + KEY_LEN + TAG_LEN;
+/// Id of a master key configured without one.
+pub const DEFAULT_KEY_ID: &str = "default";
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum SecretsError {
+    #[error("invalid master key {0:?}: expected [id:]<32 bytes in base64>")]
+    InvalidKey(String),
+    #[error("master key id {0:?} is configured twice")]
+    DuplicateKey(String),
+    #[error("value was sealed with master key {0:?}, which is not configured")]
+    UnknownKey(String),
+    #[error("malformed sealed value")]
+    Malformed,
+    #[error("encryption failed")]
+    Encrypt,
+    #[error("decryption failed (wrong master key or tampered value)")]
+    Decrypt,
+}
+
+impl From<SecretsError> for StoreError {
+    fn from(e: SecretsError) -> Self {
+        StoreError::Serialization(format!("secret: {e}"))
+    }
+}
+
+/// A named AES-256 master key. `Debug` prints the id only.
+#[derive(Clone, PartialEq, Eq)]
+pub struct MasterKey {
+    id: String,
+    key: [u8; KEY_LEN],
+}
+
+impl std::fmt::Debug for MasterKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MasterKey")
+            .field("id", &self.id)
+            .finish_non_exhaustive()
+    }
+}
+
+impl MasterKey {
+    pub fn new(id: impl Into<String>, key: [u8; KEY_LEN]) -> Result<Self, SecretsError> {
+        let id = id.into();
+        let valid_id = !id.is_empty()
+            && id.len() <= 64
+            && id
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+        if !valid_id {
+            return Err(SecretsError::InvalidKey(id));
+        }
+        Ok(Self { id, key })
+    }
+
+    /// Parses `<id>:<base64>` or a bare `<base64>`, which gets
+    /// [`DEFAULT_KEY_ID`].
+    pub fn parse(value: &str) -> Result<Self, SecretsError> {
+        let value = value.trim();
+        let (id, encoded) = value.split_once(':').unwrap_or((DEFAULT_KEY_ID, value));
+        let invalid = || SecretsError::InvalidKey(id.to_string());
+        let key = BASE64_STANDARD
+            .decode(encoded.trim())
+            .map_err(|_| invalid())?
+            .try_into()
+            .map_err(|_| invalid())?;
+        Self::new(id.trim(), key)
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn cipher(&self) -> Aes256Gcm {
+        Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&self.key))
+    }
+}
+
+/// The current master key, which seals new values, and the previous ones,
+/// which only open values sealed before a rotation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Keyring {
+    current: MasterKey,
+    previous: Vec<MasterKey>,
+}
+
+impl Keyring {
+    pub fn new(current: MasterKey, previous: Vec<MasterKey>) -> Result<Self, SecretsError> {
+        for (i, key) in previous.iter().enumerate() {
+            if key.id == current.id || previous[..i].iter().any(|k| k.id == key.id) {
+                return Err(SecretsError::DuplicateKey(key.id.clone()));
+            }
+        }
+        Ok(Self { current, previous })
+    }
+
+    /// Parses the current key and a comma-separated list of previous keys,
+    /// both in the [`MasterKey::parse`] format.
+    pub fn parse(current: &str, previous: &str) -> Result<Self, SecretsError> {
+        let previous = previous
+            .split(',')
+            .filter(|v| !v.trim().is_empty())
+            .map(MasterKey::parse)
+            .collect::<Result<Vec<_>, _>>()?;
+        Self::new(MasterKey::parse(current)?, previous)
+    }
+
+    pub fn current_id(&self) -> &str {
+        &self.current.id
+    }
+
+    fn key(&self, id: &str) -> Option<&MasterKey> {
+        std::iter::once(&self.current)
+            .chain(&self.previous)
+            .find(|k| k.id == id)
+    }
+
+    /// Seals `plaintext` under a fresh data key wrapped by the current key.
+    pub fn seal(&self, plaintext: &[u8]) -> Result<Vec<u8>, SecretsError> {
+        let id = self.current.id.as_bytes();
+        let mut out = Vec::with_capacity(
+            MAGIC.len() + 2 + id.len() + WRAPPED_KEY_LEN + NONCE_LEN + plaintext.len() + TAG_LEN,
+        );
+        out.extend_from_slice(MAGIC);
+        out.push(VERSION);
+        out.push(id.len() as u8);
+        out.extend_from_slice(id);
+        let header_len = out.len();
+
+        let mut rng = rand::rng();
+        let mut data_key = [0u8; KEY_LEN];
+        let mut key_nonce = [0u8; NONCE_LEN];
+        let mut nonce = [0u8; NONCE_LEN];
+        rng.fill(&mut data_key);
+        rng.fill(&mut key_nonce);
+        rng.fill(&mut nonce);
+
+        let wrapped = self
+            .current
+            .cipher()
+            .encrypt(
+                Nonce::from_slice(&key_nonce),
+                Payload {
+                    msg: &data_key,
+                    aad: &out[..header_len],
+                },
+            )
+            .map_err(|_| SecretsError::Encrypt)?;
+        let sealed = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&data_key))
+            .encrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: plaintext,
+                    aad: &out[..header_len],
+                },
+            )
+            .map_err(|_| SecretsError::Encrypt)?;
+
+        out.extend_from_slice(&key_nonce);
+        out.extend_from_slice(&wrapped);
+        out.extend_from_slice(&nonce);
+        out.extend_from_slice(&sealed);
+        Ok(out)
+    }
+
+    /// Opens a value from [`Keyring::seal`]; values without an envelope are
+    /// returned unchanged.
+    pub fn open(&self, data: &[u8]) -> Result<Vec<u8>, SecretsError> {
+        let Some(envelope) = Envelope::parse(data)? else {
+            return Ok(data.to_vec());
+        };
+        let key = self
+            .key(envelope.key_id)
+            .ok_or_else(|| SecretsError::UnknownKey(envelope.key_id.to_string()))?;
+        let data_key = key
+            .cipher()
+            .decrypt(
+                Nonce::from_slice(envelope.key_nonce),
+                Payload {
+                    msg: envelope.wrapped_key,
+                    aad: envelope.header,
+                },
+            )
+            .map_err(|_| SecretsError::Decrypt)?;
+        Aes256Gcm::new_from_slice(&data_key)
+            .map_err(|_| SecretsError::Decrypt)?
+            .decrypt(
+                Nonce::from_slice(envelope.nonce),
+                Payload {
+                    msg: envelope.ciphertext,
+                    aad: envelope.header,
+                },
+            )
+            .map_err(|_| SecretsError::Decrypt)
+    }
+
+    /// Seals `data` again under the current key, or `None` when it already
+    /// is. Plaintext values get sealed for the first time.
+    pub fn reseal(&self, data: &[u8]) -> Result<Option<Vec<u8>>, SecretsError> {
+        if let Some(envelope) = Envelope::parse(data)?
+            && envelope.key_id == self.current.id
+        {
+            return Ok(None);
+        }
+        self.seal(&self.open(data)?).map(Some)
+    }
+
+    /// [`Keyring::seal`] for text columns: `enc:v1:<base64>`.
+    pub fn seal_text(&self, plaintext: &str) -> Result<String, SecretsError> {
+        let sealed = self.seal(plaintext.as_bytes())?;
+        Ok(format!("{TEXT_PREFIX}{}", BASE64_STANDARD.encode(sealed)))
+    }
+
+    /// [`Keyring::open`] for text columns; values without the prefix are
+    /// returned unchanged.
+    pub fn open_text(&self, value: &str) -> Result<String, SecretsError> {
+        let Some(encoded) = value.strip_prefix(TEXT_PREFIX) else {
+            return Ok(value.to_string());
+        };
+        let sealed = BASE64_STANDARD
+            .decode(encoded)
+            .map_err(|_| SecretsError::Malformed)?;
+        if !is_sealed(&sealed) {
+            return Err(SecretsError::Malformed);
+        }
+        String::from_utf8(self.open(&sealed)?).map_err(|_| SecretsError::Malformed)
+    }
+
+    /// [`Keyring::reseal`] for text columns.
+    pub fn reseal_text(&self, value: &str) -> Result<Option<String>, SecretsError> {
+        if let Some(encoded) = value.strip_prefix(TEXT_PREFIX)
+            && let Ok(sealed) = BASE64_STANDARD.decode(encoded)
+            && let Some(envelope) = Envelope::parse(&sealed)?
+            && envelope.key_id == self.current.id
+        {
+            return Ok(None);
+        }
+        self.seal_text(&self.open_text(value)?).map(Some)
+    }
+}
+
+/// Whether `data` carries an envelope from [`Keyring::seal`].
+pub fn is_sealed(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+/// Whether `value` comes from [`Keyring::seal_text`].
+pub fn is_sealed_text(value: &str) -> bool {
+    value.starts_with(TEXT_PREFIX)
+}
+
+struct Envelope<'a> {
+    header: &'a [u8],
+    key_id: &'a str,
+    key_nonce: &'a [u8],
+    wrapped_key: &'a [u8],
+    nonce: &'a [u8],
+    ciphertext: &'a [u8],
+}
+
+impl<'a> Envelope<'a> {
+    fn parse(data: &'a [u8]) -> Result<Option<Self>, SecretsError> {
+        let Some(rest) = data.strip_prefix(MAGIC) else {
+            return Ok(None);
+        };
+        let [version, id_len, rest @ ..] = rest else {
+            return Err(SecretsError::Malformed);
+        };
+        if *version != VERSION {
+            return Err(SecretsError::Malformed);
+        }
+        let id_len = usize::from(*id_len);
+        if rest.len() < id_len + WRAPPED_KEY_LEN + NONCE_LEN + TAG_LEN {
+            return Err(SecretsError::Malformed);
+        }
+        let (id, rest) = rest.split_at(id_len);
+        let (key_nonce, rest) = rest.split_at(NONCE_LEN);
+        let (wrapped_key, rest) = rest.split_at(KEY_LEN + TAG_LEN);
+        let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+        Ok(Some(Self {
+            header: &data[..MAGIC.len() + 2 + id_len],
+            key_id: std::str::from_utf8(id).map_err(|_| SecretsError::Malformed)?,
+            key_nonce,
+            wrapped_key,
+            nonce,
+            ciphertext,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(id: &str, byte: u8) -> MasterKey {
+        MasterKey::new(id, [byte; KEY_LEN]).unwrap()
+    }
+
+    #[test]
+    fn seals_and_opens() {
+        let keyring = Keyring::new(key("k1", 1), vec![]).unwrap();
+        let sealed = keyring.seal(b"identity key").unwrap();
+        assert!(is_sealed(&sealed));
+        assert!(!sealed.windows(12).any(|w| w == b"identity key"));
+        assert_eq!(keyring.open(&sealed).unwrap(), b"identity key");
+        assert_ne!(keyring.seal(b"identity key").unwrap(), sealed);
+
+        let text = keyring.seal_text("EAAG-token").unwrap();
+        assert!(is_sealed_text(&text));
+        assert_eq!(keyring.open_text(&text).unwrap(), "EAAG-token");
+    }
+
+    #[test]
+    fn plaintext_passes_through() {
+        let keyring = Keyring::new(key("k1", 1), vec![]).unwrap();
+        assert_eq!(keyring.open(b"legacy").unwrap(), b"legacy");
+        assert_eq!(keyring.open_text("legacy").unwrap(), "legacy");
+        assert!(keyring.reseal(b"legacy").unwrap().is_some());
+    }
+
+    #[test]
+    fn rotation_keeps_old_values_readable() {
+        let old = Keyring::new(key("k1", 1), vec![]).unwrap();
+        let sealed = old.seal(b"record").unwrap();
+        let text = old.seal_text("secret").unwrap();
+
+        let rotated = Keyring::new(key("k2", 2), vec![key("k1", 1)]).unwrap();
+        assert_eq!(rotated.open(&sealed).unwrap(), b"record");
+        let resealed = rotated.reseal(&sealed).unwrap().unwrap();
+        assert_eq!(rotated.reseal(&resealed).unwrap(), None);
+        assert_eq!(
+            old.open(&resealed),
+            Err(SecretsError::UnknownKey("k2".to_string()))
+        );
+
+        let text = rotated.reseal_text(&text).unwrap().unwrap();
+        assert_eq!(rotated.reseal_text(&text).unwrap(), None);
+        assert_eq!(rotated.open_text(&text).unwrap(), "secret");
+    }
+
+    #[test]
+    fn rejects_tampering_and_wrong_keys() {
+        let keyring = Keyring::new(key("k1", 1), vec![]).unwrap();
+        let mut sealed = keyring.seal(b"record").unwrap();
+        let last = sealed.len() - 1;
+        sealed[last] ^= 1;
+        assert_eq!(keyring.open(&sealed), Err(SecretsError::Decrypt));
+
+        let sealed = keyring.seal(b"record").unwrap();
+        let impostor = Keyring::new(key("k1", 9), vec![]).unwrap();
+        assert_eq!(impostor.open(&sealed), Err(SecretsError::Decrypt));
+        assert_eq!(
+            keyring.open(&sealed[..MAGIC.len() + 4]),
+            Err(SecretsError::Malformed)
+        );
+    }
+
+    #[test]
+    fn parses_keys() {
+        let encoded = BASE64_STANDARD.encode([7u8; KEY_LEN]);
+        let keyring = Keyring::parse(&format!("k2:{encoded}"), &format!("{encoded}, ")).unwrap();
+        assert_eq!(keyring.current_id(), "k2");
+        assert_eq!(keyring.previous[0].id(), DEFAULT_KEY_ID);
+        assert!(!format!("{keyring:?}").contains(&encoded));
+
+        assert_eq!(
+            MasterKey::parse("k1:c2hvcnQ="),
+            Err(SecretsError::InvalidKey("k1".to_string()))
+        );
+        assert_eq!(
+            Keyring::parse(&format!("k1:{encoded}"), &format!("k1:{encoded}")),
+            Err(SecretsError::DuplicateKey("k1".to_string()))
+        );
+    }
+}
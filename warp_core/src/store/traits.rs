@@ -207,6 +207,9 @@ pub trait AppSyncStore: Send + Sync {
     /// Set an app state sync key.
     async fn set_sync_key(&self, key_id: &[u8], key: AppStateSyncKey) -> Result<()>;
 
+    /// Get the ID of the newest app state sync key, used to encrypt outgoing patches.
+    async fn get_latest_sync_key_id(&self) -> Result<Option<Vec<u8>>>;
+
     /// Get the app state version for a collection.
     async fn get_version(&self, name: &str) -> Result<HashState>;
 
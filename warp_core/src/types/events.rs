@@ -237,6 +237,8 @@ pub enum Event {
 
     Message(Box<wa::Message>, MessageInfo),
     Receipt(Receipt),
+    /// The server accepted (or rejected) a message we sent.
+    MessageAck(MessageAck),
     UndecryptableMessage(UndecryptableMessage),
     Notification(Node),
 
@@ -466,12 +468,112 @@ pub struct CatRefreshError {
     pub error: String,
 }
 
+/// What a `<stream:error>` means for the session, from its `code` and
+/// child element.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StreamErrorReason {
+    /// 515: the server ends the stream (e.g. after pairing) and expects an
+    /// immediate reconnect.
+    RestartRequired,
+    /// `<conflict type="replaced"/>`: another client opened this session.
+    ReplacedByOtherDevice,
+    /// 401 or `<conflict type="device_removed"/>`: the phone unlinked this
+    /// device.
+    LoggedOut,
+    /// 429: too many connections or requests.
+    RateOverlimit,
+    /// 500 or 503.
+    ServiceUnavailable,
+    Unknown,
+}
+
+impl StreamErrorReason {
+    pub fn from_parts(code: &str, conflict_type: Option<&str>) -> Self {
+        match (code, conflict_type) {
+            (_, Some("replaced")) => Self::ReplacedByOtherDevice,
+            (_, Some("device_removed")) | ("401", _) => Self::LoggedOut,
+            ("515", _) => Self::RestartRequired,
+            ("429", _) => Self::RateOverlimit,
+            ("500" | "503", _) => Self::ServiceUnavailable,
+            _ => Self::Unknown,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::RestartRequired => "restart_required",
+            Self::ReplacedByOtherDevice => "replaced_by_other_device",
+            Self::LoggedOut => "logged_out",
+            Self::RateOverlimit => "rate_overlimit",
+            Self::ServiceUnavailable => "service_unavailable",
+            Self::Unknown => "unknown",
+        }
+    }
+
+    /// Whether the client should connect again on its own. Otherwise it
+    /// stops until told to reconnect (replaced) or paired again (logged
+    /// out).
+    pub fn should_reconnect(self) -> bool {
+        !matches!(self, Self::ReplacedByOtherDevice | Self::LoggedOut)
+    }
+
+    /// Whether only a new pairing brings the session back.
+    pub fn requires_pairing(self) -> bool {
+        self == Self::LoggedOut
+    }
+}
+
+impl fmt::Display for StreamErrorReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
 #[derive(Debug, Clone, Serialize)]
 pub struct StreamError {
     pub code: String,
+    pub reason: StreamErrorReason,
+    /// Tag of the first child element (`conflict`, `ack`, ...).
+    pub child: Option<String>,
+    /// `type` of a `<conflict>` child.
+    pub conflict_type: Option<String>,
     pub raw: Option<Node>,
 }
 
+impl StreamError {
+    /// Reads a `<stream:error>` stanza.
+    pub fn from_node(node: &Node) -> Self {
+        let code = node
+            .attrs()
+            .optional_string("code")
+            .unwrap_or_default()
+            .to_string();
+        let conflict_type = node
+            .get_optional_child("conflict")
+            .and_then(|conflict| conflict.attrs().optional_string("type").map(str::to_string))
+            .filter(|t| !t.is_empty());
+        Self {
+            reason: StreamErrorReason::from_parts(&code, conflict_type.as_deref()),
+            child: node
+                .children()
+                .and_then(|children| children.first())
+                .map(|child| child.tag.to_string()),
+            code,
+            conflict_type,
+            raw: Some(node.clone()),
+        }
+    }
+
+    /// `code`, or `code:type` for conflicts, as recorded in diagnostics.
+    pub fn close_code(&self) -> String {
+        match &self.conflict_type {
+            Some(conflict_type) => format!("{}:{}", self.code, conflict_type),
+            None => self.code.clone(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize)]
 pub struct Disconnected;
 
@@ -518,6 +620,16 @@ pub struct Receipt {
     pub message_sender: Jid,
 }
 
+/// `<ack class="message">` for an outgoing message.
+#[derive(Debug, Clone, Serialize)]
+pub struct MessageAck {
+    pub id: MessageId,
+    pub chat: Option<Jid>,
+    pub timestamp: DateTime<Utc>,
+    /// Error code sent by the server when it refused the message.
+    pub error: Option<String>,
+}
+
 #[derive(Debug, Clone, Serialize)]
 pub struct ChatPresenceUpdate {
     pub source: crate::types::message::MessageSource,
@@ -641,3 +753,74 @@ pub struct NewsletterLiveUpdate {
     pub time: DateTime<Utc>,
     pub messages: Vec<crate::types::newsletter::NewsletterMessage>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use warp_core_binary::builder::NodeBuilder;
+
+    #[test]
+    fn stream_error_reason_from_code_and_conflict() {
+        assert_eq!(
+            StreamErrorReason::from_parts("515", None),
+            StreamErrorReason::RestartRequired
+        );
+        assert_eq!(
+            StreamErrorReason::from_parts("", Some("replaced")),
+            StreamErrorReason::ReplacedByOtherDevice
+        );
+        assert_eq!(
+            StreamErrorReason::from_parts("401", Some("device_removed")),
+            StreamErrorReason::LoggedOut
+        );
+        assert_eq!(
+            StreamErrorReason::from_parts("401", None),
+            StreamErrorReason::LoggedOut
+        );
+        assert_eq!(
+            StreamErrorReason::from_parts("429", None),
+            StreamErrorReason::RateOverlimit
+        );
+        assert_eq!(
+            StreamErrorReason::from_parts("503", None),
+            StreamErrorReason::ServiceUnavailable
+        );
+        assert_eq!(
+            StreamErrorReason::from_parts("516", None),
+            StreamErrorReason::Unknown
+        );
+    }
+
+    #[test]
+    fn only_replaced_and_logged_out_stop_reconnecting() {
+        assert!(StreamErrorReason::RateOverlimit.should_reconnect());
+        assert!(StreamErrorReason::Unknown.should_reconnect());
+        assert!(!StreamErrorReason::ReplacedByOtherDevice.should_reconnect());
+        assert!(!StreamErrorReason::LoggedOut.should_reconnect());
+        assert!(StreamErrorReason::LoggedOut.requires_pairing());
+        assert!(!StreamErrorReason::ReplacedByOtherDevice.requires_pairing());
+    }
+
+    #[test]
+    fn stream_error_from_conflict_node() {
+        let node = NodeBuilder::new("stream:error")
+            .children([NodeBuilder::new("conflict")
+                .attr("type", "replaced")
+                .build()])
+            .build();
+        let error = StreamError::from_node(&node);
+        assert_eq!(error.reason, StreamErrorReason::ReplacedByOtherDevice);
+        assert_eq!(error.child.as_deref(), Some("conflict"));
+        assert_eq!(error.close_code(), ":replaced");
+    }
+
+    #[test]
+    fn stream_error_from_code_node() {
+        let node = NodeBuilder::new("stream:error").attr("code", "429").build();
+        let error = StreamError::from_node(&node);
+        assert_eq!(error.reason, StreamErrorReason::RateOverlimit);
+        assert_eq!(error.child, None);
+        assert_eq!(error.close_code(), "429");
+        assert_eq!(error.reason.to_string(), "rate_overlimit");
+    }
+}
@@ -1,8 +1,11 @@
 use crate::download::MediaType;
 use crate::libsignal::crypto::{CryptographicHash, CryptographicMac, aes_256_cbc_encrypt_into};
-use anyhow::Result;
+use anyhow::{Result, anyhow};
+use hmac::{Hmac, Mac};
 use rand::Rng;
 use rand::rng;
+use sha2::{Digest, Sha256};
+use std::io::{Read, Write};
 
 pub struct EncryptedMedia {
     pub data_to_upload: Vec<u8>,
@@ -57,6 +60,96 @@ pub fn encrypt_media(plaintext: &[u8], media_type: MediaType) -> Result<Encrypte
     })
 }
 
+/// Keys and hashes of media encrypted by [`encrypt_media_stream`]; the
+/// ciphertext itself went to the writer.
+pub struct EncryptedMediaInfo {
+    pub media_key: [u8; 32],
+    pub file_sha256: [u8; 32],
+    pub file_enc_sha256: [u8; 32],
+    /// Plaintext length.
+    pub file_length: u64,
+}
+
+/// Same output as [`encrypt_media`], but reads the plaintext from `reader`
+/// and writes the upload body to `writer` in small chunks, so neither is
+/// held in memory.
+pub fn encrypt_media_stream<R: Read, W: Write>(
+    mut reader: R,
+    mut writer: W,
+    media_type: MediaType,
+) -> Result<EncryptedMediaInfo> {
+    use aes::Aes256;
+    #[allow(deprecated)]
+    use aes::cipher::generic_array::GenericArray;
+    use aes::cipher::{BlockEncrypt, KeyInit};
+
+    const MAC_SIZE: usize = 10;
+    const BLOCK: usize = 16;
+    const CHUNK: usize = 64 * 1024;
+
+    let mut media_key = [0u8; 32];
+    rng().fill(&mut media_key);
+    let (iv, cipher_key, mac_key) =
+        crate::download::DownloadUtils::get_media_keys(&media_key, media_type)?;
+    let cipher = Aes256::new_from_slice(&cipher_key).map_err(|_| anyhow!("Bad AES key length"))?;
+    let mut hmac = <Hmac<Sha256> as Mac>::new_from_slice(&mac_key)
+        .map_err(|_| anyhow!("Failed to init HMAC"))?;
+    hmac.update(&iv);
+    let mut plain_hash = Sha256::new();
+    let mut enc_hash = Sha256::new();
+
+    let mut prev_block = iv;
+    let mut encrypt_blocks = |data: &mut [u8]| {
+        for block in data.chunks_exact_mut(BLOCK) {
+            for (b, p) in block.iter_mut().zip(prev_block.iter()) {
+                *b ^= *p;
+            }
+            #[allow(deprecated)]
+            cipher.encrypt_block(GenericArray::from_mut_slice(block));
+            prev_block.copy_from_slice(block);
+        }
+    };
+
+    let mut buf = vec![0u8; CHUNK];
+    let mut filled = 0;
+    let mut file_length = 0u64;
+    loop {
+        let n = reader.read(&mut buf[filled..])?;
+        if n == 0 {
+            break;
+        }
+        plain_hash.update(&buf[filled..filled + n]);
+        file_length += n as u64;
+        filled += n;
+        if filled == CHUNK {
+            encrypt_blocks(&mut buf);
+            hmac.update(&buf);
+            enc_hash.update(&buf);
+            writer.write_all(&buf)?;
+            filled = 0;
+        }
+    }
+
+    // PKCS7: always pads, a whole block when the input is aligned.
+    let pad = BLOCK - filled % BLOCK;
+    let mut last = buf[..filled].to_vec();
+    last.resize(filled + pad, pad as u8);
+    encrypt_blocks(&mut last);
+    hmac.update(&last);
+    let mac = hmac.finalize().into_bytes();
+    last.extend_from_slice(&mac[..MAC_SIZE]);
+    enc_hash.update(&last);
+    writer.write_all(&last)?;
+    writer.flush()?;
+
+    Ok(EncryptedMediaInfo {
+        media_key,
+        file_sha256: plain_hash.finalize().into(),
+        file_enc_sha256: enc_hash.finalize().into(),
+        file_length,
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -72,4 +165,38 @@ mod tests {
             .expect("media operation should succeed");
         assert_eq!(plain, msg);
     }
+
+    #[test]
+    fn stream_matches_buffered_encryption() {
+        // Spans several chunks and ends off a block boundary.
+        let msg: Vec<u8> = (0..150_003u32).map(|i| (i % 251) as u8).collect();
+        let mut upload = Vec::new();
+        let info = encrypt_media_stream(msg.as_slice(), &mut upload, MediaType::Video)
+            .expect("media operation should succeed");
+        assert_eq!(info.file_length, msg.len() as u64);
+
+        let plain =
+            DownloadUtils::decrypt_stream(upload.as_slice(), &info.media_key, MediaType::Video)
+                .expect("media operation should succeed");
+        assert_eq!(plain, msg);
+
+        let buffered =
+            encrypt_media(&msg, MediaType::Video).expect("media operation should succeed");
+        assert_eq!(info.file_sha256, buffered.file_sha256);
+        let enc_sha256: [u8; 32] = Sha256::digest(&upload).into();
+        assert_eq!(info.file_enc_sha256, enc_sha256);
+    }
+
+    #[test]
+    fn stream_pads_aligned_input_with_a_full_block() {
+        let msg = [7u8; 64 * 1024];
+        let mut upload = Vec::new();
+        let info = encrypt_media_stream(&msg[..], &mut upload, MediaType::Document)
+            .expect("media operation should succeed");
+        assert_eq!(upload.len(), msg.len() + 16 + 10);
+        let plain =
+            DownloadUtils::decrypt_stream(upload.as_slice(), &info.media_key, MediaType::Document)
+                .expect("media operation should succeed");
+        assert_eq!(plain, msg);
+    }
 }